@@ -167,15 +167,46 @@ impl<T: AsRef<[u8]>, C: Config> serde::Serialize for Bytes<T, C> {
 #[cfg(feature = "serde")]
 impl<'de, T: From<Vec<u8>>, C: Config> serde::Deserialize<'de> for Bytes<T, C> {
     fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        use serde::de::Error;
+        use serde::de::{Error, SeqAccess, Visitor};
+
+        // NOTE: Both representations are accepted regardless of the format, since buffering
+        // deserializers, e.g. of flattened fields, always claim to be human-readable.
+        struct BytesVisitor<T, C>(PhantomData<(T, C)>);
+
+        impl<'de, T: From<Vec<u8>>, C: Config> Visitor<'de> for BytesVisitor<T, C> {
+            type Value = Bytes<T, C>;
+
+            fn expecting(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+                f.write_str("base64-encoded string or bytes")
+            }
+
+            fn visit_str<E: Error>(self, v: &str) -> Result<Self::Value, E> {
+                let buf =
+                    base64::decode_config(v, C::CONFIG).map_err(|_| E::custom("invalid base64"))?;
+                Ok(Bytes(buf.into(), PhantomData))
+            }
+
+            fn visit_bytes<E: Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Ok(Bytes(v.to_vec().into(), PhantomData))
+            }
+
+            fn visit_byte_buf<E: Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+                Ok(Bytes(v.into(), PhantomData))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut buf = Vec::with_capacity(seq.size_hint().unwrap_or_default().min(4096));
+                while let Some(b) = seq.next_element()? {
+                    buf.push(b);
+                }
+                Ok(Bytes(buf.into(), PhantomData))
+            }
+        }
 
         if deserializer.is_human_readable() {
-            let b64 = alloc::borrow::Cow::<'de, str>::deserialize(deserializer)?;
-            let buf = base64::decode_config(b64.as_ref(), C::CONFIG)
-                .map_err(|_| D::Error::custom("invalid base64"))?;
-            Ok(Self(buf.into(), PhantomData))
+            deserializer.deserialize_str(BytesVisitor(PhantomData))
         } else {
-            Ok(Self(Vec::deserialize(deserializer)?.into(), PhantomData))
+            deserializer.deserialize_byte_buf(BytesVisitor(PhantomData))
         }
     }
 }
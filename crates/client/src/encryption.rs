@@ -0,0 +1,137 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::Result;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use drawbridge_type::{Encryption, EncryptionAlgorithm};
+
+use anyhow::{anyhow, ensure, Context};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM};
+use ring::rand::{SecureRandom, SystemRandom};
+
+/// Provider of keys contents of entries are encrypted with by the client, e.g. backed by a KMS.
+pub trait KeyProvider {
+    /// Returns the identifier and the value of the key new contents are encrypted with.
+    fn current(&self) -> Result<(String, Vec<u8>)>;
+
+    /// Returns the value of the key identified by `id`.
+    fn key(&self, id: &str) -> Result<Vec<u8>>;
+}
+
+/// [KeyProvider] of a fixed set of keys.
+#[derive(Clone)]
+pub struct StaticKeys {
+    current: String,
+    keys: BTreeMap<String, Vec<u8>>,
+}
+
+impl fmt::Debug for StaticKeys {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StaticKeys")
+            .field("current", &self.current)
+            .field("keys", &self.keys.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl StaticKeys {
+    /// Constructs a provider encrypting new contents with key `value` identified by `id`.
+    pub fn new(id: impl Into<String>, value: impl Into<Vec<u8>>) -> Self {
+        let current = id.into();
+        Self {
+            keys: BTreeMap::from([(current.clone(), value.into())]),
+            current,
+        }
+    }
+
+    /// Adds key `value` identified by `id`, which contents encrypted before, e.g. before a key
+    /// rotation, are decrypted with.
+    pub fn with_key(mut self, id: impl Into<String>, value: impl Into<Vec<u8>>) -> Self {
+        _ = self.keys.insert(id.into(), value.into());
+        self
+    }
+}
+
+impl KeyProvider for StaticKeys {
+    fn current(&self) -> Result<(String, Vec<u8>)> {
+        self.key(&self.current)
+            .map(|value| (self.current.clone(), value))
+    }
+
+    fn key(&self, id: &str) -> Result<Vec<u8>> {
+        self.keys
+            .get(id)
+            .cloned()
+            .ok_or_else(|| anyhow!("unknown encryption key `{id}`"))
+    }
+}
+
+fn cipher(algorithm: EncryptionAlgorithm, key: &[u8]) -> Result<LessSafeKey> {
+    ensure!(
+        key.len() == algorithm.key_len(),
+        "invalid key length, expected {} bytes",
+        algorithm.key_len()
+    );
+    let key = match algorithm {
+        EncryptionAlgorithm::Aes256Gcm => UnboundKey::new(&AES_256_GCM, key),
+    }
+    .map_err(|_| anyhow!("invalid encryption key"))?;
+    Ok(LessSafeKey::new(key))
+}
+
+/// Encrypts `plaintext` with the current key of `keys` and returns the header of the
+/// ciphertext along with the ciphertext.
+pub fn encrypt(keys: &impl KeyProvider, mut plaintext: Vec<u8>) -> Result<(Encryption, Vec<u8>)> {
+    let algorithm = EncryptionAlgorithm::Aes256Gcm;
+    let (id, key) = keys.current().context("failed to get encryption key")?;
+    let mut nonce = [0; 12];
+    SystemRandom::new()
+        .fill(&mut nonce)
+        .map_err(|_| anyhow!("failed to generate nonce"))?;
+    cipher(algorithm, &key)?
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::empty(),
+            &mut plaintext,
+        )
+        .map_err(|_| anyhow!("failed to encrypt contents"))?;
+    Ok((
+        Encryption {
+            algorithm,
+            key: id,
+            nonce: nonce.to_vec().into(),
+        },
+        plaintext,
+    ))
+}
+
+/// Decrypts `ciphertext` described by `encryption` with the respective key of `keys`.
+pub fn decrypt(
+    keys: &impl KeyProvider,
+    encryption: &Encryption,
+    mut ciphertext: Vec<u8>,
+) -> Result<Vec<u8>> {
+    let Encryption {
+        algorithm,
+        ref key,
+        ref nonce,
+    } = *encryption;
+    let value = keys
+        .key(key)
+        .with_context(|| format!("failed to get encryption key `{key}`"))?;
+    let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| {
+        anyhow!(
+            "invalid nonce length, expected {} bytes",
+            algorithm.nonce_len()
+        )
+    })?;
+    let n = cipher(algorithm, &value)?
+        .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+        .map_err(|_| anyhow!("failed to decrypt contents"))?
+        .len();
+    ciphertext.truncate(n);
+    Ok(ciphertext)
+}
@@ -8,12 +8,11 @@ use std::marker::PhantomData;
 use std::str::FromStr;
 
 use drawbridge_type::digest::{Algorithms, ContentDigest};
-use drawbridge_type::Meta;
+use drawbridge_type::{Error, ErrorCode, MediaType, Meta};
 
 use anyhow::{anyhow, bail, ensure, Context};
-use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE, LINK};
 use http::StatusCode;
-use mime::Mime;
 use ureq::serde::{Deserialize, Serialize};
 use ureq::{Request, Response};
 
@@ -28,6 +27,18 @@ where
         .context(format!("failed to parse `{name}` header"))
 }
 
+/// Returns the target of the `next` link of `Link` header value `links`, if any.
+fn next_link(links: &str) -> Option<&str> {
+    links.split(',').find_map(|link| {
+        let (target, params) = link.trim().split_once(';')?;
+        params
+            .split(';')
+            .any(|param| matches!(param.trim(), r#"rel="next""# | "rel=next"))
+            .then(|| target.trim().strip_prefix('<')?.strip_suffix('>'))
+            .flatten()
+    })
+}
+
 #[derive(Clone, Debug)]
 pub struct Entity<'a, C: Scope, E: Scope> {
     client: &'a Client<C>,
@@ -35,14 +46,63 @@ pub struct Entity<'a, C: Scope, E: Scope> {
     phantom: PhantomData<E>,
 }
 
+/// [RFC 9457] problem details returned by the server.
+///
+/// Errors returned by the client wrap problems, which are not known as an [Error], so that
+/// callers may match on their [ErrorCode] using [anyhow::Error::downcast_ref].
+///
+/// [RFC 9457]: https://www.rfc-editor.org/rfc/rfc9457
+#[derive(Clone, Debug, Deserialize)]
+#[serde(crate = "ureq::serde")]
+pub struct Problem {
+    /// Short, human-readable summary of the problem
+    pub title: String,
+    /// Human-readable explanation specific to this occurrence of the problem
+    pub detail: Option<String>,
+    /// Machine-readable problem identifier
+    pub code: ErrorCode,
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.title)?;
+        if let Some(ref detail) = self.detail {
+            write!(f, ": {detail}")?;
+        }
+        write!(f, " ({})", self.code)
+    }
+}
+
+impl std::error::Error for Problem {}
+
+/// Converts `e` into an error, which wraps an [Error] if the server returned problem details
+/// with a known code and a [Problem] otherwise, so that callers may inspect it using
+/// [anyhow::Error::downcast_ref].
 fn parse_ureq_error(e: ureq::Error) -> anyhow::Error {
     match e {
-        ureq::Error::Status(code, msg) => match msg.into_string() {
-            Ok(msg) if !msg.is_empty() => {
+        ureq::Error::Status(code, res) => {
+            let is_problem = res.content_type() == "application/problem+json";
+            let msg = match res.into_string() {
+                Ok(msg) if is_problem => {
+                    if let Ok(e) = serde_json::from_str::<Error>(&msg) {
+                        return anyhow::Error::new(e)
+                            .context(format!("request failed with status code `{code}`"));
+                    }
+                    if let Ok(problem) = serde_json::from_str::<Problem>(&msg) {
+                        return anyhow::Error::new(problem)
+                            .context(format!("request failed with status code `{code}`"));
+                    }
+                    msg
+                }
+                Ok(msg) => msg,
+                Err(_) => String::new(),
+            };
+            if msg.is_empty() {
+                anyhow!("request failed with status code `{code}`")
+            } else {
                 anyhow!(msg).context(format!("request failed with status code `{code}`"))
             }
-            _ => anyhow!("request failed with status code `{code}`"),
-        },
+        }
 
         ureq::Error::Transport(e) => anyhow::Error::new(e).context("transport layer failure"),
     }
@@ -79,7 +139,17 @@ impl<'a, C: Scope, E: Scope> Entity<'a, C, E> {
         }
     }
 
-    pub(super) fn create_request(&self, hash: &ContentDigest, mime: &Mime) -> Result<Request> {
+    /// Returns an error unless the name of the entity satisfies the namespace rules of the
+    /// client.
+    pub(super) fn validate_name(&self) -> Result<()> {
+        let name = self.path.rsplit('/').next().unwrap_or_default();
+        self.client
+            .namespace_rules
+            .validate(name)
+            .with_context(|| format!("invalid name `{name}`"))
+    }
+
+    pub(super) fn create_request(&self, hash: &ContentDigest, mime: &MediaType) -> Result<Request> {
         let token = self.client.token.as_ref().ok_or_else(|| {
             anyhow!("endpoint requires authorization, but no token was configured")
         })?;
@@ -93,8 +163,7 @@ impl<'a, C: Scope, E: Scope> Entity<'a, C, E> {
             .set(CONTENT_TYPE.as_str(), mime.as_ref()))
     }
 
-    pub(super) fn create_bytes(&self, mime: &Mime, data: impl AsRef<[u8]>) -> Result<bool> {
-        let data = data.as_ref();
+    fn send_bytes(&self, mime: &MediaType, data: &[u8]) -> Result<Response> {
         let (n, hash) = Algorithms::default()
             .read_sync(data)
             .context("failed to compute content digest")?;
@@ -103,10 +172,13 @@ impl<'a, C: Scope, E: Scope> Entity<'a, C, E> {
             "invalid amount of bytes read, expected {}, read {n}",
             data.len(),
         );
-        let res = self
-            .create_request(&hash, mime)?
+        self.create_request(&hash, mime)?
             .send_bytes(data)
-            .map_err(parse_ureq_error)?;
+            .map_err(parse_ureq_error)
+    }
+
+    pub(super) fn create_bytes(&self, mime: &MediaType, data: impl AsRef<[u8]>) -> Result<bool> {
+        let res = self.send_bytes(mime, data.as_ref())?;
         match StatusCode::from_u16(res.status()) {
             Ok(StatusCode::CREATED) => Ok(true),
             Ok(StatusCode::OK) => Ok(false),
@@ -114,7 +186,38 @@ impl<'a, C: Scope, E: Scope> Entity<'a, C, E> {
         }
     }
 
-    pub(super) fn create_json(&self, mime: &Mime, val: &impl Serialize) -> Result<bool> {
+    /// Creates the entity like [Self::create_bytes] and returns the decoded JSON response.
+    #[allow(single_use_lifetimes)]
+    pub(super) fn create_bytes_json<T>(&self, mime: &MediaType, data: impl AsRef<[u8]>) -> Result<T>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        let res = self.send_bytes(mime, data.as_ref())?;
+        serde_json::from_reader(res.into_reader()).context("failed to decode JSON")
+    }
+
+    /// Creates the entity like [Self::create_bytes], but returns the decoded JSON response, if
+    /// the request was only accepted, e.g. since the entity awaits approval.
+    #[allow(single_use_lifetimes)]
+    pub(super) fn create_bytes_accepted<T>(
+        &self,
+        mime: &MediaType,
+        data: impl AsRef<[u8]>,
+    ) -> Result<Option<T>>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        let res = self.send_bytes(mime, data.as_ref())?;
+        match StatusCode::from_u16(res.status()) {
+            Ok(StatusCode::CREATED | StatusCode::OK) => Ok(None),
+            Ok(StatusCode::ACCEPTED) => serde_json::from_reader(res.into_reader())
+                .context("failed to decode JSON")
+                .map(Some),
+            _ => bail!("unexpected status code: {}", res.status()),
+        }
+    }
+
+    pub(super) fn create_json(&self, mime: &MediaType, val: &impl Serialize) -> Result<bool> {
         let buf = serde_json::to_vec(val).context("failed to encode value to JSON")?;
         self.create_bytes(mime, buf)
     }
@@ -136,9 +239,86 @@ impl<'a, C: Scope, E: Scope> Entity<'a, C, E> {
         }
     }
 
-    pub fn get(&self, limit: u64) -> Result<(Meta, impl Read)> {
+    /// Returns a `method` request to the entity, which is authorized if a token is configured.
+    fn request(&self, method: &str) -> Result<Request> {
+        let url = self.client.url(&self.path)?;
+        let req = self.client.inner.request(method, url.as_str());
+        Ok(match self.client.token {
+            Some(ref token) => req.set("Authorization", &format!("Bearer {token}")),
+            None => req,
+        })
+    }
+
+    /// Decodes the JSON body of `res` to a `method` request.
+    #[allow(single_use_lifetimes)]
+    fn decode_json<T>(method: &str, res: Result<Response, ureq::Error>) -> Result<T>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        let res = res
+            .map_err(parse_ureq_error)
+            .with_context(|| format!("{method} request failed"))?;
+        match StatusCode::from_u16(res.status()) {
+            Ok(StatusCode::OK) => {
+                serde_json::from_reader(res.into_reader()).context("failed to decode JSON")
+            }
+            _ => bail!("unexpected status code: {}", res.status()),
+        }
+    }
+
+    /// Sends a POST request with `val` encoded as JSON and returns the decoded JSON response.
+    #[allow(single_use_lifetimes)]
+    pub(super) fn post_json<T>(&self, val: &impl Serialize) -> Result<T>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        let buf = serde_json::to_vec(val).context("failed to encode value to JSON")?;
+        let res = self
+            .request("POST")?
+            .set(CONTENT_TYPE.as_str(), "application/json")
+            .send_bytes(&buf);
+        Self::decode_json("POST", res)
+    }
+
+    /// Sends a `method` request without a body, e.g. `DELETE`, and returns the decoded JSON
+    /// response.
+    #[allow(single_use_lifetimes)]
+    pub(super) fn call_json<T>(&self, method: &str) -> Result<T>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        let res = self.request(method)?.call();
+        Self::decode_json(method, res)
+    }
+
+    /// Returns metadata of the entity without fetching its contents, or `None` if the entity
+    /// does not exist.
+    pub fn head(&self) -> Result<Option<Meta>> {
         let url = self.client.url(&self.path)?;
-        let mut req = self.client.inner.get(url.as_str());
+        let mut req = self.client.inner.head(url.as_str());
+        if let Some(ref token) = self.client.token {
+            req = req.set("Authorization", &format!("Bearer {token}"))
+        }
+        let res = match req.set("Accept-Encoding", "").call() {
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            res => res
+                .map_err(parse_ureq_error)
+                .context("HEAD request failed")?,
+        };
+        match StatusCode::from_u16(res.status()) {
+            Ok(StatusCode::OK) => Ok(Some(Meta {
+                hash: parse_header(&res, "Content-Digest")?,
+                size: parse_header(&res, CONTENT_LENGTH.as_str())?,
+                mime: parse_header(&res, CONTENT_TYPE.as_str())?,
+            })),
+            _ => bail!("unexpected status code: {}", res.status()),
+        }
+    }
+
+    /// Sends a GET request to `url` and returns the metadata of the response, the target of its
+    /// `next` link, if any, and the verified response body.
+    fn get_url(&self, url: &str, limit: u64) -> Result<(Meta, Option<String>, impl Read)> {
+        let mut req = self.client.inner.get(url);
         if let Some(ref token) = self.client.token {
             req = req.set("Authorization", &format!("Bearer {token}"))
         }
@@ -155,6 +335,10 @@ impl<'a, C: Scope, E: Scope> Entity<'a, C, E> {
             size <= limit,
             "response size of `{size}` exceeds the limit of `{limit}`"
         );
+        let next = res
+            .header(LINK.as_str())
+            .and_then(next_link)
+            .map(Into::into);
         match StatusCode::from_u16(res.status()) {
             Ok(StatusCode::OK) => Ok((
                 Meta {
@@ -162,12 +346,43 @@ impl<'a, C: Scope, E: Scope> Entity<'a, C, E> {
                     size,
                     mime,
                 },
+                next,
                 hash.verifier(res.into_reader().take(size)),
             )),
             _ => bail!("unexpected status code: {}", res.status()),
         }
     }
 
+    pub fn get(&self, limit: u64) -> Result<(Meta, impl Read)> {
+        let url = self.client.url(&self.path)?;
+        let (meta, _, rdr) = self.get_url(url.as_str(), limit)?;
+        Ok((meta, rdr))
+    }
+
+    /// Returns all items of the JSON array listing at the entity, following `next` links of
+    /// paginated responses of at most `limit` bytes each.
+    #[allow(single_use_lifetimes)]
+    pub fn get_json_pages<T>(&self, limit: u64) -> Result<Vec<T>>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        let mut url = self.client.url(&self.path)?;
+        let mut items = vec![];
+        loop {
+            let (_, next, rdr) = self.get_url(url.as_str(), limit)?;
+            let page: Vec<T> = serde_json::from_reader(rdr).context("failed to decode JSON")?;
+            items.extend(page);
+            match next {
+                Some(next) => {
+                    url = url
+                        .join(&next)
+                        .with_context(|| format!("invalid `next` link `{next}`"))?
+                }
+                None => return Ok(items),
+            }
+        }
+    }
+
     pub fn get_to(&self, limit: u64, dst: &mut impl Write) -> Result<Meta> {
         let (meta @ Meta { size, .. }, mut rdr) = self.get(limit)?;
         let n = copy(&mut rdr, dst)?;
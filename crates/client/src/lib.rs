@@ -28,12 +28,14 @@
     variant_size_differences
 )]
 
+mod encryption;
 mod entity;
 mod repo;
 mod tag;
 mod tree;
 mod user;
 
+pub use encryption::*;
 pub use entity::*;
 pub use repo::*;
 pub use tag::*;
@@ -50,7 +52,12 @@ pub use url::Url;
 use std::marker::PhantomData;
 use std::sync::Arc;
 
-use drawbridge_type::{RepositoryContext, TagContext, TreeContext, UserContext};
+use drawbridge_type::{
+    Manifest, ManifestRequest, NamespaceRules, Quota, Reference, ReferenceTarget,
+    RepositoryContext, SearchResults, TagContext, TreeContext, UserContext,
+};
+
+use anyhow::{bail, ensure};
 
 use rustls::{Certificate, OwnedTrustAnchor, PrivateKey, RootCertStore};
 
@@ -104,6 +111,7 @@ pub struct Client<S = scope::Root> {
     inner: ureq::Agent,
     root: Url,
     token: Option<String>,
+    namespace_rules: NamespaceRules,
     scope: PhantomData<S>,
 }
 
@@ -149,6 +157,73 @@ impl Client<scope::Root> {
     pub fn tree<'a>(&'a self, TreeContext { tag, path }: &'a TreeContext) -> Node<'_, scope::Root> {
         self.tag(tag).path(path)
     }
+
+    /// Returns the first page of at most `limit` public repositories matching search query
+    /// `query` along with the total number of matches.
+    pub fn search(&self, query: &str, limit: usize) -> Result<SearchResults> {
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("q", query)
+            .append_pair("limit", &limit.to_string())
+            .finish();
+        Entity::new(self)
+            .child::<scope::Unknown>(&format!("_search?{query}"))
+            .get_json(u64::MAX)
+            .map(|(_, v)| v)
+    }
+
+    /// Returns the files `req` refers to along with URLs to download them from.
+    pub fn manifest(&self, req: &ManifestRequest) -> Result<Manifest> {
+        Entity::new(self)
+            .child::<scope::Unknown>("_manifest")
+            .post_json(req)
+    }
+
+    /// Returns the storage and request rate quotas applying to the client.
+    pub fn quota(&self) -> Result<Quota> {
+        Entity::new(self)
+            .child::<scope::Unknown>("_quota")
+            .get_json(u64::MAX)
+            .map(|(_, v)| v)
+    }
+
+    /// Resolves the tag `reference` refers to either by name or by the digest of its entry,
+    /// which requires looking up the tags of the repository.
+    pub fn resolve(&self, reference: &Reference) -> Result<TagContext> {
+        if let Some(ref host) = reference.host {
+            let root = match (self.root.host_str(), self.root.port()) {
+                (Some(name), Some(port)) => format!("{name}:{port}"),
+                (Some(name), None) => name.into(),
+                (None, _) => String::new(),
+            };
+            ensure!(
+                host.eq_ignore_ascii_case(&root),
+                "reference `{reference}` refers to another host than `{root}`"
+            );
+        }
+        match reference.target {
+            Some(ReferenceTarget::Tag(ref name)) => Ok(TagContext {
+                repository: reference.repository.clone(),
+                name: name.clone(),
+            }),
+            Some(ref target @ ReferenceTarget::Digest(..)) => {
+                let repo = self.repository(&reference.repository);
+                for name in repo.tags()? {
+                    if repo
+                        .tag(&name)
+                        .head()?
+                        .is_some_and(|meta| target.matches(&meta.hash))
+                    {
+                        return Ok(TagContext {
+                            repository: reference.repository.clone(),
+                            name,
+                        });
+                    }
+                }
+                bail!("no tag of `{}` matches `{reference}`", reference.repository)
+            }
+            None => bail!("reference `{reference}` does not refer to a tag"),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -158,6 +233,7 @@ pub struct ClientBuilder<S: Scope = scope::Root> {
     roots: Option<RootCertStore>,
     token: Option<String>,
     user_agent: Option<String>,
+    namespace_rules: NamespaceRules,
     scope: PhantomData<S>,
 }
 
@@ -169,6 +245,7 @@ impl<S: Scope> ClientBuilder<S> {
             roots: None,
             token: None,
             user_agent: None,
+            namespace_rules: Default::default(),
             scope: PhantomData,
         }
     }
@@ -201,6 +278,15 @@ impl<S: Scope> ClientBuilder<S> {
         }
     }
 
+    /// Sets the rules names of created users and repositories must satisfy, which should
+    /// match the ones enforced by the server.
+    pub fn namespace_rules(self, namespace_rules: NamespaceRules) -> Self {
+        Self {
+            namespace_rules,
+            ..self
+        }
+    }
+
     pub fn build_scoped(self) -> Result<Client<S>> {
         let tls = rustls::ClientConfig::builder()
             .with_safe_defaults()
@@ -236,6 +322,7 @@ impl<S: Scope> ClientBuilder<S> {
                 .build(),
             root: self.url,
             token: self.token,
+            namespace_rules: self.namespace_rules,
             scope: self.scope,
         })
     }
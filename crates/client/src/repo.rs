@@ -3,11 +3,17 @@
 
 use super::{scope, Entity, Result, Scope, Tag};
 
+use std::collections::BTreeMap;
+use std::io::Write;
 use std::ops::Deref;
 
-use drawbridge_type::{RepositoryConfig, RepositoryName, TagName};
-
-use mime::APPLICATION_JSON;
+use drawbridge_jose::jwk::{Jwk, JwkSet};
+use drawbridge_type::digest::AlgorithmRules;
+use drawbridge_type::{
+    AnnotationFilter, MediaType, Meta, PendingChange, RepositoryConfig, RepositoryName,
+    SnapshotImport, TagName, TagVerification, Timestamps, TrashedEntity, Version, VersionReq,
+    WebhookDelivery,
+};
 
 #[derive(Clone, Debug)]
 pub struct Repository<'a, S: Scope>(Entity<'a, S, scope::Repository>);
@@ -26,7 +32,8 @@ impl<'a, S: Scope> Repository<'a, S> {
     }
 
     pub fn create(&self, conf: &RepositoryConfig) -> Result<bool> {
-        self.0.create_json(&APPLICATION_JSON, conf)
+        self.0.validate_name()?;
+        self.0.create_json(&MediaType::JSON, conf)
     }
 
     pub fn get(&self) -> Result<RepositoryConfig> {
@@ -34,13 +41,150 @@ impl<'a, S: Scope> Repository<'a, S> {
         self.0.get_json(u64::MAX).map(|(_, v)| v)
     }
 
+    /// Deletes the repository along with all of its tags by moving it into the trash of its
+    /// owner, from which it may be restored until it expires.
+    pub fn delete(&self) -> Result<TrashedEntity> {
+        self.0.call_json("DELETE")
+    }
+
+    /// Returns the rules on hashing algorithms of content digests of uploads to the repository.
+    pub fn algorithms(&self) -> Result<AlgorithmRules> {
+        self.0
+            .child::<scope::Unknown>("_algorithms")
+            .get_json(u64::MAX)
+            .map(|(_, v)| v)
+    }
+
+    /// Returns public keys of the server-managed signing keys of the repository ordered from
+    /// the current to the oldest key.
+    pub fn keys(&self) -> Result<JwkSet> {
+        self.0
+            .child::<scope::Unknown>("_keys")
+            .get_json(u64::MAX)
+            .map(|(_, v)| v)
+    }
+
+    /// Generates a server-managed signing key of the repository, which replaces the current
+    /// key for countersigning tags, and returns its public key.
+    pub fn rotate_key(&self) -> Result<Jwk> {
+        self.0
+            .child::<scope::Unknown>("_keys")
+            .create_bytes_json(&MediaType::TEXT, b"")
+    }
+
+    /// Imports PEM-encoded PKCS#8 ECDSA P-256 private key `pem` as the current server-managed
+    /// signing key of the repository and returns its public key.
+    pub fn import_key(&self, pem: &str) -> Result<Jwk> {
+        self.0
+            .child::<scope::Unknown>("_keys")
+            .create_bytes_json(&MediaType::TEXT, pem)
+    }
+
+    /// Returns pending changes of protected tags of the repository awaiting approval.
+    pub fn pending_changes(&self) -> Result<Vec<PendingChange>> {
+        self.0
+            .child::<scope::Unknown>("_review")
+            .get_json_pages(u64::MAX)
+    }
+
+    /// Approves pending change `id` of a protected tag of the repository, which publishes the
+    /// tag, and returns `false` if the tag was published before.
+    pub fn approve(&self, id: &str) -> Result<bool> {
+        self.0
+            .child::<scope::Unknown>(&format!("_review/{id}"))
+            .create_bytes(&MediaType::TEXT, b"")
+    }
+
     pub fn tags(&self) -> Result<Vec<TagName>> {
         self.0
             .child::<scope::Unknown>("_tag")
+            .get_json_pages(u64::MAX)
+    }
+
+    /// Returns names of tags annotated as required by all of `filters`.
+    pub fn tags_annotated(&self, filters: &[AnnotationFilter]) -> Result<Vec<TagName>> {
+        let query = filters
+            .iter()
+            .fold(
+                url::form_urlencoded::Serializer::new(String::new()),
+                |mut query, filter| {
+                    _ = query.append_pair("annotation", &filter.to_string());
+                    query
+                },
+            )
+            .finish();
+        self.0
+            .child::<scope::Unknown>(&format!("_tag?{query}"))
+            .get_json_pages(u64::MAX)
+    }
+
+    /// Returns names of tags licensed under an SPDX license expression referring to license `id`.
+    pub fn tags_licensed(&self, id: &str) -> Result<Vec<TagName>> {
+        let query = url::form_urlencoded::Serializer::new(String::new())
+            .append_pair("license", id)
+            .finish();
+        self.0
+            .child::<scope::Unknown>(&format!("_tag?{query}"))
+            .get_json_pages(u64::MAX)
+    }
+
+    /// Returns the name of the tag of highest version precedence matching `req`, if any.
+    pub fn resolve(&self, req: &VersionReq) -> Result<Option<TagName>> {
+        let tags = self.tags()?;
+        Ok(Version::resolve(req, tags.iter().map(Deref::deref))
+            .cloned()
+            .map(TagName::from))
+    }
+
+    /// Returns download counts of tags of the repository keyed by tag name.
+    pub fn tag_downloads(&self) -> Result<BTreeMap<String, u64>> {
+        self.0
+            .child::<scope::Unknown>("_tag?downloads")
+            .get_json(u64::MAX)
+            .map(|(_, v)| v)
+    }
+
+    /// Returns creation and last modification times of tags of the repository keyed by tag name.
+    pub fn tag_timestamps(&self) -> Result<BTreeMap<String, Timestamps>> {
+        self.0
+            .child::<scope::Unknown>("_tag?timestamps")
+            .get_json(u64::MAX)
+            .map(|(_, v)| v)
+    }
+
+    /// Returns verification status of signatures of tags of the repository keyed by tag name.
+    pub fn tag_verifications(&self) -> Result<BTreeMap<String, TagVerification>> {
+        self.0
+            .child::<scope::Unknown>("_signature")
             .get_json(u64::MAX)
             .map(|(_, v)| v)
     }
 
+    pub fn webhook_deliveries(&self) -> Result<Vec<WebhookDelivery>> {
+        self.0
+            .child::<scope::Unknown>("_webhook/deliveries")
+            .get_json_pages(u64::MAX)
+    }
+
+    /// Exports the repository as a snapshot archive.
+    pub fn export(&self, dst: &mut impl Write) -> Result<Meta> {
+        // TODO: Use a reasonable byte limit
+        self.0
+            .child::<scope::Unknown>("_export")
+            .get_to(u64::MAX, dst)
+    }
+
+    /// Imports snapshot archive `data` exported by [Self::export] into the repository, creating
+    /// it unless it exists.
+    pub fn import(&self, data: impl AsRef<[u8]>) -> Result<SnapshotImport> {
+        let mime = "application/x-tar"
+            .parse()
+            .expect("failed to parse archive media type");
+        self.0
+            .child::<scope::Unknown>("_import")
+            .create_bytes_json(&mime, data)
+    }
+
     pub fn tag(&self, name: &TagName) -> Tag<'a, S> {
         Tag::new(self.child("_tag"), name)
     }
@@ -1,19 +1,42 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::{scope, Entity, Node, Result, Scope};
+use super::{decrypt, encrypt, scope, Context, Entity, KeyProvider, Node, Result, Scope};
 
 use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Write};
 use std::ops::Deref;
 use std::path::Path;
 
-use drawbridge_jose::jws::Jws;
-use drawbridge_jose::MediaTyped;
+use drawbridge_type::digest::Algorithms;
 use drawbridge_type::TreeContent::{Directory, File};
-use drawbridge_type::{TagEntry, TagName, Tree, TreeEntry, TreePath};
+use drawbridge_type::{
+    MediaType, Meta, PendingChange, SbomFormat, SignatureBundle, TagAlias, TagAttestation,
+    TagEntry, TagName, TagSignatures, TagStats, TrashedEntity, Tree, TreeDiff, TreeDirectory,
+    TreeEntry, TreePath, VulnerabilityReport,
+};
 
+use anyhow::{anyhow, ensure};
 use ureq::serde::Serialize;
 
+/// Contents of a file node of a tree, which may be uploaded.
+trait Upload {
+    fn upload<S: Scope>(&self, node: &Node<'_, S>, meta: &Meta) -> Result<bool>;
+}
+
+impl Upload for fs::File {
+    fn upload<S: Scope>(&self, node: &Node<'_, S>, meta: &Meta) -> Result<bool> {
+        node.create_from(meta, self)
+    }
+}
+
+impl Upload for Vec<u8> {
+    fn upload<S: Scope>(&self, node: &Node<'_, S>, meta: &Meta) -> Result<bool> {
+        node.create_from(meta, self.as_slice())
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Tag<'a, S: Scope>(Entity<'a, S, scope::Tag>);
 
@@ -31,24 +54,41 @@ impl<'a, S: Scope> Tag<'a, S> {
     }
 
     pub fn create(&self, entry: &TagEntry<impl Serialize>) -> Result<bool> {
-        let mime = match entry {
-            TagEntry::Unsigned(..) => TreeEntry::<()>::TYPE,
-            TagEntry::Signed(..) => Jws::TYPE,
-        }
-        .parse()
-        .expect("failed to parse tag entry media type");
-        self.0.create_json(&mime, entry)
+        self.0.create_json(&entry.media_type(), entry)
     }
 
-    // TODO: Support signed tags
-    pub fn create_from_path_unsigned(
+    /// Creates the tag like [Self::create], but returns the pending change awaiting approval
+    /// instead of failing, if the tag is protected by the review policy of the repository.
+    pub fn create_reviewed(
         &self,
-        path: impl AsRef<Path>,
-    ) -> Result<(bool, BTreeMap<TreePath, bool>)> {
-        let tree = Tree::from_path_sync(path)?;
-        let tag_created = self.create(&TagEntry::Unsigned(tree.root()))?;
-        let tree_created = tree
-            .into_iter()
+        entry: &TagEntry<impl Serialize>,
+    ) -> Result<Option<PendingChange>> {
+        let buf = serde_json::to_vec(entry).context("failed to encode tag entry")?;
+        self.0.create_bytes_accepted(&entry.media_type(), buf)
+    }
+
+    /// Creates the tag as an alias of tag `target` of the same repository.
+    pub fn create_alias(&self, target: &TagName) -> Result<bool> {
+        self.create(&TagEntry::<()>::Alias(TagAlias {
+            target: target.clone(),
+        }))
+    }
+
+    /// Creates a tag with a JWS of the encoded `entry` carrying the signatures returned by `sign`,
+    /// which is passed the encoded entry.
+    pub fn create_signed(
+        &self,
+        entry: &impl Serialize,
+        sign: impl FnOnce(&[u8]) -> Result<SignatureBundle>,
+    ) -> Result<bool> {
+        let payload = serde_json::to_vec(entry).context("failed to encode tag entry")?;
+        let jws = sign(&payload)?.to_jws(Some(&payload))?;
+        self.create(&TagEntry::<()>::Signed(jws))
+    }
+
+    /// Uploads nodes of `tree`, skipping the ones already uploaded.
+    fn create_tree(&self, tree: &Tree<impl Upload>) -> Result<BTreeMap<TreePath, bool>> {
+        tree.iter()
             .map(
                 |(
                     path,
@@ -58,22 +98,266 @@ impl<'a, S: Scope> Tag<'a, S> {
                         ..
                     },
                 )| {
-                    let node = Node::new(self.child("tree"), &path);
+                    let node = Node::new(self.child("tree"), path);
+                    // Skip nodes, which were already uploaded, e.g. by an interrupted push.
+                    if node
+                        .head()?
+                        .is_some_and(|existing| existing.hash == meta.hash)
+                    {
+                        return Ok((path.clone(), false));
+                    }
                     let created = match content {
-                        File(file) => node.create_from(meta, file)?,
+                        File(file) => file.upload(&node, meta)?,
                         Directory(buf) => node.create_from(meta, buf.as_slice())?,
                     };
-                    Ok((path, created))
+                    Ok((path.clone(), created))
                 },
             )
-            .collect::<Result<_>>()?;
+            .collect()
+    }
+
+    // TODO: Support signed tags
+    pub fn create_from_path_unsigned(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<(bool, BTreeMap<TreePath, bool>)> {
+        let tree = Tree::from_path_sync(path)?;
+        let tag_created = self.create(&TagEntry::Unsigned(tree.root()))?;
+        let tree_created = self.create_tree(&tree)?;
+        Ok((tag_created, tree_created))
+    }
+
+    /// Uploads the tree at `path` and then publishes it along with the tag, so that the tag
+    /// and its tree become visible at once.
+    // TODO: Support signed tags
+    pub fn publish_from_path_unsigned(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<(bool, BTreeMap<TreePath, bool>)> {
+        let tree = Tree::from_path_sync(path)?;
+        let tree_created = self.create_tree(&tree)?;
+        let tag_created = self.create(&TagEntry::Unsigned(tree.root()))?;
+        Ok((tag_created, tree_created))
+    }
+
+    /// Like [Self::publish_from_path_unsigned], but signs the tag entry using `sign`, see
+    /// [Self::create_signed].
+    pub fn publish_from_path_signed(
+        &self,
+        path: impl AsRef<Path>,
+        sign: impl FnOnce(&[u8]) -> Result<SignatureBundle>,
+    ) -> Result<(bool, BTreeMap<TreePath, bool>)> {
+        let tree = Tree::from_path_sync(path)?;
+        let tree_created = self.create_tree(&tree)?;
+        let tag_created = self.create_signed(tree.root(), sign)?;
+        Ok((tag_created, tree_created))
+    }
+
+    /// Like [Self::publish_from_path_unsigned], but encrypts contents of all files with the
+    /// current key of `keys` before uploading them, so that the server never sees them.
+    ///
+    /// Digests of the files are computed over the ciphertext and directories record the
+    /// [Encryption](drawbridge_type::Encryption) header of each file.
+    pub fn publish_from_path_encrypted(
+        &self,
+        path: impl AsRef<Path>,
+        keys: &impl KeyProvider,
+    ) -> Result<(bool, BTreeMap<TreePath, bool>)> {
+        let tree = Tree::from_path_sync(path)?.map_files(|path, mut entry| {
+            let mut plaintext = Vec::with_capacity(entry.meta.size as _);
+            _ = entry.content.read_to_end(&mut plaintext)?;
+            let (encryption, ciphertext) = encrypt(keys, plaintext)
+                .map_err(|e| std::io::Error::other(format!("failed to encrypt `{path}`: {e:#}")))?;
+            let (size, hash) = Algorithms::default().read_sync(ciphertext.as_slice())?;
+            Ok(TreeEntry {
+                meta: Meta {
+                    hash,
+                    size,
+                    ..entry.meta
+                },
+                encryption: Some(encryption),
+                content: ciphertext,
+                annotations: entry.annotations,
+                license: entry.license,
+                provenance: entry.provenance,
+                publication: entry.publication,
+                custom: entry.custom,
+            })
+        })?;
+        let tree_created = self.create_tree(&tree)?;
+        let tag_created = self.create(&TagEntry::Unsigned(tree.root()))?;
         Ok((tag_created, tree_created))
     }
 
+    /// Returns contents of the file at `path` in the tree of the tag, which are decrypted
+    /// with the respective key of `keys`, if they are encrypted.
+    ///
+    /// The digests of the contents downloaded must match those recorded in the parent
+    /// directory, i.e. of the ciphertext.
+    pub fn get_decrypted(&self, path: &TreePath, keys: &impl KeyProvider) -> Result<Vec<u8>> {
+        let (parent, name) = match (path.parent(), path.name()) {
+            (Some(parent), Some(name)) => (parent, name),
+            _ => return Err(anyhow!("`{path}` is not a file")),
+        };
+        // TODO: Use a reasonable byte limit
+        let (_, dir) = self
+            .path(&parent)
+            .get_json::<TreeDirectory>(u64::MAX)
+            .with_context(|| format!("failed to get directory `{parent}`"))?;
+        let entry = dir
+            .get(name)
+            .with_context(|| format!("`{path}` not found in directory `{parent}`"))?;
+        // TODO: Use a reasonable byte limit
+        let (meta, buf) = self.path(path).get_bytes(u64::MAX)?;
+        ensure!(
+            meta.hash.intersection(&entry.meta.hash).algorithms() == entry.meta.hash.algorithms(),
+            "digests of `{path}` do not match its directory entry"
+        );
+        match entry.encryption {
+            Some(ref encryption) => decrypt(keys, encryption, buf)
+                .with_context(|| format!("failed to decrypt `{path}`")),
+            None => Ok(buf),
+        }
+    }
+
+    /// Returns metadata of all nodes of the tree of the tag keyed by path.
+    pub fn tree_metas(&self) -> Result<BTreeMap<TreePath, Meta>> {
+        let root = self
+            .path(&TreePath::ROOT)
+            .head()?
+            .context("tree of the tag not found")?;
+        let mut dirs = vec![];
+        if root.mime.is(MediaType::DIRECTORY.essence()) {
+            dirs.push(TreePath::ROOT);
+        }
+        let mut metas = BTreeMap::from([(TreePath::ROOT, root)]);
+        while let Some(dir) = dirs.pop() {
+            // TODO: Use a reasonable byte limit
+            let (_, entries) = self
+                .path(&dir)
+                .get_json::<TreeDirectory>(u64::MAX)
+                .with_context(|| format!("failed to get directory `{dir}`"))?;
+            for (name, entry) in entries.iter() {
+                let path = dir.join(name.clone());
+                if entry.meta.mime.is(MediaType::DIRECTORY.essence()) {
+                    dirs.push(path.clone());
+                }
+                _ = metas.insert(path, entry.meta.clone());
+            }
+        }
+        Ok(metas)
+    }
+
+    /// Compares the tree at local `path` to the tree of the tag by content digest, e.g. to
+    /// only transfer files, which differ, when syncing either of them to the other.
+    pub fn diff_path(&self, path: impl AsRef<Path>) -> Result<TreeDiff> {
+        let local = Tree::from_path_sync(path)?
+            .iter()
+            .map(|(path, entry)| (path.clone(), entry.meta.clone()))
+            .collect();
+        let remote = self.tree_metas()?;
+        Ok(TreeDiff::new(&local, &remote))
+    }
+
     pub fn get(&self) -> Result<TagEntry> {
-        // TODO: Validate MIME type
         // TODO: Use a reasonable byte limit
-        self.0.get_json(u64::MAX).map(|(_, v)| v)
+        let (meta, buf) = self.0.get_bytes(u64::MAX)?;
+        TagEntry::decode(meta.mime.essence(), &buf)
+    }
+
+    /// Deletes the tag by moving it into the trash of the owner of the repository, from which
+    /// it may be restored until it expires.
+    pub fn delete(&self) -> Result<TrashedEntity> {
+        self.0.call_json("DELETE")
+    }
+
+    /// Attaches a Base64-encoded signature of the tag entry, as produced by `cosign sign-blob`.
+    pub fn sign(&self, signature: &str) -> Result<bool> {
+        self.child::<scope::Unknown>("signatures")
+            .create_bytes(&MediaType::TEXT, signature.trim())
+    }
+
+    pub fn signatures(&self) -> Result<TagSignatures> {
+        // TODO: Use a reasonable byte limit
+        self.child::<scope::Unknown>("signatures")
+            .get_json(u64::MAX)
+            .map(|(_, v)| v)
+    }
+
+    /// Attaches an in-toto attestation statement to the tag.
+    pub fn attest(&self, attestation: &TagAttestation) -> Result<bool> {
+        let mime = TagAttestation::TYPE
+            .parse()
+            .expect("failed to parse attestation media type");
+        self.child::<scope::Unknown>("attestations")
+            .create_json(&mime, attestation)
+    }
+
+    pub fn attestations(&self) -> Result<Vec<TagAttestation>> {
+        // TODO: Use a reasonable byte limit
+        self.child::<scope::Unknown>("attestations")
+            .get_json(u64::MAX)
+            .map(|(_, v)| v)
+    }
+
+    /// Attaches a vulnerability report to the tag.
+    pub fn attach_vulnerability_report(&self, report: &VulnerabilityReport) -> Result<bool> {
+        let mime = VulnerabilityReport::TYPE
+            .parse()
+            .expect("failed to parse vulnerability report media type");
+        self.child::<scope::Unknown>("vulnerabilities")
+            .create_json(&mime, report)
+    }
+
+    /// Attaches a SARIF log produced by a vulnerability scanner to the tag.
+    pub fn attach_sarif(&self, data: impl AsRef<[u8]>) -> Result<bool> {
+        let mime = VulnerabilityReport::SARIF_TYPE
+            .parse()
+            .expect("failed to parse SARIF media type");
+        self.child::<scope::Unknown>("vulnerabilities")
+            .create_bytes(&mime, data)
+    }
+
+    /// Returns the latest vulnerability report attached to the tag.
+    pub fn vulnerability_report(&self) -> Result<VulnerabilityReport> {
+        // TODO: Use a reasonable byte limit
+        self.child::<scope::Unknown>("vulnerabilities")
+            .get_json(u64::MAX)
+            .map(|(_, v)| v)
+    }
+
+    /// Attaches an SBOM document in `format` to the tag.
+    pub fn create_sbom(&self, format: SbomFormat, data: impl AsRef<[u8]>) -> Result<bool> {
+        let mime = format
+            .media_type()
+            .parse()
+            .expect("failed to parse SBOM media type");
+        self.child::<scope::Unknown>("sbom")
+            .create_bytes(&mime, data)
+    }
+
+    pub fn sbom(&self) -> Result<(Meta, Vec<u8>)> {
+        // TODO: Use a reasonable byte limit
+        self.child::<scope::Unknown>("sbom").get_bytes(u64::MAX)
+    }
+
+    /// Exports the tree of the tag as a BagIt bag packaged in a tar archive.
+    pub fn export_bagit(&self, dst: &mut impl Write) -> Result<Meta> {
+        // TODO: Use a reasonable byte limit
+        self.child::<scope::Unknown>("bagit").get_to(u64::MAX, dst)
+    }
+
+    /// Exports the tree of the tag as a CARv1 archive importable into IPFS.
+    pub fn export_ipfs(&self, dst: &mut impl Write) -> Result<Meta> {
+        // TODO: Use a reasonable byte limit
+        self.child::<scope::Unknown>("ipfs").get_to(u64::MAX, dst)
+    }
+
+    /// Returns download statistics of the tag.
+    pub fn stats(&self) -> Result<TagStats> {
+        self.child::<scope::Unknown>("stats")
+            .get_json(u64::MAX)
+            .map(|(_, v)| v)
     }
 
     pub fn path(&self, path: &TreePath) -> Node<'a, S> {
@@ -6,9 +6,8 @@ use super::{scope, Entity, Result, Scope};
 use std::io::Read;
 use std::ops::Deref;
 
-use drawbridge_type::{Meta, TreeDirectory, TreeEntry, TreePath};
+use drawbridge_type::{MediaType, Meta, TreeDirectory, TreeEntry, TreePath};
 
-use mime::Mime;
 use ureq::serde::Serialize;
 
 #[derive(Clone, Debug)]
@@ -31,11 +30,11 @@ impl<'a, S: Scope> Node<'a, S> {
         }
     }
 
-    pub fn create_bytes(&self, mime: &Mime, data: impl AsRef<[u8]>) -> Result<bool> {
+    pub fn create_bytes(&self, mime: &MediaType, data: impl AsRef<[u8]>) -> Result<bool> {
         self.0.create_bytes(mime, data)
     }
 
-    pub fn create_json(&self, mime: &Mime, val: &impl Serialize) -> Result<bool> {
+    pub fn create_json(&self, mime: &MediaType, val: &impl Serialize) -> Result<bool> {
         self.0.create_json(mime, val)
     }
 
@@ -44,9 +43,6 @@ impl<'a, S: Scope> Node<'a, S> {
     }
 
     pub fn create_directory<C>(&self, dir: &TreeDirectory<TreeEntry<C>>) -> Result<bool> {
-        let mime = TreeDirectory::<C>::TYPE
-            .parse()
-            .expect("failed to parse tree directory media type");
-        self.create_json(&mime, dir)
+        self.create_json(&MediaType::DIRECTORY, dir)
     }
 }
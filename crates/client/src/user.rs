@@ -5,9 +5,10 @@ use super::{scope, Entity, Repository, Result, Scope};
 
 use std::ops::Deref;
 
-use drawbridge_type::{RepositoryName, UserName, UserRecord};
-
-use mime::APPLICATION_JSON;
+use drawbridge_type::{
+    MediaType, RepositoryName, RetentionPolicy, RetentionReport, TrashedEntity, UserName,
+    UserRecord,
+};
 
 #[derive(Clone, Debug)]
 #[repr(transparent)]
@@ -27,7 +28,8 @@ impl<'a, S: Scope> User<'a, S> {
     }
 
     pub fn create(&self, conf: &UserRecord) -> Result<bool> {
-        self.0.create_json(&APPLICATION_JSON, conf)
+        self.0.validate_name()?;
+        self.0.create_json(&MediaType::JSON, conf)
     }
 
     pub fn get(&self) -> Result<UserRecord> {
@@ -35,6 +37,46 @@ impl<'a, S: Scope> User<'a, S> {
         self.0.get_json(u64::MAX).map(|(_, v)| v)
     }
 
+    /// Returns repositories and tags in the trash of the user ordered by the time they were
+    /// deleted.
+    pub fn trash(&self) -> Result<Vec<TrashedEntity>> {
+        self.0
+            .child::<scope::Unknown>("_trash")
+            .get_json_pages(u64::MAX)
+    }
+
+    /// Restores the repository or tag `id` from the trash of the user.
+    pub fn restore(&self, id: &str) -> Result<TrashedEntity> {
+        self.0
+            .child::<scope::Unknown>(&format!("_trash/{id}"))
+            .call_json("POST")
+    }
+
+    /// Returns the storage retention policy of the user.
+    pub fn retention_policy(&self) -> Result<RetentionPolicy> {
+        self.0
+            .child::<scope::Unknown>("_retention")
+            .get_json(u64::MAX)
+            .map(|(_, v)| v)
+    }
+
+    /// Replaces the storage retention policy of the user.
+    pub fn put_retention_policy(&self, policy: &RetentionPolicy) -> Result<()> {
+        self.0
+            .child::<scope::Unknown>("_retention")
+            .create_json(&MediaType::JSON, policy)
+            .map(|_| ())
+    }
+
+    /// Returns the storage, which the retention policy of the user would reclaim, if it ran
+    /// now, along with the storage reclaimed by its last run.
+    pub fn retention_report(&self) -> Result<RetentionReport> {
+        self.0
+            .child::<scope::Unknown>("_retention/report")
+            .get_json(u64::MAX)
+            .map(|(_, v)| v)
+    }
+
     pub fn repository(&self, name: &RepositoryName) -> Repository<'a, S> {
         Repository::new(self.0.clone(), name)
     }
@@ -6,7 +6,7 @@ use std::ops::{Deref, DerefMut};
 use drawbridge_byte::UrlSafeNoPad;
 
 use serde::de::DeserializeOwned;
-use serde::{ser::Error as _, Deserialize, Serialize};
+use serde::{de::Error as _, ser::Error as _, Deserialize, Serialize};
 
 pub type Bytes<T = Vec<u8>, C = UrlSafeNoPad> = drawbridge_byte::Bytes<T, C>;
 
@@ -35,7 +35,7 @@ impl<T> DerefMut for Json<T> {
 
 impl<T: Serialize> Serialize for Json<T> {
     fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let buf = serde_json::to_vec(self).map_err(|_| S::Error::custom("encoding error"))?;
+        let buf = serde_json::to_vec(&self.0).map_err(|_| S::Error::custom("encoding error"))?;
         Bytes::<_, UrlSafeNoPad>::from(buf).serialize(serializer)
     }
 }
@@ -43,7 +43,7 @@ impl<T: Serialize> Serialize for Json<T> {
 impl<'de, T: DeserializeOwned> Deserialize<'de> for Json<T> {
     fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         let buf = Bytes::<Vec<u8>>::deserialize(deserializer)?;
-        let val = serde_json::from_slice(&buf).unwrap();
+        let val = serde_json::from_slice(&buf).map_err(|_| D::Error::custom("decoding error"))?;
         Ok(Self(val))
     }
 }
@@ -0,0 +1,267 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Access log of requests served, separate from diagnostic logging.
+//!
+//! Each request is logged as a line in [Combined Log Format] or as a JSON object. Lines are
+//! written in background, so that a slow log never delays responses, and are dropped with a
+//! warning if the writer falls behind. Regular files are rotated once they exceed the
+//! configured size, while other files, e.g. named pipes, are only ever appended to.
+//!
+//! [Combined Log Format]: https://httpd.apache.org/docs/current/logs.html#combined
+
+use super::{Clock, PeerAddr};
+
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use async_std::channel::{bounded, Sender, TrySendError};
+use async_std::fs::{self, File, OpenOptions};
+use async_std::sync::Arc;
+use async_std::task::spawn;
+use axum::body::HttpBody;
+use axum::headers::{ContentLength, HeaderMapExt};
+use axum::http::header::{HeaderName, REFERER, USER_AGENT};
+use axum::http::{HeaderMap, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::AsyncWriteExt;
+use serde_json::json;
+use tracing::warn;
+
+/// Maximum number of lines buffered before they are written.
+const CAPACITY: usize = 4096;
+
+/// Format of access log lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AccessLogFormat {
+    /// Combined Log Format of Apache HTTP Server
+    #[default]
+    Combined,
+    /// JSON object per line
+    Json,
+}
+
+impl FromStr for AccessLogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "combined" => Ok(Self::Combined),
+            "json" => Ok(Self::Json),
+            _ => bail!("unknown access log format `{s}`, expected `combined` or `json`"),
+        }
+    }
+}
+
+/// Configuration of the access log.
+#[derive(Clone, Debug)]
+pub struct AccessLogConfig {
+    /// Path of the file to append lines to
+    pub path: PathBuf,
+    pub format: AccessLogFormat,
+    /// Size in bytes, after which a regular file is rotated, 0 disables rotation
+    pub max_size: u64,
+    /// Number of rotated files kept as `<path>.1` to `<path>.<max_files>`
+    pub max_files: usize,
+}
+
+/// Writer of the access log.
+#[derive(Debug)]
+pub(crate) struct AccessLog {
+    format: AccessLogFormat,
+    clock: Arc<dyn Clock>,
+    lines: Sender<String>,
+}
+
+/// File the access log is written to.
+struct Output {
+    config: AccessLogConfig,
+    file: File,
+    /// Whether the file is a regular file, which may be rotated
+    rotate: bool,
+    size: u64,
+}
+
+impl Output {
+    async fn open(config: AccessLogConfig) -> anyhow::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)
+            .await
+            .with_context(|| format!("failed to open `{}`", config.path.display()))?;
+        let meta = file
+            .metadata()
+            .await
+            .with_context(|| format!("failed to stat `{}`", config.path.display()))?;
+        Ok(Self {
+            rotate: meta.is_file() && config.max_size > 0,
+            size: meta.len(),
+            file,
+            config,
+        })
+    }
+
+    /// Returns the path of the `n`th rotated file.
+    fn rotated(&self, n: usize) -> PathBuf {
+        let mut path = self.config.path.clone().into_os_string();
+        path.push(format!(".{n}"));
+        path.into()
+    }
+
+    /// Moves the file to `<path>.1`, shifting previously rotated files, and reopens it.
+    async fn rotate(&mut self) -> anyhow::Result<()> {
+        self.file.flush().await.context("failed to flush")?;
+        if self.config.max_files == 0 {
+            fs::remove_file(&self.config.path)
+                .await
+                .context("failed to remove rotated file")?;
+        } else {
+            for n in (1..self.config.max_files).rev() {
+                let from = self.rotated(n);
+                if from.exists() {
+                    fs::rename(&from, self.rotated(n + 1))
+                        .await
+                        .with_context(|| format!("failed to rename `{}`", from.display()))?;
+                }
+            }
+            fs::rename(&self.config.path, self.rotated(1))
+                .await
+                .context("failed to rename rotated file")?;
+        }
+        *self = Self::open(self.config.clone()).await?;
+        Ok(())
+    }
+
+    async fn write(&mut self, line: &str) -> anyhow::Result<()> {
+        if self.rotate && self.size > 0 && self.size + line.len() as u64 > self.config.max_size {
+            self.rotate().await?;
+        }
+        self.file
+            .write_all(line.as_bytes())
+            .await
+            .context("failed to write")?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+impl AccessLog {
+    /// Opens the access log configured by `config` and spawns its writer.
+    pub(crate) async fn open(
+        config: AccessLogConfig,
+        clock: Arc<dyn Clock>,
+    ) -> anyhow::Result<Self> {
+        let format = config.format;
+        let mut output = Output::open(config).await?;
+        let (tx, rx) = bounded::<String>(CAPACITY);
+        _ = spawn(async move {
+            while let Ok(line) = rx.recv().await {
+                if let Err(e) = output.write(&line).await {
+                    warn!(target: "app::access_log", "failed to write access log: {:?}", e);
+                }
+                if rx.is_empty() {
+                    _ = output.file.flush().await;
+                }
+            }
+        });
+        Ok(Self {
+            format,
+            clock,
+            lines: tx,
+        })
+    }
+
+    fn log(&self, line: String) {
+        if let Err(TrySendError::Full(_)) = self.lines.try_send(line) {
+            warn!(target: "app::access_log", "access log writer lagging behind, dropped line");
+        }
+    }
+}
+
+/// Returns value of header `name` in `headers`.
+fn header(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into())
+}
+
+/// Escapes quotes, backslashes and control characters in `s` for inclusion in a quoted
+/// string of a Combined Log Format line.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_control() => _ = write!(escaped, "\\x{:02x}", c as u32),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Logs requests to the [AccessLog].
+pub(crate) async fn handle<B>(req: Request<B>, next: Next<B>) -> Response {
+    let log = match req.extensions().get::<Arc<AccessLog>>() {
+        Some(log) => Arc::clone(log),
+        None => return next.run(req).await,
+    };
+    let time = DateTime::<Utc>::from(log.clock.now());
+    let addr = req
+        .extensions()
+        .get::<PeerAddr>()
+        .map(|PeerAddr(addr)| addr.ip());
+    let method = req.method().clone();
+    let uri = req.uri().clone();
+    let version = req.version();
+    let referer = header(req.headers(), REFERER);
+    let user_agent = header(req.headers(), USER_AGENT);
+
+    let res = next.run(req).await;
+    let status = res.status().as_u16();
+    let size = res
+        .headers()
+        .typed_get::<ContentLength>()
+        .map(|ContentLength(n)| n)
+        .or_else(|| res.body().size_hint().exact());
+
+    let line = match log.format {
+        AccessLogFormat::Combined => format!(
+            "{} - - [{}] \"{method} {} {version:?}\" {status} {} \"{}\" \"{}\"\n",
+            addr.map(|addr| addr.to_string())
+                .unwrap_or_else(|| "-".into()),
+            time.format("%d/%b/%Y:%H:%M:%S %z"),
+            escape(&uri.to_string()),
+            size.map(|size| size.to_string())
+                .unwrap_or_else(|| "-".into()),
+            referer.as_deref().map(escape).unwrap_or_else(|| "-".into()),
+            user_agent
+                .as_deref()
+                .map(escape)
+                .unwrap_or_else(|| "-".into()),
+        ),
+        AccessLogFormat::Json => format!(
+            "{}\n",
+            json!({
+                "time": time.to_rfc3339_opts(SecondsFormat::Millis, true),
+                "remote_addr": addr.map(|addr| addr.to_string()),
+                "method": method.as_str(),
+                "uri": uri.to_string(),
+                "version": format!("{version:?}"),
+                "status": status,
+                "bytes": size,
+                "referer": referer,
+                "user_agent": user_agent,
+            })
+        ),
+    };
+    log.log(line);
+    res
+}
@@ -0,0 +1,38 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::alerts::Alerts;
+use super::super::{Store, TrustedCertificate};
+use crate::json;
+
+use async_std::sync::Arc;
+use async_std::task::spawn;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Checks consistency of the store and returns the [CheckReport](crate::store::CheckReport).
+///
+/// Integrity failures found are alerted of in background.
+pub async fn check(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref alerts): Extension<Arc<Alerts>>,
+    cert: Option<Extension<TrustedCertificate>>,
+) -> impl IntoResponse {
+    trace!(target: "app::admin::check", "called");
+
+    if cert.is_none() {
+        return Err((StatusCode::FORBIDDEN, "Trusted client certificate required").into_response());
+    }
+    let report = store.check().await.map_err(|e| {
+        debug!(target: "app::admin::check", "failed: {:?}", e);
+        e.into_response()
+    })?;
+    if !report.is_consistent() {
+        let alerts = Arc::clone(alerts);
+        let report = report.clone();
+        _ = spawn(async move { alerts.send_report(&report).await });
+    }
+    json::encode(&report).map_err(IntoResponse::into_response)
+}
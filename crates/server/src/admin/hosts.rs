@@ -0,0 +1,57 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::hosts::VanityHosts;
+use super::super::TrustedCertificate;
+use crate::json::{self, BoundedJson};
+
+use drawbridge_type::UserName;
+
+use std::collections::BTreeMap;
+
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Returns owners vanity hosts are routed to keyed by the hosts.
+pub async fn hosts(
+    Extension(ref hosts): Extension<Arc<VanityHosts>>,
+    cert: Option<Extension<TrustedCertificate>>,
+) -> impl IntoResponse {
+    trace!(target: "app::admin::hosts", "called");
+
+    if cert.is_none() {
+        return Err((StatusCode::FORBIDDEN, "Trusted client certificate required").into_response());
+    }
+    json::encode(&hosts.hosts()).map_err(IntoResponse::into_response)
+}
+
+/// Replaces all vanity hosts by the owners in the body keyed by lowercase host names.
+pub async fn put_hosts(
+    Extension(ref hosts): Extension<Arc<VanityHosts>>,
+    cert: Option<Extension<TrustedCertificate>>,
+    BoundedJson(body): BoundedJson<BTreeMap<String, UserName>>,
+) -> impl IntoResponse {
+    trace!(target: "app::admin::put_hosts", "called");
+
+    if cert.is_none() {
+        return Err((StatusCode::FORBIDDEN, "Trusted client certificate required").into_response());
+    }
+    VanityHosts::validate(&body).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid vanity hosts: {e}"),
+        )
+            .into_response()
+    })?;
+    hosts
+        .replace(body)
+        .await
+        .map(|()| StatusCode::OK)
+        .map_err(|e| {
+            debug!(target: "app::admin::put_hosts", "failed to replace vanity hosts: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })
+}
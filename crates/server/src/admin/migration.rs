@@ -0,0 +1,27 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::migration::Migration;
+use super::super::TrustedCertificate;
+use crate::json;
+
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::trace;
+
+/// Returns the progress of the migration of entities of a source store into the store.
+pub async fn migration(
+    migration: Option<Extension<Arc<Migration>>>,
+    cert: Option<Extension<TrustedCertificate>>,
+) -> impl IntoResponse {
+    trace!(target: "app::admin::migration", "called");
+
+    if cert.is_none() {
+        return Err((StatusCode::FORBIDDEN, "Trusted client certificate required").into_response());
+    }
+    let Extension(migration) = migration
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "No migration configured").into_response())?;
+    json::encode(&migration.status()).map_err(IntoResponse::into_response)
+}
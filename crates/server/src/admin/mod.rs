@@ -0,0 +1,19 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Administrative endpoints under `_admin`, which are only accessible to clients presenting
+//! a certificate signed by the trusted CA.
+
+mod check;
+mod hosts;
+mod migration;
+mod overview;
+mod replica;
+mod replication;
+
+pub use check::*;
+pub use hosts::*;
+pub use migration::*;
+pub use overview::*;
+pub use replica::*;
+pub use replication::*;
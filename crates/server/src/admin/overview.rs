@@ -0,0 +1,202 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::migration::{Migration, MigrationStatus};
+use super::super::recent_errors::{ErrorRecord, RecentErrors};
+use super::super::replica::{Replica, ReplicaStatus};
+use super::super::replication::{PeerStatus, Replicator};
+use super::super::usage::Accounting;
+use super::super::{Clock, GetError, Store, TrustedCertificate};
+use crate::json;
+
+use drawbridge_type::{Usage, UserContext};
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Context};
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use tracing::{debug, trace};
+
+/// Number of namespaces listed by size and by traffic.
+const TOP_NAMESPACES: usize = 10;
+
+/// Bytes stored by the store.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct StorageOverview {
+    /// Storage backend of the store
+    pub backend: &'static str,
+    /// Number of bytes of contents stored in all repositories
+    pub stored: u64,
+    /// Maximum number of bytes stored in all repositories, if any
+    pub quota: Option<u64>,
+    /// Number of bytes of entities in the trash of all users
+    pub trash: u64,
+    /// Number of bytes of quarantined entities
+    pub quarantine: u64,
+    /// Number of bytes of entities in the staging area
+    pub staging: u64,
+}
+
+/// Usage of all repositories of a namespace.
+#[derive(Clone, Debug, Serialize)]
+pub struct NamespaceUsage {
+    pub namespace: String,
+    #[serde(flatten)]
+    pub usage: Usage,
+}
+
+/// Status of removal of deleted entities.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct GcOverview {
+    /// Number of entities in the trash of all users
+    pub trashed: u64,
+    /// Number of trashed entities, which are expired and are purged next
+    pub expired: u64,
+}
+
+/// Server-wide statistics returned by [overview].
+#[derive(Clone, Debug, Serialize)]
+pub struct Overview {
+    /// Time the statistics were collected at in RFC 3339 format
+    pub time: String,
+    pub users: u64,
+    pub repositories: u64,
+    pub storage: StorageOverview,
+    /// Namespaces storing the most bytes, in descending order
+    pub top_by_size: Vec<NamespaceUsage>,
+    /// Namespaces transferring the most bytes, in descending order
+    pub top_by_traffic: Vec<NamespaceUsage>,
+    /// Most recent server errors, newest first
+    pub recent_errors: Vec<ErrorRecord>,
+    /// Replication status of all peers keyed by their names
+    pub replication: BTreeMap<String, PeerStatus>,
+    /// Replication status, if the server is a read replica
+    pub replica: Option<ReplicaStatus>,
+    /// Migration status, if a migration is configured
+    pub migration: Option<MigrationStatus>,
+    pub gc: GcOverview,
+}
+
+/// Returns `n` namespaces with the largest `key` of their usage in descending order.
+fn top(
+    namespaces: &[NamespaceUsage],
+    n: usize,
+    key: impl Fn(&Usage) -> u64,
+) -> Vec<NamespaceUsage> {
+    let mut namespaces = namespaces.to_vec();
+    namespaces.sort_by(|a, b| {
+        key(&b.usage)
+            .cmp(&key(&a.usage))
+            .then_with(|| a.namespace.cmp(&b.namespace))
+    });
+    namespaces.truncate(n);
+    namespaces
+}
+
+/// Counts users, repositories and trashed entities of `store` and bytes stored by the trash.
+async fn count(store: &Store, clock: &dyn Clock) -> anyhow::Result<(u64, u64, u64, GcOverview)> {
+    let names = store.users().await.map_err(|e| match e {
+        GetError::NotFound => anyhow!("users not found"),
+        GetError::Internal(e) => e.context("failed to list users"),
+    })?;
+    let now = clock.now();
+    let (mut repositories, mut trash, mut gc) = (0, 0, GcOverview::default());
+    for name in &names {
+        let cx = UserContext { name: name.clone() };
+        let user = store.user(&cx);
+        let get_error = |e: GetError<anyhow::Error>| match e {
+            GetError::NotFound => anyhow!("`{cx}` not found"),
+            GetError::Internal(e) => e,
+        };
+        repositories += user
+            .repositories()
+            .await
+            .map_err(get_error)
+            .with_context(|| format!("failed to list repositories of `{cx}`"))?
+            .len() as u64;
+        let ids = user
+            .trash_ids()
+            .await
+            .map_err(get_error)
+            .with_context(|| format!("failed to list trash of `{cx}`"))?;
+        for id in ids {
+            gc.trashed += 1;
+            match user.get_trashed(&id).await {
+                Ok(trashed) if !trashed.is_expired(now) => {}
+                _ => gc.expired += 1,
+            }
+            trash += user.get_trashed_size(&id).await?;
+        }
+    }
+    Ok((names.len() as u64, repositories, trash, gc))
+}
+
+/// Returns server-wide statistics for an operations dashboard.
+///
+/// Users, repositories and the trash are counted by traversing the store, bytes stored and
+/// transferred are taken from usage counters.
+#[allow(clippy::too_many_arguments)]
+pub async fn overview(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref accounting): Extension<Arc<Accounting>>,
+    Extension(ref replicator): Extension<Arc<Replicator>>,
+    Extension(ref errors): Extension<Arc<RecentErrors>>,
+    Extension(ref clock): Extension<Arc<dyn Clock>>,
+    replica: Option<Extension<Arc<Replica>>>,
+    migration: Option<Extension<Arc<Migration>>>,
+    cert: Option<Extension<TrustedCertificate>>,
+) -> impl IntoResponse {
+    trace!(target: "app::admin::overview", "called");
+
+    if cert.is_none() {
+        return Err((StatusCode::FORBIDDEN, "Trusted client certificate required").into_response());
+    }
+    let internal = |e: anyhow::Error| {
+        debug!(target: "app::admin::overview", "failed: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to collect statistics",
+        )
+            .into_response()
+    };
+    let (users, repositories, trash, gc) = count(store, clock.as_ref()).await.map_err(internal)?;
+    let quarantine = store.disk_usage("quarantine").await.map_err(internal)?;
+    let staging = store.disk_usage("staging").await.map_err(internal)?;
+
+    let namespaces: Vec<_> = accounting
+        .namespaces()
+        .into_iter()
+        .map(|(name, usage)| NamespaceUsage {
+            namespace: name.to_string(),
+            usage,
+        })
+        .collect();
+    json::encode(&Overview {
+        time: DateTime::<Utc>::from(clock.now()).to_rfc3339_opts(SecondsFormat::Secs, true),
+        users,
+        repositories,
+        storage: StorageOverview {
+            backend: "fs",
+            stored: accounting.stored(),
+            quota: accounting.quota(),
+            trash,
+            quarantine,
+            staging,
+        },
+        top_by_size: top(&namespaces, TOP_NAMESPACES, |usage| usage.stored),
+        top_by_traffic: top(&namespaces, TOP_NAMESPACES, |usage| {
+            usage.uploaded.saturating_add(usage.downloaded)
+        }),
+        recent_errors: errors.records(),
+        replication: replicator.status(),
+        replica: replica.map(|Extension(replica)| replica.status()),
+        migration: migration.map(|Extension(migration)| migration.status()),
+        gc,
+    })
+    .map_err(IntoResponse::into_response)
+}
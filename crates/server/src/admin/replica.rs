@@ -0,0 +1,27 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::replica::Replica;
+use super::super::TrustedCertificate;
+use crate::json;
+
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::trace;
+
+/// Returns replication status of a read replica including its lag behind the primary.
+pub async fn replica(
+    replica: Option<Extension<Arc<Replica>>>,
+    cert: Option<Extension<TrustedCertificate>>,
+) -> impl IntoResponse {
+    trace!(target: "app::admin::replica", "called");
+
+    if cert.is_none() {
+        return Err((StatusCode::FORBIDDEN, "Trusted client certificate required").into_response());
+    }
+    let Extension(replica) =
+        replica.ok_or_else(|| (StatusCode::NOT_FOUND, "Not a replica").into_response())?;
+    json::encode(&replica.status()).map_err(IntoResponse::into_response)
+}
@@ -0,0 +1,25 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::replication::Replicator;
+use super::super::TrustedCertificate;
+use crate::json;
+
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::trace;
+
+/// Returns replication status of all peers keyed by their names.
+pub async fn replication(
+    Extension(ref replicator): Extension<Arc<Replicator>>,
+    cert: Option<Extension<TrustedCertificate>>,
+) -> impl IntoResponse {
+    trace!(target: "app::admin::replication", "called");
+
+    if cert.is_none() {
+        return Err((StatusCode::FORBIDDEN, "Trusted client certificate required").into_response());
+    }
+    json::encode(&replicator.status()).map_err(IntoResponse::into_response)
+}
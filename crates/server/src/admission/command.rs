@@ -0,0 +1,61 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{AdmissionInput, AdmissionPolicy, Decision};
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use anyhow::{anyhow, bail, Context};
+
+/// [AdmissionPolicy] evaluated by an external binary, e.g. a wrapper of `opa eval`
+/// evaluating a Rego policy.
+///
+/// The binary is run with the [AdmissionInput] encoded as JSON on its standard input. Exit
+/// status 0 admits the write and exit status 1 denies it, giving the standard output of the
+/// binary as reason. Any other exit status fails evaluation, which rejects the write.
+#[derive(Clone, Debug)]
+pub struct CommandPolicy {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+}
+
+impl AdmissionPolicy for CommandPolicy {
+    fn evaluate(&self, input: &AdmissionInput) -> anyhow::Result<Decision> {
+        let input = serde_json::to_vec(input).context("failed to encode input")?;
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("failed to run `{}`", self.program.display()))?;
+        // NOTE: Input is written concurrently, so that the binary cannot block on a full pipe
+        // while output is read.
+        let stdin = child.stdin.take().map(|mut stdin| {
+            thread::spawn(move || match stdin.write_all(&input) {
+                // NOTE: The binary may exit without reading all input.
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => Err(e),
+                _ => Ok(()),
+            })
+        });
+        let output = child
+            .wait_with_output()
+            .context("failed to wait for exit")?;
+        if let Some(stdin) = stdin {
+            stdin
+                .join()
+                .map_err(|_| anyhow!("failed to write input"))?
+                .context("failed to write input")?;
+        }
+        match output.status.code() {
+            Some(0) => Ok(Decision::Allow),
+            Some(1) => Ok(Decision::Deny(
+                String::from_utf8_lossy(&output.stdout).trim().into(),
+            )),
+            _ => bail!("`{}` failed with {}", self.program.display(), output.status),
+        }
+    }
+}
@@ -0,0 +1,148 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Admission control of writes by operator-defined policies.
+//!
+//! Before a repository is created, a tag is published or either is deleted, an
+//! [AdmissionInput] document describing the request is evaluated by each [AdmissionPolicy] of
+//! [Admission] in order, and the request is rejected with `403 Forbidden` if any policy denies
+//! it. Policies are expressed either declaratively as [AdmissionRules], e.g. to only accept
+//! signed release tags in production namespaces, or by an external [CommandPolicy], e.g. an
+//! OPA evaluating Rego.
+
+mod command;
+mod rules;
+
+pub use command::*;
+pub use rules::*;
+
+use drawbridge_type::digest::ContentDigest;
+use drawbridge_type::{Annotations, TagKind};
+
+use std::fmt::{self, Debug};
+
+use async_std::sync::Arc;
+use async_std::task::spawn_blocking;
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, trace};
+
+/// A write subject to admission control.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    CreateRepository,
+    PublishTag,
+    /// Deletion of a repository or, if [AdmissionInput::tag] is set, a tag
+    Delete,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::CreateRepository => "create-repository",
+            Self::PublishTag => "publish-tag",
+            Self::Delete => "delete",
+        })
+    }
+}
+
+/// Structured description of a write, which policies are evaluated against.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct AdmissionInput {
+    pub action: Action,
+    /// Subject of the OpenID Connect token the request is authenticated with
+    pub identity: String,
+    /// Repository written to, e.g. `user/repo`
+    pub namespace: String,
+    /// Whether the repository is public, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public: Option<bool>,
+    /// Tag published or deleted, e.g. `1.2.3`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Kind of the tag published or deleted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kind: Option<TagKind>,
+    /// Digests of the repository config or tag entry written or deleted
+    pub digests: ContentDigest,
+    /// Annotations of the root of the tree of the tag published, which are not known for
+    /// deletions
+    #[serde(skip_serializing_if = "Annotations::is_empty")]
+    pub annotations: Annotations,
+}
+
+/// Outcome of an evaluation of an [AdmissionPolicy].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Decision {
+    Allow,
+    /// The write is rejected for the given reason
+    Deny(String),
+}
+
+/// Policy deciding on admission of writes.
+pub trait AdmissionPolicy: Debug + Send + Sync {
+    /// Evaluates the policy against `input`, blocking until the decision is known.
+    fn evaluate(&self, input: &AdmissionInput) -> anyhow::Result<Decision>;
+}
+
+/// [AdmissionPolicy]s writes must be admitted by.
+///
+/// Writes are admitted if there are no policies.
+#[derive(Clone, Debug, Default)]
+pub struct Admission {
+    policies: Vec<Arc<dyn AdmissionPolicy>>,
+}
+
+impl Admission {
+    /// Adds `policy`, which all writes must be admitted by.
+    pub fn policy(mut self, policy: impl 'static + AdmissionPolicy) -> Self {
+        self.policies.push(Arc::new(policy));
+        self
+    }
+
+    /// Evaluates all policies against `input` in order and returns the first denial, if any.
+    pub async fn evaluate(&self, input: AdmissionInput) -> anyhow::Result<Decision> {
+        let input = Arc::new(input);
+        for policy in &self.policies {
+            let policy = Arc::clone(policy);
+            let input = Arc::clone(&input);
+            match spawn_blocking(move || policy.evaluate(&input)).await? {
+                Decision::Allow => {}
+                deny @ Decision::Deny(_) => return Ok(deny),
+            }
+        }
+        Ok(Decision::Allow)
+    }
+
+    /// Fails with an error response unless the write described by `input` is admitted.
+    pub(crate) async fn assert_admitted(
+        &self,
+        input: AdmissionInput,
+    ) -> Result<(), (StatusCode, String)> {
+        if self.policies.is_empty() {
+            return Ok(());
+        }
+        let (action, namespace) = (input.action, input.namespace.clone());
+        match self.evaluate(input).await {
+            Ok(Decision::Allow) => {
+                trace!(target: "app::admission", "admitted {action} of `{namespace}`");
+                Ok(())
+            }
+            Ok(Decision::Deny(reason)) => {
+                debug!(target: "app::admission", "denied {action} of `{namespace}`: {reason}");
+                Err((
+                    StatusCode::FORBIDDEN,
+                    format!("Denied by admission policy: {reason}"),
+                ))
+            }
+            Err(e) => {
+                debug!(target: "app::admission", "failed to evaluate policies on {action} of `{namespace}`: {:?}", e);
+                Err((
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Failed to evaluate admission policies".into(),
+                ))
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Action, AdmissionInput, AdmissionPolicy, Decision};
+
+use drawbridge_type::{TagKind, TagName};
+
+use serde::Deserialize;
+
+/// Requirements writes must satisfy to be admitted by an [AdmissionRule].
+///
+/// Requirements on tags are only checked for [Action::PublishTag].
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Requirements {
+    /// No writes are admitted
+    #[serde(default)]
+    pub deny: bool,
+    /// Identities writes are admitted from, all if empty
+    #[serde(default)]
+    pub identities: Vec<String>,
+    /// Only signed tags are admitted
+    #[serde(default)]
+    pub signed: bool,
+    /// Only tags without a pre-release version are admitted
+    #[serde(default)]
+    pub release: bool,
+    /// Annotation keys, which the root of the tree of tags must be annotated with
+    #[serde(default)]
+    pub annotations: Vec<String>,
+}
+
+/// A declarative rule, which writes to matching namespaces must satisfy.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct AdmissionRule {
+    /// Namespaces the rule applies to, i.e. `*`, a user name, e.g. `prod`, or a repository,
+    /// e.g. `prod/app`
+    pub namespace: String,
+    /// Actions the rule applies to, all if empty
+    #[serde(default)]
+    pub actions: Vec<Action>,
+    #[serde(default)]
+    pub require: Requirements,
+    /// Human-readable description, which is given as the reason of denials
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl AdmissionRule {
+    /// Returns whether the rule applies to `input`.
+    fn applies(&self, input: &AdmissionInput) -> bool {
+        let namespace = match self.namespace.as_str() {
+            "*" => true,
+            ns if ns.contains('/') => input.namespace == ns,
+            ns => input
+                .namespace
+                .split_once('/')
+                .is_some_and(|(owner, _)| owner == ns),
+        };
+        namespace && (self.actions.is_empty() || self.actions.contains(&input.action))
+    }
+
+    /// Returns the first requirement `input` does not satisfy, if any.
+    fn violation(&self, input: &AdmissionInput) -> Option<String> {
+        let Requirements {
+            deny,
+            ref identities,
+            signed,
+            release,
+            ref annotations,
+        } = self.require;
+        if deny {
+            return Some(format!("{} is not allowed", input.action));
+        }
+        if !identities.is_empty() && !identities.contains(&input.identity) {
+            return Some(format!("identity `{}` is not allowed", input.identity));
+        }
+        if input.action != Action::PublishTag {
+            return None;
+        }
+        let tag = input.tag.as_ref()?;
+        if signed && input.kind != Some(TagKind::Signed) {
+            return Some(format!("tag `{tag}` is not signed"));
+        }
+        if release && !tag.parse::<TagName>().is_ok_and(|name| name.pre.is_empty()) {
+            return Some(format!("tag `{tag}` is not a release"));
+        }
+        annotations
+            .iter()
+            .find(|key| !input.annotations.contains_key(*key))
+            .map(|key| format!("tag `{tag}` is missing annotation `{key}`"))
+    }
+}
+
+/// [AdmissionPolicy] denying writes, which violate any of its rules applying to them.
+///
+/// Rules are usually read from a JSON array, e.g.
+///
+/// ```json
+/// [{
+///     "namespace": "prod",
+///     "actions": ["publish-tag"],
+///     "require": { "signed": true, "release": true },
+///     "description": "production repositories only accept signed release tags"
+/// }]
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct AdmissionRules(pub Vec<AdmissionRule>);
+
+impl AdmissionPolicy for AdmissionRules {
+    fn evaluate(&self, input: &AdmissionInput) -> anyhow::Result<Decision> {
+        for rule in self.0.iter().filter(|rule| rule.applies(input)) {
+            if let Some(violation) = rule.violation(input) {
+                return Ok(Decision::Deny(match rule.description {
+                    Some(ref description) => format!("{description}: {violation}"),
+                    None => violation,
+                }));
+            }
+        }
+        Ok(Decision::Allow)
+    }
+}
@@ -0,0 +1,126 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{IntegrityAlert, Notifier};
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use anyhow::{bail, ensure, Context};
+
+/// Timeout of reads from and writes to the SMTP server.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// [Notifier], which emails alerts via an SMTP relay.
+///
+/// Mail is submitted without TLS or authentication, which makes this notifier suitable for
+/// relays local to the server only, e.g. one listening on `localhost:25`.
+#[derive(Clone, Debug)]
+pub struct EmailNotifier {
+    /// Address of the SMTP relay in `HOST:PORT` form
+    pub server: String,
+    /// Address alerts are sent from
+    pub from: String,
+    /// Addresses alerts are sent to
+    pub to: Vec<String>,
+}
+
+/// SMTP session with a relay.
+struct Session {
+    rdr: BufReader<TcpStream>,
+    wtr: TcpStream,
+}
+
+impl Session {
+    fn open(server: &str) -> anyhow::Result<Self> {
+        let wtr = TcpStream::connect(server)
+            .with_context(|| format!("failed to connect to `{server}`"))?;
+        wtr.set_read_timeout(Some(TIMEOUT))
+            .context("failed to set read timeout")?;
+        wtr.set_write_timeout(Some(TIMEOUT))
+            .context("failed to set write timeout")?;
+        let rdr = BufReader::new(wtr.try_clone().context("failed to clone connection")?);
+        let mut session = Self { rdr, wtr };
+        session.expect(220).context("failed to receive greeting")?;
+        Ok(session)
+    }
+
+    /// Reads a possibly multiline reply and fails unless its code is `code`.
+    fn expect(&mut self, code: u16) -> anyhow::Result<()> {
+        loop {
+            let mut line = String::new();
+            if self
+                .rdr
+                .read_line(&mut line)
+                .context("failed to read reply")?
+                == 0
+            {
+                bail!("connection closed");
+            }
+            let line = line.trim_end();
+            ensure!(
+                line.get(..3).and_then(|c| c.parse().ok()) == Some(code),
+                "unexpected reply `{line}`, expected {code}"
+            );
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+
+    fn command(&mut self, command: &str, code: u16) -> anyhow::Result<()> {
+        write!(self.wtr, "{command}\r\n").context("failed to send command")?;
+        self.expect(code)
+            .with_context(|| format!("`{command}` failed"))
+    }
+}
+
+impl EmailNotifier {
+    /// Returns the message of `alert` including headers with lines terminated by CRLF and
+    /// dot-stuffed as required by the `DATA` command.
+    fn message(&self, alert: &IntegrityAlert) -> anyhow::Result<String> {
+        let details = serde_json::to_string_pretty(alert).context("failed to encode alert")?;
+        let body = format!(
+            "{}.\n\nNamespace: {}\nEntity: {}\nExpected digest: {}\nActual digest: {}\n\n{details}\n",
+            alert.summary(),
+            alert.namespace.as_deref().unwrap_or("-"),
+            alert.entity,
+            alert.expected,
+            alert
+                .actual
+                .as_ref()
+                .map_or_else(|| "unknown".into(), ToString::to_string),
+        );
+        let mut msg = format!(
+            "From: {}\r\nTo: {}\r\nSubject: [drawbridge] {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n",
+            self.from,
+            self.to.join(", "),
+            alert.summary(),
+        );
+        for line in body.lines() {
+            if line.starts_with('.') {
+                msg.push('.');
+            }
+            msg.push_str(line);
+            msg.push_str("\r\n");
+        }
+        Ok(msg)
+    }
+}
+
+impl Notifier for EmailNotifier {
+    fn notify(&self, alert: &IntegrityAlert) -> anyhow::Result<()> {
+        let msg = self.message(alert)?;
+        let mut session = Session::open(&self.server)?;
+        session.command("EHLO drawbridge", 250)?;
+        session.command(&format!("MAIL FROM:<{}>", self.from), 250)?;
+        for to in &self.to {
+            session.command(&format!("RCPT TO:<{to}>"), 250)?;
+        }
+        session.command("DATA", 354)?;
+        write!(session.wtr, "{msg}").context("failed to send message")?;
+        session.command(".", 250)?;
+        session.command("QUIT", 221)
+    }
+}
@@ -0,0 +1,241 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Alerting of the operator on integrity failures of stored contents.
+//!
+//! An [IntegrityAlert] is raised whenever stored contents are found not to match their
+//! digest, be it by a consistency check of the store, while serving them or by deferred
+//! verification of an upload, and is sent to each [Notifier] in background. Unlike
+//! `integrity-failure` webhooks, which are configured per repository by its owner, notifiers
+//! are configured by the operator for the whole store.
+//!
+//! If replication is configured, tree nodes failing verification are also repaired from the
+//! peers of their namespace once alerts are sent, see [repair](super::repair).
+
+mod email;
+mod pagerduty;
+mod webhook;
+
+pub use email::*;
+pub use pagerduty::*;
+pub use webhook::*;
+
+use super::repair::Repairer;
+use super::store::{namespace, CheckReport, Entity, GetError, Inconsistency};
+
+use drawbridge_type::digest::ContentDigest;
+
+use std::fmt::{self, Debug};
+use std::io;
+
+use async_std::sync::Arc;
+use async_std::task::{spawn, spawn_blocking};
+use axum::body::Bytes;
+use camino::Utf8Path;
+use futures::{Stream, TryStreamExt};
+use serde::Serialize;
+use tracing::{error, trace, warn};
+
+/// Component, which detected an integrity failure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AlertSource {
+    /// Consistency check of the store
+    Check,
+    /// Verification of contents as they are served
+    Read,
+    /// Deferred verification of uploaded contents, see
+    /// [VerificationPolicy::Deferred](super::VerificationPolicy::Deferred)
+    DeferredVerification,
+}
+
+impl fmt::Display for AlertSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Check => "consistency check",
+            Self::Read => "read verification",
+            Self::DeferredVerification => "deferred verification",
+        })
+    }
+}
+
+/// Contents of an entity, which do not match their expected digest.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct IntegrityAlert {
+    pub source: AlertSource,
+    /// User or proxied registry namespace the entity belongs to
+    pub namespace: Option<String>,
+    /// Path of the entity relative to the store root
+    pub entity: String,
+    /// Digest the contents were expected to match
+    pub expected: ContentDigest,
+    /// Digest of the contents, if it was computed
+    pub actual: Option<ContentDigest>,
+}
+
+impl IntegrityAlert {
+    /// Constructs an alert for the entity at `path` relative to the store root.
+    pub fn new(
+        source: AlertSource,
+        path: impl AsRef<Utf8Path>,
+        expected: ContentDigest,
+        actual: Option<ContentDigest>,
+    ) -> Self {
+        let path = path.as_ref();
+        Self {
+            source,
+            namespace: namespace(path).map(Into::into),
+            entity: path.to_string(),
+            expected,
+            actual,
+        }
+    }
+
+    /// Constructs an alert for contents of `entity` failing verification as they are read.
+    pub(crate) fn read(
+        entity: &Entity<'_, impl AsRef<Utf8Path>>,
+        expected: &ContentDigest,
+    ) -> Self {
+        Self::new(AlertSource::Read, entity.prefix(), expected.clone(), None)
+    }
+
+    /// Returns a single-line summary of the alert.
+    pub fn summary(&self) -> String {
+        format!(
+            "Integrity failure of `{}` detected by {}",
+            self.entity, self.source
+        )
+    }
+}
+
+/// Destination of [IntegrityAlert]s.
+pub trait Notifier: Debug + Send + Sync {
+    /// Sends `alert`, blocking until it is delivered.
+    fn notify(&self, alert: &IntegrityAlert) -> anyhow::Result<()>;
+}
+
+/// Dispatcher of [IntegrityAlert]s to [Notifier]s.
+///
+/// Alerts are always logged at `ERROR` level, even if there are no notifiers.
+#[derive(Clone, Debug, Default)]
+pub struct Alerts {
+    notifiers: Vec<Arc<dyn Notifier>>,
+    /// Repairer of entities failing verification, if any
+    repairer: Option<Arc<Repairer>>,
+}
+
+impl Alerts {
+    /// Adds `notifier`, which all alerts are sent to.
+    pub fn notifier(mut self, notifier: impl 'static + Notifier) -> Self {
+        self.notifiers.push(Arc::new(notifier));
+        self
+    }
+
+    /// Returns the dispatcher with the same notifiers, which also repairs entities alerted of
+    /// using `repairer`.
+    pub(crate) fn with_repairer(&self, repairer: Arc<Repairer>) -> Self {
+        Self {
+            notifiers: self.notifiers.clone(),
+            repairer: Some(repairer),
+        }
+    }
+
+    /// Sends `alert` to all notifiers and waits for their deliveries to finish.
+    ///
+    /// The entity alerted of is then repaired, unless it failed deferred verification, which
+    /// quarantines it.
+    pub async fn send(&self, alert: IntegrityAlert) {
+        error!(
+            target: "app::alerts",
+            source = %alert.source,
+            namespace = alert.namespace,
+            entity = alert.entity,
+            expected = %alert.expected,
+            actual = alert.actual.as_ref().map(ToString::to_string),
+            "integrity failure"
+        );
+        let alert = Arc::new(alert);
+        for notifier in &self.notifiers {
+            let notifier = Arc::clone(notifier);
+            let alert = Arc::clone(&alert);
+            match spawn_blocking(move || notifier.notify(&alert).map_err(|e| (notifier, e))).await {
+                Ok(()) => trace!(target: "app::alerts", "sent alert"),
+                Err((notifier, e)) => {
+                    warn!(target: "app::alerts", "failed to send alert to {:?}: {:?}", notifier, e)
+                }
+            }
+        }
+        if let Some(ref repairer) = self.repairer {
+            if alert.source != AlertSource::DeferredVerification {
+                repairer.repair(&alert.entity).await
+            }
+        }
+    }
+
+    /// Sends alerts for all integrity failures found by a consistency check of the store and
+    /// waits for their deliveries to finish.
+    pub async fn send_report(&self, report: &CheckReport) {
+        for inconsistency in &report.inconsistencies {
+            match inconsistency {
+                Inconsistency::CorruptContent {
+                    path,
+                    expected,
+                    actual,
+                }
+                | Inconsistency::DigestMismatch {
+                    path,
+                    expected,
+                    actual,
+                } => {
+                    self.send(IntegrityAlert::new(
+                        AlertSource::Check,
+                        path,
+                        expected.clone(),
+                        Some(actual.clone()),
+                    ))
+                    .await
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Sends `alert` in background.
+    pub(crate) fn raise(self: &Arc<Self>, alert: IntegrityAlert) {
+        let alerts = Arc::clone(self);
+        _ = spawn(async move { alerts.send(alert).await });
+    }
+
+    /// Raises `alert` if `e` was caused by contents failing verification as they were read.
+    pub(crate) fn raise_on_mismatch(
+        self: &Arc<Self>,
+        e: &GetError<anyhow::Error>,
+        alert: impl FnOnce() -> IntegrityAlert,
+    ) {
+        if let GetError::Internal(e) = e {
+            if e.chain().any(|e| {
+                e.downcast_ref::<io::Error>()
+                    .is_some_and(|e| e.kind() == io::ErrorKind::InvalidData)
+            }) {
+                self.raise(alert())
+            }
+        }
+    }
+
+    /// Returns `stream` of verified contents, which raises `alert` once they fail verification.
+    pub(crate) fn watch(
+        self: &Arc<Self>,
+        alert: IntegrityAlert,
+        stream: impl 'static + Send + Stream<Item = io::Result<Bytes>>,
+    ) -> impl 'static + Send + Stream<Item = io::Result<Bytes>> {
+        let alerts = Arc::clone(self);
+        let mut alert = Some(alert);
+        stream.inspect_err(move |e| {
+            if e.kind() == io::ErrorKind::InvalidData {
+                if let Some(alert) = alert.take() {
+                    alerts.raise(alert)
+                }
+            }
+        })
+    }
+}
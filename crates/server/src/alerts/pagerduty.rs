@@ -0,0 +1,47 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{IntegrityAlert, Notifier};
+
+use anyhow::Context;
+use openidconnect::url::Url;
+use serde_json::json;
+
+/// URL of the PagerDuty Events API v2.
+pub const PAGERDUTY_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// [Notifier], which triggers incidents using the PagerDuty Events API v2, or a compatible API.
+///
+/// Alerts of the same entity and expected digest share the deduplication key, so that
+/// repeated detections of the same failure do not open new incidents.
+#[derive(Clone, Debug)]
+pub struct PagerDutyNotifier {
+    /// Events API URL, e.g. [PAGERDUTY_EVENTS_URL]
+    pub url: Url,
+    /// Integration key of the service to trigger incidents of
+    pub routing_key: String,
+}
+
+impl Notifier for PagerDutyNotifier {
+    fn notify(&self, alert: &IntegrityAlert) -> anyhow::Result<()> {
+        let body = json!({
+            "routing_key": self.routing_key,
+            "event_action": "trigger",
+            "dedup_key": format!("drawbridge:{}:{}", alert.entity, alert.expected),
+            "payload": {
+                "summary": alert.summary(),
+                "source": "drawbridge",
+                "severity": "critical",
+                "component": alert.namespace,
+                "group": "integrity",
+                "class": "digest-mismatch",
+                "custom_details": alert,
+            },
+        });
+        _ = ureq::post(self.url.as_str())
+            .set("Content-Type", "application/json")
+            .send_bytes(body.to_string().as_bytes())
+            .with_context(|| format!("failed to trigger incident at `{}`", self.url))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,25 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{IntegrityAlert, Notifier};
+
+use anyhow::Context;
+use openidconnect::url::Url;
+
+/// [Notifier], which `POST`s alerts as JSON to a URL.
+#[derive(Clone, Debug)]
+pub struct WebhookNotifier {
+    pub url: Url,
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify(&self, alert: &IntegrityAlert) -> anyhow::Result<()> {
+        let body = serde_json::to_vec(alert).context("failed to encode alert")?;
+        _ = ureq::post(self.url.as_str())
+            .set("Content-Type", "application/json")
+            .set("Drawbridge-Event", "integrity-failure")
+            .send_bytes(&body)
+            .with_context(|| format!("failed to post alert to `{}`", self.url))?;
+        Ok(())
+    }
+}
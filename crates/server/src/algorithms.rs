@@ -0,0 +1,73 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Rules on the hashing algorithms content digests of uploads consist of.
+//!
+//! Owners restrict the algorithms of all their repositories in the `algorithms` member of
+//! their user record and repositories restrict them further in the `algorithms` member of
+//! their config. Both are enforced on uploads of tree nodes and tags, and the rules in effect
+//! for a repository are advertised under its `_algorithms` endpoint.
+
+use super::{GetError, Store};
+use crate::auth::assert_repository_read;
+use crate::json;
+
+use drawbridge_type::digest::{AlgorithmRules, ContentDigest};
+use drawbridge_type::{RepositoryConfig, RepositoryContext, UserRecord};
+
+use async_std::sync::Arc;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Returns the rules of the owner of repository `cx` merged with the rules in its `config`.
+pub(crate) async fn rules(
+    store: &Store,
+    cx: &RepositoryContext,
+    config: &RepositoryConfig,
+) -> Result<AlgorithmRules, GetError<anyhow::Error>> {
+    let owner: UserRecord = store.user(&cx.owner).get_content_json().await?;
+    Ok(match (owner.algorithms, config.algorithms.as_ref()) {
+        (Some(owner), Some(repo)) => owner.merge(repo),
+        (Some(rules), None) => rules,
+        (None, Some(rules)) => rules.clone(),
+        (None, None) => AlgorithmRules::default(),
+    })
+}
+
+/// Asserts that `rules` accept `digest` of an upload.
+pub(crate) fn assert_accepted(
+    rules: &AlgorithmRules,
+    digest: &ContentDigest,
+) -> Result<(), (StatusCode, String)> {
+    rules.validate(digest).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Content digest rejected: {e}"),
+        )
+    })
+}
+
+/// Returns the rules on hashing algorithms in effect for uploads to the repository.
+pub async fn get(
+    Extension(ref store): Extension<Arc<Store>>,
+    cx: RepositoryContext,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::algorithms::get", "called for `{cx}`");
+
+    let (repo, _) = assert_repository_read(store, &cx, req)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let config = repo.get_json().await.map_err(|e| {
+        debug!(target: "app::algorithms::get", "failed to get repository `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let rules = rules(store, &cx, &config).await.map_err(|e| {
+        debug!(target: "app::algorithms::get", "failed to get rules of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    json::encode(&rules).map_err(IntoResponse::into_response)
+}
@@ -0,0 +1,40 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::Store;
+use super::decode;
+use crate::auth::assert_repository_read;
+use crate::json;
+use crate::problem::Problem;
+
+use drawbridge_type::{Error, TagContext};
+
+use async_std::sync::Arc;
+use axum::body::Body;
+use axum::http::Request;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Returns all attestations attached to the tag.
+pub async fn get(
+    Extension(ref store): Extension<Arc<Store>>,
+    cx: TagContext,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::attestations::get", "called for `{cx}`");
+
+    let (repo, _) = assert_repository_read(store, &cx.repository, req)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let attestations = repo.tag(&cx.name).attestations().await.map_err(|e| {
+        debug!(target: "app::attestations::get", "failed for `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let attestations = decode(attestations).map_err(|e| {
+        debug!(target: "app::attestations::get", "failed to decode attestations of `{cx}`: {:?}", e);
+        Problem::from(Error::StorageFailure).into_response()
+    })?;
+    json::encode(&attestations).map_err(IntoResponse::into_response)
+}
@@ -0,0 +1,27 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! in-toto attestations, e.g. SLSA provenance, attached to tags.
+//!
+//! Attestations are stored content-addressed and linked to the tag tree by the
+//! digests of their subjects.
+
+mod get;
+mod put;
+mod query;
+
+pub use get::*;
+pub use put::*;
+pub use query::*;
+
+use drawbridge_type::TagAttestation;
+
+use anyhow::Context;
+
+/// Decodes stored attestations.
+fn decode(attestations: Vec<Vec<u8>>) -> anyhow::Result<Vec<TagAttestation>> {
+    attestations
+        .iter()
+        .map(|buf| serde_json::from_slice(buf).context("failed to decode attestation"))
+        .collect()
+}
@@ -0,0 +1,74 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use crate::json::BoundedBody;
+
+use drawbridge_type::digest::hex;
+use drawbridge_type::{Meta, TagAttestation, TagContext};
+
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use ring::digest::{digest, SHA256};
+use tracing::{debug, trace};
+
+/// Attaches an in-toto attestation statement to the tag.
+///
+/// At least one of the attestation subjects must match a node of the tag tree.
+pub async fn put(
+    Extension(store): Extension<Arc<Store>>,
+    claims: OidcClaims,
+    cx: TagContext,
+    meta: Meta,
+    BoundedBody(body): BoundedBody,
+) -> impl IntoResponse {
+    trace!(target: "app::attestations::put", "called for `{cx}`");
+
+    if meta.hash.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "At least one content digest value must be specified",
+        )
+            .into_response());
+    }
+    if meta.mime.essence() != TagAttestation::TYPE {
+        return Err((StatusCode::BAD_REQUEST, "Invalid content type").into_response());
+    }
+
+    let user = claims
+        .assert_user(
+            &store,
+            &cx.repository.owner,
+            ScopeContext::Tag,
+            ScopeLevel::Write,
+        )
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let attestation: TagAttestation = serde_json::from_slice(&body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()).into_response())?;
+
+    let tag = user.repository(&cx.repository.name).tag(&cx.name);
+    let nodes = tag.walk().await.map_err(|e| {
+        debug!(target: "app::attestations::put", "failed to walk tree of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    if !nodes.values().any(|meta| attestation.matches(&meta.hash)) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Attestation subject does not match any node of the tag tree",
+        )
+            .into_response());
+    }
+
+    let name = hex(digest(&SHA256, &body));
+    tag.create_attestation(&name, meta, body.as_slice())
+        .await
+        .map_err(|e| {
+            debug!(target: "app::attestations::put", "failed for `{cx}`: {:?}", e);
+            e.into_response()
+        })
+        .map(|_| StatusCode::CREATED)
+}
@@ -0,0 +1,81 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::Store;
+use super::decode;
+use crate::auth::assert_repository_read;
+use crate::json;
+use crate::pagination::paginate;
+use crate::problem::Problem;
+
+use drawbridge_type::{Error, RepositoryContext};
+
+use async_std::sync::Arc;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::response::IntoResponse;
+use axum::Extension;
+use openidconnect::url::form_urlencoded;
+use tracing::{debug, trace};
+
+/// Returns attestations attached to any tag of the repository, which have a subject
+/// with the digest specified by the `digest` query parameter in `<algorithm>:<hex>`
+/// format, e.g. `sha256:2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824`.
+///
+/// The listing is paginated by the `cursor` and `limit` query parameters.
+pub async fn query(
+    Extension(ref store): Extension<Arc<Store>>,
+    cx: RepositoryContext,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::attestations::query", "called for `{cx}`");
+
+    let uri = req.uri().clone();
+    let (algo, hex) = form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes())
+        .find(|(k, _)| k == "digest")
+        .and_then(|(_, v)| {
+            v.split_once(':')
+                .map(|(algo, hex)| (algo.to_ascii_lowercase(), hex.to_string()))
+        })
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                "`digest` query parameter in `<algorithm>:<hex>` format must be specified",
+            )
+                .into_response()
+        })?;
+
+    let (repo, _) = assert_repository_read(store, &cx, req)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let tags = repo.tags().await.map_err(|e| {
+        debug!(target: "app::attestations::query", "failed to list tags of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let mut attestations = vec![];
+    for tag in tags {
+        let tag_attestations = repo.tag(&tag).attestations().await.map_err(|e| {
+            debug!(target: "app::attestations::query", "failed for `{cx}:{tag}`: {:?}", e);
+            e.into_response()
+        })?;
+        let tag_attestations = decode(tag_attestations).map_err(|e| {
+            debug!(target: "app::attestations::query", "failed to decode attestations of `{cx}:{tag}`: {:?}", e);
+            Problem::from(Error::StorageFailure).into_response()
+        })?;
+        for attestation in tag_attestations {
+            let matches = attestation.subject.iter().any(|subject| {
+                subject
+                    .digest
+                    .get(&algo)
+                    .is_some_and(|v| v.eq_ignore_ascii_case(&hex))
+            });
+            if matches && !attestations.contains(&attestation) {
+                attestations.push(attestation);
+            }
+        }
+    }
+    let (attestations, link): (Vec<_>, _) = paginate(&uri, attestations)?;
+    json::encode(&attestations)
+        .map(|res| (link, res))
+        .map_err(IntoResponse::into_response)
+}
@@ -2,14 +2,23 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 
 mod oidc;
+mod presign;
+mod signature;
+mod steward;
 mod tls;
 
 pub use oidc::{Claims as OidcClaims, ScopeContext, ScopeLevel, Verifier as OidcVerifier};
-pub use tls::{Config as TlsConfig, TrustedCertificate};
+pub use presign::UrlSigner;
+pub use signature::Keys as SignatureKeys;
+pub use steward::{Steward, WorkloadIdentity};
+pub use tls::{Config as TlsConfig, ReplicaCertificate, TrustedCertificate};
 
-use super::{Repository, Store, User};
+pub(crate) use oidc::Embedded;
+pub(crate) use tls::{is_valid_for_any, verify_client_certificate};
 
-use drawbridge_type::RepositoryContext;
+use super::{GetError, PullTagError, Repository, Store, Tag, User};
+
+use drawbridge_type::{RepositoryConfig, RepositoryContext, TagName};
 
 use axum::body::Body;
 use axum::extract::RequestParts;
@@ -22,6 +31,17 @@ pub async fn assert_repository_read<'a>(
     req: Request<Body>,
 ) -> Result<(Repository<'a>, Option<User<'a>>), impl IntoResponse> {
     let repo = store.repository(cx);
+    // NOTE: Read replicas may read all repositories, while pre-signed URLs grant access to the
+    // path signed only.
+    if req.extensions().get::<ReplicaCertificate>().is_some()
+        || req
+            .extensions()
+            .get::<WorkloadIdentity>()
+            .is_some_and(|workload| workload.can_read(cx))
+        || presign::is_presigned(&req)
+    {
+        return Ok((repo, None));
+    }
     if repo
         .is_public()
         .await
@@ -38,3 +58,41 @@ pub async fn assert_repository_read<'a>(
             .map(|user| (repo, Some(user)))
     }
 }
+
+/// Returns tag `name` of `repo` along with the repository configuration, if the client may
+/// pull it as per [Repository::pull_tag].
+///
+/// Peers presenting a trusted certificate `cert` replicate tags regardless of the vulnerability
+/// policy of the repository.
+pub(crate) async fn pull_tag<'a>(
+    repo: &Repository<'a>,
+    name: &TagName,
+    cert: Option<&TrustedCertificate>,
+) -> Result<(Tag<'a>, RepositoryConfig), PullTagError<anyhow::Error>> {
+    if cert.is_some() {
+        let config = repo.get_json().await?;
+        return Ok((repo.tag(name), config));
+    }
+    repo.pull_tag(name).await
+}
+
+/// Returns whether repository `cx` exists and may be read by the identity `claims` belong to,
+/// if any, where the request the identity is authenticated by is not available.
+pub(crate) async fn may_read(
+    store: &Store,
+    cx: &RepositoryContext,
+    claims: Option<&OidcClaims>,
+) -> Result<bool, GetError<anyhow::Error>> {
+    match store.repository(cx).is_public().await {
+        Ok(true) => Ok(true),
+        Ok(false) => Ok(match claims {
+            Some(claims) => claims
+                .assert_user(store, &cx.owner, ScopeContext::Repository, ScopeLevel::Read)
+                .await
+                .is_ok(),
+            None => false,
+        }),
+        Err(GetError::NotFound) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
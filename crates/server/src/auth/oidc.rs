@@ -1,12 +1,14 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::super::{GetError, OidcConfig, Store, User};
+use super::super::{Clock, GetError, OidcConfig, Store, User};
+use crate::slow_log::Subject;
 
 use drawbridge_type::{UserContext, UserRecord};
 
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::UNIX_EPOCH;
 
 use anyhow::{anyhow, bail, Context};
 use axum::extract::rejection::{TypedHeaderRejection, TypedHeaderRejectionReason};
@@ -22,17 +24,19 @@ use openidconnect::core::CoreProviderMetadata;
 use openidconnect::ureq::http_client;
 use openidconnect::IssuerUrl;
 use serde::{Deserialize, Deserializer};
-use tracing::{error, info, trace, warn};
+use tracing::{debug, error, info, trace, warn};
 
 pub struct Verifier {
     keyset: HashMap<String, DecodingKey>,
     validator: Validation,
+    clock: Arc<dyn Clock>,
 }
 
 impl std::fmt::Debug for Verifier {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Verifier")
             .field("validator", &self.validator)
+            .field("clock", &self.clock)
             .finish()
     }
 }
@@ -43,6 +47,8 @@ struct VerifiedInfo {
     subject: String,
     #[serde(rename = "scope", deserialize_with = "deserialize_scopes")]
     scopes: HashSet<String>,
+    #[serde(rename = "exp")]
+    expires: u64,
 }
 
 #[allow(single_use_lifetimes)]
@@ -55,12 +61,13 @@ where
 }
 
 impl Verifier {
-    pub fn new(config: OidcConfig) -> Result<Self, anyhow::Error> {
+    pub fn new(config: OidcConfig, clock: Arc<dyn Clock>) -> Result<Self, anyhow::Error> {
         let mut validator = Validation::new(Algorithm::RS256);
         validator.set_audience(&[config.audience]);
         validator.set_issuer(&[config.issuer.as_str()]);
         validator.set_required_spec_claims(&["exp", "iat", "scope", "aud"]);
-        validator.validate_exp = true;
+        // NOTE: Expiry is validated against `clock` in `verify_token` instead.
+        validator.validate_exp = false;
 
         let oidc_md =
             CoreProviderMetadata::discover(&IssuerUrl::from_url(config.issuer), http_client)
@@ -85,7 +92,11 @@ impl Verifier {
             .collect::<Result<HashMap<String, DecodingKey>, anyhow::Error>>()
             .context("failed to parse jwks")?;
 
-        Ok(Self { keyset, validator })
+        Ok(Self {
+            keyset,
+            validator,
+            clock,
+        })
     }
 
     fn verify_token(&self, token: &str) -> Result<VerifiedInfo, anyhow::Error> {
@@ -100,6 +111,20 @@ impl Verifier {
             .ok_or_else(|| anyhow!("No key found for kid: {}", kid))?;
         let decoded_token =
             decode::<VerifiedInfo>(token, key, &self.validator).context("Error decoding token")?;
+        let now = self
+            .clock
+            .now()
+            .duration_since(UNIX_EPOCH)
+            .context("Current time precedes the Unix epoch")?
+            .as_secs();
+        if decoded_token
+            .claims
+            .expires
+            .saturating_add(self.validator.leeway)
+            < now
+        {
+            bail!("Token expired")
+        }
         Ok(decoded_token.claims)
     }
 }
@@ -145,11 +170,35 @@ impl ScopeLevel {
     }
 }
 
+/// Marks requests routed by a [Router](axum::Router) built by
+/// [Builder::build_router](crate::Builder::build_router), which trusts [Claims] inserted into
+/// requests by the embedding application.
+///
+/// Requests served by an [App](crate::App) never carry the marker, so that [Claims] found in
+/// their extensions are ignored.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Embedded;
+
 #[repr(transparent)]
 #[derive(Clone, Debug)]
 pub struct Claims(VerifiedInfo);
 
 impl Claims {
+    /// Constructs claims of `subject` granted `scopes`, which never expire.
+    ///
+    /// A layer of an application embedding this one may authenticate requests itself and
+    /// insert the resulting claims into their extensions, which are then used instead of
+    /// verifying a bearer token. Such claims are only trusted by routers built by
+    /// [Builder::build_router](crate::Builder::build_router) and are never verified, so the
+    /// embedding application must not let clients influence them.
+    pub fn new(subject: impl Into<String>, scopes: impl IntoIterator<Item = String>) -> Self {
+        Self(VerifiedInfo {
+            subject: subject.into(),
+            scopes: scopes.into_iter().collect(),
+            expires: u64::MAX,
+        })
+    }
+
     pub fn subject(&self) -> &str {
         &self.0.subject
     }
@@ -197,6 +246,7 @@ impl Claims {
         let subj = self.subject();
         let oidc_record = UserRecord {
             subject: subj.to_string(),
+            algorithms: None,
         };
 
         let user = store.user(cx);
@@ -209,7 +259,7 @@ e.into_response()
                 },
             }})?;
 
-        if oidc_record != owner_record {
+        if oidc_record.subject != owner_record.subject {
             warn!(target: "app::auth::oidc", ?oidc_record, user = ?cx, ?owner_record, "User access not authorized");
             return Err((
                 StatusCode::UNAUTHORIZED,
@@ -230,6 +280,15 @@ impl<B: Send> FromRequest<B> for Claims {
     type Rejection = Response;
 
     async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let embedded = req.extensions().get::<Embedded>().is_some();
+        if let Some(claims) = req.extensions().get::<Self>().filter(|_| embedded).cloned() {
+            trace!(target: "app::auth::oidc", ?claims, "using claims of embedding application");
+            if let Some(subject) = req.extensions().get::<Subject>() {
+                subject.set(claims.subject());
+            }
+            return Ok(claims);
+        }
+
         let TypedHeader(Authorization::<Bearer>(token)) =
             req.extract()
                 .await
@@ -241,13 +300,15 @@ impl<B: Send> FromRequest<B> for Claims {
                 })?;
         warn!(target: "app::auth::oidc", ?token, "got token");
 
-        let Extension(verifier) = req
-            .extract::<Extension<Arc<Verifier>>>()
-            .await
-            .map_err(|e| {
-                error!(target: "app::auth::oidc", "OpenID Connect verifier extension missing");
-                e.into_response()
-            })?;
+        // NOTE: Embedded applications may be built without a verifier, in which case only
+        // claims inserted by the embedding application authenticate clients.
+        let Extension(verifier) =
+            req.extract::<Extension<Arc<Verifier>>>()
+                .await
+                .map_err(|_| {
+                    debug!(target: "app::auth::oidc", "no OpenID Connect verifier configured");
+                    (StatusCode::UNAUTHORIZED, "Bearer tokens are not accepted").into_response()
+                })?;
 
         trace!(target: "app:auth::oidc", "verifying token");
 
@@ -257,8 +318,46 @@ impl<B: Send> FromRequest<B> for Claims {
                 error!(target: "app::auth::oidc", error = ?e, "failed to verify token");
                 (StatusCode::UNAUTHORIZED, "Invalid token provided").into_response()
             })
-            .map(Self);
+            .map(Self)?;
         info!(target: "app::auth::oidc", ?claims, "verified token");
-        claims
+        if let Some(subject) = req.extensions().get::<Subject>() {
+            subject.set(claims.subject());
+        }
+        Ok(claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use axum::http::Request;
+
+    /// Extracts claims from a request carrying claims inserted by a layer, which is routed by
+    /// an embedded router, if `embedded` is set, and returns the status of the rejection.
+    async fn inserted_claims(embedded: bool) -> Result<Claims, StatusCode> {
+        let mut req = Request::new(());
+        _ = req
+            .extensions_mut()
+            .insert(Claims::new("test|subject", ["manage:self".into()]));
+        if embedded {
+            _ = req.extensions_mut().insert(Embedded);
+        }
+        Claims::from_request(&mut RequestParts::new(req))
+            .await
+            .map_err(|res| res.status())
+    }
+
+    #[async_std::test]
+    async fn inserted_claims_require_embedding() {
+        let claims = inserted_claims(true)
+            .await
+            .expect("embedded claims rejected");
+        assert_eq!(claims.subject(), "test|subject");
+
+        let status = inserted_claims(false)
+            .await
+            .expect_err("claims inserted outside of an embedded router accepted");
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
     }
 }
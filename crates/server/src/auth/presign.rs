@@ -0,0 +1,110 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::Clock;
+
+use std::fmt::Debug;
+use std::io::BufRead;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{ensure, Context};
+use async_std::sync::Arc;
+use axum::body::Body;
+use axum::http::{Request, Uri};
+use openidconnect::url::form_urlencoded;
+use ring::hmac;
+
+/// Signer of pre-signed URLs, which grant read access to the path they refer to without
+/// authentication until they expire.
+pub struct UrlSigner {
+    key: hmac::Key,
+}
+
+impl Debug for UrlSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UrlSigner").finish_non_exhaustive()
+    }
+}
+
+impl UrlSigner {
+    /// Minimum length of secrets in bytes
+    pub const MIN_SECRET_LENGTH: usize = 32;
+
+    /// Constructs a signer signing URLs with HMAC-SHA256 keyed by `secret`.
+    pub fn new(secret: &[u8]) -> anyhow::Result<Self> {
+        ensure!(
+            secret.len() >= Self::MIN_SECRET_LENGTH,
+            "URL signing secret must be at least {} bytes long",
+            Self::MIN_SECRET_LENGTH
+        );
+        Ok(Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+        })
+    }
+
+    /// Reads a Base64-encoded secret from `rd`, e.g. as generated by `openssl rand -base64 32`.
+    pub fn read(mut rd: impl BufRead) -> anyhow::Result<Self> {
+        let mut b64 = String::new();
+        _ = rd
+            .read_to_string(&mut b64)
+            .context("failed to read URL signing secret")?;
+        base64::decode(b64.trim())
+            .context("failed to decode URL signing secret")
+            .and_then(|secret| Self::new(&secret))
+    }
+
+    fn tag(&self, path: &str, expires: u64) -> hmac::Tag {
+        hmac::sign(&self.key, format!("{expires}\n{path}").as_bytes())
+    }
+
+    /// Returns `path` pre-signed until `expires`.
+    pub(crate) fn sign(&self, path: &str, expires: SystemTime) -> String {
+        let expires = expires
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("expires", &expires.to_string())
+            .append_pair(
+                "signature",
+                &base64::encode_config(self.tag(path, expires), base64::URL_SAFE_NO_PAD),
+            )
+            .finish();
+        format!("{path}?{query}")
+    }
+
+    /// Returns whether `uri` is pre-signed and not expired at `now`.
+    pub(crate) fn verify(&self, uri: &Uri, now: SystemTime) -> bool {
+        let mut expires = None;
+        let mut signature = None;
+        for (k, v) in form_urlencoded::parse(uri.query().unwrap_or("").as_bytes()) {
+            match k.as_ref() {
+                "expires" => expires = v.parse::<u64>().ok(),
+                "signature" => signature = base64::decode_config(&*v, base64::URL_SAFE_NO_PAD).ok(),
+                _ => {}
+            }
+        }
+        let (Some(expires), Some(signature)) = (expires, signature) else {
+            return false;
+        };
+        now <= UNIX_EPOCH + Duration::from_secs(expires)
+            && hmac::verify(
+                &self.key,
+                format!("{expires}\n{}", uri.path()).as_bytes(),
+                &signature,
+            )
+            .is_ok()
+    }
+}
+
+/// Returns whether `req` is pre-signed by the [UrlSigner] of the server, if any.
+pub(crate) fn is_presigned(req: &Request<Body>) -> bool {
+    let extensions = req.extensions();
+    match (
+        extensions.get::<Arc<UrlSigner>>(),
+        extensions.get::<Arc<dyn Clock>>(),
+    ) {
+        (Some(signer), Some(clock)) => signer.verify(req.uri(), clock.now()),
+        _ => false,
+    }
+}
@@ -0,0 +1,98 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use drawbridge_jose::jws::{Flattened, General, Jws};
+use drawbridge_type::{SignatureAlgorithm, SignatureBundle};
+
+use std::collections::BTreeMap;
+use std::io::BufRead;
+
+use anyhow::{bail, ensure, Context};
+use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_ASN1, ECDSA_P256_SHA256_FIXED};
+
+/// DER-encoded prefix of an ECDSA P-256 `SubjectPublicKeyInfo`, which precedes the
+/// uncompressed public key point.
+const P256_SPKI_PREFIX: [u8; 26] = [
+    0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a,
+    0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+];
+
+/// Public keys trusted for verification of tag signatures, indexed by name.
+///
+/// Only ECDSA P-256 keys, as generated by `cosign generate-key-pair`, are supported.
+#[derive(Clone, Debug, Default)]
+pub struct Keys(BTreeMap<String, Vec<u8>>);
+
+impl Keys {
+    /// Reads a PEM-encoded public key from `rd` and adds it to the set under `name`.
+    pub fn read(&mut self, name: impl Into<String>, mut rd: impl BufRead) -> anyhow::Result<()> {
+        let mut pem = String::new();
+        _ = rd
+            .read_to_string(&mut pem)
+            .context("failed to read public key")?;
+        let b64: String = pem
+            .lines()
+            .skip_while(|line| line.trim() != "-----BEGIN PUBLIC KEY-----")
+            .skip(1)
+            .take_while(|line| line.trim() != "-----END PUBLIC KEY-----")
+            .map(str::trim)
+            .collect();
+        if b64.is_empty() {
+            bail!("PEM-encoded public key not found")
+        }
+        let der = base64::decode(b64).context("failed to decode public key")?;
+        let point = der
+            .strip_prefix(&P256_SPKI_PREFIX[..])
+            .context("unsupported public key type, only ECDSA P-256 keys are supported")?;
+        ensure!(point.len() == 65, "invalid ECDSA P-256 public key length");
+        _ = self.0.insert(name.into(), point.to_vec());
+        Ok(())
+    }
+
+    /// Returns whether a key was added under `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    /// Returns names of all keys, which `signature` of `msg` is verified against.
+    pub fn verify(&self, msg: &[u8], signature: &[u8]) -> Vec<String> {
+        self.0
+            .iter()
+            .filter(|(_, key)| {
+                UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, key)
+                    .verify(msg, signature)
+                    .is_ok()
+            })
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Returns names of all keys, which any ES256 signature of the JWS of a signed tag is
+    /// verified against.
+    pub fn verify_jws(&self, jws: &Jws) -> anyhow::Result<Vec<String>> {
+        let payload = match jws {
+            Jws::General(General { payload, .. }) | Jws::Flattened(Flattened { payload, .. }) => {
+                payload.as_deref().context("JWS payload missing")?
+            }
+        };
+        let bundle = SignatureBundle::from_jws(jws, None)?;
+        let mut names = vec![];
+        for sig in bundle
+            .signatures
+            .iter()
+            .filter(|sig| sig.algorithm == SignatureAlgorithm::Es256)
+        {
+            let input = sig.signing_input(payload)?;
+            for (name, key) in &self.0 {
+                if !names.contains(name)
+                    && UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, key)
+                        .verify(&input, &sig.signature)
+                        .is_ok()
+                {
+                    names.push(name.clone());
+                }
+            }
+        }
+        Ok(names)
+    }
+}
@@ -0,0 +1,98 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::tls::{read_certificates, verify_client_certificate};
+
+use drawbridge_type::RepositoryContext;
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::BufRead;
+use std::time::SystemTime;
+
+use anyhow::{ensure, Context};
+use rustls::Certificate;
+use webpki::{DnsNameRef, TrustAnchor};
+
+/// Identity of an attested workload, which presented a client certificate issued by Steward.
+///
+/// Workloads are granted read access to the repositories assigned to their identity.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WorkloadIdentity {
+    /// Identities the certificate is valid for
+    pub identities: BTreeSet<String>,
+    pub repositories: HashSet<RepositoryContext>,
+}
+
+impl WorkloadIdentity {
+    /// Returns `true` if the workload is granted read access to repository `cx`.
+    pub fn can_read(&self, cx: &RepositoryContext) -> bool {
+        self.repositories.contains(cx)
+    }
+}
+
+/// Configuration of Steward CA integration.
+///
+/// Steward issues certificates to Enarx keeps after verifying their attestation evidence.
+/// The attested workload identity is a DNS name in the subject alternative name extension
+/// of the issued certificate, which is mapped to a set of repositories the workload may read.
+#[derive(Clone, Debug, Default)]
+pub struct Steward {
+    roots: Vec<Certificate>,
+    grants: BTreeMap<String, HashSet<RepositoryContext>>,
+}
+
+impl Steward {
+    /// Reads PEM-encoded Steward CA certificates from `rd`.
+    pub fn read(mut rd: impl BufRead) -> anyhow::Result<Self> {
+        let roots = read_certificates(&mut rd).context("failed to read Steward CA certificates")?;
+        ensure!(!roots.is_empty(), "no Steward CA certificates found");
+        for root in &roots {
+            _ = TrustAnchor::try_from_cert_der(&root.0)
+                .map_err(|e| anyhow::anyhow!("invalid Steward CA certificate: {e:?}"))?;
+        }
+        Ok(Self {
+            roots,
+            grants: Default::default(),
+        })
+    }
+
+    /// Grants workloads with `identity` read access to repository `cx`.
+    pub fn grant(
+        &mut self,
+        identity: impl Into<String>,
+        cx: RepositoryContext,
+    ) -> anyhow::Result<()> {
+        let identity = identity.into();
+        ensure!(
+            DnsNameRef::try_from_ascii_str(&identity).is_ok(),
+            "invalid workload identity `{identity}`, expected a DNS name"
+        );
+        _ = self.grants.entry(identity).or_default().insert(cx);
+        Ok(())
+    }
+
+    /// Returns the Steward CA certificates.
+    pub fn roots(&self) -> &[Certificate] {
+        &self.roots
+    }
+
+    /// Verifies that the certificate chain `certs` is issued by Steward and valid at `now`
+    /// and returns the identity of the workload it was issued to.
+    pub(crate) fn identify(
+        &self,
+        certs: &[Certificate],
+        now: SystemTime,
+    ) -> Option<WorkloadIdentity> {
+        let cert = verify_client_certificate(&self.roots, certs, now)?;
+
+        let mut workload = WorkloadIdentity::default();
+        for (identity, repositories) in &self.grants {
+            let name = DnsNameRef::try_from_ascii_str(identity).ok()?;
+            if cert.verify_is_valid_for_dns_name(name).is_ok() {
+                _ = workload.identities.insert(identity.clone());
+                workload.repositories.extend(repositories.iter().cloned());
+            }
+        }
+        Some(workload)
+    }
+}
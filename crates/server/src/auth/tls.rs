@@ -1,38 +1,98 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
+use super::Steward;
+
 use std::io::BufRead;
 use std::ops::Deref;
+use std::time::SystemTime;
 
 use anyhow::{anyhow, bail, Context};
 use rustls::server::AllowAnyAnonymousOrAuthenticatedClient;
-use rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use rustls::{Certificate, ClientConfig, PrivateKey, RootCertStore, ServerConfig};
 use rustls_pemfile::Item::{ECKey, PKCS8Key, RSAKey, X509Certificate};
+use webpki::{DnsNameRef, EndEntityCert, TlsClientTrustAnchors, TrustAnchor};
+
+/// Signature algorithms accepted in client certificates.
+static SIGNATURE_ALGORITHMS: &[&webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::ED25519,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+];
 
 #[derive(Clone, Copy, Debug)]
 #[repr(transparent)]
 pub struct TrustedCertificate;
 
-#[repr(transparent)]
+/// Marks requests of read replicas, which presented a [TrustedCertificate] valid for one of the
+/// replica identities configured by [Builder::replica_identity](crate::Builder::replica_identity).
+///
+/// Replicas may read all users and repositories and the change feed.
+#[derive(Clone, Copy, Debug)]
+pub struct ReplicaCertificate;
+
 #[allow(missing_debug_implementations)]
 #[derive(Clone)]
-pub struct Config(ServerConfig);
+pub struct Config {
+    server: ServerConfig,
+    /// CA certificates client certificates are trusted by, which excludes Steward CA certificates.
+    roots: Vec<Certificate>,
+}
 
 impl Deref for Config {
     type Target = ServerConfig;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.server
     }
 }
 
 impl From<Config> for ServerConfig {
     fn from(conf: Config) -> Self {
-        conf.0
+        conf.server
     }
 }
 
-fn read_certificates(mut rd: impl BufRead) -> anyhow::Result<Vec<Certificate>> {
+/// Verifies that the client certificate chain `certs` is issued by one of `roots` and valid at
+/// `now` and returns the end-entity certificate of the chain.
+pub(crate) fn verify_client_certificate<'a>(
+    roots: &[Certificate],
+    certs: &'a [Certificate],
+    now: SystemTime,
+) -> Option<EndEntityCert<'a>> {
+    let (cert, intermediates) = certs.split_first()?;
+    let cert = EndEntityCert::try_from(cert.0.as_slice()).ok()?;
+    let anchors = roots
+        .iter()
+        .map(|root| TrustAnchor::try_from_cert_der(&root.0))
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    let intermediates: Vec<_> = intermediates.iter().map(|cert| cert.0.as_slice()).collect();
+    let now = now.duration_since(SystemTime::UNIX_EPOCH).ok()?.as_secs();
+    cert.verify_is_valid_tls_client_cert(
+        SIGNATURE_ALGORITHMS,
+        &TlsClientTrustAnchors(&anchors),
+        &intermediates,
+        webpki::Time::from_seconds_since_unix_epoch(now),
+    )
+    .ok()?;
+    Some(cert)
+}
+
+/// Returns `true` if `cert` is valid for any of the DNS names `identities`.
+pub(crate) fn is_valid_for_any(cert: &EndEntityCert<'_>, identities: &[String]) -> bool {
+    identities
+        .iter()
+        .filter_map(|identity| DnsNameRef::try_from_ascii_str(identity).ok())
+        .any(|name| cert.verify_is_valid_for_dns_name(name).is_ok())
+}
+
+pub(super) fn read_certificates(mut rd: impl BufRead) -> anyhow::Result<Vec<Certificate>> {
     rustls_pemfile::read_all(&mut rd)?
         .into_iter()
         .map(|item| match item {
@@ -42,37 +102,60 @@ fn read_certificates(mut rd: impl BufRead) -> anyhow::Result<Vec<Certificate>> {
         .collect()
 }
 
+fn read_key(mut key: impl BufRead) -> anyhow::Result<PrivateKey> {
+    let mut items =
+        rustls_pemfile::read_all(&mut key).context("failed to read server certificate key")?;
+    let key = items
+        .pop()
+        .ok_or_else(|| anyhow!("server certificate key missing"))
+        .and_then(|item| match item {
+            RSAKey(buf) | PKCS8Key(buf) | ECKey(buf) => Ok(PrivateKey(buf)),
+            _ => bail!("unsupported key type"),
+        })?;
+    if !items.is_empty() {
+        bail!("more than one server certificate key specified")
+    }
+    Ok(key)
+}
+
+fn read_roots(cas: impl BufRead) -> anyhow::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    read_certificates(cas)
+        .context("failed to read CA certificates")?
+        .into_iter()
+        .try_for_each(|ref cert| roots.add(cert))
+        .context("failed to construct root certificate store")?;
+    Ok(roots)
+}
+
 impl Config {
-    pub fn read(
+    pub fn read(certs: impl BufRead, key: impl BufRead, cas: impl BufRead) -> anyhow::Result<Self> {
+        Self::read_with_steward(certs, key, cas, None)
+    }
+
+    /// Reads the config like [Self::read] and additionally accepts client certificates
+    /// issued by the `steward` CA.
+    pub fn read_with_steward(
         mut certs: impl BufRead,
         mut key: impl BufRead,
         mut cas: impl BufRead,
+        steward: Option<&Steward>,
     ) -> anyhow::Result<Self> {
         let certs =
             read_certificates(&mut certs).context("failed to read server certificate chain")?;
-        let key = {
-            let mut items = rustls_pemfile::read_all(&mut key)
-                .context("failed to read server certificate key")?;
-            let key = items
-                .pop()
-                .ok_or_else(|| anyhow!("server certificate key missing"))
-                .and_then(|item| match item {
-                    RSAKey(buf) | PKCS8Key(buf) | ECKey(buf) => Ok(PrivateKey(buf)),
-                    _ => bail!("unsupported key type"),
-                })?;
-            if !items.is_empty() {
-                bail!("more than one server certificate key specified")
-            }
-            key
-        };
+        let key = read_key(&mut key)?;
 
+        let cas = read_certificates(&mut cas).context("failed to read CA certificates")?;
         let client_verifier = {
             let mut roots = RootCertStore::empty();
-            read_certificates(&mut cas)
-                .context("failed to read CA certificates")?
-                .into_iter()
-                .try_for_each(|ref cert| roots.add(cert))
+            cas.iter()
+                .try_for_each(|cert| roots.add(cert))
                 .context("failed to construct root certificate store")?;
+            steward
+                .into_iter()
+                .flat_map(Steward::roots)
+                .try_for_each(|cert| roots.add(cert))
+                .context("failed to add Steward CA certificates to root certificate store")?;
             // TODO: Allow client certificates signed by unknown CAs.
             AllowAnyAnonymousOrAuthenticatedClient::new(roots)
         };
@@ -82,6 +165,29 @@ impl Config {
             .with_client_cert_verifier(client_verifier)
             .with_single_cert(certs, key)
             .context("invalid server certificate key")
-            .map(Self)
+            .map(|server| Self { server, roots: cas })
+    }
+
+    /// Returns the CA certificates client certificates are trusted by, excluding Steward.
+    pub fn roots(&self) -> &[Certificate] {
+        &self.roots
+    }
+
+    /// Reads the configuration of a client, which authenticates using the server certificate
+    /// chain `certs` and `key` to servers presenting certificates issued by `cas`, e.g. to
+    /// other servers of the same deployment.
+    pub fn read_client(
+        mut certs: impl BufRead,
+        key: impl BufRead,
+        cas: impl BufRead,
+    ) -> anyhow::Result<ClientConfig> {
+        let certs =
+            read_certificates(&mut certs).context("failed to read server certificate chain")?;
+        let key = read_key(key)?;
+        ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(read_roots(cas)?)
+            .with_single_cert(certs, key)
+            .context("invalid server certificate key")
     }
 }
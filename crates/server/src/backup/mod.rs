@@ -0,0 +1,386 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Scheduled incremental backups of a store.
+//!
+//! Backups are written to a [BackupTarget] every [BackupConfig::interval]. The first backup
+//! contains all users and repositories of the store, subsequent ones only the repositories
+//! and tags changed since the preceding backup, as recorded by the change journal, see
+//! [changes](super::changes). Each backup is named after the cursor of the last change it
+//! includes and consists of:
+//!
+//! - `<name>/users/<user>.json`: records of the users owning the contained repositories
+//! - `<name>/repos/<user>/<repo>.tar`: [snapshots](super::snapshots) of the repositories
+//!   containing their configs and changed tags along with their trees and attachments
+//!
+//! Backups are listed in `index.json` at the target, which is written last, so that an
+//! interrupted backup is repeated by the following run.
+//!
+//! # Restore
+//!
+//! [restore] imports all backups listed in the index in order into a store, creating missing
+//! users and repositories. Tags, which already exist, are completed with their nodes and
+//! attachments missing from the store, so that a restore may be repeated. A lost store is
+//! restored by running the server with the same backup target and `--restore` against an
+//! empty store directory before starting it.
+//!
+//! # Limitations
+//!
+//! Attachments added to a published tag and changes dropped by the [EventBus] are missing
+//! from the change journal, so they are only backed up along with a later change of the tag.
+//! Deletions are not backed up. Repository configs contain webhook secrets, so backups must be
+//! protected like the store. In clustered deployments, backups are run by the leader.
+
+mod target;
+
+pub use target::*;
+
+use super::changes::{Change, MAX_LIMIT};
+use super::cluster::Cluster;
+use super::events::EventBus;
+use super::snapshots::{export_tags, import_repository, open_store};
+use super::{Clock, GetError, Store, SystemClock};
+
+use drawbridge_type::{Meta, RepositoryContext, TagName, UserContext, UserRecord};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Context};
+use async_std::sync::Arc;
+use async_std::task::{sleep, spawn};
+use cap_async_std::path::Path;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, warn};
+
+/// Path of the index of backups at the target.
+const INDEX: &str = "index.json";
+
+/// Configuration of scheduled backups.
+#[derive(Clone, Debug)]
+pub struct BackupConfig {
+    /// Location backups are written to
+    pub target: BackupTarget,
+    /// Interval between backups
+    pub interval: Duration,
+}
+
+/// A backup listed in the index.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BackupRecord {
+    /// Name of the backup, which prefixes its contents at the target
+    pub name: String,
+    /// Cursor of the last change included by the preceding backup
+    pub since: u64,
+    /// Cursor of the last change included by the backup
+    pub until: u64,
+    /// Time the backup was taken in RFC 3339 format
+    pub time: String,
+    /// Whether the backup contains all users and repositories of the store
+    pub full: bool,
+    /// Users contained in the backup
+    pub users: Vec<String>,
+    /// Repositories contained in the backup, e.g. `user/repo`
+    pub repositories: Vec<String>,
+}
+
+/// Index of the backups at a target in the order they were taken.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct BackupIndex {
+    pub backups: Vec<BackupRecord>,
+}
+
+/// Summary of a restore.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct RestoreReport {
+    /// Number of backups imported
+    pub backups: usize,
+    /// Number of users created
+    pub users: usize,
+    /// Number of repositories created
+    pub repositories: usize,
+    /// Number of tags created
+    pub tags: usize,
+}
+
+/// A user record contained in a backup.
+#[derive(Deserialize, Serialize)]
+struct UserBackup {
+    #[serde(flatten)]
+    meta: Meta,
+    record: UserRecord,
+}
+
+async fn read_index(target: &BackupTarget, now: SystemTime) -> anyhow::Result<BackupIndex> {
+    match target.get(INDEX, now).await? {
+        Some(buf) => serde_json::from_slice(&buf).context("failed to decode backup index"),
+        None => Ok(Default::default()),
+    }
+}
+
+/// Returns the tags of repositories changed after cursor `since` up to cursor `until`.
+async fn changed(
+    store: &Store,
+    since: u64,
+    until: u64,
+) -> anyhow::Result<BTreeMap<String, BTreeSet<String>>> {
+    let mut repos: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut cursor = since;
+    while cursor < until {
+        let changes = store.read_changes::<Change>(cursor, MAX_LIMIT).await?;
+        if changes.is_empty() {
+            break;
+        }
+        let last = cursor;
+        for change in changes
+            .into_iter()
+            .take_while(|change| change.cursor <= until)
+        {
+            cursor = change.cursor;
+            // NOTE: Deletions are not backed up.
+            if change.event["event"] == "entity-deleted" {
+                continue;
+            }
+            let repo = match change.event["repository"].as_str() {
+                Some(repo) => repos.entry(repo.into()).or_default(),
+                None => continue,
+            };
+            if let Some(tag) = change.event["tag"].as_str() {
+                _ = repo.insert(tag.into());
+            }
+        }
+        if cursor == last {
+            break;
+        }
+    }
+    Ok(repos)
+}
+
+/// Backs up users and repositories of `store` changed since the last backup listed in the
+/// index at `target` at `now` and returns the backup, if anything changed.
+pub async fn backup(
+    store: &Store,
+    target: &BackupTarget,
+    now: SystemTime,
+) -> anyhow::Result<Option<BackupRecord>> {
+    let mut index = read_index(target, now).await?;
+    let until = store.last_change().await?;
+    let (since, full) = match index.backups.last() {
+        Some(last) if last.until >= until => return Ok(None),
+        Some(last) => (last.until, false),
+        None => (0, true),
+    };
+
+    // NOTE: `None` stands for all tags of the repository.
+    let (users, repos) = if full {
+        let mut users = BTreeSet::new();
+        let mut repos = BTreeMap::new();
+        for user in store.users().await? {
+            let cx = UserContext { name: user };
+            for repo in store.user(&cx).repositories().await? {
+                _ = repos.insert(format!("{cx}/{repo}"), None);
+            }
+            _ = users.insert(cx.to_string());
+        }
+        (users, repos)
+    } else {
+        let repos: BTreeMap<_, _> = changed(store, since, until)
+            .await?
+            .into_iter()
+            .map(|(repo, tags)| (repo, Some(tags)))
+            .collect();
+        let users = repos
+            .keys()
+            .filter_map(|repo| repo.split_once('/').map(|(owner, _)| owner.to_string()))
+            .collect();
+        (users, repos)
+    };
+
+    let name = format!("{until:020}");
+    let mut record = BackupRecord {
+        name: name.clone(),
+        since,
+        until,
+        time: DateTime::<Utc>::from(now).to_rfc3339_opts(SecondsFormat::Secs, true),
+        full,
+        users: vec![],
+        repositories: vec![],
+    };
+    for user in users {
+        let cx: UserContext = user.parse()?;
+        let entity = store.user(&cx);
+        let meta = match entity.get_meta().await {
+            Ok(meta) => meta,
+            Err(GetError::NotFound) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let content = entity.read_content().await?;
+        let record_json = serde_json::to_vec(&UserBackup {
+            meta,
+            record: serde_json::from_slice(&content)
+                .with_context(|| format!("failed to decode record of `{cx}`"))?,
+        })
+        .context("failed to encode user record")?;
+        target
+            .put(&format!("{name}/users/{cx}.json"), record_json, now)
+            .await?;
+        record.users.push(user);
+    }
+    for (repo, tags) in repos {
+        let cx: RepositoryContext = repo.parse()?;
+        let tags = tags
+            .map(|tags| {
+                tags.iter()
+                    .map(|tag| tag.parse())
+                    .collect::<Result<Vec<TagName>, _>>()
+            })
+            .transpose()
+            .with_context(|| format!("invalid tag of `{cx}` in change journal"))?;
+        let archive = match export_tags(store, &cx, tags.as_deref()).await {
+            Ok(archive) => archive,
+            Err(GetError::NotFound) => continue,
+            Err(e) => {
+                return Err(anyhow::Error::from(e).context(format!("failed to export `{cx}`")))
+            }
+        };
+        target
+            .put(&format!("{name}/repos/{cx}.tar"), archive, now)
+            .await?;
+        record.repositories.push(repo);
+    }
+
+    index.backups.push(record.clone());
+    target
+        .put(
+            INDEX,
+            serde_json::to_vec_pretty(&index).context("failed to encode backup index")?,
+            now,
+        )
+        .await?;
+    Ok(Some(record))
+}
+
+/// Restores all backups listed in the index at `target` into `store` at `now`, publishing
+/// changes on `events`.
+pub async fn restore(
+    store: &Store,
+    events: &EventBus,
+    target: &BackupTarget,
+    now: SystemTime,
+) -> anyhow::Result<RestoreReport> {
+    let index = read_index(target, now).await?;
+    let mut report = RestoreReport::default();
+    for BackupRecord {
+        name,
+        users,
+        repositories,
+        ..
+    } in index.backups
+    {
+        for user in users {
+            let cx: UserContext = user.parse()?;
+            let UserBackup { meta, record } = serde_json::from_slice(
+                &target.read(&format!("{name}/users/{cx}.json"), now).await?,
+            )
+            .with_context(|| format!("failed to decode record of `{cx}`"))?;
+            match store.user(&cx).get_meta().await {
+                Ok(_) => continue,
+                Err(GetError::NotFound) => {}
+                Err(e) => return Err(e.into()),
+            }
+            _ = store
+                .create_user(&cx, meta, &record)
+                .await
+                .map_err(|e| anyhow!("failed to create user `{cx}`: {e:?}"))?;
+            report.users += 1;
+        }
+        for repo in repositories {
+            let cx: RepositoryContext = repo.parse()?;
+            let archive = target.read(&format!("{name}/repos/{cx}.tar"), now).await?;
+            let import = import_repository(store, events, &cx, &archive)
+                .await
+                .with_context(|| format!("failed to restore `{cx}` from backup `{name}`"))?;
+            if import.created {
+                report.repositories += 1;
+            }
+            report.tags += import.imported.len();
+        }
+        debug!(target: "app::backup", "restored backup `{name}`");
+        report.backups += 1;
+    }
+    Ok(report)
+}
+
+/// Backs up the store at `path` to `target` like [backup].
+pub async fn backup_store(
+    path: impl AsRef<Path>,
+    target: &BackupTarget,
+) -> anyhow::Result<Option<BackupRecord>> {
+    let store = open_store(path.as_ref()).await?;
+    backup(&store, target, SystemClock.now()).await
+}
+
+/// Restores the store at `path` from `target` like [restore].
+///
+/// Since no server publishes the events of the restore, they are neither recorded in the
+/// change journal nor delivered to webhooks.
+pub async fn restore_store(
+    path: impl AsRef<Path>,
+    target: &BackupTarget,
+) -> anyhow::Result<RestoreReport> {
+    let store = open_store(path.as_ref()).await?;
+    restore(&store, &EventBus::default(), target, SystemClock.now()).await
+}
+
+/// Runner of scheduled backups of a store.
+#[derive(Debug)]
+pub(crate) struct Backups {
+    config: BackupConfig,
+    cluster: Option<Arc<Cluster>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Backups {
+    pub(crate) fn new(
+        config: BackupConfig,
+        cluster: Option<Arc<Cluster>>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            config,
+            cluster,
+            clock,
+        }
+    }
+
+    /// Backs up `store` every [BackupConfig::interval] in background.
+    pub(crate) fn schedule(self: &Arc<Self>, store: &Arc<Store>) {
+        let backups = Arc::clone(self);
+        let store = Arc::clone(store);
+        _ = spawn(async move {
+            loop {
+                if backups
+                    .cluster
+                    .as_ref()
+                    .is_none_or(|cluster| cluster.is_leader())
+                {
+                    match backup(&store, &backups.config.target, backups.clock.now()).await {
+                        Ok(Some(record)) => info!(
+                            target: "app::backup",
+                            "backed up {} repositories up to change {} as `{}`",
+                            record.repositories.len(),
+                            record.until,
+                            record.name
+                        ),
+                        Ok(None) => {
+                            debug!(target: "app::backup", "nothing changed since last backup")
+                        }
+                        Err(e) => warn!(target: "app::backup", "failed to back up store: {:?}", e),
+                    }
+                }
+                sleep(backups.config.interval).await;
+            }
+        });
+    }
+}
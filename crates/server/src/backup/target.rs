@@ -0,0 +1,202 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use drawbridge_type::digest::hex;
+
+use std::io::{ErrorKind, Read};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use anyhow::{bail, Context};
+use async_std::fs;
+use async_std::task::spawn_blocking;
+use chrono::{DateTime, Utc};
+use openidconnect::url::Url;
+use ring::digest::{digest, SHA256};
+use ring::hmac;
+use uuid::Uuid;
+
+/// A bucket of an S3-compatible object store backups are written to.
+#[derive(Clone)]
+pub struct S3Target {
+    /// URL of the bucket addressed path-style, optionally followed by a key prefix, e.g.
+    /// `https://s3.example.com/bucket/drawbridge`
+    pub url: Url,
+    /// Region of the bucket, e.g. `us-east-1`
+    pub region: String,
+    /// Access key ID used to sign requests
+    pub access_key: String,
+    /// Secret access key used to sign requests
+    pub secret_key: String,
+}
+
+impl std::fmt::Debug for S3Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("S3Target")
+            .field("url", &self.url.as_str())
+            .field("region", &self.region)
+            .field("access_key", &self.access_key)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Location backups are written to.
+#[derive(Clone, Debug)]
+pub enum BackupTarget {
+    /// A directory on the local file system
+    Local(PathBuf),
+    /// A bucket of an S3-compatible object store
+    S3(S3Target),
+}
+
+/// Encodes object key `s` as required by AWS Signature Version 4.
+fn uri_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            b'/' => "/".into(),
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+impl S3Target {
+    /// Returns a request of object `key` signed using AWS Signature Version 4 at `now`.
+    fn request(&self, method: &str, key: &str, body: &[u8], now: SystemTime) -> ureq::Request {
+        let path = format!(
+            "{}/{}",
+            self.url.path().trim_end_matches('/'),
+            uri_encode(key)
+        );
+        let host = match (self.url.host_str(), self.url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.into(),
+            (None, _) => String::new(),
+        };
+        let time = DateTime::<Utc>::from(now);
+        let date = time.format("%Y%m%d").to_string();
+        let timestamp = time.format("%Y%m%dT%H%M%SZ").to_string();
+        let payload = hex(digest(&SHA256, body).as_ref());
+
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{method}\n{path}\n\nhost:{host}\nx-amz-content-sha256:{payload}\nx-amz-date:{timestamp}\n\n{signed_headers}\n{payload}"
+        );
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+            hex(digest(&SHA256, canonical_request.as_bytes()).as_ref())
+        );
+        let key = [date.as_str(), &self.region, "s3", "aws4_request"]
+            .into_iter()
+            .fold(
+                hmac::Key::new(
+                    hmac::HMAC_SHA256,
+                    format!("AWS4{}", self.secret_key).as_bytes(),
+                ),
+                |key, part| {
+                    hmac::Key::new(
+                        hmac::HMAC_SHA256,
+                        hmac::sign(&key, part.as_bytes()).as_ref(),
+                    )
+                },
+            );
+        let signature = hex(hmac::sign(&key, string_to_sign.as_bytes()).as_ref());
+
+        let mut url = self.url.clone();
+        url.set_path(&path);
+        ureq::request(method, url.as_str())
+            .set("X-Amz-Content-Sha256", &payload)
+            .set("X-Amz-Date", &timestamp)
+            .set(
+                "Authorization",
+                &format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+                    self.access_key
+                ),
+            )
+    }
+
+    fn put(&self, key: &str, body: &[u8], now: SystemTime) -> anyhow::Result<()> {
+        _ = self
+            .request("PUT", key, body, now)
+            .send_bytes(body)
+            .with_context(|| format!("failed to upload `{key}`"))?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str, now: SystemTime) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.request("GET", key, &[], now).call() {
+            Ok(res) => {
+                let mut buf = vec![];
+                _ = res
+                    .into_reader()
+                    .read_to_end(&mut buf)
+                    .with_context(|| format!("failed to download `{key}`"))?;
+                Ok(Some(buf))
+            }
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(anyhow::Error::new(e).context(format!("failed to download `{key}`"))),
+        }
+    }
+}
+
+impl BackupTarget {
+    /// Writes object `key`, replacing it if it exists, at `now`.
+    pub(super) async fn put(
+        &self,
+        key: &str,
+        body: Vec<u8>,
+        now: SystemTime,
+    ) -> anyhow::Result<()> {
+        match self {
+            Self::Local(root) => {
+                let path = root.join(key);
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)
+                        .await
+                        .with_context(|| format!("failed to create directory of `{key}`"))?;
+                }
+                // NOTE: Objects are renamed into place, so that they are never read partially.
+                let tmp = path.with_extension(format!("{}.tmp", Uuid::new_v4()));
+                fs::write(&tmp, body)
+                    .await
+                    .with_context(|| format!("failed to write `{key}`"))?;
+                fs::rename(&tmp, &path)
+                    .await
+                    .with_context(|| format!("failed to rename `{key}`"))
+            }
+            Self::S3(s3) => {
+                let s3 = s3.clone();
+                let key = key.to_string();
+                spawn_blocking(move || s3.put(&key, &body, now)).await
+            }
+        }
+    }
+
+    /// Reads object `key` at `now`, if it exists.
+    pub(super) async fn get(&self, key: &str, now: SystemTime) -> anyhow::Result<Option<Vec<u8>>> {
+        match self {
+            Self::Local(root) => match fs::read(root.join(key)).await {
+                Ok(buf) => Ok(Some(buf)),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e).with_context(|| format!("failed to read `{key}`")),
+            },
+            Self::S3(s3) => {
+                let s3 = s3.clone();
+                let key = key.to_string();
+                spawn_blocking(move || s3.get(&key, now)).await
+            }
+        }
+    }
+
+    /// Reads object `key` at `now`, which must exist.
+    pub(super) async fn read(&self, key: &str, now: SystemTime) -> anyhow::Result<Vec<u8>> {
+        match self.get(key, now).await? {
+            Some(buf) => Ok(buf),
+            None => bail!("`{key}` missing"),
+        }
+    }
+}
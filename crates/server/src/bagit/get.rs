@@ -0,0 +1,117 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{Clock, Store, TrustedCertificate};
+use crate::auth::{assert_repository_read, pull_tag};
+use crate::hash::sha256;
+use crate::stream::{spawn_chunks, CHUNK_SIZE};
+use crate::tar::{self, TAR_TYPE};
+
+use drawbridge_type::digest::hex;
+use drawbridge_type::TagContext;
+
+use std::fmt::Write;
+
+use async_std::sync::Arc;
+use axum::body::{Body, StreamBody};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::Request;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use ring::digest::{Context, SHA256};
+use tracing::{debug, trace};
+
+/// Exports the tree of the tag as a BagIt bag packaged in a tar archive.
+///
+/// The bag is contained in a `<repository>-<tag>` directory and the tree is its payload.
+/// Payload files are streamed as they are read, followed by the tag files, which list them.
+pub async fn get(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref clock): Extension<Arc<dyn Clock>>,
+    cert: Option<Extension<TrustedCertificate>>,
+    cx: TagContext,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::bagit::get", "called for `{cx}`");
+
+    let repo = if cert.is_none() {
+        assert_repository_read(store, &cx.repository, req)
+            .await
+            .map_err(IntoResponse::into_response)
+            .map(|(repo, _)| repo)?
+    } else {
+        store.repository(&cx.repository)
+    };
+    let (tag, _) = pull_tag(&repo, &cx.name, cert.as_deref())
+        .await
+        .map_err(|e| {
+            debug!(target: "app::bagit::get", "failed for `{cx}`: {:?}", e);
+            e.into_response()
+        })?;
+    let nodes = tag.walk().await.map_err(|e| {
+        debug!(target: "app::bagit::get", "failed to walk tree of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+
+    let bag = format!("{}-{}", cx.repository.name, cx.name);
+    let date = DateTime::<Utc>::from(clock.now())
+        .format("%Y-%m-%d")
+        .to_string();
+    let store = Arc::clone(store);
+    let body = spawn_chunks(move |tx| async move {
+        let tag = store.tag(&cx);
+        let mut archive = tar::Builder::default();
+        let mut manifest = String::new();
+        let (mut octets, mut count) = (0, 0);
+        let mut nodes = tag.read_nodes(nodes);
+        while let Some((path, content)) = nodes.try_next().await.map_err(|e| {
+            debug!(target: "app::bagit::get", "failed to read tree of `{cx}`: {:?}", e);
+            anyhow::Error::from(e)
+        })? {
+            let content = match content {
+                Some(content) => content,
+                None => continue,
+            };
+            let size = content.size();
+            archive.start_file(&format!("{bag}/data/{path}"), size);
+            let mut hash = Context::new(&SHA256);
+            let mut chunks = content.chunks(CHUNK_SIZE);
+            while let Some(chunk) = chunks.try_next().await.map_err(|e| {
+                debug!(target: "app::bagit::get", "failed to read `{path}` of `{cx}`: {:?}", e);
+                anyhow::Error::from(e)
+            })? {
+                hash.update(&chunk);
+                archive.append_data(&chunk);
+                tx.send(archive.take()).await?;
+            }
+            archive.end_file(size);
+            // NOTE: Writing to a `String` cannot fail.
+            _ = writeln!(manifest, "{}  data/{path}", hex(hash.finish()));
+            octets += size;
+            count += 1;
+        }
+
+        let bagit = "BagIt-Version: 1.0\nTag-File-Character-Encoding: UTF-8\n";
+        let bag_info = format!(
+            "Bagging-Date: {date}\nExternal-Identifier: {}\nPayload-Oxum: {octets}.{count}\n",
+            cx,
+        );
+        let mut tag_manifest = String::new();
+        for (name, content) in [
+            ("bagit.txt", bagit),
+            ("bag-info.txt", &bag_info),
+            ("manifest-sha256.txt", &manifest),
+        ] {
+            _ = writeln!(tag_manifest, "{}  {name}", sha256(content.as_bytes()));
+            archive.append(&format!("{bag}/{name}"), content.as_bytes());
+        }
+        archive.append(
+            &format!("{bag}/tagmanifest-sha256.txt"),
+            tag_manifest.as_bytes(),
+        );
+        tx.send(archive.finish()).await
+    });
+    Ok::<_, Response>(([(CONTENT_TYPE, TAR_TYPE)], StreamBody::new(body)))
+}
@@ -0,0 +1,10 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! [BagIt] export of tag trees.
+//!
+//! [BagIt]: https://www.rfc-editor.org/rfc/rfc8493
+
+mod get;
+
+pub use get::*;
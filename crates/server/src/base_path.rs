@@ -0,0 +1,138 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Mounting of the application under a path prefix, e.g. behind a path-routing ingress.
+//!
+//! The prefix is stripped from paths of requests before they are routed, so that handlers are
+//! unaware of it, and requests outside of it are rejected. Root-relative targets of `Link` and
+//! `Location` headers of responses are prefixed with it. Handlers generating URLs elsewhere,
+//! e.g. in response bodies, prefix them using the [BasePath] request extension.
+
+use super::links;
+
+use anyhow::{bail, ensure};
+use async_std::sync::Arc;
+use axum::http::header::LOCATION;
+use axum::http::uri::PathAndQuery;
+use axum::http::{HeaderValue, Request, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use tracing::trace;
+
+/// Path prefix the application is mounted under, e.g. `/drawbridge`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BasePath(String);
+
+impl BasePath {
+    /// Validates `path`, which must start with a slash and must not end with one.
+    pub fn new(path: impl Into<String>) -> anyhow::Result<Self> {
+        let path = path.into();
+        ensure!(
+            path.starts_with('/'),
+            "base path `{path}` must start with `/`"
+        );
+        ensure!(
+            path.len() > 1 && !path.ends_with('/'),
+            "base path `{path}` must not end with `/`"
+        );
+        for segment in path[1..].split('/') {
+            if segment.is_empty() || segment == "." || segment == ".." {
+                bail!("invalid segment `{segment}` of base path `{path}`");
+            }
+            ensure!(
+                segment
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~')),
+                "invalid segment `{segment}` of base path `{path}`"
+            );
+        }
+        Ok(Self(path))
+    }
+
+    /// Returns the prefix.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns root-relative `path` prefixed with the base path.
+    pub(crate) fn join(&self, path: &str) -> String {
+        format!("{}{path}", self.0)
+    }
+
+    /// Returns `path` relative to the base path, if it is within it.
+    fn strip<'a>(&self, path: &'a str) -> Option<&'a str> {
+        match path.strip_prefix(self.0.as_str())? {
+            "" => Some("/"),
+            rest if rest.starts_with('/') => Some(rest),
+            _ => None,
+        }
+    }
+}
+
+/// Returns `path` prefixed with `base`, if any.
+pub(crate) fn join(base: Option<&BasePath>, path: &str) -> String {
+    match base {
+        Some(base) => base.join(path),
+        None => path.into(),
+    }
+}
+
+/// Prefixes root-relative targets in the value of a `Link` header with `base`.
+fn prefix_links(base: &BasePath, links: &str) -> String {
+    links
+        .split("</")
+        .enumerate()
+        .map(|(i, part)| match i {
+            0 => part.into(),
+            _ if part.starts_with('/') => format!("</{part}"),
+            _ => format!("<{}/{part}", base.as_str()),
+        })
+        .collect()
+}
+
+/// Strips the base path from the path of requests, rejecting requests outside of it, and
+/// prefixes root-relative targets of `Link` and `Location` response headers with it.
+pub(crate) async fn handle<B>(mut req: Request<B>, next: Next<B>) -> Response {
+    let base = match req.extensions().get::<Arc<BasePath>>() {
+        Some(base) => Arc::clone(base),
+        None => return next.run(req).await,
+    };
+    let uri = req.uri();
+    let path = match base.strip(uri.path()) {
+        Some(path) => path,
+        None => {
+            trace!(target: "app::base_path", "`{}` is outside of base path", uri.path());
+            return (
+                StatusCode::NOT_FOUND,
+                format!("Route `{}` not found", uri.path()),
+            )
+                .into_response();
+        }
+    };
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path.into(),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = match path_and_query.parse::<PathAndQuery>() {
+        Ok(path_and_query) => Some(path_and_query),
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid request path").into_response(),
+    };
+    *req.uri_mut() = match Uri::from_parts(parts) {
+        Ok(uri) => uri,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid request path").into_response(),
+    };
+    _ = req.extensions_mut().insert(base.as_ref().clone());
+
+    let mut res = next.run(req).await;
+    let headers = res.headers_mut();
+    if let Some(location) = headers.get(LOCATION).and_then(|v| v.to_str().ok()) {
+        if location.starts_with('/') && !location.starts_with("//") {
+            if let Ok(location) = HeaderValue::try_from(base.join(location)) {
+                _ = headers.insert(LOCATION, location);
+            }
+        }
+    }
+    links::rewrite(headers, |v| prefix_links(&base, v));
+    res
+}
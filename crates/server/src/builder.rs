@@ -1,16 +1,62 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::{handle, App, Store, TlsConfig};
+use super::access_log::{self, AccessLog, AccessLogConfig};
+use super::admission::Admission;
+use super::alerts::Alerts;
+use super::auth::Embedded;
+use super::backup::{BackupConfig, Backups};
+use super::base_path::{self, BasePath};
+use super::changes::ChangeLog;
+use super::cluster::{Cluster, ClusterConfig};
+use super::delegation::{self, Delegation};
+use super::downloads::Downloads;
+use super::events::{self, EventBus, NatsConfig};
+use super::federation::{self, Federation};
+#[cfg(feature = "graphql")]
+use super::graphql;
+use super::hosts::{self, VanityHosts};
+use super::keys::{Kms, ManagedKeys};
+use super::migration::{self, Migration, MigrationConfig};
+use super::quota::{self, RateLimit, RateLimiter};
+use super::recent_errors::{self, RecentErrors};
+use super::repair::Repairer;
+use super::replay::{Nonces, ReplayProtection};
+use super::replica::{self, Replica, ReplicaConfig};
+use super::replication::{Replication, Replicator};
+use super::scan::Scanners;
+use super::search::SearchIndex;
+use super::seed;
+use super::slow_log::{self, SlowLogConfig};
+use super::tenant::{TenantConfig, Tenants};
+use super::trash::{self, TrashPolicy};
+use super::tuf::{Tuf, TufConfig};
+#[cfg(feature = "ui")]
+use super::ui;
+use super::uploads::{self, UploadPolicy};
+use super::usage::{self, Accounting};
+use super::webhooks::Webhooks;
+use super::{
+    cbor, conditional, handle, retention, s3, App, CachePolicy, Clock, HotCache, Placement,
+    ProxyRegistries, SignatureKeys, Steward, Store, SystemClock, TlsConfig, UrlSigner,
+    VerificationPolicy,
+};
+
+use drawbridge_type::NamespaceRules;
 
-use anyhow::{anyhow, Context};
+use anyhow::{anyhow, bail, ensure, Context};
 use async_std::fs::File;
 use async_std::sync::Arc;
 use axum::handler::Handler;
+use axum::middleware;
 use axum::routing::any;
+#[cfg(feature = "ui")]
+use axum::routing::get;
+#[cfg(feature = "graphql")]
+use axum::routing::post;
 use axum::{Extension, Router};
 use cap_async_std::fs_utf8::Dir;
-use cap_async_std::path::Path;
+use cap_async_std::path::{Path, PathBuf};
 use futures::lock::Mutex;
 use futures::TryFutureExt;
 use futures_rustls::TlsAcceptor;
@@ -23,6 +69,7 @@ use tower_http::{
     LatencyUnit,
 };
 use tracing::Level;
+use webpki::DnsNameRef;
 
 /// OpenID Connect client configuration.
 #[derive(Debug)]
@@ -52,15 +99,87 @@ impl<B> tower_http::trace::MakeSpan<B> for SpanMaker {
 /// [App] builder.
 pub struct Builder<S> {
     store: S,
-    tls: TlsConfig,
-    oidc: OidcConfig,
+    store_instance: Option<Arc<Store>>,
+    events: Option<Arc<EventBus>>,
+    tls: Option<TlsConfig>,
+    oidc: Option<OidcConfig>,
+    signature_keys: SignatureKeys,
+    proxy_registries: ProxyRegistries,
+    federation: Federation,
+    delegation: Delegation,
+    replication: Replication,
+    replica: Option<ReplicaConfig>,
+    replica_identities: Vec<String>,
+    placement: Placement,
+    namespace_rules: NamespaceRules,
+    admission: Admission,
+    cluster: Option<ClusterConfig>,
+    steward: Option<Steward>,
+    cache_policy: CachePolicy,
+    verification_policy: VerificationPolicy,
+    trash_policy: TrashPolicy,
+    upload_policy: UploadPolicy,
+    scanners: Scanners,
+    tuf: Option<TufConfig>,
+    kms: Option<Arc<dyn Kms>>,
+    url_signer: Option<Arc<UrlSigner>>,
+    replay_protection: Option<ReplayProtection>,
+    hot_cache: HotCache,
+    clock: Arc<dyn Clock>,
+    nats: Option<NatsConfig>,
+    access_log: Option<AccessLogConfig>,
+    slow_log: Option<SlowLogConfig>,
+    alerts: Alerts,
+    tenants: Vec<TenantConfig>,
+    quota: Option<u64>,
+    rate_limit: Option<RateLimit>,
+    backup: Option<BackupConfig>,
+    migration: Option<MigrationConfig>,
+    seed: Option<PathBuf>,
+    base_path: Option<String>,
 }
 
 impl<S: std::fmt::Debug> std::fmt::Debug for Builder<S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Builder")
             .field("store", &self.store)
+            .field("store_instance", &self.store_instance)
+            .field("events", &self.events)
             .field("oidc", &self.oidc)
+            .field("signature_keys", &self.signature_keys)
+            .field("proxy_registries", &self.proxy_registries)
+            .field("federation", &self.federation)
+            .field("delegation", &self.delegation)
+            .field("replication", &self.replication)
+            .field("replica", &self.replica)
+            .field("replica_identities", &self.replica_identities)
+            .field("placement", &self.placement)
+            .field("namespace_rules", &self.namespace_rules)
+            .field("admission", &self.admission)
+            .field("cluster", &self.cluster)
+            .field("steward", &self.steward)
+            .field("cache_policy", &self.cache_policy)
+            .field("verification_policy", &self.verification_policy)
+            .field("trash_policy", &self.trash_policy)
+            .field("upload_policy", &self.upload_policy)
+            .field("scanners", &self.scanners)
+            .field("tuf", &self.tuf)
+            .field("kms", &self.kms)
+            .field("url_signer", &self.url_signer)
+            .field("replay_protection", &self.replay_protection)
+            .field("hot_cache", &self.hot_cache)
+            .field("clock", &self.clock)
+            .field("nats", &self.nats)
+            .field("access_log", &self.access_log)
+            .field("slow_log", &self.slow_log)
+            .field("alerts", &self.alerts)
+            .field("tenants", &self.tenants)
+            .field("quota", &self.quota)
+            .field("rate_limit", &self.rate_limit)
+            .field("backup", &self.backup)
+            .field("migration", &self.migration)
+            .field("seed", &self.seed)
+            .field("base_path", &self.base_path)
             .finish()
     }
 }
@@ -68,55 +187,869 @@ impl<S: std::fmt::Debug> std::fmt::Debug for Builder<S> {
 impl<S: AsRef<Path>> Builder<S> {
     /// Constructs a new [Builder].
     pub fn new(store: S, tls: TlsConfig, oidc: OidcConfig) -> Self {
-        Self { store, tls, oidc }
+        Self::init(store, Some(tls), Some(oidc))
+    }
+
+    /// Constructs a new [Builder] of an application embedded into another axum application,
+    /// which serves the [Router] returned by [Self::build_router] itself.
+    ///
+    /// Clients are not authenticated, unless OpenID Connect tokens are verified as configured
+    /// by [Self::oidc] or a layer of the embedding application authenticates them by inserting
+    /// their [OidcClaims](crate::OidcClaims) into requests.
+    ///
+    /// Inserted claims are trusted as they are, so the router must only ever be served by the
+    /// embedding application behind its authenticating layers and never be mounted behind a
+    /// listener of its own, which is reachable by clients. Use [Self::new] and [Self::build] to
+    /// serve the application to clients directly instead.
+    pub fn embedded(store: S) -> Self {
+        Self::init(store, None, None)
+    }
+
+    fn init(store: S, tls: Option<TlsConfig>, oidc: Option<OidcConfig>) -> Self {
+        Self {
+            store,
+            store_instance: None,
+            events: None,
+            tls,
+            oidc,
+            signature_keys: Default::default(),
+            proxy_registries: Default::default(),
+            federation: Default::default(),
+            delegation: Default::default(),
+            replication: Default::default(),
+            replica: None,
+            replica_identities: vec![],
+            placement: Default::default(),
+            namespace_rules: Default::default(),
+            admission: Default::default(),
+            cluster: None,
+            steward: None,
+            cache_policy: Default::default(),
+            verification_policy: Default::default(),
+            trash_policy: Default::default(),
+            upload_policy: Default::default(),
+            scanners: Default::default(),
+            tuf: None,
+            kms: None,
+            url_signer: None,
+            replay_protection: None,
+            hot_cache: Default::default(),
+            clock: Arc::new(SystemClock),
+            nats: None,
+            access_log: None,
+            slow_log: None,
+            alerts: Default::default(),
+            tenants: vec![],
+            quota: None,
+            rate_limit: None,
+            backup: None,
+            migration: None,
+            seed: None,
+            base_path: None,
+        }
+    }
+
+    /// Verifies OpenID Connect tokens of clients as configured by `oidc`.
+    pub fn oidc(self, oidc: OidcConfig) -> Self {
+        Self {
+            oidc: Some(oidc),
+            ..self
+        }
+    }
+
+    /// Serves `store` as the default store instead of opening the one at the path the
+    /// builder was constructed with, e.g. to share it with an embedding application.
+    pub fn store_instance(self, store: Arc<Store>) -> Self {
+        Self {
+            store_instance: Some(store),
+            ..self
+        }
+    }
+
+    /// Publishes events of the default store to `events`, e.g. so that an embedding
+    /// application may subscribe to them.
+    pub fn events(self, events: Arc<EventBus>) -> Self {
+        Self {
+            events: Some(events),
+            ..self
+        }
+    }
+
+    /// Sets the public keys, which tag signatures are verified against.
+    pub fn signature_keys(self, signature_keys: SignatureKeys) -> Self {
+        Self {
+            signature_keys,
+            ..self
+        }
+    }
+
+    /// Sets the upstream OCI registries, which are proxied under `_proxy/<namespace>`.
+    pub fn proxy_registries(self, proxy_registries: ProxyRegistries) -> Self {
+        Self {
+            proxy_registries,
+            ..self
+        }
+    }
+
+    /// Sets the upstream drawbridge instances, which tags of federated namespaces missing
+    /// locally are pulled from and cached in the store.
+    pub fn federation(self, federation: Federation) -> Self {
+        Self { federation, ..self }
+    }
+
+    /// Sets the drawbridge instances namespaces are delegated to, which requests under
+    /// delegated namespaces are redirected or proxied to.
+    pub fn delegation(self, delegation: Delegation) -> Self {
+        Self { delegation, ..self }
+    }
+
+    /// Sets the peers and the rules assigning namespaces to them, which tags are pushed to.
+    pub fn replication(self, replication: Replication) -> Self {
+        Self {
+            replication,
+            ..self
+        }
+    }
+
+    /// Runs the server as a read replica of a primary, which tails the change feed of the
+    /// primary and redirects writes to it.
+    ///
+    /// Replicas cannot host tenants.
+    pub fn replica(self, replica: ReplicaConfig) -> Self {
+        Self {
+            replica: Some(replica),
+            ..self
+        }
+    }
+
+    /// Grants read replicas presenting a client certificate issued by the trusted CA, which is
+    /// valid for the DNS name `identity`, read access to all users and repositories and to the
+    /// change feed.
+    ///
+    /// May be called multiple times. Other trusted certificates are not granted such access.
+    pub fn replica_identity(mut self, identity: impl Into<String>) -> Self {
+        self.replica_identities.push(identity.into());
+        self
+    }
+
+    /// Sets the region of the server and the regions namespaces are pinned to, which are
+    /// enforced on repository creation and replication.
+    pub fn placement(self, placement: Placement) -> Self {
+        Self { placement, ..self }
+    }
+
+    /// Sets the rules user and repository names must satisfy, which are enforced on all
+    /// requests in addition to the syntax of names, [NamespaceRules::default] by default.
+    pub fn namespace_rules(self, namespace_rules: NamespaceRules) -> Self {
+        Self {
+            namespace_rules,
+            ..self
+        }
+    }
+
+    /// Sets the policies, which repository creation and tag publication must be admitted by.
+    pub fn admission(self, admission: Admission) -> Self {
+        Self { admission, ..self }
+    }
+
+    /// Runs the server as an instance of a cluster, which shares its stores with other
+    /// instances and coordinates with them through leases kept in the stores.
+    pub fn cluster(self, cluster: ClusterConfig) -> Self {
+        Self {
+            cluster: Some(cluster),
+            ..self
+        }
+    }
+
+    /// Sets the Steward CA integration, which grants workloads presenting client certificates
+    /// issued by Steward read access to repositories assigned to their identity.
+    ///
+    /// Note, that the [TlsConfig] must be constructed using [TlsConfig::read_with_steward]
+    /// for such certificates to be accepted.
+    pub fn steward(self, steward: Steward) -> Self {
+        Self {
+            steward: Some(steward),
+            ..self
+        }
+    }
+
+    /// Sets the caching policy of responses.
+    pub fn cache_policy(self, cache_policy: CachePolicy) -> Self {
+        Self {
+            cache_policy,
+            ..self
+        }
+    }
+
+    /// Sets the policy of soft deletion of repositories and tags.
+    pub fn trash_policy(self, trash_policy: TrashPolicy) -> Self {
+        Self {
+            trash_policy,
+            ..self
+        }
+    }
+
+    /// Sets the policy of sessions uploading trees to tags, which are not published yet.
+    pub fn upload_policy(self, upload_policy: UploadPolicy) -> Self {
+        Self {
+            upload_policy,
+            ..self
+        }
+    }
+
+    /// Sets the verification policy of content digests of uploaded tree nodes.
+    pub fn verification_policy(self, verification_policy: VerificationPolicy) -> Self {
+        Self {
+            verification_policy,
+            ..self
+        }
+    }
+
+    /// Sets the content scanners, which repositories may select to scan uploaded tree nodes.
+    pub fn scanners(self, scanners: Scanners) -> Self {
+        Self { scanners, ..self }
+    }
+
+    /// Sets the keys TUF metadata of published tags is signed with, which enables serving it
+    /// under `_tuf/`.
+    pub fn tuf(self, tuf: TufConfig) -> Self {
+        Self {
+            tuf: Some(tuf),
+            ..self
+        }
+    }
+
+    /// Sets the key management service private keys of server-managed repository signing
+    /// keys are wrapped with, which enables managing them under `_keys`.
+    pub fn kms(self, kms: impl 'static + Kms) -> Self {
+        Self {
+            kms: Some(Arc::new(kms)),
+            ..self
+        }
+    }
+
+    /// Sets the signer of pre-signed download URLs, which enables pre-signing URLs of download
+    /// manifests.
+    pub fn url_signer(self, url_signer: UrlSigner) -> Self {
+        Self {
+            url_signer: Some(Arc::new(url_signer)),
+            ..self
+        }
+    }
+
+    /// Requires signed tag entries verified against signing policies of repositories to be
+    /// bound to a single publication, see [ReplayProtection].
+    pub fn replay_protection(self, replay_protection: ReplayProtection) -> Self {
+        Self {
+            replay_protection: Some(replay_protection),
+            ..self
+        }
+    }
+
+    /// Sets the in-process cache of contents of frequently requested entities.
+    pub fn hot_cache(self, hot_cache: HotCache) -> Self {
+        Self { hot_cache, ..self }
+    }
+
+    /// Sets the source of the current time, [SystemClock] by default.
+    pub fn clock(self, clock: impl 'static + Clock) -> Self {
+        Self {
+            clock: Arc::new(clock),
+            ..self
+        }
+    }
+
+    /// Sets the NATS server, which all events are forwarded to.
+    pub fn nats(self, nats: NatsConfig) -> Self {
+        Self {
+            nats: Some(nats),
+            ..self
+        }
+    }
+
+    /// Sets the access log, which all requests are logged to.
+    pub fn access_log(self, access_log: AccessLogConfig) -> Self {
+        Self {
+            access_log: Some(access_log),
+            ..self
+        }
+    }
+
+    /// Sets the thresholds, above which requests are logged as slow or large.
+    pub fn slow_log(self, slow_log: SlowLogConfig) -> Self {
+        Self {
+            slow_log: Some(slow_log),
+            ..self
+        }
+    }
+
+    /// Sets the dispatcher of alerts on integrity failures of stored contents.
+    pub fn alerts(self, alerts: Alerts) -> Self {
+        Self { alerts, ..self }
+    }
+
+    /// Adds a tenant, which is served from its own store to requests addressed to its hosts.
+    ///
+    /// Requests to hosts not claimed by any tenant are served from the default store.
+    pub fn tenant(mut self, tenant: TenantConfig) -> Self {
+        self.tenants.push(tenant);
+        self
+    }
+
+    /// Sets the maximum number of bytes stored in all repositories of the default store.
+    pub fn quota(self, quota: u64) -> Self {
+        Self {
+            quota: Some(quota),
+            ..self
+        }
+    }
+
+    /// Sets the maximum rate of requests of each client, identified by its IP address.
+    pub fn rate_limit(self, rate_limit: RateLimit) -> Self {
+        Self {
+            rate_limit: Some(rate_limit),
+            ..self
+        }
+    }
+
+    /// Sets the scheduled backups of the default store.
+    pub fn backup(self, backup: BackupConfig) -> Self {
+        Self {
+            backup: Some(backup),
+            ..self
+        }
+    }
+
+    /// Migrates all entities of the store at [MigrationConfig::source] into the default store
+    /// in background, serving entities not migrated yet from the source store meanwhile.
+    ///
+    /// Read replicas cannot migrate stores.
+    pub fn migration(self, migration: MigrationConfig) -> Self {
+        Self {
+            migration: Some(migration),
+            ..self
+        }
+    }
+
+    /// Seeds the default store with the content of the local directory at `seed` laid out as
+    /// `OWNER/REPOSITORY/TAG` at startup, creating entities missing from the store.
+    ///
+    /// Read replicas cannot be seeded.
+    pub fn seed(self, seed: impl Into<PathBuf>) -> Self {
+        Self {
+            seed: Some(seed.into()),
+            ..self
+        }
+    }
+
+    /// Mounts the application under `base_path`, e.g. `/drawbridge`, which is stripped from
+    /// paths of requests and prepended to generated links and redirects.
+    pub fn base_path(self, base_path: impl Into<String>) -> Self {
+        Self {
+            base_path: Some(base_path.into()),
+            ..self
+        }
     }
 
     /// Builds the application and returns Drawbridge instance as a [tower::MakeService].
-    pub async fn build(self) -> anyhow::Result<App> {
-        let Self { store, tls, oidc } = self;
-        let store_path = store.as_ref();
-        let store = File::open(store_path)
-            .and_then(|f| Store::new(Dir::from_std_file(f)))
+    ///
+    /// Fails, if the builder was constructed by [Self::embedded].
+    pub async fn build(mut self) -> anyhow::Result<App> {
+        let tls = self.tls.take().context(
+            "TLS configuration required to serve the application, use `build_router` to embed it",
+        )?;
+        let replica_identities = std::mem::take(&mut self.replica_identities);
+        for identity in &replica_identities {
+            ensure!(
+                DnsNameRef::try_from_ascii_str(identity).is_ok(),
+                "invalid replica identity `{identity}`, expected a DNS name"
+            );
+        }
+        let (router, steward, clock) = self.assemble().await?;
+        Ok(App {
+            make_service: Mutex::new(router.into_make_service()),
+            roots: tls.roots().to_vec(),
+            tls: TlsAcceptor::from(Arc::new(tls.into())),
+            steward,
+            replica_identities,
+            clock,
+        })
+    }
+
+    /// Builds the application as a [Router], which another axum application may nest, merge
+    /// or wrap in layers of its own.
+    ///
+    /// The embedding application terminates TLS, so that client certificates of requests are
+    /// never trusted and a [Steward] is not consulted.
+    ///
+    /// Unlike the application returned by [Self::build], the router trusts
+    /// [OidcClaims](crate::OidcClaims) inserted into requests, see [Self::embedded].
+    pub async fn build_router(self) -> anyhow::Result<Router> {
+        self.assemble()
             .await
-            .context(anyhow!(
-                "failed to open store at `{}`",
-                store_path.to_string_lossy()
-            ))?;
+            .map(|(router, ..)| router.layer(Extension(Embedded)))
+    }
 
-        let oidc_verifier =
-            crate::auth::OidcVerifier::new(oidc).context("failed to create OIDC verifier")?;
+    /// Builds the router of the application along with the configuration required to serve it.
+    async fn assemble(self) -> anyhow::Result<(Router, Option<Steward>, Arc<dyn Clock>)> {
+        let Self {
+            store,
+            store_instance,
+            events,
+            tls: _,
+            oidc,
+            signature_keys,
+            proxy_registries,
+            federation,
+            delegation,
+            replication,
+            replica,
+            replica_identities: _,
+            placement,
+            namespace_rules,
+            admission,
+            cluster,
+            steward,
+            cache_policy,
+            verification_policy,
+            trash_policy,
+            upload_policy,
+            scanners,
+            tuf,
+            kms,
+            url_signer,
+            replay_protection,
+            hot_cache,
+            clock,
+            nats,
+            access_log,
+            slow_log,
+            alerts,
+            tenants,
+            quota,
+            rate_limit,
+            backup,
+            migration,
+            seed,
+            base_path,
+        } = self;
+        if replica.is_some() && !tenants.is_empty() {
+            bail!("read replicas cannot host tenants");
+        }
+        if replica.is_some() && migration.is_some() {
+            bail!("read replicas cannot migrate stores");
+        }
+        if replica.is_some() && seed.is_some() {
+            bail!("read replicas cannot be seeded");
+        }
+        let base_path = base_path
+            .map(BasePath::new)
+            .transpose()
+            .context("invalid base path")?;
+        let placement = Arc::new(placement);
+        let cluster = cluster
+            .map(|config| Cluster::new(config, clock.clone()).map(Arc::new))
+            .transpose()
+            .context("invalid cluster configuration")?;
+        let replica =
+            replica.map(|config| Arc::new(Replica::new(config, placement.clone(), clock.clone())));
+        let access_log = match access_log {
+            Some(config) => Some(Arc::new(
+                AccessLog::open(config, clock.clone())
+                    .await
+                    .context("failed to open access log")?,
+            )),
+            None => None,
+        };
 
-        Ok(App {
-            make_service: Mutex::new(
-                Router::new()
-                    .fallback(handle.into_service())
-                    .route("/health", any(|| async {}))
-                    .layer(Extension(Arc::new(store)))
-                    .layer(Extension(Arc::new(oidc_verifier)))
-                    .layer(
-                        TraceLayer::new_for_http()
-                            .make_span_with(SpanMaker::default())
-                            .on_request(DefaultOnRequest::new().level(Level::INFO))
-                            .on_response(
-                                DefaultOnResponse::new()
-                                    .level(Level::INFO)
-                                    .latency_unit(LatencyUnit::Micros),
-                            )
-                            .on_body_chunk(DefaultOnBodyChunk::new())
-                            .on_eos(
-                                DefaultOnEos::new()
-                                    .level(Level::INFO)
-                                    .latency_unit(LatencyUnit::Micros),
-                            )
-                            .on_failure(
-                                DefaultOnFailure::new()
-                                    .level(Level::INFO)
-                                    .latency_unit(LatencyUnit::Micros),
-                            ),
+        let shared = Shared {
+            signature_keys: Arc::new(signature_keys),
+            proxy_registries: Arc::new(proxy_registries),
+            federation: Arc::new(federation),
+            delegation: Arc::new(delegation),
+            replication: Arc::new(replication),
+            placement,
+            namespace_rules: Arc::new(namespace_rules),
+            admission: Arc::new(admission),
+            cluster,
+            cache_policy: Arc::new(cache_policy),
+            verification_policy: Arc::new(verification_policy),
+            trash_policy: Arc::new(trash_policy),
+            upload_policy: Arc::new(upload_policy),
+            scanners: Arc::new(scanners),
+            tuf,
+            kms,
+            url_signer,
+            replay_protection,
+            alerts: Arc::new(alerts),
+            clock: clock.clone(),
+        };
+        let default = shared
+            .router(
+                store,
+                store_instance,
+                events,
+                oidc,
+                quota,
+                hot_cache.with_same_limits(),
+                nats.clone(),
+                replica,
+                backup,
+                migration,
+                seed,
+            )
+            .await?;
+        let router = if tenants.is_empty() {
+            default
+        } else {
+            let mut dispatch = Tenants::new(default);
+            for TenantConfig {
+                name,
+                hosts,
+                store,
+                oidc,
+                quota,
+            } in tenants
+            {
+                let nats = nats.clone().map(|nats| NatsConfig {
+                    subject_prefix: format!("{}.{name}", nats.subject_prefix),
+                    ..nats
+                });
+                let router = shared
+                    .router(
+                        store,
+                        None,
+                        None,
+                        Some(oidc),
+                        quota,
+                        hot_cache.with_same_limits(),
+                        nats,
+                        None,
+                        None,
+                        None,
+                        None,
                     )
-                    .into_make_service(),
-            ),
-            tls: TlsAcceptor::from(Arc::new(tls.into())),
+                    .await
+                    .with_context(|| format!("failed to build tenant `{name}`"))?;
+                dispatch.insert(&name, hosts, router)?;
+            }
+            Router::new().fallback(dispatch)
+        };
+
+        let router = router.layer(
+            TraceLayer::new_for_http()
+                .make_span_with(SpanMaker::default())
+                .on_request(DefaultOnRequest::new().level(Level::INFO))
+                .on_response(
+                    DefaultOnResponse::new()
+                        .level(Level::INFO)
+                        .latency_unit(LatencyUnit::Micros),
+                )
+                .on_body_chunk(DefaultOnBodyChunk::new())
+                .on_eos(
+                    DefaultOnEos::new()
+                        .level(Level::INFO)
+                        .latency_unit(LatencyUnit::Micros),
+                )
+                .on_failure(
+                    DefaultOnFailure::new()
+                        .level(Level::INFO)
+                        .latency_unit(LatencyUnit::Micros),
+                ),
+        );
+        let router = if let Some(rate_limit) = rate_limit {
+            router
+                .layer(middleware::from_fn(quota::handle))
+                .layer(Extension(Arc::new(RateLimiter::new(
+                    rate_limit,
+                    clock.clone(),
+                ))))
+        } else {
+            router
+        };
+        let router = if let Some(slow_log) = slow_log {
+            router
+                .layer(middleware::from_fn(slow_log::handle))
+                .layer(Extension(slow_log))
+        } else {
+            router
+        };
+        let router = if let Some(access_log) = access_log {
+            router
+                .layer(middleware::from_fn(access_log::handle))
+                .layer(Extension(access_log))
+        } else {
+            router
+        };
+        let router = if let Some(base_path) = base_path {
+            router
+                .layer(middleware::from_fn(base_path::handle))
+                .layer(Extension(Arc::new(base_path)))
+        } else {
+            router
+        };
+        Ok((router, steward, clock))
+    }
+}
+
+/// Configuration shared by all tenants.
+struct Shared {
+    signature_keys: Arc<SignatureKeys>,
+    proxy_registries: Arc<ProxyRegistries>,
+    federation: Arc<Federation>,
+    delegation: Arc<Delegation>,
+    replication: Arc<Replication>,
+    placement: Arc<Placement>,
+    namespace_rules: Arc<NamespaceRules>,
+    admission: Arc<Admission>,
+    cluster: Option<Arc<Cluster>>,
+    cache_policy: Arc<CachePolicy>,
+    verification_policy: Arc<VerificationPolicy>,
+    trash_policy: Arc<TrashPolicy>,
+    upload_policy: Arc<UploadPolicy>,
+    scanners: Arc<Scanners>,
+    tuf: Option<TufConfig>,
+    kms: Option<Arc<dyn Kms>>,
+    url_signer: Option<Arc<UrlSigner>>,
+    replay_protection: Option<ReplayProtection>,
+    alerts: Arc<Alerts>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Shared {
+    /// Builds the router serving `instance` or, if none, the store at `store` to users
+    /// authenticated by `oidc`, if any, which publishes events to `events`, if any, is kept up
+    /// to date by `replica`, if the server is a read replica, backed up as configured by
+    /// `backup`, migrated into as configured by `migration` and seeded with the content at
+    /// `seed`, if any.
+    #[allow(clippy::too_many_arguments)]
+    async fn router(
+        &self,
+        store: impl AsRef<Path>,
+        instance: Option<Arc<Store>>,
+        events: Option<Arc<EventBus>>,
+        oidc: Option<OidcConfig>,
+        quota: Option<u64>,
+        hot_cache: HotCache,
+        nats: Option<NatsConfig>,
+        replica: Option<Arc<Replica>>,
+        backup: Option<BackupConfig>,
+        migration: Option<MigrationConfig>,
+        seed: Option<PathBuf>,
+    ) -> anyhow::Result<Router> {
+        let store = match instance {
+            Some(store) => store,
+            None => {
+                let store_path = store.as_ref();
+                let store = File::open(store_path)
+                    .and_then(|f| {
+                        let root = Dir::from_std_file(f);
+                        async move {
+                            if self.cluster.is_some() {
+                                Store::new_shared(root).await
+                            } else {
+                                Store::new(root).await
+                            }
+                        }
+                    })
+                    .await
+                    .context(anyhow!(
+                        "failed to open store at `{}`",
+                        store_path.to_string_lossy()
+                    ))?;
+                Arc::new(store)
+            }
+        };
+        let events = events.unwrap_or_default();
+        let webhooks = Arc::new(Webhooks::new(self.clock.clone()));
+        webhooks.subscribe(&store, &events);
+        let accounting = Arc::new(
+            Accounting::load(&store, self.cluster.clone(), quota)
+                .await
+                .context("failed to load usage counters")?,
+        );
+        accounting.subscribe(&events);
+        accounting.roll_up(&store);
+        let changes = Arc::new(
+            ChangeLog::load(&store, self.cluster.clone(), self.clock.clone())
+                .await
+                .context("failed to load change journal")?,
+        );
+        changes.subscribe(&store, &events);
+        let replicator = Arc::new(Replicator::new(
+            self.replication.clone(),
+            self.placement.clone(),
+            self.clock.clone(),
+        ));
+        replicator.subscribe(&store, &events);
+        let alerts = Arc::new(self.alerts.with_repairer(Arc::new(Repairer::new(
+            store.clone(),
+            events.clone(),
+            self.replication.clone(),
+        ))));
+        let tuf = match self.tuf {
+            Some(ref config) => {
+                let tuf = Arc::new(Tuf::new(
+                    config.clone(),
+                    self.cluster.clone(),
+                    self.clock.clone(),
+                ));
+                tuf.init(&store)
+                    .await
+                    .context("failed to initialize TUF metadata")?;
+                tuf.subscribe(&store, &events);
+                tuf.refresh_periodically(&store);
+                Some(tuf)
+            }
+            None => None,
+        };
+        let keys = self.kms.as_ref().map(|kms| {
+            let keys = Arc::new(ManagedKeys::new(kms.clone(), self.clock.clone()));
+            keys.subscribe(&store, &events);
+            keys
+        });
+        let hosts = Arc::new(
+            VanityHosts::load(store.clone(), self.clock.clone())
+                .await
+                .context("failed to load vanity hosts")?,
+        );
+        let search = Arc::new(SearchIndex::default());
+        search.subscribe(&store, &events);
+        let downloads = Arc::new(Downloads::default());
+        downloads.flush_periodically(&store, self.cluster.clone());
+        trash::purge_periodically(&store, self.cluster.clone(), self.clock.clone());
+        retention::run_periodically(&store, self.cluster.clone(), self.clock.clone());
+        uploads::expire_periodically(
+            &store,
+            self.cluster.clone(),
+            self.upload_policy.clone(),
+            self.clock.clone(),
+        );
+        if let Some(ref seed) = seed {
+            seed::seed(&store, &events, seed)
+                .await
+                .with_context(|| format!("failed to seed store from `{}`", seed.display()))?;
+        }
+        if let Some(ref cluster) = self.cluster {
+            cluster.lead(&store, &alerts);
+        }
+        if let Some(nats) = nats {
+            events::forward(nats, &events);
+        }
+        if let Some(ref replica) = replica {
+            replica.tail(&store, &events);
+        }
+        let migration = match migration {
+            Some(config) => {
+                let migration = Arc::new(
+                    Migration::open(config, &store, self.clock.clone())
+                        .await
+                        .context("failed to start migration")?,
+                );
+                migration.run(&store, &events, self.cluster.clone());
+                Some(migration)
+            }
+            None => None,
+        };
+        if let Some(backup) = backup {
+            Arc::new(Backups::new(
+                backup,
+                self.cluster.clone(),
+                self.clock.clone(),
+            ))
+            .schedule(&store);
+        }
+
+        let oidc_verifier = oidc
+            .map(|oidc| crate::auth::OidcVerifier::new(oidc, self.clock.clone()))
+            .transpose()
+            .context("failed to create OIDC verifier")?;
+
+        let router = Router::new()
+            .fallback(handle.into_service())
+            .route("/health", any(|| async {}))
+            .route("/s3", any(s3::handle))
+            .route("/s3/*path", any(s3::handle));
+        #[cfg(feature = "graphql")]
+        let router = router.route("/graphql", post(graphql::graphql));
+        #[cfg(feature = "ui")]
+        let router = router
+            .route("/ui", get(ui::redirect))
+            .route("/ui/*path", get(ui::asset));
+        let router = router
+            .layer(middleware::from_fn(migration::handle))
+            .layer(middleware::from_fn(federation::handle))
+            .layer(middleware::from_fn(replica::handle))
+            .layer(middleware::from_fn(delegation::handle))
+            .layer(Extension(store))
+            .layer(Extension(self.signature_keys.clone()))
+            .layer(Extension(self.proxy_registries.clone()))
+            .layer(Extension(self.federation.clone()))
+            .layer(Extension(self.delegation.clone()))
+            .layer(Extension(self.replication.clone()))
+            .layer(Extension(self.placement.clone()))
+            .layer(Extension(self.namespace_rules.clone()))
+            .layer(Extension(self.admission.clone()))
+            .layer(Extension(self.cache_policy.clone()))
+            .layer(Extension(self.verification_policy.clone()))
+            .layer(Extension(self.trash_policy.clone()))
+            .layer(Extension(self.upload_policy.clone()))
+            .layer(Extension(self.scanners.clone()))
+            .layer(Extension(Arc::new(hot_cache)))
+            .layer(Extension(events))
+            .layer(Extension(webhooks))
+            .layer(Extension(replicator))
+            .layer(Extension(downloads))
+            .layer(Extension(search))
+            .layer(Extension(alerts))
+            .layer(Extension(self.clock.clone()))
+            .layer(middleware::from_fn(cbor::handle))
+            .layer(middleware::from_fn(conditional::handle))
+            .layer(middleware::from_fn(usage::handle))
+            .layer(Extension(accounting))
+            .layer(middleware::from_fn(recent_errors::handle))
+            .layer(Extension(Arc::new(RecentErrors::new(self.clock.clone()))))
+            .layer(middleware::from_fn(hosts::handle))
+            .layer(Extension(hosts));
+        let router = if let Some(replica) = replica {
+            router.layer(Extension(replica))
+        } else {
+            router
+        };
+        let router = if let Some(migration) = migration {
+            router.layer(Extension(migration))
+        } else {
+            router
+        };
+        let router = if let Some(tuf) = tuf {
+            router.layer(Extension(tuf))
+        } else {
+            router
+        };
+        let router = if let Some(keys) = keys {
+            router.layer(Extension(keys))
+        } else {
+            router
+        };
+        let router = if let Some(oidc_verifier) = oidc_verifier {
+            router.layer(Extension(Arc::new(oidc_verifier)))
+        } else {
+            router
+        };
+        let router = if let Some(ref url_signer) = self.url_signer {
+            router.layer(Extension(url_signer.clone()))
+        } else {
+            router
+        };
+        let router = if let Some(replay_protection) = self.replay_protection {
+            router.layer(Extension(Arc::new(Nonces::new(replay_protection))))
+        } else {
+            router
+        };
+        Ok(if let Some(ref cluster) = self.cluster {
+            router.layer(Extension(cluster.clone()))
+        } else {
+            router
         })
     }
 }
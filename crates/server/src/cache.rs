@@ -0,0 +1,79 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! `Cache-Control` headers of responses.
+//!
+//! Only responses addressed by digest, i.e. versioned TUF root metadata, can never change and
+//! may be cached indefinitely. Tags and tree nodes are addressed by their names and paths,
+//! which are bound to different contents once a tag is deleted and published again, and a
+//! vulnerability policy of a repository may block pulls of a tag once a report is attached to
+//! it. Hence responses containing them must be revalidated by caches before they are reused,
+//! which is cheap using their [entity tags](super::conditional). Tag listings change whenever
+//! a tag is created and are therefore only cached briefly. Responses concerning private
+//! repositories are marked `private`, so that shared caches, e.g. CDNs, never store them.
+
+use std::time::Duration;
+
+use axum::http::header::{HeaderName, CACHE_CONTROL};
+
+/// A `Cache-Control` header, which can be returned as part of a response.
+pub(crate) type CacheControl = [(HeaderName, String); 1];
+
+/// Caching policy of responses.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CachePolicy {
+    /// Maximum age of immutable responses, i.e. versioned TUF root metadata
+    pub immutable_max_age: Duration,
+    /// Maximum age of mutable responses, i.e. tag listings
+    pub mutable_max_age: Duration,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            immutable_max_age: Duration::from_secs(365 * 24 * 60 * 60),
+            mutable_max_age: Duration::from_secs(60),
+        }
+    }
+}
+
+fn visibility(public: bool) -> &'static str {
+    if public {
+        "public"
+    } else {
+        "private"
+    }
+}
+
+impl CachePolicy {
+    /// Returns the `Cache-Control` header of an immutable response, which is addressed by
+    /// digest.
+    pub(crate) fn immutable(&self, public: bool) -> CacheControl {
+        [(
+            CACHE_CONTROL,
+            format!(
+                "{}, max-age={}, immutable",
+                visibility(public),
+                self.immutable_max_age.as_secs()
+            ),
+        )]
+    }
+
+    /// Returns the `Cache-Control` header of a response containing a tag or a tree node of a
+    /// repository, which must be revalidated before it is reused.
+    pub(crate) fn content(&self, public: bool) -> CacheControl {
+        [(CACHE_CONTROL, format!("{}, no-cache", visibility(public)))]
+    }
+
+    /// Returns the `Cache-Control` header of a mutable response concerning a repository.
+    pub(crate) fn mutable(&self, public: bool) -> CacheControl {
+        [(
+            CACHE_CONTROL,
+            format!(
+                "{}, max-age={}",
+                visibility(public),
+                self.mutable_max_age.as_secs()
+            ),
+        )]
+    }
+}
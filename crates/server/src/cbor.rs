@@ -0,0 +1,230 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! `Accept` and `Content-Type` negotiation of [CBOR]-encoded entities.
+//!
+//! Tag entries, directories and problem details may be exchanged encoded as CBOR instead of
+//! JSON by using the media type of their JSON representation with a `+cbor` instead of the
+//! `+json` suffix, e.g. `application/vnd.drawbridge.entry.v1+cbor`, or `application/cbor`.
+//! Entities are stored and identified by the digest of their JSON encoding, so CBOR is merely
+//! a wire format: responses carry the `Content-Digest` of the CBOR representation, while
+//! uploads are verified against the digest of the CBOR request body and stored as JSON.
+//!
+//! Encodings are deterministic as specified by [RFC 8949, section 4.2.1], i.e. only definite
+//! lengths are used and map entries are sorted by the encoding of their keys.
+//!
+//! [CBOR]: https://www.rfc-editor.org/rfc/rfc8949
+//! [RFC 8949, section 4.2.1]: https://www.rfc-editor.org/rfc/rfc8949#section-4.2.1
+
+use super::encoding;
+use super::json::{self, BoundedBody, MAX_BODY_SIZE};
+use super::problem::PROBLEM_TYPE;
+
+use drawbridge_type::digest::{Algorithms, ContentDigest};
+use drawbridge_type::{MediaType, Meta};
+
+use axum::body::{boxed, BoxBody, Bytes, Full, HttpBody};
+use axum::extract::{FromRequest, RequestParts};
+use axum::headers::HeaderMapExt;
+use axum::http::header::{HeaderName, CONTENT_LENGTH, CONTENT_TYPE, VARY};
+use axum::http::{HeaderMap, HeaderValue, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::BoxError;
+use ciborium::value::Value;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Media type of CBOR data of unspecified structure.
+const CBOR_TYPE: &str = "application/cbor";
+
+/// A `Vary` header, which must be returned with every negotiated response.
+pub(crate) const VARY_ACCEPT: [(HeaderName, &str); 1] = [(VARY, "accept")];
+
+/// Returns the media type of the CBOR representation of entities of JSON media type `json`.
+pub(crate) fn cbor_type(json: &str) -> Option<MediaType> {
+    json.strip_suffix("+json")
+        .and_then(|prefix| format!("{prefix}+cbor").parse().ok())
+}
+
+/// Returns the media type of the JSON representation of entities of CBOR media type `cbor`.
+pub(crate) fn json_type(cbor: &str) -> Option<String> {
+    cbor.strip_suffix("+cbor")
+        .map(|prefix| format!("{prefix}+json"))
+}
+
+/// Returns `true` if `Accept` headers in `headers` prefer the CBOR representation of entities
+/// of JSON media type `json` over the JSON one.
+pub(crate) fn accepts(headers: &HeaderMap, json: &str) -> bool {
+    let cbor = cbor_type(json);
+    let cbor = cbor.as_ref().map(MediaType::essence).unwrap_or(CBOR_TYPE);
+    json::prefers(headers, &[cbor, CBOR_TYPE], json)
+}
+
+/// Returns the `Vary` header of a response negotiated by `Accept`, if `cbor`, and by
+/// `Accept-Encoding`, if `gzip`.
+pub(crate) fn vary(cbor: bool, gzip: bool) -> Option<[(HeaderName, &'static str); 1]> {
+    match (cbor, gzip) {
+        (true, true) => Some([(VARY, "accept, accept-encoding")]),
+        (true, false) => Some(VARY_ACCEPT),
+        (false, true) => Some(encoding::VARY_ACCEPT_ENCODING),
+        (false, false) => None,
+    }
+}
+
+/// Sorts entries of maps in `val` by the encoding of their keys.
+fn canonicalize(val: &mut Value) {
+    match val {
+        Value::Map(entries) => {
+            entries.iter_mut().for_each(|(_, val)| canonicalize(val));
+            entries.sort_by_cached_key(|(key, _)| {
+                let mut buf = vec![];
+                _ = ciborium::ser::into_writer(key, &mut buf);
+                buf
+            });
+        }
+        Value::Array(vals) => vals.iter_mut().for_each(canonicalize),
+        Value::Tag(_, val) => canonicalize(val),
+        _ => {}
+    }
+}
+
+/// Encodes `val` as deterministic CBOR.
+pub(crate) fn to_vec(val: &impl Serialize) -> Option<Vec<u8>> {
+    // NOTE: Values are serialized via `Value` to encode maps of unknown size, e.g. those
+    // containing flattened fields, with a definite length.
+    let mut val = Value::serialized(val).ok()?;
+    canonicalize(&mut val);
+    let mut buf = vec![];
+    ciborium::ser::into_writer(&val, &mut buf).ok()?;
+    Some(buf)
+}
+
+/// Encodes `val` as CBOR of media type `mime` and returns it along with its [Meta].
+pub(crate) fn encode(
+    val: &impl Serialize,
+    mime: MediaType,
+) -> Result<(Meta, Vec<u8>), (StatusCode, &'static str)> {
+    let buf = to_vec(val).ok_or((StatusCode::INTERNAL_SERVER_ERROR, "Failed to encode CBOR"))?;
+    let (_, hash) = Algorithms::default().read_sync(&buf[..]).map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to compute digest",
+        )
+    })?;
+    Ok((
+        Meta {
+            hash,
+            size: buf.len() as _,
+            mime,
+        },
+        buf,
+    ))
+}
+
+/// Transcodes JSON-encoded `T` in `body` described by `meta` to CBOR and returns it along with
+/// its [Meta].
+pub(crate) async fn transcode<T>(
+    meta: Meta,
+    body: BoxBody,
+) -> Result<(Meta, Vec<u8>), (StatusCode, &'static str)>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let mime = cbor_type(meta.mime.essence()).ok_or((
+        StatusCode::NOT_ACCEPTABLE,
+        "No CBOR representation available",
+    ))?;
+    let buf = hyper::body::to_bytes(body)
+        .await
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read content"))?;
+    let val: T = serde_json::from_slice(&buf)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to decode JSON"))?;
+    encode(&val, mime)
+}
+
+/// Returns the [Meta] of the JSON encoding of `val` of media type `mime`, which CBOR-encoded
+/// uploads are stored as.
+pub(crate) fn stored_meta(
+    val: &impl Serialize,
+    mime: MediaType,
+) -> Result<Meta, (StatusCode, &'static str)> {
+    json::encode(val).map(|(meta, _)| Meta { mime, ..meta })
+}
+
+/// A CBOR request body of at most `MAX` bytes decoded as `T`, see [BoundedBody].
+///
+/// The `Content-Type` must be `application/cbor` or use the `+cbor` suffix. Since the body is
+/// not stored as is, it is verified against its `Content-Digest`, if specified.
+#[derive(Clone, Debug)]
+pub(crate) struct BoundedCbor<T, const MAX: u64 = MAX_BODY_SIZE>(pub(crate) T);
+
+#[axum::async_trait]
+impl<B, T, const MAX: u64> FromRequest<B> for BoundedCbor<T, MAX>
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Into<Bytes>,
+    B::Error: Into<BoxError>,
+    T: DeserializeOwned,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let mime = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|mime| mime.to_str().ok()?.parse::<MediaType>().ok());
+        if !mime.is_some_and(|mime| mime.is(CBOR_TYPE) || mime.suffix() == Some("cbor")) {
+            return Err((StatusCode::UNSUPPORTED_MEDIA_TYPE, "Expected CBOR body").into_response());
+        }
+        let hash = req.headers().typed_get::<ContentDigest>();
+        let BoundedBody(buf) = req.extract::<BoundedBody<MAX>>().await?;
+        if let Some(hash) = hash {
+            match hash.algorithms().read_sync(&buf[..]) {
+                Ok((_, actual)) if actual == hash => {}
+                _ => {
+                    return Err((StatusCode::BAD_REQUEST, "Content digest mismatch").into_response())
+                }
+            }
+        }
+        ciborium::de::from_reader(&buf[..]).map(Self).map_err(|e| {
+            (StatusCode::BAD_REQUEST, format!("Invalid CBOR body: {e}")).into_response()
+        })
+    }
+}
+
+/// Encodes problem details of error responses as CBOR for requests preferring it.
+pub(crate) async fn handle<B>(req: Request<B>, next: Next<B>) -> Response {
+    let cbor = accepts(req.headers(), PROBLEM_TYPE);
+
+    let res = next.run(req).await;
+    if res.headers().get(CONTENT_TYPE).map(HeaderValue::as_bytes) != Some(PROBLEM_TYPE.as_bytes()) {
+        return res;
+    }
+    let (mut parts, body) = res.into_parts();
+    _ = parts
+        .headers
+        .append(VARY, HeaderValue::from_static("accept"));
+    if !cbor {
+        return Response::from_parts(parts, body);
+    }
+    let buf = match hyper::body::to_bytes(body).await {
+        Ok(buf) => buf,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let problem = serde_json::from_slice::<serde_json::Value>(&buf)
+        .ok()
+        .and_then(|problem| to_vec(&problem))
+        .zip(cbor_type(PROBLEM_TYPE));
+    let body = match problem {
+        Some((problem, mime)) => {
+            _ = parts.headers.remove(CONTENT_LENGTH);
+            if let Ok(mime) = HeaderValue::from_str(mime.as_ref()) {
+                _ = parts.headers.insert(CONTENT_TYPE, mime);
+            }
+            Bytes::from(problem)
+        }
+        None => buf,
+    };
+    Response::from_parts(parts, boxed(Full::new(body)))
+}
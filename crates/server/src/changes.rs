@@ -0,0 +1,200 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Persistent change feed paginated by cursors.
+//!
+//! Events published on the [EventBus] are appended to a journal in the store along with a
+//! cursor, which is assigned in publication order starting at 1 and, unlike [EventId]s,
+//! persists across restarts. Tooling, e.g. for replication, indexing or backups, consumes the
+//! journal incrementally via [changes] by passing the cursor of the last change it processed
+//! instead of scanning the store.
+//!
+//! Events dropped by the [EventBus], since the journal fell behind, are missing from it.
+//!
+//! With the `msgpack` feature, pages are encoded as MessagePack for requests preferring it.
+//!
+//! [EventId]: super::events::EventId
+
+use super::cluster::Cluster;
+use super::events::{Event, EventBus};
+use super::{json, pagination, Clock, Store, TrustedCertificate};
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use async_std::sync::Arc;
+use async_std::task::spawn;
+use axum::body::Body;
+use axum::http::header::LINK;
+use axum::http::{Request, StatusCode};
+use axum::response::IntoResponse;
+use axum::Extension;
+use chrono::{DateTime, SecondsFormat, Utc};
+use openidconnect::url::form_urlencoded;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, trace, warn};
+
+/// Number of changes returned by [changes] unless a limit is requested.
+pub const DEFAULT_LIMIT: usize = 100;
+
+/// Maximum number of changes returned by [changes].
+pub const MAX_LIMIT: usize = 1000;
+
+/// Name of the cluster lock serializing appends to the change journal.
+const CHANGES_LOCK: &str = "changes";
+
+/// An entity mutation recorded in the change journal.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Change {
+    pub cursor: u64,
+    /// Time the change was recorded in RFC 3339 format
+    pub time: String,
+    /// The event as returned by [Event::to_json](super::events::Event::to_json)
+    #[serde(flatten)]
+    pub event: Value,
+}
+
+/// A page of changes returned by [changes].
+#[derive(Clone, Debug, Serialize)]
+pub struct ChangePage {
+    pub changes: Vec<Change>,
+    /// Cursor to request the following page with
+    pub next: u64,
+    /// Whether changes following the page were recorded
+    pub more: bool,
+}
+
+/// Writer of the change journal.
+#[derive(Debug)]
+pub struct ChangeLog {
+    /// Cursor of the most recently recorded change
+    last: AtomicU64,
+    cluster: Option<Arc<Cluster>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ChangeLog {
+    /// Loads the cursor of the most recently recorded change from `store`, which is shared
+    /// with other instances of `cluster`, if any.
+    pub(crate) async fn load(
+        store: &Store,
+        cluster: Option<Arc<Cluster>>,
+        clock: Arc<dyn Clock>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            last: AtomicU64::new(store.last_change().await?),
+            cluster,
+            clock,
+        })
+    }
+
+    /// Appends `event` to the change journal in `store` and returns its cursor.
+    async fn record(&self, store: &Store, event: &Event) -> anyhow::Result<u64> {
+        let lock = match self.cluster {
+            Some(ref cluster) => Some(cluster.lock(store, CHANGES_LOCK).await?),
+            None => None,
+        };
+        let res = async {
+            // NOTE: Other instances of the cluster append to the journal as well.
+            let last = match lock {
+                Some(_) => store.last_change().await?,
+                None => self.last.load(Ordering::Acquire),
+            };
+            let cursor = last + 1;
+            let change = Change {
+                cursor,
+                time: DateTime::<Utc>::from(self.clock.now())
+                    .to_rfc3339_opts(SecondsFormat::Secs, true),
+                event: event.to_json(),
+            };
+            store.append_change(cursor, &change).await?;
+            self.last.store(cursor, Ordering::Release);
+            Ok(cursor)
+        }
+        .await;
+        if let Some(lock) = lock {
+            lock.release().await;
+        }
+        res
+    }
+
+    /// Records events published on `events` in background.
+    pub(crate) fn subscribe(self: &Arc<Self>, store: &Arc<Store>, events: &EventBus) {
+        let log = Arc::clone(self);
+        let store = Arc::clone(store);
+        let events = events.subscribe();
+        _ = spawn(async move {
+            while let Ok((_, event)) = events.recv().await {
+                match log.record(&store, &event).await {
+                    Ok(cursor) => trace!(target: "app::changes", "recorded change {cursor}"),
+                    Err(e) => {
+                        warn!(target: "app::changes", "failed to record {:?}: {:?}", event, e)
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Returns changes following the cursor passed as the `since` query parameter, 0 by default,
+/// in order.
+///
+/// At most [DEFAULT_LIMIT] changes are returned unless the `limit` query parameter requests
+/// up to [MAX_LIMIT]. If more changes follow, a `Link` header refers to the `next` page.
+pub async fn changes(
+    Extension(ref store): Extension<Arc<Store>>,
+    cert: Option<Extension<TrustedCertificate>>,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::changes::changes", "called");
+
+    if cert.is_none() {
+        return Err((StatusCode::FORBIDDEN, "Trusted client certificate required").into_response());
+    }
+    let mut since = 0;
+    let mut limit = DEFAULT_LIMIT;
+    for (k, v) in form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes()) {
+        match k.as_ref() {
+            "since" => {
+                since = v.parse().map_err(|_| {
+                    (StatusCode::BAD_REQUEST, "Invalid `since` cursor").into_response()
+                })?
+            }
+            "limit" => {
+                limit = v
+                    .parse::<usize>()
+                    .map(|n| n.min(MAX_LIMIT))
+                    .map_err(|_| (StatusCode::BAD_REQUEST, "Invalid `limit`").into_response())?
+            }
+            _ => {}
+        }
+    }
+
+    let mut changes: Vec<Change> = store.read_changes(since, limit + 1).await.map_err(|e| {
+        debug!(target: "app::changes::changes", "failed to read changes since {since}: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })?;
+    let more = changes.len() > limit;
+    changes.truncate(limit);
+    let next = changes.last().map(|change| change.cursor).unwrap_or(since);
+    let link = more.then(|| {
+        [(
+            LINK,
+            pagination::link(req.uri(), "next", &[("since", next.to_string())]),
+        )]
+    });
+    let page = ChangePage {
+        changes,
+        next,
+        more,
+    };
+    #[cfg(feature = "msgpack")]
+    if super::msgpack::accepts(req.headers()) {
+        return super::msgpack::encode(&page)
+            .map(|res| (link, res))
+            .map_err(IntoResponse::into_response);
+    }
+    json::encode(&page)
+        .map(|res| (link, res))
+        .map_err(IntoResponse::into_response)
+}
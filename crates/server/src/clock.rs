@@ -0,0 +1,29 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Source of the current time.
+//!
+//! All time-dependent behavior, e.g. token expiry, certificate validity and timestamps of
+//! bags, webhook deliveries and entities, queries the [Clock] configured in the [Builder], so
+//! that it can be made deterministic by supplying a fixed clock.
+//!
+//! [Builder]: super::Builder
+
+use std::fmt::Debug;
+use std::time::SystemTime;
+
+/// A source of the current time.
+pub trait Clock: Debug + Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> SystemTime;
+}
+
+/// A [Clock] reading the system time.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
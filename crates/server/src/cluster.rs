@@ -0,0 +1,193 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Coordination of server instances sharing a store.
+//!
+//! Instances of a cluster serve the same store, e.g. on a shared file system, and coordinate
+//! through leases kept in the store, which expire after [ClusterConfig::lease_ttl], so that
+//! locks and leadership of crashed instances are eventually taken over:
+//!
+//! - Publication of tags and updates of their download statistics, which read and modify
+//!   shared state, are serialized by per-tag locks, as are appends to the change journal.
+//! - Background tasks, which must only run once per store, are run by the leader, i.e. the
+//!   instance holding the leader lease: removal of stale entities from the staging area and,
+//!   if configured, periodic consistency checks of the store and backups.
+//!
+//! Uploads are single requests and pending tags are kept in the store, so requests may be
+//! routed to any instance without session affinity. Usage counters are merged into the store
+//! under a lock on each roll-up, so storage quotas are enforced against usage of other
+//! instances as of their last roll-up.
+
+use super::alerts::Alerts;
+use super::{Clock, Store};
+
+use drawbridge_type::TagContext;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use anyhow::bail;
+use async_std::sync::Arc;
+use async_std::task::{sleep, spawn};
+use tracing::{debug, info, trace, warn};
+use uuid::Uuid;
+
+/// Name of the lease held by the leader.
+const LEADER_LEASE: &str = "leader";
+
+/// Age, after which entities left in the staging area are considered stale.
+const STALE_STAGING: Duration = Duration::from_secs(60 * 60);
+
+/// Delay between attempts to acquire a lock held by another instance.
+const LOCK_RETRY: Duration = Duration::from_millis(50);
+
+/// Configuration of an instance of a cluster.
+#[derive(Clone, Debug)]
+pub struct ClusterConfig {
+    /// Name of the instance, which must be unique within the cluster
+    pub instance: String,
+    /// Duration, after which leases not renewed expire
+    pub lease_ttl: Duration,
+    /// Interval of consistency checks run by the leader, if any
+    pub scrub_interval: Option<Duration>,
+}
+
+/// Coordinator of an instance of a cluster.
+#[derive(Debug)]
+pub struct Cluster {
+    config: ClusterConfig,
+    clock: Arc<dyn Clock>,
+    /// Whether the instance holds the leader lease
+    leader: AtomicBool,
+}
+
+/// A lock acquired by [Cluster::lock], which must be released by [Lock::release].
+///
+/// Locks, which are not released, expire.
+#[derive(Debug)]
+#[must_use]
+pub(crate) struct Lock<'a> {
+    store: &'a Store,
+    name: String,
+    holder: String,
+}
+
+impl Lock<'_> {
+    /// Releases the lock.
+    pub(crate) async fn release(self) {
+        if let Err(e) = self.store.release_lease(&self.name, &self.holder).await {
+            warn!(target: "app::cluster", "failed to release lock `{}`: {:?}", self.name, e);
+        }
+    }
+}
+
+impl Cluster {
+    /// Constructs a new [Cluster], which times leases using `clock`.
+    pub fn new(config: ClusterConfig, clock: Arc<dyn Clock>) -> anyhow::Result<Self> {
+        if config.instance.is_empty() {
+            bail!("instance name must not be empty");
+        }
+        if config.lease_ttl < Duration::from_secs(3) {
+            bail!("lease TTL must be at least 3 seconds");
+        }
+        Ok(Self {
+            config,
+            clock,
+            leader: AtomicBool::new(false),
+        })
+    }
+
+    /// Acquires lock `name` in `store`, waiting until it is released by other instances or
+    /// expires.
+    pub(crate) async fn lock<'a>(&self, store: &'a Store, name: &str) -> anyhow::Result<Lock<'a>> {
+        let holder = format!("{}/{}", self.config.instance, Uuid::new_v4());
+        while !store
+            .acquire_lease(name, &holder, self.clock.now(), self.config.lease_ttl)
+            .await?
+        {
+            sleep(LOCK_RETRY).await;
+        }
+        trace!(target: "app::cluster", "acquired lock `{name}`");
+        Ok(Lock {
+            store,
+            name: name.into(),
+            holder,
+        })
+    }
+
+    /// Acquires the lock of tag `cx` in `store`.
+    pub(crate) async fn lock_tag<'a>(
+        &self,
+        store: &'a Store,
+        cx: &TagContext,
+    ) -> anyhow::Result<Lock<'a>> {
+        self.lock(store, &format!("tag/{cx}")).await
+    }
+
+    /// Returns whether the instance is the leader of the cluster.
+    pub(crate) fn is_leader(&self) -> bool {
+        self.leader.load(Ordering::Acquire)
+    }
+
+    /// Competes for leadership of `store` and runs background tasks of the leader, alerting
+    /// of integrity failures found via `alerts`, in background.
+    pub(crate) fn lead(self: &Arc<Self>, store: &Arc<Store>, alerts: &Arc<Alerts>) {
+        let cluster = Arc::clone(self);
+        let store = Arc::clone(store);
+        let alerts = Arc::clone(alerts);
+        let interval = self.config.lease_ttl / 3;
+        _ = spawn(async move {
+            let mut leader = false;
+            let mut last_scrub = None;
+            loop {
+                let now = cluster.clock.now();
+                match store
+                    .acquire_lease(
+                        LEADER_LEASE,
+                        &cluster.config.instance,
+                        now,
+                        cluster.config.lease_ttl,
+                    )
+                    .await
+                {
+                    Ok(acquired) if acquired != leader => {
+                        leader = acquired;
+                        info!(target: "app::cluster", "instance `{}` {} leadership", cluster.config.instance, if leader { "acquired" } else { "lost" });
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        warn!(target: "app::cluster", "failed to acquire leadership: {:?}", e);
+                        leader = false;
+                    }
+                }
+                cluster.leader.store(leader, Ordering::Release);
+                if leader {
+                    match store.sweep_staging(now - STALE_STAGING).await {
+                        Ok(0) => {}
+                        Ok(n) => {
+                            debug!(target: "app::cluster", "removed {n} stale staging entities")
+                        }
+                        Err(e) => {
+                            warn!(target: "app::cluster", "failed to sweep staging area: {:?}", e)
+                        }
+                    }
+                    if let Some(scrub) = cluster.config.scrub_interval {
+                        if last_scrub.is_none_or(|last| now >= last + scrub) {
+                            last_scrub = Some(now);
+                            match store.check().await {
+                                Ok(report) if report.is_consistent() => {
+                                    debug!(target: "app::cluster", "checked {} entities", report.entities)
+                                }
+                                Ok(report) => alerts.send_report(&report).await,
+                                Err(e) => {
+                                    warn!(target: "app::cluster", "failed to check store: {:?}", e)
+                                }
+                            }
+                        }
+                    }
+                }
+                sleep(interval).await;
+            }
+        });
+    }
+}
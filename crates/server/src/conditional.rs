@@ -0,0 +1,112 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Entity tags and [RFC 9110] conditional `GET` and `HEAD` requests.
+//!
+//! Successful responses carrying a `Content-Digest` header are assigned a strong entity tag
+//! derived from the digest, unless they already carry one. Requests with a matching
+//! `If-None-Match`, or, in its absence, an `If-Modified-Since` not preceding the
+//! `Last-Modified` time of the response, are answered with `304 Not Modified`.
+//!
+//! Writes of repositories and tags evaluate `If-Unmodified-Since` against the time the entity
+//! was last modified at using [assert_unmodified_since] and are answered with
+//! `412 Precondition Failed`, if it was modified after the date.
+//!
+//! [RFC 9110]: https://www.rfc-editor.org/rfc/rfc9110#name-conditional-requests
+
+use super::{Entity, GetError};
+
+use drawbridge_type::digest::ContentDigest;
+
+use std::time::SystemTime;
+
+use axum::body::{boxed, Empty};
+use axum::headers::{
+    ETag, Header, HeaderMapExt, IfMatch, IfModifiedSince, IfNoneMatch, IfUnmodifiedSince,
+    LastModified,
+};
+use axum::http::header::CONTENT_LENGTH;
+use axum::http::{HeaderMap, Method, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use camino::Utf8Path;
+use tracing::debug;
+
+/// Returns the strong entity tag of content with digest `value` of a `Content-Digest` header.
+fn etag(value: &str) -> Option<ETag> {
+    format!(r#""{value}""#).parse().ok()
+}
+
+/// Returns `412 Precondition Failed`, if `headers` of a write request carry an
+/// `If-Unmodified-Since` date preceding the time `entity` was last modified at.
+///
+/// As specified by [RFC 9110], the header is ignored along with an `If-Match` header and for
+/// entities, which do not exist.
+///
+/// [RFC 9110]: https://www.rfc-editor.org/rfc/rfc9110#name-if-unmodified-since
+#[allow(clippy::result_large_err)]
+pub(crate) async fn assert_unmodified_since<P: AsRef<Utf8Path>>(
+    headers: &HeaderMap,
+    entity: &Entity<'_, P>,
+) -> Result<(), Response> {
+    let since = match headers.typed_get::<IfUnmodifiedSince>() {
+        Some(since) if !headers.contains_key(IfMatch::name()) => since,
+        _ => return Ok(()),
+    };
+    match entity.get_modified().await {
+        Ok(modified) if since.precondition_passes(modified) => Ok(()),
+        Ok(_) => Err((
+            StatusCode::PRECONDITION_FAILED,
+            "Entity was modified since the `If-Unmodified-Since` date",
+        )
+            .into_response()),
+        Err(GetError::NotFound) => Ok(()),
+        Err(e) => {
+            debug!(target: "app::conditional", "failed to get modification time: {:?}", e);
+            Err(e.into_response())
+        }
+    }
+}
+
+/// Assigns entity tags to responses and evaluates conditional request headers.
+pub(crate) async fn handle<B>(req: Request<B>, next: Next<B>) -> Response {
+    let method = req.method().clone();
+    let if_none_match = req.headers().typed_get::<IfNoneMatch>();
+    let if_modified_since = req.headers().typed_get::<IfModifiedSince>();
+
+    let mut res = next.run(req).await;
+    if !matches!(method, Method::GET | Method::HEAD) || res.status() != StatusCode::OK {
+        return res;
+    }
+
+    let etag = match res.headers().typed_get::<ETag>() {
+        Some(etag) => Some(etag),
+        None => {
+            let etag = res
+                .headers()
+                .get(ContentDigest::<Box<[u8]>>::name())
+                .and_then(|value| value.to_str().ok())
+                .and_then(etag);
+            if let Some(ref etag) = etag {
+                res.headers_mut().typed_insert(etag.clone());
+            }
+            etag
+        }
+    };
+    let not_modified = match (if_none_match, etag) {
+        (Some(cond), Some(ref etag)) => !cond.precondition_passes(etag),
+        (Some(_), None) => false,
+        (None, _) => match (if_modified_since, res.headers().typed_get::<LastModified>()) {
+            (Some(since), Some(modified)) => !since.is_modified(SystemTime::from(modified)),
+            _ => false,
+        },
+    };
+    if !not_modified {
+        return res;
+    }
+
+    let (mut parts, _) = res.into_parts();
+    parts.status = StatusCode::NOT_MODIFIED;
+    _ = parts.headers.remove(CONTENT_LENGTH);
+    Response::from_parts(parts, boxed(Empty::new()))
+}
@@ -0,0 +1,231 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Delegation of namespaces to other drawbridge servers.
+//!
+//! Requests under a delegated namespace, e.g. `acme/*`, are either redirected to the server
+//! the namespace is delegated to or transparently proxied to it, so that namespaces may be
+//! hosted by several servers under one logical naming root. Delegation takes precedence over
+//! [federation](super::federation) and [read replicas](super::replica).
+//!
+//! Proxied requests are forwarded along with their headers, including `Authorization`, so the
+//! server the namespace is delegated to must accept tokens of the same OpenID Connect issuer.
+//! Client certificates cannot be forwarded.
+
+use super::handle::split_api_path;
+use super::stream::stream_chunks;
+
+use drawbridge_type::UserName;
+
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::str::FromStr;
+
+use anyhow::{bail, ensure};
+use async_std::sync::Arc;
+use async_std::task::{block_on, spawn_blocking};
+use axum::body::{boxed, Body, Bytes, HttpBody, StreamBody};
+use axum::http::header::{HOST, LOCATION};
+use axum::http::{Request, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use blocking::Unblock;
+use openidconnect::url::Url;
+use tracing::{debug, trace};
+
+/// Headers, which are not forwarded by proxies.
+const HOP_BY_HOP: [&str; 8] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// How requests under a delegated namespace are handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DelegationMode {
+    /// Redirect the client with `307 Temporary Redirect`
+    #[default]
+    Redirect,
+    /// Forward the request and reply with the response
+    Proxy,
+}
+
+impl FromStr for DelegationMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "redirect" => Ok(Self::Redirect),
+            "proxy" => Ok(Self::Proxy),
+            _ => bail!("unknown delegation mode `{s}`, expected `redirect` or `proxy`"),
+        }
+    }
+}
+
+/// A server a namespace is delegated to.
+#[derive(Clone, Debug)]
+pub struct Delegate {
+    /// URL of the server, e.g. `https://acme.example.com`
+    pub url: Url,
+    pub mode: DelegationMode,
+}
+
+/// Delegated namespaces and the servers they are delegated to.
+#[derive(Clone, Debug, Default)]
+pub struct Delegation(HashMap<UserName, Delegate>);
+
+impl Delegation {
+    /// Delegates `namespace` to `delegate`.
+    pub fn insert(&mut self, namespace: UserName, delegate: Delegate) -> anyhow::Result<()> {
+        ensure!(
+            matches!(delegate.url.scheme(), "http" | "https"),
+            "unsupported delegate URL scheme `{}`",
+            delegate.url.scheme()
+        );
+        ensure!(
+            !self.0.contains_key(&namespace),
+            "namespace `{namespace}` is already delegated"
+        );
+        _ = self.0.insert(namespace, delegate);
+        Ok(())
+    }
+
+    /// Returns the server `namespace` is delegated to, if any.
+    pub fn get(&self, namespace: &UserName) -> Option<&Delegate> {
+        self.0.get(namespace)
+    }
+}
+
+/// Returns the namespace targeted by a request to `path`, if any.
+fn target(path: &str) -> Option<UserName> {
+    let (_, path) = split_api_path(path)?;
+    path.trim_start_matches('/').split('/').next()?.parse().ok()
+}
+
+/// Returns the URL of `uri` on the server at `url`.
+fn location(url: &Url, uri: &Uri) -> Url {
+    let mut url = url.clone();
+    url.set_path(uri.path());
+    url.set_query(uri.query());
+    url
+}
+
+fn is_hop_by_hop(name: &str) -> bool {
+    HOP_BY_HOP.iter().any(|h| h.eq_ignore_ascii_case(name))
+}
+
+/// Blocking reader of a request body.
+struct BodyReader {
+    body: Body,
+    buf: Bytes,
+}
+
+impl Read for BodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.buf.is_empty() {
+            match block_on(self.body.data()) {
+                None => return Ok(0),
+                Some(Ok(chunk)) => self.buf = chunk,
+                Some(Err(e)) => return Err(io::Error::other(e)),
+            }
+        }
+        let n = buf.len().min(self.buf.len());
+        buf[..n].copy_from_slice(&self.buf.split_to(n));
+        Ok(n)
+    }
+}
+
+/// Forwards `req` to the server at `url` and returns its response.
+async fn forward(url: Url, req: Request<Body>) -> Response {
+    let (parts, body) = req.into_parts();
+    let res = spawn_blocking(move || {
+        let mut req = ureq::AgentBuilder::new()
+            .redirects(0)
+            .build()
+            .request(parts.method.as_str(), url.as_str());
+        for (name, value) in parts.headers.iter() {
+            if name == HOST || is_hop_by_hop(name.as_str()) {
+                continue;
+            }
+            if let Ok(value) = value.to_str() {
+                req = req.set(name.as_str(), value);
+            }
+        }
+        let res = if body.is_end_stream() {
+            req.call()
+        } else {
+            req.send(BodyReader {
+                body,
+                buf: Bytes::new(),
+            })
+        };
+        match res {
+            Ok(res) | Err(ureq::Error::Status(_, res)) => Ok(res),
+            Err(e) => Err(anyhow::Error::from(e)),
+        }
+    })
+    .await;
+    let res = match res {
+        Ok(res) => res,
+        Err(e) => {
+            debug!(target: "app::delegation", "failed to forward request: {:?}", e);
+            return (
+                StatusCode::BAD_GATEWAY,
+                "Failed to forward request to delegated server",
+            )
+                .into_response();
+        }
+    };
+
+    let mut builder = Response::builder().status(res.status());
+    for name in res.headers_names() {
+        if is_hop_by_hop(&name) {
+            continue;
+        }
+        for value in res.all(&name) {
+            builder = builder.header(&name, value);
+        }
+    }
+    builder
+        .body(boxed(StreamBody::new(stream_chunks(Unblock::new(
+            res.into_reader(),
+        )))))
+        .unwrap_or_else(|e| {
+            debug!(target: "app::delegation", "invalid response of delegated server: {:?}", e);
+            StatusCode::BAD_GATEWAY.into_response()
+        })
+}
+
+/// Redirects or proxies requests under delegated namespaces to the servers they are
+/// delegated to.
+pub(crate) async fn handle(req: Request<Body>, next: Next<Body>) -> Response {
+    let delegate = req
+        .extensions()
+        .get::<Arc<Delegation>>()
+        .zip(target(req.uri().path()))
+        .and_then(|(delegation, namespace)| delegation.get(&namespace).cloned());
+    let Delegate { url, mode } = match delegate {
+        Some(delegate) => delegate,
+        None => return next.run(req).await,
+    };
+    let url = location(&url, req.uri());
+    match mode {
+        DelegationMode::Redirect => {
+            trace!(target: "app::delegation", "redirect to `{url}`");
+            (
+                StatusCode::TEMPORARY_REDIRECT,
+                [(LOCATION, url.to_string())],
+            )
+                .into_response()
+        }
+        DelegationMode::Proxy => {
+            trace!(target: "app::delegation", "forward to `{url}`");
+            forward(url, req).await
+        }
+    }
+}
@@ -0,0 +1,129 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Approximate counters of downloads of tags and their tree entries.
+//!
+//! Downloads are counted in memory and periodically added to the [TagStats] persisted
+//! alongside each tag, so that serving a download never waits for a write to the store.
+//! Counts not yet persisted are lost if the server crashes.
+
+use super::cluster::Cluster;
+use super::{GetError, Store};
+
+use drawbridge_type::{TagContext, TagStats, TreeContext};
+
+use std::collections::HashMap;
+use std::mem::take;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_std::sync::Arc;
+use async_std::task::{sleep, spawn};
+use tracing::{debug, trace, warn};
+
+/// Interval, in which counts are persisted.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Download counts, which were not persisted yet.
+#[derive(Debug, Default)]
+pub struct Downloads {
+    pending: Mutex<HashMap<TagContext, TagStats>>,
+}
+
+impl Downloads {
+    fn update(&self, cx: &TagContext, f: impl FnOnce(&mut TagStats)) {
+        if let Ok(mut pending) = self.pending.lock() {
+            f(pending.entry(cx.clone()).or_default());
+        }
+    }
+
+    /// Counts a successful pull of tag `cx`.
+    pub(crate) fn count_tag(&self, cx: &TagContext) {
+        self.update(cx, |stats| stats.downloads += 1);
+    }
+
+    /// Counts a successful download of tree entry `cx`.
+    pub(crate) fn count_entry(&self, cx: &TreeContext) {
+        self.update(&cx.tag, |stats| {
+            *stats.entries.entry(cx.path.to_string()).or_default() += 1
+        });
+    }
+
+    /// Returns download statistics of tag `cx` including counts not persisted yet.
+    pub(crate) async fn stats(
+        &self,
+        store: &Store,
+        cx: &TagContext,
+    ) -> Result<TagStats, GetError<anyhow::Error>> {
+        let mut stats = store.tag(cx).get_stats().await?;
+        if let Some(pending) = self
+            .pending
+            .lock()
+            .ok()
+            .and_then(|pending| pending.get(cx).cloned())
+        {
+            stats += &pending;
+        }
+        Ok(stats)
+    }
+
+    /// Persists counts every [FLUSH_INTERVAL] in background, locking each tag if `store` is
+    /// shared with other instances of `cluster`.
+    pub(crate) fn flush_periodically(
+        self: &Arc<Self>,
+        store: &Arc<Store>,
+        cluster: Option<Arc<Cluster>>,
+    ) {
+        let downloads = Arc::clone(self);
+        let store = Arc::clone(store);
+        _ = spawn(async move {
+            loop {
+                sleep(FLUSH_INTERVAL).await;
+                let pending = match downloads.pending.lock() {
+                    Ok(mut pending) => take(&mut *pending),
+                    Err(_) => return,
+                };
+                for (cx, counts) in pending {
+                    let lock = match cluster {
+                        Some(ref cluster) => match cluster.lock_tag(&store, &cx).await {
+                            Ok(lock) => Some(lock),
+                            Err(e) => {
+                                warn!(target: "app::downloads", "failed to lock `{cx}`: {:?}", e);
+                                continue;
+                            }
+                        },
+                        None => None,
+                    };
+                    flush(&store, &cx, &counts).await;
+                    if let Some(lock) = lock {
+                        lock.release().await;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Adds `counts` to the statistics of tag `cx` persisted in `store`.
+async fn flush(store: &Store, cx: &TagContext, counts: &TagStats) {
+    let tag = store.tag(cx);
+    // NOTE: Statistics are only kept for published tags.
+    if tag.get_meta().await.is_err() {
+        debug!(target: "app::downloads", "skip counts of missing tag `{cx}`");
+        return;
+    }
+    let mut stats = match tag.get_stats().await {
+        Ok(stats) => stats,
+        Err(e) => {
+            warn!(target: "app::downloads", "failed to read statistics of `{cx}`: {:?}", e);
+            return;
+        }
+    };
+    stats += counts;
+    match tag.put_stats(&stats).await {
+        Ok(()) => trace!(target: "app::downloads", "persisted counts of `{cx}`"),
+        Err(e) => {
+            warn!(target: "app::downloads", "failed to write statistics of `{cx}`: {:?}", e)
+        }
+    }
+}
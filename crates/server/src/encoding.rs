@@ -0,0 +1,81 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! `Accept-Encoding` negotiation of tree node contents.
+//!
+//! Compressible tree nodes are served `gzip`-encoded to clients accepting it. The compressed
+//! variant is stored alongside the original, while the `Content-Digest` of the response always
+//! refers to the identity encoding, so that clients verify the contents after decoding them.
+//! Other encodings, e.g. `zstd` and `br`, are not supported and negotiate to `identity`.
+
+use drawbridge_type::Meta;
+
+use axum::headers::ETag;
+use axum::http::header::{HeaderName, ACCEPT_ENCODING, CONTENT_ENCODING, VARY};
+use axum::http::HeaderMap;
+
+/// Maximum size of contents, which are compressed.
+const MAX_COMPRESSIBLE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Minimum size of contents, which are compressed.
+const MIN_COMPRESSIBLE_SIZE: u64 = 1024;
+
+/// A `Vary` header, which must be returned with every negotiated response.
+pub(crate) const VARY_ACCEPT_ENCODING: [(HeaderName, &str); 1] = [(VARY, "accept-encoding")];
+
+/// A `Content-Encoding` header of a `gzip`-encoded response.
+pub(crate) const CONTENT_ENCODING_GZIP: [(HeaderName, &str); 1] = [(CONTENT_ENCODING, "gzip")];
+
+/// Returns `true` if contents described by `meta` benefit from compression.
+pub(crate) fn is_compressible(meta: &Meta) -> bool {
+    if !(MIN_COMPRESSIBLE_SIZE..=MAX_COMPRESSIBLE_SIZE).contains(&meta.size) {
+        return false;
+    }
+    let mime = &meta.mime;
+    match (mime.type_(), mime.subtype()) {
+        ("text", _) => true,
+        ("application", sub) => {
+            matches!(
+                sub,
+                "json" | "javascript" | "toml" | "wasm" | "xml" | "yaml"
+            ) || mime
+                .suffix()
+                .is_some_and(|suffix| suffix == "json" || suffix == "xml")
+        }
+        _ => false,
+    }
+}
+
+/// Returns `true` if `Accept-Encoding` headers in `headers` accept `gzip`.
+pub(crate) fn accepts_gzip(headers: &HeaderMap) -> bool {
+    let mut gzip = None;
+    let mut wildcard = None;
+    for coding in headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+    {
+        let mut params = coding.split(';').map(str::trim);
+        let name = params.next().unwrap_or_default();
+        let accepted = params
+            .filter_map(|param| param.strip_prefix("q="))
+            .filter_map(|q| q.parse::<f32>().ok())
+            .next_back()
+            .is_none_or(|q| q > 0.0);
+        if name.eq_ignore_ascii_case("gzip") || name.eq_ignore_ascii_case("x-gzip") {
+            gzip = Some(accepted);
+        } else if name == "*" {
+            wildcard = Some(accepted);
+        }
+    }
+    gzip.or(wildcard).unwrap_or(false)
+}
+
+/// Returns the strong entity tag of the `gzip` variant of contents described by `meta`.
+///
+/// The tag differs from the one assigned to the identity encoding, since the representations
+/// are not byte-for-byte identical.
+pub(crate) fn gzip_etag(meta: &Meta) -> Option<ETag> {
+    format!(r#""{}+gzip""#, meta.hash).parse().ok()
+}
@@ -0,0 +1,324 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Internal bus of entity lifecycle events.
+//!
+//! Handlers publish an [Event] once the change it describes is visible in the store.
+//! Side effects, e.g. [Webhooks](super::webhooks::Webhooks) notifications, are performed by
+//! subscribers of the [EventBus], each of which receives all events in publication order.
+//! Events may also be forwarded to external systems, see [NatsConfig], and are streamed to
+//! clients by [stream].
+
+mod nats;
+mod stream;
+
+pub use nats::*;
+pub use stream::*;
+
+use super::cbor;
+
+use drawbridge_type::digest::ContentDigest;
+use drawbridge_type::{RepositoryContext, TagContext, TreeContext};
+
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use anyhow::bail;
+use async_std::channel::{bounded, Receiver, Sender, TrySendError};
+use serde_json::{json, Value};
+use tracing::{trace, warn};
+
+/// Maximum number of events buffered per subscriber, events published to a subscriber with a
+/// full buffer are dropped.
+const SUBSCRIBER_CAPACITY: usize = 1024;
+
+/// Number of most recently published events kept for [EventBus::subscribe_after].
+const HISTORY_CAPACITY: usize = 1024;
+
+/// Identifier of a published event, which is assigned in publication order starting at 1.
+///
+/// Identifiers are not persisted and restart with the server.
+pub type EventId = u64;
+
+/// An [Event] along with its identifier.
+pub type Published = (EventId, Event);
+
+/// Reason of removal of an entity from the store.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeleteCause {
+    /// Contents failed deferred digest verification and were quarantined
+    IntegrityFailure,
+    /// Contents were rejected by a content scanner and their tag was quarantined
+    ScanRejected,
+    /// A repository or tag was deleted by a user and moved into the trash
+    Trashed,
+}
+
+impl DeleteCause {
+    /// Returns the name of the cause, e.g. `scan-rejected`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::IntegrityFailure => "integrity-failure",
+            Self::ScanRejected => "scan-rejected",
+            Self::Trashed => "trashed",
+        }
+    }
+}
+
+/// An entity lifecycle event.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// A repository was created
+    RepositoryCreated { repository: RepositoryContext },
+    /// A repository was removed from the store along with all of its tags
+    RepositoryDeleted {
+        repository: RepositoryContext,
+        /// Total size of contents of all tree nodes removed
+        size: u64,
+        cause: DeleteCause,
+    },
+    /// A repository deleted was restored from the trash along with all of its tags
+    RepositoryRestored {
+        repository: RepositoryContext,
+        /// Total size of contents of all tree nodes restored
+        size: u64,
+    },
+    /// A tag was created, or published along with its tree
+    TagUpdated {
+        tag: TagContext,
+        digest: ContentDigest,
+    },
+    /// A tag was removed from the store along with its tree
+    TagDeleted {
+        tag: TagContext,
+        digest: ContentDigest,
+        /// Total size of contents of all tree nodes removed
+        size: u64,
+        cause: DeleteCause,
+    },
+    /// A tag deleted was restored from the trash along with its tree
+    TagRestored {
+        tag: TagContext,
+        /// Total size of contents of all tree nodes restored
+        size: u64,
+    },
+    /// A tree node was uploaded, possibly to a tag, which is not published yet
+    TreeEntryUploaded {
+        node: TreeContext,
+        digest: ContentDigest,
+        size: u64,
+    },
+    /// A tree node was removed from the store
+    EntityDeleted {
+        node: TreeContext,
+        digest: ContentDigest,
+        size: u64,
+        cause: DeleteCause,
+    },
+    /// Corrupted contents of a tree node were replaced by verified contents fetched from a
+    /// replication peer
+    EntityRepaired {
+        node: TreeContext,
+        digest: ContentDigest,
+        size: u64,
+        /// Name of the peer the contents were fetched from
+        peer: String,
+    },
+}
+
+impl Event {
+    /// Returns the repository the event relates to.
+    pub fn repository(&self) -> &RepositoryContext {
+        match self {
+            Self::RepositoryCreated { repository }
+            | Self::RepositoryDeleted { repository, .. }
+            | Self::RepositoryRestored { repository, .. } => repository,
+            Self::TagUpdated { tag, .. }
+            | Self::TagDeleted { tag, .. }
+            | Self::TagRestored { tag, .. } => &tag.repository,
+            Self::TreeEntryUploaded { node, .. }
+            | Self::EntityDeleted { node, .. }
+            | Self::EntityRepaired { node, .. } => &node.tag.repository,
+        }
+    }
+
+    /// Returns the name of the kind of the event, e.g. `tag-updated`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::RepositoryCreated { .. } => "repository-created",
+            Self::RepositoryDeleted { .. } => "repository-deleted",
+            Self::RepositoryRestored { .. } => "repository-restored",
+            Self::TagUpdated { .. } => "tag-updated",
+            Self::TagDeleted { .. } => "tag-deleted",
+            Self::TagRestored { .. } => "tag-restored",
+            Self::TreeEntryUploaded { .. } => "tree-entry-uploaded",
+            Self::EntityDeleted { .. } => "entity-deleted",
+            Self::EntityRepaired { .. } => "entity-repaired",
+        }
+    }
+
+    /// Returns the event as a JSON object, which is how it is represented outside of the
+    /// server.
+    pub fn to_json(&self) -> Value {
+        let (repository, tag, path, digest) = match self {
+            Self::RepositoryCreated { repository }
+            | Self::RepositoryDeleted { repository, .. }
+            | Self::RepositoryRestored { repository, .. } => (repository, None, None, None),
+            Self::TagUpdated { tag, digest } | Self::TagDeleted { tag, digest, .. } => {
+                (&tag.repository, Some(&tag.name), None, Some(digest))
+            }
+            Self::TagRestored { tag, .. } => (&tag.repository, Some(&tag.name), None, None),
+            Self::TreeEntryUploaded { node, digest, .. }
+            | Self::EntityDeleted { node, digest, .. }
+            | Self::EntityRepaired { node, digest, .. } => (
+                &node.tag.repository,
+                Some(&node.tag.name),
+                Some(&node.path),
+                Some(digest),
+            ),
+        };
+        let mut value = json!({
+            "event": self.name(),
+            "repository": repository.to_string(),
+        });
+        for (name, member) in [
+            ("tag", tag.map(ToString::to_string)),
+            ("path", path.map(ToString::to_string)),
+            ("digest", digest.map(ToString::to_string)),
+        ] {
+            if let Some(member) = member {
+                value[name] = member.into();
+            }
+        }
+        if let Self::RepositoryDeleted { cause, .. }
+        | Self::TagDeleted { cause, .. }
+        | Self::EntityDeleted { cause, .. } = self
+        {
+            value["cause"] = cause.name().into();
+        }
+        if let Self::EntityRepaired { peer, .. } = self {
+            value["peer"] = peer.as_str().into();
+        }
+        if let Self::RepositoryDeleted { size, .. }
+        | Self::RepositoryRestored { size, .. }
+        | Self::TagDeleted { size, .. }
+        | Self::TagRestored { size, .. }
+        | Self::TreeEntryUploaded { size, .. }
+        | Self::EntityDeleted { size, .. }
+        | Self::EntityRepaired { size, .. } = self
+        {
+            value["length"] = (*size).into();
+        }
+        value
+    }
+
+    /// Encodes the event represented as returned by [Self::to_json] in `format`.
+    pub fn encode(&self, format: EventFormat) -> Vec<u8> {
+        let value = self.to_json();
+        match format {
+            // NOTE: Serialization of a `Value` cannot fail.
+            EventFormat::Json => serde_json::to_vec(&value).unwrap_or_default(),
+            EventFormat::Cbor => cbor::to_vec(&value).unwrap_or_default(),
+            #[cfg(feature = "msgpack")]
+            EventFormat::MessagePack => rmp_serde::to_vec_named(&value).unwrap_or_default(),
+        }
+    }
+}
+
+/// Serialization format of events forwarded to external systems.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EventFormat {
+    #[default]
+    Json,
+    Cbor,
+    /// MessagePack, available with the `msgpack` feature
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl FromStr for EventFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "cbor" => Ok(Self::Cbor),
+            #[cfg(feature = "msgpack")]
+            "msgpack" => Ok(Self::MessagePack),
+            #[cfg(not(feature = "msgpack"))]
+            "msgpack" => bail!("event format `msgpack` requires the `msgpack` feature"),
+            _ => bail!("unknown event format `{s}`, expected `json`, `cbor` or `msgpack`"),
+        }
+    }
+}
+
+/// Broadcast channel of [Event]s.
+#[derive(Debug, Default)]
+pub struct EventBus {
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    last_id: EventId,
+    history: VecDeque<Published>,
+    subscribers: Vec<Sender<Published>>,
+}
+
+impl EventBus {
+    /// Returns a receiver of all events published from now on along with their identifiers.
+    pub fn subscribe(&self) -> Receiver<Published> {
+        let (_, rx) = self.subscribe_after(None);
+        rx
+    }
+
+    /// Like [Self::subscribe], but also returns the recently published events following the
+    /// one identified by `last_id`, if any.
+    ///
+    /// If `last_id` is not known, e.g. because it was published before the server restarted,
+    /// all recently published events are returned.
+    pub fn subscribe_after(
+        &self,
+        last_id: Option<EventId>,
+    ) -> (Vec<Published>, Receiver<Published>) {
+        let (tx, rx) = bounded(SUBSCRIBER_CAPACITY);
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return (vec![], rx),
+        };
+        let missed = match last_id {
+            None => vec![],
+            Some(last_id) if last_id > state.last_id => state.history.iter().cloned().collect(),
+            Some(last_id) => state
+                .history
+                .iter()
+                .filter(|(id, _)| *id > last_id)
+                .cloned()
+                .collect(),
+        };
+        state.subscribers.push(tx);
+        (missed, rx)
+    }
+
+    /// Publishes `event` to all subscribers.
+    pub fn publish(&self, event: Event) {
+        trace!(target: "app::events", "publish {:?}", event);
+        if let Ok(mut state) = self.state.lock() {
+            state.last_id += 1;
+            let id = state.last_id;
+            if state.history.len() == HISTORY_CAPACITY {
+                _ = state.history.pop_front();
+            }
+            state.history.push_back((id, event.clone()));
+            state.subscribers.retain(|tx| match tx.try_send((id, event.clone())) {
+                Ok(()) => true,
+                Err(TrySendError::Full((_, event))) => {
+                    warn!(target: "app::events", "subscriber lagging behind, dropped {:?}", event);
+                    true
+                }
+                Err(TrySendError::Closed(_)) => false,
+            });
+        }
+    }
+}
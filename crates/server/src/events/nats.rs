@@ -0,0 +1,176 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Forwarding of events to a [NATS] server.
+//!
+//! Each event is published to subject `<prefix>.<name>`, e.g. `drawbridge.tag-updated`,
+//! using the core NATS protocol. The connection is re-established with exponential backoff
+//! if it fails, events published in the meantime are buffered by the [EventBus]. The event,
+//! which failed to be published, is published again once reconnected, so events are
+//! delivered at least once, as the server may have received it before the connection failed.
+//!
+//! [NATS]: https://docs.nats.io/reference/reference-protocols/nats-protocol
+
+use super::{EventBus, EventFormat};
+
+use std::time::Duration;
+
+use anyhow::{anyhow, ensure, Context};
+use async_std::io::BufReader;
+use async_std::net::TcpStream;
+use async_std::sync::{Arc, Mutex};
+use async_std::task::{sleep, spawn};
+use futures::{AsyncBufReadExt, AsyncWriteExt, StreamExt};
+use openidconnect::url::Url;
+use percent_encoding::percent_decode_str;
+use serde_json::json;
+use tracing::{debug, info, trace, warn};
+
+/// Port of NATS servers, unless specified in the URL.
+const DEFAULT_PORT: u16 = 4222;
+
+/// Delay before the first reconnection attempt, which is doubled after each failed attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Maximum delay between reconnection attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Configuration of event forwarding to a NATS server.
+#[derive(Clone, Debug)]
+pub struct NatsConfig {
+    /// URL of the server of the form `nats://[user:password@]host[:port]`
+    pub url: Url,
+    /// Prefix of subjects events are published to
+    pub subject_prefix: String,
+    pub format: EventFormat,
+}
+
+/// A connection to a NATS server.
+#[derive(Debug)]
+struct Connection {
+    stream: Arc<Mutex<TcpStream>>,
+}
+
+impl Connection {
+    /// Connects to the server at `url` and answers its pings in background.
+    async fn open(url: &Url) -> anyhow::Result<Self> {
+        ensure!(
+            url.scheme() == "nats",
+            "unsupported URL scheme `{}`",
+            url.scheme()
+        );
+        let host = url.host_str().ok_or_else(|| anyhow!("missing host"))?;
+        let stream = TcpStream::connect((host, url.port().unwrap_or(DEFAULT_PORT)))
+            .await
+            .context("failed to connect")?;
+
+        let mut lines = BufReader::new(stream.clone()).lines();
+        let info = lines
+            .next()
+            .await
+            .ok_or_else(|| anyhow!("connection closed before `INFO`"))?
+            .context("failed to read `INFO`")?;
+        ensure!(info.starts_with("INFO "), "unexpected message `{info}`");
+
+        let mut options = json!({
+            "verbose": false,
+            "pedantic": false,
+            "name": "drawbridge",
+            "lang": "rust",
+            "version": env!("CARGO_PKG_VERSION"),
+        });
+        if !url.username().is_empty() {
+            // NOTE: Credentials are percent-encoded in URLs.
+            let decode = |s| {
+                percent_decode_str(s)
+                    .decode_utf8()
+                    .map(String::from)
+                    .context("invalid credentials")
+            };
+            options["user"] = decode(url.username())?.into();
+            options["pass"] = decode(url.password().unwrap_or_default())?.into();
+        }
+        let stream = Arc::new(Mutex::new(stream));
+        stream
+            .lock()
+            .await
+            .write_all(format!("CONNECT {options}\r\n").as_bytes())
+            .await
+            .context("failed to send `CONNECT`")?;
+
+        let pong = Arc::clone(&stream);
+        _ = spawn(async move {
+            while let Some(Ok(line)) = lines.next().await {
+                if line == "PING" {
+                    if let Err(e) = pong.lock().await.write_all(b"PONG\r\n").await {
+                        debug!(target: "app::events::nats", "failed to send `PONG`: {:?}", e);
+                        return;
+                    }
+                } else if let Some(err) = line.strip_prefix("-ERR ") {
+                    warn!(target: "app::events::nats", "server error: {err}");
+                }
+            }
+        });
+        Ok(Self { stream })
+    }
+
+    /// Publishes `payload` to `subject`.
+    async fn publish(&self, subject: &str, payload: &[u8]) -> anyhow::Result<()> {
+        let mut msg = format!("PUB {subject} {}\r\n", payload.len()).into_bytes();
+        msg.extend(payload);
+        msg.extend(b"\r\n");
+        self.stream
+            .lock()
+            .await
+            .write_all(&msg)
+            .await
+            .context("failed to send `PUB`")
+    }
+}
+
+/// Forwards all events published on `events` to the NATS server configured by `config`
+/// in background.
+pub(crate) fn forward(config: NatsConfig, events: &EventBus) {
+    let events = events.subscribe();
+    _ = spawn(async move {
+        let NatsConfig {
+            url,
+            subject_prefix,
+            format,
+        } = config;
+        let mut backoff = INITIAL_BACKOFF;
+        // Subject and payload of the event, which failed to be published, if any
+        let mut pending = None;
+        loop {
+            let conn = match Connection::open(&url).await {
+                Ok(conn) => conn,
+                Err(e) => {
+                    warn!(target: "app::events::nats", "failed to connect to `{}`: {:?}", url.host_str().unwrap_or_default(), e);
+                    sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    continue;
+                }
+            };
+            info!(target: "app::events::nats", "connected to `{}`", url.host_str().unwrap_or_default());
+            backoff = INITIAL_BACKOFF;
+            loop {
+                let (subject, payload) = match pending.take() {
+                    Some(pending) => pending,
+                    None => match events.recv().await {
+                        Ok((_, event)) => (
+                            format!("{subject_prefix}.{}", event.name()),
+                            event.encode(format),
+                        ),
+                        Err(_) => return,
+                    },
+                };
+                trace!(target: "app::events::nats", "publish to `{subject}`");
+                if let Err(e) = conn.publish(&subject, &payload).await {
+                    warn!(target: "app::events::nats", "failed to publish to `{subject}`: {:?}", e);
+                    pending = Some((subject, payload));
+                    break;
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,100 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{OidcClaims, ReplicaCertificate, ScopeContext, ScopeLevel, Store};
+use super::{Event, EventBus, EventId};
+
+use drawbridge_type::UserContext;
+
+use std::convert::Infallible;
+use std::time::Duration;
+
+use async_std::future::timeout;
+use async_std::sync::Arc;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{self, Sse};
+use axum::response::IntoResponse;
+use axum::Extension;
+use futures::{future, stream, Stream, StreamExt};
+use tracing::trace;
+
+/// Interval, after which a comment is sent on an idle stream to keep the connection open.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Returns the stream of server-sent events published after the one identified by the
+/// `Last-Event-ID` header in `headers`, if any, which match `filter`.
+fn sse(
+    events: &EventBus,
+    headers: &HeaderMap,
+    filter: impl 'static + Send + Fn(&Event) -> bool,
+) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+    let last_id = headers
+        .get("last-event-id")
+        .and_then(|id| id.to_str().ok())
+        .and_then(|id| id.parse::<EventId>().ok());
+    let (missed, rx) = events.subscribe_after(last_id);
+    let live = stream::unfold(rx, |rx| async move {
+        match timeout(KEEP_ALIVE_INTERVAL, rx.recv()).await {
+            Ok(Ok(event)) => Some((Some(event), rx)),
+            Ok(Err(_)) => None,
+            Err(_) => Some((None, rx)),
+        }
+    });
+    let stream = stream::iter(missed.into_iter().map(Some))
+        .chain(live)
+        .filter_map(move |event| {
+            future::ready(match event {
+                Some((id, event)) if filter(&event) => Some(
+                    sse::Event::default()
+                        .id(id.to_string())
+                        .event(event.name())
+                        .data(event.to_json().to_string()),
+                ),
+                Some(_) => None,
+                None => Some(sse::Event::default().comment("")),
+            })
+        })
+        .map(Ok::<_, Infallible>);
+    Sse::new(stream)
+}
+
+/// Streams events of repositories of the user as [server-sent events].
+///
+/// Each event is sent with its identifier, name and JSON representation as data. Clients
+/// reconnecting with the identifier of the last event received in the `Last-Event-ID` header
+/// are first sent the recently published events they missed.
+///
+/// [server-sent events]: https://html.spec.whatwg.org/multipage/server-sent-events.html
+pub async fn stream(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref events): Extension<Arc<EventBus>>,
+    claims: OidcClaims,
+    cx: UserContext,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    trace!(target: "app::events::stream", "called for `{cx}`");
+
+    _ = claims
+        .assert_user(store, &cx, ScopeContext::User, ScopeLevel::Read)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    Ok::<_, axum::response::Response>(sse(events, &headers, move |event| {
+        event.repository().owner == cx
+    }))
+}
+
+/// Streams events of all repositories in the store like [stream], which serves as the change
+/// feed read replicas are kept up to date by.
+pub async fn feed(
+    Extension(ref events): Extension<Arc<EventBus>>,
+    replica: Option<Extension<ReplicaCertificate>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    trace!(target: "app::events::feed", "called");
+
+    if replica.is_none() {
+        return Err((StatusCode::FORBIDDEN, "Replica client certificate required").into_response());
+    }
+    Ok(sse(events, &headers, |_| true))
+}
@@ -0,0 +1,450 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Pull-through caching of namespaces hosted by upstream drawbridge instances.
+//!
+//! An edge instance federates configured namespaces, i.e. users, to upstream instances. A tag
+//! of a federated namespace, which is not stored locally, is fetched from the upstream along
+//! with its tree and repository on first read and stored as if it was uploaded, so that the
+//! store verifies every node against the digest referenced by its parent. Subsequent reads
+//! are served from the store. Since tags are immutable, cached tags are never revalidated.
+//!
+//! Federated namespaces are read-only on the edge: their owner is recorded with a subject,
+//! which matches no OpenID Connect identity. Repositories keep their upstream configuration,
+//! so contents of private repositories may only be read by Steward workload identities
+//! granted access to them.
+
+use super::events::{Event, EventBus};
+use super::handle::{split_api_path, API_VERSION};
+use super::proxy::read_body;
+use super::store::tree_root;
+use super::{json, CreateError, GetError, Placement, Store};
+
+use drawbridge_type::digest::ContentDigest;
+use drawbridge_type::{
+    Error, MediaType, Meta, RepositoryConfig, RepositoryContext, TagContext, TagEntry, TreeContext,
+    TreeDirectory, TreeEntry, TreePath, UserName, UserRecord,
+};
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, ensure, Context};
+use async_std::sync::Arc;
+use async_std::task::spawn_blocking;
+use axum::http::{Method, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use openidconnect::url::Url;
+use rustls::ClientConfig;
+use tracing::{debug, trace};
+
+/// An upstream drawbridge instance.
+#[derive(Clone, Debug)]
+pub struct Upstream {
+    /// URL of the instance, e.g. `https://store.example.com`
+    pub url: Url,
+    /// Bearer token presented to the instance, if any
+    pub token: Option<String>,
+    /// TLS configuration of connections to the instance, e.g. to present a client certificate
+    pub tls: Option<Arc<ClientConfig>>,
+}
+
+/// Upstream drawbridge instances, indexed by the namespace they are federated for.
+#[derive(Clone, Debug, Default)]
+pub struct Federation(HashMap<UserName, Upstream>);
+
+impl Federation {
+    /// Federates `namespace` to `upstream`.
+    pub fn insert(&mut self, namespace: UserName, upstream: Upstream) -> anyhow::Result<()> {
+        ensure!(
+            matches!(upstream.url.scheme(), "http" | "https"),
+            "unsupported upstream URL scheme `{}`",
+            upstream.url.scheme()
+        );
+        ensure!(
+            !self.0.contains_key(&namespace),
+            "namespace `{namespace}` is already federated"
+        );
+        _ = self.0.insert(namespace, upstream);
+        Ok(())
+    }
+
+    /// Returns the upstream `namespace` is federated to.
+    pub fn get(&self, namespace: &UserName) -> Option<&Upstream> {
+        self.0.get(namespace)
+    }
+}
+
+/// How owners of pulled repositories, which do not exist locally, are recorded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Owner {
+    /// With a subject, which matches no OpenID Connect identity
+    Federated,
+    /// With the record fetched from the upstream, which requires a trusted client certificate
+    Fetched,
+}
+
+/// Error returned when pulling from an upstream instance.
+#[derive(Debug)]
+pub(crate) enum PullError {
+    NotFound,
+    Upstream(anyhow::Error),
+    Store(anyhow::Error),
+}
+
+impl From<anyhow::Error> for PullError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Upstream(e)
+    }
+}
+
+impl From<CreateError<anyhow::Error>> for PullError {
+    fn from(e: CreateError<anyhow::Error>) -> Self {
+        match e {
+            CreateError::Internal(e) => Self::Store(e),
+            e => Self::Upstream(anyhow!(
+                "failed to store contents fetched from upstream: {}",
+                Error::from(e)
+            )),
+        }
+    }
+}
+
+impl From<GetError<anyhow::Error>> for PullError {
+    fn from(e: GetError<anyhow::Error>) -> Self {
+        match e {
+            GetError::NotFound => Self::Store(anyhow!("entity not found")),
+            GetError::Internal(e) => Self::Store(e),
+        }
+    }
+}
+
+impl IntoResponse for PullError {
+    fn into_response(self) -> Response {
+        match self {
+            PullError::NotFound => StatusCode::NOT_FOUND.into_response(),
+            PullError::Upstream(e) => {
+                debug!(target: "app::federation", "failed to pull from upstream: {:?}", e);
+                (
+                    StatusCode::BAD_GATEWAY,
+                    "Failed to pull tag from upstream instance",
+                )
+                    .into_response()
+            }
+            PullError::Store(e) => {
+                debug!(target: "app::federation", "failed to cache pulled tag: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+/// Blocking client of the API of an upstream instance.
+#[derive(Clone, Debug)]
+struct Client {
+    agent: ureq::Agent,
+    url: String,
+    token: Option<String>,
+}
+
+impl Client {
+    fn new(Upstream { url, token, tls }: &Upstream) -> Self {
+        let agent = ureq::AgentBuilder::new().user_agent(&format!(
+            "{}/{}",
+            env!("CARGO_CRATE_NAME"),
+            env!("CARGO_PKG_VERSION")
+        ));
+        let agent = match tls {
+            Some(tls) => agent.tls_config(Arc::clone(tls)),
+            None => agent,
+        };
+        Self {
+            agent: agent.build(),
+            url: format!(
+                "{}/api/v{}",
+                url.as_str().trim_end_matches('/'),
+                *API_VERSION
+            ),
+            token: token.clone(),
+        }
+    }
+
+    /// Fetches at most `limit` bytes of the entity at `path` along with its [Meta].
+    fn get(&self, path: &str, limit: u64) -> Result<(Meta, Vec<u8>), PullError> {
+        let url = format!("{}/{path}", self.url);
+        let req = self.agent.get(&url).set("Accept-Encoding", "identity");
+        let req = match self.token {
+            Some(ref token) => req.set("Authorization", &format!("Bearer {token}")),
+            None => req,
+        };
+        let res = req.call().map_err(|e| match e {
+            ureq::Error::Status(404, _) => PullError::NotFound,
+            e => PullError::Upstream(
+                anyhow::Error::new(e).context(format!("failed to fetch `{url}`")),
+            ),
+        })?;
+        let hash = res
+            .header("Content-Digest")
+            .context("`Content-Digest` header missing")?
+            .parse()
+            .context("failed to parse `Content-Digest` header")?;
+        let mime = res
+            .content_type()
+            .parse()
+            .context("failed to parse `Content-Type` header")?;
+        let buf = read_body(res, limit)?;
+        Ok((
+            Meta {
+                hash,
+                size: buf.len() as _,
+                mime,
+            },
+            buf,
+        ))
+    }
+
+    /// Fetches the entity at `path` like [Self::get] without blocking the executor.
+    async fn fetch(&self, path: String, limit: u64) -> Result<(Meta, Vec<u8>), PullError> {
+        let client = self.clone();
+        spawn_blocking(move || client.get(&path, limit)).await
+    }
+}
+
+/// Decodes a fetched JSON document.
+fn decode<T: serde::de::DeserializeOwned>(buf: &[u8]) -> Result<T, PullError> {
+    serde_json::from_slice(buf)
+        .context("failed to decode JSON fetched from upstream")
+        .map_err(PullError::Upstream)
+}
+
+/// Creates repository `cx` along with its owner unless they exist, fetching its configuration
+/// from `upstream`.
+pub(crate) async fn pull_repository(
+    store: &Store,
+    events: &EventBus,
+    upstream: &Upstream,
+    cx: &RepositoryContext,
+    owner: Owner,
+) -> Result<(), PullError> {
+    let client = Client::new(upstream);
+
+    let user = store.user(&cx.owner);
+    match user.get_meta().await {
+        Ok(_) => {}
+        Err(GetError::NotFound) => {
+            let (meta, rec) = match owner {
+                Owner::Federated => {
+                    let rec = UserRecord {
+                        subject: format!("federated:{}", upstream.url),
+                        algorithms: None,
+                    };
+                    let (meta, _) =
+                        json::encode(&rec).map_err(|(_, e)| PullError::Store(anyhow!(e)))?;
+                    (meta, rec)
+                }
+                Owner::Fetched => {
+                    let (meta, buf) = client
+                        .fetch(cx.owner.to_string(), json::MAX_BODY_SIZE)
+                        .await?;
+                    (meta, decode(&buf)?)
+                }
+            };
+            match store.create_user(&cx.owner, meta, &rec).await {
+                Ok(_) | Err(CreateError::Occupied) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    match user.repository(&cx.name).get_meta().await {
+        Ok(_) => Ok(()),
+        Err(GetError::NotFound) => {
+            let (meta, buf) = client.fetch(cx.to_string(), json::MAX_BODY_SIZE).await?;
+            let conf: RepositoryConfig = decode(&buf)?;
+            match user.create_repository(&cx.name, meta, &conf).await {
+                Ok(_) => {
+                    events.publish(Event::RepositoryCreated {
+                        repository: cx.clone(),
+                    });
+                    Ok(())
+                }
+                Err(CreateError::Occupied) => Ok(()),
+                Err(e) => Err(e.into()),
+            }
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Pulls tag `cx` along with its tree from `upstream` and stores it, creating its owner and
+/// repository unless they exist.
+pub(crate) async fn pull(
+    store: &Store,
+    events: &EventBus,
+    upstream: &Upstream,
+    cx: &TagContext,
+    owner: Owner,
+) -> Result<(), PullError> {
+    pull_repository(store, events, upstream, &cx.repository, owner).await?;
+
+    let client = Client::new(upstream);
+    let repo = store.repository(&cx.repository);
+    let path = format!("{}/_tag/{}", cx.repository, cx.name);
+    let (meta, buf) = client.fetch(path.clone(), json::MAX_BODY_SIZE).await?;
+    let entry = TagEntry::decode(meta.mime.essence(), &buf)?;
+
+    if let Some(root) = tree_root(&entry) {
+        let pending = repo.create_pending_tag(&cx.name).await?;
+        let mut nodes = vec![(TreePath::ROOT, root)];
+        while let Some((node, meta)) = nodes.pop() {
+            let url = format!("{path}/tree/{node}");
+            let res = if meta.mime == MediaType::DIRECTORY {
+                let (_, buf) = client.fetch(url, json::MAX_BODY_SIZE).await?;
+                let dir: TreeDirectory<TreeEntry> = decode(&buf)?;
+                nodes.extend(
+                    dir.iter()
+                        .map(|(name, entry)| (node.join(name.clone()), entry.meta.clone())),
+                );
+                pending
+                    .create_directory_node(&node, meta.clone(), &dir)
+                    .await
+            } else {
+                let (_, buf) = client.fetch(url, meta.size).await?;
+                pending
+                    .create_file_node(&node, meta.clone(), buf.as_slice())
+                    .await
+            };
+            match res {
+                Ok(_) => events.publish(Event::TreeEntryUploaded {
+                    node: TreeContext {
+                        tag: cx.clone(),
+                        path: node,
+                    },
+                    digest: meta.hash,
+                    size: meta.size,
+                }),
+                Err(CreateError::Occupied) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    let digest = meta.hash.clone();
+    match repo.create_tag(&cx.name, meta, &entry).await {
+        Ok(_) => events.publish(Event::TagUpdated {
+            tag: cx.clone(),
+            digest,
+        }),
+        Err(CreateError::Occupied) => {}
+        Err(e) => return Err(e.into()),
+    }
+    Ok(())
+}
+
+/// Pulls node `cx` of length `size` with `digest`, which was uploaded to the published tag
+/// after its creation, from `upstream` and stores it, unless the tag does not exist locally.
+pub(crate) async fn pull_node(
+    store: &Store,
+    events: &EventBus,
+    upstream: &Upstream,
+    cx: &TreeContext,
+    digest: &ContentDigest,
+    size: u64,
+) -> Result<(), PullError> {
+    let tag = store.tag(&cx.tag);
+    match tag.get_meta().await {
+        Ok(_) => {}
+        // NOTE: Nodes of tags missing locally are pulled along with the tag.
+        Err(GetError::NotFound) => return Ok(()),
+        Err(e) => return Err(e.into()),
+    }
+    let path = format!(
+        "{}/_tag/{}/tree/{}",
+        cx.tag.repository, cx.tag.name, cx.path
+    );
+    let (meta, buf) = Client::new(upstream).fetch(path, size).await?;
+    if meta.hash != *digest {
+        return Err(anyhow!("digest of `{cx}` does not match the one published").into());
+    }
+    let res = if meta.mime == MediaType::DIRECTORY {
+        let dir: TreeDirectory<TreeEntry> = decode(&buf)?;
+        tag.create_directory_node(&cx.path, meta.clone(), &dir)
+            .await
+    } else {
+        tag.create_file_node(&cx.path, meta.clone(), buf.as_slice())
+            .await
+    };
+    match res {
+        Ok(_) => {
+            events.publish(Event::TreeEntryUploaded {
+                node: cx.clone(),
+                digest: meta.hash,
+                size: meta.size,
+            });
+            Ok(())
+        }
+        Err(CreateError::Occupied) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Returns the tag targeted by a request to `path`, if any.
+fn target(path: &str) -> Option<TagContext> {
+    let (_, path) = split_api_path(path)?;
+    let (repository, tail) = path.trim_start_matches('/').split_once("/_tag/")?;
+    let name = tail.split('/').next()?.parse().ok()?;
+    Some(TagContext {
+        repository: repository.split_once('/')?.try_into().ok()?,
+        name,
+    })
+}
+
+/// Pulls the tag targeted by `GET` or `HEAD` request `req` from `upstream` unless it is
+/// stored locally, returning the response to reply with if pulling failed.
+pub(crate) async fn pull_missing<B>(
+    req: &Request<B>,
+    upstream: &Upstream,
+    owner: Owner,
+) -> Option<Response> {
+    if !matches!(*req.method(), Method::GET | Method::HEAD) {
+        return None;
+    }
+    let cx = target(req.uri().path())?;
+    let store = req.extensions().get::<Arc<Store>>()?;
+    let events = req.extensions().get::<Arc<EventBus>>()?;
+    if !matches!(store.tag(&cx).get_meta().await, Err(GetError::NotFound)) {
+        return None;
+    }
+    if let Some(placement) = req.extensions().get::<Arc<Placement>>() {
+        if let Err(e) = placement.assert_local(&cx.repository) {
+            return Some(e.into_response());
+        }
+    }
+
+    trace!(target: "app::federation", "pull `{cx}` from `{}`", upstream.url);
+    match pull(store, events, upstream, &cx, owner).await {
+        // NOTE: Tags missing upstream are reported missing by the handler.
+        Ok(()) | Err(PullError::NotFound) => None,
+        Err(e) => {
+            debug!(target: "app::federation", "failed to pull `{cx}`: {:?}", e);
+            Some(e.into_response())
+        }
+    }
+}
+
+/// Pulls tags of federated namespaces targeted by `GET` and `HEAD` requests from their
+/// upstream unless they are stored locally, before the request is handled.
+pub(crate) async fn handle<B>(req: Request<B>, next: Next<B>) -> Response {
+    let upstream = req
+        .extensions()
+        .get::<Arc<Federation>>()
+        .zip(target(req.uri().path()))
+        .and_then(|(federation, cx)| federation.get(&cx.repository.owner.name).cloned());
+    if let Some(upstream) = upstream {
+        if let Some(res) = pull_missing(&req, &upstream, Owner::Federated).await {
+            return res;
+        }
+    }
+    next.run(req).await
+}
@@ -0,0 +1,364 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! GraphQL endpoint served at `/graphql`.
+//!
+//! Queries are executed against the following schema, access to repositories is checked as
+//! for the JSON API and repositories, which may not be read, resolve to `null`:
+//!
+//! ```graphql
+//! type Query {
+//!   repository(owner: String!, name: String!): Repository
+//! }
+//!
+//! type Repository {
+//!   owner: String!
+//!   name: String!
+//!   public: Boolean!
+//!   description: String
+//!   tags: [Tag!]!
+//!   tag(name: String!): Tag
+//! }
+//!
+//! type Tag {
+//!   name: String!
+//!   digest: String!
+//!   type: String!
+//!   entry: JSON!
+//!   stats: JSON!
+//!   tree(path: String = ""): Node
+//! }
+//!
+//! type Node {
+//!   name: String
+//!   path: String!
+//!   digest: String!
+//!   type: String!
+//!   length: Int!
+//!   annotations: JSON!
+//!   entries: [Node!]
+//! }
+//! ```
+//!
+//! `JSON` values may be queried as a whole or by selecting their keys as fields.
+
+mod parse;
+
+use super::auth::may_read;
+use super::downloads::Downloads;
+use super::json::BoundedBody;
+use super::store::{tree_entry, Repository, Tag};
+use super::{GetError, OidcClaims, PullTagError, Store};
+use parse::{operation, Field};
+
+use drawbridge_type::{
+    Annotations, GraphQlRequest, GraphQlResponse, MediaType, RepositoryContext, TagEntry, TagName,
+    TreeDirectory, TreeEntry, TreePath,
+};
+
+use anyhow::{bail, Context};
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use serde_json::{Map, Value};
+use tracing::{debug, trace};
+
+/// Fails if the vulnerability policy of `repo` blocks pulls of tag `name`, whose entry and tree
+/// are only resolved if it may be pulled.
+async fn assert_pullable(repo: &Repository<'_>, name: &TagName) -> anyhow::Result<()> {
+    match repo.pull_tag(name).await {
+        Ok(_) => Ok(()),
+        Err(PullTagError::Get(e)) => Err(anyhow::Error::from(e)).context("failed to read tag"),
+        Err(PullTagError::Blocked(e)) => bail!("tag `{name}` may not be pulled: {e}"),
+    }
+}
+
+/// Returns the fields of `value` selected by `selections` or `value` itself, if none are.
+fn project(value: Value, selections: &[Field]) -> Value {
+    if selections.is_empty() {
+        return value;
+    }
+    match value {
+        Value::Array(values) => values
+            .into_iter()
+            .map(|value| project(value, selections))
+            .collect(),
+        Value::Object(mut obj) => selections
+            .iter()
+            .map(|field| {
+                let value = obj.remove(&field.name).unwrap_or_default();
+                (field.key().into(), project(value, &field.selections))
+            })
+            .collect::<Map<_, _>>()
+            .into(),
+        value => value,
+    }
+}
+
+/// Returns the annotations of the node at `path` of `tag`, which are stored in its parent
+/// directory or, for the root, in the tag entry.
+async fn annotations(tag: &Tag<'_>, path: &TreePath) -> anyhow::Result<Annotations> {
+    let Some(name) = path.name() else {
+        let meta = tag.get_meta().await?;
+        let buf = tag.read_content().await?;
+        let entry = TagEntry::decode(meta.mime.essence(), &buf)?;
+        return Ok(tree_entry(&entry)
+            .map(|entry| entry.annotations)
+            .unwrap_or_default());
+    };
+    let mut parent: TreeDirectory<TreeEntry> = tag
+        .node(&path.parent().unwrap_or_default())
+        .get_content_json()
+        .await
+        .map_err(anyhow::Error::from)
+        .context("failed to read parent directory")?;
+    Ok(parent
+        .remove(name)
+        .map(|entry| entry.annotations)
+        .unwrap_or_default())
+}
+
+struct Executor<'a> {
+    store: &'a Store,
+    downloads: &'a Downloads,
+    claims: Option<&'a OidcClaims>,
+}
+
+impl Executor<'_> {
+    async fn query(&self, fields: &[Field]) -> anyhow::Result<Value> {
+        let mut data = Map::new();
+        for field in fields {
+            let value = match field.name.as_str() {
+                "__typename" => "Query".into(),
+                "repository" => {
+                    let cx = RepositoryContext {
+                        owner: field
+                            .required_string("owner")?
+                            .parse()
+                            .context("invalid owner")?,
+                        name: field
+                            .required_string("name")?
+                            .parse()
+                            .context("invalid repository name")?,
+                    };
+                    self.repository(&cx, &field.selections).await?
+                }
+                name => bail!("field `{name}` is not defined on `Query`"),
+            };
+            _ = data.insert(field.key().into(), value);
+        }
+        Ok(data.into())
+    }
+
+    async fn repository(
+        &self,
+        cx: &RepositoryContext,
+        selections: &[Field],
+    ) -> anyhow::Result<Value> {
+        if !may_read(self.store, cx, self.claims)
+            .await
+            .map_err(anyhow::Error::from)
+            .context("failed to read repository")?
+        {
+            return Ok(Value::Null);
+        }
+        let repo = self.store.repository(cx);
+        let config = repo
+            .get_json()
+            .await
+            .map_err(anyhow::Error::from)
+            .context("failed to read repository")?;
+        let mut data = Map::new();
+        for field in selections {
+            let value = match field.name.as_str() {
+                "__typename" => "Repository".into(),
+                "owner" => cx.owner.to_string().into(),
+                "name" => cx.name.to_string().into(),
+                "public" => config.public.into(),
+                "description" => config.description.clone().into(),
+                "tags" => {
+                    let mut tags = vec![];
+                    for name in repo
+                        .tags()
+                        .await
+                        .map_err(anyhow::Error::from)
+                        .context("failed to list tags")?
+                    {
+                        tags.push(self.tag(&repo, cx, &name, &field.selections).await?);
+                    }
+                    tags.into()
+                }
+                "tag" => {
+                    let name = field
+                        .required_string("name")?
+                        .parse()
+                        .context("invalid tag name")?;
+                    self.tag(&repo, cx, &name, &field.selections).await?
+                }
+                name => bail!("field `{name}` is not defined on `Repository`"),
+            };
+            _ = data.insert(field.key().into(), value);
+        }
+        Ok(data.into())
+    }
+
+    async fn tag(
+        &self,
+        repo: &Repository<'_>,
+        cx: &RepositoryContext,
+        name: &TagName,
+        selections: &[Field],
+    ) -> anyhow::Result<Value> {
+        let tag = repo.tag(name);
+        let meta = match tag.get_meta().await {
+            Ok(meta) => meta,
+            Err(GetError::NotFound) => return Ok(Value::Null),
+            Err(e) => return Err(anyhow::Error::from(e)).context("failed to read tag"),
+        };
+        let mut data = Map::new();
+        for field in selections {
+            let value = match field.name.as_str() {
+                "__typename" => "Tag".into(),
+                "name" => name.to_string().into(),
+                "digest" => meta.hash.to_string().into(),
+                "type" => meta.mime.to_string().into(),
+                "entry" => {
+                    assert_pullable(repo, name).await?;
+                    let buf = tag
+                        .read_content()
+                        .await
+                        .map_err(anyhow::Error::from)
+                        .context("failed to read tag")?;
+                    let entry: TagEntry = TagEntry::decode(meta.mime.essence(), &buf)?;
+                    project(serde_json::to_value(entry)?, &field.selections)
+                }
+                "stats" => {
+                    let cx = drawbridge_type::TagContext {
+                        repository: cx.clone(),
+                        name: name.clone(),
+                    };
+                    let stats = self
+                        .downloads
+                        .stats(self.store, &cx)
+                        .await
+                        .map_err(anyhow::Error::from)
+                        .context("failed to read tag statistics")?;
+                    project(serde_json::to_value(stats)?, &field.selections)
+                }
+                "tree" => {
+                    assert_pullable(repo, name).await?;
+                    let path = field
+                        .string("path")?
+                        .unwrap_or("")
+                        .parse()
+                        .context("invalid tree path")?;
+                    self.node(&tag, path, &field.selections).await?
+                }
+                name => bail!("field `{name}` is not defined on `Tag`"),
+            };
+            _ = data.insert(field.key().into(), value);
+        }
+        Ok(data.into())
+    }
+
+    fn node<'b>(
+        &'b self,
+        tag: &'b Tag<'b>,
+        path: TreePath,
+        selections: &'b [Field],
+    ) -> BoxFuture<'b, anyhow::Result<Value>> {
+        async move {
+            let node = tag.node(&path);
+            let meta = match node.get_meta().await {
+                Ok(meta) => meta,
+                Err(GetError::NotFound) => return Ok(Value::Null),
+                Err(e) => return Err(anyhow::Error::from(e)).context("failed to read node"),
+            };
+            let mut data = Map::new();
+            for field in selections {
+                let value = match field.name.as_str() {
+                    "__typename" => "Node".into(),
+                    "name" => path.name().map(ToString::to_string).into(),
+                    "path" => path.to_string().into(),
+                    "digest" => meta.hash.to_string().into(),
+                    "type" => meta.mime.to_string().into(),
+                    "length" => meta.size.into(),
+                    "annotations" => project(
+                        serde_json::to_value(annotations(tag, &path).await?)?,
+                        &field.selections,
+                    ),
+                    "entries" if meta.mime.is(MediaType::DIRECTORY.essence()) => {
+                        let children: TreeDirectory<TreeEntry> = node
+                            .get_content_json()
+                            .await
+                            .map_err(anyhow::Error::from)
+                            .context("failed to read directory")?;
+                        let mut entries = vec![];
+                        for name in children.keys() {
+                            entries.push(
+                                self.node(tag, path.join(name.clone()), &field.selections)
+                                    .await?,
+                            );
+                        }
+                        entries.into()
+                    }
+                    "entries" => Value::Null,
+                    name => bail!("field `{name}` is not defined on `Node`"),
+                };
+                _ = data.insert(field.key().into(), value);
+            }
+            Ok(data.into())
+        }
+        .boxed()
+    }
+}
+
+/// Executes a GraphQL query POSTed as JSON.
+///
+/// Unauthenticated requests are served public repositories only.
+pub(crate) async fn graphql(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref downloads): Extension<Arc<Downloads>>,
+    claims: Option<OidcClaims>,
+    BoundedBody(body): BoundedBody,
+) -> impl IntoResponse {
+    trace!(target: "app::graphql::graphql", "called");
+
+    let req: GraphQlRequest = match serde_json::from_slice(&body) {
+        Ok(req) => req,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(GraphQlResponse::error(format!("invalid request: {e}"))),
+            )
+        }
+    };
+    let fields = match operation(&req) {
+        Ok(fields) => fields,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(GraphQlResponse::error(e))),
+    };
+    let executor = Executor {
+        store,
+        downloads,
+        claims: claims.as_ref(),
+    };
+    match executor.query(&fields).await {
+        Ok(data) => (
+            StatusCode::OK,
+            Json(GraphQlResponse {
+                data: Some(data),
+                errors: vec![],
+            }),
+        ),
+        Err(e) => {
+            debug!(target: "app::graphql::graphql", "failed: {:?}", e);
+            (
+                StatusCode::OK,
+                Json(GraphQlResponse::error(format!("{e:#}"))),
+            )
+        }
+    }
+}
@@ -0,0 +1,472 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Parser of the subset of GraphQL queries supported by the endpoint.
+//!
+//! Queries are parsed into [Field]s, which the endpoint resolves against its schema, so only
+//! the executable query syntax needs to be supported.
+
+use drawbridge_type::GraphQlRequest;
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, bail, ensure, Context};
+use serde_json::{Map, Number, Value};
+
+/// Maximum nesting of selection sets and values of a query
+const MAX_DEPTH: usize = 16;
+
+/// Parses the query of `req` and returns the top-level fields of the operation requested.
+///
+/// Only the query subset of GraphQL without fragments and directives is supported.
+/// Variables are substituted by their values or defaults.
+pub(crate) fn operation(req: &GraphQlRequest) -> anyhow::Result<Vec<Field>> {
+    let mut parser = Parser {
+        tokens: tokenize(&req.query)?,
+        pos: 0,
+    };
+    let mut operations = vec![];
+    while parser.peek().is_some() {
+        operations.push(parser.operation()?);
+    }
+    let operation = match req.operation_name {
+        Some(ref name) => operations
+            .into_iter()
+            .find(|op| op.name.as_deref() == Some(name))
+            .ok_or_else(|| anyhow!("operation `{name}` not found"))?,
+        None if operations.len() == 1 => operations.remove(0),
+        None if operations.is_empty() => bail!("document contains no operations"),
+        None => bail!("document contains multiple operations, but no `operationName`"),
+    };
+    let mut variables = operation.defaults;
+    variables.extend(req.variables.clone());
+    operation
+        .selections
+        .into_iter()
+        .map(|field| field.substitute(&variables))
+        .collect()
+}
+
+/// A field selected by a GraphQL query
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Field {
+    pub(crate) alias: Option<String>,
+
+    pub(crate) name: String,
+
+    pub(crate) arguments: BTreeMap<String, Value>,
+
+    /// Subfields selected, empty for scalars
+    pub(crate) selections: Vec<Field>,
+}
+
+impl Field {
+    /// Returns the key of the field in the response.
+    pub(crate) fn key(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Returns the string argument `name`, if it is passed.
+    pub(crate) fn string(&self, name: &str) -> anyhow::Result<Option<&str>> {
+        match self.arguments.get(name) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s)),
+            Some(_) => bail!("argument `{name}` of `{}` must be a string", self.name),
+        }
+    }
+
+    /// Returns the string argument `name` or fails, if it is not passed.
+    pub(crate) fn required_string(&self, name: &str) -> anyhow::Result<&str> {
+        self.string(name)?
+            .ok_or_else(|| anyhow!("argument `{name}` of `{}` is required", self.name))
+    }
+
+    fn substitute(self, variables: &Map<String, Value>) -> anyhow::Result<Self> {
+        Ok(Self {
+            arguments: self
+                .arguments
+                .into_iter()
+                .map(|(k, v)| Ok((k, substitute(v, variables)?)))
+                .collect::<anyhow::Result<_>>()?,
+            selections: self
+                .selections
+                .into_iter()
+                .map(|field| field.substitute(variables))
+                .collect::<anyhow::Result<_>>()?,
+            ..self
+        })
+    }
+}
+
+/// Variables are represented as single-key objects with a key, which is not a valid name, until
+/// substituted.
+const VARIABLE: &str = "$";
+
+fn substitute(value: Value, variables: &Map<String, Value>) -> anyhow::Result<Value> {
+    match value {
+        Value::Object(obj) if obj.len() == 1 && obj.contains_key(VARIABLE) => {
+            let name = obj[VARIABLE].as_str().unwrap_or_default();
+            variables
+                .get(name)
+                .cloned()
+                .ok_or_else(|| anyhow!("variable `${name}` is not defined"))
+        }
+        Value::Object(obj) => Ok(Value::Object(
+            obj.into_iter()
+                .map(|(k, v)| Ok((k, substitute(v, variables)?)))
+                .collect::<anyhow::Result<_>>()?,
+        )),
+        Value::Array(values) => Ok(Value::Array(
+            values
+                .into_iter()
+                .map(|v| substitute(v, variables))
+                .collect::<anyhow::Result<_>>()?,
+        )),
+        value => Ok(value),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Punct(char),
+    Spread,
+    Name(String),
+    Number(Number),
+    String(String),
+}
+
+fn tokenize(s: &str) -> anyhow::Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            // NOTE: Commas are insignificant in GraphQL.
+            c if c.is_whitespace() || c == ',' || c == '\u{feff}' => {}
+            '#' => while chars.next_if(|&(_, c)| c != '\n' && c != '\r').is_some() {},
+            '{' | '}' | '(' | ')' | '[' | ']' | ':' | '$' | '!' | '=' | '@' => {
+                tokens.push(Token::Punct(c))
+            }
+            '.' => {
+                ensure!(s[i..].starts_with("..."), "unexpected `.` at offset {i}");
+                _ = chars.next();
+                _ = chars.next();
+                tokens.push(Token::Spread)
+            }
+            '"' => {
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        None | Some((_, '\n' | '\r')) => bail!("unterminated string at offset {i}"),
+                        Some((_, '"')) => break,
+                        Some((_, '\\')) => match chars.next().map(|(_, c)| c) {
+                            Some(c @ ('"' | '\\' | '/')) => value.push(c),
+                            Some('b') => value.push('\u{8}'),
+                            Some('f') => value.push('\u{c}'),
+                            Some('n') => value.push('\n'),
+                            Some('r') => value.push('\r'),
+                            Some('t') => value.push('\t'),
+                            Some('u') => {
+                                let hex: String = (0..4)
+                                    .filter_map(|_| chars.next())
+                                    .map(|(_, c)| c)
+                                    .collect();
+                                let c = u32::from_str_radix(&hex, 16)
+                                    .ok()
+                                    .and_then(char::from_u32)
+                                    .ok_or_else(|| {
+                                        anyhow!("invalid escape `\\u{hex}` in string at offset {i}")
+                                    })?;
+                                value.push(c)
+                            }
+                            _ => bail!("invalid escape in string at offset {i}"),
+                        },
+                        Some((_, c)) => value.push(c),
+                    }
+                }
+                tokens.push(Token::String(value))
+            }
+            c if c == '-' || c.is_ascii_digit() => {
+                let mut end = i + c.len_utf8();
+                while let Some((j, c)) = chars
+                    .next_if(|&(_, c)| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+'))
+                {
+                    end = j + c.len_utf8();
+                }
+                let number = &s[i..end];
+                let number = serde_json::from_str::<Number>(number)
+                    .with_context(|| format!("invalid number `{number}` at offset {i}"))?;
+                tokens.push(Token::Number(number))
+            }
+            c if c == '_' || c.is_ascii_alphabetic() => {
+                let mut end = i + c.len_utf8();
+                while let Some((j, c)) =
+                    chars.next_if(|&(_, c)| c == '_' || c.is_ascii_alphanumeric())
+                {
+                    end = j + c.len_utf8();
+                }
+                tokens.push(Token::Name(s[i..end].into()))
+            }
+            c => bail!("unexpected `{c}` at offset {i}"),
+        }
+    }
+    Ok(tokens)
+}
+
+struct Operation {
+    name: Option<String>,
+    defaults: Map<String, Value>,
+    selections: Vec<Field>,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> anyhow::Result<Token> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| anyhow!("unexpected end of query"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> anyhow::Result<()> {
+        match self.next()? {
+            Token::Punct(p) if p == c => Ok(()),
+            token => bail!("expected `{c}`, found {token:?}"),
+        }
+    }
+
+    fn name(&mut self) -> anyhow::Result<String> {
+        match self.next()? {
+            Token::Name(name) => Ok(name),
+            token => bail!("expected a name, found {token:?}"),
+        }
+    }
+
+    fn operation(&mut self) -> anyhow::Result<Operation> {
+        let mut op = Operation {
+            name: None,
+            defaults: Map::new(),
+            selections: vec![],
+        };
+        match self.peek() {
+            Some(Token::Punct('{')) => {}
+            Some(Token::Name(kind)) if kind == "query" => {
+                self.pos += 1;
+                if let Some(Token::Name(..)) = self.peek() {
+                    op.name = Some(self.name()?);
+                }
+                if self.eat('(') {
+                    while !self.eat(')') {
+                        self.expect('$')?;
+                        let name = self.name()?;
+                        self.expect(':')?;
+                        self.skip_type(0)?;
+                        if self.eat('=') {
+                            _ = op.defaults.insert(name, self.value(0)?);
+                        }
+                    }
+                }
+            }
+            Some(Token::Name(kind)) if kind == "mutation" || kind == "subscription" => {
+                bail!("only queries are supported")
+            }
+            Some(Token::Name(kind)) if kind == "fragment" => bail!("fragments are not supported"),
+            token => bail!("expected an operation, found {token:?}"),
+        }
+        op.selections = self.selections(0)?;
+        Ok(op)
+    }
+
+    fn skip_type(&mut self, depth: usize) -> anyhow::Result<()> {
+        ensure!(depth < MAX_DEPTH, "type nested too deeply");
+        if self.eat('[') {
+            self.skip_type(depth + 1)?;
+            self.expect(']')?;
+        } else {
+            _ = self.name()?;
+        }
+        _ = self.eat('!');
+        Ok(())
+    }
+
+    fn selections(&mut self, depth: usize) -> anyhow::Result<Vec<Field>> {
+        ensure!(depth < MAX_DEPTH, "selection nested too deeply");
+        self.expect('{')?;
+        let mut fields = vec![];
+        while !self.eat('}') {
+            match self.peek() {
+                Some(Token::Spread) => bail!("fragments are not supported"),
+                Some(Token::Punct('@')) => bail!("directives are not supported"),
+                _ => {}
+            }
+            let mut name = self.name()?;
+            let mut alias = None;
+            if self.eat(':') {
+                alias = Some(name);
+                name = self.name()?;
+            }
+            let mut arguments = BTreeMap::new();
+            if self.eat('(') {
+                while !self.eat(')') {
+                    let name = self.name()?;
+                    self.expect(':')?;
+                    _ = arguments.insert(name, self.value(depth)?);
+                }
+            }
+            ensure!(
+                self.peek() != Some(&Token::Punct('@')),
+                "directives are not supported"
+            );
+            let selections = if self.peek() == Some(&Token::Punct('{')) {
+                self.selections(depth + 1)?
+            } else {
+                vec![]
+            };
+            fields.push(Field {
+                alias,
+                name,
+                arguments,
+                selections,
+            })
+        }
+        Ok(fields)
+    }
+
+    fn value(&mut self, depth: usize) -> anyhow::Result<Value> {
+        ensure!(depth < MAX_DEPTH, "value nested too deeply");
+        match self.next()? {
+            Token::Punct('$') => Ok(Value::Object(Map::from_iter([(
+                VARIABLE.into(),
+                self.name()?.into(),
+            )]))),
+            Token::Number(n) => Ok(n.into()),
+            Token::String(s) => Ok(s.into()),
+            Token::Name(name) => Ok(match name.as_str() {
+                "true" => true.into(),
+                "false" => false.into(),
+                "null" => Value::Null,
+                // NOTE: Enum values are represented as strings.
+                _ => name.into(),
+            }),
+            Token::Punct('[') => {
+                let mut values = vec![];
+                while !self.eat(']') {
+                    values.push(self.value(depth + 1)?);
+                }
+                Ok(values.into())
+            }
+            Token::Punct('{') => {
+                let mut obj = Map::new();
+                while !self.eat('}') {
+                    let name = self.name()?;
+                    self.expect(':')?;
+                    _ = obj.insert(name, self.value(depth + 1)?);
+                }
+                Ok(obj.into())
+            }
+            token => bail!("expected a value, found {token:?}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn request(query: &str, variables: Value) -> GraphQlRequest {
+        serde_json::from_value(json!({ "query": query, "variables": variables })).unwrap()
+    }
+
+    #[test]
+    fn operations() {
+        let fields = operation(&request(
+            r#"
+            # Tags of a repository
+            query Tags($name: String! = "nginx", $limit: Int) {
+                repository(owner: "acme", name: $name) {
+                    latest: tag(name: "1.0.0") { name, tree(path: "a\nb") { length } }
+                    tags { name }
+                }
+                __typename
+            }
+            "#,
+            json!({ "limit": 2 }),
+        ))
+        .unwrap();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[1].key(), "__typename");
+
+        let repo = &fields[0];
+        assert_eq!(repo.required_string("owner").unwrap(), "acme");
+        assert_eq!(repo.required_string("name").unwrap(), "nginx");
+        assert!(repo.required_string("tag").is_err());
+        let tag = &repo.selections[0];
+        assert_eq!(tag.key(), "latest");
+        assert_eq!(tag.name, "tag");
+        assert_eq!(tag.selections[1].string("path").unwrap(), Some("a\nb"));
+        assert_eq!(repo.selections[1].key(), "tags");
+
+        let fields = request(
+            "query A { a } query B { b(n: -1.5e3, l: [1 2], o: {k: ENUM}) }",
+            json!({}),
+        );
+        assert!(operation(&fields).is_err());
+        let fields = operation(&GraphQlRequest {
+            operation_name: Some("B".into()),
+            ..fields
+        })
+        .unwrap();
+        assert_eq!(
+            fields[0].arguments,
+            BTreeMap::from([
+                ("l".into(), json!([1, 2])),
+                ("n".into(), json!(-1500.0)),
+                ("o".into(), json!({ "k": "ENUM" })),
+            ])
+        );
+    }
+
+    #[test]
+    fn unsupported() {
+        for query in [
+            "",
+            "{ a(n: $undefined) }",
+            "mutation { a }",
+            "{ ...F } fragment F on Query { a }",
+            "{ a @skip(if: true) }",
+            "{ a(s: \"unterminated) }",
+            "{ a",
+            &format!(
+                "{}{}",
+                "{ a ".repeat(MAX_DEPTH + 1),
+                "}".repeat(MAX_DEPTH + 1)
+            ),
+        ] {
+            assert!(
+                operation(&request(query, json!({}))).is_err(),
+                "`{query}` should fail"
+            );
+        }
+    }
+}
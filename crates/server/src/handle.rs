@@ -1,10 +1,15 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::{repos, tags, trees, users};
+use super::{
+    admin, algorithms, attestations, bagit, changes, events, ipfs, keys, manifest, placement,
+    proxy, quota, repos, retention, reviews, sboms, search, signatures, snapshots, tags, trash,
+    trees, tuf, uploads, usage, users, vulnerabilities, webhooks,
+};
 
-use drawbridge_type::{RepositoryName, TagName, TreePath, UserName};
+use drawbridge_type::{NamespaceRules, RepositoryName, TagName, TreePath, UserName};
 
+use async_std::sync::Arc;
 use axum::body::Body;
 use axum::handler::Handler;
 use axum::http::{Method, Request, StatusCode};
@@ -12,6 +17,7 @@ use axum::response::IntoResponse;
 use once_cell::sync::Lazy;
 use tower::Service;
 use tracing::trace;
+use uuid::Uuid;
 
 /// Server API version
 pub(crate) static API_VERSION: Lazy<semver::Version> = Lazy::new(|| {
@@ -23,6 +29,16 @@ pub(crate) static API_VERSION: Lazy<semver::Version> = Lazy::new(|| {
     })
 });
 
+/// Splits request `path` into the requested API version and the path following it, as
+/// routed by [handle].
+pub(crate) fn split_api_path(path: &str) -> Option<(&str, &str)> {
+    path.trim_start_matches('/')
+        .strip_prefix("api")?
+        .trim_start_matches('/')
+        .strip_prefix('v')?
+        .split_once('/')
+}
+
 /// Parses the URI of `req` and routes it to respective component.
 pub(crate) async fn handle(mut req: Request<Body>) -> impl IntoResponse {
     #[inline]
@@ -32,14 +48,7 @@ pub(crate) async fn handle(mut req: Request<Body>) -> impl IntoResponse {
 
     trace!(target: "app::handle", "begin HTTP request handling {:?}", req);
     let path = req.uri().path().trim_start_matches('/');
-    let (ver, path) = path
-        .strip_prefix("api")
-        .ok_or_else(|| not_found(path))?
-        .trim_start_matches('/')
-        .strip_prefix('v')
-        .ok_or_else(|| not_found(path))?
-        .split_once('/')
-        .ok_or_else(|| not_found(path))?;
+    let (ver, path) = split_api_path(path).ok_or_else(|| not_found(path))?;
     let ver = ver.parse::<semver::Version>().map_err(|e| {
         (
             StatusCode::BAD_REQUEST,
@@ -55,6 +64,176 @@ pub(crate) async fn handle(mut req: Request<Body>) -> impl IntoResponse {
             format!("Unsupported API version `{ver}`"),
         ));
     }
+    if path.trim_start_matches('/') == "_changes" {
+        return match *req.method() {
+            Method::GET => Ok(changes::changes
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for change feed endpoint".into(),
+            )),
+        };
+    }
+    if path.trim_start_matches('/') == "_search" {
+        return match *req.method() {
+            Method::GET => Ok(search::search
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for search endpoint".into(),
+            )),
+        };
+    }
+    if path.trim_start_matches('/') == "_manifest" {
+        return match *req.method() {
+            Method::POST => Ok(manifest::manifest
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for download manifest endpoint".into(),
+            )),
+        };
+    }
+    if path.trim_start_matches('/') == "_quota" {
+        return match *req.method() {
+            Method::GET => Ok(quota::get.into_service().call(req).await.into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for quota endpoint".into(),
+            )),
+        };
+    }
+    if path.trim_start_matches('/') == "_admin/check" {
+        return match *req.method() {
+            Method::GET => Ok(admin::check.into_service().call(req).await.into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for consistency check endpoint".into(),
+            )),
+        };
+    }
+    if path.trim_start_matches('/') == "_admin/events" {
+        return match *req.method() {
+            Method::GET => Ok(events::feed.into_service().call(req).await.into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for change feed endpoint".into(),
+            )),
+        };
+    }
+    if path.trim_start_matches('/') == "_admin/hosts" {
+        return match *req.method() {
+            Method::GET => Ok(admin::hosts.into_service().call(req).await.into_response()),
+            Method::PUT => Ok(admin::put_hosts
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for vanity hosts endpoint".into(),
+            )),
+        };
+    }
+    if path.trim_start_matches('/') == "_admin/migration" {
+        return match *req.method() {
+            Method::GET => Ok(admin::migration
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for migration status endpoint".into(),
+            )),
+        };
+    }
+    if path.trim_start_matches('/') == "_admin/overview" {
+        return match *req.method() {
+            Method::GET => Ok(admin::overview
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for overview endpoint".into(),
+            )),
+        };
+    }
+    if path.trim_start_matches('/') == "_admin/replica" {
+        return match *req.method() {
+            Method::GET => Ok(admin::replica
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for replica status endpoint".into(),
+            )),
+        };
+    }
+    if path.trim_start_matches('/') == "_admin/replication" {
+        return match *req.method() {
+            Method::GET => Ok(admin::replication
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for replication status endpoint".into(),
+            )),
+        };
+    }
+    if let Some(name) = path.trim_start_matches('/').strip_prefix("_tuf/") {
+        let name = tuf::MetadataName(name.into());
+        trace!(target: "app::handle", "parsed TUF metadata name: `{}`", name.0);
+        assert_eq!(
+            req.extensions_mut().insert(name),
+            None,
+            "duplicate TUF metadata name"
+        );
+        return match *req.method() {
+            Method::GET => Ok(tuf::get.into_service().call(req).await.into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for TUF metadata endpoint".into(),
+            )),
+        };
+    }
+    if let Some(path) = path.trim_start_matches('/').strip_prefix("_proxy/") {
+        let cx = path.parse::<proxy::Context>().map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Failed to parse proxied image reference: {e}"),
+            )
+        })?;
+        trace!(target: "app::handle", "parsed proxied image context: `{cx}`");
+        assert_eq!(
+            req.extensions_mut().insert(cx),
+            None,
+            "duplicate proxied image context"
+        );
+        return match *req.method() {
+            Method::GET => Ok(proxy::get.into_service().call(req).await.into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for proxy endpoint".into(),
+            )),
+        };
+    }
+
     let (head, tail) = path
         .trim_start_matches('/')
         .split_once("/_")
@@ -64,6 +243,11 @@ pub(crate) async fn handle(mut req: Request<Body>) -> impl IntoResponse {
         return Err(not_found(path));
     }
 
+    let rules = req
+        .extensions()
+        .get::<Arc<NamespaceRules>>()
+        .cloned()
+        .unwrap_or_default();
     let extensions = req.extensions_mut();
 
     let (user, head) = head.split_once('/').unwrap_or((&head, ""));
@@ -73,8 +257,93 @@ pub(crate) async fn handle(mut req: Request<Body>) -> impl IntoResponse {
             format!("Failed to parse user name: {e}"),
         )
     })?;
+    rules
+        .validate(&user)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid user name: {e}")))?;
     trace!(target: "app::handle", "parsed user name: `{user}`");
     assert_eq!(extensions.insert(user), None, "duplicate user name");
+    if head.is_empty() && tail == "_events" {
+        return match *req.method() {
+            Method::GET => Ok(events::stream
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for user event stream endpoint".into(),
+            )),
+        };
+    }
+    if head.is_empty() && tail == "_usage" {
+        return match *req.method() {
+            Method::GET => Ok(usage::user.into_service().call(req).await.into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for user usage endpoint".into(),
+            )),
+        };
+    }
+    if head.is_empty() && tail == "_retention" {
+        return match *req.method() {
+            Method::GET => Ok(retention::get
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            Method::PUT => Ok(retention::put
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for user retention policy endpoint".into(),
+            )),
+        };
+    }
+    if head.is_empty() && tail == "_retention/report" {
+        return match *req.method() {
+            Method::GET => Ok(retention::report
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for user retention report endpoint".into(),
+            )),
+        };
+    }
+    if head.is_empty() && tail == "_trash" {
+        return match *req.method() {
+            Method::GET => Ok(trash::get.into_service().call(req).await.into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for user trash endpoint".into(),
+            )),
+        };
+    }
+    if let Some(id) = tail.strip_prefix("_trash/").filter(|_| head.is_empty()) {
+        let id = id.parse::<Uuid>().map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Failed to parse trashed entity ID: {e}"),
+            )
+        })?;
+        assert_eq!(
+            extensions.insert(trash::TrashId(id.to_string())),
+            None,
+            "duplicate trashed entity ID"
+        );
+        return match *req.method() {
+            Method::POST => Ok(trash::post.into_service().call(req).await.into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for user trashed entity endpoint".into(),
+            )),
+        };
+    }
     if head.is_empty() {
         return match *req.method() {
             Method::HEAD => Ok(users::head.into_service().call(req).await.into_response()),
@@ -93,6 +362,12 @@ pub(crate) async fn handle(mut req: Request<Body>) -> impl IntoResponse {
             format!("Failed to parse repository name: {e}"),
         )
     })?;
+    rules.validate(&repo).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid repository name: {e}"),
+        )
+    })?;
     trace!(target: "app::handle", "parsed repository name: `{repo}`");
     assert_eq!(extensions.insert(repo), None, "duplicate repository name");
 
@@ -102,6 +377,7 @@ pub(crate) async fn handle(mut req: Request<Body>) -> impl IntoResponse {
             Method::HEAD => Ok(repos::head.into_service().call(req).await.into_response()),
             Method::GET => Ok(repos::get.into_service().call(req).await.into_response()),
             Method::PUT => Ok(repos::put.into_service().call(req).await.into_response()),
+            Method::DELETE => Ok(repos::delete.into_service().call(req).await.into_response()),
             _ => Err((
                 StatusCode::METHOD_NOT_ALLOWED,
                 "Method not allowed for repository endpoint".into(),
@@ -114,7 +390,173 @@ pub(crate) async fn handle(mut req: Request<Body>) -> impl IntoResponse {
                 "Method not allowed for repository tag query endpoint".into(),
             )),
         },
-        (Some("_tag"), Some(tag), prop @ (None | Some("tree"))) => {
+        (Some("_tag.atom"), None, None) => match *req.method() {
+            Method::GET => Ok(tags::feed.into_service().call(req).await.into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for repository tag feed endpoint".into(),
+            )),
+        },
+        (Some("_algorithms"), None, None) => match *req.method() {
+            Method::GET => Ok(algorithms::get
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for repository algorithm rules endpoint".into(),
+            )),
+        },
+        (Some("_keys"), None, None) => match *req.method() {
+            Method::GET => Ok(keys::get.into_service().call(req).await.into_response()),
+            Method::PUT => Ok(keys::put.into_service().call(req).await.into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for repository signing keys endpoint".into(),
+            )),
+        },
+        (Some("_review"), None, None) => match *req.method() {
+            Method::GET => Ok(reviews::get.into_service().call(req).await.into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for repository pending changes endpoint".into(),
+            )),
+        },
+        (Some("_review"), Some(id), None) => {
+            let id = id.parse::<Uuid>().map_err(|e| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to parse pending change ID: {e}"),
+                )
+            })?;
+            assert_eq!(
+                extensions.insert(reviews::ReviewId(id.to_string())),
+                None,
+                "duplicate pending change ID"
+            );
+            match *req.method() {
+                Method::PUT => Ok(reviews::put.into_service().call(req).await.into_response()),
+                _ => Err((
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "Method not allowed for repository pending change endpoint".into(),
+                )),
+            }
+        }
+        (Some("_attestation"), None, None) => match *req.method() {
+            Method::GET => Ok(attestations::query
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for repository attestation query endpoint".into(),
+            )),
+        },
+        (Some("_signature"), None, None) => match *req.method() {
+            Method::GET => Ok(signatures::audit
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for repository signature audit endpoint".into(),
+            )),
+        },
+        (Some("_usage"), None, None) => match *req.method() {
+            Method::GET => Ok(usage::repository
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for repository usage endpoint".into(),
+            )),
+        },
+        (Some("_placement"), None, None) => match *req.method() {
+            Method::GET => Ok(placement::repository
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for repository placement endpoint".into(),
+            )),
+        },
+        (Some("_export"), None, None) => match *req.method() {
+            Method::GET => Ok(snapshots::export
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for repository export endpoint".into(),
+            )),
+        },
+        (Some("_import"), None, None) => match *req.method() {
+            Method::PUT => Ok(snapshots::import
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for repository import endpoint".into(),
+            )),
+        },
+        (Some("_uploads"), None, None) => match *req.method() {
+            Method::GET => Ok(uploads::get.into_service().call(req).await.into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for repository upload sessions endpoint".into(),
+            )),
+        },
+        (Some("_uploads"), Some(tag), None) => {
+            let tag = tag.parse::<TagName>().map_err(|e| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Failed to parse tag name: {e}"),
+                )
+            })?;
+            trace!(target: "app::handle", "parsed tag name: `{tag}`");
+            assert_eq!(extensions.insert(tag), None, "duplicate tag name");
+            match *req.method() {
+                Method::DELETE => Ok(uploads::delete
+                    .into_service()
+                    .call(req)
+                    .await
+                    .into_response()),
+                _ => Err((
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "Method not allowed for repository upload session endpoint".into(),
+                )),
+            }
+        }
+        (Some("_webhook"), Some("deliveries"), None) => match *req.method() {
+            Method::GET => Ok(webhooks::deliveries
+                .into_service()
+                .call(req)
+                .await
+                .into_response()),
+            _ => Err((
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method not allowed for repository webhook delivery endpoint".into(),
+            )),
+        },
+        (
+            Some("_tag"),
+            Some(tag),
+            prop @ (None
+            | Some(
+                "attestations" | "bagit" | "ipfs" | "sbom" | "signatures" | "stats" | "tree"
+                | "vulnerabilities",
+            )),
+        ) => {
             let tag = tag.parse::<TagName>().map_err(|e| {
                 (
                     StatusCode::BAD_REQUEST,
@@ -124,34 +566,133 @@ pub(crate) async fn handle(mut req: Request<Body>) -> impl IntoResponse {
             trace!(target: "app::handle", "parsed tag name: `{tag}`");
             assert_eq!(extensions.insert(tag), None, "duplicate tag name");
 
-            if prop.is_none() {
-                return match *req.method() {
+            match (prop, tail.next()) {
+                (None, _) => match *req.method() {
                     Method::HEAD => Ok(tags::head.into_service().call(req).await.into_response()),
                     Method::GET => Ok(tags::get.into_service().call(req).await.into_response()),
                     Method::PUT => Ok(tags::put.into_service().call(req).await.into_response()),
+                    Method::DELETE => {
+                        Ok(tags::delete.into_service().call(req).await.into_response())
+                    }
                     _ => Err((
                         StatusCode::METHOD_NOT_ALLOWED,
                         "Method not allowed for tag endpoint".into(),
                     )),
-                };
-            }
-
-            let path = tail.next().unwrap_or("").parse::<TreePath>().map_err(|e| {
-                (
-                    StatusCode::BAD_REQUEST,
-                    format!("Failed to parse tree path: {e}"),
-                )
-            })?;
-            trace!(target: "app::handle", "parsed tree path: `{path}`");
-            assert_eq!(extensions.insert(path), None, "duplicate tree path");
-            match *req.method() {
-                Method::HEAD => Ok(trees::head.into_service().call(req).await.into_response()),
-                Method::GET => Ok(trees::get.into_service().call(req).await.into_response()),
-                Method::PUT => Ok(trees::put.into_service().call(req).await.into_response()),
-                _ => Err((
-                    StatusCode::METHOD_NOT_ALLOWED,
-                    "Method not allowed for tag tree endpoint".into(),
-                )),
+                },
+                (Some("attestations"), None) => match *req.method() {
+                    Method::GET => Ok(attestations::get
+                        .into_service()
+                        .call(req)
+                        .await
+                        .into_response()),
+                    Method::PUT => Ok(attestations::put
+                        .into_service()
+                        .call(req)
+                        .await
+                        .into_response()),
+                    _ => Err((
+                        StatusCode::METHOD_NOT_ALLOWED,
+                        "Method not allowed for tag attestations endpoint".into(),
+                    )),
+                },
+                (Some("bagit"), None) => match *req.method() {
+                    Method::GET => Ok(bagit::get.into_service().call(req).await.into_response()),
+                    _ => Err((
+                        StatusCode::METHOD_NOT_ALLOWED,
+                        "Method not allowed for tag BagIt export endpoint".into(),
+                    )),
+                },
+                (Some("ipfs"), None) => match *req.method() {
+                    Method::GET => Ok(ipfs::get.into_service().call(req).await.into_response()),
+                    _ => Err((
+                        StatusCode::METHOD_NOT_ALLOWED,
+                        "Method not allowed for tag IPFS export endpoint".into(),
+                    )),
+                },
+                (Some("sbom"), None) => match *req.method() {
+                    Method::HEAD => Ok(sboms::head.into_service().call(req).await.into_response()),
+                    Method::GET => Ok(sboms::get.into_service().call(req).await.into_response()),
+                    Method::PUT => Ok(sboms::put.into_service().call(req).await.into_response()),
+                    _ => Err((
+                        StatusCode::METHOD_NOT_ALLOWED,
+                        "Method not allowed for tag SBOM endpoint".into(),
+                    )),
+                },
+                (Some("vulnerabilities"), None) => match *req.method() {
+                    Method::GET => Ok(vulnerabilities::get
+                        .into_service()
+                        .call(req)
+                        .await
+                        .into_response()),
+                    Method::PUT => Ok(vulnerabilities::put
+                        .into_service()
+                        .call(req)
+                        .await
+                        .into_response()),
+                    _ => Err((
+                        StatusCode::METHOD_NOT_ALLOWED,
+                        "Method not allowed for tag vulnerability report endpoint".into(),
+                    )),
+                },
+                (Some("stats"), None) => match *req.method() {
+                    Method::GET => Ok(tags::stats.into_service().call(req).await.into_response()),
+                    _ => Err((
+                        StatusCode::METHOD_NOT_ALLOWED,
+                        "Method not allowed for tag statistics endpoint".into(),
+                    )),
+                },
+                (Some("signatures"), None) => match *req.method() {
+                    Method::GET => Ok(signatures::get
+                        .into_service()
+                        .call(req)
+                        .await
+                        .into_response()),
+                    Method::PUT => Ok(signatures::put
+                        .into_service()
+                        .call(req)
+                        .await
+                        .into_response()),
+                    _ => Err((
+                        StatusCode::METHOD_NOT_ALLOWED,
+                        "Method not allowed for tag signatures endpoint".into(),
+                    )),
+                },
+                (Some("tree"), path) => {
+                    let path = path.unwrap_or("").parse::<TreePath>().map_err(|e| {
+                        (
+                            StatusCode::BAD_REQUEST,
+                            format!("Failed to parse tree path: {e}"),
+                        )
+                    })?;
+                    trace!(target: "app::handle", "parsed tree path: `{path}`");
+                    assert_eq!(extensions.insert(path), None, "duplicate tree path");
+                    match *req.method() {
+                        Method::HEAD => {
+                            Ok(trees::head.into_service().call(req).await.into_response())
+                        }
+                        Method::GET => {
+                            Ok(trees::get.into_service().call(req).await.into_response())
+                        }
+                        Method::PUT => {
+                            Ok(trees::put.into_service().call(req).await.into_response())
+                        }
+                        Method::OPTIONS => Ok(trees::options
+                            .into_service()
+                            .call(req)
+                            .await
+                            .into_response()),
+                        ref m if m == "PROPFIND" => Ok(trees::propfind
+                            .into_service()
+                            .call(req)
+                            .await
+                            .into_response()),
+                        _ => Err((
+                            StatusCode::METHOD_NOT_ALLOWED,
+                            "Method not allowed for tag tree endpoint".into(),
+                        )),
+                    }
+                }
+                _ => Err((StatusCode::NOT_FOUND, "Route not found on tag".into())),
             }
         }
         _ => Err((
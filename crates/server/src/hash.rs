@@ -0,0 +1,11 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use drawbridge_type::digest::hex;
+
+use ring::digest::{digest, SHA256};
+
+/// Returns the hex-encoded SHA-256 digest of `buf`.
+pub(crate) fn sha256(buf: &[u8]) -> String {
+    hex(digest(&SHA256, buf))
+}
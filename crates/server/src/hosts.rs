@@ -0,0 +1,225 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Vanity hosts of owners, e.g. `artifacts.acme.com` serving namespace `acme`.
+//!
+//! Hosts are assigned to owners by [admin::put_hosts](super::admin::put_hosts) under
+//! `_admin/hosts` and persisted in the store. API paths of requests addressed to a vanity
+//! host are relative to the namespace of its owner, i.e. `/api/v0.1.0/repo/_tag` on
+//! `artifacts.acme.com` is served as `/api/v0.1.0/acme/repo/_tag`, and the namespace is
+//! stripped from `Link` and `Location` headers of responses in turn. Endpoints not scoped
+//! to a namespace, e.g. `_search`, are served as usual.
+//!
+//! Instances of a cluster sharing the store pick up hosts assigned by other instances
+//! within [RELOAD_INTERVAL].
+
+use super::links;
+use super::tenant::request_host;
+use super::{Clock, Store};
+
+use drawbridge_type::UserName;
+
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, ensure, Context};
+use async_std::sync::Arc;
+use axum::http::header::LOCATION;
+use axum::http::uri::PathAndQuery;
+use axum::http::{HeaderValue, Request, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use tracing::{trace, warn};
+
+/// Interval, after which vanity hosts are reloaded from the store.
+pub const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Owners vanity hosts are routed to keyed by the hosts.
+#[derive(Debug)]
+pub struct VanityHosts {
+    store: Arc<Store>,
+    clock: Arc<dyn Clock>,
+    /// Hosts along with the time they were loaded at
+    hosts: RwLock<(SystemTime, BTreeMap<String, UserName>)>,
+}
+
+/// Returns an error, unless `host` is a lowercase DNS name without a port.
+fn validate_host(host: &str) -> anyhow::Result<()> {
+    ensure!(
+        !host.is_empty() && host.len() <= 253,
+        "invalid length of host `{host}`"
+    );
+    for label in host.split('.') {
+        if label.is_empty() || label.len() > 63 || label.starts_with('-') || label.ends_with('-') {
+            bail!("invalid label `{label}` of host `{host}`");
+        }
+        ensure!(
+            label
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'),
+            "invalid label `{label}` of host `{host}`"
+        );
+    }
+    Ok(())
+}
+
+impl VanityHosts {
+    /// Loads vanity hosts persisted in `store`.
+    pub async fn load(store: Arc<Store>, clock: Arc<dyn Clock>) -> anyhow::Result<Self> {
+        let hosts = store.read_hosts().await?;
+        let now = clock.now();
+        Ok(Self {
+            store,
+            clock,
+            hosts: RwLock::new((now, hosts)),
+        })
+    }
+
+    /// Returns owners vanity hosts are routed to keyed by the hosts.
+    pub fn hosts(&self) -> BTreeMap<String, UserName> {
+        self.hosts
+            .read()
+            .map(|hosts| hosts.1.clone())
+            .unwrap_or_default()
+    }
+
+    /// Returns the owner vanity host `host` is routed to, if any.
+    pub fn owner(&self, host: &str) -> Option<UserName> {
+        self.hosts.read().ok()?.1.get(host).cloned()
+    }
+
+    /// Returns an error, unless all `hosts` are lowercase DNS names without a port.
+    pub fn validate(hosts: &BTreeMap<String, UserName>) -> anyhow::Result<()> {
+        hosts.keys().try_for_each(|host| validate_host(host))
+    }
+
+    /// Validates `hosts` and replaces all vanity hosts by them.
+    pub async fn replace(&self, hosts: BTreeMap<String, UserName>) -> anyhow::Result<()> {
+        Self::validate(&hosts)?;
+        self.store
+            .write_hosts(&hosts)
+            .await
+            .context("failed to persist vanity hosts")?;
+        if let Ok(mut current) = self.hosts.write() {
+            *current = (self.clock.now(), hosts);
+        }
+        Ok(())
+    }
+
+    /// Reloads vanity hosts from the store, if they were loaded more than [RELOAD_INTERVAL]
+    /// ago.
+    async fn refresh(&self) {
+        let now = self.clock.now();
+        let fresh = self
+            .hosts
+            .read()
+            .map(|hosts| now.duration_since(hosts.0).unwrap_or_default() < RELOAD_INTERVAL)
+            .unwrap_or(false);
+        if fresh {
+            return;
+        }
+        match self.store.read_hosts().await {
+            Ok(hosts) => {
+                if let Ok(mut current) = self.hosts.write() {
+                    *current = (now, hosts);
+                }
+            }
+            Err(e) => warn!(target: "app::hosts", "failed to reload vanity hosts: {:?}", e),
+        }
+    }
+}
+
+/// Returns API path `path` relative to the namespace of `owner` as an absolute path, unless
+/// it is not scoped to a namespace.
+fn to_namespace(path: &str, owner: &UserName) -> Option<String> {
+    let rest = path.strip_prefix("/api/v")?;
+    let (ver, rest) = rest.split_once('/')?;
+    if rest.starts_with('_') {
+        return None;
+    }
+    Some(match rest.trim_end_matches('/') {
+        "" => format!("/api/v{ver}/{owner}"),
+        _ => format!("/api/v{ver}/{owner}/{rest}"),
+    })
+}
+
+/// Returns absolute API path `path` in the namespace of `owner` relative to it, if it is
+/// within it.
+fn from_namespace(path: &str, owner: &UserName) -> Option<String> {
+    let rest = path.strip_prefix("/api/v")?;
+    let (ver, rest) = rest.split_once('/')?;
+    match rest.strip_prefix(owner.as_str())? {
+        "" => Some(format!("/api/v{ver}/")),
+        rest if rest.starts_with('/') || rest.starts_with('?') => {
+            Some(format!("/api/v{ver}{rest}"))
+        }
+        _ => None,
+    }
+}
+
+/// Strips the namespace of `owner` from targets in the value of a `Link` header.
+fn strip_links(links: &str, owner: &UserName) -> String {
+    let mut parts = links.split('<');
+    let mut stripped = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        stripped.push('<');
+        match part
+            .split_once('>')
+            .and_then(|(target, rest)| Some((from_namespace(target, owner)?, rest)))
+        {
+            Some((target, rest)) => {
+                stripped.push_str(&target);
+                stripped.push('>');
+                stripped.push_str(rest);
+            }
+            None => stripped.push_str(part),
+        }
+    }
+    stripped
+}
+
+/// Routes API requests addressed to vanity hosts to the namespaces of their owners.
+pub(crate) async fn handle<B>(mut req: Request<B>, next: Next<B>) -> Response {
+    let hosts = match req.extensions().get::<Arc<VanityHosts>>().cloned() {
+        Some(hosts) => hosts,
+        None => return next.run(req).await,
+    };
+    hosts.refresh().await;
+    let owner = match request_host(&req).and_then(|host| hosts.owner(&host)) {
+        Some(owner) => owner,
+        None => return next.run(req).await,
+    };
+    let path = match to_namespace(req.uri().path(), &owner) {
+        Some(path) => path,
+        None => return next.run(req).await,
+    };
+    trace!(target: "app::hosts", "route `{}` to `{path}`", req.uri().path());
+    let path_and_query = match req.uri().query() {
+        Some(query) => format!("{path}?{query}"),
+        None => path,
+    };
+    let mut parts = req.uri().clone().into_parts();
+    parts.path_and_query = match path_and_query.parse::<PathAndQuery>() {
+        Ok(path_and_query) => Some(path_and_query),
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid request path").into_response(),
+    };
+    *req.uri_mut() = match Uri::from_parts(parts) {
+        Ok(uri) => uri,
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid request path").into_response(),
+    };
+
+    let mut res = next.run(req).await;
+    let headers = res.headers_mut();
+    if let Some(location) = headers
+        .get(LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| from_namespace(v, &owner))
+    {
+        if let Ok(location) = HeaderValue::try_from(location) {
+            _ = headers.insert(LOCATION, location);
+        }
+    }
+    links::rewrite(headers, |v| strip_links(v, &owner));
+    res
+}
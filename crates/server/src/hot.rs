@@ -0,0 +1,148 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! In-process cache of contents of frequently requested entities.
+//!
+//! Contents are keyed by their digest, so identical contents stored in several entities are
+//! cached once. Only contents verified against their digest are cached and the least
+//! recently used contents are evicted when the cache is full. This lets a tag pulled by
+//! a whole fleet at once be served from memory instead of the store.
+
+use super::alerts::{Alerts, IntegrityAlert};
+use super::store::{Entity, GetError};
+
+use drawbridge_type::Meta;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::Context;
+use async_std::sync::Arc;
+use axum::body::{boxed, BoxBody, Bytes, Full, StreamBody};
+use camino::Utf8Path;
+use futures::AsyncReadExt;
+
+/// Default maximum total size of cached contents.
+const DEFAULT_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Default maximum size of cached contents of a single entity.
+const DEFAULT_MAX_ENTRY_SIZE: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug, Default)]
+struct Contents {
+    /// Cached contents and the time they were last used at, keyed by digest
+    entries: HashMap<String, (Bytes, u64)>,
+    /// Total size of cached contents
+    size: u64,
+    /// Logical time, incremented on each use
+    clock: u64,
+}
+
+/// In-process cache of entity contents.
+#[derive(Debug)]
+pub struct HotCache {
+    max_size: u64,
+    max_entry_size: u64,
+    contents: Mutex<Contents>,
+}
+
+impl Default for HotCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_SIZE, DEFAULT_MAX_ENTRY_SIZE)
+    }
+}
+
+impl HotCache {
+    /// Constructs a new [HotCache] holding at most `max_size` bytes of contents in total and
+    /// at most `max_entry_size` bytes of contents of a single entity.
+    ///
+    /// A `max_size` of 0 disables the cache.
+    pub fn new(max_size: u64, max_entry_size: u64) -> Self {
+        Self {
+            max_size,
+            max_entry_size: max_entry_size.min(max_size),
+            contents: Default::default(),
+        }
+    }
+
+    /// Returns an empty cache with the same limits.
+    pub(crate) fn with_same_limits(&self) -> Self {
+        Self::new(self.max_size, self.max_entry_size)
+    }
+
+    fn get(&self, key: &str) -> Option<Bytes> {
+        let mut contents = self.contents.lock().ok()?;
+        contents.clock += 1;
+        let now = contents.clock;
+        contents.entries.get_mut(key).map(|(body, used)| {
+            *used = now;
+            body.clone()
+        })
+    }
+
+    fn insert(&self, key: String, body: Bytes) {
+        let size = body.len() as u64;
+        let mut contents = match self.contents.lock() {
+            Ok(contents) => contents,
+            Err(_) => return,
+        };
+        if contents.entries.contains_key(&key) {
+            return;
+        }
+        while contents.size + size > self.max_size {
+            let lru = contents
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            match lru.and_then(|key| contents.entries.remove(&key)) {
+                Some((body, _)) => contents.size -= body.len() as u64,
+                None => return,
+            }
+        }
+        contents.clock += 1;
+        let now = contents.clock;
+        contents.size += size;
+        _ = contents.entries.insert(key, (body, now));
+    }
+
+    /// Returns metadata of `entity` and its contents as a response body, which is served
+    /// from the cache if possible.
+    ///
+    /// Contents not exceeding the maximum entry size are read, verified and cached,
+    /// larger contents are streamed from the store.
+    ///
+    /// Contents failing verification are alerted of using `alerts`.
+    pub(crate) async fn get_body(
+        &self,
+        entity: &Entity<'_, impl AsRef<Utf8Path>>,
+        alerts: &Arc<Alerts>,
+    ) -> Result<(Meta, BoxBody), GetError<anyhow::Error>> {
+        let meta = entity.get_meta().await?;
+        let key = meta.hash.to_string();
+        if let Some(body) = self.get(&key) {
+            return Ok((meta, boxed(Full::new(body))));
+        }
+        if meta.size > self.max_entry_size {
+            let (meta, body) = entity.get_stream().await?;
+            let body = alerts.watch(IntegrityAlert::read(entity, &meta.hash), body);
+            return Ok((meta, boxed(StreamBody::new(body))));
+        }
+
+        let mut buf = Vec::with_capacity(meta.size.try_into().unwrap_or_default());
+        _ = meta
+            .hash
+            .clone()
+            .verifier(entity.get_content().await?)
+            .read_to_end(&mut buf)
+            .await
+            .context("failed to read content")
+            .map_err(GetError::Internal)
+            .inspect_err(|e| {
+                alerts.raise_on_mismatch(e, || IntegrityAlert::read(entity, &meta.hash))
+            })?;
+        let body = Bytes::from(buf);
+        self.insert(key, body.clone());
+        Ok((meta, boxed(Full::new(body))))
+    }
+}
@@ -0,0 +1,89 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Deferred verification of uploaded tree node contents.
+//!
+//! With [VerificationPolicy::Deferred], uploads of file nodes are accepted after verifying
+//! only the strongest algorithm of their `Content-Digest` inline. Remaining algorithms are
+//! verified in background and nodes failing verification are moved into the `quarantine`
+//! directory of the store, after which an [Event::EntityDeleted] is published, which
+//! `integrity-failure` webhooks of the repository are notified of, and an [IntegrityAlert]
+//! is raised. Quarantined nodes are no longer served and may be uploaded again.
+
+use super::alerts::{AlertSource, Alerts, IntegrityAlert};
+use super::events::{DeleteCause, Event, EventBus};
+use super::{GetError, Store};
+
+use drawbridge_type::digest::ContentDigest;
+use drawbridge_type::TreeContext;
+
+use async_std::sync::Arc;
+use async_std::task::spawn;
+use tracing::{error, trace, warn};
+
+/// Policy of content digest verification of uploaded tree nodes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VerificationPolicy {
+    /// All algorithms are verified before the upload is accepted
+    #[default]
+    Inline,
+    /// Only the strongest algorithm is verified before the upload is accepted
+    Deferred,
+}
+
+/// Verifies `deferred` digests of contents of tree node `cx` with digest `hash` and length
+/// `size` in background.
+pub(crate) fn verify_deferred(
+    store: &Arc<Store>,
+    events: &Arc<EventBus>,
+    alerts: &Arc<Alerts>,
+    cx: TreeContext,
+    hash: ContentDigest,
+    size: u64,
+    deferred: ContentDigest,
+) {
+    if deferred.is_empty() {
+        return;
+    }
+    let store = Arc::clone(store);
+    let events = Arc::clone(events);
+    let alerts = Arc::clone(alerts);
+    _ = spawn(async move {
+        let repo = store.repository(&cx.tag.repository);
+        // NOTE: The node may have been uploaded to a pending tag, which is not published yet.
+        let tag = match repo.tag(&cx.tag.name).node(&cx.path).get_meta().await {
+            Err(GetError::NotFound) => repo.pending_tag(&cx.tag.name),
+            _ => repo.tag(&cx.tag.name),
+        };
+        let node = tag.node(&cx.path);
+        let actual = match node.digest(&deferred).await {
+            Ok(actual) if actual == deferred => {
+                trace!(target: "app::integrity", "verified deferred digests of `{cx}`");
+                return;
+            }
+            Ok(actual) => actual,
+            Err(e) => {
+                error!(target: "app::integrity", "failed to verify `{cx}`: {:?}", e);
+                return;
+            }
+        };
+        alerts.raise(IntegrityAlert::new(
+            AlertSource::DeferredVerification,
+            node.prefix(),
+            deferred,
+            Some(actual),
+        ));
+        match tag.quarantine_node(&cx.path).await {
+            Ok(path) => {
+                warn!(target: "app::integrity", "`{cx}` failed verification, quarantined at `{path}`");
+                events.publish(Event::EntityDeleted {
+                    node: cx,
+                    digest: hash,
+                    size,
+                    cause: DeleteCause::IntegrityFailure,
+                });
+            }
+            Err(e) => error!(target: "app::integrity", "`{cx}` failed verification: {:?}", e),
+        }
+    });
+}
@@ -0,0 +1,126 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{GetError, Store, TrustedCertificate};
+use super::{leaf, Car, Dag, Link, CAR_TYPE, CHUNK_SIZE};
+use crate::auth::{assert_repository_read, pull_tag};
+use crate::stream::spawn_chunks;
+
+use drawbridge_type::{TagContext, TreePath};
+
+use std::collections::BTreeMap;
+
+use async_std::sync::Arc;
+use axum::body::{Body, StreamBody};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use futures::TryStreamExt;
+use tracing::{debug, trace};
+
+/// Exports the tree of the tag as a CARv1 archive rooted at the CID of the tree root.
+///
+/// The root CID is additionally returned in the `ipfs-root` header. Since the header of the
+/// archive contains the root CID, all file contents are hashed before the response is sent.
+/// Blocks are then streamed by reading the contents again, so that only the dag-pb nodes of
+/// the tree are kept in memory.
+pub async fn get(
+    Extension(ref store): Extension<Arc<Store>>,
+    cert: Option<Extension<TrustedCertificate>>,
+    cx: TagContext,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::ipfs::get", "called for `{cx}`");
+
+    let repo = if cert.is_none() {
+        assert_repository_read(store, &cx.repository, req)
+            .await
+            .map_err(IntoResponse::into_response)
+            .map(|(repo, _)| repo)?
+    } else {
+        store.repository(&cx.repository)
+    };
+    let (tag, _) = pull_tag(&repo, &cx.name, cert.as_deref())
+        .await
+        .map_err(|e| {
+            debug!(target: "app::ipfs::get", "failed for `{cx}`: {:?}", e);
+            e.into_response()
+        })?;
+    let nodes = tag.walk().await.map_err(|e| {
+        debug!(target: "app::ipfs::get", "failed to walk tree of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+
+    let mut dag = Dag::default();
+    let mut children: BTreeMap<TreePath, BTreeMap<String, Link>> = BTreeMap::new();
+    let mut root = None;
+    // NOTE: Nodes are visited in reverse order, so that children precede their parents.
+    let mut contents = tag.read_nodes(nodes.clone().into_iter().rev());
+    while let Some((path, content)) = contents.try_next().await.map_err(|e| {
+        debug!(target: "app::ipfs::get", "failed to read tree of `{cx}`: {:?}", e);
+        e.into_response()
+    })? {
+        let link = match content {
+            Some(content) => {
+                let leaves = content
+                    .chunks(CHUNK_SIZE)
+                    .map_ok(|chunk| leaf(&chunk))
+                    .try_collect()
+                    .await
+                    .map_err(|e| {
+                        debug!(target: "app::ipfs::get", "failed to read `{path}` of `{cx}`: {:?}", e);
+                        GetError::Internal(anyhow::Error::from(e)).into_response()
+                    })?;
+                dag.add_file(leaves)
+            }
+            None => {
+                let entries = children.remove(&path).unwrap_or_default();
+                dag.add_directory(entries.iter().map(|(name, link)| (name.as_str(), link)))
+            }
+        };
+        match (path.parent(), path.name()) {
+            (Some(parent), Some(name)) => {
+                _ = children
+                    .entry(parent)
+                    .or_default()
+                    .insert(name.to_string(), link)
+            }
+            _ => root = Some(link),
+        }
+    }
+    drop(contents);
+    let root = root
+        .ok_or_else(|| (StatusCode::INTERNAL_SERVER_ERROR, "Tree root missing").into_response())?
+        .cid;
+
+    let store = Arc::clone(store);
+    let header = Car::header(&root);
+    let body = spawn_chunks(move |tx| async move {
+        let tag = store.tag(&cx);
+        let mut car = Car::default();
+        tx.send(header).await?;
+        let mut contents = tag.read_nodes(nodes.into_iter().rev());
+        while let Some((path, content)) = contents.try_next().await.map_err(|e| {
+            debug!(target: "app::ipfs::get", "failed to read tree of `{cx}`: {:?}", e);
+            anyhow::Error::from(e)
+        })? {
+            let mut chunks = match content {
+                Some(content) => content.chunks(CHUNK_SIZE),
+                None => continue,
+            };
+            while let Some(chunk) = chunks.try_next().await.map_err(|e| {
+                debug!(target: "app::ipfs::get", "failed to read `{path}` of `{cx}`: {:?}", e);
+                anyhow::Error::from(e)
+            })? {
+                tx.send(car.add_leaf(&chunk)).await?;
+            }
+        }
+        tx.send(car.add_dag(dag)).await
+    });
+    Ok::<_, Response>((
+        [(CONTENT_TYPE, CAR_TYPE)],
+        [("ipfs-root", root.to_string())],
+        StreamBody::new(body),
+    ))
+}
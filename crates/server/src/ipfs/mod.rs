@@ -0,0 +1,261 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! IPFS export of tag trees.
+//!
+//! Files are chunked into raw leaves linked by balanced UnixFS file nodes and directories
+//! are encoded as UnixFS directory nodes, all of which are addressed by CIDv1 with SHA-256
+//! multihashes. Trees are exported as CARv1 archives, which can be imported into IPFS nodes
+//! using e.g. `ipfs dag import`.
+
+mod get;
+
+pub use get::*;
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use ring::digest::{digest, SHA256};
+
+/// Media type of CARv1 archives.
+pub const CAR_TYPE: &str = "application/vnd.ipld.car";
+
+/// Multicodec code of raw binary blocks.
+const RAW: u64 = 0x55;
+
+/// Multicodec code of dag-pb blocks.
+const DAG_PB: u64 = 0x70;
+
+/// Multicodec code of SHA-256 multihashes.
+const SHA2_256: u64 = 0x12;
+
+/// Maximum size of a file chunk.
+const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Maximum number of links in a UnixFS file node.
+const MAX_LINKS: usize = 174;
+
+/// UnixFS `Directory` data type.
+const UNIXFS_DIRECTORY: u64 = 1;
+
+/// UnixFS `File` data type.
+const UNIXFS_FILE: u64 = 2;
+
+fn write_varint(buf: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        buf.push((n as u8 & 0x7f) | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+fn write_pb_varint(buf: &mut Vec<u8>, field: u64, n: u64) {
+    write_varint(buf, field << 3);
+    write_varint(buf, n);
+}
+
+fn write_pb_bytes(buf: &mut Vec<u8>, field: u64, data: &[u8]) {
+    write_varint(buf, field << 3 | 2);
+    write_varint(buf, data.len() as _);
+    buf.extend_from_slice(data);
+}
+
+/// A binary-encoded CIDv1.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cid(Vec<u8>);
+
+impl Cid {
+    /// Computes the CID of a block of `data` encoded with `codec`.
+    fn new(codec: u64, data: &[u8]) -> Self {
+        let mut buf = vec![];
+        write_varint(&mut buf, 1);
+        write_varint(&mut buf, codec);
+        write_varint(&mut buf, SHA2_256);
+        let hash = digest(&SHA256, data);
+        write_varint(&mut buf, hash.as_ref().len() as _);
+        buf.extend_from_slice(hash.as_ref());
+        Self(buf)
+    }
+}
+
+impl AsRef<[u8]> for Cid {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Formats the CID using the lowercase base32 multibase encoding.
+impl Display for Cid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+        let mut s = String::from("b");
+        let (mut acc, mut bits) = (0u16, 0);
+        for b in &self.0 {
+            acc = acc << 8 | *b as u16;
+            bits += 8;
+            while bits >= 5 {
+                bits -= 5;
+                s.push(ALPHABET[(acc >> bits & 0x1f) as usize] as char);
+            }
+        }
+        if bits > 0 {
+            s.push(ALPHABET[(acc << (5 - bits) & 0x1f) as usize] as char);
+        }
+        f.write_str(&s)
+    }
+}
+
+/// A link to a DAG.
+#[derive(Clone, Debug)]
+pub struct Link {
+    pub cid: Cid,
+    /// Total size of all blocks in the DAG.
+    pub size: u64,
+}
+
+/// Returns the link to the raw leaf `chunk` of a file.
+pub fn leaf(chunk: &[u8]) -> Link {
+    Link {
+        cid: Cid::new(RAW, chunk),
+        size: chunk.len() as _,
+    }
+}
+
+/// A set of UnixFS DAGs.
+///
+/// Only dag-pb nodes are kept, since raw leaves are file contents, which are encoded by
+/// [Car::add_leaf] when the DAGs are written.
+#[derive(Clone, Debug, Default)]
+pub struct Dag {
+    seen: BTreeSet<Cid>,
+    nodes: Vec<(Cid, Vec<u8>)>,
+}
+
+impl Dag {
+    fn put_node<'a>(
+        &mut self,
+        links: impl IntoIterator<Item = (Option<&'a str>, &'a Link)>,
+        data: &[u8],
+    ) -> Link {
+        let mut buf = vec![];
+        let mut size = 0;
+        for (name, link) in links {
+            let mut pb = vec![];
+            write_pb_bytes(&mut pb, 1, link.cid.as_ref());
+            if let Some(name) = name {
+                write_pb_bytes(&mut pb, 2, name.as_bytes());
+            }
+            write_pb_varint(&mut pb, 3, link.size);
+            write_pb_bytes(&mut buf, 2, &pb);
+            size += link.size;
+        }
+        write_pb_bytes(&mut buf, 1, data);
+        let cid = Cid::new(DAG_PB, &buf);
+        let link = Link {
+            cid: cid.clone(),
+            size: buf.len() as u64 + size,
+        };
+        if self.seen.insert(cid.clone()) {
+            self.nodes.push((cid, buf));
+        }
+        link
+    }
+
+    /// Adds a file to the DAG, whose contents are chunked into [CHUNK_SIZE] bytes linked by
+    /// `leaves`, see [leaf].
+    ///
+    /// Empty files consist of a single empty leaf, hence `leaves` must not be empty.
+    pub fn add_file(&mut self, leaves: Vec<Link>) -> Link {
+        let mut nodes: Vec<_> = leaves
+            .into_iter()
+            .map(|link| {
+                let size = link.size;
+                (link, size)
+            })
+            .collect();
+        while nodes.len() > 1 {
+            nodes = nodes
+                .chunks(MAX_LINKS)
+                .map(|children| {
+                    let mut data = vec![];
+                    write_pb_varint(&mut data, 1, UNIXFS_FILE);
+                    let filesize = children.iter().map(|(_, n)| n).sum();
+                    write_pb_varint(&mut data, 3, filesize);
+                    for (_, n) in children {
+                        write_pb_varint(&mut data, 4, *n);
+                    }
+                    let link = self.put_node(children.iter().map(|(link, _)| (None, link)), &data);
+                    (link, filesize)
+                })
+                .collect();
+        }
+        let (link, _) = nodes.remove(0);
+        link
+    }
+
+    /// Adds a directory with `entries` sorted by name to the DAG.
+    pub fn add_directory<'a>(
+        &mut self,
+        entries: impl IntoIterator<Item = (&'a str, &'a Link)>,
+    ) -> Link {
+        let mut data = vec![];
+        write_pb_varint(&mut data, 1, UNIXFS_DIRECTORY);
+        self.put_node(
+            entries.into_iter().map(|(name, link)| (Some(name), link)),
+            &data,
+        )
+    }
+}
+
+/// A CARv1 archive encoder, which encodes every block at most once.
+///
+/// Archives are encoded piecewise, so that they can be streamed: the header is followed by
+/// the raw leaves of all files added to a [Dag] and finally by the nodes of the [Dag].
+#[derive(Debug, Default)]
+pub struct Car {
+    seen: BTreeSet<Cid>,
+}
+
+impl Car {
+    /// Encodes the header of an archive with a single `root`.
+    pub fn header(root: &Cid) -> Vec<u8> {
+        // NOTE: The header is a DAG-CBOR encoded map `{"roots": [root], "version": 1}`.
+        let mut header = vec![0xa2, 0x65];
+        header.extend_from_slice(b"roots");
+        header.extend_from_slice(&[0x81, 0xd8, 0x2a, 0x58, root.0.len() as u8 + 1, 0x00]);
+        header.extend_from_slice(root.as_ref());
+        header.push(0x67);
+        header.extend_from_slice(b"version");
+        header.push(0x01);
+
+        let mut buf = vec![];
+        write_varint(&mut buf, header.len() as _);
+        buf.extend(header);
+        buf
+    }
+
+    fn write_block(&mut self, buf: &mut Vec<u8>, cid: Cid, data: &[u8]) {
+        if self.seen.insert(cid.clone()) {
+            write_varint(buf, (cid.0.len() + data.len()) as _);
+            buf.extend(cid.0);
+            buf.extend_from_slice(data);
+        }
+    }
+
+    /// Encodes the raw leaf `chunk` of a file, unless it was encoded already.
+    pub fn add_leaf(&mut self, chunk: &[u8]) -> Vec<u8> {
+        let mut buf = vec![];
+        self.write_block(&mut buf, Cid::new(RAW, chunk), chunk);
+        buf
+    }
+
+    /// Encodes the nodes of `dag`.
+    pub fn add_dag(&mut self, dag: Dag) -> Vec<u8> {
+        let mut buf = vec![];
+        for (cid, data) in dag.nodes {
+            self.write_block(&mut buf, cid, &data);
+        }
+        buf
+    }
+}
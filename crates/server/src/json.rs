@@ -0,0 +1,154 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use drawbridge_type::digest::Algorithms;
+use drawbridge_type::{MediaType, Meta};
+
+use axum::body::{Bytes, HttpBody};
+use axum::extract::{BodyStream, FromRequest, RequestParts};
+use axum::http::header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::BoxError;
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Maximum size of JSON request bodies, which, unlike file contents, are decoded in memory.
+pub(crate) const MAX_BODY_SIZE: u64 = 8 * 1024 * 1024;
+
+fn too_large() -> Response {
+    (StatusCode::PAYLOAD_TOO_LARGE, "Request body too large").into_response()
+}
+
+/// A request body of at most `MAX` bytes, which is read into memory.
+///
+/// Bodies exceeding the limit, either by their declared `Content-Length` or by the contents
+/// actually sent, are rejected with `413 Payload Too Large` before being read any further.
+#[derive(Clone, Debug)]
+pub struct BoundedBody<const MAX: u64 = MAX_BODY_SIZE>(pub Vec<u8>);
+
+#[axum::async_trait]
+impl<B, const MAX: u64> FromRequest<B> for BoundedBody<MAX>
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Into<Bytes>,
+    B::Error: Into<BoxError>,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let declared = req
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|size| size.to_str().ok()?.parse::<u64>().ok());
+        if declared.is_some_and(|size| size > MAX) {
+            return Err(too_large());
+        }
+        let mut body = req
+            .extract::<BodyStream>()
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let mut buf = Vec::with_capacity(declared.unwrap_or_default() as _);
+        while let Some(chunk) = body.next().await {
+            let chunk =
+                chunk.map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()).into_response())?;
+            if (buf.len() + chunk.len()) as u64 > MAX {
+                return Err(too_large());
+            }
+            buf.extend_from_slice(&chunk);
+        }
+        Ok(Self(buf))
+    }
+}
+
+/// A JSON request body of at most `MAX` bytes decoded as `T`, see [BoundedBody].
+///
+/// The `Content-Type` must be `application/json` or use the `+json` suffix.
+#[derive(Clone, Debug)]
+pub struct BoundedJson<T, const MAX: u64 = MAX_BODY_SIZE>(pub T);
+
+#[axum::async_trait]
+impl<B, T, const MAX: u64> FromRequest<B> for BoundedJson<T, MAX>
+where
+    B: HttpBody + Send + 'static,
+    B::Data: Into<Bytes>,
+    B::Error: Into<BoxError>,
+    T: DeserializeOwned,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let mime = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|mime| mime.to_str().ok()?.parse::<MediaType>().ok());
+        if !mime
+            .is_some_and(|mime| mime.is(MediaType::JSON.essence()) || mime.suffix() == Some("json"))
+        {
+            return Err((StatusCode::UNSUPPORTED_MEDIA_TYPE, "Expected JSON body").into_response());
+        }
+        let BoundedBody(buf) = req.extract::<BoundedBody<MAX>>().await?;
+        serde_json::from_slice(&buf).map(Self).map_err(|e| {
+            (StatusCode::BAD_REQUEST, format!("Invalid JSON body: {e}")).into_response()
+        })
+    }
+}
+
+/// Returns `true` if `Accept` headers in `headers` prefer any of the media types
+/// `alternatives` over JSON media type `json`, which wildcards and `application/json` count
+/// towards.
+pub(crate) fn prefers(headers: &HeaderMap, alternatives: &[&str], json: &str) -> bool {
+    let mut alternative_q = None::<f32>;
+    let mut json_q = None::<f32>;
+    for range in headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+    {
+        let mut params = range.split(';').map(str::trim);
+        let essence = params.next().unwrap_or_default();
+        let q = params
+            .filter_map(|param| param.strip_prefix("q="))
+            .filter_map(|q| q.parse::<f32>().ok())
+            .next_back()
+            .unwrap_or(1.0);
+        let best = if alternatives
+            .iter()
+            .any(|alternative| essence.eq_ignore_ascii_case(alternative))
+        {
+            &mut alternative_q
+        } else if essence.eq_ignore_ascii_case(json)
+            || essence.eq_ignore_ascii_case(MediaType::JSON.essence())
+            || essence == "application/*"
+            || essence == "*/*"
+        {
+            &mut json_q
+        } else {
+            continue;
+        };
+        *best = Some(best.map_or(q, |best| best.max(q)));
+    }
+    alternative_q.is_some_and(|q| q > 0.0 && json_q.is_none_or(|json_q| q >= json_q))
+}
+
+/// Encodes `val` as JSON and returns it along with its [Meta].
+pub(crate) fn encode(val: &impl Serialize) -> Result<(Meta, Vec<u8>), (StatusCode, &'static str)> {
+    let buf = serde_json::to_vec(val)
+        .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR, "Failed to encode JSON"))?;
+    let (_, hash) = Algorithms::default().read_sync(&buf[..]).map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to compute digest",
+        )
+    })?;
+    Ok((
+        Meta {
+            hash,
+            size: buf.len() as _,
+            mime: MediaType::JSON,
+        },
+        buf,
+    ))
+}
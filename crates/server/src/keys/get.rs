@@ -0,0 +1,62 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::Store;
+use super::{ManagedKeys, PublicKey};
+use crate::auth::assert_repository_read;
+use crate::json;
+
+use drawbridge_jose::jwk::{EllipticCurveType, Jwk, JwkSet, Key, Parameters, Use};
+use drawbridge_type::RepositoryContext;
+
+use async_std::sync::Arc;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Returns `key` as an ES256 JWK.
+pub(crate) fn jwk(key: PublicKey) -> Jwk {
+    // NOTE: Points are uncompressed, i.e. `0x04 || x || y`.
+    let (x, y) = key.point[1..].split_at(32);
+    Jwk {
+        key: Key::EllipticCurve {
+            crv: EllipticCurveType::P256,
+            d: None,
+            x: x.to_vec().into(),
+            y: y.to_vec().into(),
+        },
+        prm: Parameters {
+            alg: Some("ES256".into()),
+            kid: Some(key.id),
+            key_use: Some(Use::Signing),
+            ..Default::default()
+        },
+    }
+}
+
+/// Returns public keys of all server-managed signing keys of the repository as a JWK set
+/// ordered from the current to the oldest key.
+pub async fn get(
+    Extension(ref store): Extension<Arc<Store>>,
+    keys: Option<Extension<Arc<ManagedKeys>>>,
+    cx: RepositoryContext,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::keys::get", "called for `{cx}`");
+
+    let Extension(keys) = keys
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Signing keys are not managed").into_response())?;
+    let (repo, _) = assert_repository_read(store, &cx, req)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let public_keys = keys.public_keys(&repo).await.map_err(|e| {
+        debug!(target: "app::keys::get", "failed for `{cx}`: {:?}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, "Failed to read keys").into_response()
+    })?;
+    json::encode(&JwkSet {
+        keys: public_keys.into_iter().rev().map(jwk).collect(),
+    })
+    .map_err(IntoResponse::into_response)
+}
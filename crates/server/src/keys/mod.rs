@@ -0,0 +1,362 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Server-managed signing keys of repositories.
+//!
+//! With a [Kms] configured, owners generate or import ECDSA P-256 signing keys of their
+//! repositories using [put]. Private keys are only stored wrapped by the [Kms], which may be
+//! the [LocalKms] or an external key management service. Generating a key again rotates it,
+//! i.e. the newest key becomes the current one, while older keys are kept for verification.
+//!
+//! Whenever a tag is published, the server countersigns the tag entry contents with the
+//! current key of its repository and attaches the signature to the tag like a cosign-style
+//! signature named `server-KID`. Public keys of all keys of a repository are served by [get]
+//! under `_keys` as a JWK set, so that clients can verify countersigned tags.
+
+mod get;
+mod put;
+
+pub use get::*;
+pub use put::*;
+
+use super::events::{Event, EventBus};
+use super::store::{CreateError, Repository};
+use super::{Clock, Store};
+
+use drawbridge_type::digest::{hex, Algorithms};
+use drawbridge_type::{MediaType, Meta};
+
+use std::fmt;
+use std::io::BufRead;
+use std::time::UNIX_EPOCH;
+
+use anyhow::{anyhow, ensure, Context};
+use async_std::sync::Arc;
+use async_std::task::{spawn, spawn_blocking};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::digest::{digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{
+    EcdsaKeyPair, KeyPair, UnparsedPublicKey, ECDSA_P256_SHA256_ASN1,
+    ECDSA_P256_SHA256_ASN1_SIGNING,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+
+/// Prefix of names of signatures the server countersigns tags with.
+pub const SIGNATURE_PREFIX: &str = "server-";
+
+/// Key management service, which wraps private keys before they are stored and unwraps them
+/// before they are used.
+///
+/// Keys are wrapped in the `context` of the path of the repository they belong to, which
+/// implementations must authenticate, so that wrapped keys cannot be moved to other
+/// repositories.
+pub trait Kms: fmt::Debug + Send + Sync {
+    /// Returns `plaintext` encrypted and bound to `context`.
+    fn wrap(&self, context: &[u8], plaintext: &[u8]) -> anyhow::Result<Vec<u8>>;
+
+    /// Returns the plaintext of `wrapped`, as returned by [Self::wrap] for `context`.
+    fn unwrap(&self, context: &[u8], wrapped: &[u8]) -> anyhow::Result<Vec<u8>>;
+}
+
+/// [Kms], which encrypts private keys with AES-256-GCM using a local master key and the
+/// context as additional authenticated data.
+pub struct LocalKms {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl fmt::Debug for LocalKms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalKms").finish_non_exhaustive()
+    }
+}
+
+impl LocalKms {
+    /// Constructs a KMS from a 32-byte master key.
+    pub fn new(key: &[u8]) -> anyhow::Result<Self> {
+        let key = UnboundKey::new(&AES_256_GCM, key)
+            .map_err(|_| anyhow!("master key must be 32 bytes long"))?;
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// Reads a Base64-encoded master key from `rd`, e.g. as generated by
+    /// `openssl rand -base64 32`.
+    pub fn read(mut rd: impl BufRead) -> anyhow::Result<Self> {
+        let mut b64 = String::new();
+        _ = rd
+            .read_to_string(&mut b64)
+            .context("failed to read master key")?;
+        base64::decode(b64.trim())
+            .context("failed to decode master key")
+            .and_then(|key| Self::new(&key))
+    }
+}
+
+impl Kms for LocalKms {
+    fn wrap(&self, context: &[u8], plaintext: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let mut nonce = [0; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| anyhow!("failed to generate nonce"))?;
+        let mut buf = plaintext.to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(context),
+                &mut buf,
+            )
+            .map_err(|_| anyhow!("failed to encrypt key"))?;
+        Ok([&nonce[..], &buf].concat())
+    }
+
+    fn unwrap(&self, context: &[u8], wrapped: &[u8]) -> anyhow::Result<Vec<u8>> {
+        ensure!(wrapped.len() > NONCE_LEN, "wrapped key too short");
+        let (nonce, ciphertext) = wrapped.split_at(NONCE_LEN);
+        let nonce =
+            Nonce::try_assume_unique_for_key(nonce).map_err(|_| anyhow!("invalid nonce length"))?;
+        let mut buf = ciphertext.to_vec();
+        let plaintext = self
+            .key
+            .open_in_place(nonce, Aad::from(context), &mut buf)
+            .map_err(|_| anyhow!("failed to decrypt key"))?;
+        Ok(plaintext.to_vec())
+    }
+}
+
+/// A signing key as stored in a repository.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct StoredKey {
+    /// Base64-encoded uncompressed public key point
+    public: String,
+
+    /// Base64-encoded PKCS#8 private key wrapped by the [Kms]
+    wrapped: String,
+}
+
+/// Public key of a server-managed signing key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PublicKey {
+    /// Key ID, which orders keys by the time of their creation
+    pub id: String,
+
+    /// Uncompressed ECDSA P-256 public key point
+    pub point: Vec<u8>,
+}
+
+/// Decodes the first PEM-encoded PKCS#8 private key in `pem`.
+pub(crate) fn decode_private_key(mut pem: &[u8]) -> anyhow::Result<Vec<u8>> {
+    rustls_pemfile::pkcs8_private_keys(&mut pem)
+        .context("failed to decode private key")?
+        .into_iter()
+        .next()
+        .context("PEM-encoded private key not found")
+}
+
+fn create_error(e: CreateError<anyhow::Error>) -> anyhow::Error {
+    match e {
+        CreateError::Internal(e) => e,
+        e => anyhow!("{e:?}"),
+    }
+}
+
+/// Manager of the signing keys of repositories.
+#[derive(Debug)]
+pub struct ManagedKeys {
+    kms: Arc<dyn Kms>,
+    clock: Arc<dyn Clock>,
+}
+
+impl ManagedKeys {
+    pub(crate) fn new(kms: Arc<dyn Kms>, clock: Arc<dyn Clock>) -> Self {
+        Self { kms, clock }
+    }
+
+    /// Generates a signing key of `repo`, which becomes its current key, and returns it.
+    pub(crate) async fn generate(&self, repo: &Repository<'_>) -> anyhow::Result<PublicKey> {
+        let pkcs8 =
+            EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &SystemRandom::new())
+                .map_err(|_| anyhow!("failed to generate key"))?;
+        self.import(repo, pkcs8.as_ref().to_vec()).await
+    }
+
+    /// Imports PKCS#8-encoded ECDSA P-256 private key `pkcs8` as the current signing key of
+    /// `repo` and returns it.
+    pub(crate) async fn import(
+        &self,
+        repo: &Repository<'_>,
+        pkcs8: Vec<u8>,
+    ) -> anyhow::Result<PublicKey> {
+        let pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8)
+            .map_err(|e| anyhow!("invalid ECDSA P-256 private key: {e}"))?;
+        let point = pair.public_key().as_ref().to_vec();
+
+        let kms = Arc::clone(&self.kms);
+        let context = repo.prefix().to_string();
+        let wrapped = spawn_blocking(move || kms.wrap(context.as_bytes(), &pkcs8))
+            .await
+            .context("failed to wrap key")?;
+
+        let secs = self
+            .clock
+            .now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let fingerprint = hex(&digest(&SHA256, &point).as_ref()[..8]);
+        let id = format!("{secs:020}-{fingerprint}");
+        _ = repo
+            .create_key(
+                &id,
+                &StoredKey {
+                    public: base64::encode(&point),
+                    wrapped: base64::encode(wrapped),
+                },
+            )
+            .await
+            .map_err(create_error)
+            .context("failed to store key")?;
+        Ok(PublicKey { id, point })
+    }
+
+    /// Returns public keys of all signing keys of `repo` ordered from the oldest to the
+    /// current one.
+    pub(crate) async fn public_keys(
+        &self,
+        repo: &Repository<'_>,
+    ) -> anyhow::Result<Vec<PublicKey>> {
+        let mut keys = vec![];
+        for id in repo
+            .key_names()
+            .await
+            .map_err(anyhow::Error::from)
+            .context("failed to list keys")?
+        {
+            let key: StoredKey = repo
+                .key(&id)
+                .get_content_json()
+                .await
+                .map_err(anyhow::Error::from)
+                .with_context(|| format!("failed to read key `{id}`"))?;
+            let point = base64::decode(key.public)
+                .with_context(|| format!("failed to decode public key `{id}`"))?;
+            keys.push(PublicKey { id, point });
+        }
+        Ok(keys)
+    }
+
+    /// Returns the ID of the current signing key of `repo` along with the ASN.1-encoded
+    /// signature of `msg` made with it, if `repo` has any keys.
+    pub(crate) async fn sign(
+        &self,
+        repo: &Repository<'_>,
+        msg: Vec<u8>,
+    ) -> anyhow::Result<Option<(String, Vec<u8>)>> {
+        let id = match repo
+            .key_names()
+            .await
+            .map_err(anyhow::Error::from)
+            .context("failed to list keys")?
+            .pop()
+        {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        let key: StoredKey = repo
+            .key(&id)
+            .get_content_json()
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("failed to read key `{id}`"))?;
+        let wrapped = base64::decode(key.wrapped)
+            .with_context(|| format!("failed to decode wrapped key `{id}`"))?;
+
+        let kms = Arc::clone(&self.kms);
+        let context = repo.prefix().to_string();
+        let signature = spawn_blocking(move || {
+            let pkcs8 = kms
+                .unwrap(context.as_bytes(), &wrapped)
+                .context("failed to unwrap key")?;
+            let pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8)
+                .map_err(|e| anyhow!("invalid private key: {e}"))?;
+            pair.sign(&SystemRandom::new(), &msg)
+                .map(|sig| sig.as_ref().to_vec())
+                .map_err(|_| anyhow!("failed to sign"))
+        })
+        .await?;
+        Ok(Some((id, signature)))
+    }
+
+    /// Countersigns tags published, as published on `events`, with the current signing key
+    /// of their repositories in background.
+    pub(crate) fn subscribe(self: &Arc<Self>, store: &Arc<Store>, events: &EventBus) {
+        let keys = Arc::clone(self);
+        let store = Arc::clone(store);
+        let events = events.subscribe();
+        _ = spawn(async move {
+            while let Ok((_, event)) = events.recv().await {
+                let cx = match event {
+                    Event::TagUpdated { tag, .. } => tag,
+                    Event::RepositoryCreated { .. }
+                    | Event::RepositoryDeleted { .. }
+                    | Event::RepositoryRestored { .. }
+                    | Event::TagDeleted { .. }
+                    | Event::TagRestored { .. }
+                    | Event::TreeEntryUploaded { .. }
+                    | Event::EntityDeleted { .. }
+                    | Event::EntityRepaired { .. } => continue,
+                };
+                let repo = store.repository(&cx.repository);
+                let tag = repo.tag(&cx.name);
+                let res = async {
+                    let entry = tag
+                        .read_content()
+                        .await
+                        .map_err(anyhow::Error::from)
+                        .context("failed to read tag")?;
+                    let (id, signature) = match keys.sign(&repo, entry).await? {
+                        Some(signed) => signed,
+                        None => return Ok(()),
+                    };
+                    let buf = base64::encode(signature).into_bytes();
+                    let (size, hash) = Algorithms::default()
+                        .read_sync(&buf[..])
+                        .context("failed to compute signature digest")?;
+                    let meta = Meta {
+                        hash,
+                        size,
+                        mime: MediaType::TEXT,
+                    };
+                    match tag
+                        .create_signature(&format!("{SIGNATURE_PREFIX}{id}"), meta, buf.as_slice())
+                        .await
+                    {
+                        Ok(_) | Err(CreateError::Occupied) => {}
+                        Err(e) => return Err(create_error(e)).context("failed to store signature"),
+                    }
+                    debug!(target: "app::keys", "countersigned `{cx}` with key `{id}`");
+                    anyhow::Ok(())
+                };
+                if let Err(e) = res.await {
+                    warn!(target: "app::keys", "failed to countersign `{cx}`: {:?}", e);
+                }
+            }
+        });
+    }
+}
+
+/// Returns IDs of all `keys`, which ASN.1-encoded `signature` of `msg` is verified against.
+pub(crate) fn verify(keys: &[PublicKey], msg: &[u8], signature: &[u8]) -> Vec<String> {
+    keys.iter()
+        .filter(|key| {
+            UnparsedPublicKey::new(&ECDSA_P256_SHA256_ASN1, &key.point)
+                .verify(msg, signature)
+                .is_ok()
+        })
+        .map(|key| key.id.clone())
+        .collect()
+}
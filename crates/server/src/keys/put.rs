@@ -0,0 +1,64 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use super::{decode_private_key, jwk, ManagedKeys};
+use crate::json::{self, BoundedBody};
+
+use drawbridge_type::RepositoryContext;
+
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Adds a signing key to the repository, which becomes its current key, and returns its
+/// public key as a JWK.
+///
+/// An empty body generates a key, i.e. rotates the current key, while a PEM-encoded PKCS#8
+/// ECDSA P-256 private key, e.g. as generated by `openssl genpkey -algorithm EC -pkeyopt
+/// ec_paramgen_curve:P-256`, is imported.
+pub async fn put(
+    Extension(store): Extension<Arc<Store>>,
+    keys: Option<Extension<Arc<ManagedKeys>>>,
+    claims: OidcClaims,
+    cx: RepositoryContext,
+    BoundedBody(body): BoundedBody,
+) -> impl IntoResponse {
+    trace!(target: "app::keys::put", "called for `{cx}`");
+
+    let Extension(keys) = keys
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Signing keys are not managed").into_response())?;
+    let user = claims
+        .assert_user(
+            &store,
+            &cx.owner,
+            ScopeContext::Repository,
+            ScopeLevel::Write,
+        )
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let repo = user.repository(&cx.name);
+    _ = repo.get_json().await.map_err(|e| {
+        debug!(target: "app::keys::put", "failed to get repository `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+
+    let key = if body.iter().all(u8::is_ascii_whitespace) {
+        keys.generate(&repo).await
+    } else {
+        let pkcs8 = decode_private_key(&body).map_err(|e| {
+            debug!(target: "app::keys::put", "failed to decode key for `{cx}`: {:?}", e);
+            (StatusCode::BAD_REQUEST, "Invalid private key encoding").into_response()
+        })?;
+        keys.import(&repo, pkcs8).await
+    }
+    .map_err(|e| {
+        debug!(target: "app::keys::put", "failed for `{cx}`: {:?}", e);
+        (StatusCode::BAD_REQUEST, format!("Failed to add key: {e}")).into_response()
+    })?;
+    json::encode(&jwk(key))
+        .map(|res| (StatusCode::CREATED, res))
+        .map_err(IntoResponse::into_response)
+}
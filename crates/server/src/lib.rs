@@ -28,24 +28,111 @@
     variant_size_differences
 )]
 
+mod access_log;
+mod base_path;
 mod builder;
+mod cache;
+mod cbor;
+mod clock;
+mod conditional;
+mod downloads;
+mod encoding;
+#[cfg(feature = "graphql")]
+mod graphql;
 mod handle;
+mod hash;
+mod hot;
+mod integrity;
+mod json;
+mod links;
+#[cfg(feature = "msgpack")]
+mod msgpack;
+mod pagination;
+mod problem;
+mod quota;
+mod recent_errors;
+mod repair;
+mod seed;
+mod slow_log;
+mod stream;
+mod tar;
+mod tenant;
+#[cfg(feature = "ui")]
+mod ui;
+mod xml;
 
+pub mod admin;
+pub mod admission;
+pub mod alerts;
+pub mod algorithms;
+pub mod attestations;
 pub mod auth;
+pub mod backup;
+pub mod bagit;
+pub mod changes;
+pub mod cluster;
+pub mod delegation;
+pub mod events;
+pub mod federation;
+pub mod hosts;
+pub mod ipfs;
+pub mod keys;
+pub mod manifest;
+pub mod migration;
+pub mod placement;
+pub mod proxy;
+pub mod replay;
+pub mod replica;
+pub mod replication;
 pub mod repos;
+pub mod retention;
+pub mod reviews;
+pub mod s3;
+pub mod sboms;
+pub mod scan;
+pub mod search;
+pub mod signatures;
+pub mod snapshots;
 pub mod store;
 pub mod tags;
+pub mod trash;
 pub mod trees;
+pub mod tuf;
+pub mod uploads;
+pub mod usage;
 pub mod users;
+pub mod vulnerabilities;
+pub mod webhooks;
 
-pub use auth::{OidcClaims, ScopeContext, ScopeLevel, TlsConfig, TrustedCertificate};
+pub use access_log::{AccessLogConfig, AccessLogFormat};
+pub use auth::{
+    OidcClaims, ReplicaCertificate, ScopeContext, ScopeLevel, SignatureKeys, Steward, TlsConfig,
+    TrustedCertificate, UrlSigner, WorkloadIdentity,
+};
+pub use base_path::BasePath;
 pub use builder::*;
+pub use cache::CachePolicy;
+pub use clock::{Clock, SystemClock};
+pub use delegation::Delegation;
+pub use downloads::Downloads;
+pub use federation::Federation;
 pub(crate) use handle::*;
+pub use hot::HotCache;
+pub use integrity::VerificationPolicy;
+pub use placement::Placement;
+pub use proxy::Registries as ProxyRegistries;
+pub use quota::RateLimit;
+pub use recent_errors::{ErrorRecord, RecentErrors};
+pub use slow_log::SlowLogConfig;
 pub(crate) use store::*;
+pub use tenant::TenantConfig;
 
 pub use openidconnect::url;
 
+use std::net::SocketAddr;
+
 use anyhow::Context as _;
+use async_std::sync::Arc;
 use axum::extract::Extension;
 use axum::routing::IntoMakeService;
 use axum::Router;
@@ -54,14 +141,24 @@ use futures::lock::Mutex;
 use futures::{AsyncRead, AsyncWrite};
 use futures_rustls::TlsAcceptor;
 use hyper::server::conn::Http;
+use rustls::Certificate;
 use tokio_util::compat::FuturesAsyncReadCompatExt;
 use tower::MakeService;
-use tracing::trace;
+use tracing::{debug, trace};
+
+/// Address of the peer a request was received from, see [App::handle_from].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerAddr(pub SocketAddr);
 
 #[allow(missing_debug_implementations)] // TlsAcceptor does not implement Debug
 pub struct App {
     make_service: Mutex<IntoMakeService<Router>>,
     tls: TlsAcceptor,
+    roots: Vec<Certificate>,
+    steward: Option<Steward>,
+    /// DNS names, which identify read replicas presenting a trusted certificate.
+    replica_identities: Vec<String>,
+    clock: Arc<dyn Clock>,
 }
 
 impl App {
@@ -80,6 +177,24 @@ impl App {
     pub async fn handle(
         &self,
         stream: impl 'static + Unpin + AsyncRead + AsyncWrite,
+    ) -> anyhow::Result<()> {
+        self.serve(stream, None).await
+    }
+
+    /// Like [Self::handle], but makes the address of the peer of `stream` available to
+    /// requests as [PeerAddr], e.g. for the access log.
+    pub async fn handle_from(
+        &self,
+        stream: impl 'static + Unpin + AsyncRead + AsyncWrite,
+        peer: SocketAddr,
+    ) -> anyhow::Result<()> {
+        self.serve(stream, Some(peer)).await
+    }
+
+    async fn serve(
+        &self,
+        stream: impl 'static + Unpin + AsyncRead + AsyncWrite,
+        peer: Option<SocketAddr>,
     ) -> anyhow::Result<()> {
         trace!(target: "app::App::handle", "begin TLS handshake");
         let stream = self
@@ -97,9 +212,31 @@ impl App {
             .await
             .context("failed to create app service")?;
         let (_, conn) = stream.get_ref();
-        if conn.peer_certificates().is_some() {
-            svc = svc.layer(Extension(TrustedCertificate));
-            trace!(target: "app::App::handle", "add TrustedCertificate to extensions");
+        if let Some(certs) = conn.peer_certificates() {
+            let now = self.clock.now();
+            // NOTE: The TLS handshake accepts certificates issued by either the trusted CA or
+            // Steward, hence the chain is verified again against the trusted CA only before
+            // the certificate is trusted, while all other certificates are ignored.
+            if let Some(workload) = self
+                .steward
+                .as_ref()
+                .and_then(|steward| steward.identify(certs, now))
+            {
+                trace!(target: "app::App::handle", "add WorkloadIdentity {:?} to extensions", workload.identities);
+                svc = svc.layer(Extension(workload));
+            } else if let Some(cert) = auth::verify_client_certificate(&self.roots, certs, now) {
+                svc = svc.layer(Extension(TrustedCertificate));
+                trace!(target: "app::App::handle", "add TrustedCertificate to extensions");
+                if auth::is_valid_for_any(&cert, &self.replica_identities) {
+                    svc = svc.layer(Extension(ReplicaCertificate));
+                    trace!(target: "app::App::handle", "add ReplicaCertificate to extensions");
+                }
+            } else {
+                debug!(target: "app::App::handle", "ignore untrusted client certificate");
+            }
+        }
+        if let Some(peer) = peer {
+            svc = svc.layer(Extension(PeerAddr(peer)));
         }
         trace!(target: "app::App::handle", "begin HTTP request serving");
         Http::new()
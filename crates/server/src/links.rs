@@ -0,0 +1,66 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! [RFC 8288] `Link` headers pointing to related entities.
+//!
+//! [RFC 8288]: https://www.rfc-editor.org/rfc/rfc8288
+
+use super::API_VERSION;
+
+use drawbridge_type::{RepositoryContext, TagContext, TreeContext};
+
+use std::fmt::Display;
+
+use axum::http::header::{HeaderName, LINK};
+use axum::http::{HeaderMap, HeaderValue};
+
+/// A `Link` header, which can be returned as part of a response.
+pub(crate) type Link = [(HeaderName, String); 1];
+
+fn link(path: impl Display, rel: &str) -> Link {
+    [(
+        LINK,
+        format!(r#"</api/v{}/{path}>; rel="{rel}""#, *API_VERSION),
+    )]
+}
+
+/// Returns a link from the repository to its tag listing.
+pub(crate) fn repository(cx: &RepositoryContext) -> Link {
+    link(format_args!("{cx}/_tag"), "tags")
+}
+
+/// Returns a link from the tag to its tree root.
+pub(crate) fn tag(TagContext { repository, name }: &TagContext) -> Link {
+    link(format_args!("{repository}/_tag/{name}/tree"), "tree")
+}
+
+/// Returns a link from the tree node to its parent directory or, if the node is the
+/// tree root, to the tag.
+pub(crate) fn tree(TreeContext { tag, path }: &TreeContext) -> Link {
+    let TagContext { repository, name } = tag;
+    match path.parent() {
+        Some(parent) if parent.is_root() => {
+            link(format_args!("{repository}/_tag/{name}/tree"), "up")
+        }
+        Some(parent) => link(format_args!("{repository}/_tag/{name}/tree/{parent}"), "up"),
+        None => link(format_args!("{repository}/_tag/{name}"), "up"),
+    }
+}
+
+/// Replaces the values of all `Link` headers in `headers` by the result of `f` applied to
+/// them, e.g. to rewrite their targets, dropping values, which are not valid header values.
+pub(crate) fn rewrite(headers: &mut HeaderMap, f: impl Fn(&str) -> String) {
+    let links: Vec<_> = headers
+        .get_all(LINK)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .filter_map(|v| HeaderValue::try_from(f(v)).ok())
+        .collect();
+    if links.is_empty() {
+        return;
+    }
+    _ = headers.remove(LINK);
+    for link in links {
+        _ = headers.append(LINK, link);
+    }
+}
@@ -0,0 +1,192 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Bulk download manifests resolving references to the files they refer to.
+
+use super::auth::{may_read, UrlSigner};
+use super::base_path::{self, BasePath};
+use super::json::{self, BoundedBody};
+use super::store::is_directory;
+use super::{Clock, GetError, OidcClaims, PullTagError, Store, API_VERSION};
+
+use drawbridge_type::{
+    Manifest, ManifestEntry, ManifestRequest, Reference, ReferenceTarget, TagName, TreePath,
+};
+
+use std::time::Duration;
+
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Maximum number of seconds download URLs may be pre-signed for.
+pub const MAX_PRESIGN: u64 = 7 * 24 * 60 * 60;
+
+fn not_found(reference: &str) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        format!("Reference `{reference}` not found"),
+    )
+        .into_response()
+}
+
+fn internal_error(reference: &str, e: GetError<anyhow::Error>) -> Response {
+    match e {
+        GetError::NotFound => not_found(reference),
+        GetError::Internal(e) => {
+            debug!(target: "app::manifest::manifest", "failed to resolve `{reference}`: {:?}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to resolve `{reference}`"),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Appends the files `reference` requested as `requested` refers to to `manifest`, along with
+/// URLs pre-signed by `presign`, if specified, under the `base` path of the application.
+#[allow(clippy::result_large_err)]
+async fn resolve(
+    store: &Store,
+    claims: Option<&OidcClaims>,
+    presign: Option<(&UrlSigner, std::time::SystemTime)>,
+    base: Option<&BasePath>,
+    requested: &str,
+    reference: &Reference,
+    manifest: &mut Manifest,
+) -> Result<(), Response> {
+    let cx = &reference.repository;
+    if !may_read(store, cx, claims)
+        .await
+        .map_err(|e| internal_error(requested, e))?
+    {
+        return Err(not_found(requested));
+    }
+    let repo = store.repository(cx);
+    let name: TagName = match reference.target {
+        Some(ReferenceTarget::Tag(ref name)) => name.clone(),
+        Some(ref target @ ReferenceTarget::Digest(..)) => {
+            let mut resolved = None;
+            for name in repo
+                .tags()
+                .await
+                .map_err(|e| internal_error(requested, e))?
+            {
+                let meta = repo
+                    .tag(&name)
+                    .get_meta()
+                    .await
+                    .map_err(|e| internal_error(requested, e))?;
+                if target.matches(&meta.hash) {
+                    resolved = Some(name);
+                    break;
+                }
+            }
+            resolved.ok_or_else(|| not_found(requested))?
+        }
+        None => return Err(not_found(requested)),
+    };
+    let (tag, _) = repo.pull_tag(&name).await.map_err(|e| match e {
+        PullTagError::Get(e) => internal_error(requested, e),
+        e => e.into_response(),
+    })?;
+
+    let prefix = reference.path.clone().unwrap_or(TreePath::ROOT);
+    let mut found = false;
+    for (path, meta) in tag.walk().await.map_err(|e| internal_error(requested, e))? {
+        if !path.starts_with(&prefix) {
+            continue;
+        }
+        found = true;
+        if is_directory(&meta) {
+            continue;
+        }
+        let url = format!("/api/v{}/{cx}/_tag/{name}/tree/{path}", *API_VERSION);
+        let (url, expires) = match presign {
+            Some((signer, expires)) => (signer.sign(&url, expires), Some(expires.into())),
+            None => (url, None),
+        };
+        // NOTE: Signatures are verified against paths with the base path stripped.
+        let url = base_path::join(base, &url);
+        manifest.entries.push(ManifestEntry {
+            reference: requested.into(),
+            artifact: format!("{cx}:{name}/{path}"),
+            meta,
+            url,
+            expires,
+        });
+    }
+    if found {
+        Ok(())
+    } else {
+        Err(not_found(requested))
+    }
+}
+
+/// Returns a [Manifest] of the files references of the [ManifestRequest] in the body refer to,
+/// in order of the references and paths.
+///
+/// References to repositories, which may not be read by the requester, are not found. URLs are
+/// only pre-signed, if the server is configured with a [UrlSigner], for up to [MAX_PRESIGN]
+/// seconds.
+pub async fn manifest(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref clock): Extension<Arc<dyn Clock>>,
+    signer: Option<Extension<Arc<UrlSigner>>>,
+    base: Option<Extension<BasePath>>,
+    claims: Option<OidcClaims>,
+    BoundedBody(body): BoundedBody,
+) -> impl IntoResponse {
+    trace!(target: "app::manifest::manifest", "called");
+
+    let req: ManifestRequest = serde_json::from_slice(&body).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid manifest request: {e}"),
+        )
+            .into_response()
+    })?;
+    let references = req.references().map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid manifest request: {e:#}"),
+        )
+            .into_response()
+    })?;
+    let presign = match (req.presign, signer) {
+        (None, _) => None,
+        (Some(secs), _) if secs > MAX_PRESIGN => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("URLs may be pre-signed for at most {MAX_PRESIGN} seconds"),
+            )
+                .into_response())
+        }
+        (Some(_), None) => {
+            return Err((StatusCode::CONFLICT, "URL signing is not configured").into_response())
+        }
+        (Some(secs), Some(Extension(signer))) => {
+            Some((signer, clock.now() + Duration::from_secs(secs)))
+        }
+    };
+
+    let mut manifest = Manifest::default();
+    for (requested, reference) in req.references.iter().zip(&references) {
+        resolve(
+            store,
+            claims.as_ref(),
+            presign
+                .as_ref()
+                .map(|(signer, expires)| (signer.as_ref(), *expires)),
+            base.as_ref().map(|Extension(base)| base),
+            requested,
+            reference,
+            &mut manifest,
+        )
+        .await?;
+    }
+    json::encode(&manifest).map_err(IntoResponse::into_response)
+}
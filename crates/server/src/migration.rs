@@ -0,0 +1,402 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Live migration of all entities of another store into the store being served.
+//!
+//! Users and repositories of the source store are copied in background, each repository as a
+//! [snapshot](super::snapshots), so that all contents are verified against the digests
+//! recorded by the source as they are imported. Repositories migrated are recorded in the
+//! target store, so that an interrupted migration resumes where it left off, and progress is
+//! reported by [admin::migration](super::admin::migration).
+//!
+//! While the migration runs, reads of users and repositories, which were not migrated yet,
+//! are served from the source store, and writes to them migrate them right away, before
+//! they are handled. Once all repositories are migrated, completion is recorded in the target
+//! store and the source store is no longer read from.
+//!
+//! # Limitations
+//!
+//! The source store must not be written to during the migration. Only user records and the
+//! contents of repositories contained in snapshots are migrated, but e.g. trash, retention
+//! policies and usage counters of the source are not. Listings of users with repositories,
+//! which were not migrated yet, are incomplete until they are. In clustered deployments,
+//! entities are copied by the leader.
+
+use super::cluster::Cluster;
+use super::events::EventBus;
+use super::handle::split_api_path;
+use super::snapshots::{export_repository, import_repository, open_store};
+use super::{Clock, CreateError, GetError, MigrationProgress, Store};
+
+use drawbridge_type::{RepositoryContext, UserContext};
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context};
+use async_std::sync::Arc;
+use async_std::task::{sleep, spawn};
+use axum::http::{Method, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use cap_async_std::path::Path;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use tracing::{debug, info, trace, warn};
+
+/// Interval, in which a failed migration is retried and instances, which do not lead the
+/// cluster, reload its progress.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Configuration of a migration.
+#[derive(Clone, Debug)]
+pub struct MigrationConfig {
+    /// Path of the store entities are migrated from
+    pub source: PathBuf,
+}
+
+/// Status of a migration.
+#[derive(Clone, Debug, Serialize)]
+pub struct MigrationStatus {
+    /// Path of the store entities are migrated from
+    pub source: String,
+    /// Time the migration completed at in RFC 3339 format, if it did
+    pub completed: Option<String>,
+    /// Number of repositories migrated
+    pub migrated: u64,
+    /// Number of repositories of the source store, once they were listed
+    pub total: Option<u64>,
+    /// Number of bytes of snapshots of repositories migrated since the server started
+    pub bytes: u64,
+    /// Number of repositories, which failed to migrate
+    pub failed: u64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct State {
+    progress: MigrationProgress,
+    total: Option<u64>,
+    bytes: u64,
+    failed: u64,
+    last_error: Option<String>,
+}
+
+/// Migration of all entities of a source store into the store being served.
+#[derive(Debug)]
+pub struct Migration {
+    source_path: PathBuf,
+    source: Arc<Store>,
+    state: Mutex<State>,
+    /// Whether the migration completed, after which the source store is no longer read from
+    complete: AtomicBool,
+    clock: Arc<dyn Clock>,
+}
+
+impl Migration {
+    /// Opens the source store of `config` and resumes the migration into `store` recorded
+    /// in it, timestamping completion using `clock`.
+    pub(crate) async fn open(
+        config: MigrationConfig,
+        store: &Store,
+        clock: Arc<dyn Clock>,
+    ) -> anyhow::Result<Self> {
+        let source = open_store(Path::new(&config.source))
+            .await
+            .context("failed to open source store")?;
+        let progress = store.read_migration().await?;
+        Ok(Self {
+            source_path: config.source,
+            source: Arc::new(source),
+            complete: AtomicBool::new(progress.completed.is_some()),
+            state: Mutex::new(State {
+                progress,
+                ..Default::default()
+            }),
+            clock,
+        })
+    }
+
+    /// Returns the status of the migration.
+    pub fn status(&self) -> MigrationStatus {
+        let state = self
+            .state
+            .lock()
+            .map(|state| state.clone())
+            .unwrap_or_default();
+        MigrationStatus {
+            source: self.source_path.to_string_lossy().into(),
+            completed: state.progress.completed,
+            migrated: state.progress.repositories.len() as _,
+            total: state.total,
+            bytes: state.bytes,
+            failed: state.failed,
+            last_error: state.last_error,
+        }
+    }
+
+    /// Returns whether the migration completed.
+    pub fn is_complete(&self) -> bool {
+        self.complete.load(Ordering::Acquire)
+    }
+
+    fn update<T>(&self, f: impl FnOnce(&mut State) -> T) -> Option<T> {
+        self.state.lock().ok().map(|mut state| f(&mut state))
+    }
+
+    fn is_migrated(&self, cx: &RepositoryContext) -> bool {
+        self.update(|state| state.progress.repositories.contains(&cx.to_string()))
+            .unwrap_or_default()
+    }
+
+    /// Replaces the progress of the migration by `progress` recorded by another instance.
+    fn reload(&self, progress: MigrationProgress) {
+        let completed = progress.completed.is_some();
+        _ = self.update(|state| state.progress = progress);
+        if completed {
+            self.complete.store(true, Ordering::Release);
+        }
+    }
+
+    /// Migrates user `cx` into `store` unless it exists.
+    async fn migrate_user(&self, store: &Store, cx: &UserContext) -> anyhow::Result<()> {
+        match store.user(cx).get_meta().await {
+            Ok(_) => return Ok(()),
+            Err(GetError::NotFound) => {}
+            Err(e) => return Err(e.into()),
+        }
+        let user = self.source.user(cx);
+        let meta = user.get_meta().await?;
+        let record = user.get_content_json().await?;
+        match store.create_user(cx, meta, &record).await {
+            Ok(_) | Err(CreateError::Occupied) => Ok(()),
+            Err(e) => Err(anyhow!("failed to create user `{cx}`: {e:?}")),
+        }
+    }
+
+    /// Migrates repository `cx` along with its owner into `store`, publishing the changes on
+    /// `events`, and records it as migrated.
+    async fn migrate_repository(
+        &self,
+        store: &Store,
+        events: &EventBus,
+        cx: &RepositoryContext,
+    ) -> anyhow::Result<()> {
+        self.migrate_user(store, &cx.owner)
+            .await
+            .with_context(|| format!("failed to migrate owner of `{cx}`"))?;
+        let bytes = match export_repository(&self.source, cx).await {
+            Ok(archive) => {
+                _ = import_repository(store, events, cx, &archive)
+                    .await
+                    .map_err(|e| anyhow!("failed to import `{cx}`: {e:?}"))?;
+                archive.len() as u64
+            }
+            // NOTE: Repositories removed from the source need not be migrated.
+            Err(GetError::NotFound) => 0,
+            Err(GetError::Internal(e)) => return Err(e.context(format!("failed to export `{cx}`"))),
+        };
+        let progress = self
+            .update(|state| {
+                _ = state.progress.repositories.insert(cx.to_string());
+                state.bytes = state.bytes.saturating_add(bytes);
+                state.progress.clone()
+            })
+            .ok_or_else(|| anyhow!("migration state poisoned"))?;
+        debug!(target: "app::migration", "migrated `{cx}`");
+        store.write_migration(&progress).await
+    }
+
+    /// Migrates all users and repositories of the source store, which were not migrated yet,
+    /// into `store` and records completion, if all were migrated.
+    async fn migrate_all(&self, store: &Store, events: &EventBus) -> anyhow::Result<()> {
+        let mut repos = vec![];
+        for name in self.source.users().await? {
+            let cx = UserContext { name };
+            self.migrate_user(store, &cx)
+                .await
+                .with_context(|| format!("failed to migrate user `{cx}`"))?;
+            for name in self.source.user(&cx).repositories().await? {
+                repos.push(RepositoryContext {
+                    owner: cx.clone(),
+                    name,
+                });
+            }
+        }
+        _ = self.update(|state| state.total = Some(repos.len() as _));
+
+        let mut failed = 0;
+        for cx in repos {
+            if self.is_migrated(&cx) {
+                continue;
+            }
+            if let Err(e) = self.migrate_repository(store, events, &cx).await {
+                warn!(target: "app::migration", "failed to migrate `{cx}`: {:?}", e);
+                failed += 1;
+                _ = self.update(|state| {
+                    state.failed += 1;
+                    state.last_error = Some(format!("{e:#}"));
+                });
+            }
+        }
+        if failed > 0 {
+            return Err(anyhow!("failed to migrate {failed} repositories"));
+        }
+
+        let completed =
+            DateTime::<Utc>::from(self.clock.now()).to_rfc3339_opts(SecondsFormat::Secs, true);
+        let progress = self
+            .update(|state| {
+                state.progress.completed = Some(completed);
+                state.progress.clone()
+            })
+            .ok_or_else(|| anyhow!("migration state poisoned"))?;
+        store.write_migration(&progress).await?;
+        self.complete.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Runs the migration into `store`, publishing changes on `events`, in background until
+    /// it completes, if the instance is the leader of `cluster`, if any, retrying every
+    /// [RETRY_INTERVAL] on failure.
+    pub(crate) fn run(
+        self: &Arc<Self>,
+        store: &Arc<Store>,
+        events: &Arc<EventBus>,
+        cluster: Option<Arc<Cluster>>,
+    ) {
+        let migration = Arc::clone(self);
+        let store = Arc::clone(store);
+        let events = Arc::clone(events);
+        _ = spawn(async move {
+            while !migration.is_complete() {
+                if cluster.as_ref().is_none_or(|cluster| cluster.is_leader()) {
+                    let started = Instant::now();
+                    match migration.migrate_all(&store, &events).await {
+                        Ok(()) => info!(
+                            target: "app::migration",
+                            "completed migration from `{}` in {:?}",
+                            migration.source_path.to_string_lossy(),
+                            started.elapsed()
+                        ),
+                        Err(e) => warn!(target: "app::migration", "failed to migrate: {:?}", e),
+                    }
+                } else {
+                    match store.read_migration().await {
+                        Ok(progress) => migration.reload(progress),
+                        Err(e) => {
+                            warn!(target: "app::migration", "failed to reload migration progress: {:?}", e)
+                        }
+                    }
+                }
+                if !migration.is_complete() {
+                    sleep(RETRY_INTERVAL).await;
+                }
+            }
+        });
+    }
+
+    /// Returns whether a request for user `cx` is served from the source store, which is the
+    /// case for reads of users not migrated yet, and migrates `cx` into `store` for writes.
+    async fn route_user(
+        &self,
+        store: &Store,
+        cx: &UserContext,
+        read: bool,
+    ) -> anyhow::Result<bool> {
+        match store.user(cx).get_meta().await {
+            Ok(_) => return Ok(false),
+            Err(GetError::NotFound) => {}
+            Err(e) => return Err(e.into()),
+        }
+        match self.source.user(cx).get_meta().await {
+            Ok(_) if read => Ok(true),
+            Ok(_) => self.migrate_user(store, cx).await.map(|()| false),
+            Err(GetError::NotFound) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Returns whether a request for repository `cx` is served from the source store, which
+    /// is the case for reads of repositories not migrated yet, and migrates `cx` into `store`
+    /// publishing changes on `events` for writes.
+    async fn route_repository(
+        &self,
+        store: &Store,
+        events: &EventBus,
+        cx: &RepositoryContext,
+        read: bool,
+    ) -> anyhow::Result<bool> {
+        if self.is_migrated(cx) {
+            return Ok(false);
+        }
+        match self.source.repository(cx).get_meta().await {
+            Ok(_) if read => Ok(true),
+            Ok(_) => self
+                .migrate_repository(store, events, cx)
+                .await
+                .map(|()| false),
+            Err(GetError::NotFound) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Entity targeted by a request.
+#[derive(Debug)]
+enum Target {
+    User(UserContext),
+    Repository(RepositoryContext),
+}
+
+/// Returns the user or repository targeted by a request to `path`, if any.
+fn target(path: &str) -> Option<Target> {
+    let (_, path) = split_api_path(path)?;
+    let path = path.trim_start_matches('/');
+    let head = path.split_once("/_").map_or(path, |(head, _)| head);
+    if head.is_empty() || head.starts_with('_') {
+        return None;
+    }
+    match head.split_once('/') {
+        Some(repo) => repo.try_into().ok().map(Target::Repository),
+        None => head.parse().ok().map(Target::User),
+    }
+}
+
+/// Serves reads of users and repositories, which were not migrated yet, from the source store
+/// of a running migration and migrates them before writes are handled.
+pub(crate) async fn handle<B>(mut req: Request<B>, next: Next<B>) -> Response {
+    let migration = match req.extensions().get::<Arc<Migration>>() {
+        Some(migration) if !migration.is_complete() => Arc::clone(migration),
+        _ => return next.run(req).await,
+    };
+    let store = req.extensions().get::<Arc<Store>>().cloned();
+    let events = req.extensions().get::<Arc<EventBus>>().cloned();
+    let (target, store, events) = match (target(req.uri().path()), store, events) {
+        (Some(target), Some(store), Some(events)) => (target, store, events),
+        _ => return next.run(req).await,
+    };
+    let read = matches!(*req.method(), Method::GET | Method::HEAD);
+    let res = match target {
+        Target::User(ref cx) => migration.route_user(&store, cx, read).await,
+        Target::Repository(ref cx) => migration.route_repository(&store, &events, cx, read).await,
+    };
+    match res {
+        Ok(false) => {}
+        Ok(true) => {
+            trace!(target: "app::migration", "serve {:?} from source store", target);
+            _ = req.extensions_mut().insert(Arc::clone(&migration.source));
+        }
+        Err(e) => {
+            debug!(target: "app::migration", "failed to migrate {:?}: {:?}", target, e);
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Failed to migrate the entity from the source store",
+            )
+                .into_response();
+        }
+    }
+    next.run(req).await
+}
@@ -0,0 +1,56 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! [MessagePack] encoding of responses of high-volume internal APIs, e.g. the change feed.
+//!
+//! Responses are encoded as MessagePack instead of JSON for requests preferring
+//! `application/msgpack`. Structs are encoded as maps keyed by field name, so that documents
+//! mirror their JSON representation and decode into the same serde models.
+//!
+//! [MessagePack]: https://github.com/msgpack/msgpack/blob/master/spec.md
+
+use super::json;
+
+use drawbridge_type::digest::Algorithms;
+use drawbridge_type::{MediaType, Meta};
+
+use axum::http::{HeaderMap, StatusCode};
+use serde::Serialize;
+
+/// Media type of MessagePack data.
+pub(crate) const MSGPACK_TYPE: &str = "application/msgpack";
+
+/// Returns `true` if `Accept` headers in `headers` prefer MessagePack over JSON.
+pub(crate) fn accepts(headers: &HeaderMap) -> bool {
+    json::prefers(headers, &[MSGPACK_TYPE], MediaType::JSON.essence())
+}
+
+/// Encodes `val` as MessagePack and returns it along with its [Meta].
+pub(crate) fn encode(val: &impl Serialize) -> Result<(Meta, Vec<u8>), (StatusCode, &'static str)> {
+    let buf = rmp_serde::to_vec_named(val).map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to encode MessagePack",
+        )
+    })?;
+    let (_, hash) = Algorithms::default().read_sync(&buf[..]).map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to compute digest",
+        )
+    })?;
+    let mime = MSGPACK_TYPE.parse().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Invalid MessagePack media type",
+        )
+    })?;
+    Ok((
+        Meta {
+            hash,
+            size: buf.len() as _,
+            mime,
+        },
+        buf,
+    ))
+}
@@ -0,0 +1,157 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Uniform cursor pagination of listings.
+//!
+//! Listings are paginated if a `limit` or `cursor` query parameter is passed. Pages contain at
+//! most `limit` items, [DEFAULT_LIMIT] by default and capped at [MAX_LIMIT], and carry an
+//! [RFC 8288] `Link` header referring to the `next` and `prev` pages, if any, by opaque
+//! cursors, which clients pass back unmodified.
+//!
+//! [RFC 8288]: https://www.rfc-editor.org/rfc/rfc8288
+
+use super::links::Link;
+
+use axum::http::header::LINK;
+use axum::http::{StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use openidconnect::url::form_urlencoded;
+
+/// Number of items of a page unless a limit is requested.
+pub(crate) const DEFAULT_LIMIT: usize = 100;
+
+/// Maximum number of items of a page.
+pub(crate) const MAX_LIMIT: usize = 1000;
+
+const CURSOR_PREFIX: &str = "o:";
+
+fn encode_cursor(offset: usize) -> String {
+    base64::encode_config(format!("{CURSOR_PREFIX}{offset}"), base64::URL_SAFE_NO_PAD)
+}
+
+fn decode_cursor(cursor: &str) -> Option<usize> {
+    let buf = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD).ok()?;
+    std::str::from_utf8(&buf)
+        .ok()?
+        .strip_prefix(CURSOR_PREFIX)?
+        .parse()
+        .ok()
+}
+
+/// Returns a link to the listing at `uri` with query parameters `params` replacing those of
+/// `uri` of the same name.
+pub(crate) fn link(uri: &Uri, rel: &str, params: &[(&str, String)]) -> String {
+    let mut query = form_urlencoded::Serializer::new(String::new());
+    for (k, v) in form_urlencoded::parse(uri.query().unwrap_or("").as_bytes()) {
+        if !params.iter().any(|(name, _)| *name == k) {
+            _ = query.append_pair(&k, &v);
+        }
+    }
+    for (k, v) in params {
+        _ = query.append_pair(k, v);
+    }
+    format!(r#"<{}?{}>; rel="{rel}""#, uri.path(), query.finish())
+}
+
+/// A page of a listing requested.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Pagination {
+    offset: usize,
+    limit: usize,
+}
+
+impl Pagination {
+    /// Parses the pagination requested by `uri`, if any.
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn from_uri(uri: &Uri) -> Result<Option<Self>, Response> {
+        let mut page = None;
+        for (k, v) in form_urlencoded::parse(uri.query().unwrap_or("").as_bytes()) {
+            match k.as_ref() {
+                "cursor" => {
+                    page.get_or_insert(Self {
+                        offset: 0,
+                        limit: DEFAULT_LIMIT,
+                    })
+                    .offset = decode_cursor(&v).ok_or_else(|| {
+                        (StatusCode::BAD_REQUEST, "Invalid `cursor`").into_response()
+                    })?
+                }
+                "limit" => {
+                    page.get_or_insert(Self {
+                        offset: 0,
+                        limit: DEFAULT_LIMIT,
+                    })
+                    .limit = v
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|&n| n > 0)
+                        .map(|n| n.min(MAX_LIMIT))
+                        .ok_or_else(|| {
+                            (StatusCode::BAD_REQUEST, "Invalid `limit`").into_response()
+                        })?
+                }
+                _ => {}
+            }
+        }
+        Ok(page)
+    }
+
+    /// Parses the pagination requested by `uri` or returns the first page of `limit` items.
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn from_uri_or(uri: &Uri, limit: usize) -> Result<Self, Response> {
+        Self::from_uri(uri).map(|page| page.unwrap_or(Self { offset: 0, limit }))
+    }
+
+    /// Returns the page of `items` along with a `Link` header referring to adjacent pages of
+    /// the listing at `uri`, if any.
+    pub(crate) fn page<I, C>(self, uri: &Uri, items: I) -> (C, Option<Link>)
+    where
+        I: IntoIterator,
+        C: FromIterator<I::Item>,
+    {
+        let mut items = items.into_iter().skip(self.offset).peekable();
+        let page = items.by_ref().take(self.limit).collect();
+        let limit = ("limit", self.limit.to_string());
+        let mut links = vec![];
+        if items.peek().is_some() {
+            links.push(link(
+                uri,
+                "next",
+                &[
+                    limit.clone(),
+                    ("cursor", encode_cursor(self.offset + self.limit)),
+                ],
+            ));
+        }
+        if self.offset > 0 {
+            links.push(link(
+                uri,
+                "prev",
+                &[
+                    limit,
+                    (
+                        "cursor",
+                        encode_cursor(self.offset.saturating_sub(self.limit)),
+                    ),
+                ],
+            ));
+        }
+        let links = Some(links)
+            .filter(|links| !links.is_empty())
+            .map(|links| [(LINK, links.join(", "))]);
+        (page, links)
+    }
+}
+
+/// Returns `items` paginated as requested by `uri`, if at all.
+#[allow(clippy::result_large_err)]
+pub(crate) fn paginate<I, C>(uri: &Uri, items: I) -> Result<(C, Option<Link>), Response>
+where
+    I: IntoIterator,
+    C: FromIterator<I::Item>,
+{
+    Ok(match Pagination::from_uri(uri)? {
+        Some(page) => page.page(uri, items),
+        None => (items.into_iter().collect(), None),
+    })
+}
@@ -0,0 +1,168 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Placement policies pinning namespaces to regions, e.g. for data residency.
+//!
+//! The server and each replication [Peer] are located in a region. Repositories of a pinned
+//! namespace may only be created on servers located in one of the regions the namespace is
+//! pinned to, which applies to repositories created by users as well as to repositories pulled
+//! through [federation](super::federation) or by a [Replica](super::replica::Replica), and their
+//! tags are only pushed to peers located in those regions. Namespaces, which are not pinned,
+//! may be stored anywhere.
+//!
+//! Where data of a repository physically lives is reported by [repository].
+
+use super::replication::{Peer, Replication};
+use super::{json, OidcClaims, ScopeContext, ScopeLevel, Store};
+
+use drawbridge_type::{RepositoryContext, UserName};
+
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::ensure;
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use openidconnect::url::Url;
+use serde::Serialize;
+use tracing::{debug, trace};
+
+/// Validates region name `name`.
+pub(crate) fn validate_region(name: &str) -> anyhow::Result<()> {
+    ensure!(
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.')),
+        "invalid region name `{name}`"
+    );
+    Ok(())
+}
+
+/// Region of the server and the regions namespaces are pinned to.
+#[derive(Clone, Debug, Default)]
+pub struct Placement {
+    region: Option<String>,
+    policies: HashMap<UserName, BTreeSet<String>>,
+}
+
+impl Placement {
+    /// Sets the region the server is located in.
+    pub fn region(&mut self, name: impl Into<String>) -> anyhow::Result<()> {
+        let name = name.into();
+        validate_region(&name)?;
+        self.region = Some(name);
+        Ok(())
+    }
+
+    /// Pins `namespace` to region `name`.
+    ///
+    /// May be called multiple times to allow a namespace to be stored in several regions.
+    pub fn pin(&mut self, namespace: UserName, name: impl Into<String>) -> anyhow::Result<()> {
+        let name = name.into();
+        validate_region(&name)?;
+        _ = self.policies.entry(namespace).or_default().insert(name);
+        Ok(())
+    }
+
+    /// Returns the regions `namespace` is pinned to or [None], if it may be stored anywhere.
+    pub fn regions(&self, namespace: &UserName) -> Option<&BTreeSet<String>> {
+        self.policies.get(namespace)
+    }
+
+    /// Returns whether `namespace` may be stored in region `region`.
+    ///
+    /// Pinned namespaces may not be stored in unknown regions.
+    pub fn allows(&self, namespace: &UserName, region: Option<&str>) -> bool {
+        match (self.regions(namespace), region) {
+            (None, _) => true,
+            (Some(regions), Some(region)) => regions.contains(region),
+            (Some(_), None) => false,
+        }
+    }
+
+    /// Returns whether `namespace` may be stored by the server.
+    pub fn allows_local(&self, namespace: &UserName) -> bool {
+        self.allows(namespace, self.region.as_deref())
+    }
+
+    /// Returns an error response unless repositories of `cx` may be created on the server.
+    pub(crate) fn assert_local(&self, cx: &RepositoryContext) -> Result<(), impl IntoResponse> {
+        if self.allows_local(&cx.owner.name) {
+            return Ok(());
+        }
+        debug!(target: "app::placement", "placement policy forbids storing `{cx}` in region {:?}", self.region);
+        Err((
+            StatusCode::FORBIDDEN,
+            format!(
+                "Placement policy of namespace `{}` forbids storing repositories in this region",
+                cx.owner.name
+            ),
+        ))
+    }
+}
+
+/// Location a repository is stored at.
+#[derive(Clone, Debug, Serialize)]
+pub struct Location {
+    /// Name of the replication peer or [None] for the server itself
+    pub peer: Option<String>,
+    /// URL of the replication peer
+    pub url: Option<Url>,
+    /// Region the location is in, if known
+    pub region: Option<String>,
+    /// Whether the placement policy allows the repository to be stored at the location, tags
+    /// are not pushed to peers, which it is not allowed at
+    pub allowed: bool,
+}
+
+/// Placement of a repository.
+#[derive(Clone, Debug, Serialize)]
+pub struct RepositoryPlacement {
+    /// Regions the namespace is pinned to or [None], if it may be stored anywhere
+    pub regions: Option<BTreeSet<String>>,
+    pub locations: Vec<Location>,
+}
+
+/// Returns the placement policy of the repository and locations its data is stored at.
+pub async fn repository(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref placement): Extension<Arc<Placement>>,
+    Extension(ref replication): Extension<Arc<Replication>>,
+    claims: OidcClaims,
+    cx: RepositoryContext,
+) -> impl IntoResponse {
+    trace!(target: "app::placement::repository", "called for `{cx}`");
+
+    let user = claims
+        .assert_user(store, &cx.owner, ScopeContext::Repository, ScopeLevel::Read)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    _ = user
+        .repository(&cx.name)
+        .get_meta()
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let namespace = &cx.owner.name;
+    let local = Location {
+        peer: None,
+        url: None,
+        region: placement.region.clone(),
+        allowed: placement.allows_local(namespace),
+    };
+    let peers = replication
+        .peers(namespace)
+        .map(|(name, Peer { url, region, .. })| Location {
+            peer: Some(name.into()),
+            url: Some(url.clone()),
+            region: region.clone(),
+            allowed: placement.allows(namespace, region.as_deref()),
+        });
+    json::encode(&RepositoryPlacement {
+        regions: placement.regions(namespace).cloned(),
+        locations: [local].into_iter().chain(peers).collect(),
+    })
+    .map_err(IntoResponse::into_response)
+}
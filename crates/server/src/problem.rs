@@ -0,0 +1,97 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! [RFC 9457] problem details of error responses.
+//!
+//! In addition to the standard members, problems carry a machine-readable `code`, which
+//! distinguishes errors sharing a status code. Errors of the shared [Error] taxonomy are
+//! converted to problems carrying their code and fields, which clients decode back into
+//! [Error].
+//!
+//! [RFC 9457]: https://www.rfc-editor.org/rfc/rfc9457
+
+use drawbridge_type::{Error, ErrorCode};
+
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Media type of problem details.
+pub(crate) const PROBLEM_TYPE: &str = "application/problem+json";
+
+/// Problem details of an error response.
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Problem {
+    /// Short, human-readable summary of the problem
+    pub title: &'static str,
+    pub status: u16,
+    /// Human-readable explanation specific to this occurrence of the problem
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Machine-readable problem identifier
+    pub code: ErrorCode,
+    /// Additional members specific to the problem
+    #[serde(flatten)]
+    pub extensions: Map<String, Value>,
+}
+
+impl Problem {
+    pub(crate) fn new(status: StatusCode, code: ErrorCode, title: &'static str) -> Self {
+        Self {
+            title,
+            status: status.as_u16(),
+            detail: None,
+            code,
+            extensions: Default::default(),
+        }
+    }
+
+    pub(crate) fn detail(self, detail: impl Into<String>) -> Self {
+        Self {
+            detail: Some(detail.into()),
+            ..self
+        }
+    }
+
+    pub(crate) fn extension(mut self, name: impl Into<String>, value: impl Into<Value>) -> Self {
+        _ = self.extensions.insert(name.into(), value.into());
+        self
+    }
+}
+
+impl From<Error> for Problem {
+    fn from(e: Error) -> Self {
+        let status = StatusCode::from_u16(e.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let problem = Self::new(status, e.code(), e.title());
+        let problem = match e {
+            Error::LengthMismatch { expected, got } => {
+                problem.detail(format!("expected: {expected}, got {got}"))
+            }
+            Error::Truncated => {
+                problem.detail("The request body ended before all content was received")
+            }
+            _ => problem,
+        };
+        match serde_json::to_value(e) {
+            Ok(Value::Object(members)) => members
+                .into_iter()
+                .filter(|(name, _)| name != "code")
+                .fold(problem, |problem, (name, value)| {
+                    problem.extension(name, value)
+                }),
+            _ => problem,
+        }
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        match serde_json::to_vec(&self) {
+            Ok(body) => (status, [(CONTENT_TYPE, PROBLEM_TYPE)], body).into_response(),
+            Err(_) => (status, self.title).into_response(),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::alerts::{Alerts, IntegrityAlert};
+use super::super::{Entity, OidcClaims, Store, TrustedCertificate};
+use super::{Context, Reference, Registries, Upstream};
+
+use async_std::sync::Arc;
+use async_std::task::spawn_blocking;
+use axum::body::{Body, StreamBody};
+use axum::extract::RequestParts;
+use axum::http::{Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Returns the tag entry or a tree node of a proxied image, pulling the image from the
+/// upstream registry unless it is already cached.
+///
+/// The digest of the image manifest is returned in the `docker-content-digest` header.
+pub async fn get(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref registries): Extension<Arc<Registries>>,
+    Extension(ref alerts): Extension<Arc<Alerts>>,
+    Extension(cx): Extension<Context>,
+    cert: Option<Extension<TrustedCertificate>>,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::proxy::get", "called for `{cx}`");
+
+    if cert.is_none() {
+        _ = RequestParts::new(req).extract::<OidcClaims>().await?;
+    }
+    let registry = registries
+        .get(&cx.namespace)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Proxied registry not found").into_response())?;
+
+    let digest = match cx.reference {
+        Reference::Digest(ref digest)
+            if store
+                .proxy_tag(&cx.namespace, &cx.name, digest)
+                .is_complete()
+                .await
+                .unwrap_or(false) =>
+        {
+            digest.clone()
+        }
+        ref reference => {
+            let mut upstream = Upstream::new(registry, &cx.name);
+            let reference = reference.clone();
+            let (upstream, manifest) = spawn_blocking(move || {
+                upstream
+                    .manifest(&reference)
+                    .map(|manifest| (upstream, manifest))
+            })
+            .await
+            .map_err(IntoResponse::into_response)?;
+            let digest = manifest.digest.clone();
+            let cached = store
+                .proxy_tag(&cx.namespace, &cx.name, &digest)
+                .is_complete()
+                .await
+                .map_err(|e| {
+                    debug!(target: "app::proxy::get", "failed to check cache of `{cx}`: {:?}", e);
+                    e.into_response()
+                })?;
+            if !cached {
+                upstream
+                    .pull(manifest, store)
+                    .await
+                    .map_err(IntoResponse::into_response)?
+                    .cache(store, &cx)
+                    .await
+                    .map_err(|e| {
+                        debug!(target: "app::proxy::get", "failed to cache `{cx}`: {:?}", e);
+                        e.into_response()
+                    })?;
+            }
+            digest
+        }
+    };
+
+    let tag = store.proxy_tag(&cx.namespace, &cx.name, &digest);
+    let entity = match cx.path {
+        Some(ref path) => Entity::clone(&tag.node(path)),
+        None => Entity::clone(&tag),
+    };
+    let (meta, body) = entity.get_stream().await.map_err(|e| {
+        debug!(target: "app::proxy::get", "failed for `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let body = alerts.watch(IntegrityAlert::read(&entity, &meta.hash), body);
+    Ok::<_, Response>((
+        meta,
+        [("docker-content-digest", digest)],
+        StreamBody::new(body),
+    ))
+}
@@ -0,0 +1,652 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Pull-through proxy of upstream OCI registries.
+//!
+//! Images are addressed as `_proxy/<namespace>/<name>/_tag/<reference>`, where `namespace`
+//! identifies a configured upstream registry, `name` is the name of the repository in that
+//! registry and `reference` is either a tag or a manifest digest. Tags are resolved against
+//! the upstream registry on each request, while pulled images are verified, converted into
+//! trees and cached in the store by manifest digest.
+//!
+//! The tree of an image consists of the image manifest at `manifest.json`, the image
+//! configuration at `config.json` and the layers at `layers/<index>`.
+
+mod get;
+
+pub use get::*;
+
+use super::hash::sha256;
+use super::{CreateError, Staged, Store};
+
+use drawbridge_type::digest::{hex, Algorithm, Algorithms, ContentDigest};
+use drawbridge_type::{Meta, TagEntry, TreeDirectory, TreeEntry, TreePath};
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::Read;
+use std::iter;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, ensure, Context as _};
+use async_std::task::spawn_blocking;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use blocking::Unblock;
+use openidconnect::url::Url;
+use serde::Deserialize;
+use tracing::debug;
+
+/// Media types of image manifests accepted from upstream registries.
+const MANIFEST_TYPES: [&str; 2] = [
+    "application/vnd.oci.image.manifest.v1+json",
+    "application/vnd.docker.distribution.manifest.v2+json",
+];
+
+/// Maximum size of an image manifest.
+const MAX_MANIFEST_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Maximum size of an image configuration or layer blob.
+const MAX_BLOB_SIZE: u64 = 10 * 1024 * 1024 * 1024;
+
+/// Maximum size of an authentication token response.
+const MAX_TOKEN_SIZE: u64 = 64 * 1024;
+
+/// Upstream OCI registries, indexed by the namespace they are proxied under.
+#[derive(Clone, Debug, Default)]
+pub struct Registries(BTreeMap<String, Url>);
+
+impl Registries {
+    /// Proxies the registry at `url` under `namespace`.
+    pub fn insert(&mut self, namespace: impl Into<String>, url: Url) -> anyhow::Result<()> {
+        let namespace = namespace.into();
+        ensure!(
+            !namespace.is_empty()
+                && namespace
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.')),
+            "invalid namespace `{namespace}`"
+        );
+        ensure!(
+            matches!(url.scheme(), "http" | "https"),
+            "unsupported registry URL scheme `{}`",
+            url.scheme()
+        );
+        _ = self.0.insert(namespace, url);
+        Ok(())
+    }
+
+    /// Returns the URL of the registry proxied under `namespace`.
+    pub fn get(&self, namespace: &str) -> Option<&Url> {
+        self.0.get(namespace)
+    }
+}
+
+/// A reference to an image in an upstream registry.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Reference {
+    Tag(String),
+    /// A `sha256` manifest digest
+    Digest(String),
+}
+
+impl Display for Reference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tag(s) | Self::Digest(s) => f.write_str(s),
+        }
+    }
+}
+
+impl FromStr for Reference {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(hex) = s.strip_prefix("sha256:") {
+            ensure!(
+                hex.len() == 64 && hex.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f')),
+                "invalid sha256 digest"
+            );
+            Ok(Self::Digest(s.into()))
+        } else {
+            ensure!(!s.is_empty() && s.len() <= 128, "invalid tag length");
+            ensure!(
+                s.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-')),
+                "invalid characters in tag"
+            );
+            ensure!(
+                !s.starts_with(['.', '-']),
+                "tag must not start with `.` or `-`"
+            );
+            Ok(Self::Tag(s.into()))
+        }
+    }
+}
+
+/// Proxied image context.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Context {
+    pub namespace: String,
+    pub name: String,
+    pub reference: Reference,
+    /// Path of the requested node within the tree, if any.
+    pub path: Option<TreePath>,
+}
+
+impl Display for Context {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "_proxy/{}/{}/_tag/{}",
+            self.namespace, self.name, self.reference
+        )?;
+        if let Some(ref path) = self.path {
+            write!(f, "/tree/{path}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses `<namespace>/<name>/_tag/<reference>[/tree[/<path>]]`.
+impl FromStr for Context {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (image, tail) = s.split_once("/_tag/").context("tag reference missing")?;
+        let (namespace, name) = image.split_once('/').context("repository name missing")?;
+        ensure!(!namespace.is_empty(), "namespace missing");
+        ensure!(
+            name.split('/').all(|component| {
+                component
+                    .chars()
+                    .all(|c| matches!(c, 'a'..='z' | '0'..='9' | '.' | '_' | '-'))
+                    && component.starts_with(|c: char| c.is_ascii_alphanumeric())
+                    && component.ends_with(|c: char| c.is_ascii_alphanumeric())
+            }),
+            "invalid repository name"
+        );
+        let (reference, path) = match tail.split_once('/') {
+            None => (tail, None),
+            Some((reference, "tree")) => (reference, Some(TreePath::ROOT)),
+            Some((reference, prop)) => {
+                let path = prop
+                    .strip_prefix("tree/")
+                    .context("unknown image property")?;
+                (reference, Some(path.parse()?))
+            }
+        };
+        Ok(Self {
+            namespace: namespace.into(),
+            name: name.into(),
+            reference: reference.parse()?,
+            path,
+        })
+    }
+}
+
+/// Error returned when pulling an image from an upstream registry.
+#[derive(Debug)]
+pub enum PullError {
+    NotFound,
+    Unsupported(&'static str),
+    Upstream(anyhow::Error),
+}
+
+impl From<anyhow::Error> for PullError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Upstream(e)
+    }
+}
+
+impl IntoResponse for PullError {
+    fn into_response(self) -> axum::response::Response {
+        match self {
+            PullError::NotFound => (
+                StatusCode::NOT_FOUND,
+                "Image not found in upstream registry",
+            )
+                .into_response(),
+            PullError::Unsupported(msg) => (StatusCode::BAD_GATEWAY, msg).into_response(),
+            PullError::Upstream(e) => {
+                debug!(target: "app::proxy", "failed to pull image: {:?}", e);
+                (
+                    StatusCode::BAD_GATEWAY,
+                    "Failed to pull image from upstream registry",
+                )
+                    .into_response()
+            }
+        }
+    }
+}
+
+/// An OCI content descriptor.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    media_type: String,
+    digest: String,
+    size: u64,
+}
+
+/// An image manifest, or an image index, which is not supported.
+#[derive(Clone, Debug, Deserialize)]
+struct Manifest {
+    config: Option<Descriptor>,
+    #[serde(default)]
+    layers: Vec<Descriptor>,
+    manifests: Option<Vec<Descriptor>>,
+}
+
+/// A verified image manifest.
+#[derive(Clone, Debug)]
+pub struct ImageManifest {
+    /// Digest of the manifest in the `sha256:<hex>` form
+    pub digest: String,
+    mime: String,
+    content: Vec<u8>,
+    config: Descriptor,
+    layers: Vec<Descriptor>,
+}
+
+/// A verified image pulled from an upstream registry, the blobs of which, configuration
+/// first, are held in the staging area of the store until the image is cached.
+#[derive(Debug)]
+pub struct Image<'a> {
+    manifest: ImageManifest,
+    blobs: Vec<Staged<'a>>,
+}
+
+/// Reads at most `limit` bytes of the body of `res`.
+pub(crate) fn read_body(res: ureq::Response, limit: u64) -> anyhow::Result<Vec<u8>> {
+    let mut buf = vec![];
+    _ = res
+        .into_reader()
+        .take(limit + 1)
+        .read_to_end(&mut buf)
+        .context("failed to read response body")?;
+    ensure!(
+        buf.len() as u64 <= limit,
+        "response body exceeds {limit} bytes"
+    );
+    Ok(buf)
+}
+
+/// Parses parameters of a `WWW-Authenticate` challenge, e.g. `realm="...",service="..."`.
+fn parse_challenge(mut s: &str) -> BTreeMap<String, String> {
+    let mut params = BTreeMap::new();
+    while let Some((key, tail)) = s.split_once('=') {
+        let (val, tail) = match tail.strip_prefix('"') {
+            Some(tail) => tail.split_once('"').unwrap_or((tail, "")),
+            None => tail.split_once(',').unwrap_or((tail, "")),
+        };
+        _ = params.insert(key.trim().to_ascii_lowercase(), val.into());
+        s = tail.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+    }
+    params
+}
+
+/// Blocking client of a repository in an upstream registry.
+#[derive(Debug)]
+pub struct Upstream {
+    agent: ureq::Agent,
+    url: String,
+    token: Option<String>,
+}
+
+impl Upstream {
+    pub fn new(registry: &Url, name: &str) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .user_agent(&format!(
+                    "{}/{}",
+                    env!("CARGO_CRATE_NAME"),
+                    env!("CARGO_PKG_VERSION")
+                ))
+                .build(),
+            url: format!("{}/v2/{name}", registry.as_str().trim_end_matches('/')),
+            token: None,
+        }
+    }
+
+    fn request(&self, url: &str, accept: &str) -> ureq::Request {
+        let req = self.agent.get(url).set("Accept", accept);
+        match self.token {
+            Some(ref token) => req.set("Authorization", &format!("Bearer {token}")),
+            None => req,
+        }
+    }
+
+    /// Requests an anonymous token as specified by the `WWW-Authenticate` `challenge`.
+    fn authenticate(&self, challenge: Option<&str>) -> anyhow::Result<String> {
+        #[derive(Deserialize)]
+        struct Token {
+            token: Option<String>,
+            access_token: Option<String>,
+        }
+
+        let challenge = challenge
+            .and_then(|challenge| challenge.strip_prefix("Bearer "))
+            .context("unsupported authentication challenge")?;
+        let params = parse_challenge(challenge);
+        let mut url: Url = params
+            .get("realm")
+            .context("authentication realm missing")?
+            .parse()
+            .context("invalid authentication realm")?;
+        for key in ["service", "scope"] {
+            if let Some(val) = params.get(key) {
+                _ = url.query_pairs_mut().append_pair(key, val);
+            }
+        }
+        let res = self
+            .agent
+            .get(url.as_str())
+            .call()
+            .context("failed to request token")?;
+        let Token {
+            token,
+            access_token,
+        } = serde_json::from_slice(&read_body(res, MAX_TOKEN_SIZE)?)
+            .context("failed to decode token response")?;
+        token
+            .or(access_token)
+            .context("token missing in authentication response")
+    }
+
+    /// Fetches `path` relative to the repository, authenticating if requested by the registry.
+    fn get(&mut self, path: &str, accept: &str) -> Result<ureq::Response, PullError> {
+        let url = format!("{}/{path}", self.url);
+        match self.request(&url, accept).call() {
+            Err(ureq::Error::Status(401, res)) if self.token.is_none() => {
+                self.token = Some(self.authenticate(res.header("www-authenticate"))?);
+                self.request(&url, accept).call()
+            }
+            res => res,
+        }
+        .map_err(|e| match e {
+            ureq::Error::Status(404, _) => PullError::NotFound,
+            e => PullError::Upstream(
+                anyhow::Error::new(e).context(format!("failed to fetch `{url}`")),
+            ),
+        })
+    }
+
+    /// Fetches and verifies the image manifest referenced by `reference`.
+    pub fn manifest(&mut self, reference: &Reference) -> Result<ImageManifest, PullError> {
+        let res = self.get(
+            &format!("manifests/{reference}"),
+            &MANIFEST_TYPES.join(", "),
+        )?;
+        let mime = res.content_type().to_string();
+        let content = read_body(res, MAX_MANIFEST_SIZE)?;
+        let digest = format!("sha256:{}", sha256(&content));
+        if let Reference::Digest(ref expected) = reference {
+            if *expected != digest {
+                return Err(anyhow!(
+                    "manifest digest mismatch, expected `{expected}`, got `{digest}`"
+                )
+                .into());
+            }
+        }
+        let manifest: Manifest =
+            serde_json::from_slice(&content).context("failed to decode manifest")?;
+        match manifest {
+            Manifest {
+                manifests: Some(_), ..
+            } => Err(PullError::Unsupported(
+                "Image indexes are not supported, reference a platform-specific manifest by digest",
+            )),
+            Manifest {
+                config: Some(config),
+                layers,
+                ..
+            } if MANIFEST_TYPES.contains(&mime.as_str()) => Ok(ImageManifest {
+                digest,
+                mime,
+                content,
+                config,
+                layers,
+            }),
+            _ => Err(PullError::Unsupported("Unsupported image manifest type")),
+        }
+    }
+
+    /// Fetches the blob described by `desc` and returns a reader of at most one byte more
+    /// than its expected size, so that oversized blobs fail verification.
+    fn blob(&mut self, desc: &Descriptor) -> Result<impl 'static + Send + Read, PullError> {
+        if !desc.digest.starts_with("sha256:") {
+            return Err(PullError::Unsupported(
+                "Only sha256 blob digests are supported",
+            ));
+        }
+        if desc.size > MAX_BLOB_SIZE {
+            return Err(PullError::Unsupported(
+                "Image blob exceeds the maximum size",
+            ));
+        }
+        let res = self.get(&format!("blobs/{}", desc.digest), "*/*")?;
+        Ok(res.into_reader().take(desc.size + 1))
+    }
+
+    /// Streams the blob described by `desc` into the staging area of `store` and verifies
+    /// its size and digest.
+    async fn stage<'a>(
+        self,
+        desc: &Descriptor,
+        store: &'a Store,
+    ) -> Result<(Self, Staged<'a>), PullError> {
+        let mut upstream = self;
+        let (upstream, rdr) = {
+            let desc = desc.clone();
+            spawn_blocking(move || upstream.blob(&desc).map(|rdr| (upstream, rdr))).await?
+        };
+        let blob = store
+            .stage(Unblock::new(rdr))
+            .await
+            .with_context(|| format!("failed to fetch blob `{}`", desc.digest))?;
+        let digest = blob
+            .hash
+            .get(&Algorithm::Sha256)
+            .map(|hash| format!("sha256:{}", hex(hash)));
+        let err = if blob.size != desc.size {
+            anyhow!("blob `{}` size mismatch", desc.digest)
+        } else if digest.as_ref() != Some(&desc.digest) {
+            anyhow!("blob `{}` digest mismatch", desc.digest)
+        } else {
+            return Ok((upstream, blob));
+        };
+        blob.remove().await;
+        Err(err.into())
+    }
+
+    /// Fetches all blobs referenced by `manifest` into the staging area of `store`, verifying
+    /// them as they are written, so that blobs are never buffered in memory.
+    pub async fn pull(
+        self,
+        manifest: ImageManifest,
+        store: &Store,
+    ) -> Result<Image<'_>, PullError> {
+        let mut upstream = self;
+        let mut blobs = Vec::with_capacity(manifest.layers.len() + 1);
+        for desc in iter::once(&manifest.config).chain(&manifest.layers) {
+            match upstream.stage(desc, store).await {
+                Ok((next, blob)) => {
+                    upstream = next;
+                    blobs.push(blob);
+                }
+                Err(e) => {
+                    for blob in blobs {
+                        blob.remove().await;
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(Image { manifest, blobs })
+    }
+}
+
+/// Nodes of an image tree, as path, metadata and content.
+type Nodes<'a> = Vec<(TreePath, Meta, Content<'a>)>;
+
+/// Content of a node of an image tree.
+#[derive(Clone, Debug)]
+enum Content<'a> {
+    File(&'a [u8]),
+    Blob(&'a Staged<'a>),
+    Directory(TreeDirectory<TreeEntry>),
+}
+
+fn entry(mime: &str, buf: &[u8]) -> anyhow::Result<TreeEntry> {
+    let (size, hash) = Algorithms::default()
+        .read_sync(buf)
+        .context("failed to compute digest")?;
+    blob_entry(mime, size, hash)
+}
+
+fn blob_entry(mime: &str, size: u64, hash: ContentDigest) -> anyhow::Result<TreeEntry> {
+    let mime = mime
+        .parse()
+        .with_context(|| format!("invalid media type `{mime}`"))?;
+    Ok(TreeEntry {
+        meta: Meta { hash, size, mime },
+        annotations: Default::default(),
+        license: None,
+        provenance: None,
+        publication: None,
+        encryption: None,
+        custom: Default::default(),
+        content: (),
+    })
+}
+
+impl Image<'_> {
+    /// Converts the image into a tree, returning the tag entry and all nodes ordered
+    /// parents first.
+    fn tree(&self) -> anyhow::Result<(TreeEntry, Nodes<'_>)> {
+        fn directory(
+            entries: Vec<(&str, TreeEntry)>,
+        ) -> anyhow::Result<(TreeEntry, TreeDirectory<TreeEntry>)> {
+            let dir = entries
+                .into_iter()
+                .map(|(name, entry)| Ok((name.parse()?, entry)))
+                .collect::<anyhow::Result<TreeDirectory<_>>>()?;
+            let buf = serde_json::to_vec(&dir).context("failed to encode directory")?;
+            Ok((entry(TreeDirectory::<()>::TYPE, &buf)?, dir))
+        }
+
+        let Self { manifest, blobs } = self;
+        let (config, layers) = blobs.split_first().context("image configuration missing")?;
+        if layers.len() != manifest.layers.len() {
+            bail!("layer count mismatch")
+        }
+
+        let manifest_entry = entry(&manifest.mime, &manifest.content)?;
+        let config_entry = blob_entry(
+            &manifest.config.media_type,
+            config.size,
+            config.hash.clone(),
+        )?;
+        let layer_names: Vec<_> = (0..layers.len()).map(|i| i.to_string()).collect();
+        let layer_entries = manifest
+            .layers
+            .iter()
+            .zip(layers)
+            .map(|(desc, blob)| blob_entry(&desc.media_type, blob.size, blob.hash.clone()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let (layers_entry, layers_dir) = directory(
+            layer_names
+                .iter()
+                .map(String::as_str)
+                .zip(layer_entries.iter().cloned())
+                .collect(),
+        )?;
+        let (root, root_dir) = directory(vec![
+            ("config.json", config_entry.clone()),
+            ("layers", layers_entry.clone()),
+            ("manifest.json", manifest_entry.clone()),
+        ])?;
+
+        let mut nodes = vec![
+            (
+                TreePath::ROOT,
+                root.meta.clone(),
+                Content::Directory(root_dir),
+            ),
+            (
+                "config.json".parse()?,
+                config_entry.meta,
+                Content::Blob(config),
+            ),
+            (
+                "manifest.json".parse()?,
+                manifest_entry.meta,
+                Content::File(&manifest.content),
+            ),
+            (
+                "layers".parse()?,
+                layers_entry.meta,
+                Content::Directory(layers_dir),
+            ),
+        ];
+        for ((name, entry), blob) in layer_names.iter().zip(layer_entries).zip(layers) {
+            nodes.push((
+                format!("layers/{name}").parse()?,
+                entry.meta,
+                Content::Blob(blob),
+            ));
+        }
+        Ok((root, nodes))
+    }
+
+    /// Converts the image into a tree and caches it in `store`, removing its blobs from the
+    /// staging area.
+    pub async fn cache(
+        self,
+        store: &Store,
+        cx: &Context,
+    ) -> Result<(), CreateError<anyhow::Error>> {
+        let res = self.create(store, cx).await;
+        for blob in self.blobs {
+            blob.remove().await;
+        }
+        res
+    }
+
+    async fn create(&self, store: &Store, cx: &Context) -> Result<(), CreateError<anyhow::Error>> {
+        let (root, nodes) = self.tree().map_err(CreateError::Internal)?;
+        let entry = TagEntry::Unsigned(root);
+        let meta = serde_json::to_vec(&entry)
+            .context("failed to encode tag entry")
+            .and_then(|buf| self::entry(TreeEntry::<()>::TYPE, &buf))
+            .map_err(CreateError::Internal)?
+            .meta;
+
+        // NOTE: Entities may already exist if the same image is being pulled concurrently.
+        let digest = &self.manifest.digest;
+        let tag = match store
+            .create_proxy_tag(&cx.namespace, &cx.name, digest, meta, &entry)
+            .await
+        {
+            Ok(tag) => tag,
+            Err(CreateError::Occupied) => store.proxy_tag(&cx.namespace, &cx.name, digest),
+            Err(e) => return Err(e),
+        };
+        for (path, meta, content) in nodes {
+            let res = match content {
+                Content::File(buf) => tag.create_file_node(&path, meta, buf).await,
+                Content::Blob(blob) => {
+                    let rdr = blob.open().await.map_err(CreateError::Internal)?;
+                    tag.create_file_node(&path, meta, rdr).await
+                }
+                Content::Directory(dir) => tag.create_directory_node(&path, meta, &dir).await,
+            };
+            match res {
+                Ok(_) | Err(CreateError::Occupied) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,211 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Request rate quotas of clients and reporting of quotas.
+//!
+//! If a [RateLimit] is set, clients are identified by the IP address of their peer and may
+//! send at most the configured number of requests per window. Responses to limited clients
+//! carry `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset` headers and requests
+//! exceeding the limit are rejected with [Error::RateLimited] along with a `Retry-After`
+//! header. Clients behind a shared proxy share a quota.
+//!
+//! Clients may check the quotas applying to them ahead of time using [get] under `_quota`.
+
+use super::problem::Problem;
+use super::usage::Accounting;
+use super::{json, Clock, PeerAddr};
+
+use drawbridge_type::{Error, Quota, RateQuota, StorageQuota};
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use async_std::sync::Arc;
+use axum::http::header::{HeaderName, RETRY_AFTER};
+use axum::http::{HeaderMap, HeaderValue, Request};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use tracing::trace;
+
+/// Number of clients tracked, above which windows elapsed are dropped.
+const CAPACITY: usize = 4096;
+
+const RATELIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+const RATELIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+const RATELIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// Maximum rate of requests of a client.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    /// Maximum number of requests in a window
+    pub requests: u64,
+    /// Duration of a window
+    pub window: Duration,
+}
+
+/// Requests of a client in the current window.
+#[derive(Clone, Copy, Debug)]
+struct Window {
+    start: SystemTime,
+    used: u64,
+}
+
+/// Counters of requests of all clients.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    clock: Arc<dyn Clock>,
+    clients: Mutex<HashMap<IpAddr, Window>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            limit,
+            clock,
+            clients: Default::default(),
+        }
+    }
+
+    /// Returns the quota of a client, which sent `used` requests in the window started at
+    /// `start`, at `now`.
+    fn quota(&self, Window { start, used }: Window, now: SystemTime) -> RateQuota {
+        let reset = (start + self.limit.window)
+            .duration_since(now)
+            .unwrap_or_default();
+        RateQuota {
+            limit: self.limit.requests,
+            window: self.limit.window.as_secs(),
+            remaining: self.limit.requests.saturating_sub(used),
+            // NOTE: Round up, so that clients retrying after `reset` seconds are not limited.
+            reset: reset.as_secs() + u64::from(reset.subsec_nanos() > 0),
+        }
+    }
+
+    /// Returns the window of `ip` at `now`, starting a new one, if the last one elapsed.
+    fn window<'a>(
+        &self,
+        clients: &'a mut HashMap<IpAddr, Window>,
+        ip: IpAddr,
+        now: SystemTime,
+    ) -> &'a mut Window {
+        let window = clients.entry(ip).or_insert(Window {
+            start: now,
+            used: 0,
+        });
+        if window.start + self.limit.window <= now {
+            *window = Window {
+                start: now,
+                used: 0,
+            };
+        }
+        window
+    }
+
+    /// Counts a request of `ip` and returns the number of requests sent in the current window
+    /// along with the quota remaining.
+    fn hit(&self, ip: IpAddr) -> (u64, RateQuota) {
+        let now = self.clock.now();
+        let Ok(mut clients) = self.clients.lock() else {
+            return (
+                0,
+                self.quota(
+                    Window {
+                        start: now,
+                        used: 0,
+                    },
+                    now,
+                ),
+            );
+        };
+        if clients.len() >= CAPACITY {
+            let window = self.limit.window;
+            clients.retain(|_, Window { start, .. }| *start + window > now);
+        }
+        let window = self.window(&mut clients, ip, now);
+        window.used = window.used.saturating_add(1);
+        let window = *window;
+        (window.used, self.quota(window, now))
+    }
+
+    /// Returns the quota remaining for `ip` without counting a request.
+    pub(crate) fn status(&self, ip: IpAddr) -> RateQuota {
+        let now = self.clock.now();
+        let window = match self.clients.lock() {
+            Ok(mut clients) => *self.window(&mut clients, ip, now),
+            Err(_) => Window {
+                start: now,
+                used: 0,
+            },
+        };
+        self.quota(window, now)
+    }
+}
+
+fn insert_headers(headers: &mut HeaderMap, quota: &RateQuota) {
+    for (name, value) in [
+        (&RATELIMIT_LIMIT, quota.limit),
+        (&RATELIMIT_REMAINING, quota.remaining),
+        (&RATELIMIT_RESET, quota.reset),
+    ] {
+        _ = headers.insert(name.clone(), HeaderValue::from(value));
+    }
+}
+
+/// Counts requests of clients and rejects requests exceeding their rate quota.
+pub(crate) async fn handle<B>(req: Request<B>, next: Next<B>) -> Response {
+    let limiter = req.extensions().get::<Arc<RateLimiter>>().cloned();
+    let peer = req.extensions().get::<PeerAddr>().copied();
+    let (limiter, PeerAddr(peer)) = match (limiter, peer) {
+        (Some(limiter), Some(peer)) => (limiter, peer),
+        _ => return next.run(req).await,
+    };
+    if req.uri().path() == "/health" {
+        return next.run(req).await;
+    }
+    let (used, quota) = limiter.hit(peer.ip());
+    let mut res = if used > quota.limit {
+        trace!(target: "app::quota", "reject request of `{}` exceeding rate limit", peer.ip());
+        let mut res = Problem::from(Error::RateLimited {
+            limit: quota.limit,
+            used,
+            reset: quota.reset,
+        })
+        .detail(format!(
+            "At most {} requests may be sent in {} seconds, retry in {} seconds",
+            quota.limit, quota.window, quota.reset
+        ))
+        .into_response();
+        _ = res
+            .headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(quota.reset));
+        res
+    } else {
+        next.run(req).await
+    };
+    insert_headers(res.headers_mut(), &quota);
+    res
+}
+
+/// Returns the storage quota of the tenant and the request rate quota of the client, if set.
+pub(crate) async fn get(
+    Extension(ref accounting): Extension<Arc<Accounting>>,
+    limiter: Option<Extension<Arc<RateLimiter>>>,
+    peer: Option<Extension<PeerAddr>>,
+) -> impl IntoResponse {
+    trace!(target: "app::quota::get", "called");
+
+    let quota = Quota {
+        storage: accounting.quota().map(|limit| StorageQuota {
+            limit,
+            used: accounting.stored(),
+        }),
+        rate: limiter
+            .zip(peer)
+            .map(|(Extension(limiter), Extension(PeerAddr(peer)))| limiter.status(peer.ip())),
+    };
+    json::encode(&quota).map_err(IntoResponse::into_response)
+}
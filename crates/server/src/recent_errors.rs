@@ -0,0 +1,86 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! In-memory record of the most recent server errors, which is reported by
+//! [admin::overview](super::admin::overview).
+
+use super::Clock;
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use async_std::sync::Arc;
+use axum::http::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+
+/// Number of most recent server errors kept.
+const CAPACITY: usize = 100;
+
+/// A request, which failed with a server error.
+#[derive(Clone, Debug, Serialize)]
+pub struct ErrorRecord {
+    /// Time the response was produced at in RFC 3339 format
+    pub time: String,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+}
+
+/// Most recent server errors, newest last.
+#[derive(Debug)]
+pub struct RecentErrors {
+    records: Mutex<VecDeque<ErrorRecord>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl RecentErrors {
+    /// Constructs a new [RecentErrors], which timestamps errors using `clock`.
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            records: Default::default(),
+            clock,
+        }
+    }
+
+    /// Returns the most recent server errors, newest first.
+    pub fn records(&self) -> Vec<ErrorRecord> {
+        self.records
+            .lock()
+            .map(|records| records.iter().rev().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn record(&self, record: ErrorRecord) {
+        if let Ok(mut records) = self.records.lock() {
+            if records.len() == CAPACITY {
+                _ = records.pop_front();
+            }
+            records.push_back(record);
+        }
+    }
+}
+
+/// Records requests failing with a server error.
+pub(crate) async fn handle<B>(req: Request<B>, next: Next<B>) -> Response {
+    let errors = match req.extensions().get::<Arc<RecentErrors>>().cloned() {
+        Some(errors) => errors,
+        None => return next.run(req).await,
+    };
+    let method = req.method().to_string();
+    let path = req.uri().path().to_string();
+
+    let res = next.run(req).await;
+    if res.status().is_server_error() {
+        errors.record(ErrorRecord {
+            time: DateTime::<Utc>::from(errors.clock.now())
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+            method,
+            path,
+            status: res.status().as_u16(),
+        });
+    }
+    res
+}
@@ -0,0 +1,149 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Automatic repair of corrupted tree node contents from replication peers.
+//!
+//! Once contents of a tree node of a published tag are found not to match their digest, be it
+//! by a consistency check of the store or while serving them, and [Replication] rules assign
+//! its namespace to peers, the contents are fetched from the peers in turn, verified against
+//! the metadata of the node and moved over the corrupted contents. Each repair is published
+//! as an [Event::EntityRepaired], which is recorded in the change journal.
+//!
+//! Nodes, which none of the peers provide verified contents of, are left in place and keep
+//! failing verification. Nodes failing deferred verification of their upload are quarantined
+//! instead, see [VerificationPolicy::Deferred](super::VerificationPolicy::Deferred).
+
+use super::events::{Event, EventBus};
+use super::replication::{Client, Replication};
+use super::{tree_context, CreateError, GetError, Store};
+
+use drawbridge_type::TreeContext;
+
+use std::collections::HashSet;
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use async_std::sync::Arc;
+use camino::Utf8Path;
+use tracing::{debug, info, trace, warn};
+
+/// Repairer of corrupted tree nodes of a store.
+#[derive(Debug)]
+pub(crate) struct Repairer {
+    store: Arc<Store>,
+    events: Arc<EventBus>,
+    replication: Arc<Replication>,
+    /// Nodes being repaired
+    repairing: Mutex<HashSet<TreeContext>>,
+}
+
+impl Repairer {
+    /// Constructs a new [Repairer] of `store`, which fetches contents from peers of
+    /// `replication` and publishes repairs on `events`.
+    pub(crate) fn new(
+        store: Arc<Store>,
+        events: Arc<EventBus>,
+        replication: Arc<Replication>,
+    ) -> Self {
+        Self {
+            store,
+            events,
+            replication,
+            repairing: Default::default(),
+        }
+    }
+
+    /// Repairs contents of the entity at `path` relative to the store root, if it is a tree
+    /// node of a published tag, whose contents fail verification.
+    pub(crate) async fn repair(&self, path: impl AsRef<Utf8Path>) {
+        let path = path.as_ref();
+        let cx = match tree_context(path) {
+            Some(cx) => cx,
+            None => {
+                trace!(target: "app::repair", "`{path}` is not a tree node, skip repair");
+                return;
+            }
+        };
+        if self
+            .replication
+            .peers(&cx.tag.repository.owner.name)
+            .next()
+            .is_none()
+        {
+            return;
+        }
+        if !self
+            .repairing
+            .lock()
+            .map(|mut repairing| repairing.insert(cx.clone()))
+            .unwrap_or(false)
+        {
+            return;
+        }
+        if let Err(e) = self.repair_node(&cx).await {
+            warn!(target: "app::repair", "failed to repair `{cx}`: {:?}", e);
+        }
+        if let Ok(mut repairing) = self.repairing.lock() {
+            _ = repairing.remove(&cx);
+        }
+    }
+
+    /// Replaces contents of tree node `cx` by verified contents fetched from the first peer
+    /// providing them, unless they were repaired already.
+    async fn repair_node(&self, cx: &TreeContext) -> anyhow::Result<()> {
+        let tag = self.store.tag(&cx.tag);
+        let node = tag.node(&cx.path);
+        let meta = match node.get_meta().await {
+            Ok(meta) => meta,
+            // NOTE: The node may have been quarantined or removed in the meantime.
+            Err(GetError::NotFound) => return Ok(()),
+            Err(GetError::Internal(e)) => return Err(e),
+        };
+        match node.verify(&meta.hash).await {
+            Ok(true) => {
+                trace!(target: "app::repair", "`{cx}` passes verification, skip repair");
+                return Ok(());
+            }
+            Ok(false) | Err(GetError::NotFound) => {}
+            Err(GetError::Internal(e)) => return Err(e),
+        }
+
+        let path = format!(
+            "{}/_tag/{}/tree/{}",
+            cx.tag.repository, cx.tag.name, cx.path
+        );
+        for (name, peer) in self.replication.peers(&cx.tag.repository.owner.name) {
+            let client = Client::new(peer);
+            let path = path.clone();
+            // NOTE: One byte more than expected is read, so that longer contents are rejected.
+            let limit = meta.size.saturating_add(1);
+            let body = match client
+                .blocking(move |client| client.get(&path, limit))
+                .await
+            {
+                Ok(body) => body,
+                Err(e) => {
+                    debug!(target: "app::repair", "failed to fetch `{cx}` from `{name}`: {:?}", e);
+                    continue;
+                }
+            };
+            match tag.repair_node(&cx.path, body.as_slice()).await {
+                Ok(meta) => {
+                    info!(target: "app::repair", "repaired `{cx}` from `{name}`");
+                    self.events.publish(Event::EntityRepaired {
+                        node: cx.clone(),
+                        digest: meta.hash,
+                        size: meta.size,
+                        peer: name.into(),
+                    });
+                    return Ok(());
+                }
+                Err(CreateError::Internal(e)) => return Err(e),
+                Err(e) => {
+                    debug!(target: "app::repair", "contents of `{cx}` fetched from `{name}` failed verification: {:?}", e)
+                }
+            }
+        }
+        Err(anyhow!("no peer provided verified contents"))
+    }
+}
@@ -0,0 +1,164 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Replay protection of signed tag publications.
+//!
+//! If the server is configured with [ReplayProtection], signed tag entries verified against
+//! the signing policy of a repository must carry a [Publication] binding them to the tag they
+//! are published as, a nonce and the time they were signed at. Entries are rejected, if they
+//! are published as another tag, signed at a time differing from the one of the server by
+//! more than [ReplayProtection::skew], or carry a nonce seen before. Nonces are remembered
+//! for as long as their entries are accepted, so that a captured publish request can neither
+//! be replayed to re-point another tag nor to re-create a deleted one.
+//!
+//! Nonces are kept in the store, so that they survive restarts and are shared by instances
+//! of a [Cluster], which serialize updates of them by a lock.
+
+use super::cluster::Cluster;
+use super::Store;
+
+use drawbridge_type::{Publication, TagContext};
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, ensure, Context};
+use async_std::sync::Mutex;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use tracing::debug;
+
+/// Name of the cluster lock serializing updates of nonces.
+const NONCES_LOCK: &str = "nonces";
+
+/// Configuration of replay protection of signed tag publications.
+#[derive(Clone, Copy, Debug)]
+pub struct ReplayProtection {
+    /// Maximum difference between the time a tag entry was signed at and the time of the
+    /// server
+    pub skew: Duration,
+}
+
+impl Default for ReplayProtection {
+    fn default() -> Self {
+        Self {
+            skew: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+/// Error returned by [Nonces::check].
+#[derive(Debug)]
+pub(crate) enum CheckError {
+    /// The publication is missing, invalid or replayed
+    Invalid(anyhow::Error),
+    /// Nonces could not be read or written
+    Internal(anyhow::Error),
+}
+
+impl IntoResponse for CheckError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Invalid(e) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid publication: {e:#}"),
+            )
+                .into_response(),
+            Self::Internal(e) => {
+                debug!(target: "app::replay", "failed to check nonce: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+/// Nonces of accepted publications, which are kept in the store.
+#[derive(Debug)]
+pub struct Nonces {
+    config: ReplayProtection,
+    /// Serializes updates of nonces by this instance
+    lock: Mutex<()>,
+}
+
+impl Nonces {
+    pub(crate) fn new(config: ReplayProtection) -> Self {
+        Self {
+            config,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Returns an error, unless `publication` binds a signed entry published as tag `cx` at
+    /// `now` and its nonce was not seen before, in which case the nonce is remembered in
+    /// `store` shared with `cluster`, if any.
+    pub(crate) async fn check(
+        &self,
+        store: &Store,
+        cluster: Option<&Cluster>,
+        cx: &TagContext,
+        publication: Option<&Publication>,
+        now: SystemTime,
+    ) -> Result<(), CheckError> {
+        let (nonce, issued) = self
+            .validate(cx, publication, now)
+            .map_err(CheckError::Invalid)?;
+
+        let _guard = self.lock.lock().await;
+        let lock = match cluster {
+            Some(cluster) => Some(
+                cluster
+                    .lock(store, NONCES_LOCK)
+                    .await
+                    .map_err(CheckError::Internal)?,
+            ),
+            None => None,
+        };
+        let res = async {
+            let mut seen = store.read_nonces().await.map_err(CheckError::Internal)?;
+            seen.retain(|_, expires| SystemTime::from(*expires) >= now);
+            if seen.contains_key(nonce) {
+                return Err(CheckError::Invalid(anyhow!(
+                    "nonce `{nonce}` was used before"
+                )));
+            }
+            _ = seen.insert(nonce.into(), (issued + self.config.skew).into());
+            store
+                .write_nonces(&seen)
+                .await
+                .map_err(CheckError::Internal)
+        }
+        .await;
+        if let Some(lock) = lock {
+            lock.release().await;
+        }
+        res
+    }
+
+    /// Validates `publication` of an entry published as tag `cx` at `now` and returns its
+    /// nonce and the time it was signed at.
+    fn validate<'a>(
+        &self,
+        cx: &TagContext,
+        publication: Option<&'a Publication>,
+        now: SystemTime,
+    ) -> anyhow::Result<(&'a str, SystemTime)> {
+        let publication = publication.context("publication claims are required")?;
+        publication.validate()?;
+        ensure!(
+            publication.tag == cx.to_string(),
+            "entry is bound to tag `{}`",
+            publication.tag
+        );
+        let issued = SystemTime::from(publication.issued);
+        let skew = match now.duration_since(issued) {
+            Ok(skew) => skew,
+            Err(e) => e.duration(),
+        };
+        ensure!(
+            skew <= self.config.skew,
+            "entry was signed at {}, which differs from the time of the server by more than {} seconds",
+            publication.issued,
+            self.config.skew.as_secs()
+        );
+        Ok((&publication.nonce, issued))
+    }
+}
@@ -0,0 +1,521 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Read replicas kept up to date by tailing the change feed of a primary server.
+//!
+//! A replica subscribes to the change feed of the primary at `_admin/events`, authenticating
+//! with a client certificate issued by the CA trusted by the primary, which is valid for one
+//! of the [replica identities](super::Builder::replica_identity) of the primary, and applies
+//! each event
+//! to its store: repositories are pulled along with their owner as they are created, tags are
+//! pulled along with their tree as by [federation](super::federation), nodes uploaded to
+//! published tags are pulled individually and nodes and tags quarantined by the primary are
+//! quarantined by the replica as well. Repositories and tags deleted by the primary are
+//! removed by the replica and pulled again, once they are restored from the trash of the
+//! primary. Since all contents are pulled, they are verified by the store of the
+//! replica against the digests referenced by their parents.
+//!
+//! When the connection to the primary is lost, the replica reconnects and catches up from the
+//! last event it received. The primary only keeps recently published events, so tags, which
+//! were missed nonetheless, e.g. because the primary restarted, are pulled when first read.
+//!
+//! Writes are rejected with a redirect to the primary. Replication lag is reported by
+//! [admin::replica](super::admin::replica).
+
+use super::events::{DeleteCause, Event, EventBus, EventId};
+use super::federation::{self, Owner, PullError, Upstream};
+use super::handle::API_VERSION;
+use super::store::{Entity, User};
+use super::trash::{self, TrashPolicy};
+use super::{Clock, GetError, Placement, Store};
+
+use drawbridge_type::digest::ContentDigest;
+use drawbridge_type::{RepositoryContext, RepositoryName, TagContext, TagName, TreeContext};
+
+use std::io::{BufRead, BufReader};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Context};
+use async_std::channel::{unbounded, Sender};
+use async_std::sync::Arc;
+use async_std::task::{sleep, spawn, spawn_blocking};
+use axum::http::header::LOCATION;
+use axum::http::{Method, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use camino::Utf8Path;
+use chrono::{DateTime, SecondsFormat, Utc};
+use openidconnect::url::Url;
+use rustls::ClientConfig;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, trace, warn};
+
+/// Delay before the first reconnection attempt, which is doubled after each subsequent
+/// attempt up to [MAX_BACKOFF].
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Maximum delay between reconnection attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// Timeout of reads from the change feed, after which the connection is considered lost.
+///
+/// The primary sends a comment on idle feeds every 15 seconds.
+const FEED_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Configuration of a read replica.
+#[derive(Clone, Debug)]
+pub struct ReplicaConfig {
+    /// URL of the primary, e.g. `https://store.example.com`
+    pub primary: Url,
+    /// TLS configuration presenting a client certificate issued by the CA trusted by the
+    /// primary, which is valid for one of its replica identities, see
+    /// [TlsConfig::read_client](super::TlsConfig::read_client)
+    pub tls: Arc<ClientConfig>,
+}
+
+/// Replication status of a read replica.
+#[derive(Clone, Debug, Serialize)]
+pub struct ReplicaStatus {
+    pub primary: Url,
+    /// Whether the replica is connected to the change feed of the primary
+    pub connected: bool,
+    /// Identifier of the most recent event received from the primary
+    pub received: EventId,
+    /// Identifier of the most recent event applied
+    pub applied: EventId,
+    /// Number of events received, but not applied yet
+    pub lag_events: u64,
+    /// Number of seconds since the oldest event not applied yet was received
+    pub lag_seconds: u64,
+    /// Number of events, which failed to apply
+    pub failed: u64,
+    /// Time the most recent event was applied in RFC 3339 format
+    pub last_applied: Option<String>,
+    /// Most recent error of the connection to the primary or of applying an event
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct State {
+    connected: bool,
+    received: EventId,
+    applied: EventId,
+    pending: u64,
+    failed: u64,
+    /// Time the oldest event not applied yet was received
+    behind_since: Option<SystemTime>,
+    last_applied: Option<SystemTime>,
+    last_error: Option<String>,
+}
+
+/// Message read from the change feed.
+#[derive(Debug)]
+enum Feed {
+    Connected,
+    Event {
+        id: EventId,
+        name: String,
+        data: String,
+    },
+}
+
+/// JSON representation of an event, see [Event::to_json].
+#[derive(Debug, Deserialize)]
+struct FeedEvent {
+    repository: String,
+    tag: Option<String>,
+    path: Option<String>,
+    digest: Option<String>,
+    length: Option<u64>,
+    cause: Option<String>,
+}
+
+/// Reads the change feed at `url` from the event following `last_id` and sends the received
+/// events to `tx` until the connection is lost.
+fn read_feed(
+    agent: ureq::Agent,
+    url: &str,
+    last_id: Option<EventId>,
+    tx: Sender<Feed>,
+) -> anyhow::Result<()> {
+    let req = agent.get(url).set("Accept", "text/event-stream");
+    let req = match last_id {
+        Some(id) => req.set("Last-Event-ID", &id.to_string()),
+        None => req,
+    };
+    let res = req.call().context("failed to connect to change feed")?;
+    tx.try_send(Feed::Connected)?;
+
+    let (mut id, mut name, mut data) = (None, String::new(), String::new());
+    for line in BufReader::new(res.into_reader()).lines() {
+        let line = line.context("failed to read change feed")?;
+        if line.is_empty() {
+            if let Some(id) = id.take() {
+                tx.try_send(Feed::Event {
+                    id,
+                    name: std::mem::take(&mut name),
+                    data: std::mem::take(&mut data),
+                })?;
+            }
+            continue;
+        }
+        let (field, value) = line.split_once(':').unwrap_or((&line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "id" => id = value.parse().ok(),
+            "event" => name = value.into(),
+            "data" => data.push_str(value),
+            _ => {}
+        }
+    }
+    Err(anyhow!("change feed closed"))
+}
+
+/// Read replica of a primary server.
+#[derive(Debug)]
+pub struct Replica {
+    upstream: Upstream,
+    placement: Arc<Placement>,
+    state: Mutex<State>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Replica {
+    /// Constructs a new [Replica], which only applies events of namespaces allowed by
+    /// `placement` and timestamps applied events using `clock`.
+    pub fn new(
+        ReplicaConfig { primary, tls }: ReplicaConfig,
+        placement: Arc<Placement>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            upstream: Upstream {
+                url: primary,
+                token: None,
+                tls: Some(tls),
+            },
+            placement,
+            state: Default::default(),
+            clock,
+        }
+    }
+
+    /// Returns the replication status.
+    pub fn status(&self) -> ReplicaStatus {
+        let now = self.clock.now();
+        let state = self
+            .state
+            .lock()
+            .map(|state| state.clone())
+            .unwrap_or_default();
+        let rfc3339 = |time: SystemTime| {
+            DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true)
+        };
+        ReplicaStatus {
+            primary: self.upstream.url.clone(),
+            connected: state.connected,
+            received: state.received,
+            applied: state.applied,
+            lag_events: state.pending,
+            lag_seconds: state
+                .behind_since
+                .and_then(|since| now.duration_since(since).ok())
+                .map(|lag| lag.as_secs())
+                .unwrap_or_default(),
+            failed: state.failed,
+            last_applied: state.last_applied.map(rfc3339),
+            last_error: state.last_error,
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut State)) {
+        if let Ok(mut state) = self.state.lock() {
+            f(&mut state)
+        }
+    }
+
+    /// Tails the change feed of the primary and applies its events to `store` in background.
+    pub(crate) fn tail(self: &Arc<Self>, store: &Arc<Store>, events: &Arc<EventBus>) {
+        let replica = Arc::clone(self);
+        let store = Arc::clone(store);
+        let events = Arc::clone(events);
+        let url = format!(
+            "{}/api/v{}/_admin/events",
+            self.upstream.url.as_str().trim_end_matches('/'),
+            *API_VERSION
+        );
+        let mut agent = ureq::AgentBuilder::new()
+            .user_agent(&format!(
+                "{}/{}",
+                env!("CARGO_CRATE_NAME"),
+                env!("CARGO_PKG_VERSION")
+            ))
+            .timeout_read(FEED_TIMEOUT);
+        if let Some(ref tls) = self.upstream.tls {
+            agent = agent.tls_config(Arc::clone(tls));
+        }
+        let agent = agent.build();
+        _ = spawn(async move {
+            let mut backoff = INITIAL_BACKOFF;
+            let mut last_id = None;
+            loop {
+                let (tx, rx) = unbounded();
+                let reader = {
+                    let agent = agent.clone();
+                    let url = url.clone();
+                    spawn_blocking(move || read_feed(agent, &url, last_id, tx))
+                };
+                while let Ok(msg) = rx.recv().await {
+                    match msg {
+                        Feed::Connected => {
+                            trace!(target: "app::replica", "connected to change feed");
+                            backoff = INITIAL_BACKOFF;
+                            replica.update(|state| state.connected = true);
+                        }
+                        Feed::Event { id, name, data } => {
+                            last_id = Some(id);
+                            let now = replica.clock.now();
+                            replica.update(|state| {
+                                state.received = id;
+                                state.pending += 1;
+                                _ = state.behind_since.get_or_insert(now);
+                            });
+                            let res = replica.apply(&store, &events, &name, &data).await;
+                            let now = replica.clock.now();
+                            replica.update(|state| {
+                                state.applied = id;
+                                state.pending -= 1;
+                                if state.pending == 0 {
+                                    state.behind_since = None;
+                                }
+                                state.last_applied = Some(now);
+                                if let Err(ref e) = res {
+                                    state.failed += 1;
+                                    state.last_error =
+                                        Some(format!("failed to apply event {id}: {e:#}"));
+                                }
+                            });
+                            if let Err(e) = res {
+                                warn!(target: "app::replica", "failed to apply event {id} `{name}`: {:?}", e);
+                            }
+                        }
+                    }
+                }
+                let e = reader
+                    .await
+                    .err()
+                    .unwrap_or_else(|| anyhow!("change feed closed"));
+                debug!(target: "app::replica", "lost change feed: {:?}", e);
+                replica.update(|state| {
+                    state.connected = false;
+                    state.last_error = Some(format!("{e:#}"));
+                });
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+        });
+    }
+
+    /// Removes `entity`, repository `repository` or its tag `tag` of `user`, which was deleted
+    /// by the primary.
+    ///
+    /// The entity is not kept in the trash of the replica, since it is pulled from the primary
+    /// again, once it is restored there.
+    async fn remove(
+        &self,
+        user: &User<'_>,
+        entity: &Entity<'_, impl AsRef<Utf8Path>>,
+        repository: RepositoryName,
+        tag: Option<TagName>,
+    ) -> anyhow::Result<()> {
+        let policy = TrashPolicy {
+            retention: Duration::ZERO,
+        };
+        trash::trash(
+            user,
+            entity,
+            &policy,
+            self.clock.as_ref(),
+            repository,
+            tag,
+            self.upstream.url.as_str(),
+        )
+        .await
+        .map(|_| ())
+        .map_err(|res| anyhow!("failed to remove `{}`: {}", entity.prefix(), res.status()))
+    }
+
+    /// Applies event `name` represented by JSON `data` to `store`.
+    async fn apply(
+        &self,
+        store: &Store,
+        events: &EventBus,
+        name: &str,
+        data: &str,
+    ) -> anyhow::Result<()> {
+        let event: FeedEvent = serde_json::from_str(data).context("failed to decode event")?;
+        let repository: RepositoryContext = event
+            .repository
+            .split_once('/')
+            .context("invalid repository")?
+            .try_into()
+            .context("invalid repository")?;
+        let tag = |name: Option<String>| -> anyhow::Result<TagContext> {
+            Ok(TagContext {
+                repository: repository.clone(),
+                name: name.context("tag missing")?.parse()?,
+            })
+        };
+        let node = |tag: TagContext, path: Option<String>| -> anyhow::Result<TreeContext> {
+            Ok(TreeContext {
+                tag,
+                path: path.context("path missing")?.parse()?,
+            })
+        };
+        let digest = |digest: Option<String>| -> anyhow::Result<ContentDigest> {
+            Ok(digest.context("digest missing")?.parse()?)
+        };
+        if !self.placement.allows_local(&repository.owner.name) {
+            trace!(target: "app::replica", "placement policy forbids storing `{repository}`");
+            return Ok(());
+        }
+        trace!(target: "app::replica", "apply `{name}` of `{repository}`");
+        let res = match name {
+            "repository-created" | "repository-restored" => {
+                federation::pull_repository(
+                    store,
+                    events,
+                    &self.upstream,
+                    &repository,
+                    Owner::Fetched,
+                )
+                .await
+            }
+            "repository-deleted" => {
+                let user = store.user(&repository.owner);
+                let repo = user.repository(&repository.name);
+                match repo.get_meta().await {
+                    Ok(_) => {}
+                    Err(GetError::NotFound) => return Ok(()),
+                    Err(GetError::Internal(e)) => return Err(e),
+                }
+                let size = repo.stored_size().await?;
+                self.remove(&user, &repo, repository.name.clone(), None)
+                    .await?;
+                debug!(target: "app::replica", "removed `{repository}`");
+                events.publish(Event::RepositoryDeleted {
+                    repository,
+                    size,
+                    cause: DeleteCause::Trashed,
+                });
+                Ok(())
+            }
+            "tag-deleted" => {
+                let cx = tag(event.tag)?;
+                let user = store.user(&repository.owner);
+                let tag = user.repository(&repository.name).tag(&cx.name);
+                let meta = match tag.get_meta().await {
+                    Ok(meta) => meta,
+                    Err(GetError::NotFound) => return Ok(()),
+                    Err(GetError::Internal(e)) => return Err(e),
+                };
+                let size = tag.stored_size().await?;
+                self.remove(&user, &tag, repository.name.clone(), Some(cx.name.clone()))
+                    .await?;
+                debug!(target: "app::replica", "removed `{cx}`");
+                events.publish(Event::TagDeleted {
+                    tag: cx,
+                    digest: meta.hash,
+                    size,
+                    cause: DeleteCause::Trashed,
+                });
+                Ok(())
+            }
+            "tag-updated" | "tag-restored" => {
+                let cx = tag(event.tag)?;
+                match store.tag(&cx).get_meta().await {
+                    Err(GetError::NotFound) => {
+                        federation::pull(store, events, &self.upstream, &cx, Owner::Fetched).await
+                    }
+                    _ => Ok(()),
+                }
+            }
+            "tree-entry-uploaded" => {
+                let cx = node(tag(event.tag)?, event.path)?;
+                let digest = digest(event.digest)?;
+                federation::pull_node(
+                    store,
+                    events,
+                    &self.upstream,
+                    &cx,
+                    &digest,
+                    event.length.context("length missing")?,
+                )
+                .await
+            }
+            "entity-deleted" => {
+                let cx = node(tag(event.tag)?, event.path)?;
+                let tag = store.tag(&cx.tag);
+                let meta = match tag.node(&cx.path).get_meta().await {
+                    Ok(meta) => meta,
+                    // NOTE: Nodes of pending tags and tags missing locally are not replicated.
+                    Err(GetError::NotFound) => return Ok(()),
+                    Err(GetError::Internal(e)) => return Err(e),
+                };
+                let cause = if event.cause.as_deref() == Some("scan-rejected") {
+                    let path = store
+                        .repository(&cx.tag.repository)
+                        .quarantine_tag(&cx.tag.name)
+                        .await?;
+                    debug!(target: "app::replica", "quarantined `{}` at `{path}`", cx.tag);
+                    DeleteCause::ScanRejected
+                } else {
+                    let path = tag.quarantine_node(&cx.path).await?;
+                    debug!(target: "app::replica", "quarantined `{cx}` at `{path}`");
+                    DeleteCause::IntegrityFailure
+                };
+                events.publish(Event::EntityDeleted {
+                    node: cx,
+                    digest: meta.hash,
+                    size: meta.size,
+                    cause,
+                });
+                Ok(())
+            }
+            _ => Ok(()),
+        };
+        match res {
+            // NOTE: Entities may be removed from the primary before they are pulled.
+            Ok(()) | Err(PullError::NotFound) => Ok(()),
+            Err(PullError::Upstream(e) | PullError::Store(e)) => Err(e),
+        }
+    }
+}
+
+/// Rejects writes to a read replica with a redirect to the primary and pulls tags targeted
+/// by reads, which were missed by the replica, before the request is handled.
+pub(crate) async fn handle<B>(req: Request<B>, next: Next<B>) -> Response {
+    let replica = match req.extensions().get::<Arc<Replica>>() {
+        Some(replica) => Arc::clone(replica),
+        None => return next.run(req).await,
+    };
+    let method = req.method();
+    if !matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+        && method.as_str() != "PROPFIND"
+    {
+        let mut url = replica.upstream.url.clone();
+        url.set_path(req.uri().path());
+        url.set_query(req.uri().query());
+        trace!(target: "app::replica", "redirect {method} request to `{url}`");
+        return (
+            StatusCode::TEMPORARY_REDIRECT,
+            [(LOCATION, url.to_string())],
+        )
+            .into_response();
+    }
+    if let Some(res) = federation::pull_missing(&req, &replica.upstream, Owner::Fetched).await {
+        return res;
+    }
+    next.run(req).await
+}
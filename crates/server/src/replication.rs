@@ -0,0 +1,472 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Push replication of tags to peer servers.
+//!
+//! Tags of namespaces, which [Replication] rules assign to peers, are pushed to the peers once
+//! their tree is complete, as the change is published on the [EventBus], which acts as the
+//! change feed. Transfers are digest-based deltas: the peer is queried for each node of the
+//! tree and only nodes, which it does not store with the same digest, are uploaded, so that
+//! interrupted pushes resume where they left off. Trees are uploaded before their tag, so that
+//! the tag is published on the peer along with its complete tree.
+//!
+//! Tags are only pushed to peers allowed by the [Placement] policy of their namespace.
+//!
+//! Failed pushes are retried with exponential backoff. Status of each peer is kept in memory
+//! and reported by [admin::replication](super::admin::replication).
+//!
+//! Users owning replicated namespaces must exist on the peers and the token presented to the
+//! peers must be authorized to write to them. Missing repositories are created on the peers
+//! with the configuration of the local repository.
+
+use super::events::{Event, EventBus};
+use super::handle::API_VERSION;
+use super::placement::{validate_region, Placement};
+use super::{json, Clock, GetToWriterError, Store};
+
+use drawbridge_type::digest::ContentDigest;
+use drawbridge_type::{Meta, TagContext, UserName};
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::Read;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, ensure, Context};
+use async_std::sync::Arc;
+use async_std::task::{sleep, spawn, spawn_blocking};
+use chrono::{DateTime, SecondsFormat, Utc};
+use openidconnect::url::Url;
+use serde::Serialize;
+use tracing::{debug, trace};
+
+/// Maximum number of push attempts.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry, which is doubled after each subsequent attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// A peer server tags are pushed to.
+#[derive(Clone, Debug)]
+pub struct Peer {
+    /// URL of the peer, e.g. `https://replica.example.com`
+    pub url: Url,
+    /// Bearer token presented to the peer, if any
+    pub token: Option<String>,
+    /// Region the peer is located in, if known, see [Placement]
+    pub region: Option<String>,
+}
+
+/// Peers and rules assigning namespaces to them.
+#[derive(Clone, Debug, Default)]
+pub struct Replication {
+    peers: BTreeMap<String, Peer>,
+    rules: HashMap<UserName, BTreeSet<String>>,
+}
+
+impl Replication {
+    /// Adds peer `name`.
+    pub fn peer(&mut self, name: impl Into<String>, peer: Peer) -> anyhow::Result<()> {
+        let name = name.into();
+        ensure!(
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.')),
+            "invalid peer name `{name}`"
+        );
+        ensure!(
+            matches!(peer.url.scheme(), "http" | "https"),
+            "unsupported peer URL scheme `{}`",
+            peer.url.scheme()
+        );
+        if let Some(ref region) = peer.region {
+            validate_region(region)?;
+        }
+        ensure!(
+            !self.peers.contains_key(&name),
+            "peer `{name}` is already added"
+        );
+        _ = self.peers.insert(name, peer);
+        Ok(())
+    }
+
+    /// Replicates tags of `namespace` to peer `name`, which must have been added.
+    pub fn rule(&mut self, namespace: UserName, name: &str) -> anyhow::Result<()> {
+        ensure!(self.peers.contains_key(name), "unknown peer `{name}`");
+        _ = self.rules.entry(namespace).or_default().insert(name.into());
+        Ok(())
+    }
+
+    /// Returns the peers tags of `namespace` are replicated to.
+    pub(crate) fn peers<'a>(
+        &'a self,
+        namespace: &UserName,
+    ) -> impl Iterator<Item = (&'a str, &'a Peer)> {
+        self.rules
+            .get(namespace)
+            .into_iter()
+            .flatten()
+            .filter_map(|name| self.peers.get_key_value(name))
+            .map(|(name, peer)| (name.as_str(), peer))
+    }
+}
+
+/// Replication status of a peer.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PeerStatus {
+    /// Number of tags being pushed
+    pub pending: u64,
+    /// Number of tags pushed
+    pub replicated: u64,
+    /// Number of tags, which could not be pushed
+    pub failed: u64,
+    /// Number of pushes skipped, since the placement policy of the namespace forbids storing
+    /// its tags on the peer
+    pub blocked: u64,
+    /// Number of tree nodes uploaded
+    pub nodes_uploaded: u64,
+    /// Number of tree nodes skipped, since the peer already stored them
+    pub nodes_skipped: u64,
+    /// Number of bytes uploaded
+    pub bytes_uploaded: u64,
+    /// Tag pushed most recently
+    pub last_replicated: Option<String>,
+    /// Time of the most recent successful push in RFC 3339 format
+    pub last_success: Option<String>,
+    /// Error of the most recent failed push
+    pub last_error: Option<String>,
+}
+
+/// Amounts transferred by a push.
+#[derive(Clone, Copy, Debug, Default)]
+struct Transfer {
+    uploaded: u64,
+    skipped: u64,
+    bytes: u64,
+}
+
+/// Blocking client of the API of a peer.
+#[derive(Clone, Debug)]
+pub(crate) struct Client {
+    agent: ureq::Agent,
+    url: String,
+    token: Option<String>,
+}
+
+impl Client {
+    pub(crate) fn new(Peer { url, token, .. }: &Peer) -> Self {
+        Self {
+            agent: ureq::AgentBuilder::new()
+                .user_agent(&format!(
+                    "{}/{}",
+                    env!("CARGO_CRATE_NAME"),
+                    env!("CARGO_PKG_VERSION")
+                ))
+                .build(),
+            url: format!(
+                "{}/api/v{}",
+                url.as_str().trim_end_matches('/'),
+                *API_VERSION
+            ),
+            token: token.clone(),
+        }
+    }
+
+    fn request(&self, method: &str, path: &str) -> ureq::Request {
+        let req = self.agent.request(method, &format!("{}/{path}", self.url));
+        match self.token {
+            Some(ref token) => req.set("Authorization", &format!("Bearer {token}")),
+            None => req,
+        }
+    }
+
+    /// Returns the digest of the entity at `path`, if the peer stores it.
+    fn head(&self, path: &str) -> anyhow::Result<Option<ContentDigest>> {
+        match self.request("HEAD", path).call() {
+            Ok(res) => res
+                .header("Content-Digest")
+                .context("`Content-Digest` header missing")?
+                .parse()
+                .map(Some)
+                .context("failed to parse `Content-Digest` header"),
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(e) => Err(anyhow::Error::new(e).context(format!("failed to query `{path}`"))),
+        }
+    }
+
+    /// Returns at most `limit` bytes of contents of the entity at `path`.
+    pub(crate) fn get(&self, path: &str, limit: u64) -> anyhow::Result<Vec<u8>> {
+        let res = self
+            .request("GET", path)
+            .call()
+            .with_context(|| format!("failed to fetch `{path}`"))?;
+        let mut body = vec![];
+        _ = res
+            .into_reader()
+            .take(limit)
+            .read_to_end(&mut body)
+            .with_context(|| format!("failed to read `{path}`"))?;
+        Ok(body)
+    }
+
+    /// Uploads `body` described by `meta` to `path`.
+    fn put(&self, path: &str, meta: &Meta, body: &[u8]) -> anyhow::Result<()> {
+        _ = self
+            .request("PUT", path)
+            .set("Content-Digest", &meta.hash.to_string())
+            .set("Content-Type", meta.mime.as_ref())
+            .send_bytes(body)
+            .with_context(|| format!("failed to upload `{path}`"))?;
+        Ok(())
+    }
+
+    /// Runs `f` with the client without blocking the executor.
+    pub(crate) async fn blocking<T: 'static + Send>(
+        &self,
+        f: impl 'static + Send + FnOnce(&Self) -> anyhow::Result<T>,
+    ) -> anyhow::Result<T> {
+        let client = self.clone();
+        spawn_blocking(move || f(&client)).await
+    }
+}
+
+fn get_to_writer_error(e: GetToWriterError<anyhow::Error>) -> anyhow::Error {
+    match e {
+        GetToWriterError::IO(e) => e.into(),
+        GetToWriterError::Get(e) => e.into(),
+    }
+}
+
+/// Pushes tag `cx` along with the nodes of its tree missing on the peer, creating its
+/// repository on the peer if it does not exist.
+async fn push(store: &Store, client: &Client, cx: &TagContext) -> anyhow::Result<Transfer> {
+    let repo = store.repository(&cx.repository);
+    let tag = repo.tag(&cx.name);
+    let mut buf = vec![];
+    let meta = tag
+        .get_to_writer(&mut buf)
+        .await
+        .map_err(get_to_writer_error)
+        .context("failed to read tag entry")?;
+
+    let tag_path = format!("{}/_tag/{}", cx.repository, cx.name);
+    let digest = client
+        .blocking({
+            let path = tag_path.clone();
+            move |client| client.head(&path)
+        })
+        .await?;
+    match digest {
+        Some(digest) if digest == meta.hash => return Ok(Transfer::default()),
+        Some(_) => bail!("tag `{cx}` on peer does not match"),
+        None => {}
+    }
+
+    let repo_path = cx.repository.to_string();
+    let exists = client
+        .blocking({
+            let path = repo_path.clone();
+            move |client| client.head(&path)
+        })
+        .await?
+        .is_some();
+    if !exists {
+        let conf = repo.get_json().await?;
+        let (meta, body) = json::encode(&conf).map_err(|(_, e)| anyhow!(e))?;
+        client
+            .blocking(move |client| client.put(&repo_path, &meta, &body))
+            .await
+            .context("failed to create repository")?;
+    }
+
+    let mut transfer = Transfer::default();
+    // NOTE: Nodes are ordered by path, so that directories are uploaded before their entries.
+    for (path, meta) in tag.walk().await? {
+        let node_path = format!("{tag_path}/tree/{path}");
+        let digest = client
+            .blocking({
+                let path = node_path.clone();
+                move |client| client.head(&path)
+            })
+            .await?;
+        if digest.as_ref() == Some(&meta.hash) {
+            transfer.skipped += 1;
+            continue;
+        }
+        let mut body = vec![];
+        _ = tag
+            .node(&path)
+            .get_to_writer(&mut body)
+            .await
+            .map_err(get_to_writer_error)
+            .with_context(|| format!("failed to read `{path}`"))?;
+        transfer.uploaded += 1;
+        transfer.bytes += body.len() as u64;
+        client
+            .blocking(move |client| client.put(&node_path, &meta, &body))
+            .await?;
+    }
+    transfer.bytes += buf.len() as u64;
+    client
+        .blocking(move |client| client.put(&tag_path, &meta, &buf))
+        .await?;
+    Ok(transfer)
+}
+
+/// Pusher of tags to peers, which keeps their status.
+#[derive(Debug)]
+pub struct Replicator {
+    replication: Arc<Replication>,
+    placement: Arc<Placement>,
+    status: Mutex<BTreeMap<String, PeerStatus>>,
+    /// Tags being pushed to peers
+    pushing: Mutex<HashSet<(String, TagContext)>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Replicator {
+    /// Constructs a new [Replicator], which only pushes tags to peers allowed by `placement`
+    /// and timestamps pushes using `clock`.
+    pub fn new(
+        replication: Arc<Replication>,
+        placement: Arc<Placement>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        let status = replication
+            .peers
+            .keys()
+            .map(|name| (name.clone(), Default::default()))
+            .collect();
+        Self {
+            replication,
+            placement,
+            status: Mutex::new(status),
+            pushing: Default::default(),
+            clock,
+        }
+    }
+
+    /// Returns status of all peers.
+    pub fn status(&self) -> BTreeMap<String, PeerStatus> {
+        self.status
+            .lock()
+            .map(|status| status.clone())
+            .unwrap_or_default()
+    }
+
+    fn update(&self, name: &str, f: impl FnOnce(&mut PeerStatus)) {
+        if let Ok(mut status) = self.status.lock() {
+            f(status.entry(name.into()).or_default())
+        }
+    }
+
+    /// Pushes tags changed as published on `events` in background.
+    pub(crate) fn subscribe(self: &Arc<Self>, store: &Arc<Store>, events: &EventBus) {
+        if self.replication.rules.is_empty() {
+            return;
+        }
+        let replicator = Arc::clone(self);
+        let store = Arc::clone(store);
+        let events = events.subscribe();
+        _ = spawn(async move {
+            while let Ok((_, event)) = events.recv().await {
+                match event {
+                    Event::TagUpdated { tag: cx, .. } => replicator.schedule(&store, cx).await,
+                    // NOTE: Nodes uploaded to pending tags are pushed once the tag is published.
+                    Event::TreeEntryUploaded { node: cx, .. }
+                        if store.tag(&cx.tag).get_meta().await.is_ok() =>
+                    {
+                        replicator.schedule(&store, cx.tag).await
+                    }
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    /// Pushes tag `cx` to peers of its namespace in background, if its tree is complete.
+    async fn schedule(self: &Arc<Self>, store: &Arc<Store>, cx: TagContext) {
+        let namespace = &cx.repository.owner.name;
+        let peers: Vec<_> = self
+            .replication
+            .peers(namespace)
+            .filter(|(name, peer)| {
+                if self.placement.allows(namespace, peer.region.as_deref()) {
+                    return true;
+                }
+                trace!(target: "app::replication", "placement policy forbids pushing `{cx}` to `{name}`");
+                self.update(name, |status| status.blocked += 1);
+                false
+            })
+            .map(|(name, peer)| (name.to_string(), peer.clone()))
+            .collect();
+        if peers.is_empty() {
+            return;
+        }
+        match store.tag(&cx).is_complete().await {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => {
+                debug!(target: "app::replication", "failed to check completeness of `{cx}`: {:?}", e);
+                return;
+            }
+        }
+        for (name, peer) in peers {
+            let key = (name.clone(), cx.clone());
+            if !self
+                .pushing
+                .lock()
+                .map(|mut pushing| pushing.insert(key))
+                .unwrap_or(false)
+            {
+                continue;
+            }
+            let replicator = Arc::clone(self);
+            let store = Arc::clone(store);
+            let cx = cx.clone();
+            _ = spawn(async move { replicator.replicate(&store, name, peer, cx).await });
+        }
+    }
+
+    /// Pushes tag `cx` to peer `name`, retrying failed pushes.
+    async fn replicate(&self, store: &Store, name: String, peer: Peer, cx: TagContext) {
+        self.update(&name, |status| status.pending += 1);
+        let client = Client::new(&peer);
+        let mut backoff = INITIAL_BACKOFF;
+        for attempt in 1..=MAX_ATTEMPTS {
+            trace!(target: "app::replication", "push `{cx}` to `{name}`, attempt {attempt}");
+            match push(store, &client, &cx).await {
+                Ok(transfer) => {
+                    let time = DateTime::<Utc>::from(self.clock.now())
+                        .to_rfc3339_opts(SecondsFormat::Secs, true);
+                    self.update(&name, |status| {
+                        status.replicated += 1;
+                        status.nodes_uploaded += transfer.uploaded;
+                        status.nodes_skipped += transfer.skipped;
+                        status.bytes_uploaded += transfer.bytes;
+                        status.last_replicated = Some(cx.to_string());
+                        status.last_success = Some(time);
+                    });
+                    break;
+                }
+                Err(e) => {
+                    debug!(target: "app::replication", "failed to push `{cx}` to `{name}`: {:?}", e);
+                    if attempt < MAX_ATTEMPTS {
+                        sleep(backoff).await;
+                        backoff *= 2;
+                    } else {
+                        self.update(&name, |status| {
+                            status.failed += 1;
+                            status.last_error = Some(format!("failed to push `{cx}`: {e:#}"));
+                        });
+                    }
+                }
+            }
+        }
+        self.update(&name, |status| status.pending -= 1);
+        if let Ok(mut pushing) = self.pushing.lock() {
+            _ = pushing.remove(&(name, cx));
+        }
+    }
+}
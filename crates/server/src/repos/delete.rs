@@ -0,0 +1,84 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::admission::{Action, Admission, AdmissionInput};
+use super::super::events::{DeleteCause, Event, EventBus};
+use super::super::trash::{self, TrashPolicy};
+use super::super::{Clock, OidcClaims, ScopeContext, ScopeLevel, Store};
+use crate::conditional;
+use crate::json;
+
+use drawbridge_type::RepositoryContext;
+
+use async_std::sync::Arc;
+use axum::http::HeaderMap;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Deletes the repository along with all of its tags by moving it into the trash of its owner
+/// and returns the trashed entity, which may be restored until it expires.
+///
+/// Deletion is subject to admission control like creation of the repository.
+#[allow(clippy::too_many_arguments)]
+pub async fn delete(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref events): Extension<Arc<EventBus>>,
+    Extension(ref policy): Extension<Arc<TrashPolicy>>,
+    Extension(ref clock): Extension<Arc<dyn Clock>>,
+    Extension(ref admission): Extension<Arc<Admission>>,
+    claims: OidcClaims,
+    cx: RepositoryContext,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    trace!(target: "app::repos::delete", "called for `{cx}`");
+
+    let user = claims
+        .assert_user(
+            store,
+            &cx.owner,
+            ScopeContext::Repository,
+            ScopeLevel::Write,
+        )
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let repo = user.repository(&cx.name);
+    let meta = repo.get_meta().await.map_err(|e| {
+        debug!(target: "app::repos::delete", "failed to get repository `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    conditional::assert_unmodified_since(&headers, &repo).await?;
+    admission
+        .assert_admitted(AdmissionInput {
+            action: Action::Delete,
+            identity: claims.subject().into(),
+            namespace: cx.to_string(),
+            public: None,
+            tag: None,
+            kind: None,
+            digests: meta.hash,
+            annotations: Default::default(),
+        })
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let size = repo.stored_size().await.map_err(|e| {
+        debug!(target: "app::repos::delete", "failed to get size of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let trashed = trash::trash(
+        &user,
+        &repo,
+        policy,
+        clock.as_ref(),
+        cx.name.clone(),
+        None,
+        claims.subject(),
+    )
+    .await?;
+    events.publish(Event::RepositoryDeleted {
+        repository: cx,
+        size,
+        cause: DeleteCause::Trashed,
+    });
+    json::encode(&trashed).map_err(IntoResponse::into_response)
+}
@@ -1,13 +1,16 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use super::super::{GetToWriterError, OidcClaims, ScopeContext, ScopeLevel, Store};
+use crate::links;
 
 use drawbridge_type::RepositoryContext;
 
 use async_std::sync::Arc;
+use axum::headers::LastModified;
 use axum::response::IntoResponse;
-use axum::Extension;
+use axum::{Extension, TypedHeader};
+use futures::{try_join, TryFutureExt};
 use tracing::{debug, trace};
 
 pub async fn get(
@@ -25,12 +28,21 @@ pub async fn get(
     // TODO: Stream body
     // https://github.com/profianinc/drawbridge/issues/56
     let mut body = vec![];
-    user.repository(&cx.name)
-        .get_to_writer(&mut body)
-        .await
-        .map_err(|e| {
-            debug!(target: "app::repos::get", "failed for `{cx}`: {:?}", e);
-            e.into_response()
-        })
-        .map(|meta| (meta, body))
+    let repo = user.repository(&cx.name);
+    try_join!(
+        repo.get_to_writer(&mut body),
+        repo.get_modified().map_err(GetToWriterError::Get)
+    )
+    .map_err(|e| {
+        debug!(target: "app::repos::get", "failed for `{cx}`: {:?}", e);
+        e.into_response()
+    })
+    .map(|(meta, modified)| {
+        (
+            meta,
+            links::repository(&cx),
+            TypedHeader(LastModified::from(modified)),
+            body,
+        )
+    })
 }
@@ -2,12 +2,15 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 
 use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use crate::links;
 
 use drawbridge_type::RepositoryContext;
 
 use async_std::sync::Arc;
+use axum::headers::LastModified;
 use axum::response::IntoResponse;
-use axum::Extension;
+use axum::{Extension, TypedHeader};
+use futures::try_join;
 use tracing::{debug, trace};
 
 pub async fn head(
@@ -17,16 +20,22 @@ pub async fn head(
 ) -> impl IntoResponse {
     trace!(target: "app::trees::head", "called for `{cx}`");
 
-    claims
+    let repo = claims
         .assert_user(store, &cx.owner, ScopeContext::Repository, ScopeLevel::Read)
         .await
         .map_err(IntoResponse::into_response)?
-        .repository(&cx.name)
-        .get_meta()
-        .await
+        .repository(&cx.name);
+    try_join!(repo.get_meta(), repo.get_modified())
         .map_err(|e| {
             debug!(target: "app::repos::head", "failed for `{cx}`: {:?}", e);
             e.into_response()
         })
-        .map(|meta| (meta, ()))
+        .map(|(meta, modified)| {
+            (
+                meta,
+                links::repository(&cx),
+                TypedHeader(LastModified::from(modified)),
+                (),
+            )
+        })
 }
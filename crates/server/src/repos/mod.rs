@@ -1,10 +1,12 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
+mod delete;
 mod get;
 mod head;
 mod put;
 
+pub use delete::*;
 pub use get::*;
 pub use head::*;
 pub use put::*;
@@ -1,26 +1,41 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use super::super::admission::{Action, Admission, AdmissionInput};
+use super::super::events::{Event, EventBus};
+use super::super::scan::Scanners;
+use super::super::{
+    Clock, CreateError, OidcClaims, Placement, ScopeContext, ScopeLevel, SignatureKeys, Store,
+};
+use crate::conditional;
+use crate::json::BoundedJson;
 
 use drawbridge_type::{Meta, RepositoryConfig, RepositoryContext};
 
 use async_std::sync::Arc;
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::IntoResponse;
-use axum::{Extension, Json};
+use axum::Extension;
 use tracing::{debug, trace};
 
+#[allow(clippy::too_many_arguments)]
 pub async fn put(
     Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref events): Extension<Arc<EventBus>>,
+    Extension(ref placement): Extension<Arc<Placement>>,
+    Extension(ref scanners): Extension<Arc<Scanners>>,
+    Extension(ref admission): Extension<Arc<Admission>>,
+    Extension(ref keys): Extension<Arc<SignatureKeys>>,
+    Extension(ref clock): Extension<Arc<dyn Clock>>,
     claims: OidcClaims,
     cx: RepositoryContext,
     meta: Meta,
-    Json(config): Json<RepositoryConfig>,
+    headers: HeaderMap,
+    BoundedJson(config): BoundedJson<RepositoryConfig>,
 ) -> impl IntoResponse {
     trace!(target: "app::trees::put", "called for `{cx}`");
 
-    claims
+    let user = claims
         .assert_user(
             store,
             &cx.owner,
@@ -28,12 +43,68 @@ pub async fn put(
             ScopeLevel::Write,
         )
         .await
-        .map_err(IntoResponse::into_response)?
-        .create_repository(&cx.name, meta, &config)
-        .await
-        .map_err(|e| {
-            debug!(target: "app::repos::put", "failed for `{cx}`: {:?}", e);
-            e.into_response()
+        .map_err(IntoResponse::into_response)?;
+    placement
+        .assert_local(&cx)
+        .map_err(IntoResponse::into_response)?;
+    if let Some(name) = config
+        .scanning
+        .iter()
+        .flat_map(|scanning| &scanning.scanners)
+        .find(|name| !scanners.contains(name))
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Unknown content scanner `{name}`"),
+        )
+            .into_response());
+    }
+    if let Some(name) = config
+        .signing
+        .iter()
+        .flat_map(|signing| &signing.keys)
+        .find(|name| !keys.contains(name))
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Unknown signature key `{name}`"),
+        )
+            .into_response());
+    }
+    admission
+        .assert_admitted(AdmissionInput {
+            action: Action::CreateRepository,
+            identity: claims.subject().into(),
+            namespace: cx.to_string(),
+            public: Some(config.public),
+            tag: None,
+            kind: None,
+            digests: meta.hash.clone(),
+            annotations: Default::default(),
         })
-        .map(|_| StatusCode::CREATED)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    conditional::assert_unmodified_since(&headers, &user.repository(&cx.name)).await?;
+    let hash = meta.hash.clone();
+    match user.create_repository(&cx.name, meta, &config).await {
+        Ok(repo) => {
+            if let Err(e) = repo.touch(clock.now()).await {
+                debug!(target: "app::repos::put", "failed to record timestamps of `{cx}`: {:?}", e);
+            }
+            events.publish(Event::RepositoryCreated {
+                repository: cx.clone(),
+            });
+            Ok(StatusCode::CREATED)
+        }
+        Err(CreateError::Occupied) => user
+            .repository(&cx.name)
+            .resolve_occupied(&hash)
+            .await
+            .map(|()| StatusCode::OK),
+        Err(e) => Err(e),
+    }
+    .map_err(|e| {
+        debug!(target: "app::repos::put", "failed for `{cx}`: {:?}", e);
+        e.into_response()
+    })
 }
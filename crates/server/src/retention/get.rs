@@ -0,0 +1,31 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use crate::json;
+
+use drawbridge_type::UserContext;
+
+use async_std::sync::Arc;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Returns the storage retention policy of the user.
+pub async fn get(
+    Extension(ref store): Extension<Arc<Store>>,
+    claims: OidcClaims,
+    cx: UserContext,
+) -> impl IntoResponse {
+    trace!(target: "app::retention::get", "called for `{cx}`");
+
+    let user = claims
+        .assert_user(store, &cx, ScopeContext::User, ScopeLevel::Read)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let policy = user.get_retention_policy().await.map_err(|e| {
+        debug!(target: "app::retention::get", "failed to get retention policy of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    json::encode(&policy).map_err(IntoResponse::into_response)
+}
@@ -0,0 +1,216 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Storage retention policies of owners.
+//!
+//! Owners may set a [RetentionPolicy] using [put] under `_retention`, which ages out storage
+//! no longer referenced by any tag of their repositories: trees uploaded to tags, which were
+//! never published, whether complete or not, and repositories and tags in their trash. Due
+//! policies are run in background, by the leader only if the store is shared by a cluster,
+//! and [report] under `_retention/report` returns the storage, which a run would reclaim
+//! now, along with the one reclaimed by the last run.
+//!
+//! [RetentionPolicy]: drawbridge_type::RetentionPolicy
+
+mod get;
+mod put;
+mod report;
+
+pub use get::*;
+pub use put::*;
+pub use report::*;
+
+use super::cluster::Cluster;
+use super::{Clock, GetError, Store};
+
+use drawbridge_type::{
+    Reclaimed, RepositoryContext, RetentionPolicy, RetentionRun, RetentionStats, TagContext,
+    UserContext,
+};
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use async_std::sync::Arc;
+use async_std::task::{sleep, spawn};
+use tracing::{debug, warn};
+
+/// Interval, in which policies are checked for being due.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Returns the storage of user `cx` aged out by `policy` at `now` and removes it, if `apply`
+/// is set, locking each tag removed if `store` is shared with other instances of `cluster`.
+pub(crate) async fn run(
+    store: &Store,
+    cluster: Option<&Cluster>,
+    cx: &UserContext,
+    policy: &RetentionPolicy,
+    now: SystemTime,
+    apply: bool,
+) -> anyhow::Result<RetentionStats> {
+    let user = store.user(cx);
+    let mut stats = RetentionStats::default();
+    if policy.untagged.is_some() || policy.uploads.is_some() {
+        let names = user
+            .repositories()
+            .await
+            .map_err(anyhow::Error::from)
+            .context("failed to list repositories")?;
+        for name in names {
+            let repo = user.repository(&name);
+            let tags = repo
+                .pending_tags()
+                .await
+                .map_err(anyhow::Error::from)
+                .with_context(|| format!("failed to list pending tags of `{cx}/{name}`"))?;
+            for tag in tags {
+                let cx = TagContext {
+                    repository: RepositoryContext {
+                        owner: cx.clone(),
+                        name: name.clone(),
+                    },
+                    name: tag,
+                };
+                let pending = repo.pending_tag(&cx.name);
+                let last = match pending.get_last_upload().await {
+                    Ok(last) => last,
+                    // NOTE: The tag may have been published in the meantime.
+                    Err(GetError::NotFound) => continue,
+                    Err(GetError::Internal(e)) => {
+                        return Err(e.context(format!("failed to read uploads to `{cx}`")))
+                    }
+                };
+                let complete = pending
+                    .is_complete()
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .with_context(|| format!("failed to read tree of `{cx}`"))?;
+                let (max_age, reclaimed) = if complete {
+                    (policy.untagged, &mut stats.untagged)
+                } else {
+                    (policy.uploads, &mut stats.uploads)
+                };
+                if !RetentionPolicy::ages_out(max_age, now.duration_since(last).unwrap_or_default())
+                {
+                    continue;
+                }
+                let bytes = pending
+                    .disk_usage()
+                    .await
+                    .with_context(|| format!("failed to compute size of `{cx}`"))?;
+                if apply {
+                    // NOTE: Other instances of the cluster may publish the tag concurrently.
+                    let lock = match cluster {
+                        Some(cluster) => Some(cluster.lock_tag(store, &cx).await?),
+                        None => None,
+                    };
+                    let res = repo.remove_pending_tag(&cx.name).await;
+                    if let Some(lock) = lock {
+                        lock.release().await;
+                    }
+                    res.with_context(|| format!("failed to remove pending tag `{cx}`"))?;
+                }
+                *reclaimed += Reclaimed { entities: 1, bytes };
+            }
+        }
+    }
+    if policy.trash.is_some() {
+        let ids = user
+            .trash_ids()
+            .await
+            .map_err(anyhow::Error::from)
+            .context("failed to list trash")?;
+        for id in ids {
+            let trashed = match user.get_trashed(&id).await {
+                Ok(trashed) => trashed,
+                Err(GetError::NotFound) => continue,
+                Err(GetError::Internal(e)) => {
+                    return Err(e.context(format!("failed to read trashed entity `{id}`")))
+                }
+            };
+            if !RetentionPolicy::ages_out(policy.trash, trashed.deleted.age(now)) {
+                continue;
+            }
+            let bytes = user
+                .get_trashed_size(&id)
+                .await
+                .with_context(|| format!("failed to compute size of trashed entity `{id}`"))?;
+            if apply {
+                user.purge(&id)
+                    .await
+                    .with_context(|| format!("failed to purge trashed entity `{id}`"))?;
+            }
+            stats.trash += Reclaimed { entities: 1, bytes };
+        }
+    }
+    Ok(stats)
+}
+
+/// Runs due retention policies of all users of `store` every [CHECK_INTERVAL] in background, if
+/// the instance is the leader of `cluster`, if any.
+pub(crate) fn run_periodically(
+    store: &Arc<Store>,
+    cluster: Option<Arc<Cluster>>,
+    clock: Arc<dyn Clock>,
+) {
+    let store = Arc::clone(store);
+    _ = spawn(async move {
+        loop {
+            if cluster.as_ref().is_none_or(|cluster| cluster.is_leader()) {
+                if let Err(e) = run_due(&store, cluster.as_deref(), clock.now()).await {
+                    warn!(target: "app::retention", "failed to run retention policies: {:?}", e);
+                }
+            }
+            sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+/// Runs retention policies of all users of `store`, which are due at `now`, and records
+/// their runs.
+async fn run_due(store: &Store, cluster: Option<&Cluster>, now: SystemTime) -> anyhow::Result<()> {
+    let names = store
+        .users()
+        .await
+        .map_err(anyhow::Error::from)
+        .context("failed to list users")?;
+    for name in names {
+        let cx = UserContext { name };
+        let user = store.user(&cx);
+        let policy = match user.get_retention_policy().await {
+            Ok(policy) => policy,
+            Err(GetError::NotFound) => continue,
+            Err(GetError::Internal(e)) => {
+                warn!(target: "app::retention", "failed to read retention policy of `{cx}`: {:?}", e);
+                continue;
+            }
+        };
+        let last = match user.get_retention_run().await {
+            Ok(run) => Some(run.time),
+            Err(GetError::NotFound) => None,
+            Err(GetError::Internal(e)) => {
+                warn!(target: "app::retention", "failed to read last retention run of `{cx}`: {:?}", e);
+                continue;
+            }
+        };
+        if !policy.is_due(last, now) {
+            continue;
+        }
+        let reclaimed = match run(store, cluster, &cx, &policy, now, true).await {
+            Ok(reclaimed) => reclaimed,
+            Err(e) => {
+                warn!(target: "app::retention", "failed to run retention policy of `{cx}`: {:?}", e);
+                continue;
+            }
+        };
+        let total = reclaimed.total();
+        debug!(target: "app::retention", "reclaimed {} bytes of {} entities of `{cx}`", total.bytes, total.entities);
+        user.put_retention_run(&RetentionRun {
+            time: now.into(),
+            reclaimed,
+        })
+        .await
+        .with_context(|| format!("failed to record retention run of `{cx}`"))?;
+    }
+    Ok(())
+}
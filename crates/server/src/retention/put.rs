@@ -0,0 +1,42 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use crate::json::BoundedJson;
+
+use drawbridge_type::{RetentionPolicy, UserContext};
+
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Replaces the storage retention policy of the user, which is run next when due.
+pub async fn put(
+    Extension(ref store): Extension<Arc<Store>>,
+    claims: OidcClaims,
+    cx: UserContext,
+    BoundedJson(ref policy): BoundedJson<RetentionPolicy>,
+) -> impl IntoResponse {
+    trace!(target: "app::retention::put", "called for `{cx}`");
+
+    let user = claims
+        .assert_user(store, &cx, ScopeContext::User, ScopeLevel::Write)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    policy.validate().map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid retention policy: {e}"),
+        )
+            .into_response()
+    })?;
+    user.put_retention_policy(policy)
+        .await
+        .map(|()| StatusCode::OK)
+        .map_err(|e| {
+            debug!(target: "app::retention::put", "failed to put retention policy of `{cx}`: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })
+}
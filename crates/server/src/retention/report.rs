@@ -0,0 +1,50 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{GetError, OidcClaims, ScopeContext, ScopeLevel, Store};
+use super::run;
+use crate::json;
+use crate::Clock;
+
+use drawbridge_type::{RetentionReport, UserContext};
+
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Returns the storage, which the retention policy of the user would reclaim, if it ran now,
+/// along with the storage actually reclaimed by its last run, if any.
+pub async fn report(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref clock): Extension<Arc<dyn Clock>>,
+    claims: OidcClaims,
+    cx: UserContext,
+) -> impl IntoResponse {
+    trace!(target: "app::retention::report", "called for `{cx}`");
+
+    let user = claims
+        .assert_user(store, &cx, ScopeContext::User, ScopeLevel::Read)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let policy = user.get_retention_policy().await.map_err(|e| {
+        debug!(target: "app::retention::report", "failed to get retention policy of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let projected = run(store, None, &cx, &policy, clock.now(), false)
+        .await
+        .map_err(|e| {
+            debug!(target: "app::retention::report", "failed to project retention policy of `{cx}`: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })?;
+    let last = match user.get_retention_run().await {
+        Ok(run) => Some(run),
+        Err(GetError::NotFound) => None,
+        Err(e) => {
+            debug!(target: "app::retention::report", "failed to get last retention run of `{cx}`: {:?}", e);
+            return Err(e.into_response());
+        }
+    };
+    json::encode(&RetentionReport { projected, last }).map_err(IntoResponse::into_response)
+}
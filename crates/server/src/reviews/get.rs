@@ -0,0 +1,59 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::Store;
+use crate::auth::assert_repository_read;
+use crate::json;
+use crate::pagination::paginate;
+use crate::Clock;
+
+use drawbridge_type::RepositoryContext;
+
+use async_std::sync::Arc;
+use axum::body::Body;
+use axum::http::Request;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Returns pending changes of protected tags of the repository, which are not expired,
+/// ordered by the time they were requested.
+///
+/// Expired changes are discarded.
+///
+/// The listing is paginated by the `cursor` and `limit` query parameters.
+pub async fn get(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref clock): Extension<Arc<dyn Clock>>,
+    cx: RepositoryContext,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::reviews::get", "called for `{cx}`");
+
+    let uri = req.uri().clone();
+    let (repo, _) = assert_repository_read(store, &cx, req)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let ids = repo.review_ids().await.map_err(|e| {
+        debug!(target: "app::reviews::get", "failed to list pending changes of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let now = clock.now();
+    let mut changes = vec![];
+    for id in ids {
+        let (change, ..) = repo.get_review(&id).await.map_err(|e| {
+            debug!(target: "app::reviews::get", "failed to get pending change `{id}` of `{cx}`: {:?}", e);
+            e.into_response()
+        })?;
+        if !change.is_expired(now) {
+            changes.push(change);
+        } else if let Err(e) = repo.remove_review(&id).await {
+            debug!(target: "app::reviews::get", "failed to discard pending change `{id}` of `{cx}`: {:?}", e);
+        }
+    }
+    changes.sort_by_key(|change| change.created);
+    let (changes, link): (Vec<_>, _) = paginate(&uri, changes)?;
+    json::encode(&changes)
+        .map(|res| (link, res))
+        .map_err(IntoResponse::into_response)
+}
@@ -0,0 +1,23 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Two-person review of publishes to protected tags.
+//!
+//! Repositories with a [ReviewPolicy] do not publish protected tags right away. Instead, the
+//! tag entry is kept as a [PendingChange] listed by [get] under `_review`, until one of the
+//! reviewers of the policy other than the publisher approves it using [put] under
+//! `_review/ID`, which publishes the tag. Changes, which are not approved before they expire,
+//! are discarded.
+//!
+//! [ReviewPolicy]: drawbridge_type::ReviewPolicy
+//! [PendingChange]: drawbridge_type::PendingChange
+
+mod get;
+mod put;
+
+pub use get::*;
+pub use put::*;
+
+/// ID of a pending change of a protected tag, which is a UUID.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReviewId(pub String);
@@ -0,0 +1,90 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::cluster::Cluster;
+use super::super::events::EventBus;
+use super::super::tags::publish;
+use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use super::ReviewId;
+use crate::Clock;
+
+use drawbridge_type::{RepositoryContext, TagContext};
+
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Approves the pending change [ReviewId] of a protected tag, which publishes the tag.
+///
+/// Only reviewers of the review policy of the repository other than the publisher may approve
+/// changes, expired changes are discarded.
+#[allow(clippy::too_many_arguments)]
+pub async fn put(
+    Extension(store): Extension<Arc<Store>>,
+    Extension(events): Extension<Arc<EventBus>>,
+    Extension(clock): Extension<Arc<dyn Clock>>,
+    Extension(ReviewId(id)): Extension<ReviewId>,
+    cluster: Option<Extension<Arc<Cluster>>>,
+    claims: OidcClaims,
+    cx: RepositoryContext,
+) -> impl IntoResponse {
+    trace!(target: "app::reviews::put", "called for `{cx}`");
+
+    claims
+        .assert_scope(ScopeContext::Tag, ScopeLevel::Write)
+        .map_err(IntoResponse::into_response)?;
+
+    let repo = store.repository(&cx);
+    let config = repo.get_json().await.map_err(|e| {
+        debug!(target: "app::reviews::put", "failed to get repository `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let policy = config.review.ok_or_else(|| {
+        (
+            StatusCode::CONFLICT,
+            format!("Repository `{cx}` does not require review"),
+        )
+            .into_response()
+    })?;
+    let (change, meta, entry) = repo.get_review(&id).await.map_err(|e| {
+        debug!(target: "app::reviews::put", "failed to get pending change `{id}` of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    if change.is_expired(clock.now()) {
+        if let Err(e) = repo.remove_review(&id).await {
+            debug!(target: "app::reviews::put", "failed to discard pending change `{id}` of `{cx}`: {:?}", e);
+        }
+        return Err((StatusCode::GONE, format!("Pending change `{id}` expired")).into_response());
+    }
+    if !policy.may_approve(&change, claims.subject()) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            format!(
+                "`{}` may not approve pending change `{id}`",
+                claims.subject()
+            ),
+        )
+            .into_response());
+    }
+
+    let tag = TagContext {
+        repository: cx.clone(),
+        name: change.tag,
+    };
+    let status = publish(
+        &store,
+        &events,
+        clock.as_ref(),
+        cluster.as_ref().map(|Extension(c)| c.as_ref()),
+        tag,
+        meta,
+        &entry,
+    )
+    .await?;
+    if let Err(e) = repo.remove_review(&id).await {
+        debug!(target: "app::reviews::put", "failed to remove approved change `{id}` of `{cx}`: {:?}", e);
+    }
+    Ok(status)
+}
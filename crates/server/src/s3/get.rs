@@ -0,0 +1,92 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::alerts::{Alerts, IntegrityAlert};
+use super::super::{is_directory, GetError, PullTagError, Store, TrustedCertificate};
+use super::{assert_read, error, object_headers};
+use crate::auth::pull_tag;
+
+use drawbridge_type::TreeContext;
+
+use async_std::sync::Arc;
+use axum::body::StreamBody;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use tracing::{debug, trace};
+
+fn no_such_key() -> Response {
+    error(StatusCode::NOT_FOUND, "NoSuchKey", "Key does not exist")
+}
+
+fn pull_error(cx: &TreeContext, e: PullTagError<anyhow::Error>) -> Response {
+    match e {
+        PullTagError::Get(GetError::NotFound) => no_such_key(),
+        PullTagError::Blocked(e) => error(StatusCode::FORBIDDEN, "AccessDenied", &e.to_string()),
+        e => {
+            debug!(target: "app::s3::get", "failed to get tag of `{cx}`: {:?}", e);
+            e.into_response()
+        }
+    }
+}
+
+/// Implements the S3 `HeadObject` operation.
+pub async fn head(
+    Extension(ref store): Extension<Arc<Store>>,
+    cert: Option<Extension<TrustedCertificate>>,
+    Extension(cx): Extension<TreeContext>,
+) -> impl IntoResponse {
+    trace!(target: "app::s3::head", "called for `{cx}`");
+
+    let meta = assert_read(store, &cx.tag.repository, cert.as_deref())
+        .await
+        .map_err(|e| *e)?
+        .tag(&cx.tag.name)
+        .node(&cx.path)
+        .get_meta()
+        .await
+        .map_err(|e| match e {
+            GetError::NotFound => no_such_key(),
+            e => {
+                debug!(target: "app::s3::head", "failed for `{cx}`: {:?}", e);
+                e.into_response()
+            }
+        })?;
+    if is_directory(&meta) {
+        return Err(no_such_key());
+    }
+    let headers = object_headers(&meta);
+    Ok((meta, headers, ()))
+}
+
+/// Implements the S3 `GetObject` operation.
+pub async fn get(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref alerts): Extension<Arc<Alerts>>,
+    cert: Option<Extension<TrustedCertificate>>,
+    Extension(cx): Extension<TreeContext>,
+) -> impl IntoResponse {
+    trace!(target: "app::s3::get", "called for `{cx}`");
+
+    let repo = assert_read(store, &cx.tag.repository, cert.as_deref())
+        .await
+        .map_err(|e| *e)?;
+    let (tag, _) = pull_tag(&repo, &cx.tag.name, cert.as_deref())
+        .await
+        .map_err(|e| pull_error(&cx, e))?;
+
+    let node = tag.node(&cx.path);
+    let (meta, body) = node.get_stream().await.map_err(|e| match e {
+        GetError::NotFound => no_such_key(),
+        e => {
+            debug!(target: "app::s3::get", "failed for `{cx}`: {:?}", e);
+            e.into_response()
+        }
+    })?;
+    if is_directory(&meta) {
+        return Err(no_such_key());
+    }
+    let headers = object_headers(&meta);
+    let body = alerts.watch(IntegrityAlert::read(&node, &meta.hash), body);
+    Ok((meta, headers, StreamBody::new(body)))
+}
@@ -0,0 +1,198 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{is_directory, Store, TrustedCertificate};
+use super::{assert_read, error, etag};
+use crate::xml::escape;
+
+use drawbridge_type::{Meta, RepositoryContext, TagName};
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use async_std::sync::Arc;
+use axum::body::Body;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{Request, StatusCode};
+use axum::response::IntoResponse;
+use axum::Extension;
+use openidconnect::url::form_urlencoded;
+use tracing::{debug, trace};
+
+/// Default and maximum number of keys returned in a single response.
+const MAX_KEYS: usize = 1000;
+
+/// Implements the S3 `ListObjectsV2` operation.
+pub async fn list(
+    Extension(ref store): Extension<Arc<Store>>,
+    cert: Option<Extension<TrustedCertificate>>,
+    Extension(cx): Extension<RepositoryContext>,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::s3::list", "called for `{cx}`");
+
+    let mut list_type = None;
+    let mut prefix = String::new();
+    let mut delimiter = None;
+    let mut max_keys = MAX_KEYS;
+    let mut continuation_token = None;
+    let mut start_after = None;
+    for (k, v) in form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes()) {
+        match k.as_ref() {
+            "list-type" => list_type = Some(v.into_owned()),
+            "prefix" => prefix = v.into_owned(),
+            "delimiter" if !v.is_empty() => delimiter = Some(v.into_owned()),
+            "max-keys" => {
+                max_keys = v.parse::<usize>().map(|n| n.min(MAX_KEYS)).map_err(|_| {
+                    error(
+                        StatusCode::BAD_REQUEST,
+                        "InvalidArgument",
+                        "Invalid `max-keys` value",
+                    )
+                })?
+            }
+            "continuation-token" => continuation_token = Some(v.into_owned()),
+            "start-after" => start_after = Some(v.into_owned()),
+            _ => {}
+        }
+    }
+    if list_type.as_deref() != Some("2") {
+        return Err(error(
+            StatusCode::NOT_IMPLEMENTED,
+            "NotImplemented",
+            "Only ListObjectsV2 is supported",
+        ));
+    }
+
+    let repo = assert_read(store, &cx, cert.as_deref())
+        .await
+        .map_err(|e| *e)?;
+    let tags = repo.tags().await.map_err(|e| {
+        debug!(target: "app::s3::list", "failed to list tags of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    // NOTE: Tag names cannot contain `/`, so keys of a tag are contiguous and sort by their
+    // `<tag>/` prefix. Tags are walked in order and only until the page is full.
+    let tags: BTreeMap<String, TagName> = tags
+        .into_iter()
+        .map(|tag| (format!("{tag}/"), tag))
+        .collect();
+
+    // NOTE: The continuation token is the last key or common prefix returned.
+    let after = continuation_token.as_deref().or(start_after.as_deref());
+    let mut contents = vec![];
+    let mut common_prefixes: Vec<String> = vec![];
+    let mut is_truncated = false;
+    'tags: for (tag_prefix, tag) in tags {
+        if !tag_prefix.starts_with(&prefix) && !prefix.starts_with(&tag_prefix) {
+            continue;
+        }
+        if let Some(after) = after {
+            if tag_prefix.as_str() < after && !after.starts_with(&tag_prefix) {
+                continue;
+            }
+        }
+        let nodes = repo.tag(&tag).walk().await.map_err(|e| {
+            debug!(target: "app::s3::list", "failed to walk tree of `{cx}:{tag}`: {:?}", e);
+            e.into_response()
+        })?;
+        let objects: BTreeMap<String, Meta> = nodes
+            .into_iter()
+            .filter(|(_, meta)| !is_directory(meta))
+            .map(|(path, meta)| (format!("{tag_prefix}{path}"), meta))
+            .collect();
+        for (key, meta) in objects {
+            if !key.starts_with(&prefix) {
+                continue;
+            }
+            if let Some(after) = after {
+                if key.as_str() <= after
+                    || delimiter.as_deref().is_some_and(|d| after.ends_with(d))
+                        && key.starts_with(after)
+                {
+                    continue;
+                }
+            }
+            let common_prefix = delimiter.as_deref().and_then(|d| {
+                key[prefix.len()..]
+                    .find(d)
+                    .map(|i| key[..prefix.len() + i + d.len()].to_string())
+            });
+            if let Some(ref common_prefix) = common_prefix {
+                if common_prefixes.last() == Some(common_prefix) {
+                    continue;
+                }
+            }
+            if contents.len() + common_prefixes.len() >= max_keys {
+                is_truncated = true;
+                break 'tags;
+            }
+            match common_prefix {
+                Some(common_prefix) => common_prefixes.push(common_prefix),
+                None => contents.push((key, meta)),
+            }
+        }
+    }
+    let next_continuation_token = if is_truncated {
+        contents
+            .last()
+            .map(|(key, _)| key.clone())
+            .into_iter()
+            .chain(common_prefixes.last().cloned())
+            .max()
+    } else {
+        None
+    };
+
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?><ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">"#,
+    );
+    // NOTE: Writing to a `String` cannot fail.
+    _ = write!(
+        xml,
+        "<Name>{}.{}</Name><Prefix>{}</Prefix><KeyCount>{}</KeyCount><MaxKeys>{max_keys}</MaxKeys><IsTruncated>{is_truncated}</IsTruncated>",
+        cx.owner,
+        cx.name,
+        escape(&prefix),
+        contents.len() + common_prefixes.len(),
+    );
+    if let Some(ref delimiter) = delimiter {
+        _ = write!(xml, "<Delimiter>{}</Delimiter>", escape(delimiter));
+    }
+    if let Some(ref token) = continuation_token {
+        _ = write!(
+            xml,
+            "<ContinuationToken>{}</ContinuationToken>",
+            escape(token)
+        );
+    }
+    if let Some(ref token) = next_continuation_token {
+        _ = write!(
+            xml,
+            "<NextContinuationToken>{}</NextContinuationToken>",
+            escape(token)
+        );
+    }
+    if let Some(ref start_after) = start_after {
+        _ = write!(xml, "<StartAfter>{}</StartAfter>", escape(start_after));
+    }
+    for (key, meta) in contents {
+        _ = write!(
+            xml,
+            "<Contents><Key>{}</Key><ETag>{}</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+            escape(&key),
+            escape(&etag(&meta).unwrap_or_default()),
+            meta.size,
+        );
+    }
+    for common_prefix in common_prefixes {
+        _ = write!(
+            xml,
+            "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+            escape(&common_prefix)
+        );
+    }
+    xml.push_str("</ListBucketResult>");
+
+    Ok(([(CONTENT_TYPE, "application/xml")], xml))
+}
@@ -0,0 +1,177 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Minimal read-only S3-compatible gateway.
+//!
+//! Buckets are named `<owner>.<repository>` and object keys have the form
+//! `<tag>/<path>`, where `<path>` is a file within the tree of `<tag>`.
+//! Only anonymous access to public repositories and access using a trusted
+//! client certificate are supported, since S3 request signatures cannot be
+//! verified by the server.
+
+mod get;
+mod list;
+
+pub use get::*;
+pub use list::*;
+
+use super::{Repository, Store, TrustedCertificate};
+use crate::xml::escape;
+
+use drawbridge_type::digest::{hex, Algorithm};
+use drawbridge_type::{Meta, RepositoryContext, TagContext, TagName, TreeContext, TreePath};
+
+use axum::body::Body;
+use axum::handler::Handler;
+use axum::http::header::{CONTENT_TYPE, ETAG};
+use axum::http::{HeaderMap, Method, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use tower::Service;
+use tracing::{debug, trace};
+
+/// Returns an S3 error response.
+pub(crate) fn error(status: StatusCode, code: &str, message: &str) -> Response {
+    (
+        status,
+        [(CONTENT_TYPE, "application/xml")],
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><Error><Code>{code}</Code><Message>{}</Message></Error>"#,
+            escape(message)
+        ),
+    )
+        .into_response()
+}
+
+/// Returns a quoted hexadecimal SHA-256 digest of the entity, if known, to be used as an S3 ETag.
+pub(crate) fn etag(meta: &Meta) -> Option<String> {
+    meta.hash
+        .get(&Algorithm::Sha256)
+        .map(|hash| format!(r#""{}""#, hex(hash)))
+}
+
+/// Returns the headers of an S3 object response, which are sent in addition to [Meta].
+fn object_headers(meta: &Meta) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(etag) = etag(meta).and_then(|etag| etag.parse().ok()) {
+        _ = headers.insert(ETAG, etag);
+    }
+    headers
+}
+
+/// Parses an S3 bucket name of the form `<owner>.<repository>`.
+///
+/// Error responses are boxed here and below, since they are large compared to parsed values.
+fn parse_bucket(bucket: &str) -> Result<RepositoryContext, Box<Response>> {
+    bucket
+        .split_once('.')
+        .ok_or(())
+        .and_then(|cx| cx.try_into().map_err(|_| ()))
+        .map_err(|()| {
+            Box::new(error(
+                StatusCode::BAD_REQUEST,
+                "InvalidBucketName",
+                "Bucket name must be of the form `<owner>.<repository>`",
+            ))
+        })
+}
+
+/// Parses an S3 object key of the form `<tag>/<path>`.
+fn parse_key(key: &str) -> Result<(TagName, TreePath), Box<Response>> {
+    let no_such_key = || {
+        Box::new(error(
+            StatusCode::NOT_FOUND,
+            "NoSuchKey",
+            "Key does not exist",
+        ))
+    };
+    let (tag, path) = key.split_once('/').ok_or_else(no_such_key)?;
+    let tag = tag.parse().map_err(|_| no_such_key())?;
+    let path = path.parse().map_err(|_| no_such_key())?;
+    Ok((tag, path))
+}
+
+/// Asserts that the repository identified by `cx` may be read over the S3 gateway.
+async fn assert_read<'a>(
+    store: &'a Store,
+    cx: &'a RepositoryContext,
+    cert: Option<&TrustedCertificate>,
+) -> Result<Repository<'a>, Box<Response>> {
+    let repo = store.repository(cx);
+    if cert.is_some() {
+        return Ok(repo);
+    }
+    match repo.is_public().await {
+        Ok(true) => Ok(repo),
+        Ok(false) => Err(Box::new(error(
+            StatusCode::FORBIDDEN,
+            "AccessDenied",
+            "Access Denied",
+        ))),
+        Err(e) => {
+            debug!(target: "app::s3", "failed to get repository `{cx}` config: {:?}", e);
+            Err(Box::new(error(
+                StatusCode::NOT_FOUND,
+                "NoSuchBucket",
+                "The specified bucket does not exist",
+            )))
+        }
+    }
+}
+
+/// Parses the URI of `req` and routes it to respective S3 operation.
+pub(crate) async fn handle(mut req: Request<Body>) -> impl IntoResponse {
+    trace!(target: "app::s3::handle", "begin S3 request handling {:?}", req);
+    let path = req
+        .uri()
+        .path()
+        .trim_start_matches("/s3")
+        .trim_start_matches('/')
+        .to_string();
+    let (bucket, key) = path.split_once('/').unwrap_or((&path, ""));
+    if bucket.is_empty() {
+        return Err(error(
+            StatusCode::NOT_IMPLEMENTED,
+            "NotImplemented",
+            "Listing buckets is not supported",
+        ));
+    }
+    let repository = parse_bucket(bucket).map_err(|e| *e)?;
+    trace!(target: "app::s3::handle", "parsed bucket: `{repository}`");
+
+    if key.is_empty() {
+        assert_eq!(
+            req.extensions_mut().insert(repository),
+            None,
+            "duplicate repository context"
+        );
+        return match *req.method() {
+            Method::GET => Ok(list.into_service().call(req).await.into_response()),
+            _ => Err(error(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "MethodNotAllowed",
+                "The specified method is not allowed against this resource",
+            )),
+        };
+    }
+
+    let (name, path) = parse_key(key).map_err(|e| *e)?;
+    let cx = TreeContext {
+        tag: TagContext { repository, name },
+        path,
+    };
+    trace!(target: "app::s3::handle", "parsed key: `{cx}`");
+    assert_eq!(
+        req.extensions_mut().insert(cx),
+        None,
+        "duplicate tree context"
+    );
+    match *req.method() {
+        Method::HEAD => Ok(head.into_service().call(req).await.into_response()),
+        Method::GET => Ok(get.into_service().call(req).await.into_response()),
+        _ => Err(error(
+            StatusCode::METHOD_NOT_ALLOWED,
+            "MethodNotAllowed",
+            "The specified method is not allowed against this resource",
+        )),
+    }
+}
@@ -0,0 +1,40 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::alerts::{Alerts, IntegrityAlert};
+use super::super::Store;
+use crate::auth::assert_repository_read;
+
+use drawbridge_type::TagContext;
+
+use async_std::sync::Arc;
+use axum::body::{Body, StreamBody};
+use axum::http::Request;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+pub async fn get(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref alerts): Extension<Arc<Alerts>>,
+    cx: TagContext,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::sboms::get", "called for `{cx}`");
+
+    let (repo, _) = assert_repository_read(store, &cx.repository, req)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let sbom = repo.tag(&cx.name).sbom();
+    sbom.get_stream()
+        .await
+        .map_err(|e| {
+            debug!(target: "app::sboms::get", "failed for `{cx}`: {:?}", e);
+            e.into_response()
+        })
+        .map(|(meta, body)| {
+            let body = alerts.watch(IntegrityAlert::read(&sbom, &meta.hash), body);
+            (meta, StreamBody::new(body))
+        })
+}
@@ -0,0 +1,14 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Software bills of materials (SBOMs) attached to tags.
+//!
+//! SPDX and CycloneDX JSON documents are supported, see [SbomFormat](drawbridge_type::SbomFormat).
+
+mod get;
+mod head;
+mod put;
+
+pub use get::*;
+pub use head::*;
+pub use put::*;
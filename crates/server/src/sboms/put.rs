@@ -0,0 +1,62 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use crate::json::BoundedBody;
+
+use drawbridge_type::{Meta, SbomFormat, TagContext};
+
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Attaches an SBOM document to the tag, validating that it matches the format
+/// identified by its media type.
+pub async fn put(
+    Extension(store): Extension<Arc<Store>>,
+    claims: OidcClaims,
+    cx: TagContext,
+    meta: Meta,
+    BoundedBody(body): BoundedBody,
+) -> impl IntoResponse {
+    trace!(target: "app::sboms::put", "called for `{cx}`");
+
+    if meta.hash.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "At least one content digest value must be specified",
+        )
+            .into_response());
+    }
+    let format = SbomFormat::from_media_type(meta.mime.essence())
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "Invalid content type").into_response())?;
+
+    let user = claims
+        .assert_user(
+            &store,
+            &cx.repository.owner,
+            ScopeContext::Tag,
+            ScopeLevel::Write,
+        )
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    format
+        .validate(&body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("{e:#}")).into_response())?;
+
+    let tag = user.repository(&cx.repository.name).tag(&cx.name);
+    _ = tag.get_meta().await.map_err(|e| {
+        debug!(target: "app::sboms::put", "failed to get tag `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    tag.create_sbom(meta, body.as_slice())
+        .await
+        .map_err(|e| {
+            debug!(target: "app::sboms::put", "failed for `{cx}`: {:?}", e);
+            e.into_response()
+        })
+        .map(|_| StatusCode::CREATED)
+}
@@ -0,0 +1,84 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{ContentScanner, Verdict};
+
+use drawbridge_type::TreeContext;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+
+/// Timeout of reads from and writes to the ClamAV daemon.
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Size of chunks contents are streamed to the ClamAV daemon in.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// [ContentScanner], which streams contents to a ClamAV daemon using the `INSTREAM` command.
+///
+/// Note, that the daemon rejects streams exceeding its `StreamMaxLength`, in which case
+/// scanning fails.
+#[derive(Clone, Debug)]
+pub struct ClamdScanner {
+    /// Path of the UNIX socket of the daemon, if it starts with `/`, or its address in
+    /// `HOST:PORT` form
+    pub address: String,
+}
+
+impl ClamdScanner {
+    fn instream(
+        &self,
+        mut conn: impl Read + Write,
+        content: &mut dyn Read,
+    ) -> anyhow::Result<Verdict> {
+        conn.write_all(b"zINSTREAM\0")
+            .context("failed to send command")?;
+        let mut buf = vec![0; CHUNK_SIZE];
+        loop {
+            let n = content.read(&mut buf).context("failed to read contents")?;
+            // NOTE: Chunk sizes are at most `CHUNK_SIZE` and therefore fit into `u32`.
+            conn.write_all(&(n as u32).to_be_bytes())
+                .and_then(|()| conn.write_all(&buf[..n]))
+                .context("failed to send contents")?;
+            if n == 0 {
+                break;
+            }
+        }
+        let mut reply = String::new();
+        _ = conn
+            .read_to_string(&mut reply)
+            .context("failed to read reply")?;
+        let reply = reply.trim_end_matches(['\0', '\n']);
+        match reply.strip_prefix("stream: ") {
+            Some("OK") => Ok(Verdict::Clean),
+            Some(found) if found.ends_with(" FOUND") => {
+                Ok(Verdict::Rejected(found.trim_end_matches(" FOUND").into()))
+            }
+            _ => bail!("unexpected reply `{reply}`"),
+        }
+    }
+}
+
+impl ContentScanner for ClamdScanner {
+    fn scan(&self, _: &TreeContext, content: &mut dyn Read) -> anyhow::Result<Verdict> {
+        if self.address.starts_with('/') {
+            let conn = UnixStream::connect(&self.address)
+                .with_context(|| format!("failed to connect to `{}`", self.address))?;
+            conn.set_read_timeout(Some(TIMEOUT))
+                .and_then(|()| conn.set_write_timeout(Some(TIMEOUT)))
+                .context("failed to set timeouts")?;
+            self.instream(conn, content)
+        } else {
+            let conn = TcpStream::connect(&self.address)
+                .with_context(|| format!("failed to connect to `{}`", self.address))?;
+            conn.set_read_timeout(Some(TIMEOUT))
+                .and_then(|()| conn.set_write_timeout(Some(TIMEOUT)))
+                .context("failed to set timeouts")?;
+            self.instream(conn, content)
+        }
+    }
+}
@@ -0,0 +1,67 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{ContentScanner, Verdict};
+
+use drawbridge_type::TreeContext;
+
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+
+use anyhow::{anyhow, bail, Context};
+
+/// [ContentScanner], which runs a policy binary with contents on its standard input.
+///
+/// The tree node is passed to the binary as `DRAWBRIDGE_NODE` environment variable, e.g.
+/// `user/repo:0.1.0/path/to/file`. Exit status 0 accepts the contents and exit status 1
+/// rejects them, giving the standard output of the binary as reason. Any other exit status
+/// fails scanning.
+#[derive(Clone, Debug)]
+pub struct CommandScanner {
+    pub program: PathBuf,
+    pub args: Vec<String>,
+}
+
+impl ContentScanner for CommandScanner {
+    fn scan(&self, node: &TreeContext, content: &mut dyn Read) -> anyhow::Result<Verdict> {
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .env("DRAWBRIDGE_NODE", node.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("failed to run `{}`", self.program.display()))?;
+        let (mut stdin, mut stdout) = match (child.stdin.take(), child.stdout.take()) {
+            (Some(stdin), Some(stdout)) => (stdin, stdout),
+            _ => bail!("failed to open standard streams"),
+        };
+        // NOTE: Output is read concurrently, so that the binary cannot block on a full pipe
+        // while contents are written.
+        let output = thread::spawn(move || {
+            let mut output = String::new();
+            stdout.read_to_string(&mut output).map(|_| output)
+        });
+        match io::copy(content, &mut stdin) {
+            // NOTE: The binary may exit without reading all contents.
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                _ = child.kill();
+                _ = child.wait();
+                return Err(e).context("failed to write contents");
+            }
+            _ => drop(stdin),
+        }
+        let status = child.wait().context("failed to wait for exit")?;
+        let output = output
+            .join()
+            .map_err(|_| anyhow!("failed to read output"))?
+            .context("failed to read output")?;
+        match status.code() {
+            Some(0) => Ok(Verdict::Clean),
+            Some(1) => Ok(Verdict::Rejected(output.trim().into())),
+            _ => bail!("`{}` failed with {status}", self.program.display()),
+        }
+    }
+}
@@ -0,0 +1,183 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Scanning of contents of uploaded tree nodes.
+//!
+//! The operator registers [ContentScanner]s by name in [Scanners], e.g. a [ClamdScanner],
+//! a [SecretScanner] or a [CommandScanner] running a custom policy binary, and owners of repositories select
+//! the scanners uploaded file nodes are scanned by in the `scanning` member of the repository
+//! config. With [ScanMode::Sync], nodes are scanned before the upload is accepted and nodes
+//! rejected by any scanner are quarantined instead. With [ScanMode::Queued], nodes are scanned
+//! in background and the whole tag containing a rejected node is quarantined, after which an
+//! [Event::EntityDeleted] is published. Quarantined tags are no longer served and may be
+//! created again. Nodes flagged by a scanner are stored regardless and the finding is logged
+//! and, with [ScanMode::Sync], reported in a `Warning` header of the response.
+//!
+//! [ScanMode::Sync]: drawbridge_type::ScanMode::Sync
+//! [ScanMode::Queued]: drawbridge_type::ScanMode::Queued
+
+mod clamd;
+mod command;
+mod secrets;
+
+pub use clamd::*;
+pub use command::*;
+pub use secrets::*;
+
+use super::events::{DeleteCause, Event, EventBus};
+use super::{Entity, GetError, Store};
+
+use drawbridge_type::digest::ContentDigest;
+use drawbridge_type::TreeContext;
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::io::{self, Read};
+
+use anyhow::{anyhow, Context};
+use async_std::sync::Arc;
+use async_std::task::{block_on, spawn, spawn_blocking};
+use camino::Utf8Path;
+use futures::{AsyncRead, AsyncReadExt};
+use tracing::{error, trace, warn};
+
+/// Verdict of a [ContentScanner] on contents of a tree node.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// Contents may be stored
+    Clean,
+    /// Contents must not be stored for the given reason, e.g. the name of a detected malware
+    Rejected(String),
+    /// Contents may be stored, but are suspicious for the given reason, e.g. a detected secret
+    Flagged(String),
+}
+
+/// Scanner of contents of uploaded tree nodes.
+pub trait ContentScanner: Debug + Send + Sync {
+    /// Scans contents of tree node `node` read from `content`, blocking until the verdict is
+    /// known.
+    fn scan(&self, node: &TreeContext, content: &mut dyn Read) -> anyhow::Result<Verdict>;
+}
+
+/// [ContentScanner]s repositories may select by name.
+#[derive(Clone, Debug, Default)]
+pub struct Scanners {
+    scanners: BTreeMap<String, Arc<dyn ContentScanner>>,
+}
+
+impl Scanners {
+    /// Adds `scanner` as `name`, replacing any scanner previously added as `name`.
+    pub fn scanner(
+        mut self,
+        name: impl Into<String>,
+        scanner: impl 'static + ContentScanner,
+    ) -> Self {
+        _ = self.scanners.insert(name.into(), Arc::new(scanner));
+        self
+    }
+
+    /// Returns whether a scanner was added as `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.scanners.contains_key(name)
+    }
+
+    /// Scans contents of `entity`, which is tree node `cx`, by scanners `names` in order and
+    /// returns the first rejection, if any, or else all flags.
+    pub(crate) async fn scan(
+        &self,
+        entity: &Entity<'_, impl AsRef<Utf8Path>>,
+        cx: &TreeContext,
+        names: &[String],
+    ) -> anyhow::Result<Verdict> {
+        let mut flags = vec![];
+        for name in names {
+            let scanner = self
+                .scanners
+                .get(name)
+                .cloned()
+                .ok_or_else(|| anyhow!("unknown scanner `{name}`"))?;
+            let content = entity
+                .get_content()
+                .await
+                .map_err(|e| anyhow!("failed to open contents: {:?}", e))?;
+            let cx = cx.clone();
+            let verdict = spawn_blocking(move || scanner.scan(&cx, &mut BlockingRead(content)))
+                .await
+                .with_context(|| format!("scanner `{name}` failed"))?;
+            match verdict {
+                Verdict::Clean => {}
+                Verdict::Rejected(reason) => {
+                    return Ok(Verdict::Rejected(format!("{name}: {reason}")))
+                }
+                Verdict::Flagged(reason) => flags.push(format!("{name}: {reason}")),
+            }
+        }
+        if flags.is_empty() {
+            Ok(Verdict::Clean)
+        } else {
+            Ok(Verdict::Flagged(flags.join(", ")))
+        }
+    }
+
+    /// Scans contents of tree node `cx` with digest `hash` and length `size` by scanners
+    /// `names` in background and quarantines its tag if they are rejected, see
+    /// [ScanMode::Queued](drawbridge_type::ScanMode::Queued).
+    pub(crate) fn scan_queued(
+        self: &Arc<Self>,
+        store: &Arc<Store>,
+        events: &Arc<EventBus>,
+        cx: TreeContext,
+        hash: ContentDigest,
+        size: u64,
+        names: Vec<String>,
+    ) {
+        let scanners = Arc::clone(self);
+        let store = Arc::clone(store);
+        let events = Arc::clone(events);
+        _ = spawn(async move {
+            let repo = store.repository(&cx.tag.repository);
+            // NOTE: The node may have been uploaded to a pending tag, which is not published yet.
+            let tag = match repo.tag(&cx.tag.name).node(&cx.path).get_meta().await {
+                Err(GetError::NotFound) => repo.pending_tag(&cx.tag.name),
+                _ => repo.tag(&cx.tag.name),
+            };
+            let reason = match scanners.scan(&tag.node(&cx.path), &cx, &names).await {
+                Ok(Verdict::Clean) => {
+                    trace!(target: "app::scan", "`{cx}` passed scanning");
+                    return;
+                }
+                Ok(Verdict::Flagged(reason)) => {
+                    warn!(target: "app::scan", "`{cx}` was flagged by {reason}");
+                    return;
+                }
+                Ok(Verdict::Rejected(reason)) => reason,
+                Err(e) => {
+                    error!(target: "app::scan", "failed to scan `{cx}`: {:?}", e);
+                    return;
+                }
+            };
+            match repo.quarantine_tag(&cx.tag.name).await {
+                Ok(path) => {
+                    warn!(target: "app::scan", "`{cx}` was rejected by {reason}, quarantined tag at `{path}`");
+                    events.publish(Event::EntityDeleted {
+                        node: cx,
+                        digest: hash,
+                        size,
+                        cause: DeleteCause::ScanRejected,
+                    });
+                }
+                Err(e) => error!(target: "app::scan", "`{cx}` was rejected by {reason}: {:?}", e),
+            }
+        });
+    }
+}
+
+/// Adapter of an [AsyncRead] to [Read], which may only be used off the async executor,
+/// e.g. in [spawn_blocking].
+struct BlockingRead<R>(R);
+
+impl<R: Unpin + AsyncRead> Read for BlockingRead<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        block_on(self.0.read(buf))
+    }
+}
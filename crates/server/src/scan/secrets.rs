@@ -0,0 +1,158 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{ContentScanner, Verdict};
+
+use drawbridge_type::TreeContext;
+
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use anyhow::Context;
+
+/// Number of leading bytes of contents, which are considered binary if they contain a NUL byte.
+const BINARY_PREFIX: usize = 8 * 1024;
+
+/// Number of leading bytes of contents, which are scanned.
+const MAX_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Least length of tokens checked for high entropy.
+const MIN_TOKEN_LEN: usize = 20;
+
+/// Least Shannon entropy in bits per character of hexadecimal tokens reported as secrets.
+const HEX_ENTROPY: f64 = 3.0;
+
+/// Case-insensitive keywords, one of which a line must contain for its high-entropy tokens to
+/// be reported as secrets.
+const KEYWORDS: &[&str] = &[
+    "secret",
+    "token",
+    "password",
+    "passwd",
+    "api_key",
+    "apikey",
+    "access_key",
+    "credential",
+    "private_key",
+];
+
+/// Prefixes of AWS access key IDs.
+const AWS_KEY_PREFIXES: &[&str] = &["AKIA", "ASIA", "ABIA", "ACCA"];
+
+/// Prefixes of GitHub tokens.
+const GITHUB_TOKEN_PREFIXES: &[&str] = &["ghp_", "gho_", "ghu_", "ghs_", "ghr_"];
+
+/// [ContentScanner], which detects secrets in text-like contents to prevent their accidental
+/// disclosure.
+///
+/// Contents are searched for known credential patterns, i.e. AWS access key IDs, GitHub
+/// tokens and PEM headers of private keys, and for high-entropy tokens on lines mentioning
+/// secrets, e.g. `aws_secret_access_key = ...`. Contents with a NUL byte among their first
+/// 8 KiB are considered binary and not scanned, only the first 16 MiB of contents are.
+#[derive(Clone, Copy, Debug)]
+pub struct SecretScanner {
+    /// Whether contents containing secrets are rejected, rather than only flagged
+    pub reject: bool,
+
+    /// Least Shannon entropy in bits per character of Base64 tokens reported as secrets, high
+    /// entropy tokens are not reported if [None]
+    pub entropy: Option<f64>,
+}
+
+impl Default for SecretScanner {
+    fn default() -> Self {
+        Self {
+            reject: true,
+            entropy: Some(4.3),
+        }
+    }
+}
+
+impl SecretScanner {
+    /// Returns a description of the first secret found in `text`, if any.
+    fn find(&self, text: &str) -> Option<String> {
+        text.lines().enumerate().find_map(|(i, line)| {
+            known_secret(line)
+                .or_else(|| {
+                    self.entropy
+                        .and_then(|threshold| high_entropy_secret(line, threshold))
+                })
+                .map(|kind| format!("{kind} on line {}", i + 1))
+        })
+    }
+}
+
+impl ContentScanner for SecretScanner {
+    fn scan(&self, _: &TreeContext, content: &mut dyn Read) -> anyhow::Result<Verdict> {
+        let mut buf = vec![];
+        _ = content
+            .take(MAX_SIZE)
+            .read_to_end(&mut buf)
+            .context("failed to read contents")?;
+        if buf[..buf.len().min(BINARY_PREFIX)].contains(&0) {
+            return Ok(Verdict::Clean);
+        }
+        Ok(match self.find(&String::from_utf8_lossy(&buf)) {
+            None => Verdict::Clean,
+            Some(secret) if self.reject => Verdict::Rejected(secret),
+            Some(secret) => Verdict::Flagged(secret),
+        })
+    }
+}
+
+/// Returns the kind of the credential with a known pattern on `line`, if any.
+fn known_secret(line: &str) -> Option<&'static str> {
+    if line.contains("-----BEGIN ") && line.contains("PRIVATE KEY-----") {
+        return Some("private key");
+    }
+    let tokens = |word: fn(char) -> bool| line.split(move |c| !word(c)).filter(|t| !t.is_empty());
+    if tokens(|c| c.is_ascii_alphanumeric()).any(|token| {
+        token.len() == 20
+            && AWS_KEY_PREFIXES.iter().any(|p| token.starts_with(p))
+            && token
+                .chars()
+                .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+    }) {
+        return Some("AWS access key ID");
+    }
+    if tokens(|c| c.is_ascii_alphanumeric() || c == '_').any(|token| {
+        token.len() >= 40 && GITHUB_TOKEN_PREFIXES.iter().any(|p| token.starts_with(p))
+    }) {
+        return Some("GitHub token");
+    }
+    None
+}
+
+/// Returns the kind of a high-entropy token on `line`, if the line mentions secrets and the
+/// entropy of the token exceeds `threshold`, or [HEX_ENTROPY] for hexadecimal tokens.
+fn high_entropy_secret(line: &str, threshold: f64) -> Option<&'static str> {
+    let lower = line.to_ascii_lowercase();
+    if !KEYWORDS.iter().any(|keyword| lower.contains(keyword)) {
+        return None;
+    }
+    line.split(|c: char| !(c.is_ascii_alphanumeric() || "+/=_-".contains(c)))
+        .filter(|token| token.len() >= MIN_TOKEN_LEN)
+        .find_map(|token| {
+            if token.chars().all(|c| c.is_ascii_hexdigit()) {
+                (entropy(token) >= HEX_ENTROPY).then_some("high-entropy hexadecimal string")
+            } else {
+                (entropy(token) >= threshold).then_some("high-entropy string")
+            }
+        })
+}
+
+/// Returns the Shannon entropy of `token` in bits per character.
+fn entropy(token: &str) -> f64 {
+    let counts = token.chars().fold(BTreeMap::new(), |mut counts, c| {
+        *counts.entry(c).or_insert(0usize) += 1;
+        counts
+    });
+    let len = token.chars().count() as f64;
+    counts
+        .values()
+        .map(|&n| {
+            let p = n as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
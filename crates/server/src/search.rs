@@ -0,0 +1,234 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Search of public repositories by name, description, tag names and annotations.
+//!
+//! The [SearchIndex] is kept in memory. It is built by scanning the store in background on
+//! startup and updated incrementally from events published on the [EventBus], so that
+//! searching never scans the store. Private repositories are not indexed.
+
+use super::events::{DeleteCause, Event, EventBus};
+use super::pagination::Pagination;
+use super::store::tree_entry;
+use super::{json, GetError, Store};
+
+use drawbridge_type::{
+    Annotations, RepositoryConfig, RepositoryContext, SearchDocument, SearchHit, SearchQuery,
+    SearchResults, TagContext, TagEntry, TreeContext, UserContext,
+};
+
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use anyhow::Context;
+use async_std::sync::Arc;
+use async_std::task::spawn;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::response::IntoResponse;
+use axum::Extension;
+use futures::try_join;
+use openidconnect::url::form_urlencoded;
+use tracing::{debug, trace, warn};
+
+/// Number of hits returned by [search] unless a limit is requested.
+pub const DEFAULT_LIMIT: usize = 20;
+
+/// Index of public repositories keyed by `owner/name`.
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    docs: RwLock<BTreeMap<String, SearchDocument>>,
+}
+
+impl SearchIndex {
+    /// Returns the annotations of the tree root of tag `cx` in `store`, if it is published.
+    async fn tag_annotations(
+        store: &Store,
+        cx: &TagContext,
+    ) -> anyhow::Result<Option<Annotations>> {
+        let tag = store.tag(cx);
+        let (meta, buf) = match try_join!(tag.get_meta(), tag.read_content()) {
+            Ok(res) => res,
+            Err(GetError::NotFound) => return Ok(None),
+            Err(e) => return Err(anyhow::Error::from(e)).context("failed to read tag"),
+        };
+        let entry = TagEntry::decode(meta.mime.essence(), &buf)?;
+        Ok(Some(
+            tree_entry(&entry)
+                .map(|entry| entry.annotations)
+                .unwrap_or_default(),
+        ))
+    }
+
+    /// Indexes repository `cx` in `store` along with all its tags, or removes it from the
+    /// index, if it is private or does not exist.
+    async fn index_repository(&self, store: &Store, cx: &RepositoryContext) -> anyhow::Result<()> {
+        let repo = store.repository(cx);
+        let config: RepositoryConfig = match repo.get_json().await {
+            Ok(config) if config.public => config,
+            Ok(_) | Err(GetError::NotFound) => {
+                if let Ok(mut docs) = self.docs.write() {
+                    _ = docs.remove(&cx.to_string());
+                }
+                return Ok(());
+            }
+            Err(e) => return Err(anyhow::Error::from(e)).context("failed to read repository"),
+        };
+        let mut tags = BTreeMap::new();
+        for name in repo
+            .tags()
+            .await
+            .map_err(anyhow::Error::from)
+            .context("failed to list tags")?
+        {
+            let tag = TagContext {
+                repository: cx.clone(),
+                name,
+            };
+            if let Some(annotations) = Self::tag_annotations(store, &tag).await? {
+                _ = tags.insert(tag.name, annotations);
+            }
+        }
+        if let Ok(mut docs) = self.docs.write() {
+            _ = docs.insert(
+                cx.to_string(),
+                SearchDocument {
+                    repository: cx.clone(),
+                    description: config.description,
+                    tags,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Updates tag `cx` in the index, if its repository is indexed.
+    async fn index_tag(&self, store: &Store, cx: &TagContext) -> anyhow::Result<()> {
+        let key = cx.repository.to_string();
+        if !self.docs.read().is_ok_and(|docs| docs.contains_key(&key)) {
+            // NOTE: The repository may have been created before the initial scan reached it.
+            return self.index_repository(store, &cx.repository).await;
+        }
+        let annotations = Self::tag_annotations(store, cx).await?;
+        if let Ok(mut docs) = self.docs.write() {
+            if let Some(doc) = docs.get_mut(&key) {
+                match annotations {
+                    Some(annotations) => _ = doc.tags.insert(cx.name.clone(), annotations),
+                    None => _ = doc.tags.remove(&cx.name),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Indexes all repositories in `store`.
+    async fn index_all(&self, store: &Store) -> anyhow::Result<()> {
+        for owner in store
+            .users()
+            .await
+            .map_err(anyhow::Error::from)
+            .context("failed to list users")?
+        {
+            let owner = UserContext { name: owner };
+            for name in store
+                .user(&owner)
+                .repositories()
+                .await
+                .map_err(anyhow::Error::from)
+                .context("failed to list repositories")?
+            {
+                let cx = RepositoryContext {
+                    owner: owner.clone(),
+                    name,
+                };
+                if let Err(e) = self.index_repository(store, &cx).await {
+                    warn!(target: "app::search", "failed to index `{cx}`: {:?}", e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the index from `store` and keeps it up to date with events published on
+    /// `events` in background.
+    pub(crate) fn subscribe(self: &Arc<Self>, store: &Arc<Store>, events: &EventBus) {
+        let index = Arc::clone(self);
+        let store = Arc::clone(store);
+        let events = events.subscribe();
+        _ = spawn(async move {
+            match index.index_all(&store).await {
+                Ok(()) => debug!(target: "app::search", "built search index"),
+                Err(e) => warn!(target: "app::search", "failed to build search index: {:?}", e),
+            }
+            while let Ok((_, event)) = events.recv().await {
+                let res = match event {
+                    Event::RepositoryCreated { ref repository }
+                    | Event::RepositoryDeleted { ref repository, .. }
+                    | Event::RepositoryRestored { ref repository, .. } => {
+                        index.index_repository(&store, repository).await
+                    }
+                    Event::TagUpdated { ref tag, .. }
+                    | Event::TagDeleted { ref tag, .. }
+                    | Event::TagRestored { ref tag, .. }
+                    | Event::EntityDeleted {
+                        node: TreeContext { ref tag, .. },
+                        cause: DeleteCause::ScanRejected,
+                        ..
+                    } => index.index_tag(&store, tag).await,
+                    Event::TreeEntryUploaded { .. }
+                    | Event::EntityDeleted { .. }
+                    | Event::EntityRepaired { .. } => continue,
+                };
+                if let Err(e) = res {
+                    warn!(target: "app::search", "failed to index {:?}: {:?}", event, e);
+                }
+            }
+        });
+    }
+
+    /// Returns hits of `query` ordered by descending relevance and name.
+    pub fn search(&self, query: &SearchQuery) -> Vec<SearchHit> {
+        let mut hits: Vec<_> = self
+            .docs
+            .read()
+            .map(|docs| docs.values().filter_map(|doc| query.score(doc)).collect())
+            .unwrap_or_default();
+        hits.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then_with(|| a.repository.cmp(&b.repository))
+        });
+        hits
+    }
+}
+
+/// Returns public repositories matching the [SearchQuery] passed as the `q` query parameter.
+///
+/// Results are always paginated by the `cursor` and `limit` query parameters, with pages
+/// of [DEFAULT_LIMIT] hits unless the `limit` query parameter requests otherwise.
+pub async fn search(
+    Extension(ref index): Extension<Arc<SearchIndex>>,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::search::search", "called");
+
+    let page = Pagination::from_uri_or(req.uri(), DEFAULT_LIMIT)?;
+    let query = form_urlencoded::parse(req.uri().query().unwrap_or("").as_bytes())
+        .find(|(k, _)| k == "q")
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                "Query parameter `q` missing".to_string(),
+            )
+                .into_response()
+        })?
+        .1
+        .parse::<SearchQuery>()
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid query: {e}")).into_response())?;
+    let hits = index.search(&query);
+    let total = hits.len();
+    let (hits, link) = page.page(req.uri(), hits);
+    json::encode(&SearchResults { hits, total })
+        .map(|res| (link, res))
+        .map_err(IntoResponse::into_response)
+}
@@ -0,0 +1,269 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Seeding of the store with content of a local directory at startup, e.g. for test
+//! environments and demos.
+//!
+//! The directory is laid out as `OWNER/REPOSITORY/TAG`, where the tree of each tag is the
+//! contents of its directory. Owners and repositories missing from the store are created
+//! with the [UserRecord] at `OWNER/user.json` and the [RepositoryConfig] at
+//! `OWNER/REPOSITORY/config.json`, if present, and tags missing from it are published along
+//! with their trees, whose digests are computed as they are seeded. Existing entities are
+//! left as they are, so that seeding is idempotent across restarts.
+
+use super::events::{Event, EventBus};
+use super::{json, CreateError, GetError, Store};
+
+use drawbridge_type::{
+    Meta, RepositoryConfig, RepositoryContext, RepositoryName, TagContext, TagEntry, TagName, Tree,
+    TreeContent, TreeContext, TreeDirectory, TreeEntry, UserContext, UserName, UserRecord,
+};
+
+use anyhow::{anyhow, bail, Context};
+use async_std::fs::{self, File};
+use async_std::task::spawn_blocking;
+use cap_async_std::path::{Path, PathBuf};
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::{debug, info};
+
+/// Name of the file in the directory of an owner containing its [UserRecord].
+const USER_FILE: &str = "user.json";
+
+/// Name of the file in the directory of a repository containing its [RepositoryConfig].
+const CONFIG_FILE: &str = "config.json";
+
+/// Returns the names of subdirectories of `path` in order.
+async fn subdirs(path: &Path) -> anyhow::Result<Vec<String>> {
+    let mut entries = fs::read_dir(path)
+        .await
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    let mut names = vec![];
+    while let Some(entry) = entries.next().await {
+        let entry = entry.with_context(|| format!("failed to read `{}`", path.display()))?;
+        if !entry.file_type().await?.is_dir() {
+            continue;
+        }
+        match entry.file_name().into_string() {
+            Ok(name) => names.push(name),
+            Err(name) => bail!(
+                "invalid name `{}` in `{}`",
+                name.to_string_lossy(),
+                path.display()
+            ),
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Reads the JSON file at `path` along with its metadata, if it exists.
+async fn read_json<T: DeserializeOwned + Serialize>(
+    path: &Path,
+) -> anyhow::Result<Option<(Meta, T)>> {
+    if !fs::metadata(path).await.is_ok_and(|meta| meta.is_file()) {
+        return Ok(None);
+    }
+    let buf = fs::read(path)
+        .await
+        .with_context(|| format!("failed to read `{}`", path.display()))?;
+    let val = serde_json::from_slice(&buf)
+        .with_context(|| format!("failed to decode `{}`", path.display()))?;
+    let (meta, _) = json::encode(&val).map_err(|(_, e)| anyhow!(e))?;
+    Ok(Some((meta, val)))
+}
+
+/// Returns `val` encoded as JSON along with its metadata.
+fn encode<T: Serialize>(val: T) -> anyhow::Result<(Meta, T)> {
+    let (meta, _) = json::encode(&val).map_err(|(_, e)| anyhow!(e))?;
+    Ok((meta, val))
+}
+
+/// Returns whether `res` created an entity, i.e. the entity did not exist.
+fn created<T>(res: Result<T, CreateError<anyhow::Error>>) -> anyhow::Result<bool> {
+    match res {
+        Ok(_) => Ok(true),
+        Err(CreateError::Occupied) => Ok(false),
+        Err(CreateError::Internal(e)) => Err(e),
+        Err(e) => Err(anyhow!("{e:?}")),
+    }
+}
+
+/// Creates owner `cx` unless it exists.
+async fn seed_user(store: &Store, cx: &UserContext, path: &Path) -> anyhow::Result<()> {
+    match store.user(cx).get_meta().await {
+        Ok(_) => return Ok(()),
+        Err(GetError::NotFound) => {}
+        Err(GetError::Internal(e)) => return Err(e),
+    }
+    let (meta, rec) = match read_json(&path.join(USER_FILE)).await? {
+        Some(rec) => rec,
+        None => encode(UserRecord {
+            subject: format!("seed:{cx}"),
+            algorithms: None,
+        })?,
+    };
+    _ = created(store.create_user(cx, meta, &rec).await)?;
+    Ok(())
+}
+
+/// Creates repository `cx` unless it exists.
+async fn seed_repository(
+    store: &Store,
+    events: &EventBus,
+    cx: &RepositoryContext,
+    path: &Path,
+) -> anyhow::Result<()> {
+    let user = store.user(&cx.owner);
+    match user.repository(&cx.name).get_meta().await {
+        Ok(_) => return Ok(()),
+        Err(GetError::NotFound) => {}
+        Err(GetError::Internal(e)) => return Err(e),
+    }
+    let (meta, conf) = match read_json(&path.join(CONFIG_FILE)).await? {
+        Some(conf) => conf,
+        None => encode(RepositoryConfig::default())?,
+    };
+    if created(user.create_repository(&cx.name, meta, &conf).await)? {
+        events.publish(Event::RepositoryCreated {
+            repository: cx.clone(),
+        });
+    }
+    Ok(())
+}
+
+/// Publishes tag `cx` with the tree at `path` unless it exists and returns whether it was
+/// published.
+async fn seed_tag(
+    store: &Store,
+    events: &EventBus,
+    cx: &TagContext,
+    path: PathBuf,
+) -> anyhow::Result<bool> {
+    let repo = store.repository(&cx.repository);
+    match repo.tag(&cx.name).get_meta().await {
+        Ok(_) => return Ok(false),
+        Err(GetError::NotFound) => {}
+        Err(GetError::Internal(e)) => return Err(e),
+    }
+    let tree = spawn_blocking(move || Tree::from_path_sync(path)).await?;
+    let pending = repo
+        .create_pending_tag(&cx.name)
+        .await
+        .map_err(|e| match e {
+            CreateError::Internal(e) => e,
+            e => anyhow!("{e:?}"),
+        })?;
+    let mut root = None;
+    // NOTE: Parents are ordered before their children.
+    for (path, entry) in tree {
+        let TreeEntry {
+            meta,
+            annotations,
+            license,
+            provenance,
+            publication,
+            encryption,
+            custom,
+            content,
+        } = entry;
+        let res = match content {
+            TreeContent::Directory(ref buf) => {
+                let dir: TreeDirectory<TreeEntry> = serde_json::from_slice(buf)
+                    .with_context(|| format!("failed to decode directory `{path}`"))?;
+                pending
+                    .create_directory_node(&path, meta.clone(), &dir)
+                    .await
+            }
+            TreeContent::File(file) => {
+                pending
+                    .create_file_node(&path, meta.clone(), File::from(file))
+                    .await
+            }
+        };
+        if created(res).with_context(|| format!("failed to seed `{cx}/{path}`"))? {
+            events.publish(Event::TreeEntryUploaded {
+                node: TreeContext {
+                    tag: cx.clone(),
+                    path: path.clone(),
+                },
+                digest: meta.hash.clone(),
+                size: meta.size,
+            });
+        }
+        if path.is_root() {
+            root = Some(TreeEntry {
+                meta,
+                annotations,
+                license,
+                provenance,
+                publication,
+                encryption,
+                custom,
+                content: (),
+            });
+        }
+    }
+    let entry = TagEntry::Unsigned(root.context("tree has no root")?);
+    let (meta, _) = encode(&entry)?;
+    let meta = Meta {
+        mime: entry.media_type(),
+        ..meta
+    };
+    let digest = meta.hash.clone();
+    if !created(repo.create_tag(&cx.name, meta, &entry).await)? {
+        return Ok(false);
+    }
+    events.publish(Event::TagUpdated {
+        tag: cx.clone(),
+        digest,
+    });
+    Ok(true)
+}
+
+/// Seeds `store` with the content of the directory at `path`, publishing events of entities
+/// created to `events`.
+pub(crate) async fn seed(store: &Store, events: &EventBus, path: &Path) -> anyhow::Result<()> {
+    let mut seeded = 0;
+    for owner in subdirs(path).await? {
+        let owner_path = path.join(&owner);
+        let cx = UserContext {
+            name: owner
+                .parse::<UserName>()
+                .with_context(|| format!("invalid owner name `{owner}`"))?,
+        };
+        seed_user(store, &cx, &owner_path)
+            .await
+            .with_context(|| format!("failed to seed owner `{cx}`"))?;
+        for repo in subdirs(&owner_path).await? {
+            let repo_path = owner_path.join(&repo);
+            let cx = RepositoryContext {
+                owner: cx.clone(),
+                name: repo
+                    .parse::<RepositoryName>()
+                    .with_context(|| format!("invalid repository name `{repo}`"))?,
+            };
+            seed_repository(store, events, &cx, &repo_path)
+                .await
+                .with_context(|| format!("failed to seed repository `{cx}`"))?;
+            for tag in subdirs(&repo_path).await? {
+                let cx = TagContext {
+                    repository: cx.clone(),
+                    name: tag
+                        .parse::<TagName>()
+                        .with_context(|| format!("invalid tag name `{tag}`"))?,
+                };
+                if seed_tag(store, events, &cx, repo_path.join(&tag))
+                    .await
+                    .with_context(|| format!("failed to seed tag `{cx}`"))?
+                {
+                    debug!(target: "app::seed", "seeded `{cx}`");
+                    seeded += 1;
+                }
+            }
+        }
+    }
+    info!(target: "app::seed", "seeded {seeded} tags from `{}`", path.display());
+    Ok(())
+}
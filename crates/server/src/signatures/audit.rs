@@ -0,0 +1,95 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{SignatureKeys, Store};
+use super::decode;
+use crate::auth::assert_repository_read;
+use crate::json;
+use crate::pagination::paginate;
+use crate::problem::Problem;
+
+use drawbridge_type::{Error, RepositoryContext, TagEntry, TagVerification, TreeEntry};
+
+use std::collections::BTreeMap;
+
+use async_std::sync::Arc;
+use axum::body::Body;
+use axum::http::Request;
+use axum::response::IntoResponse;
+use axum::Extension;
+use futures::try_join;
+use tracing::{debug, trace};
+
+/// Returns verification status of the signatures of all tags of the repository against the
+/// server keys and its signing policy, keyed by tag name.
+///
+/// Aliases are verified if the tag they refer to is.
+///
+/// The listing is paginated by the `cursor` and `limit` query parameters.
+pub async fn audit(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref keys): Extension<Arc<SignatureKeys>>,
+    cx: RepositoryContext,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::signatures::audit", "called for `{cx}`");
+
+    let uri = req.uri().clone();
+    let (repo, _) = assert_repository_read(store, &cx, req)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let (config, tags) = try_join!(repo.get_json(), repo.tags()).map_err(|e| {
+        debug!(target: "app::signatures::audit", "failed for `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let policy = config.signing.unwrap_or_default();
+
+    let mut statuses = BTreeMap::new();
+    let mut aliases = vec![];
+    for name in tags {
+        let tag = repo.tag(&name);
+        let (meta, buf, signatures) =
+            try_join!(tag.get_meta(), tag.read_content(), tag.signatures()).map_err(|e| {
+                debug!(target: "app::signatures::audit", "failed for `{cx}:{name}`: {:?}", e);
+                e.into_response()
+            })?;
+        let status = TagEntry::<TreeEntry>::decode(meta.mime.essence(), &buf)
+            .and_then(|entry| {
+                let (signed, mut names) = match entry {
+                    TagEntry::Signed(ref jws) => (true, keys.verify_jws(jws)?),
+                    TagEntry::Alias(alias) => {
+                        aliases.push((name.clone(), alias.target));
+                        (false, vec![])
+                    }
+                    TagEntry::Unsigned(_) => (false, vec![]),
+                };
+                for signature in signatures {
+                    for key in keys.verify(&buf, &decode(&signature)?) {
+                        if !names.contains(&key) {
+                            names.push(key);
+                        }
+                    }
+                }
+                Ok(TagVerification {
+                    signed,
+                    verified: names.iter().any(|name| policy.accepts(name)),
+                    keys: names,
+                })
+            })
+            .map_err(|e| {
+                debug!(target: "app::signatures::audit", "failed to verify `{cx}:{name}`: {:?}", e);
+                Problem::from(Error::StorageFailure).into_response()
+            })?;
+        _ = statuses.insert(name, status);
+    }
+    for (name, target) in aliases {
+        let verified = statuses.get(&target).is_some_and(|status| status.verified);
+        if let Some(status) = statuses.get_mut(&name) {
+            status.verified |= verified;
+        }
+    }
+    let (statuses, link): (BTreeMap<_, _>, _) = paginate(&uri, statuses)?;
+    json::encode(&statuses)
+        .map(|res| (link, res))
+        .map_err(IntoResponse::into_response)
+}
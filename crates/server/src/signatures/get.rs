@@ -0,0 +1,75 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::keys::{self, ManagedKeys};
+use super::super::{SignatureKeys, Store};
+use super::decode;
+use crate::auth::assert_repository_read;
+use crate::json;
+use crate::problem::Problem;
+
+use drawbridge_type::{Error, TagContext, TagSignature, TagSignatures};
+
+use async_std::sync::Arc;
+use axum::body::Body;
+use axum::http::Request;
+use axum::response::IntoResponse;
+use axum::Extension;
+use futures::try_join;
+use tracing::{debug, trace};
+
+/// Returns signatures attached to the tag along with names of the keys, which verify them.
+///
+/// Server countersignatures are verified by the IDs of the signing keys of the repository,
+/// which made them.
+///
+/// Clients may use [TagSignatures::verified] to only accept signed tags.
+pub async fn get(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref keys): Extension<Arc<SignatureKeys>>,
+    managed: Option<Extension<Arc<ManagedKeys>>>,
+    cx: TagContext,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::signatures::get", "called for `{cx}`");
+
+    let (repo, _) = assert_repository_read(store, &cx.repository, req)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let tag = repo.tag(&cx.name);
+    let (entry, signatures) = try_join!(tag.read_content(), tag.signatures()).map_err(|e| {
+        debug!(target: "app::signatures::get", "failed for `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let managed = match managed {
+        Some(Extension(managed)) => managed.public_keys(&repo).await.map_err(|e| {
+            debug!(target: "app::signatures::get", "failed to read keys of `{cx}`: {:?}", e);
+            Problem::from(Error::StorageFailure).into_response()
+        })?,
+        None => vec![],
+    };
+    let signatures = signatures
+        .into_iter()
+        .map(|buf| {
+            let signature = decode(&buf)?;
+            let mut keys = keys.verify(&entry, &signature);
+            keys.extend(keys::verify(&managed, &entry, &signature));
+            Ok(TagSignature {
+                signature: signature.into(),
+                keys,
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()
+        .map_err(|e| {
+            debug!(target: "app::signatures::get", "failed to decode signatures of `{cx}`: {:?}", e);
+            Problem::from(Error::StorageFailure).into_response()
+        })?;
+    let verified = signatures.iter().any(|sig| !sig.keys.is_empty());
+
+    json::encode(&TagSignatures {
+        signatures,
+        verified,
+    })
+    .map_err(IntoResponse::into_response)
+}
@@ -0,0 +1,34 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Cosign-style signatures of tags.
+//!
+//! A signature is a Base64-encoded ECDSA P-256 signature of the tag entry contents, as
+//! produced by `cosign sign-blob`. Signatures are verified against the [SignatureKeys]
+//! configured on the server, keyless verification using Fulcio and Rekor is not supported.
+//!
+//! Repositories with a [SigningPolicy] only accept signed tags verified by one of the keys
+//! required by the policy, signatures of all tags of a repository may be audited using
+//! [audit].
+//!
+//! [SignatureKeys]: super::SignatureKeys
+//! [SigningPolicy]: drawbridge_type::SigningPolicy
+
+mod audit;
+mod get;
+mod put;
+
+pub use audit::*;
+pub use get::*;
+pub use put::*;
+
+use std::str;
+
+use anyhow::Context;
+
+/// Decodes a stored Base64-encoded signature.
+fn decode(buf: &[u8]) -> anyhow::Result<Vec<u8>> {
+    str::from_utf8(buf)
+        .context("signature is not valid UTF-8")
+        .and_then(|s| base64::decode(s.trim()).context("failed to decode signature"))
+}
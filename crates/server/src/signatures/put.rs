@@ -0,0 +1,74 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{OidcClaims, ScopeContext, ScopeLevel, SignatureKeys, Store};
+use crate::json::BoundedBody;
+
+use drawbridge_type::digest::hex;
+use drawbridge_type::{Meta, TagContext};
+
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use ring::digest::{digest, SHA256};
+use tracing::{debug, trace};
+
+/// Attaches a Base64-encoded signature of the tag entry contents to the tag.
+///
+/// Signatures, which are not verified by any of the server keys, are rejected.
+pub async fn put(
+    Extension(store): Extension<Arc<Store>>,
+    Extension(keys): Extension<Arc<SignatureKeys>>,
+    claims: OidcClaims,
+    cx: TagContext,
+    meta: Meta,
+    BoundedBody(body): BoundedBody,
+) -> impl IntoResponse {
+    trace!(target: "app::signatures::put", "called for `{cx}`");
+
+    if meta.hash.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "At least one content digest value must be specified",
+        )
+            .into_response());
+    }
+
+    let user = claims
+        .assert_user(
+            &store,
+            &cx.repository.owner,
+            ScopeContext::Tag,
+            ScopeLevel::Write,
+        )
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let signature = std::str::from_utf8(&body)
+        .ok()
+        .and_then(|s| base64::decode(s.trim()).ok())
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "Invalid signature encoding").into_response())?;
+
+    let tag = user.repository(&cx.repository.name).tag(&cx.name);
+    let entry = tag.read_content().await.map_err(|e| {
+        debug!(target: "app::signatures::put", "failed to read tag `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    if keys.verify(&entry, &signature).is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Signature is not verified by any of the trusted keys",
+        )
+            .into_response());
+    }
+
+    let name = hex(digest(&SHA256, &signature));
+    tag.create_signature(&name, meta, body.as_slice())
+        .await
+        .map_err(|e| {
+            debug!(target: "app::signatures::put", "failed for `{cx}`: {:?}", e);
+            e.into_response()
+        })
+        .map(|_| StatusCode::CREATED)
+}
@@ -0,0 +1,224 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Logging of requests, which are slower or transfer more bytes than configured thresholds.
+//!
+//! Requests are logged at `WARN` level with target `app::slow_log` once their response body
+//! is sent, along with the identity of the client, the namespace and entity requested, the
+//! sizes of request and response bodies, the time the server took to produce the response
+//! and the time it took to send its body.
+
+use super::handle::split_api_path;
+use super::{TrustedCertificate, WorkloadIdentity};
+
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use async_std::sync::Arc;
+use axum::body::{boxed, BoxBody, Bytes, HttpBody};
+use axum::headers::{ContentLength, HeaderMapExt};
+use axum::http::{HeaderMap, Method, Request, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::Response;
+use hyper::body::SizeHint;
+use tracing::warn;
+
+/// Thresholds, above which requests are logged.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SlowLogConfig {
+    /// Total duration of a request, including sending the response body
+    pub duration: Option<Duration>,
+    /// Size in bytes of the request or response body
+    pub size: Option<u64>,
+}
+
+/// Subject of the OpenID Connect token a request was authenticated with, which is recorded
+/// by [OidcClaims](super::OidcClaims) once verified.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Subject(Arc<Mutex<Option<String>>>);
+
+impl Subject {
+    pub(crate) fn set(&self, subject: &str) {
+        if let Ok(mut inner) = self.0.lock() {
+            *inner = Some(subject.into());
+        }
+    }
+
+    fn get(&self) -> Option<String> {
+        self.0.lock().ok().and_then(|inner| inner.clone())
+    }
+}
+
+/// Request context logged along with measurements.
+#[derive(Debug)]
+struct Record {
+    config: SlowLogConfig,
+    method: Method,
+    uri: Uri,
+    status: StatusCode,
+    /// Identity of the client, unless authenticated by a token
+    identity: Option<String>,
+    subject: Subject,
+    request_size: u64,
+    start: Instant,
+    /// Time the response head was produced in
+    head: Duration,
+}
+
+/// Response body, which counts the bytes sent and logs the request once dropped.
+struct Measured {
+    body: BoxBody,
+    record: Record,
+    response_size: u64,
+    complete: bool,
+}
+
+impl HttpBody for Measured {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let poll = Pin::new(&mut self.body).poll_data(cx);
+        match poll {
+            Poll::Ready(Some(Ok(ref data))) => self.response_size += data.len() as u64,
+            Poll::Ready(None) => self.complete = true,
+            _ => {}
+        }
+        poll
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.body).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+impl Drop for Measured {
+    fn drop(&mut self) {
+        let Record {
+            config,
+            ref method,
+            ref uri,
+            status,
+            ref identity,
+            ref subject,
+            request_size,
+            start,
+            head,
+        } = self.record;
+        let total = start.elapsed();
+        let slow = config.duration.is_some_and(|max| total > max);
+        let large = config
+            .size
+            .is_some_and(|max| request_size > max || self.response_size > max);
+        if !slow && !large {
+            return;
+        }
+        let identity = subject
+            .get()
+            .or_else(|| identity.clone())
+            .unwrap_or_else(|| "anonymous".into());
+        let (namespace, entity) = locate(uri.path());
+        warn!(
+            target: "app::slow_log",
+            %method,
+            %uri,
+            status = status.as_u16(),
+            %identity,
+            namespace,
+            entity,
+            request_size,
+            response_size = self.response_size,
+            complete = self.complete,
+            head_ms = head.as_millis() as u64,
+            transfer_ms = total.saturating_sub(head).as_millis() as u64,
+            total_ms = total.as_millis() as u64,
+            slow,
+            large,
+            "{}",
+            if slow { "slow request" } else { "large transfer" },
+        );
+    }
+}
+
+/// Returns the namespace and the path of the entity within it of a request to `path`.
+fn locate(path: &str) -> (Option<&str>, Option<&str>) {
+    let path = match split_api_path(path) {
+        Some((_, path)) => path,
+        None => return (None, None),
+    };
+    match path.trim_start_matches('/').split_once('/') {
+        Some((namespace, entity)) => (Some(namespace), Some(entity)),
+        None if path.is_empty() => (None, None),
+        None => (Some(path), None),
+    }
+}
+
+/// Logs requests exceeding the [SlowLogConfig] thresholds.
+pub(crate) async fn handle<B>(mut req: Request<B>, next: Next<B>) -> Response {
+    let config = match req.extensions().get::<SlowLogConfig>() {
+        Some(config) => *config,
+        None => return next.run(req).await,
+    };
+    let start = Instant::now();
+    let identity = if let Some(workload) = req.extensions().get::<WorkloadIdentity>() {
+        Some(format!(
+            "workload:{}",
+            workload
+                .identities
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+                .join(",")
+        ))
+    } else if req.extensions().get::<TrustedCertificate>().is_some() {
+        Some("trusted-certificate".into())
+    } else {
+        None
+    };
+    let subject = Subject::default();
+    _ = req.extensions_mut().insert(subject.clone());
+    let method = req.method().clone();
+    let uri = req.uri().clone();
+    let request_size = req
+        .headers()
+        .typed_get::<ContentLength>()
+        .map(|ContentLength(n)| n)
+        .unwrap_or_default();
+
+    let res = next.run(req).await;
+    let record = Record {
+        config,
+        method,
+        uri,
+        status: res.status(),
+        identity,
+        subject,
+        request_size,
+        start,
+        head: start.elapsed(),
+    };
+    res.map(|body| {
+        boxed(Measured {
+            body,
+            record,
+            response_size: 0,
+            complete: false,
+        })
+    })
+}
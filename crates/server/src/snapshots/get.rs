@@ -0,0 +1,79 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store, TrustedCertificate};
+use crate::tar::TAR_TYPE;
+
+use drawbridge_type::digest::Algorithms;
+use drawbridge_type::{Meta, RepositoryContext};
+
+use async_std::sync::Arc;
+use axum::body::Body;
+use axum::extract::RequestParts;
+use axum::http::{Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Exports the repository as a snapshot archive.
+///
+/// Since the repository config is exported along with webhook secrets, read access of the
+/// owner or a trusted client certificate is required even for public repositories.
+pub async fn export(
+    Extension(ref store): Extension<Arc<Store>>,
+    cert: Option<Extension<TrustedCertificate>>,
+    cx: RepositoryContext,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::snapshots::export", "called for `{cx}`");
+
+    if cert.is_none() {
+        _ = RequestParts::new(req)
+            .extract::<OidcClaims>()
+            .await
+            .map_err(IntoResponse::into_response)?
+            .assert_user(store, &cx.owner, ScopeContext::Repository, ScopeLevel::Read)
+            .await
+            .map_err(IntoResponse::into_response)?;
+    }
+
+    // TODO: Stream body
+    // https://github.com/profianinc/drawbridge/issues/56
+    // NOTE: Peers presenting a trusted certificate replicate all tags, while tags blocked by
+    // the vulnerability policy of the repository are not exported to other clients.
+    let buf = if cert.is_none() {
+        match store.repository(&cx).pullable_tags().await {
+            Ok(names) => super::export_tags(store, &cx, Some(&names)).await,
+            Err(e) => Err(e),
+        }
+    } else {
+        super::export_repository(store, &cx).await
+    }
+    .map_err(|e| {
+        debug!(target: "app::snapshots::export", "failed for `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let (_, hash) = Algorithms::default().read_sync(&buf[..]).map_err(|e| {
+        debug!(target: "app::snapshots::export", "failed to compute archive digest: {:?}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to compute digest",
+        )
+            .into_response()
+    })?;
+    let mime = TAR_TYPE.parse().map_err(|_| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Invalid archive media type",
+        )
+            .into_response()
+    })?;
+    Ok::<_, Response>((
+        Meta {
+            hash,
+            size: buf.len() as _,
+            mime,
+        },
+        buf,
+    ))
+}
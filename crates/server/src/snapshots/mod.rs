@@ -0,0 +1,470 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Export and import of repository snapshots for migrations and offline transfer.
+//!
+//! A snapshot is a tar archive containing the repository config and all tags of the
+//! repository along with their trees and attached SBOMs, signatures and attestations. Its
+//! first entry is a [RepositorySnapshot] manifest at [RepositorySnapshot::PATH], which lists
+//! [Meta] of every entity and the path of its contents within the archive. Contents are stored
+//! once at `blobs/<hex-encoded SHA-256 digest>`, so that nodes shared by tags are not
+//! duplicated.
+//!
+//! Archives are verified against their manifest before anything is imported. Tags, which
+//! already exist, are skipped, but nodes and attachments missing from them are imported if
+//! their entries match, so that repeated imports complete partially imported tags. Signatures
+//! are imported as they are and not verified against the signature keys of the importing
+//! server.
+
+mod get;
+mod put;
+
+pub use get::*;
+pub use put::*;
+
+use super::events::{Event, EventBus};
+use super::hash::sha256;
+use super::store::{is_directory, Entity, Tag};
+use super::tar;
+use super::{CreateError, GetError, Store};
+
+use drawbridge_type::{
+    Meta, RepositoryConfig, RepositoryContext, RepositorySnapshot, SnapshotEntity, SnapshotImport,
+    SnapshotTag, TagContext, TagEntry, TagName, TreeContext, TreeDirectory, TreeEntry, TreePath,
+};
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io;
+
+use anyhow::{anyhow, bail, ensure, Context};
+use async_std::fs::File;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use camino::Utf8Path;
+use cap_async_std::fs_utf8::Dir;
+use cap_async_std::path::Path;
+use tracing::debug;
+
+/// Error returned when importing a snapshot.
+#[derive(Debug)]
+pub enum ImportError {
+    /// The archive is malformed or does not match its manifest
+    Invalid(anyhow::Error),
+    Get(GetError<anyhow::Error>),
+    Create(CreateError<anyhow::Error>),
+}
+
+impl From<anyhow::Error> for ImportError {
+    fn from(e: anyhow::Error) -> Self {
+        Self::Invalid(e)
+    }
+}
+
+impl From<GetError<anyhow::Error>> for ImportError {
+    fn from(e: GetError<anyhow::Error>) -> Self {
+        Self::Get(e)
+    }
+}
+
+impl From<CreateError<anyhow::Error>> for ImportError {
+    fn from(e: CreateError<anyhow::Error>) -> Self {
+        Self::Create(e)
+    }
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Invalid(e) => write!(f, "invalid snapshot archive: {e:#}"),
+            Self::Get(GetError::NotFound) => f.write_str("entity not found"),
+            Self::Get(GetError::Internal(e)) | Self::Create(CreateError::Internal(e)) => {
+                write!(f, "{e:#}")
+            }
+            Self::Create(e) => write!(f, "failed to create entity: {e:?}"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl IntoResponse for ImportError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Invalid(e) => (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid snapshot archive: {e:#}"),
+            )
+                .into_response(),
+            Self::Get(e) => e.into_response(),
+            Self::Create(e) => e.into_response(),
+        }
+    }
+}
+
+/// Contents of a snapshot archive being exported.
+#[derive(Debug, Default)]
+struct Blobs {
+    paths: HashSet<String>,
+    blobs: Vec<(String, Vec<u8>)>,
+}
+
+impl Blobs {
+    /// Reads `entity` and adds its contents unless they were added before.
+    async fn add<P: AsRef<Utf8Path>>(
+        &mut self,
+        entity: &Entity<'_, P>,
+    ) -> Result<SnapshotEntity, GetError<anyhow::Error>> {
+        let meta = entity.get_meta().await?;
+        let content = entity.read_content().await?;
+        let path = format!("blobs/{}", sha256(&content));
+        if self.paths.insert(path.clone()) {
+            self.blobs.push((path.clone(), content));
+        }
+        Ok(SnapshotEntity { path, meta })
+    }
+
+    /// Adds all entities of `tag`.
+    async fn add_tag(&mut self, tag: &Tag<'_>) -> Result<SnapshotTag, GetError<anyhow::Error>> {
+        let entry = self.add(tag).await?;
+        let nodes = match tag.walk().await {
+            Ok(nodes) => nodes,
+            Err(GetError::NotFound) => Default::default(),
+            Err(e) => return Err(e),
+        };
+        let mut tree = BTreeMap::new();
+        for path in nodes.into_keys() {
+            let node = self.add(&tag.node(&path)).await?;
+            _ = tree.insert(format!("/{path}"), node);
+        }
+        let sbom = match self.add(&tag.sbom()).await {
+            Ok(sbom) => Some(sbom),
+            Err(GetError::NotFound) => None,
+            Err(e) => return Err(e),
+        };
+        let mut signatures = BTreeMap::new();
+        for (name, signature) in tag.signature_entities().await? {
+            _ = signatures.insert(name, self.add(&signature).await?);
+        }
+        let mut attestations = BTreeMap::new();
+        for (name, attestation) in tag.attestation_entities().await? {
+            _ = attestations.insert(name, self.add(&attestation).await?);
+        }
+        Ok(SnapshotTag {
+            entry,
+            tree,
+            sbom,
+            signatures,
+            attestations,
+        })
+    }
+}
+
+/// Exports repository `cx` in `store` as a snapshot archive.
+pub async fn export_repository(
+    store: &Store,
+    cx: &RepositoryContext,
+) -> Result<Vec<u8>, GetError<anyhow::Error>> {
+    export_tags(store, cx, None).await
+}
+
+/// Exports repository `cx` in `store` like [export_repository], but only includes tags
+/// contained in `names`, if specified, skipping those which do not exist.
+pub(crate) async fn export_tags(
+    store: &Store,
+    cx: &RepositoryContext,
+    names: Option<&[TagName]>,
+) -> Result<Vec<u8>, GetError<anyhow::Error>> {
+    let repo = store.repository(cx);
+    let mut blobs = Blobs::default();
+    let config = blobs.add(&repo).await?;
+    let names = match names {
+        Some(names) => names.to_vec(),
+        None => repo.tags().await?,
+    };
+    let mut tags = BTreeMap::new();
+    for name in names {
+        let tag = match blobs.add_tag(&repo.tag(&name)).await {
+            Ok(tag) => tag,
+            // NOTE: Tags listed by the repository exist unless removed concurrently.
+            Err(GetError::NotFound) => continue,
+            Err(e) => return Err(e),
+        };
+        _ = tags.insert(name.to_string(), tag);
+    }
+    let manifest = serde_json::to_vec_pretty(&RepositorySnapshot {
+        version: RepositorySnapshot::VERSION,
+        repository: cx.to_string(),
+        config,
+        tags,
+    })
+    .context("failed to encode snapshot manifest")
+    .map_err(GetError::Internal)?;
+
+    let mut archive = tar::Builder::default();
+    archive.append(RepositorySnapshot::PATH, &manifest);
+    for (path, content) in blobs.blobs {
+        archive.append(&path, &content);
+    }
+    Ok(archive.finish())
+}
+
+/// Returns `Ok(())` if `content` matches `meta`.
+pub(crate) fn verify(meta: &Meta, content: &[u8]) -> anyhow::Result<()> {
+    ensure!(!meta.hash.is_empty(), "no content digest specified");
+    ensure!(
+        meta.size == content.len() as u64,
+        "length mismatch, expected: {}, got {}",
+        meta.size,
+        content.len()
+    );
+    let mut rdr = meta.hash.reader(content);
+    _ = io::copy(&mut rdr, &mut io::sink()).context("failed to compute content digest")?;
+    ensure!(rdr.digests() == meta.hash, "digest mismatch");
+    Ok(())
+}
+
+/// Contents of a snapshot archive being imported keyed by path, which were verified against
+/// its manifest.
+struct Contents<'a>(HashMap<String, &'a [u8]>);
+
+impl<'a> Contents<'a> {
+    fn get(&self, entity: &SnapshotEntity) -> &'a [u8] {
+        // NOTE: Presence of all entities is verified by `parse`.
+        self.0.get(&entity.path).copied().unwrap_or_default()
+    }
+
+    fn decode<T: serde::de::DeserializeOwned>(&self, entity: &SnapshotEntity) -> anyhow::Result<T> {
+        serde_json::from_slice(self.get(entity))
+            .with_context(|| format!("failed to decode `{}`", entity.path))
+    }
+}
+
+/// Parses snapshot archive `buf` and verifies all entities listed in its manifest.
+fn parse(buf: &[u8]) -> anyhow::Result<(RepositorySnapshot, Contents<'_>)> {
+    let mut contents = HashMap::new();
+    for (path, content) in tar::entries(buf)? {
+        ensure!(
+            contents.insert(path.clone(), content).is_none(),
+            "duplicate entry `{path}`"
+        );
+    }
+    let manifest: RepositorySnapshot = serde_json::from_slice(
+        contents
+            .get(RepositorySnapshot::PATH)
+            .context("manifest missing")?,
+    )
+    .context("failed to decode manifest")?;
+    ensure!(
+        manifest.version == RepositorySnapshot::VERSION,
+        "unsupported archive format version {}",
+        manifest.version
+    );
+    for entity in manifest.entities() {
+        let content = contents
+            .get(&entity.path)
+            .with_context(|| format!("entry `{}` missing", entity.path))?;
+        verify(&entity.meta, content).with_context(|| format!("invalid `{}`", entity.path))?;
+    }
+    for (name, tag) in &manifest.tags {
+        for attachment in tag.signatures.keys().chain(tag.attestations.keys()) {
+            ensure!(
+                !attachment.is_empty() && attachment.bytes().all(|b| b.is_ascii_alphanumeric()),
+                "invalid attachment name `{attachment}` of tag `{name}`"
+            );
+        }
+    }
+    Ok((manifest, Contents(contents)))
+}
+
+/// Maps creation of an entity, which may already exist, to whether it was created.
+fn created<T>(res: Result<T, CreateError<anyhow::Error>>) -> Result<bool, ImportError> {
+    match res {
+        Ok(_) => Ok(true),
+        Err(CreateError::Occupied) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Imports tag `cx` from `tag` and returns whether it was created.
+///
+/// If the tag exists with the same entry, its missing nodes and attachments are imported.
+async fn import_tag(
+    store: &Store,
+    events: &EventBus,
+    cx: &TagContext,
+    tag: &SnapshotTag,
+    contents: &Contents<'_>,
+) -> Result<bool, ImportError> {
+    let repo = store.repository(&cx.repository);
+    let exists = match repo.tag(&cx.name).get_meta().await {
+        Ok(meta) if meta.hash == tag.entry.meta.hash => true,
+        Ok(_) => return Ok(false),
+        Err(GetError::NotFound) => false,
+        Err(e) => return Err(e.into()),
+    };
+    let entry = TagEntry::decode(tag.entry.meta.mime.essence(), contents.get(&tag.entry))
+        .with_context(|| format!("failed to decode `{}`", tag.entry.path))?;
+
+    let nodes = tag
+        .tree
+        .iter()
+        .map(|(path, node)| Ok((path.parse::<TreePath>()?, node)))
+        .collect::<anyhow::Result<BTreeMap<_, _>>>()
+        .map_err(ImportError::Invalid)?;
+    if !nodes.is_empty() {
+        let dest = if exists {
+            repo.tag(&cx.name)
+        } else {
+            repo.create_pending_tag(&cx.name).await?
+        };
+        // NOTE: Parents are ordered before their children.
+        for (path, node) in nodes {
+            let meta = node.meta.clone();
+            let res = if is_directory(&meta) {
+                let dir: TreeDirectory<TreeEntry> = contents.decode(node)?;
+                dest.create_directory_node(&path, meta, &dir).await
+            } else {
+                dest.create_file_node(&path, meta, contents.get(node)).await
+            };
+            if created(res)? {
+                events.publish(Event::TreeEntryUploaded {
+                    node: TreeContext {
+                        tag: cx.clone(),
+                        path,
+                    },
+                    digest: node.meta.hash.clone(),
+                    size: node.meta.size,
+                });
+            }
+        }
+    }
+    if !exists {
+        if !created(
+            repo.create_tag(&cx.name, tag.entry.meta.clone(), &entry)
+                .await,
+        )? {
+            return Ok(false);
+        }
+        events.publish(Event::TagUpdated {
+            tag: cx.clone(),
+            digest: tag.entry.meta.hash.clone(),
+        });
+    }
+
+    let created_tag = repo.tag(&cx.name);
+    if let Some(ref sbom) = tag.sbom {
+        _ = created(
+            created_tag
+                .create_sbom(sbom.meta.clone(), contents.get(sbom))
+                .await,
+        )?;
+    }
+    for (name, signature) in &tag.signatures {
+        _ = created(
+            created_tag
+                .create_signature(name, signature.meta.clone(), contents.get(signature))
+                .await,
+        )?;
+    }
+    for (name, attestation) in &tag.attestations {
+        _ = created(
+            created_tag
+                .create_attestation(name, attestation.meta.clone(), contents.get(attestation))
+                .await,
+        )?;
+    }
+    Ok(!exists)
+}
+
+/// Imports snapshot archive `buf` into repository `cx` in `store`, whose owner must exist,
+/// creating the repository unless it exists.
+pub async fn import_repository(
+    store: &Store,
+    events: &EventBus,
+    cx: &RepositoryContext,
+    buf: &[u8],
+) -> Result<SnapshotImport, ImportError> {
+    let (manifest, contents) = parse(buf)?;
+    let tags = manifest
+        .tags
+        .iter()
+        .map(|(name, tag)| match name.parse::<TagName>() {
+            Ok(name) => Ok((name, tag)),
+            Err(e) => bail!("invalid tag name `{name}`: {e}"),
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let config: RepositoryConfig = contents.decode(&manifest.config)?;
+
+    let user = store.user(&cx.owner);
+    _ = user.get_meta().await?;
+    let mut report = SnapshotImport {
+        created: created(
+            user.create_repository(&cx.name, manifest.config.meta.clone(), &config)
+                .await,
+        )?,
+        ..Default::default()
+    };
+    if report.created {
+        events.publish(Event::RepositoryCreated {
+            repository: cx.clone(),
+        });
+    }
+    for (name, tag) in tags {
+        let tag_cx = TagContext {
+            repository: cx.clone(),
+            name,
+        };
+        match import_tag(store, events, &tag_cx, tag, &contents).await {
+            Ok(true) => report.imported.push(tag_cx.name),
+            Ok(false) => report.skipped.push(tag_cx.name),
+            Err(e) => {
+                debug!(target: "app::snapshots", "failed to import `{tag_cx}`: {e}");
+                return Err(e);
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Opens the store at `path` without removing entities left in the staging area, so that
+/// snapshots may be exported and imported while it is being served.
+pub(crate) async fn open_store(path: &Path) -> anyhow::Result<Store> {
+    let root = File::open(path)
+        .await
+        .map(Dir::from_std_file)
+        .with_context(|| anyhow!("failed to open store at `{}`", path.to_string_lossy()))?;
+    Store::new_shared(root)
+        .await
+        .context("failed to initialize store")
+}
+
+/// Exports repository `cx` of the store at `path` like [export_repository].
+pub async fn export_store(
+    path: impl AsRef<Path>,
+    cx: &RepositoryContext,
+) -> anyhow::Result<Vec<u8>> {
+    let store = open_store(path.as_ref()).await?;
+    export_repository(&store, cx).await.map_err(|e| match e {
+        GetError::NotFound => anyhow!("repository `{cx}` not found"),
+        GetError::Internal(e) => e.context(format!("failed to export `{cx}`")),
+    })
+}
+
+/// Imports snapshot archive `buf` into repository `cx` of the store at `path` like
+/// [import_repository].
+///
+/// Since no server publishes the events of the import, they are neither recorded in the change
+/// journal nor delivered to webhooks.
+pub async fn import_store(
+    path: impl AsRef<Path>,
+    cx: &RepositoryContext,
+    buf: &[u8],
+) -> anyhow::Result<SnapshotImport> {
+    let store = open_store(path.as_ref()).await?;
+    import_repository(&store, &EventBus::default(), cx, buf)
+        .await
+        .map_err(|e| match e {
+            ImportError::Get(GetError::NotFound) => anyhow!("owner of `{cx}` not found"),
+            e => anyhow::Error::new(e).context(format!("failed to import `{cx}`")),
+        })
+}
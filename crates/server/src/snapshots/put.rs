@@ -0,0 +1,68 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::events::EventBus;
+use super::super::{json, OidcClaims, Placement, ScopeContext, ScopeLevel, Store};
+use crate::tar::TAR_TYPE;
+
+use drawbridge_type::{Meta, RepositoryContext};
+
+use async_std::sync::Arc;
+use axum::body::Bytes;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Imports a snapshot archive exported by [export](super::export) into the repository,
+/// creating it unless it exists, and returns a [SnapshotImport](drawbridge_type::SnapshotImport)
+/// report.
+pub async fn import(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref events): Extension<Arc<EventBus>>,
+    Extension(ref placement): Extension<Arc<Placement>>,
+    claims: OidcClaims,
+    cx: RepositoryContext,
+    meta: Meta,
+    body: Bytes,
+) -> impl IntoResponse {
+    trace!(target: "app::snapshots::import", "called for `{cx}`");
+
+    if meta.mime.essence() != TAR_TYPE {
+        return Err((StatusCode::BAD_REQUEST, "Invalid content type").into_response());
+    }
+    _ = claims
+        .assert_user(
+            store,
+            &cx.owner,
+            ScopeContext::Repository,
+            ScopeLevel::Write,
+        )
+        .await
+        .map_err(IntoResponse::into_response)?;
+    placement
+        .assert_local(&cx)
+        .map_err(IntoResponse::into_response)?;
+    super::verify(&meta, &body).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid snapshot archive: {e:#}"),
+        )
+            .into_response()
+    })?;
+
+    let report = super::import_repository(store, events, &cx, &body)
+        .await
+        .map_err(|e| {
+            debug!(target: "app::snapshots::import", "failed for `{cx}`: {e}");
+            e.into_response()
+        })?;
+    let status = if report.created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    json::encode(&report)
+        .map(|res| (status, res))
+        .map_err(IntoResponse::into_response)
+}
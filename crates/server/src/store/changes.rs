@@ -0,0 +1,128 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Store, STAGING_DIR};
+
+use std::io::ErrorKind;
+
+use anyhow::Context;
+use cap_async_std::fs_utf8::OpenOptions;
+use futures::AsyncWriteExt;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use uuid::Uuid;
+
+/// Directory of the change journal relative to the store root.
+pub(super) const CHANGES_DIR: &str = "changes";
+
+/// Number of changes per segment of the change journal.
+const SEGMENT_SIZE: u64 = 1024;
+
+/// Returns the index of the journal segment containing the change at `cursor`.
+fn segment_index(cursor: u64) -> u64 {
+    cursor.saturating_sub(1) / SEGMENT_SIZE
+}
+
+/// Returns the path of journal segment `index` relative to the store root.
+fn segment_path(index: u64) -> String {
+    format!("{CHANGES_DIR}/{index:020}.jsonl")
+}
+
+impl Store {
+    /// Returns the cursor of the most recent change appended by [Self::append_change] or 0,
+    /// if none was appended yet.
+    ///
+    /// A change partially written to the journal, e.g. due to a crash, is discarded.
+    pub async fn last_change(&self) -> anyhow::Result<u64> {
+        let last = self
+            .root
+            .read_dir(CHANGES_DIR)
+            .await
+            .context("failed to read change journal")?
+            .map(|entry| {
+                entry?
+                    .file_name()
+                    .context("failed to read change journal segment name")
+            })
+            .filter_map(|name| match name {
+                Ok(name) => name
+                    .strip_suffix(".jsonl")
+                    .and_then(|index| index.parse::<u64>().ok())
+                    .map(Ok),
+                Err(e) => Some(Err(e)),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?
+            .into_iter()
+            .max();
+        let index = match last {
+            Some(index) => index,
+            None => return Ok(0),
+        };
+        let path = segment_path(index);
+        let mut buf = self
+            .root
+            .read_to_string(&path)
+            .await
+            .context("failed to read change journal segment")?;
+        if !buf.is_empty() && !buf.ends_with('\n') {
+            buf.truncate(buf.rfind('\n').map(|i| i + 1).unwrap_or(0));
+            let tmp = format!("{STAGING_DIR}/{CHANGES_DIR}.{}", Uuid::new_v4());
+            self.root
+                .write(&tmp, &buf)
+                .await
+                .context("failed to write change journal segment")?;
+            self.root
+                .rename(&tmp, &self.root, &path)
+                .await
+                .context("failed to rename change journal segment")?;
+        }
+        Ok(index * SEGMENT_SIZE + buf.lines().count() as u64)
+    }
+
+    /// Appends `change` at `cursor` to the change journal, which must directly follow the
+    /// cursor of the most recently appended change.
+    pub async fn append_change(&self, cursor: u64, change: &impl Serialize) -> anyhow::Result<()> {
+        let mut line = serde_json::to_vec(change).context("failed to encode change")?;
+        line.push(b'\n');
+        let mut file = self
+            .root
+            .open_with(
+                segment_path(segment_index(cursor)),
+                OpenOptions::new().create(true).append(true),
+            )
+            .await
+            .context("failed to open change journal segment")?;
+        file.write_all(&line)
+            .await
+            .context("failed to append change")?;
+        file.flush().await.context("failed to append change")
+    }
+
+    /// Returns at most `limit` changes following the one at cursor `since` in order.
+    pub async fn read_changes<T: DeserializeOwned>(
+        &self,
+        since: u64,
+        limit: usize,
+    ) -> anyhow::Result<Vec<T>> {
+        let mut changes = Vec::with_capacity(limit);
+        let mut cursor = since.saturating_add(1);
+        while changes.len() < limit {
+            let index = segment_index(cursor);
+            let buf = match self.root.read_to_string(segment_path(index)).await {
+                Ok(buf) => buf,
+                Err(e) if e.kind() == ErrorKind::NotFound => break,
+                Err(e) => return Err(e).context("failed to read change journal segment"),
+            };
+            let skip = cursor - index * SEGMENT_SIZE - 1;
+            let before = changes.len();
+            for line in buf.lines().skip(skip as _).take(limit - before) {
+                changes.push(serde_json::from_str(line).context("failed to decode change")?);
+            }
+            match changes.len() - before {
+                0 => break,
+                n => cursor += n as u64,
+            }
+        }
+        Ok(changes)
+    }
+}
@@ -0,0 +1,255 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{is_directory, tree_root, Entity, GetError, Store, Tag};
+
+use std::collections::BTreeSet;
+
+use drawbridge_type::digest::ContentDigest;
+use drawbridge_type::{Meta, TagEntry, TreeDirectory, TreeEntry, TreeName, TreePath};
+
+use anyhow::{anyhow, Context};
+use async_std::fs::File;
+use camino::{Utf8Path, Utf8PathBuf};
+use cap_async_std::fs_utf8::Dir;
+use cap_async_std::path::Path;
+use serde::Serialize;
+
+/// An inconsistency found by [Store::check].
+///
+/// Paths are relative to the store root.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Inconsistency {
+    /// Entity directory without metadata, e.g. left behind by an interrupted creation
+    MissingMeta { path: String },
+    /// Entity metadata without contents
+    MissingContent { path: String },
+    /// Entity contents, which do not match the digest in its metadata
+    CorruptContent {
+        path: String,
+        expected: ContentDigest,
+        actual: ContentDigest,
+    },
+    /// Entity, whose metadata or contents could not be read or decoded
+    Unreadable { path: String, error: String },
+    /// Tree node, whose digest differs from the one in the referencing tag or directory entry
+    DigestMismatch {
+        path: String,
+        expected: ContentDigest,
+        actual: ContentDigest,
+    },
+    /// Tree node referenced by a tag or directory entry, which does not exist
+    Dangling { path: String },
+    /// Tree node, which is not referenced by the directory containing it
+    Orphan { path: String },
+}
+
+/// Result of [Store::check].
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CheckReport {
+    /// Number of entities checked
+    pub entities: u64,
+    pub inconsistencies: Vec<Inconsistency>,
+}
+
+impl CheckReport {
+    /// Returns `true` if no inconsistencies were found.
+    pub fn is_consistent(&self) -> bool {
+        self.inconsistencies.is_empty()
+    }
+
+    /// Checks metadata of `entity` against presence and digest of its contents and returns
+    /// the metadata, if it could be read.
+    async fn check_entity(&mut self, entity: &Entity<'_, impl AsRef<Utf8Path>>) -> Option<Meta> {
+        self.entities += 1;
+        let path = entity.prefix().to_string();
+        let meta = match entity.get_meta().await {
+            Ok(meta) => meta,
+            Err(GetError::NotFound) => {
+                self.inconsistencies
+                    .push(Inconsistency::MissingMeta { path });
+                return None;
+            }
+            Err(GetError::Internal(e)) => {
+                self.inconsistencies.push(Inconsistency::Unreadable {
+                    path,
+                    error: format!("{e:#}"),
+                });
+                return None;
+            }
+        };
+        match entity.digest(&meta.hash).await {
+            Ok(actual) if actual == meta.hash => {}
+            Ok(actual) => self.inconsistencies.push(Inconsistency::CorruptContent {
+                path,
+                expected: meta.hash.clone(),
+                actual,
+            }),
+            Err(GetError::NotFound) => self
+                .inconsistencies
+                .push(Inconsistency::MissingContent { path }),
+            Err(GetError::Internal(e)) => self.inconsistencies.push(Inconsistency::Unreadable {
+                path,
+                error: format!("{e:#}"),
+            }),
+        }
+        Some(meta)
+    }
+
+    /// Checks SBOMs, signatures and attestations attached to `tag`.
+    async fn check_attachments(
+        &mut self,
+        tag: &Tag<'_, impl AsRef<Utf8Path>>,
+    ) -> Result<(), GetError<anyhow::Error>> {
+        let sbom = tag.sbom();
+        if sbom.exists().await {
+            _ = self.check_entity(&sbom).await;
+        }
+        for dir in ["signatures", "attestations"] {
+            for entity in children(tag, dir).await? {
+                _ = self.check_entity(&entity).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the tree of `tag`, whose root is expected to match `root`, if known.
+    async fn check_tree(
+        &mut self,
+        tag: &Tag<'_, impl AsRef<Utf8Path>>,
+        root: Option<Meta>,
+    ) -> Result<(), GetError<anyhow::Error>> {
+        let mut nodes = vec![(TreePath::ROOT, root)];
+        while let Some((path, expected)) = nodes.pop() {
+            let node = tag.node(&path);
+            if !node.exists().await {
+                if expected.is_some() {
+                    self.inconsistencies.push(Inconsistency::Dangling {
+                        path: node.prefix().to_string(),
+                    });
+                }
+                continue;
+            }
+            let meta = match self.check_entity(&node).await {
+                Some(meta) => meta,
+                None => continue,
+            };
+            match expected {
+                Some(expected) if expected.hash != meta.hash => {
+                    self.inconsistencies.push(Inconsistency::DigestMismatch {
+                        path: node.prefix().to_string(),
+                        expected: expected.hash,
+                        actual: meta.hash.clone(),
+                    })
+                }
+                _ => {}
+            }
+            if !is_directory(&meta) {
+                continue;
+            }
+            let dir: TreeDirectory<TreeEntry> = match node.get_content_json().await {
+                Ok(dir) => dir,
+                Err(GetError::NotFound) => continue,
+                Err(GetError::Internal(e)) => {
+                    self.inconsistencies.push(Inconsistency::Unreadable {
+                        path: node.prefix().to_string(),
+                        error: format!("{e:#}"),
+                    });
+                    continue;
+                }
+            };
+            let names = match node.read_dir("entries").await {
+                Err(GetError::NotFound) => BTreeSet::new(),
+                Err(e) => return Err(e),
+                Ok(entries) => entries
+                    .map(|entry| entry?.file_name().context("failed to read node name"))
+                    .collect::<Result<_, _>>()
+                    .map_err(GetError::Internal)?,
+            };
+            for name in names {
+                match name.parse::<TreeName>() {
+                    Ok(ref name) if dir.contains_key(name) => {}
+                    _ => self.inconsistencies.push(Inconsistency::Orphan {
+                        path: node.prefix().join("entries").join(name).to_string(),
+                    }),
+                }
+            }
+            for (name, TreeEntry { meta, .. }) in dir.iter() {
+                let path = path.join(name.clone());
+                nodes.push((path, Some(meta.clone())));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns all entities in directory `dir` of `parent`, ordered by name.
+async fn children<'a>(
+    parent: &Entity<'a, impl AsRef<Utf8Path>>,
+    dir: &str,
+) -> Result<Vec<Entity<'a, Utf8PathBuf>>, GetError<anyhow::Error>> {
+    let names = match parent.read_dir(dir).await {
+        Err(GetError::NotFound) => return Ok(vec![]),
+        Err(e) => return Err(e),
+        Ok(entries) => entries
+            .map(|entry| entry?.file_name().context("failed to read entity name"))
+            .collect::<Result<BTreeSet<_>, _>>()
+            .map_err(GetError::Internal)?,
+    };
+    Ok(names
+        .into_iter()
+        .map(|name| parent.child(format!("{dir}/{name}")))
+        .collect())
+}
+
+impl Store {
+    /// Checks consistency of the store.
+    ///
+    /// Metadata of all entities is checked against presence and digests of their contents
+    /// and trees of tags are checked against the entries referencing them, reporting dangling
+    /// and orphaned nodes. Trees of pending tags are checked for consistency of the nodes
+    /// uploaded so far. The check only reads the store, but may report entities being created
+    /// concurrently.
+    pub async fn check(&self) -> Result<CheckReport, GetError<anyhow::Error>> {
+        let mut report = CheckReport::default();
+        for user in children(&Entity::new(&self.root), "users").await? {
+            _ = report.check_entity(&user).await;
+            for repo in children(&user, "repos").await? {
+                _ = report.check_entity(&repo).await;
+                for tag in children(&repo, "tags").await? {
+                    let tag = Tag::from(tag);
+                    let root = match report.check_entity(&tag).await {
+                        Some(_) => match tag.get_content_json::<TagEntry>().await {
+                            Ok(entry) => tree_root(&entry),
+                            Err(_) => None,
+                        },
+                        None => None,
+                    };
+                    report.check_tree(&tag, root).await?;
+                    report.check_attachments(&tag).await?;
+                }
+                for tag in children(&repo, "pending").await? {
+                    report.check_tree(&Tag::from(tag), None).await?;
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Checks consistency of the store at `path` like [Store::check].
+///
+/// Unlike [Store::new], the store is not initialized, so that it may be checked while being
+/// served.
+pub async fn check_store(path: impl AsRef<Path>) -> anyhow::Result<CheckReport> {
+    let path = path.as_ref();
+    let root = File::open(path)
+        .await
+        .map(Dir::from_std_file)
+        .with_context(|| anyhow!("failed to open store at `{}`", path.to_string_lossy()))?;
+    Store { root }.check().await.map_err(|e| match e {
+        GetError::NotFound => anyhow!("store not found"),
+        GetError::Internal(e) => e.context("failed to check store"),
+    })
+}
@@ -1,68 +1,111 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use std::io;
+use super::super::problem::Problem;
+use super::super::stream::stream_chunks;
+
+use std::future::Future;
+use std::io::{self, Write};
 use std::os::unix::fs::DirBuilderExt;
+use std::time::SystemTime;
 
-use drawbridge_type::Meta;
+use drawbridge_type::{Error, Meta, Timestamps};
 
-use anyhow::Context;
-use axum::http::StatusCode;
+use anyhow::{anyhow, Context};
+use async_std::task::{spawn, spawn_blocking};
+use axum::body::Bytes;
 use axum::response::{IntoResponse, Response};
 use camino::{Utf8Path, Utf8PathBuf};
-use cap_async_std::fs_utf8::{Dir, DirBuilder, ReadDir};
-use drawbridge_type::digest::ContentDigest;
+use cap_async_std::fs_utf8::{Dir, DirBuilder, File, ReadDir};
+use drawbridge_type::digest::{Algorithms, ContentDigest};
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use futures::future::TryFutureExt;
-use futures::io::copy;
+use futures::io::{copy, sink};
 use futures::try_join;
-use futures::{AsyncRead, AsyncWrite};
+use futures::{AsyncRead, AsyncWrite, AsyncWriteExt, Stream};
 use serde::{Deserialize, Serialize};
-use tracing::{debug, trace};
+use tracing::{debug, debug_span, field, trace, Instrument, Span};
+use uuid::Uuid;
+
+/// Directory of the store root, which entities are written to before being moved into place.
+pub(super) const STAGING_DIR: &str = "staging";
 
-const STORAGE_FAILURE_RESPONSE: (StatusCode, &str) =
-    (StatusCode::INTERNAL_SERVER_ERROR, "Storage backend failure");
+/// Name of the auxiliary file of an entity holding its [Timestamps].
+const TIMESTAMPS_FILE: &str = "timestamps.json";
 
 #[derive(Debug)]
 pub enum CreateError<E> {
     Occupied,
-    LengthMismatch { expected: u64, got: u64 },
+    LengthMismatch {
+        expected: u64,
+        got: u64,
+    },
     DigestMismatch,
+    Truncated,
+    Incomplete,
+    Conflict {
+        existing: ContentDigest,
+        requested: ContentDigest,
+    },
     Internal(E),
 }
 
-impl<E> IntoResponse for CreateError<E> {
-    fn into_response(self) -> Response {
-        match self {
-            CreateError::Occupied => (StatusCode::CONFLICT, "Already exists").into_response(),
-            CreateError::DigestMismatch => {
-                (StatusCode::BAD_REQUEST, "Content digest mismatch").into_response()
+impl<E> From<CreateError<E>> for Error {
+    fn from(e: CreateError<E>) -> Self {
+        match e {
+            CreateError::Occupied => Error::AlreadyExists,
+            CreateError::LengthMismatch { expected, got } => {
+                Error::LengthMismatch { expected, got }
             }
-            CreateError::LengthMismatch { expected, got } => (
-                StatusCode::BAD_REQUEST,
-                format!(
-                    "Content length mismatch, expected: {}, got {}",
-                    expected, got
-                ),
-            )
-                .into_response(),
-            CreateError::Internal(_) => STORAGE_FAILURE_RESPONSE.into_response(),
+            CreateError::DigestMismatch => Error::DigestMismatch,
+            CreateError::Truncated => Error::Truncated,
+            CreateError::Incomplete => Error::Incomplete,
+            CreateError::Conflict {
+                existing,
+                requested,
+            } => Error::DigestConflict {
+                existing,
+                requested,
+            },
+            CreateError::Internal(_) => Error::StorageFailure,
         }
     }
 }
 
+impl<E> IntoResponse for CreateError<E> {
+    fn into_response(self) -> Response {
+        Problem::from(Error::from(self)).into_response()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum GetError<E> {
     NotFound,
     Internal(E),
 }
 
+impl<E> From<GetError<E>> for Error {
+    fn from(e: GetError<E>) -> Self {
+        match e {
+            GetError::NotFound => Error::NotFound,
+            GetError::Internal(_) => Error::StorageFailure,
+        }
+    }
+}
+
 impl<E> IntoResponse for GetError<E> {
     fn into_response(self) -> Response {
-        match self {
-            GetError::NotFound => (StatusCode::NOT_FOUND, "Not found"),
-            GetError::Internal(_) => STORAGE_FAILURE_RESPONSE,
+        Problem::from(Error::from(self)).into_response()
+    }
+}
+
+impl From<GetError<anyhow::Error>> for anyhow::Error {
+    fn from(e: GetError<anyhow::Error>) -> Self {
+        match e {
+            GetError::NotFound => anyhow!("entity not found"),
+            GetError::Internal(e) => e,
         }
-        .into_response()
     }
 }
 
@@ -72,27 +115,90 @@ pub enum GetToWriterError<E> {
     Get(GetError<E>),
 }
 
+impl<E> From<GetToWriterError<E>> for Error {
+    fn from(e: GetToWriterError<E>) -> Self {
+        match e {
+            GetToWriterError::Get(e) => e.into(),
+            GetToWriterError::IO(_) => Error::StorageFailure,
+        }
+    }
+}
+
 impl<E> IntoResponse for GetToWriterError<E> {
     fn into_response(self) -> Response {
+        Problem::from(Error::from(self)).into_response()
+    }
+}
+
+/// Outcome of a store operation recorded in its span.
+trait Outcome {
+    fn outcome(&self) -> &'static str;
+}
+
+impl<T, E> Outcome for Result<T, CreateError<E>> {
+    fn outcome(&self) -> &'static str {
         match self {
-            GetToWriterError::Get(GetError::NotFound) => {
-                (StatusCode::NOT_FOUND, "Repository does not exist")
-            }
-            GetToWriterError::Get(GetError::Internal(_)) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "Storage backend failure")
-            }
-            GetToWriterError::IO(_) => (StatusCode::INTERNAL_SERVER_ERROR, "I/O error"),
+            Ok(_) => "ok",
+            Err(CreateError::Occupied) => "occupied",
+            Err(CreateError::Conflict { .. }) => "conflict",
+            Err(CreateError::Internal(_)) => "error",
+            Err(_) => "invalid",
+        }
+    }
+}
+
+impl<T, E> Outcome for Result<T, GetError<E>> {
+    fn outcome(&self) -> &'static str {
+        match self {
+            Ok(_) => "ok",
+            Err(GetError::NotFound) => "not-found",
+            Err(GetError::Internal(_)) => "error",
         }
-        .into_response()
     }
 }
 
+impl<T, E> Outcome for Result<T, GetToWriterError<E>> {
+    fn outcome(&self) -> &'static str {
+        match self {
+            Ok(_) => "ok",
+            Err(GetToWriterError::Get(GetError::NotFound)) => "not-found",
+            Err(_) => "error",
+        }
+    }
+}
+
+impl<T> Outcome for anyhow::Result<T> {
+    fn outcome(&self) -> &'static str {
+        match self {
+            Ok(_) => "ok",
+            Err(_) => "error",
+        }
+    }
+}
+
+/// Returns the namespace of the entity at `prefix`, i.e. the user or the proxied registry
+/// namespace it belongs to.
+pub(crate) fn namespace(prefix: &Utf8Path) -> Option<&str> {
+    let mut components = prefix.components().map(|c| c.as_str());
+    match (components.next(), components.next()) {
+        (Some("users" | "proxy"), namespace) => namespace,
+        _ => None,
+    }
+}
+
+/// Records the number of bytes transferred by the store operation in progress.
+fn record_bytes(n: u64) {
+    _ = Span::current().record("bytes", n);
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct Entity<'a, P> {
     root: &'a Dir,
     prefix: P,
 }
 
+/// Writes contents read from `rdr` to a file at `path` in `dir`, computing digests of the
+/// contents inline with the write, so that they are never read back for verification.
 async fn create_verified(
     dir: &Dir,
     path: impl AsRef<Utf8Path>,
@@ -100,12 +206,13 @@ async fn create_verified(
     size: u64,
     rdr: impl Unpin + AsyncRead,
 ) -> Result<(), CreateError<anyhow::Error>> {
-    let mut file = dir.create(path).await.map_err(|e| match e.kind() {
+    let file = dir.create(path).await.map_err(|e| match e.kind() {
         io::ErrorKind::AlreadyExists => CreateError::Occupied,
         _ => CreateError::Internal(anyhow::Error::new(e).context("failed to create file")),
     })?;
-    match copy(hash.verifier(rdr), &mut file).await {
-        Err(e) if e.kind() == io::ErrorKind::InvalidData => Err(CreateError::DigestMismatch),
+    let mut wtr = hash.writer(file);
+    match copy(rdr, &mut wtr).await {
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Err(CreateError::Truncated),
         Err(e) => Err(CreateError::Internal(
             anyhow::Error::new(e).context("failed to write file"),
         )),
@@ -113,10 +220,132 @@ async fn create_verified(
             expected: size,
             got: n,
         }),
+        Ok(_) if wtr.digests() != hash => Err(CreateError::DigestMismatch),
         Ok(_) => Ok(()),
     }
 }
 
+/// Contents written to the staging area by [Store::stage](super::Store::stage), along with
+/// their size and digests.
+///
+/// Staged contents must be removed using [Self::remove] once no longer needed. Contents
+/// left behind, e.g. due to a crash, are removed along with stale entities of the staging
+/// area.
+#[derive(Debug)]
+pub struct Staged<'a> {
+    root: &'a Dir,
+    path: Utf8PathBuf,
+    pub size: u64,
+    pub hash: ContentDigest,
+}
+
+impl<'a> Staged<'a> {
+    /// Writes contents read from `rdr` to a new file in the staging area of `root`,
+    /// computing digests of the contents using [Algorithms::default] inline with the write.
+    pub(super) async fn create(
+        root: &'a Dir,
+        rdr: impl Unpin + AsyncRead,
+    ) -> anyhow::Result<Staged<'a>> {
+        let path = Utf8PathBuf::from(format!("{STAGING_DIR}/{}", Uuid::new_v4()));
+        let file = root
+            .create(&path)
+            .await
+            .with_context(|| format!("failed to create `{path}`"))?;
+        let mut wtr = Algorithms::default().writer(file);
+        let res = async {
+            let n = copy(rdr, &mut wtr).await?;
+            wtr.flush().await?;
+            Ok::<_, io::Error>(n)
+        };
+        let size = match res.await {
+            Ok(size) => size,
+            Err(e) => {
+                Staged {
+                    root,
+                    path,
+                    size: 0,
+                    hash: Default::default(),
+                }
+                .remove()
+                .await;
+                return Err(anyhow::Error::new(e).context("failed to write staged contents"));
+            }
+        };
+        Ok(Staged {
+            root,
+            path,
+            size,
+            hash: wtr.digests(),
+        })
+    }
+
+    /// Opens the staged contents for reading.
+    pub async fn open(&self) -> anyhow::Result<File> {
+        self.root
+            .open(&self.path)
+            .await
+            .with_context(|| format!("failed to open `{}`", self.path))
+    }
+
+    /// Removes the staged contents.
+    pub async fn remove(self) {
+        if let Err(e) = self.root.remove_file(&self.path).await {
+            if e.kind() != io::ErrorKind::NotFound {
+                debug!(target: "app::store::Staged::remove", "failed to remove staged contents `{}`: {:?}", self.path, e);
+            }
+        }
+    }
+}
+
+/// A directory created for an entity, which is removed unless the entity is created, so
+/// that creation may be retried.
+///
+/// Claims dropped before they are disarmed or released, e.g. because the request creating the
+/// entity was cancelled by the client disconnecting, are released in background.
+#[derive(Debug)]
+#[must_use]
+pub(super) struct Claim {
+    root: Dir,
+    /// Path of the directory relative to the store root, unless disarmed
+    path: Option<Utf8PathBuf>,
+}
+
+/// Removes the directory at `path` in `root` along with its contents, unless the entity was
+/// created, i.e. its metadata exists.
+async fn remove_unclaimed(root: &Dir, path: &Utf8Path) {
+    if root.exists(path.join("meta.json")).await {
+        return;
+    }
+    if let Err(e) = root.remove_dir_all(path).await {
+        if e.kind() != io::ErrorKind::NotFound {
+            debug!(target: "app::store::Claim::release", "failed to remove directory `{path}`: {:?}", e);
+        }
+    }
+}
+
+impl Claim {
+    /// Keeps the directory of the created entity.
+    pub(super) fn disarm(mut self) {
+        self.path = None;
+    }
+
+    /// Removes the directory of the entity, which failed to be created.
+    pub(super) async fn release(mut self) {
+        if let Some(path) = self.path.take() {
+            remove_unclaimed(&self.root, &path).await;
+        }
+    }
+}
+
+impl Drop for Claim {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            let root = self.root.clone();
+            _ = spawn(async move { remove_unclaimed(&root, &path).await });
+        }
+    }
+}
+
 impl<'a> Entity<'a, &'static str> {
     pub fn new(root: &'a Dir) -> Self {
         Self { root, prefix: "" }
@@ -132,6 +361,17 @@ impl<'a, P: AsRef<Utf8Path>> Entity<'a, P> {
         }
     }
 
+    /// Returns the path of the entity relative to the store root.
+    pub(crate) fn prefix(&self) -> &Utf8Path {
+        self.prefix.as_ref()
+    }
+
+    /// Returns `true` if the entity directory exists, regardless of whether the entity
+    /// itself was created.
+    pub(super) async fn exists(&self) -> bool {
+        self.root.is_dir(self.prefix.as_ref()).await
+    }
+
     fn path(&self, path: impl AsRef<Utf8Path>) -> Utf8PathBuf {
         self.prefix.as_ref().join(path)
     }
@@ -144,12 +384,113 @@ impl<'a, P: AsRef<Utf8Path>> Entity<'a, P> {
         self.path("content")
     }
 
+    fn gzip_path(&self) -> Utf8PathBuf {
+        self.path("content.gz")
+    }
+
+    /// Runs store operation `op` within a span carrying the entity and the outcome of `fut`,
+    /// so that store latency shows up in traces of the requests it is performed for.
+    async fn traced<T, E>(
+        &self,
+        op: &'static str,
+        fut: impl Future<Output = Result<T, E>>,
+    ) -> Result<T, E>
+    where
+        Result<T, E>: Outcome,
+    {
+        let prefix = self.prefix.as_ref();
+        let span = debug_span!(
+            target: "app::store",
+            "store",
+            op,
+            backend = "fs",
+            namespace = namespace(prefix),
+            entity = %prefix,
+            bytes = field::Empty,
+            outcome = field::Empty,
+        );
+        let res = fut.instrument(span.clone()).await;
+        _ = span.record("outcome", res.outcome());
+        res
+    }
+
     pub(super) async fn create_from_reader(
         &self,
         meta: Meta,
         rdr: impl Unpin + AsyncRead,
+    ) -> Result<(), CreateError<anyhow::Error>> {
+        let hash = meta.hash.clone();
+        self.create_verified_from_reader(meta, hash, rdr).await
+    }
+
+    /// Creates the entity like [Self::create_from_reader], but only verifies the strongest
+    /// algorithm of the content digest inline and returns the digests of remaining algorithms,
+    /// which must be verified later using [Self::verify].
+    pub(super) async fn create_from_reader_deferred(
+        &self,
+        meta: Meta,
+        rdr: impl Unpin + AsyncRead,
+    ) -> Result<ContentDigest, CreateError<anyhow::Error>> {
+        let mut deferred = meta.hash.clone();
+        let inline = match deferred.keys().next_back() {
+            Some(&strongest) => deferred.split_off(&strongest).into(),
+            None => ContentDigest::default(),
+        };
+        self.create_verified_from_reader(meta, inline, rdr).await?;
+        Ok(deferred)
+    }
+
+    /// Creates the entity with `meta` and contents read from `rdr`, which are verified
+    /// against `hash` only.
+    ///
+    /// The entity is first written to a staging area keyed by a unique upload identifier and
+    /// only moved into place once its contents are verified, metadata last, so that readers
+    /// never observe partially uploaded or unverified contents. Nothing is left in the
+    /// staging area on failure.
+    async fn create_verified_from_reader(
+        &self,
+        meta: Meta,
+        hash: ContentDigest,
+        rdr: impl Unpin + AsyncRead,
+    ) -> Result<(), CreateError<anyhow::Error>> {
+        self.traced("create", self.create_staged(meta, hash, rdr))
+            .await
+    }
+
+    /// Creates the entity via the staging area, see [Self::create_verified_from_reader].
+    async fn create_staged(
+        &self,
+        meta: Meta,
+        hash: ContentDigest,
+        rdr: impl Unpin + AsyncRead,
     ) -> Result<(), CreateError<anyhow::Error>> {
         trace!(target: "app::store::Entity::create_from_reader", "create entity at `{}`", self.prefix.as_ref());
+        record_bytes(meta.size);
+        let staged = Entity {
+            root: self.root,
+            prefix: Utf8PathBuf::from(format!("{STAGING_DIR}/{}", Uuid::new_v4())),
+        };
+        staged.create_dir("").await?;
+        let res = staged.write_verified(meta, hash, rdr).await;
+        let res = match res {
+            Ok(()) => self.commit(&staged).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = self.root.remove_dir_all(&staged.prefix).await {
+            if e.kind() != io::ErrorKind::NotFound {
+                debug!(target: "app::store::Entity::create_from_reader", "failed to remove staged entity `{}`: {:?}", staged.prefix, e);
+            }
+        }
+        res
+    }
+
+    /// Writes metadata and contents of the entity read from `rdr` verified against `hash`.
+    async fn write_verified(
+        &self,
+        meta: Meta,
+        hash: ContentDigest,
+        rdr: impl Unpin + AsyncRead,
+    ) -> Result<(), CreateError<anyhow::Error>> {
         let meta_json = serde_json::to_vec(&meta)
             .context("failed to encode metadata")
             .map_err(CreateError::Internal)?;
@@ -166,7 +507,7 @@ impl<'a, P: AsRef<Utf8Path>> Entity<'a, P> {
                     debug!(target: "app::store::Entity::create_from_reader", "failed to create meta file `{:?}`", e);
                     e
                 }),
-            create_verified(self.root, self.content_path(), meta.hash, meta.size, rdr).map_err(|e| {
+            create_verified(self.root, self.content_path(), hash, meta.size, rdr).map_err(|e| {
                 debug!(target: "app::store::Entity::create_from_reader", "failed to create content file `{:?}`", e);
                 e
             })
@@ -174,6 +515,25 @@ impl<'a, P: AsRef<Utf8Path>> Entity<'a, P> {
         Ok(())
     }
 
+    /// Moves contents and metadata of the `staged` entity into place, in that order, since
+    /// the presence of metadata is what makes an entity visible to readers.
+    async fn commit(
+        &self,
+        staged: &Entity<'_, Utf8PathBuf>,
+    ) -> Result<(), CreateError<anyhow::Error>> {
+        for (from, to) in [
+            (staged.content_path(), self.content_path()),
+            (staged.meta_path(), self.meta_path()),
+        ] {
+            self.root
+                .rename(&from, self.root, &to)
+                .await
+                .with_context(|| format!("failed to move `{from}` to `{to}`"))
+                .map_err(CreateError::Internal)?;
+        }
+        Ok(())
+    }
+
     pub(super) async fn create_json(
         &self,
         meta: Meta,
@@ -194,72 +554,143 @@ impl<'a, P: AsRef<Utf8Path>> Entity<'a, P> {
         debug_assert_ne!(path, self.content_path());
 
         trace!(target: "app::store::Entity::create_dir", "create directory at `{path}`");
-        self.root
-            .create_dir_with(path, DirBuilder::new().mode(0o700))
-            .map_err(|e| match e.kind() {
-                io::ErrorKind::AlreadyExists => CreateError::Occupied,
-                _ => CreateError::Internal(
-                    anyhow::Error::new(e).context("failed to create directory"),
-                ),
-            })
-            .map_err(|e| {
-                debug!(target: "app::store::Entity::create_dir", "failed to create directory: `{:?}`", e);
-                e
+        let res = self
+            .traced("create_dir", async {
+                self.root
+                    .create_dir_with(path, DirBuilder::new().mode(0o700))
+                    .map_err(|e| match e.kind() {
+                        io::ErrorKind::AlreadyExists => CreateError::Occupied,
+                        _ => CreateError::Internal(
+                            anyhow::Error::new(e).context("failed to create directory"),
+                        ),
+                    })
             })
+            .await;
+        res.map_err(|e| {
+            debug!(target: "app::store::Entity::create_dir", "failed to create directory: `{:?}`", e);
+            e
+        })
     }
 
     pub(super) async fn read_dir(
         &self,
         path: impl AsRef<Utf8Path>,
     ) -> Result<ReadDir, GetError<anyhow::Error>> {
-        self.root
-            .read_dir(self.path(path))
-            .await
-            .map_err(|e| match e.kind() {
-                io::ErrorKind::NotFound => GetError::NotFound,
-                _ => GetError::Internal(anyhow::Error::new(e).context("failed to read directory")),
-            })
+        self.traced(
+            "read_dir",
+            self.root
+                .read_dir(self.path(path))
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::NotFound => GetError::NotFound,
+                    _ => GetError::Internal(
+                        anyhow::Error::new(e).context("failed to read directory"),
+                    ),
+                }),
+        )
+        .await
     }
 
     /// Returns metadata of the entity.
     pub async fn get_meta(&self) -> Result<Meta, GetError<anyhow::Error>> {
-        let buf = self
-            .root
-            .read(self.meta_path())
+        self.traced("get_meta", async {
+            let buf = self
+                .root
+                .read(self.meta_path())
+                .await
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::NotFound => GetError::NotFound,
+                    _ => {
+                        GetError::Internal(anyhow::Error::new(e).context("failed to read metadata"))
+                    }
+                })?;
+            let meta: Meta = serde_json::from_slice(&buf)
+                .context("failed to decode metadata")
+                .map_err(GetError::Internal)?;
+            record_bytes(meta.size);
+            Ok(meta)
+        })
+        .await
+    }
+
+    /// Returns the time the entity was last modified at, see [Self::get_timestamps].
+    pub async fn get_modified(&self) -> Result<SystemTime, GetError<anyhow::Error>> {
+        self.get_timestamps()
             .await
-            .map_err(|e| match e.kind() {
-                io::ErrorKind::NotFound => GetError::NotFound,
-                _ => GetError::Internal(anyhow::Error::new(e).context("failed to read metadata")),
-            })?;
-        serde_json::from_slice(&buf)
-            .context("failed to decode metadata")
-            .map_err(GetError::Internal)
+            .map(|timestamps| timestamps.modified.into())
+    }
+
+    /// Returns creation and last modification time of the entity last recorded by
+    /// [Self::touch].
+    ///
+    /// Entities, for which none were recorded, e.g. those created before timestamps were
+    /// introduced, are considered to be created and last modified when their metadata was
+    /// written.
+    pub async fn get_timestamps(&self) -> Result<Timestamps, GetError<anyhow::Error>> {
+        match self.get_aux_json(TIMESTAMPS_FILE).await {
+            Err(GetError::NotFound) => {}
+            res => return res,
+        }
+        self.traced("get_modified", async {
+            self.root
+                .metadata(self.meta_path())
+                .await
+                .and_then(|meta| meta.modified())
+                .map(|modified| Timestamps::new(modified.into_std()))
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::NotFound => GetError::NotFound,
+                    _ => GetError::Internal(
+                        anyhow::Error::new(e).context("failed to read modification time"),
+                    ),
+                })
+        })
+        .await
+    }
+
+    /// Records modification of the entity at `now`, which is also recorded as its creation
+    /// time, if no timestamps were recorded yet.
+    pub async fn touch(&self, now: SystemTime) -> anyhow::Result<()> {
+        let timestamps = match self.get_aux_json::<Timestamps>(TIMESTAMPS_FILE).await {
+            Ok(timestamps) => timestamps.touch(now),
+            Err(GetError::NotFound) => Timestamps::new(now),
+            Err(GetError::Internal(e)) => return Err(e),
+        };
+        self.put_aux_json(TIMESTAMPS_FILE, &timestamps).await
     }
 
     /// Returns contents of the entity as [AsyncRead].
-    pub async fn get_content(&self) -> Result<impl '_ + AsyncRead, GetError<anyhow::Error>> {
-        self.root
-            .open(self.content_path())
-            .map_err(|e| match e.kind() {
-                io::ErrorKind::NotFound => GetError::NotFound,
-                _ => {
-                    GetError::Internal(anyhow::Error::new(e).context("failed to open content file"))
-                }
-            })
-            .await
+    pub async fn get_content(&self) -> Result<File, GetError<anyhow::Error>> {
+        self.traced(
+            "open",
+            self.root
+                .open(self.content_path())
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::NotFound => GetError::NotFound,
+                    _ => GetError::Internal(
+                        anyhow::Error::new(e).context("failed to open content file"),
+                    ),
+                }),
+        )
+        .await
     }
 
     /// Reads contents of the entity.
     pub async fn read_content(&self) -> Result<Vec<u8>, GetError<anyhow::Error>> {
-        self.root
-            .read(self.content_path())
-            .map_err(|e| match e.kind() {
-                io::ErrorKind::NotFound => GetError::NotFound,
-                _ => {
-                    GetError::Internal(anyhow::Error::new(e).context("failed to read content file"))
-                }
-            })
-            .await
+        self.traced(
+            "read",
+            self.root
+                .read(self.content_path())
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::NotFound => GetError::NotFound,
+                    _ => GetError::Internal(
+                        anyhow::Error::new(e).context("failed to read content file"),
+                    ),
+                })
+                .map_ok(|buf| {
+                    record_bytes(buf.len() as u64);
+                    buf
+                }),
+        )
+        .await
     }
 
     /// Returns the contents of the entity as JSON.
@@ -274,9 +705,386 @@ impl<'a, P: AsRef<Utf8Path>> Entity<'a, P> {
             .map_err(GetError::Internal)
     }
 
+    /// Returns the auxiliary JSON file `name` of the entity, which is kept alongside the
+    /// entity, but is not part of its contents, e.g. statistics.
+    #[allow(single_use_lifetimes)]
+    pub(super) async fn get_aux_json<T>(&self, name: &str) -> Result<T, GetError<anyhow::Error>>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        self.traced("get_aux", async {
+            let buf = self
+                .root
+                .read(self.path(name))
+                .await
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::NotFound => GetError::NotFound,
+                    _ => GetError::Internal(
+                        anyhow::Error::new(e).context("failed to read auxiliary file"),
+                    ),
+                })?;
+            record_bytes(buf.len() as u64);
+            serde_json::from_slice(&buf)
+                .context("failed to decode auxiliary file as JSON")
+                .map_err(GetError::Internal)
+        })
+        .await
+    }
+
+    /// Replaces the auxiliary JSON file `name` of the entity, see [Self::get_aux_json].
+    ///
+    /// The file is written to a temporary file first and atomically renamed, so that
+    /// concurrent readers never observe a partially written file.
+    pub(super) async fn put_aux_json(
+        &self,
+        name: &str,
+        value: &impl Serialize,
+    ) -> anyhow::Result<()> {
+        self.traced("put_aux", async {
+            let buf =
+                serde_json::to_vec(value).context("failed to encode auxiliary file as JSON")?;
+            record_bytes(buf.len() as u64);
+            let tmp = self.path(format!("{name}.{}", Uuid::new_v4()));
+            self.root
+                .write(&tmp, buf)
+                .await
+                .context("failed to write auxiliary file")?;
+            self.root
+                .rename(&tmp, self.root, self.path(name))
+                .await
+                .context("failed to rename auxiliary file")
+        })
+        .await
+    }
+
     /// Returns metadata of the entity and a reader of its contents.
     pub async fn get(&self) -> Result<(Meta, impl '_ + AsyncRead), GetError<anyhow::Error>> {
-        try_join!(self.get_meta(), self.get_content())
+        self.traced("get", async {
+            try_join!(self.get_meta(), self.get_content())
+        })
+        .await
+    }
+
+    /// Returns metadata of the entity and a stream of its contents, which is verified
+    /// against the digest in the metadata as it is read.
+    ///
+    /// The stream does not borrow the entity and yields an error if the contents do not
+    /// match the digest, which allows it to be used as a response body directly.
+    pub async fn get_stream(
+        &self,
+    ) -> Result<
+        (Meta, impl 'static + Send + Stream<Item = io::Result<Bytes>>),
+        GetError<anyhow::Error>,
+    > {
+        self.traced("get_stream", async {
+            let (meta, file) = try_join!(self.get_meta(), self.get_content())?;
+            record_bytes(meta.size);
+            let rdr = meta.hash.clone().verifier(file);
+            Ok((meta, stream_chunks(rdr)))
+        })
+        .await
+    }
+
+    /// Returns metadata of the entity, the size of its gzip-compressed contents and a stream
+    /// of them.
+    ///
+    /// The compressed variant is produced from verified contents on first use and stored
+    /// alongside the original, so that compression cost is only paid once. The metadata,
+    /// including the digest, always describes the identity encoding of the contents.
+    pub async fn get_gzip_stream(
+        &self,
+    ) -> Result<
+        (
+            Meta,
+            u64,
+            impl 'static + Send + Stream<Item = io::Result<Bytes>>,
+        ),
+        GetError<anyhow::Error>,
+    > {
+        self.traced("get_gzip_stream", async {
+            let meta = self.get_meta().await?;
+            let file = match self.root.open(self.gzip_path()).await {
+                Ok(file) => file,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    self.create_gzip(&meta).await?;
+                    self.root
+                        .open(self.gzip_path())
+                        .await
+                        .context("failed to open compressed content file")
+                        .map_err(GetError::Internal)?
+                }
+                Err(e) => {
+                    return Err(GetError::Internal(
+                        anyhow::Error::new(e).context("failed to open compressed content file"),
+                    ))
+                }
+            };
+            let size = file
+                .metadata()
+                .context("failed to read compressed content file metadata")
+                .map_err(GetError::Internal)?
+                .len();
+            record_bytes(size);
+            Ok((meta, size, stream_chunks(file)))
+        })
+        .await
+    }
+
+    /// Compresses verified contents of the entity and stores them at [Self::gzip_path].
+    ///
+    /// The compressed contents are written to a temporary file first and atomically renamed,
+    /// so that concurrent requests never observe a partially written variant.
+    async fn create_gzip(&self, meta: &Meta) -> Result<(), GetError<anyhow::Error>> {
+        self.traced("create_gzip", self.compress(meta)).await
+    }
+
+    /// Compresses verified contents of the entity, see [Self::create_gzip].
+    async fn compress(&self, meta: &Meta) -> Result<(), GetError<anyhow::Error>> {
+        trace!(target: "app::store::Entity::create_gzip", "compress entity at `{}`", self.prefix.as_ref());
+        let mut buf = Vec::with_capacity(meta.size.try_into().unwrap_or_default());
+        _ = copy(
+            meta.hash.clone().verifier(self.get_content().await?),
+            &mut buf,
+        )
+        .await
+        .context("failed to read content")
+        .map_err(GetError::Internal)?;
+        let buf = spawn_blocking(move || {
+            let mut enc = GzEncoder::new(vec![], Compression::default());
+            enc.write_all(&buf)?;
+            enc.finish()
+        })
+        .await
+        .context("failed to compress content")
+        .map_err(GetError::Internal)?;
+        record_bytes(buf.len() as u64);
+
+        let tmp = self.path(format!("content.gz.{}", Uuid::new_v4()));
+        self.root
+            .write(&tmp, buf)
+            .await
+            .context("failed to write compressed content file")
+            .map_err(GetError::Internal)?;
+        self.root
+            .rename(&tmp, self.root, self.gzip_path())
+            .await
+            .context("failed to rename compressed content file")
+            .map_err(GetError::Internal)
+    }
+
+    /// Resolves a failure to create the entity with contents of digest `hash`, because it
+    /// already exists.
+    ///
+    /// Returns `Ok(())` if the existing entity has identical contents, which makes creation
+    /// idempotent, and [CreateError::Conflict] with both digests otherwise. Digests are
+    /// identical if they share at least one algorithm and all shared algorithms match.
+    pub async fn resolve_occupied(
+        &self,
+        hash: &ContentDigest,
+    ) -> Result<(), CreateError<anyhow::Error>> {
+        self.traced("resolve_occupied", async {
+            let existing = match self.get_meta().await {
+                Ok(meta) => meta.hash,
+                // NOTE: The entity is still being created by a concurrent request.
+                Err(GetError::NotFound) => return Err(CreateError::Occupied),
+                Err(GetError::Internal(e)) => return Err(CreateError::Internal(e)),
+            };
+            let mut shared = hash
+                .iter()
+                .filter_map(|(algo, requested)| Some((existing.get(algo)?, requested)))
+                .peekable();
+            if shared.peek().is_some() && shared.all(|(existing, requested)| existing == requested)
+            {
+                Ok(())
+            } else {
+                Err(CreateError::Conflict {
+                    existing,
+                    requested: hash.clone(),
+                })
+            }
+        })
+        .await
+    }
+
+    /// Creates the entity directory like [Self::create_dir] and returns a [Claim] on it, which
+    /// must be disarmed once the entity is created.
+    pub(super) async fn claim(&self) -> Result<Claim, CreateError<anyhow::Error>> {
+        self.create_dir("").await?;
+        Ok(Claim {
+            root: self.root.clone(),
+            path: Some(self.prefix.as_ref().to_owned()),
+        })
+    }
+
+    /// Removes the entity along with its auxiliary files and children.
+    pub(super) async fn remove(&self) -> anyhow::Result<()> {
+        self.traced("remove", async {
+            self.root
+                .remove_dir_all(self.prefix.as_ref())
+                .await
+                .with_context(|| format!("failed to remove `{}`", self.prefix.as_ref()))
+        })
+        .await
+    }
+
+    /// Returns the number of bytes of all files of the entity along with its auxiliary files
+    /// and children.
+    pub(crate) async fn disk_usage(&self) -> anyhow::Result<u64> {
+        self.traced("disk_usage", async {
+            let mut bytes = 0;
+            let mut dirs = vec![self.prefix.as_ref().to_path_buf()];
+            while let Some(dir) = dirs.pop() {
+                let entries = match self.root.read_dir(&dir).await {
+                    Ok(entries) => entries,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(e).with_context(|| format!("failed to read `{dir}`")),
+                };
+                for entry in entries {
+                    let entry =
+                        entry.with_context(|| format!("failed to read entry of `{dir}`"))?;
+                    let meta = match entry.metadata() {
+                        Ok(meta) => meta,
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                        Err(e) => {
+                            return Err(e)
+                                .with_context(|| format!("failed to read metadata of `{dir}`"))
+                        }
+                    };
+                    if meta.is_dir() {
+                        let name = entry
+                            .file_name()
+                            .with_context(|| format!("failed to read entry name of `{dir}`"))?;
+                        dirs.push(dir.join(name));
+                    } else {
+                        bytes += meta.len();
+                    }
+                }
+            }
+            Ok(bytes)
+        })
+        .await
+    }
+
+    /// Returns the time the directory of the entity was last modified at, which is when a
+    /// child was last added or removed.
+    pub(super) async fn get_dir_modified(&self) -> Result<SystemTime, GetError<anyhow::Error>> {
+        self.root
+            .metadata(self.prefix.as_ref())
+            .await
+            .and_then(|meta| meta.modified())
+            .map(|modified| modified.into_std())
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => GetError::NotFound,
+                _ => GetError::Internal(
+                    anyhow::Error::new(e).context("failed to read modification time"),
+                ),
+            })
+    }
+
+    /// Moves the entity to the location of `to`, which must not exist.
+    pub(super) async fn move_to(
+        &self,
+        to: &Entity<'_, impl AsRef<Utf8Path>>,
+    ) -> Result<(), CreateError<anyhow::Error>> {
+        self.traced("move", async {
+            if self.root.exists(to.prefix.as_ref()).await {
+                return Err(CreateError::Occupied);
+            }
+            self.root
+                .rename(self.prefix.as_ref(), to.root, to.prefix.as_ref())
+                .await
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::AlreadyExists | io::ErrorKind::DirectoryNotEmpty => {
+                        CreateError::Occupied
+                    }
+                    _ => CreateError::Internal(anyhow::Error::new(e).context(format!(
+                        "failed to move `{}` to `{}`",
+                        self.prefix.as_ref(),
+                        to.prefix.as_ref()
+                    ))),
+                })
+        })
+        .await
+    }
+
+    /// Reads contents of the entity and returns `true` if they match `hash`.
+    pub async fn verify(&self, hash: &ContentDigest) -> Result<bool, GetError<anyhow::Error>> {
+        self.digest(hash).await.map(|digest| digest == *hash)
+    }
+
+    /// Reads contents of the entity and returns their digest computed using the algorithms
+    /// of `hash`.
+    pub async fn digest(
+        &self,
+        hash: &ContentDigest,
+    ) -> Result<ContentDigest, GetError<anyhow::Error>> {
+        self.traced("digest", async {
+            // NOTE: Unlike a plain reader, the verifier is `Send`.
+            let mut rdr = hash.clone().verifier(self.get_content().await?);
+            match copy(&mut rdr, &mut sink()).await {
+                Ok(n) => record_bytes(n),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {}
+                Err(e) => {
+                    return Err(GetError::Internal(
+                        anyhow::Error::new(e).context("failed to read content"),
+                    ))
+                }
+            }
+            Ok(rdr.digests())
+        })
+        .await
+    }
+
+    /// Replaces contents of the entity described by `meta`, e.g. ones failing verification,
+    /// with contents read from `rdr`, which are verified against `meta`.
+    ///
+    /// The contents are written to the staging area first and only moved over the existing
+    /// ones once verified, so that readers never observe partially written contents.
+    pub(super) async fn replace_content(
+        &self,
+        meta: &Meta,
+        rdr: impl Unpin + AsyncRead,
+    ) -> Result<(), CreateError<anyhow::Error>> {
+        self.traced("replace_content", async {
+            record_bytes(meta.size);
+            let staged = Utf8PathBuf::from(format!("{STAGING_DIR}/{}", Uuid::new_v4()));
+            let res = match create_verified(self.root, &staged, meta.hash.clone(), meta.size, rdr)
+                .await
+            {
+                Ok(()) => self
+                    .root
+                    .rename(&staged, self.root, self.content_path())
+                    .await
+                    .with_context(|| {
+                        format!("failed to move `{staged}` to `{}`", self.content_path())
+                    })
+                    .map_err(CreateError::Internal),
+                Err(e) => Err(e),
+            };
+            if res.is_err() {
+                if let Err(e) = self.root.remove_file(&staged).await {
+                    if e.kind() != io::ErrorKind::NotFound {
+                        debug!(target: "app::store::Entity::replace_content", "failed to remove staged content `{staged}`: {:?}", e);
+                    }
+                }
+            }
+            res
+        })
+        .await
+    }
+
+    /// Moves the entity out of the way into the `quarantine` directory of the store root
+    /// and returns its new path.
+    pub(super) async fn quarantine(&self) -> Result<Utf8PathBuf, anyhow::Error> {
+        self.traced("quarantine", async {
+            let path = Utf8PathBuf::from(format!("quarantine/{}", Uuid::new_v4()));
+            self.root
+                .rename(self.prefix.as_ref(), self.root, &path)
+                .await
+                .with_context(|| format!("failed to quarantine `{}`", self.prefix.as_ref()))?;
+            Ok(path)
+        })
+        .await
     }
 
     /// Returns metadata of the entity and writes its contents into `dst`.
@@ -284,9 +1092,13 @@ impl<'a, P: AsRef<Utf8Path>> Entity<'a, P> {
         &self,
         dst: &mut (impl Unpin + AsyncWrite),
     ) -> Result<Meta, GetToWriterError<anyhow::Error>> {
-        let (meta, rdr) = self.get().await.map_err(GetToWriterError::Get)?;
-        _ = copy(rdr, dst).await.map_err(GetToWriterError::IO)?;
-        // TODO: Validate size
-        Ok(meta)
+        self.traced("get_to_writer", async {
+            let (meta, rdr) = self.get().await.map_err(GetToWriterError::Get)?;
+            let n = copy(rdr, dst).await.map_err(GetToWriterError::IO)?;
+            record_bytes(n);
+            // TODO: Validate size
+            Ok(meta)
+        })
+        .await
     }
 }
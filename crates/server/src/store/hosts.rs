@@ -0,0 +1,46 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Store, STAGING_DIR};
+
+use drawbridge_type::UserName;
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+
+use anyhow::Context;
+use uuid::Uuid;
+
+/// Path of the vanity hosts of owners relative to the store root.
+const HOSTS_PATH: &str = "hosts.json";
+
+impl Store {
+    /// Reads the owners vanity hosts are routed to keyed by the hosts last written by
+    /// [Self::write_hosts].
+    ///
+    /// Returns no hosts if none were written yet.
+    pub async fn read_hosts(&self) -> anyhow::Result<BTreeMap<String, UserName>> {
+        match self.root.read(HOSTS_PATH).await {
+            Ok(buf) => serde_json::from_slice(&buf).context("failed to decode vanity hosts"),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Default::default()),
+            Err(e) => Err(e).context("failed to read vanity hosts"),
+        }
+    }
+
+    /// Replaces the vanity hosts of owners.
+    ///
+    /// The hosts are written to the staging area first and atomically renamed, so that a
+    /// crash never leaves them partially written.
+    pub async fn write_hosts(&self, hosts: &BTreeMap<String, UserName>) -> anyhow::Result<()> {
+        let buf = serde_json::to_vec(hosts).context("failed to encode vanity hosts")?;
+        let tmp = format!("{STAGING_DIR}/{HOSTS_PATH}.{}", Uuid::new_v4());
+        self.root
+            .write(&tmp, buf)
+            .await
+            .context("failed to write vanity hosts")?;
+        self.root
+            .rename(&tmp, &self.root, HOSTS_PATH)
+            .await
+            .context("failed to rename vanity hosts")
+    }
+}
@@ -0,0 +1,252 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Store, STAGING_DIR};
+
+use std::io::ErrorKind;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use openidconnect::url::form_urlencoded::byte_serialize;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Directory of leases relative to the store root.
+pub(super) const LEASES_DIR: &str = "leases";
+
+/// A lease held by an instance sharing the store.
+#[derive(Debug, Deserialize, Serialize)]
+struct Lease<'a> {
+    holder: &'a str,
+    /// Seconds since UNIX epoch, after which the lease may be taken over
+    expires: u64,
+}
+
+/// Returns the path of the directory of lease `name` relative to the store root.
+fn lease_dir(name: &str) -> String {
+    format!(
+        "{LEASES_DIR}/{}",
+        byte_serialize(name.as_bytes()).collect::<String>()
+    )
+}
+
+/// Returns the path of generation `generation` of the lease in `dir`.
+fn generation_path(dir: &str, generation: u64) -> String {
+    format!("{dir}/{generation}")
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl Store {
+    /// Returns generations of the lease in `dir`.
+    async fn lease_generations(&self, dir: &str) -> anyhow::Result<Vec<u64>> {
+        let entries = match self.root.read_dir(dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e).context("failed to read lease directory"),
+        };
+        let mut generations = vec![];
+        for entry in entries {
+            let name = entry
+                .and_then(|entry| entry.file_name())
+                .context("failed to read lease directory entry")?;
+            if let Ok(generation) = name.parse() {
+                generations.push(generation);
+            }
+        }
+        Ok(generations)
+    }
+
+    /// Returns the latest generation of the lease in `dir` along with its contents, if any.
+    async fn latest_lease(&self, dir: &str) -> anyhow::Result<Option<(u64, Vec<u8>)>> {
+        let latest = match self.lease_generations(dir).await?.into_iter().max() {
+            Some(latest) => latest,
+            None => return Ok(None),
+        };
+        match self.root.read(generation_path(dir, latest)).await {
+            Ok(buf) => Ok(Some((latest, buf))),
+            // NOTE: The generation was superseded and removed in the meantime.
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).context("failed to read lease"),
+        }
+    }
+
+    /// Acquires or renews lease `name` for `holder` until `ttl` after `now` and returns
+    /// whether it was acquired.
+    ///
+    /// Leases held by other holders are only taken over once they expire. Each lease is a
+    /// sequence of generations, the latest of which is in effect. Generations are files linked
+    /// into place, which only succeeds if the generation does not exist yet, so that at most
+    /// one holder takes over a lease even if the store is shared by several instances.
+    pub async fn acquire_lease(
+        &self,
+        name: &str,
+        holder: &str,
+        now: SystemTime,
+        ttl: Duration,
+    ) -> anyhow::Result<bool> {
+        let dir = lease_dir(name);
+        match self.root.create_dir(&dir) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(e).context("failed to create lease directory"),
+        }
+        let lease = serde_json::to_vec(&Lease {
+            holder,
+            expires: unix_secs(now + ttl),
+        })
+        .context("failed to encode lease")?;
+        let tmp = format!("{STAGING_DIR}/lease.{}", Uuid::new_v4());
+        self.root
+            .write(&tmp, &lease)
+            .await
+            .context("failed to write lease")?;
+        let res = self.link_lease(&tmp, &dir, holder, now).await;
+        _ = self.root.remove_file(&tmp).await;
+        res
+    }
+
+    async fn link_lease(
+        &self,
+        tmp: &str,
+        dir: &str,
+        holder: &str,
+        now: SystemTime,
+    ) -> anyhow::Result<bool> {
+        let next = match self.latest_lease(dir).await? {
+            Some((latest, current)) => match serde_json::from_slice::<Lease<'_>>(&current) {
+                Ok(lease) if lease.holder == holder => {
+                    // NOTE: Holders renew their leases long before they expire, so the lease
+                    // is not taken over concurrently, unless the holder stalled.
+                    self.root
+                        .rename(tmp, &self.root, generation_path(dir, latest))
+                        .await
+                        .context("failed to renew lease")?;
+                    return Ok(!self.root.exists(generation_path(dir, latest + 1)).await);
+                }
+                Ok(lease) if lease.expires > unix_secs(now) => return Ok(false),
+                _ => latest + 1,
+            },
+            None if self.lease_generations(dir).await?.is_empty() => 0,
+            None => return Ok(false),
+        };
+        match self
+            .root
+            .hard_link(tmp, &self.root, generation_path(dir, next))
+            .await
+        {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => return Ok(false),
+            Err(e) => return Err(e).context("failed to link lease"),
+        }
+        for generation in self.lease_generations(dir).await? {
+            if generation < next {
+                _ = self
+                    .root
+                    .remove_file(generation_path(dir, generation))
+                    .await;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Releases lease `name` if held by `holder`.
+    ///
+    /// The lease is marked as expired rather than removed, so that it is taken over by its
+    /// next generation.
+    pub async fn release_lease(&self, name: &str, holder: &str) -> anyhow::Result<()> {
+        let dir = lease_dir(name);
+        let (latest, buf) = match self.latest_lease(&dir).await? {
+            Some(lease) => lease,
+            None => return Ok(()),
+        };
+        match serde_json::from_slice::<Lease<'_>>(&buf) {
+            Ok(lease) if lease.holder == holder => {}
+            _ => return Ok(()),
+        }
+        let released = serde_json::to_vec(&Lease {
+            holder: "",
+            expires: 0,
+        })
+        .context("failed to encode lease")?;
+        let tmp = format!("{STAGING_DIR}/lease.{}", Uuid::new_v4());
+        self.root
+            .write(&tmp, &released)
+            .await
+            .context("failed to write lease")?;
+        let res = self
+            .root
+            .rename(&tmp, &self.root, generation_path(&dir, latest))
+            .await
+            .context("failed to release lease");
+        if res.is_err() {
+            _ = self.root.remove_file(&tmp).await;
+        }
+        res
+    }
+
+    /// Returns the holder of lease `name`, if it is held at `now`.
+    pub async fn lease_holder(
+        &self,
+        name: &str,
+        now: SystemTime,
+    ) -> anyhow::Result<Option<String>> {
+        Ok(match self.latest_lease(&lease_dir(name)).await? {
+            Some((_, buf)) => match serde_json::from_slice::<Lease<'_>>(&buf) {
+                Ok(lease) if lease.expires > unix_secs(now) => Some(lease.holder.into()),
+                _ => None,
+            },
+            None => None,
+        })
+    }
+
+    /// Removes entities left in the staging area, which were last modified before `before`,
+    /// and returns their number.
+    ///
+    /// Unlike [Self::new], which removes all entities left in the staging area, this may be
+    /// called while other instances sharing the store write to it.
+    pub async fn sweep_staging(&self, before: SystemTime) -> anyhow::Result<usize> {
+        let mut stale = vec![];
+        for entry in self
+            .root
+            .read_dir(STAGING_DIR)
+            .await
+            .context("failed to read staging area")?
+        {
+            let entry = entry.context("failed to read staging area entry")?;
+            let meta = match entry.metadata() {
+                Ok(meta) => meta,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e).context("failed to read staging area entry metadata"),
+            };
+            let modified = meta
+                .modified()
+                .context("failed to read staging area entry modification time")?
+                .into_std();
+            if modified < before {
+                let name = entry
+                    .file_name()
+                    .context("failed to read staging area entry name")?;
+                stale.push((format!("{STAGING_DIR}/{name}"), meta.is_dir()));
+            }
+        }
+        let mut removed = 0;
+        for (path, is_dir) in stale {
+            let res = if is_dir {
+                self.root.remove_dir_all(&path).await
+            } else {
+                self.root.remove_file(&path).await
+            };
+            match res {
+                Ok(()) => removed += 1,
+                Err(e) if e.kind() == ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| format!("failed to remove `{path}`")),
+            }
+        }
+        Ok(removed)
+    }
+}
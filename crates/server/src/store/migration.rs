@@ -0,0 +1,56 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Store, STAGING_DIR};
+
+use std::collections::BTreeSet;
+use std::io::ErrorKind;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Path of the progress of a migration into the store relative to the store root.
+const MIGRATION_PATH: &str = "migration.json";
+
+/// Progress of a migration of all entities of another store into the store.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct MigrationProgress {
+    /// Repositories migrated in `owner/name` form
+    pub repositories: BTreeSet<String>,
+    /// Time the migration completed at in RFC 3339 format, after which the source store is no
+    /// longer read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed: Option<String>,
+}
+
+impl Store {
+    /// Reads the progress of a migration into the store last written by
+    /// [Self::write_migration].
+    ///
+    /// Returns no progress if none was written yet.
+    pub async fn read_migration(&self) -> anyhow::Result<MigrationProgress> {
+        match self.root.read(MIGRATION_PATH).await {
+            Ok(buf) => serde_json::from_slice(&buf).context("failed to decode migration progress"),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Default::default()),
+            Err(e) => Err(e).context("failed to read migration progress"),
+        }
+    }
+
+    /// Replaces the progress of a migration into the store.
+    ///
+    /// The progress is written to the staging area first and atomically renamed, so that a
+    /// crash never leaves it partially written.
+    pub async fn write_migration(&self, progress: &MigrationProgress) -> anyhow::Result<()> {
+        let buf = serde_json::to_vec(progress).context("failed to encode migration progress")?;
+        let tmp = format!("{STAGING_DIR}/{MIGRATION_PATH}.{}", Uuid::new_v4());
+        self.root
+            .write(&tmp, buf)
+            .await
+            .context("failed to write migration progress")?;
+        self.root
+            .rename(&tmp, &self.root, MIGRATION_PATH)
+            .await
+            .context("failed to rename migration progress")
+    }
+}
@@ -1,24 +1,40 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
+mod changes;
+mod check;
 mod entity;
+mod hosts;
+mod lease;
+mod migration;
+mod nonces;
 mod repo;
+mod retention;
 mod tag;
+mod trash;
 mod tree;
+mod tuf;
+mod uploads;
+mod usage;
 mod user;
 
+pub use check::*;
 pub use entity::*;
+pub use migration::*;
 pub use repo::*;
 pub use tag::*;
 pub use tree::*;
 pub use user::*;
 
-use drawbridge_type::{Meta, RepositoryContext, TagContext, TreeContext, UserContext, UserRecord};
+use drawbridge_type::{
+    Meta, RepositoryContext, TagContext, TagEntry, TreeContext, UserContext, UserName, UserRecord,
+};
 
+use anyhow::Context;
 use async_std::io;
 use camino::{Utf8Path, Utf8PathBuf};
 use cap_async_std::fs_utf8::Dir;
-use futures::try_join;
+use futures::{try_join, AsyncRead};
 
 #[derive(Debug)]
 pub struct Store {
@@ -37,7 +53,24 @@ async fn upsert_dir(root: &Dir, path: impl AsRef<Utf8Path>) -> io::Result<()> {
 impl Store {
     /// Initalizes a new [Store] at `root`
     pub async fn new(root: Dir) -> io::Result<Self> {
+        // NOTE: Entities left in the staging area were never committed, e.g. due to a crash.
+        if root.is_dir(STAGING_DIR).await {
+            root.remove_dir_all(STAGING_DIR).await?;
+        }
+        Self::new_shared(root).await
+    }
+
+    /// Initalizes a new [Store] at `root` like [Self::new], but keeps entities left in the
+    /// staging area, since other instances sharing the store may be writing them.
+    ///
+    /// Stale entities are removed by [Self::sweep_staging].
+    pub async fn new_shared(root: Dir) -> io::Result<Self> {
         upsert_dir(&root, "users").await?;
+        upsert_dir(&root, STAGING_DIR).await?;
+        upsert_dir(&root, "quarantine").await?;
+        upsert_dir(&root, changes::CHANGES_DIR).await?;
+        upsert_dir(&root, lease::LEASES_DIR).await?;
+        upsert_dir(&root, tuf::TUF_DIR).await?;
         Ok(Self { root })
     }
 
@@ -47,6 +80,28 @@ impl Store {
             .into()
     }
 
+    pub async fn users(&self) -> Result<Vec<UserName>, GetError<anyhow::Error>> {
+        Entity::new(&self.root)
+            .read_dir("users")
+            .await?
+            .try_fold(vec![], |mut names, entry| {
+                let name = entry?
+                    .file_name()
+                    .context("failed to read user name")?
+                    .parse()
+                    .context("failed to parse user name")?;
+                names.push(name);
+                Ok(names)
+            })
+            .map_err(GetError::Internal)
+    }
+
+    /// Returns the number of bytes of all files in directory `dir` of the store root, e.g.
+    /// `quarantine`.
+    pub async fn disk_usage(&self, dir: &str) -> anyhow::Result<u64> {
+        Entity::new(&self.root).child(dir).disk_usage().await
+    }
+
     pub async fn create_user(
         &self,
         cx: &UserContext,
@@ -73,4 +128,42 @@ impl Store {
     pub fn tree<'a>(&'a self, TreeContext { tag, path }: &'a TreeContext) -> Node<'_> {
         self.tag(tag).node(path)
     }
+
+    /// Writes contents read from `rdr` to the staging area, so that contents of digests not
+    /// known upfront can be verified before they are created in the store.
+    pub async fn stage(&self, rdr: impl Unpin + AsyncRead) -> anyhow::Result<Staged<'_>> {
+        Staged::create(&self.root, rdr).await
+    }
+
+    /// Returns the tag caching the image with manifest `digest` pulled from repository `name`
+    /// of the upstream registry proxied under `namespace`.
+    pub fn proxy_tag(&self, namespace: &str, name: &str, digest: &str) -> Tag<'_> {
+        Entity::new(&self.root)
+            .child(format!("proxy/{namespace}/{name}/{digest}"))
+            .into()
+    }
+
+    /// Creates the tag returned by [Self::proxy_tag] along with all of its ancestors.
+    pub async fn create_proxy_tag(
+        &self,
+        namespace: &str,
+        name: &str,
+        digest: &str,
+        meta: Meta,
+        entry: &TagEntry,
+    ) -> Result<Tag<'_>, CreateError<anyhow::Error>> {
+        let root = Entity::new(&self.root);
+        let path = Utf8PathBuf::from(format!("proxy/{namespace}/{name}/{digest}"));
+        let mut ancestors: Vec<_> = path.ancestors().collect();
+        ancestors.reverse();
+        for dir in ancestors.into_iter().filter(|dir| !dir.as_str().is_empty()) {
+            match root.create_dir(dir).await {
+                Ok(()) | Err(CreateError::Occupied) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let tag = self.proxy_tag(namespace, name, digest);
+        tag.create_json(meta, entry).await?;
+        Ok(tag)
+    }
 }
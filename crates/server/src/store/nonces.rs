@@ -0,0 +1,46 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Store, STAGING_DIR};
+
+use drawbridge_type::Timestamp;
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+
+use anyhow::Context;
+use uuid::Uuid;
+
+/// Path of nonces of accepted publications relative to the store root.
+const NONCES_PATH: &str = "nonces.json";
+
+impl Store {
+    /// Reads nonces of accepted publications along with the times they expire at last
+    /// written by [Self::write_nonces].
+    ///
+    /// Returns no nonces if none were written yet.
+    pub async fn read_nonces(&self) -> anyhow::Result<BTreeMap<String, Timestamp>> {
+        match self.root.read(NONCES_PATH).await {
+            Ok(buf) => serde_json::from_slice(&buf).context("failed to decode nonces"),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Default::default()),
+            Err(e) => Err(e).context("failed to read nonces"),
+        }
+    }
+
+    /// Replaces nonces of accepted publications.
+    ///
+    /// The nonces are written to the staging area first and atomically renamed, so that
+    /// a crash never leaves them partially written.
+    pub async fn write_nonces(&self, nonces: &BTreeMap<String, Timestamp>) -> anyhow::Result<()> {
+        let buf = serde_json::to_vec(nonces).context("failed to encode nonces")?;
+        let tmp = format!("{STAGING_DIR}/{NONCES_PATH}.{}", Uuid::new_v4());
+        self.root
+            .write(&tmp, buf)
+            .await
+            .context("failed to write nonces")?;
+        self.root
+            .rename(&tmp, &self.root, NONCES_PATH)
+            .await
+            .context("failed to rename nonces")
+    }
+}
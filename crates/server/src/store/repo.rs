@@ -1,15 +1,70 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
+use super::super::problem::Problem;
 use super::{CreateError, Entity, GetError, Tag};
 
 use std::ops::Deref;
 
+use drawbridge_jose::jws::{Flattened, General, Jws};
 use drawbridge_type::digest::{Algorithms, ContentDigest};
-use drawbridge_type::{Meta, RepositoryConfig, TagEntry, TagName};
+use drawbridge_type::{
+    Error, MediaType, Meta, PendingChange, RepositoryConfig, TagEntry, TagName, TreeEntry, TreePath,
+};
 
 use anyhow::{anyhow, Context};
+use axum::response::{IntoResponse, Response};
 use camino::{Utf8Path, Utf8PathBuf};
+use futures::try_join;
+use serde::Serialize;
+
+/// Name of the auxiliary file of a pending change of a protected tag, which describes it.
+const REVIEW_FILE: &str = "review.json";
+
+/// Returns the tree entry of the tree root referenced by tag `entry`, which is the payload of
+/// signed entries. Aliases do not reference a tree.
+pub(crate) fn tree_entry(entry: &TagEntry) -> Option<TreeEntry> {
+    match entry {
+        TagEntry::Unsigned(entry) => Some(entry.clone()),
+        TagEntry::Signed(
+            Jws::General(General { payload, .. }) | Jws::Flattened(Flattened { payload, .. }),
+        ) => serde_json::from_slice(payload.as_ref()?).ok(),
+        TagEntry::Alias(..) => None,
+    }
+}
+
+/// Returns metadata of the tree root referenced by tag `entry`.
+pub(crate) fn tree_root(entry: &TagEntry) -> Option<Meta> {
+    tree_entry(entry).map(|entry| entry.meta)
+}
+
+#[derive(Debug)]
+pub enum PullTagError<E> {
+    Get(GetError<E>),
+    /// The vulnerability policy of the repository blocks pulls of the tag
+    Blocked(Error),
+}
+
+impl<E> From<GetError<E>> for PullTagError<E> {
+    fn from(e: GetError<E>) -> Self {
+        Self::Get(e)
+    }
+}
+
+impl<E> From<PullTagError<E>> for Error {
+    fn from(e: PullTagError<E>) -> Self {
+        match e {
+            PullTagError::Get(e) => e.into(),
+            PullTagError::Blocked(e) => e,
+        }
+    }
+}
+
+impl<E> IntoResponse for PullTagError<E> {
+    fn into_response(self) -> Response {
+        Problem::from(Error::from(self)).into_response()
+    }
+}
 
 #[repr(transparent)]
 #[derive(Copy, Clone, Debug)]
@@ -39,8 +94,10 @@ impl<'a, P: AsRef<Utf8Path>> Repository<'a, P> {
         Ok(conf.public)
     }
 
+    /// Returns names of all tags of the repository ordered by version precedence.
     pub async fn tags(&self) -> Result<Vec<TagName>, GetError<anyhow::Error>> {
-        self.read_dir("tags")
+        let mut names = self
+            .read_dir("tags")
             .await?
             .try_fold(vec![], |mut names, entry| {
                 let name = entry?
@@ -51,7 +108,23 @@ impl<'a, P: AsRef<Utf8Path>> Repository<'a, P> {
                 names.push(name);
                 Ok(names)
             })
-            .map_err(GetError::Internal)
+            .map_err(GetError::Internal)?;
+        names.sort();
+        Ok(names)
+    }
+
+    /// Returns the total size of contents of all tree nodes of all tags of the repository, see
+    /// [Tag::stored_size].
+    pub async fn stored_size(&self) -> Result<u64, GetError<anyhow::Error>> {
+        let mut size = 0;
+        for name in self.tags().await? {
+            size += match self.tag(&name).stored_size().await {
+                Ok(n) => n,
+                Err(GetError::NotFound) => 0,
+                Err(e) => return Err(e),
+            };
+        }
+        Ok(size)
     }
 
     pub async fn tags_json(&self) -> Result<(ContentDigest, Vec<u8>), GetError<anyhow::Error>> {
@@ -73,19 +146,235 @@ impl<'a, P: AsRef<Utf8Path>> Repository<'a, P> {
         Ok((hash, buf))
     }
 
+    /// Returns names of all server-managed signing keys of the repository ordered by name,
+    /// which is the order of their creation.
+    pub async fn key_names(&self) -> Result<Vec<String>, GetError<anyhow::Error>> {
+        let mut names = match self.read_dir("keys").await {
+            Ok(entries) => entries
+                .map(|entry| entry?.file_name().context("failed to read key name"))
+                .collect::<anyhow::Result<Vec<_>>>()
+                .map_err(GetError::Internal)?,
+            Err(GetError::NotFound) => vec![],
+            Err(e) => return Err(e),
+        };
+        names.sort();
+        Ok(names)
+    }
+
+    pub fn key(&self, name: &str) -> Entity<'a, Utf8PathBuf> {
+        self.child(format!("keys/{name}"))
+    }
+
+    /// Creates server-managed signing key `name` of the repository with JSON contents `key`.
+    pub async fn create_key(
+        &self,
+        name: &str,
+        key: &impl Serialize,
+    ) -> Result<Entity<'a, Utf8PathBuf>, CreateError<anyhow::Error>> {
+        let buf = serde_json::to_vec(key)
+            .context("failed to encode key")
+            .map_err(CreateError::Internal)?;
+        let (size, hash) = Algorithms::default()
+            .read_sync(&buf[..])
+            .context("failed to compute key digest")
+            .map_err(CreateError::Internal)?;
+        match self.create_dir("keys").await {
+            Ok(()) | Err(CreateError::Occupied) => {}
+            Err(e) => return Err(e),
+        }
+        let entity = self.key(name);
+        entity.create_dir("").await?;
+        entity
+            .create_from_reader(
+                Meta {
+                    hash,
+                    size,
+                    mime: MediaType::JSON,
+                },
+                buf.as_slice(),
+            )
+            .await?;
+        Ok(entity)
+    }
+
+    /// Returns IDs of all pending changes of protected tags of the repository.
+    pub async fn review_ids(&self) -> Result<Vec<String>, GetError<anyhow::Error>> {
+        let mut ids = match self.read_dir("reviews").await {
+            Ok(entries) => entries
+                .map(|entry| entry?.file_name().context("failed to read review ID"))
+                .collect::<anyhow::Result<Vec<_>>>()
+                .map_err(GetError::Internal)?,
+            Err(GetError::NotFound) => vec![],
+            Err(e) => return Err(e),
+        };
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Returns the pending change `id`, whose contents are the tag entry published.
+    pub fn review(&self, id: &str) -> Entity<'a, Utf8PathBuf> {
+        self.child(format!("reviews/{id}"))
+    }
+
+    /// Returns the pending change `id` along with the tag entry published.
+    pub async fn get_review(
+        &self,
+        id: &str,
+    ) -> Result<(PendingChange, Meta, TagEntry), GetError<anyhow::Error>> {
+        let review = self.review(id);
+        let (change, meta, buf) = try_join!(
+            review.get_aux_json(REVIEW_FILE),
+            review.get_meta(),
+            review.read_content()
+        )?;
+        let entry = TagEntry::decode(meta.mime.essence(), &buf).map_err(GetError::Internal)?;
+        Ok((change, meta, entry))
+    }
+
+    /// Records `change` of a protected tag to `entry` pending approval.
+    pub async fn create_review(
+        &self,
+        change: &PendingChange,
+        meta: Meta,
+        entry: &TagEntry,
+    ) -> Result<Entity<'a, Utf8PathBuf>, CreateError<anyhow::Error>> {
+        match self.create_dir("reviews").await {
+            Ok(()) | Err(CreateError::Occupied) => {}
+            Err(e) => return Err(e),
+        }
+        let review = self.review(&change.id);
+        review.create_dir("").await?;
+        review.create_json(meta, entry).await?;
+        review
+            .put_aux_json(REVIEW_FILE, change)
+            .await
+            .map_err(CreateError::Internal)?;
+        Ok(review)
+    }
+
+    /// Removes pending change `id`, once it is approved, discarded or expired.
+    pub async fn remove_review(&self, id: &str) -> anyhow::Result<()> {
+        self.review(id).remove().await
+    }
+
     pub fn tag(&self, name: &TagName) -> Tag<'a, Utf8PathBuf> {
         self.child(format!("tags/{name}")).into()
     }
 
+    /// Returns tag `name` along with the repository configuration, if the vulnerability policy
+    /// of the repository permits pulling it.
+    ///
+    /// All reads of tag entries and tree contents on behalf of clients go through this method,
+    /// whatever the protocol. Peers presenting a trusted certificate, requests for metadata only
+    /// and internal maintenance use [Self::tag] instead, which does not consult the policy.
+    pub async fn pull_tag(
+        &self,
+        name: &TagName,
+    ) -> Result<(Tag<'a, Utf8PathBuf>, RepositoryConfig), PullTagError<anyhow::Error>> {
+        let tag = self.tag(name);
+        let config = self.get_json().await?;
+        if let Some(policy) = &config.vulnerabilities {
+            let report = tag.latest_vulnerability_report().await?;
+            policy
+                .check(report.as_ref())
+                .map_err(PullTagError::Blocked)?;
+        }
+        Ok((tag, config))
+    }
+
+    /// Returns names of all tags of the repository ordered by version precedence, which the
+    /// vulnerability policy of the repository permits pulling, see [Self::pull_tag].
+    pub async fn pullable_tags(&self) -> Result<Vec<TagName>, GetError<anyhow::Error>> {
+        let (names, config) = try_join!(self.tags(), self.get_json())?;
+        let Some(policy) = &config.vulnerabilities else {
+            return Ok(names);
+        };
+        let mut pullable = vec![];
+        for name in names {
+            let report = self.tag(&name).latest_vulnerability_report().await?;
+            if policy.check(report.as_ref()).is_ok() {
+                pullable.push(name);
+            }
+        }
+        Ok(pullable)
+    }
+
+    /// Returns the pending tag `name`, whose tree is uploaded before the tag is published.
+    pub fn pending_tag(&self, name: &TagName) -> Tag<'a, Utf8PathBuf> {
+        self.child(format!("pending/{name}")).into()
+    }
+
+    /// Quarantines tag `name`, or the pending tag `name` if it is not published yet, and
+    /// returns its new path relative to the store root.
+    pub async fn quarantine_tag(&self, name: &TagName) -> anyhow::Result<Utf8PathBuf> {
+        match self.tag(name).get_meta().await {
+            Err(GetError::NotFound) => self.pending_tag(name).quarantine().await,
+            _ => self.tag(name).quarantine().await,
+        }
+    }
+
+    /// Returns the pending tag `name`, creating it if it does not exist yet.
+    pub async fn create_pending_tag(
+        &self,
+        name: &TagName,
+    ) -> Result<Tag<'a, Utf8PathBuf>, CreateError<anyhow::Error>> {
+        for dir in ["pending".into(), format!("pending/{name}")] {
+            match self.create_dir(dir).await {
+                Ok(()) | Err(CreateError::Occupied) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(self.pending_tag(name))
+    }
+
+    /// Creates tag `name` with `entry`.
+    ///
+    /// If a tree was uploaded to the pending tag `name`, the tag is published instead: the tree
+    /// must be complete and its root must match the one referenced by `entry`. The tag entry is
+    /// written to the pending tag, which is then moved into place, so that the tag and its tree
+    /// become visible at once.
     pub async fn create_tag(
         &self,
         name: &TagName,
         meta: Meta,
         entry: &TagEntry,
     ) -> Result<Tag<'a, Utf8PathBuf>, CreateError<anyhow::Error>> {
+        let pending = self.pending_tag(name);
+        let root = match pending.node(&TreePath::ROOT).get_meta().await {
+            Err(GetError::NotFound) => {
+                let tag = self.tag(name);
+                let claim = tag.claim().await?;
+                if let Err(e) = tag.create_json(meta, entry).await {
+                    claim.release().await;
+                    return Err(e);
+                }
+                claim.disarm();
+                return Ok(tag);
+            }
+            Err(GetError::Internal(e)) => return Err(CreateError::Internal(e)),
+            Ok(root) => root,
+        };
+        if tree_root(entry).is_none_or(|meta| meta.hash != root.hash) {
+            return Err(CreateError::DigestMismatch);
+        }
         let tag = self.tag(name);
-        tag.create_dir("").await?;
-        tag.create_json(meta, entry).await?;
-        Ok(tag)
+        let res = async {
+            match pending.is_complete().await {
+                Ok(true) => {}
+                Ok(false) | Err(GetError::NotFound) => return Err(CreateError::Incomplete),
+                Err(GetError::Internal(e)) => return Err(CreateError::Internal(e)),
+            }
+            pending.create_json(meta, entry).await?;
+            pending.move_to(&tag).await
+        }
+        .await;
+        match res {
+            Ok(()) => Ok(tag),
+            // NOTE: The pending tag was published by a concurrent request meanwhile.
+            Err(CreateError::Incomplete | CreateError::Internal(_)) if !pending.exists().await => {
+                Err(CreateError::Occupied)
+            }
+            Err(e) => Err(e),
+        }
     }
 }
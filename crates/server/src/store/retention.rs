@@ -0,0 +1,89 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{GetError, Repository, Tag, User};
+
+use std::time::SystemTime;
+
+use drawbridge_type::{RetentionPolicy, RetentionRun, TagName};
+
+use anyhow::Context;
+use camino::Utf8Path;
+
+/// Auxiliary file of a user containing its [RetentionPolicy].
+const RETENTION_FILE: &str = "retention.json";
+
+/// Auxiliary file of a user containing the last [RetentionRun] of its policy.
+const RETENTION_RUN_FILE: &str = "retention-run.json";
+
+impl<P: AsRef<Utf8Path>> User<'_, P> {
+    /// Returns the storage retention policy of the user last written by
+    /// [Self::put_retention_policy].
+    pub async fn get_retention_policy(&self) -> Result<RetentionPolicy, GetError<anyhow::Error>> {
+        self.get_aux_json(RETENTION_FILE).await
+    }
+
+    /// Replaces the storage retention policy of the user.
+    pub async fn put_retention_policy(&self, policy: &RetentionPolicy) -> anyhow::Result<()> {
+        self.put_aux_json(RETENTION_FILE, policy).await
+    }
+
+    /// Returns the last run of the storage retention policy of the user last written by
+    /// [Self::put_retention_run].
+    pub async fn get_retention_run(&self) -> Result<RetentionRun, GetError<anyhow::Error>> {
+        self.get_aux_json(RETENTION_RUN_FILE).await
+    }
+
+    /// Records `run` as the last run of the storage retention policy of the user.
+    pub async fn put_retention_run(&self, run: &RetentionRun) -> anyhow::Result<()> {
+        self.put_aux_json(RETENTION_RUN_FILE, run).await
+    }
+}
+
+impl<P: AsRef<Utf8Path>> Repository<'_, P> {
+    /// Returns names of all pending tags of the repository, see [Self::pending_tag], in order.
+    pub async fn pending_tags(&self) -> Result<Vec<TagName>, GetError<anyhow::Error>> {
+        let mut names = match self.read_dir("pending").await {
+            Ok(entries) => entries
+                .map(|entry| {
+                    entry?
+                        .file_name()
+                        .context("failed to read tag name")?
+                        .parse()
+                        .context("failed to parse tag name")
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+                .map_err(GetError::Internal)?,
+            Err(GetError::NotFound) => vec![],
+            Err(e) => return Err(e),
+        };
+        names.sort();
+        Ok(names)
+    }
+
+    /// Removes pending tag `name` along with the tree uploaded to it.
+    pub async fn remove_pending_tag(&self, name: &TagName) -> anyhow::Result<()> {
+        self.pending_tag(name).remove().await
+    }
+}
+
+impl<P: AsRef<Utf8Path>> Tag<'_, P> {
+    /// Returns the time a node was last uploaded to the tree of the tag at or, if none was,
+    /// the time the directory of the tag was last modified at.
+    pub async fn get_last_upload(&self) -> Result<SystemTime, GetError<anyhow::Error>> {
+        let mut last = self.get_dir_modified().await?;
+        let nodes = match self.walk().await {
+            Ok(nodes) => nodes,
+            Err(GetError::NotFound) => return Ok(last),
+            Err(e) => return Err(e),
+        };
+        for path in nodes.keys() {
+            match self.node(path).get_modified().await {
+                Ok(modified) => last = last.max(modified),
+                Err(GetError::NotFound) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(last)
+    }
+}
@@ -1,16 +1,106 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::{CreateError, Entity, Node};
+use super::{is_directory, CreateError, Entity, GetError, Node};
 
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
 use std::ops::Deref;
 
-use drawbridge_type::{Meta, TreeDirectory, TreeEntry, TreePath};
+use drawbridge_type::digest::ContentDigest;
+use drawbridge_type::{Meta, TagStats, TreeDirectory, TreeEntry, TreePath, VulnerabilityReport};
 
+use anyhow::{anyhow, Context};
+use axum::body::Bytes;
 use camino::{Utf8Path, Utf8PathBuf};
-use futures::{try_join, AsyncRead};
+use futures::io::Cursor;
+use futures::stream::{try_unfold, unfold, FuturesOrdered, Stream, StreamExt};
+use futures::{try_join, AsyncRead, AsyncReadExt};
 use tracing::debug;
 
+/// Name of the auxiliary file of a tag holding its [TagStats].
+const STATS_FILE: &str = "stats.json";
+
+/// Maximum number of tree nodes read ahead by [Tag::read_nodes].
+const EXPORT_PREFETCH: usize = 16;
+
+/// Maximum total size of contents of tree nodes read ahead by [Tag::read_nodes].
+const EXPORT_PREFETCH_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Path of a tree node and its contents, if it is a file node.
+type NodeContents = (TreePath, Option<NodeContent>);
+
+/// Contents of a file node read by [Tag::read_nodes], which are verified against its digest.
+pub enum NodeContent {
+    /// Contents read ahead into memory
+    Buffered(Bytes),
+    /// Contents of the given size exceeding [EXPORT_PREFETCH_SIZE], which are verified as
+    /// they are read
+    Streamed(u64, Box<dyn Send + Unpin + AsyncRead>),
+}
+
+impl fmt::Debug for NodeContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Buffered(buf) => f.debug_tuple("Buffered").field(buf).finish(),
+            Self::Streamed(size, _) => f.debug_tuple("Streamed").field(size).finish(),
+        }
+    }
+}
+
+impl NodeContent {
+    /// Returns the size of the contents.
+    pub fn size(&self) -> u64 {
+        match self {
+            Self::Buffered(buf) => buf.len() as _,
+            Self::Streamed(size, _) => *size,
+        }
+    }
+
+    /// Returns a stream of the contents in chunks of `size` bytes, except for the last one.
+    ///
+    /// Empty contents consist of a single empty chunk. Streamed contents not matching the
+    /// digest of the node fail the stream once they are read to the end.
+    pub fn chunks(self, size: usize) -> impl Send + Unpin + Stream<Item = io::Result<Bytes>> {
+        let rdr: Box<dyn Send + Unpin + AsyncRead> = match self {
+            Self::Buffered(buf) => Box::new(Cursor::new(buf)),
+            Self::Streamed(_, rdr) => rdr,
+        };
+        Box::pin(try_unfold(Some((rdr, true)), move |state| async move {
+            let (mut rdr, first) = match state {
+                Some(state) => state,
+                None => return Ok(None),
+            };
+            let mut buf = Vec::with_capacity(size);
+            match (&mut rdr).take(size as _).read_to_end(&mut buf).await? {
+                0 if !first => Ok(None),
+                n if n < size => Ok(Some((buf.into(), None))),
+                _ => Ok(Some((buf.into(), Some((rdr, false))))),
+            }
+        }))
+    }
+}
+
+/// Opens contents of `node` described by `meta` for verified reading and reads them into
+/// memory, unless they exceed [EXPORT_PREFETCH_SIZE].
+async fn read_node(
+    node: &Entity<'_, impl AsRef<Utf8Path>>,
+    meta: Meta,
+) -> Result<NodeContent, GetError<anyhow::Error>> {
+    let mut rdr = meta.hash.verifier(node.get_content().await?);
+    if meta.size > EXPORT_PREFETCH_SIZE {
+        return Ok(NodeContent::Streamed(meta.size, Box::new(rdr)));
+    }
+    let mut buf = Vec::with_capacity(meta.size as _);
+    _ = rdr
+        .read_to_end(&mut buf)
+        .await
+        .context("failed to read verified content")
+        .map_err(GetError::Internal)?;
+    Ok(NodeContent::Buffered(buf.into()))
+}
+
 #[repr(transparent)]
 #[derive(Copy, Clone, Debug)]
 pub struct Tag<'a, P = Utf8PathBuf>(Entity<'a, P>);
@@ -40,6 +130,276 @@ impl<'a, P: AsRef<Utf8Path>> Tag<'a, P> {
         }
     }
 
+    /// Returns download statistics of the tag last written by [Self::put_stats].
+    ///
+    /// Returns empty statistics if none were written yet.
+    pub async fn get_stats(&self) -> Result<TagStats, GetError<anyhow::Error>> {
+        match self.get_aux_json(STATS_FILE).await {
+            Err(GetError::NotFound) => Ok(Default::default()),
+            res => res,
+        }
+    }
+
+    /// Replaces download statistics of the tag.
+    pub async fn put_stats(&self, stats: &TagStats) -> anyhow::Result<()> {
+        self.put_aux_json(STATS_FILE, stats).await
+    }
+
+    /// Returns metadata of all nodes in the tree of the tag, ordered by path.
+    ///
+    /// Directories are traversed using the stored directory entities, so that
+    /// metadata of the children is read from their parent directory.
+    pub async fn walk(&self) -> Result<BTreeMap<TreePath, Meta>, GetError<anyhow::Error>> {
+        let root = self.node(&TreePath::ROOT).get_meta().await?;
+        let mut dirs = vec![];
+        if is_directory(&root) {
+            dirs.push(TreePath::ROOT);
+        }
+        let mut nodes = BTreeMap::from([(TreePath::ROOT, root)]);
+        while let Some(path) = dirs.pop() {
+            let dir: TreeDirectory<TreeEntry> = self.node(&path).get_content_json().await?;
+            for (name, TreeEntry { meta, .. }) in dir.iter() {
+                let path = path.join(name.clone());
+                if is_directory(meta) {
+                    dirs.push(path.clone());
+                }
+                _ = nodes.insert(path, meta.clone());
+            }
+        }
+        Ok(nodes)
+    }
+
+    /// Returns the total size of contents of all nodes in the tree of the tag, which have been
+    /// created, as accounted when they were uploaded.
+    ///
+    /// Unlike [Self::walk], the tree need not be complete.
+    pub async fn stored_size(&self) -> Result<u64, GetError<anyhow::Error>> {
+        let mut size = 0;
+        let mut paths = vec![TreePath::ROOT];
+        while let Some(path) = paths.pop() {
+            let node = self.node(&path);
+            let meta = match node.get_meta().await {
+                Ok(meta) => meta,
+                Err(GetError::NotFound) => continue,
+                Err(e) => return Err(e),
+            };
+            size += meta.size;
+            if is_directory(&meta) {
+                let dir: TreeDirectory<TreeEntry> = node.get_content_json().await?;
+                paths.extend(dir.keys().map(|name| path.join(name.clone())));
+            }
+        }
+        Ok(size)
+    }
+
+    /// Returns a stream of paths of `nodes` along with contents of the file nodes among them,
+    /// in order.
+    ///
+    /// Contents of up to [EXPORT_PREFETCH] upcoming nodes are read concurrently, while the
+    /// current one is being consumed, so that exports are not gated on sequential reads.
+    /// Nodes are only read ahead while their total size does not exceed [EXPORT_PREFETCH_SIZE],
+    /// which bounds the memory held by streamed exports. Contents of nodes exceeding it on
+    /// their own are not read ahead, but streamed as they are consumed.
+    ///
+    /// All contents are verified against the digests of the nodes in `nodes`.
+    pub fn read_nodes<'b, I>(
+        &'b self,
+        nodes: I,
+    ) -> impl 'b + Stream<Item = Result<NodeContents, GetError<anyhow::Error>>>
+    where
+        I: IntoIterator<Item = (TreePath, Meta)>,
+        I::IntoIter: 'b,
+    {
+        let read = move |path: TreePath, meta: Meta| async move {
+            if is_directory(&meta) {
+                return (Ok((path, None)), 0);
+            }
+            let size = meta.size;
+            let content = read_node(&self.node(&path), meta).await;
+            (content.map(|content| (path, Some(content))), size)
+        };
+        let state = (nodes.into_iter().peekable(), FuturesOrdered::new(), 0);
+        Box::pin(unfold(
+            state,
+            move |(mut nodes, mut reads, mut size)| async move {
+                while reads.len() < EXPORT_PREFETCH {
+                    match nodes.peek() {
+                        Some((_, meta))
+                            if reads.is_empty()
+                                || is_directory(meta)
+                                || size + meta.size <= EXPORT_PREFETCH_SIZE => {}
+                        _ => break,
+                    }
+                    let (path, meta) = nodes.next()?;
+                    if !is_directory(&meta) {
+                        size += meta.size;
+                    }
+                    reads.push_back(read(path, meta));
+                }
+                let (res, n) = reads.next().await?;
+                Some((res, (nodes, reads, size - n)))
+            },
+        ))
+    }
+
+    /// Returns `true` if all nodes of the tree of the tag have been created.
+    pub async fn is_complete(&self) -> Result<bool, GetError<anyhow::Error>> {
+        let nodes = match self.walk().await {
+            Err(GetError::NotFound) => return Ok(false),
+            Err(e) => return Err(e),
+            Ok(nodes) => nodes,
+        };
+        for (path, meta) in nodes {
+            if is_directory(&meta) {
+                continue;
+            }
+            match self.node(&path).get_meta().await {
+                Err(GetError::NotFound) => return Ok(false),
+                Err(e) => return Err(e),
+                Ok(_) => {}
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns names of all entities in directory `dir` of the tag in order.
+    async fn child_names(&self, dir: &str) -> Result<Vec<String>, GetError<anyhow::Error>> {
+        let mut names = match self.read_dir(dir).await {
+            Err(GetError::NotFound) => return Ok(vec![]),
+            Err(e) => return Err(e),
+            Ok(entries) => entries
+                .map(|entry| entry?.file_name().context("failed to read entity name"))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(GetError::Internal)?,
+        };
+        names.sort();
+        Ok(names)
+    }
+
+    /// Returns contents of all entities in directory `dir` of the tag, ordered by name.
+    async fn read_children(&self, dir: &str) -> Result<Vec<Vec<u8>>, GetError<anyhow::Error>> {
+        let names = self.child_names(dir).await?;
+        let mut children = Vec::with_capacity(names.len());
+        for name in names {
+            children.push(self.child(format!("{dir}/{name}")).read_content().await?);
+        }
+        Ok(children)
+    }
+
+    /// Returns all entities in directory `dir` of the tag keyed by name, ordered by name.
+    async fn children(
+        &self,
+        dir: &str,
+    ) -> Result<Vec<(String, Entity<'a, Utf8PathBuf>)>, GetError<anyhow::Error>> {
+        Ok(self
+            .child_names(dir)
+            .await?
+            .into_iter()
+            .map(|name| {
+                let child = self.child(format!("{dir}/{name}"));
+                (name, child)
+            })
+            .collect())
+    }
+
+    /// Creates entity `name` in directory `dir` of the tag, creating `dir` if necessary.
+    async fn create_child(
+        &self,
+        dir: &str,
+        name: &str,
+        meta: Meta,
+        rdr: impl Unpin + AsyncRead,
+    ) -> Result<Entity<'a, Utf8PathBuf>, CreateError<anyhow::Error>> {
+        match self.create_dir(dir).await {
+            Ok(()) | Err(CreateError::Occupied) => {}
+            Err(e) => return Err(e),
+        }
+        let child = self.child(format!("{dir}/{name}"));
+        child.create_dir("").await?;
+        child.create_from_reader(meta, rdr).await?;
+        Ok(child)
+    }
+
+    pub fn sbom(&self) -> Entity<'a, Utf8PathBuf> {
+        self.0.child("sbom")
+    }
+
+    pub async fn create_sbom(
+        &self,
+        meta: Meta,
+        rdr: impl Unpin + AsyncRead,
+    ) -> Result<Entity<'a, Utf8PathBuf>, CreateError<anyhow::Error>> {
+        let sbom = self.sbom();
+        sbom.create_dir("").await?;
+        sbom.create_from_reader(meta, rdr).await?;
+        Ok(sbom)
+    }
+
+    /// Returns contents of all signatures attached to the tag, ordered by name.
+    pub async fn signatures(&self) -> Result<Vec<Vec<u8>>, GetError<anyhow::Error>> {
+        self.read_children("signatures").await
+    }
+
+    /// Returns all signatures attached to the tag keyed by name.
+    pub async fn signature_entities(
+        &self,
+    ) -> Result<Vec<(String, Entity<'a, Utf8PathBuf>)>, GetError<anyhow::Error>> {
+        self.children("signatures").await
+    }
+
+    pub async fn create_signature(
+        &self,
+        name: &str,
+        meta: Meta,
+        rdr: impl Unpin + AsyncRead,
+    ) -> Result<Entity<'a, Utf8PathBuf>, CreateError<anyhow::Error>> {
+        self.create_child("signatures", name, meta, rdr).await
+    }
+
+    /// Returns contents of all attestations attached to the tag, ordered by name.
+    pub async fn attestations(&self) -> Result<Vec<Vec<u8>>, GetError<anyhow::Error>> {
+        self.read_children("attestations").await
+    }
+
+    /// Returns all attestations attached to the tag keyed by name.
+    pub async fn attestation_entities(
+        &self,
+    ) -> Result<Vec<(String, Entity<'a, Utf8PathBuf>)>, GetError<anyhow::Error>> {
+        self.children("attestations").await
+    }
+
+    pub async fn create_attestation(
+        &self,
+        name: &str,
+        meta: Meta,
+        rdr: impl Unpin + AsyncRead,
+    ) -> Result<Entity<'a, Utf8PathBuf>, CreateError<anyhow::Error>> {
+        self.create_child("attestations", name, meta, rdr).await
+    }
+
+    /// Decodes the latest vulnerability report attached to the tag, SARIF logs are converted.
+    pub async fn latest_vulnerability_report(
+        &self,
+    ) -> Result<Option<VulnerabilityReport>, GetError<anyhow::Error>> {
+        let Some((_, report)) = self.children("vulnerabilities").await?.pop() else {
+            return Ok(None);
+        };
+        let (meta, buf) = try_join!(report.get_meta(), report.read_content())?;
+        VulnerabilityReport::decode(meta.mime.essence(), &buf)
+            .map(Some)
+            .map_err(GetError::Internal)
+    }
+
+    /// Attaches vulnerability report `name`, reports are ordered by name, the latest last.
+    pub async fn create_vulnerability_report(
+        &self,
+        name: &str,
+        meta: Meta,
+        rdr: impl Unpin + AsyncRead,
+    ) -> Result<Entity<'a, Utf8PathBuf>, CreateError<anyhow::Error>> {
+        self.create_child("vulnerabilities", name, meta, rdr).await
+    }
+
     pub async fn create_file_node(
         &self,
         path: &TreePath,
@@ -49,14 +409,67 @@ impl<'a, P: AsRef<Utf8Path>> Tag<'a, P> {
         // TODO: Validate node hash against parents' expected values
         // https://github.com/profianinc/drawbridge/issues/77
         let node = self.node(path);
-        node.create_dir("").await.map_err(|e| {
+        let claim = node.claim().await.map_err(|e| {
             debug!(target: "app::store::Tag::create_file_node", "failed to create content directory: {:?}", e);
             e
         })?;
-        node.create_from_reader(meta, rdr).await?;
+        if let Err(e) = node.create_from_reader(meta, rdr).await {
+            claim.release().await;
+            return Err(e);
+        }
+        claim.disarm();
         Ok(node)
     }
 
+    /// Creates a file node like [Self::create_file_node], but only verifies the strongest
+    /// algorithm of the content digest inline and returns the digests of remaining algorithms,
+    /// which must be verified later using [Entity::verify].
+    pub async fn create_file_node_deferred(
+        &self,
+        path: &TreePath,
+        meta: Meta,
+        rdr: impl Unpin + AsyncRead,
+    ) -> Result<(Node<'a, Utf8PathBuf>, ContentDigest), CreateError<anyhow::Error>> {
+        let node = self.node(path);
+        let claim = node.claim().await.map_err(|e| {
+            debug!(target: "app::store::Tag::create_file_node_deferred", "failed to create content directory: {:?}", e);
+            e
+        })?;
+        match node.create_from_reader_deferred(meta, rdr).await {
+            Ok(deferred) => {
+                claim.disarm();
+                Ok((node, deferred))
+            }
+            Err(e) => {
+                claim.release().await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Quarantines node at `path`, which failed verification, and returns its new path
+    /// relative to the store root.
+    pub async fn quarantine_node(&self, path: &TreePath) -> anyhow::Result<Utf8PathBuf> {
+        self.node(path).quarantine().await
+    }
+
+    /// Replaces contents of node at `path`, which failed verification, with contents read
+    /// from `rdr`, which are verified against the metadata of the node, and returns the
+    /// metadata.
+    pub async fn repair_node(
+        &self,
+        path: &TreePath,
+        rdr: impl Unpin + AsyncRead,
+    ) -> Result<Meta, CreateError<anyhow::Error>> {
+        let node = self.node(path);
+        let meta = node.get_meta().await.map_err(|e| match e {
+            GetError::NotFound => CreateError::Internal(anyhow!("node not found")),
+            GetError::Internal(e) => CreateError::Internal(e),
+        })?;
+        node.replace_content(&meta, rdr).await?;
+        Ok(meta)
+    }
+
     pub async fn create_directory_node(
         &self,
         path: &TreePath,
@@ -66,11 +479,15 @@ impl<'a, P: AsRef<Utf8Path>> Tag<'a, P> {
         // TODO: Validate node hash against parents' expected values
         // https://github.com/profianinc/drawbridge/issues/77
         let node = self.node(path);
-        node.create_dir("").await.map_err(|e| {
+        let claim = node.claim().await.map_err(|e| {
             debug!(target: "app::store::Tag::create_directory_node", "failed to create content directory: {:?}", e);
             e
         })?;
-        try_join!(node.create_json(meta, dir), node.create_dir("entries"))?;
+        if let Err(e) = try_join!(node.create_json(meta, dir), node.create_dir("entries")) {
+            claim.release().await;
+            return Err(e);
+        }
+        claim.disarm();
         Ok(node)
     }
 }
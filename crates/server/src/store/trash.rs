@@ -0,0 +1,149 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{CreateError, Entity, GetError, Repository, Store, Tag, User};
+
+use std::time::SystemTime;
+
+use drawbridge_type::{TrashedEntity, UserContext};
+
+use anyhow::{anyhow, Context};
+use camino::{Utf8Path, Utf8PathBuf};
+use tracing::debug;
+
+/// Directory of the trash of a user relative to the user.
+const TRASH_DIR: &str = "trash";
+
+/// Auxiliary file of a trashed entity describing it.
+const TRASH_FILE: &str = "trash.json";
+
+/// Child of a trashed entity, which the entity deleted was moved to.
+const TRASH_ENTITY: &str = "entity";
+
+impl<'a, P: AsRef<Utf8Path>> User<'a, P> {
+    /// Returns IDs of all entities in the trash of the user.
+    pub async fn trash_ids(&self) -> Result<Vec<String>, GetError<anyhow::Error>> {
+        let mut ids = match self.read_dir(TRASH_DIR).await {
+            Ok(entries) => entries
+                .map(|entry| {
+                    entry?
+                        .file_name()
+                        .context("failed to read trashed entity ID")
+                })
+                .collect::<anyhow::Result<Vec<_>>>()
+                .map_err(GetError::Internal)?,
+            Err(GetError::NotFound) => vec![],
+            Err(e) => return Err(e),
+        };
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Returns the trashed entity `id`, whose child [TRASH_ENTITY] is the entity deleted.
+    fn trashed(&self, id: &str) -> Entity<'a, Utf8PathBuf> {
+        self.child(format!("{TRASH_DIR}/{id}"))
+    }
+
+    /// Returns the description of trashed entity `id`.
+    pub async fn get_trashed(&self, id: &str) -> Result<TrashedEntity, GetError<anyhow::Error>> {
+        self.trashed(id).get_aux_json(TRASH_FILE).await
+    }
+
+    /// Returns the number of bytes stored by trashed entity `id`.
+    pub async fn get_trashed_size(&self, id: &str) -> anyhow::Result<u64> {
+        self.trashed(id).disk_usage().await
+    }
+
+    /// Returns the total size of contents of all tree nodes of the entity deleted of `trashed`,
+    /// see [Repository::stored_size] and [Tag::stored_size].
+    pub async fn get_trashed_stored_size(
+        &self,
+        trashed: &TrashedEntity,
+    ) -> Result<u64, GetError<anyhow::Error>> {
+        let entity = self.trashed(&trashed.id).child(TRASH_ENTITY);
+        match trashed.tag {
+            Some(_) => Tag::from(entity).stored_size().await,
+            None => Repository::from(entity).stored_size().await,
+        }
+    }
+
+    /// Moves `entity`, a repository or tag of the user, into the trash described by `trashed`.
+    pub async fn trash(
+        &self,
+        entity: &Entity<'_, impl AsRef<Utf8Path>>,
+        trashed: &TrashedEntity,
+    ) -> Result<(), CreateError<anyhow::Error>> {
+        match self.create_dir(TRASH_DIR).await {
+            Ok(()) | Err(CreateError::Occupied) => {}
+            Err(e) => return Err(e),
+        }
+        let dir = self.trashed(&trashed.id);
+        dir.create_dir("").await?;
+        if let Err(e) = dir.put_aux_json(TRASH_FILE, trashed).await {
+            _ = dir.remove().await;
+            return Err(CreateError::Internal(e));
+        }
+        if let Err(e) = entity.move_to(&dir.child(TRASH_ENTITY)).await {
+            _ = dir.remove().await;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Moves the entity deleted of trashed entity `id` back to `to`, which must not exist, and
+    /// removes it from the trash.
+    pub async fn restore(
+        &self,
+        id: &str,
+        to: &Entity<'_, impl AsRef<Utf8Path>>,
+    ) -> Result<(), CreateError<anyhow::Error>> {
+        let dir = self.trashed(id);
+        dir.child(TRASH_ENTITY).move_to(to).await?;
+        if let Err(e) = dir.remove().await {
+            debug!(target: "app::store::User::restore", "failed to remove trashed entity `{id}`: {:?}", e);
+        }
+        Ok(())
+    }
+
+    /// Removes trashed entity `id` permanently.
+    pub async fn purge(&self, id: &str) -> anyhow::Result<()> {
+        self.trashed(id).remove().await
+    }
+}
+
+impl Store {
+    /// Removes entities in the trash of all users, which are expired at `now`, permanently and
+    /// returns their number.
+    ///
+    /// Entities without a description were never moved into the trash and are removed as well.
+    pub async fn purge_trash(&self, now: SystemTime) -> anyhow::Result<usize> {
+        let mut purged = 0;
+        let names = match self.users().await {
+            Ok(names) => names,
+            Err(GetError::NotFound) => return Ok(0),
+            Err(GetError::Internal(e)) => return Err(e.context("failed to list users")),
+        };
+        for name in names {
+            let cx = UserContext { name };
+            let user = self.user(&cx);
+            let ids = user.trash_ids().await.map_err(|e| match e {
+                GetError::NotFound => anyhow!("trash of `{cx}` not found"),
+                GetError::Internal(e) => e.context(format!("failed to list trash of `{cx}`")),
+            })?;
+            for id in ids {
+                match user.get_trashed(&id).await {
+                    Ok(trashed) if !trashed.is_expired(now) => continue,
+                    Ok(_) | Err(GetError::NotFound) => {}
+                    Err(GetError::Internal(e)) => {
+                        return Err(e.context(format!("failed to read trashed entity `{id}`")))
+                    }
+                }
+                user.purge(&id)
+                    .await
+                    .with_context(|| format!("failed to purge trashed entity `{id}` of `{cx}`"))?;
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+}
@@ -5,7 +5,11 @@ use super::Entity;
 
 use std::ops::Deref;
 
-use camino::Utf8PathBuf;
+use drawbridge_type::{
+    Meta, RepositoryContext, TagContext, TreeContext, TreeDirectory, UserContext,
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
 
 #[repr(transparent)]
 #[derive(Copy, Clone, Debug)]
@@ -24,3 +28,39 @@ impl<'a, P> From<Entity<'a, P>> for Node<'a, P> {
         Self(entity)
     }
 }
+
+/// Returns `true` if `meta` describes a directory node.
+pub(crate) fn is_directory(meta: &Meta) -> bool {
+    meta.mime.essence() == TreeDirectory::<()>::TYPE
+}
+
+/// Returns the context of the tree node of a published tag at `prefix` relative to the store
+/// root, if it is one.
+pub(crate) fn tree_context(prefix: &Utf8Path) -> Option<TreeContext> {
+    let components: Vec<_> = prefix.components().map(|c| c.as_str()).collect();
+    let (owner, repo, tag, entries) = match components.as_slice() {
+        ["users", owner, "repos", repo, "tags", tag, "tree", entries @ ..] => {
+            (owner, repo, tag, entries)
+        }
+        _ => return None,
+    };
+    let path = entries
+        .chunks(2)
+        .map(|chunk| match chunk {
+            ["entries", name] => Some(*name),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(TreeContext {
+        tag: TagContext {
+            repository: RepositoryContext {
+                owner: UserContext {
+                    name: owner.parse().ok()?,
+                },
+                name: repo.parse().ok()?,
+            },
+            name: tag.parse().ok()?,
+        },
+        path: path.join("/").parse().ok()?,
+    })
+}
@@ -0,0 +1,58 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Store, STAGING_DIR};
+
+use std::io::ErrorKind;
+
+use anyhow::{ensure, Context};
+use uuid::Uuid;
+
+/// Directory of TUF metadata relative to the store root.
+pub(super) const TUF_DIR: &str = "tuf";
+
+/// Returns whether `name` is a valid name of a TUF metadata file, e.g. `root.json` or
+/// `2.root.json`.
+fn is_metadata_name(name: &str) -> bool {
+    name.strip_suffix(".json").is_some_and(|stem| {
+        !stem.is_empty()
+            && stem
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+            && !stem.starts_with('.')
+            && !stem.contains("..")
+    })
+}
+
+impl Store {
+    /// Reads TUF metadata file `name` last written by [Self::write_tuf], if any.
+    ///
+    /// Returns no metadata if `name` is not a valid name of a metadata file.
+    pub async fn read_tuf(&self, name: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        if !is_metadata_name(name) {
+            return Ok(None);
+        }
+        match self.root.read(format!("{TUF_DIR}/{name}")).await {
+            Ok(buf) => Ok(Some(buf)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("failed to read TUF metadata `{name}`")),
+        }
+    }
+
+    /// Replaces TUF metadata file `name`.
+    ///
+    /// The file is written to the staging area first and atomically renamed, so that clients
+    /// never observe it partially written.
+    pub async fn write_tuf(&self, name: &str, buf: &[u8]) -> anyhow::Result<()> {
+        ensure!(is_metadata_name(name), "invalid TUF metadata name `{name}`");
+        let tmp = format!("{STAGING_DIR}/{TUF_DIR}.{name}.{}", Uuid::new_v4());
+        self.root
+            .write(&tmp, buf)
+            .await
+            .with_context(|| format!("failed to write TUF metadata `{name}`"))?;
+        self.root
+            .rename(&tmp, &self.root, format!("{TUF_DIR}/{name}"))
+            .await
+            .with_context(|| format!("failed to rename TUF metadata `{name}`"))
+    }
+}
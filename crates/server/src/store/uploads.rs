@@ -0,0 +1,93 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{GetError, Repository, Tag};
+
+use std::time::{Duration, SystemTime};
+
+use drawbridge_type::{TagName, Timestamp, UploadSession};
+
+use camino::Utf8Path;
+use serde::{Deserialize, Serialize};
+
+/// Auxiliary file of a pending tag recording the start of the upload of its tree.
+const UPLOAD_FILE: &str = "upload.json";
+
+/// Start of the upload of the tree of a pending tag.
+#[derive(Deserialize, Serialize)]
+struct UploadStart {
+    /// OpenID Connect subject of the uploader
+    owner: String,
+    started: Timestamp,
+}
+
+impl<P: AsRef<Utf8Path>> Tag<'_, P> {
+    /// Records that `owner` started uploading the tree of the pending tag at `now`, unless a
+    /// start was recorded before.
+    pub async fn start_upload(&self, owner: &str, now: SystemTime) -> anyhow::Result<()> {
+        match self.get_aux_json::<UploadStart>(UPLOAD_FILE).await {
+            Ok(_) => Ok(()),
+            Err(GetError::NotFound) => {
+                self.put_aux_json(
+                    UPLOAD_FILE,
+                    &UploadStart {
+                        owner: owner.into(),
+                        started: now.into(),
+                    },
+                )
+                .await
+            }
+            Err(GetError::Internal(e)) => Err(e),
+        }
+    }
+}
+
+impl<P: AsRef<Utf8Path>> Repository<'_, P> {
+    /// Returns the session uploading the tree of pending tag `name`, which expires once no
+    /// node was uploaded to it for `expiry`.
+    pub async fn upload_session(
+        &self,
+        name: &TagName,
+        expiry: Duration,
+    ) -> Result<UploadSession, GetError<anyhow::Error>> {
+        let pending = self.pending_tag(name);
+        let updated = pending.get_last_upload().await?;
+        let (owner, started) = match pending.get_aux_json::<UploadStart>(UPLOAD_FILE).await {
+            Ok(UploadStart { owner, started }) => (Some(owner), started),
+            // NOTE: Starts of uploads to pending tags created by older versions were not
+            // recorded.
+            Err(GetError::NotFound) => (None, pending.get_dir_modified().await?.into()),
+            Err(e) => return Err(e),
+        };
+        let complete = pending.is_complete().await?;
+        let received = pending.disk_usage().await.map_err(GetError::Internal)?;
+        Ok(UploadSession {
+            tag: name.clone(),
+            owner,
+            started,
+            updated: updated.into(),
+            received,
+            complete,
+            expires: (updated + expiry).into(),
+        })
+    }
+
+    /// Returns sessions uploading trees of all pending tags of the repository, see
+    /// [Self::upload_session], ordered by tag name.
+    pub async fn upload_sessions(
+        &self,
+        expiry: Duration,
+    ) -> Result<Vec<UploadSession>, GetError<anyhow::Error>> {
+        let mut sessions = vec![];
+        for name in self.pending_tags().await? {
+            match self.upload_session(&name, expiry).await {
+                Ok(session) => sessions.push(session),
+                // NOTE: The tag may have been published or the session cancelled in the
+                // meantime.
+                Err(GetError::NotFound) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(sessions)
+    }
+}
@@ -0,0 +1,45 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Store, STAGING_DIR};
+
+use drawbridge_type::Usage;
+
+use std::collections::BTreeMap;
+use std::io::ErrorKind;
+
+use anyhow::Context;
+use uuid::Uuid;
+
+/// Path of usage counters relative to the store root.
+const USAGE_PATH: &str = "usage.json";
+
+impl Store {
+    /// Reads usage counters keyed by repository last written by [Self::write_usage].
+    ///
+    /// Returns no counters if none were written yet.
+    pub async fn read_usage(&self) -> anyhow::Result<BTreeMap<String, Usage>> {
+        match self.root.read(USAGE_PATH).await {
+            Ok(buf) => serde_json::from_slice(&buf).context("failed to decode usage counters"),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Default::default()),
+            Err(e) => Err(e).context("failed to read usage counters"),
+        }
+    }
+
+    /// Replaces usage counters keyed by repository.
+    ///
+    /// The counters are written to the staging area first and atomically renamed, so that
+    /// a crash never leaves them partially written.
+    pub async fn write_usage(&self, usage: &BTreeMap<String, Usage>) -> anyhow::Result<()> {
+        let buf = serde_json::to_vec(usage).context("failed to encode usage counters")?;
+        let tmp = format!("{STAGING_DIR}/{USAGE_PATH}.{}", Uuid::new_v4());
+        self.root
+            .write(&tmp, buf)
+            .await
+            .context("failed to write usage counters")?;
+        self.root
+            .rename(&tmp, &self.root, USAGE_PATH)
+            .await
+            .context("failed to rename usage counters")
+    }
+}
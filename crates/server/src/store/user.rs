@@ -1,12 +1,13 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::{CreateError, Entity, Repository};
+use super::{CreateError, Entity, GetError, Repository};
 
 use std::ops::Deref;
 
 use drawbridge_type::{Meta, RepositoryConfig, RepositoryName};
 
+use anyhow::Context;
 use camino::{Utf8Path, Utf8PathBuf};
 use futures::try_join;
 
@@ -33,6 +34,21 @@ impl<'a, P: AsRef<Utf8Path>> User<'a, P> {
         self.0.child(format!("repos/{name}")).into()
     }
 
+    pub async fn repositories(&self) -> Result<Vec<RepositoryName>, GetError<anyhow::Error>> {
+        self.read_dir("repos")
+            .await?
+            .try_fold(vec![], |mut names, entry| {
+                let name = entry?
+                    .file_name()
+                    .context("failed to read repository name")?
+                    .parse()
+                    .context("failed to parse repository name")?;
+                names.push(name);
+                Ok(names)
+            })
+            .map_err(GetError::Internal)
+    }
+
     pub async fn create_repository(
         &self,
         name: &RepositoryName,
@@ -0,0 +1,70 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Streaming of contents in chunks, e.g. into response bodies.
+
+use std::future::Future;
+use std::io;
+
+use anyhow::anyhow;
+use async_std::channel::{bounded, Sender};
+use async_std::task::spawn;
+use axum::body::Bytes;
+use futures::stream::{try_unfold, Stream};
+use futures::{AsyncRead, AsyncReadExt};
+
+/// Maximum size of streamed chunks.
+pub(crate) const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Maximum number of chunks buffered by [spawn_chunks].
+const SPAWN_BUFFER: usize = 4;
+
+/// Returns a stream of chunks of at most [CHUNK_SIZE] bytes read from `rdr`.
+///
+/// Blocking readers are streamed by wrapping them in [Unblock](blocking::Unblock).
+pub(crate) fn stream_chunks(
+    rdr: impl 'static + Send + Unpin + AsyncRead,
+) -> impl 'static + Send + Stream<Item = io::Result<Bytes>> {
+    try_unfold(rdr, |mut rdr| async move {
+        let mut buf = vec![0; CHUNK_SIZE];
+        match rdr.read(&mut buf).await? {
+            0 => Ok(None),
+            n => {
+                buf.truncate(n);
+                Ok(Some((Bytes::from(buf), rdr)))
+            }
+        }
+    })
+}
+
+/// Sender of chunks of a stream returned by [spawn_chunks].
+pub(crate) struct ChunkSender(Sender<io::Result<Bytes>>);
+
+impl ChunkSender {
+    /// Sends `chunk`, failing if the stream was dropped, e.g. because the client disconnected.
+    pub(crate) async fn send(&self, chunk: impl Into<Bytes>) -> anyhow::Result<()> {
+        self.0
+            .send(Ok(chunk.into()))
+            .await
+            .map_err(|_| anyhow!("stream was dropped"))
+    }
+}
+
+/// Returns a stream of chunks sent by `f`, which runs in a task of its own.
+///
+/// At most [SPAWN_BUFFER] chunks are buffered, so that `f` does not produce chunks faster
+/// than the stream is consumed. If `f` fails, the stream ends with the error.
+pub(crate) fn spawn_chunks<F, Fut>(f: F) -> impl 'static + Send + Stream<Item = io::Result<Bytes>>
+where
+    F: FnOnce(ChunkSender) -> Fut,
+    Fut: 'static + Send + Future<Output = anyhow::Result<()>>,
+{
+    let (tx, rx) = bounded(SPAWN_BUFFER);
+    let fut = f(ChunkSender(tx.clone()));
+    _ = spawn(async move {
+        if let Err(e) = fut.await {
+            _ = tx.send(Err(io::Error::other(e))).await;
+        }
+    });
+    rx
+}
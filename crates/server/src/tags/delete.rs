@@ -0,0 +1,103 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::admission::{Action, Admission, AdmissionInput};
+use super::super::cluster::Cluster;
+use super::super::events::{DeleteCause, Event, EventBus};
+use super::super::trash::{self, TrashPolicy};
+use super::super::{Clock, OidcClaims, ScopeContext, ScopeLevel, Store};
+use crate::conditional;
+use crate::json;
+
+use drawbridge_type::{TagContext, TagKind};
+
+use async_std::sync::Arc;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Deletes the tag along with its tree by moving it into the trash of the owner of the
+/// repository and returns the trashed entity, which may be restored until it expires.
+///
+/// Deletion is subject to admission control like publication of the tag.
+#[allow(clippy::too_many_arguments)]
+pub async fn delete(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref events): Extension<Arc<EventBus>>,
+    Extension(ref policy): Extension<Arc<TrashPolicy>>,
+    Extension(ref clock): Extension<Arc<dyn Clock>>,
+    Extension(ref admission): Extension<Arc<Admission>>,
+    cluster: Option<Extension<Arc<Cluster>>>,
+    claims: OidcClaims,
+    cx: TagContext,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    trace!(target: "app::tags::delete", "called for `{cx}`");
+
+    let user = claims
+        .assert_user(
+            store,
+            &cx.repository.owner,
+            ScopeContext::Tag,
+            ScopeLevel::Write,
+        )
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let tag = user.repository(&cx.repository.name).tag(&cx.name);
+    // NOTE: Other instances of the cluster may publish the tag concurrently.
+    let lock = match cluster {
+        Some(Extension(ref cluster)) => Some(cluster.lock_tag(store, &cx).await.map_err(|e| {
+            debug!(target: "app::tags::delete", "failed to lock `{cx}`: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })?),
+        None => None,
+    };
+    let res = async {
+        let meta = tag.get_meta().await.map_err(|e| {
+            debug!(target: "app::tags::delete", "failed to get tag `{cx}`: {:?}", e);
+            e.into_response()
+        })?;
+        conditional::assert_unmodified_since(&headers, &tag).await?;
+        admission
+            .assert_admitted(AdmissionInput {
+                action: Action::Delete,
+                identity: claims.subject().into(),
+                namespace: cx.repository.to_string(),
+                public: None,
+                tag: Some(cx.name.to_string()),
+                kind: TagKind::of(meta.mime.essence()),
+                digests: meta.hash.clone(),
+                annotations: Default::default(),
+            })
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let size = tag.stored_size().await.map_err(|e| {
+            debug!(target: "app::tags::delete", "failed to get size of `{cx}`: {:?}", e);
+            e.into_response()
+        })?;
+        let trashed = trash::trash(
+            &user,
+            &tag,
+            policy,
+            clock.as_ref(),
+            cx.repository.name.clone(),
+            Some(cx.name.clone()),
+            claims.subject(),
+        )
+        .await?;
+        Ok::<_, Response>((trashed, meta.hash, size))
+    }
+    .await;
+    if let Some(lock) = lock {
+        lock.release().await;
+    }
+    let (trashed, digest, size) = res?;
+    events.publish(Event::TagDeleted {
+        tag: cx,
+        digest,
+        size,
+        cause: DeleteCause::Trashed,
+    });
+    json::encode(&trashed).map_err(IntoResponse::into_response)
+}
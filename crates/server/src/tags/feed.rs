@@ -0,0 +1,97 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{Store, API_VERSION};
+use crate::auth::assert_repository_read;
+use crate::base_path::{self, BasePath};
+use crate::xml::escape;
+
+use drawbridge_type::RepositoryContext;
+
+use std::fmt::Write;
+use std::time::SystemTime;
+
+use async_std::sync::Arc;
+use axum::body::Body;
+use axum::http::header::{CONTENT_TYPE, HOST};
+use axum::http::Request;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::try_join;
+use tracing::{debug, trace};
+
+/// Maximum number of entries in the feed.
+const MAX_ENTRIES: usize = 50;
+
+fn format_time(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Returns an Atom feed of the most recently created tags of the repository.
+pub async fn feed(
+    Extension(ref store): Extension<Arc<Store>>,
+    cx: RepositoryContext,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::tags::feed", "called for `{cx}`");
+
+    // NOTE: The `Host` header is chosen by the client, hence the URL is escaped like all other
+    // values interpolated into the feed.
+    let base = escape(&format!(
+        "https://{}{}",
+        req.headers()
+            .get(HOST)
+            .and_then(|host| host.to_str().ok())
+            .unwrap_or("localhost"),
+        base_path::join(
+            req.extensions().get::<BasePath>(),
+            &format!("/api/v{}/{cx}", *API_VERSION)
+        )
+    ));
+
+    let (repo, _) = assert_repository_read(store, &cx, req)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let names = repo.tags().await.map_err(|e| {
+        debug!(target: "app::tags::feed", "failed to list tags of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let mut tags = Vec::with_capacity(names.len());
+    for name in names {
+        let tag = repo.tag(&name);
+        let (meta, modified) = try_join!(tag.get_meta(), tag.get_modified()).map_err(|e| {
+            debug!(target: "app::tags::feed", "failed to get tag `{cx}:{name}`: {:?}", e);
+            e.into_response()
+        })?;
+        tags.push((modified, name, meta));
+    }
+    tags.sort_by(|(a, ..), (b, ..)| b.cmp(a));
+    tags.truncate(MAX_ENTRIES);
+
+    let updated = tags
+        .first()
+        .map(|(modified, ..)| *modified)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+    // NOTE: Writing to a `String` cannot fail.
+    _ = write!(
+        xml,
+        r#"<feed xmlns="http://www.w3.org/2005/Atom"><id>{base}/_tag</id><title>{} tags</title><updated>{}</updated><author><name>{}</name></author><link rel="self" href="{base}/_tag.atom"/>"#,
+        escape(&cx.to_string()),
+        format_time(updated),
+        escape(&cx.owner.to_string()),
+    );
+    for (modified, name, meta) in tags {
+        let name = escape(&name.to_string());
+        _ = write!(
+            xml,
+            r#"<entry><id>{base}/_tag/{name}</id><title>{name}</title><updated>{}</updated><link href="{base}/_tag/{name}"/><summary>{}</summary></entry>"#,
+            format_time(modified),
+            escape(&meta.hash.to_string()),
+        );
+    }
+    xml.push_str("</feed>");
+
+    Ok::<_, Response>(([(CONTENT_TYPE, "application/atom+xml")], xml))
+}
@@ -1,38 +1,67 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
+use super::super::alerts::Alerts;
 use super::super::Store;
 use crate::auth::assert_repository_read;
+use crate::cache::CachePolicy;
+use crate::downloads::Downloads;
+use crate::hot::HotCache;
+use crate::{cbor, links};
 
-use drawbridge_type::TagContext;
+use drawbridge_type::{MediaType, TagContext, TreeEntry};
 
 use async_std::sync::Arc;
 use axum::body::Body;
+use axum::headers::LastModified;
 use axum::http::Request;
-use axum::response::IntoResponse;
-use axum::Extension;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, TypedHeader};
+use futures::try_join;
 use tracing::{debug, trace};
 
 pub async fn get(
     Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref cache): Extension<Arc<CachePolicy>>,
+    Extension(ref hot): Extension<Arc<HotCache>>,
+    Extension(ref downloads): Extension<Arc<Downloads>>,
+    Extension(ref alerts): Extension<Arc<Alerts>>,
     cx: TagContext,
     req: Request<Body>,
 ) -> impl IntoResponse {
     trace!(target: "app::tags::get", "called for `{cx}`");
 
+    let cbor = cbor::accepts(req.headers(), TreeEntry::<()>::TYPE);
+
     let (repo, _) = assert_repository_read(store, &cx.repository, req)
         .await
         .map_err(IntoResponse::into_response)?;
 
-    // TODO: Stream body
-    // https://github.com/profianinc/drawbridge/issues/56
-    let mut body = vec![];
-    repo.tag(&cx.name)
-        .get_to_writer(&mut body)
-        .await
+    let (tag, config) = repo.pull_tag(&cx.name).await.map_err(|e| {
+        debug!(target: "app::tags::get", "failed for `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let ((meta, body), modified) = try_join!(hot.get_body(&tag, alerts), tag.get_modified())
         .map_err(|e| {
             debug!(target: "app::tags::get", "failed for `{cx}`: {:?}", e);
             e.into_response()
-        })
-        .map(|meta| (meta, body))
+        })?;
+    let headers = (
+        links::tag(&cx),
+        cache.content(config.public),
+        TypedHeader(LastModified::from(modified)),
+    );
+    if !meta.mime.is(MediaType::TAG.essence()) {
+        downloads.count_tag(&cx);
+        return Ok((meta, headers, body).into_response());
+    }
+    if !cbor {
+        downloads.count_tag(&cx);
+        return Ok((meta, headers, cbor::VARY_ACCEPT, body).into_response());
+    }
+    let (meta, body) = cbor::transcode::<TreeEntry>(meta, body)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    downloads.count_tag(&cx);
+    Ok::<_, Response>((meta, headers, cbor::VARY_ACCEPT, body).into_response())
 }
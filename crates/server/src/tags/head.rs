@@ -3,33 +3,50 @@
 
 use super::super::Store;
 use crate::auth::assert_repository_read;
+use crate::cache::CachePolicy;
+use crate::{cbor, links};
 
-use drawbridge_type::TagContext;
+use drawbridge_type::{MediaType, TagContext};
 
 use async_std::sync::Arc;
 use axum::body::Body;
+use axum::headers::LastModified;
 use axum::http::Request;
 use axum::response::IntoResponse;
-use axum::Extension;
+use axum::{Extension, TypedHeader};
+use futures::try_join;
 use tracing::{debug, trace};
 
 pub async fn head(
     Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref cache): Extension<Arc<CachePolicy>>,
     cx: TagContext,
     req: Request<Body>,
 ) -> impl IntoResponse {
     trace!(target: "app::tags::head", "called for `{cx}`");
 
-    assert_repository_read(store, &cx.repository, req)
-        .await
-        .map_err(IntoResponse::into_response)
-        .map(|(repo, _)| repo)?
-        .tag(&cx.name)
-        .get_meta()
+    let (repo, _) = assert_repository_read(store, &cx.repository, req)
         .await
+        .map_err(IntoResponse::into_response)?;
+    // NOTE: The vulnerability policy is not consulted, since no content is transferred.
+    let tag = repo.tag(&cx.name);
+    try_join!(tag.get_meta(), tag.get_modified(), repo.get_json())
         .map_err(|e| {
             debug!(target: "app::tags::head", "failed for `{cx}`: {:?}", e);
             e.into_response()
         })
-        .map(|meta| (meta, ()))
+        .map(|(meta, modified, config)| {
+            let vary = meta
+                .mime
+                .is(MediaType::TAG.essence())
+                .then_some(cbor::VARY_ACCEPT);
+            (
+                meta,
+                links::tag(&cx),
+                cache.content(config.public),
+                TypedHeader(LastModified::from(modified)),
+                vary,
+                (),
+            )
+        })
 }
@@ -1,12 +1,18 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
+mod delete;
+mod feed;
 mod get;
 mod head;
 mod put;
 mod query;
+mod stats;
 
+pub use delete::*;
+pub use feed::*;
 pub use get::*;
 pub use head::*;
 pub use put::*;
 pub use query::*;
+pub use stats::*;
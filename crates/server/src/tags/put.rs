@@ -1,22 +1,42 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use super::super::admission::{Action, Admission, AdmissionInput};
+use super::super::cluster::Cluster;
+use super::super::events::{Event, EventBus};
+use super::super::replay::Nonces;
+use super::super::store::tree_entry;
+use super::super::{
+    Clock, CreateError, GetError, OidcClaims, ScopeContext, ScopeLevel, SignatureKeys, Store,
+};
+use crate::algorithms;
+use crate::cbor::{self, BoundedCbor};
+use crate::conditional;
+use crate::json::{self, BoundedJson};
 
-use drawbridge_jose::jws::Jws;
-use drawbridge_jose::MediaTyped;
-use drawbridge_type::{Meta, TagContext, TagEntry, TreeEntry};
+use drawbridge_type::{
+    MediaType, Meta, SignatureBundle, TagAlias, TagContext, TagEntry, TagKind, TreeEntry,
+};
 
 use async_std::sync::Arc;
 use axum::body::Body;
 use axum::extract::RequestParts;
 use axum::http::{Request, StatusCode};
-use axum::response::IntoResponse;
-use axum::{Extension, Json};
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use futures::try_join;
 use tracing::{debug, trace};
+use uuid::Uuid;
 
+#[allow(clippy::too_many_arguments)]
 pub async fn put(
     Extension(store): Extension<Arc<Store>>,
+    Extension(events): Extension<Arc<EventBus>>,
+    Extension(clock): Extension<Arc<dyn Clock>>,
+    Extension(admission): Extension<Arc<Admission>>,
+    Extension(keys): Extension<Arc<SignatureKeys>>,
+    cluster: Option<Extension<Arc<Cluster>>>,
+    nonces: Option<Extension<Arc<Nonces>>>,
     claims: OidcClaims,
     cx: TagContext,
     meta: Meta,
@@ -41,20 +61,223 @@ pub async fn put(
         )
         .await
         .map_err(IntoResponse::into_response)?;
+    conditional::assert_unmodified_since(
+        req.headers(),
+        &user.repository(&cx.repository.name).tag(&cx.name),
+    )
+    .await?;
 
     let mut req = RequestParts::new(req);
-    let entry = match meta.mime.to_string().as_str() {
-        TreeEntry::<()>::TYPE => req.extract().await.map(|Json(v)| TagEntry::Unsigned(v)),
-        Jws::TYPE => req.extract().await.map(|Json(v)| TagEntry::Signed(v)),
-        _ => return Err((StatusCode::BAD_REQUEST, "Invalid content type").into_response()),
-    }
-    .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
-    user.repository(&cx.repository.name)
-        .create_tag(&cx.name, meta, &entry)
+    let mime = meta.mime.essence();
+    let (meta, entry) = match TagKind::of(mime) {
+        Some(TagKind::Unsigned) => {
+            let BoundedJson(entry) = req.extract::<BoundedJson<_>>().await?;
+            (meta, TagEntry::Unsigned(entry))
+        }
+        Some(TagKind::Signed) => {
+            let BoundedJson(jws) = req.extract::<BoundedJson<_>>().await?;
+            (meta, TagEntry::Signed(jws))
+        }
+        Some(TagKind::Alias) => {
+            let BoundedJson(alias) = req.extract::<BoundedJson<_>>().await?;
+            (meta, TagEntry::Alias(alias))
+        }
+        None if cbor::json_type(mime).as_deref() == Some(TreeEntry::<()>::TYPE) => {
+            let BoundedCbor(entry) = req.extract::<BoundedCbor<_>>().await?;
+            let entry = TagEntry::Unsigned(entry);
+            let meta =
+                cbor::stored_meta(&entry, MediaType::TAG).map_err(IntoResponse::into_response)?;
+            (meta, entry)
+        }
+        None => return Err((StatusCode::BAD_REQUEST, "Invalid content type").into_response()),
+    };
+    if let Some(Err(e)) = tree_entry(&entry).map(|entry| entry.annotations.assert_unreserved()) {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid annotations: {e}")).into_response());
+    }
+    if let TagEntry::Signed(ref jws) = entry {
+        if let Err(e) = SignatureBundle::from_jws(jws, None) {
+            return Err(
+                (StatusCode::BAD_REQUEST, format!("Invalid signatures: {e}")).into_response(),
+            );
+        }
+    }
+    let repo = user.repository(&cx.repository.name);
+    let config = repo.get_json().await.map_err(|e| {
+        debug!(target: "app::tags::put", "failed to get repository `{}`: {:?}", cx.repository, e);
+        e.into_response()
+    })?;
+    let rules = algorithms::rules(&store, &cx.repository, &config)
         .await
         .map_err(|e| {
-            debug!(target: "app::tags::put", "failed for `{cx}`: {:?}", e);
+            debug!(target: "app::tags::put", "failed to get algorithm rules of `{}`: {:?}", cx.repository, e);
             e.into_response()
+        })?;
+    algorithms::assert_accepted(&rules, &meta.hash).map_err(IntoResponse::into_response)?;
+    if let Some(ref policy) = config.signing {
+        let verified = match entry {
+            TagEntry::Signed(ref jws) => keys
+                .verify_jws(jws)
+                .map_err(|e| {
+                    (StatusCode::BAD_REQUEST, format!("Invalid signatures: {e}")).into_response()
+                })?
+                .iter()
+                .any(|name| policy.accepts(name)),
+            // NOTE: Aliases refer to tags, which satisfied the policy when they were published.
+            TagEntry::Alias(_) => true,
+            TagEntry::Unsigned(_) => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Tags of `{}` must be signed", cx.repository),
+                )
+                    .into_response())
+            }
+        };
+        if !verified {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "Signature is not verified by any of the keys required by the signing policy",
+            )
+                .into_response());
+        }
+        if let (TagEntry::Signed(_), Some(Extension(nonces))) = (&entry, nonces) {
+            let publication = tree_entry(&entry).and_then(|entry| entry.publication);
+            nonces
+                .check(
+                    &store,
+                    cluster.as_ref().map(|Extension(c)| c.as_ref()),
+                    &cx,
+                    publication.as_ref(),
+                    clock.now(),
+                )
+                .await
+                .map_err(IntoResponse::into_response)?;
+        }
+    }
+    if let TagEntry::Alias(TagAlias { ref target }) = entry {
+        // NOTE: Aliases must refer to existing tags, which are not aliases themselves, so that
+        // they are resolved in a single step.
+        match repo.tag(target).get_meta().await {
+            Ok(meta) if TagKind::of(meta.mime.essence()) != Some(TagKind::Alias) => {}
+            Ok(_) => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Alias target `{target}` is an alias"),
+                )
+                    .into_response())
+            }
+            Err(GetError::NotFound) => {
+                return Err((
+                    StatusCode::BAD_REQUEST,
+                    format!("Alias target `{target}` does not exist"),
+                )
+                    .into_response())
+            }
+            Err(e) => {
+                debug!(target: "app::tags::put", "failed to get alias target `{target}`: {:?}", e);
+                return Err(e.into_response());
+            }
+        }
+    }
+    admission
+        .assert_admitted(AdmissionInput {
+            action: Action::PublishTag,
+            identity: claims.subject().into(),
+            namespace: cx.repository.to_string(),
+            public: Some(config.public),
+            tag: Some(cx.name.to_string()),
+            kind: Some(entry.kind()),
+            digests: meta.hash.clone(),
+            annotations: tree_entry(&entry)
+                .map(|entry| entry.annotations)
+                .unwrap_or_default(),
         })
-        .map(|_| StatusCode::CREATED)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    if let Some(ref policy) = config.review {
+        if policy.protects(&cx.name)
+            && matches!(repo.tag(&cx.name).get_meta().await, Err(GetError::NotFound))
+        {
+            let change = policy.change(
+                Uuid::new_v4().to_string(),
+                cx.name.clone(),
+                entry.kind(),
+                meta.hash.clone(),
+                claims.subject(),
+                clock.now(),
+            );
+            _ = repo.create_review(&change, meta, &entry).await.map_err(|e| {
+                debug!(target: "app::tags::put", "failed to create pending change of `{cx}`: {:?}", e);
+                e.into_response()
+            })?;
+            debug!(target: "app::tags::put", "change `{}` of `{cx}` awaits approval", change.id);
+            return json::encode(&change)
+                .map(|res| (StatusCode::ACCEPTED, res).into_response())
+                .map_err(IntoResponse::into_response);
+        }
+    }
+    publish(
+        &store,
+        &events,
+        clock.as_ref(),
+        cluster.as_ref().map(|Extension(c)| c.as_ref()),
+        cx,
+        meta,
+        &entry,
+    )
+    .await
+    .map(IntoResponse::into_response)
+}
+
+/// Publishes tag `cx` of `entry` with `meta`, which passed all checks, and returns
+/// [StatusCode::CREATED], or [StatusCode::OK] if the same tag was published before.
+#[allow(clippy::result_large_err)]
+pub(crate) async fn publish(
+    store: &Store,
+    events: &EventBus,
+    clock: &dyn Clock,
+    cluster: Option<&Cluster>,
+    cx: TagContext,
+    meta: Meta,
+    entry: &TagEntry,
+) -> Result<StatusCode, Response> {
+    let repo = store.repository(&cx.repository);
+    let digest = meta.hash.clone();
+    // NOTE: Other instances of the cluster may publish the tag concurrently.
+    let lock = match cluster {
+        Some(cluster) => Some(cluster.lock_tag(store, &cx).await.map_err(|e| {
+            debug!(target: "app::tags::put", "failed to lock `{cx}`: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })?),
+        None => None,
+    };
+    let res = repo.create_tag(&cx.name, meta, entry).await;
+    if let Some(lock) = lock {
+        lock.release().await;
+    }
+    match res {
+        Ok(_) => Ok(()),
+        Err(CreateError::Occupied) => {
+            return repo
+                .tag(&cx.name)
+                .resolve_occupied(&digest)
+                .await
+                .map(|()| StatusCode::OK)
+                .map_err(|e| {
+                    debug!(target: "app::tags::put", "failed for `{cx}`: {:?}", e);
+                    e.into_response()
+                })
+        }
+        Err(e) => Err(e),
+    }
+    .map_err(|e| {
+        debug!(target: "app::tags::put", "failed for `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let now = clock.now();
+    let tag = repo.tag(&cx.name);
+    if let Err(e) = try_join!(tag.touch(now), repo.touch(now)) {
+        debug!(target: "app::tags::put", "failed to record timestamps of `{cx}`: {:?}", e);
+    }
+    events.publish(Event::TagUpdated { tag: cx, digest });
+    Ok(StatusCode::CREATED)
 }
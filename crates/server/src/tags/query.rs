@@ -1,41 +1,167 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::super::Store;
+use super::super::store::{tree_entry, Repository};
+use super::super::{GetError, Store};
 use crate::auth::assert_repository_read;
+use crate::cache::CachePolicy;
+use crate::downloads::Downloads;
+use crate::json;
+use crate::pagination::Pagination;
 
-use drawbridge_type::{Meta, RepositoryContext};
+use drawbridge_type::{
+    AnnotationFilter, MediaType, Meta, RepositoryContext, TagContext, TagEntry, TagName,
+};
+
+use std::collections::BTreeMap;
 
 use async_std::sync::Arc;
 use axum::body::Body;
-use axum::http::Request;
+use axum::http::{Request, StatusCode};
 use axum::response::IntoResponse;
 use axum::Extension;
-use mime::APPLICATION_JSON;
+use futures::try_join;
+use openidconnect::url::form_urlencoded;
 use tracing::{debug, trace};
 
+/// Returns names of tags of `repo` among `names`, whose tree entries match all of `filters`
+/// and are licensed under a license expression referring to `license`, if specified.
+async fn matching(
+    repo: &Repository<'_>,
+    names: Vec<TagName>,
+    filters: &[AnnotationFilter],
+    license: Option<&str>,
+) -> Result<Vec<TagName>, GetError<anyhow::Error>> {
+    if filters.is_empty() && license.is_none() {
+        return Ok(names);
+    }
+    let mut matching = vec![];
+    for name in names {
+        let entry: TagEntry = repo.tag(&name).get_content_json().await?;
+        if tree_entry(&entry).is_some_and(|entry| {
+            entry.annotations.matches(filters)
+                && license
+                    .is_none_or(|id| entry.license.is_some_and(|license| license.mentions(id)))
+        }) {
+            matching.push(name);
+        }
+    }
+    Ok(matching)
+}
+
+/// Returns names of tags of the repository.
+///
+/// If the `downloads` or `timestamps` query parameter is specified, an object mapping tag names
+/// to their download counts or creation and last modification times respectively is returned
+/// instead. If `annotation` query parameters of the form `KEY` or `KEY=VALUE` are specified,
+/// only tags annotated accordingly are returned. If a `license` query parameter is specified,
+/// only tags licensed under an SPDX license expression referring to it are returned.
+///
+/// Tags are paginated by the `cursor` and `limit` query parameters.
 pub async fn query(
     Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref cache): Extension<Arc<CachePolicy>>,
+    Extension(ref downloads): Extension<Arc<Downloads>>,
     ref cx: RepositoryContext,
     req: Request<Body>,
 ) -> impl IntoResponse {
     trace!(target: "app::tags::query", "called for `{cx}`");
 
-    assert_repository_read(store, cx, req)
-        .await
-        .map_err(IntoResponse::into_response)
-        .map(|(repo, _)| repo)?
-        .tags_json()
+    let uri = req.uri().clone();
+    let page = Pagination::from_uri(&uri)?;
+    let query = uri.query().unwrap_or("").to_string();
+    let with_downloads = form_urlencoded::parse(query.as_bytes()).any(|(k, _)| k == "downloads");
+    let with_timestamps = form_urlencoded::parse(query.as_bytes()).any(|(k, _)| k == "timestamps");
+    if with_downloads && with_timestamps {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Only one of `downloads` and `timestamps` may be specified",
+        )
+            .into_response());
+    }
+    let filters = form_urlencoded::parse(query.as_bytes())
+        .filter(|(k, _)| k == "annotation")
+        .map(|(_, v)| v.parse::<AnnotationFilter>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid annotation filter: {e}"),
+            )
+                .into_response()
+        })?;
+    let license = form_urlencoded::parse(query.as_bytes())
+        .find(|(k, _)| k == "license")
+        .map(|(_, v)| v.into_owned());
+    let (repo, _) = assert_repository_read(store, cx, req)
         .await
-        .map(|(hash, buf)| {
+        .map_err(IntoResponse::into_response)?;
+    if with_downloads
+        || with_timestamps
+        || !filters.is_empty()
+        || license.is_some()
+        || page.is_some()
+    {
+        let (names, public) = try_join!(repo.tags(), repo.is_public()).map_err(|e| {
+            debug!(target: "app::tags::query", "failed: {:?}", e);
+            e.into_response()
+        })?;
+        let names = matching(&repo, names, &filters, license.as_deref())
+            .await
+            .map_err(|e| {
+                debug!(target: "app::tags::query", "failed to filter tags: {:?}", e);
+                e.into_response()
+            })?;
+        let (names, link) = match page {
+            Some(page) => page.page(&uri, names),
+            None => (names, None),
+        };
+        if with_timestamps {
+            let mut timestamps = BTreeMap::new();
+            for name in names {
+                let tag = repo.tag(&name).get_timestamps().await.map_err(|e| {
+                    debug!(target: "app::tags::query", "failed to get timestamps of `{name}`: {:?}", e);
+                    e.into_response()
+                })?;
+                _ = timestamps.insert(name.to_string(), tag);
+            }
+            return json::encode(&timestamps)
+                .map(|(meta, buf)| (meta, link, cache.mutable(public), buf).into_response())
+                .map_err(IntoResponse::into_response);
+        }
+        if !with_downloads {
+            return json::encode(&names)
+                .map(|(meta, buf)| (meta, link, cache.mutable(public), buf).into_response())
+                .map_err(IntoResponse::into_response);
+        }
+        let mut counts = BTreeMap::new();
+        for name in names {
+            let tag = TagContext {
+                repository: cx.clone(),
+                name,
+            };
+            let stats = downloads.stats(store, &tag).await.map_err(|e| {
+                debug!(target: "app::tags::query", "failed to get statistics of `{tag}`: {:?}", e);
+                e.into_response()
+            })?;
+            _ = counts.insert(tag.name.to_string(), stats.downloads);
+        }
+        return json::encode(&counts)
+            .map(|(meta, buf)| (meta, link, cache.mutable(public), buf).into_response())
+            .map_err(IntoResponse::into_response);
+    }
+    try_join!(repo.tags_json(), repo.is_public())
+        .map(|((hash, buf), public)| {
             (
                 Meta {
                     hash,
                     size: buf.len() as _,
-                    mime: APPLICATION_JSON,
+                    mime: MediaType::JSON,
                 },
+                cache.mutable(public),
                 buf,
             )
+                .into_response()
         })
         .map_err(|e| {
             debug!(target: "app::tags::query", "failed: {:?}", e);
@@ -0,0 +1,46 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::Store;
+use crate::auth::assert_repository_read;
+use crate::cache::CachePolicy;
+use crate::downloads::Downloads;
+use crate::json;
+
+use drawbridge_type::TagContext;
+
+use async_std::sync::Arc;
+use axum::body::Body;
+use axum::http::Request;
+use axum::response::IntoResponse;
+use axum::Extension;
+use futures::try_join;
+use tracing::{debug, trace};
+
+/// Returns download statistics of the tag.
+pub async fn stats(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref cache): Extension<Arc<CachePolicy>>,
+    Extension(ref downloads): Extension<Arc<Downloads>>,
+    cx: TagContext,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::tags::stats", "called for `{cx}`");
+
+    let (repo, _) = assert_repository_read(store, &cx.repository, req)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let tag = repo.tag(&cx.name);
+    let (_, stats, public) = try_join!(
+        tag.get_meta(),
+        downloads.stats(store, &cx),
+        repo.is_public()
+    )
+    .map_err(|e| {
+        debug!(target: "app::tags::stats", "failed for `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    json::encode(&stats)
+        .map(|(meta, buf)| (meta, cache.mutable(public), buf))
+        .map_err(IntoResponse::into_response)
+}
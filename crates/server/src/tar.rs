@@ -0,0 +1,304 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Minimal POSIX tar archive reader and writer.
+
+use anyhow::{bail, ensure, Context};
+
+/// Size of a tar block.
+const BLOCK_SIZE: usize = 512;
+
+/// Maximum length of a path stored directly in the ustar header.
+const NAME_SIZE: usize = 100;
+
+/// Maximum size of an entry stored in octal in the ustar header.
+const MAX_OCTAL_SIZE: u64 = 0o777_7777_7777;
+
+/// Media type of tar archives.
+pub(crate) const TAR_TYPE: &str = "application/x-tar";
+
+/// A tar archive writer, which stores long paths and large sizes in PAX extended headers.
+///
+/// Sizes of large entries are stored in the ustar header in the base-256 encoding of GNU tar
+/// as well, for the sake of readers not supporting PAX.
+///
+/// Archives can be streamed by taking the entries written so far with [Builder::take].
+#[derive(Debug, Default)]
+pub(crate) struct Builder(Vec<u8>);
+
+/// Returns the PAX extended header record setting `key` to `value`.
+fn pax_record(key: &str, value: &str) -> String {
+    // NOTE: The record length includes the length of its own decimal representation.
+    let record = format!(" {key}={value}\n");
+    let mut len = record.len() + 1;
+    while len != record.len() + len.to_string().len() {
+        len += 1;
+    }
+    format!("{len}{record}")
+}
+
+/// Returns the size field of a ustar header of an entry of `size`, which is octal if it fits
+/// and base-256 otherwise.
+fn encode_size(size: u64) -> [u8; 12] {
+    let mut field = [0; 12];
+    if size <= MAX_OCTAL_SIZE {
+        field.copy_from_slice(format!("{size:011o}\0").as_bytes());
+    } else {
+        field[0] = 0x80;
+        field[4..].copy_from_slice(&size.to_be_bytes());
+    }
+    field
+}
+
+impl Builder {
+    fn write_header(&mut self, path: &str, size: u64, kind: u8) {
+        let mut header = [0u8; BLOCK_SIZE];
+        let name = path.as_bytes();
+        let n = name.len().min(NAME_SIZE);
+        header[..n].copy_from_slice(&name[..n]);
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[108..116].copy_from_slice(b"0000000\0");
+        header[116..124].copy_from_slice(b"0000000\0");
+        header[124..136].copy_from_slice(&encode_size(size));
+        header[136..148].copy_from_slice(b"00000000000\0");
+        header[156] = kind;
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+
+        // NOTE: The checksum is computed with the checksum field filled with spaces.
+        header[148..156].copy_from_slice(b"        ");
+        let sum: u32 = header.iter().map(|b| *b as u32).sum();
+        header[148..156].copy_from_slice(format!("{sum:06o}\0 ").as_bytes());
+
+        self.0.extend_from_slice(&header);
+    }
+
+    fn write_padding(&mut self, size: u64) {
+        let padding = (BLOCK_SIZE as u64 - size % BLOCK_SIZE as u64) % BLOCK_SIZE as u64;
+        self.0.resize(self.0.len() + padding as usize, 0);
+    }
+
+    fn write_data(&mut self, data: &[u8]) {
+        self.0.extend_from_slice(data);
+        self.write_padding(data.len() as _);
+    }
+
+    /// Appends a regular file at `path` with `data` to the archive.
+    pub(crate) fn append(&mut self, path: &str, data: &[u8]) {
+        self.start_file(path, data.len() as _);
+        self.write_data(data);
+    }
+
+    /// Appends the header of a regular file at `path` of `size` to the archive.
+    ///
+    /// The data of the file must be appended by [Builder::append_data] and then terminated
+    /// by [Builder::end_file], so that files can be streamed without holding them in memory.
+    pub(crate) fn start_file(&mut self, path: &str, size: u64) {
+        let mut records = String::new();
+        if path.len() > NAME_SIZE {
+            records.push_str(&pax_record("path", path));
+        }
+        if size > MAX_OCTAL_SIZE {
+            records.push_str(&pax_record("size", &size.to_string()));
+        }
+        if !records.is_empty() {
+            self.write_header("././@PaxHeader", records.len() as u64, b'x');
+            self.write_data(records.as_bytes());
+        }
+        self.write_header(path, size, b'0');
+    }
+
+    /// Appends `data` to the file started by [Builder::start_file].
+    pub(crate) fn append_data(&mut self, data: &[u8]) {
+        self.0.extend_from_slice(data);
+    }
+
+    /// Terminates the file of `size` started by [Builder::start_file].
+    pub(crate) fn end_file(&mut self, size: u64) {
+        self.write_padding(size);
+    }
+
+    /// Returns the contents written since the last call, if any.
+    pub(crate) fn take(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.0)
+    }
+
+    /// Terminates the archive and returns the contents not taken yet.
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        self.0.resize(self.0.len() + 2 * BLOCK_SIZE, 0);
+        self.0
+    }
+}
+
+/// Parses the NUL-terminated string in `field` of a header.
+fn parse_str(field: &[u8]) -> anyhow::Result<&str> {
+    let n = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    std::str::from_utf8(&field[..n]).context("invalid UTF-8 in tar header")
+}
+
+/// Parses the octal number in `field` of a header.
+fn parse_octal(field: &[u8]) -> anyhow::Result<usize> {
+    let s = parse_str(field)?.trim_matches(|c| c == ' ' || c == '\0');
+    usize::from_str_radix(s, 8).with_context(|| format!("invalid octal number `{s}` in tar header"))
+}
+
+/// Parses the number in `field` of a header, which is either octal or encoded in base-256.
+fn parse_number(field: &[u8]) -> anyhow::Result<usize> {
+    match field.split_first() {
+        Some((first, rest)) if first & 0x80 != 0 => {
+            ensure!(*first == 0x80, "negative number in tar header");
+            rest.iter().try_fold(0usize, |n, b| {
+                n.checked_mul(256)
+                    .and_then(|n| n.checked_add(*b as usize))
+                    .context("number in tar header is too large")
+            })
+        }
+        _ => parse_octal(field),
+    }
+}
+
+/// Values of a PAX extended header, which apply to the following entry.
+#[derive(Debug, Default)]
+struct PaxHeader {
+    path: Option<String>,
+    size: Option<usize>,
+}
+
+/// Parses the `path` and `size` records of PAX extended header `data`.
+fn parse_pax(mut data: &[u8]) -> anyhow::Result<PaxHeader> {
+    let mut pax = PaxHeader::default();
+    while !data.is_empty() {
+        let (len, _) = std::str::from_utf8(data)
+            .ok()
+            .and_then(|s| s.split_once(' '))
+            .context("invalid PAX extended header record")?;
+        let len: usize = len
+            .parse()
+            .context("invalid PAX extended header record length")?;
+        ensure!(
+            len > 0 && len <= data.len(),
+            "invalid PAX extended header record length"
+        );
+        let (record, rest) = data.split_at(len);
+        let record = std::str::from_utf8(record).context("invalid PAX extended header record")?;
+        if let Some((_, kv)) = record.trim_end_matches('\n').split_once(' ') {
+            if let Some(value) = kv.strip_prefix("path=") {
+                pax.path = Some(value.into());
+            } else if let Some(value) = kv.strip_prefix("size=") {
+                pax.size = Some(value.parse().context("invalid PAX extended header size")?);
+            }
+        }
+        data = rest;
+    }
+    Ok(pax)
+}
+
+/// Returns paths and contents of all regular files in archive `buf` in order.
+///
+/// Paths and sizes stored in PAX extended headers, ustar prefixes and base-256 sizes are
+/// supported, other entry types are skipped.
+pub(crate) fn entries(buf: &[u8]) -> anyhow::Result<Vec<(String, &[u8])>> {
+    let mut entries = vec![];
+    let mut next = PaxHeader::default();
+    let mut offset = 0;
+    while offset + BLOCK_SIZE <= buf.len() {
+        let header = &buf[offset..offset + BLOCK_SIZE];
+        if header.iter().all(|b| *b == 0) {
+            return Ok(entries);
+        }
+        let sum: usize = header[..148]
+            .iter()
+            .chain(&[b' '; 8])
+            .chain(&header[156..])
+            .map(|b| *b as usize)
+            .sum();
+        ensure!(
+            parse_octal(&header[148..156])? == sum,
+            "invalid tar header checksum at offset {offset}"
+        );
+        let kind = header[156];
+        let pax = if kind == b'x' {
+            PaxHeader::default()
+        } else {
+            std::mem::take(&mut next)
+        };
+        let size = match pax.size {
+            Some(size) => size,
+            None => parse_number(&header[124..136])?,
+        };
+        let start = offset + BLOCK_SIZE;
+        let data = start
+            .checked_add(size)
+            .and_then(|end| buf.get(start..end))
+            .context("truncated tar archive")?;
+        offset = start + size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+        match kind {
+            b'x' => next = parse_pax(data)?,
+            b'0' | 0 => {
+                let path = match pax.path {
+                    Some(path) => path,
+                    None if header[257..262] == *b"ustar" => match parse_str(&header[345..500])? {
+                        "" => parse_str(&header[..NAME_SIZE])?.into(),
+                        prefix => format!("{prefix}/{}", parse_str(&header[..NAME_SIZE])?),
+                    },
+                    None => parse_str(&header[..NAME_SIZE])?.into(),
+                };
+                entries.push((path, data));
+            }
+            _ => {}
+        }
+    }
+    bail!("tar archive is not terminated")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_sizes() {
+        let size = 8 << 30;
+        assert_eq!(parse_number(&encode_size(size)).unwrap(), size as usize);
+        assert_eq!(
+            parse_number(&encode_size(MAX_OCTAL_SIZE)).unwrap(),
+            MAX_OCTAL_SIZE as usize
+        );
+
+        let pax = parse_pax(pax_record("size", &size.to_string()).as_bytes()).unwrap();
+        assert_eq!(pax.size, Some(size as usize));
+        assert_eq!(pax.path, None);
+    }
+
+    #[test]
+    fn long_paths() {
+        let path = format!("{}/test-file.txt", "a".repeat(NAME_SIZE));
+        let mut archive = Builder::default();
+        archive.append(&path, b"text");
+        archive.append("short.txt", b"");
+        let buf = archive.finish();
+        assert_eq!(
+            entries(&buf).unwrap(),
+            [
+                (path, b"text".as_slice()),
+                ("short.txt".into(), b"".as_slice())
+            ]
+        );
+    }
+
+    #[test]
+    fn streamed_files() {
+        let mut archive = Builder::default();
+        archive.start_file("test-file.txt", 1024);
+        let mut buf = archive.take();
+        for chunk in [[1; 600].as_slice(), &[2; 424]] {
+            archive.append_data(chunk);
+            buf.extend(archive.take());
+        }
+        archive.end_file(1024);
+        buf.extend(archive.finish());
+
+        let mut expected = Builder::default();
+        expected.append("test-file.txt", &[[1; 600].as_slice(), &[2; 424]].concat());
+        assert_eq!(buf, expected.finish());
+    }
+}
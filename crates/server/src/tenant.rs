@@ -0,0 +1,128 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Strictly isolated tenants hosted by a single deployment.
+//!
+//! Each tenant has its own store root, OpenID Connect realm and storage quota. Requests are
+//! routed to the tenant by the host name they are addressed to, and requests to host names
+//! not claimed by any tenant are served from the default store. Everything derived from a
+//! store, i.e. events, webhooks, usage accounting, download counts and the in-process cache
+//! of contents, is kept per tenant, so that no state is shared among tenants.
+//!
+//! The trusted CA and Steward are configured for the whole deployment, so that access granted
+//! by client certificates could not be scoped to a tenant. Certificates are therefore ignored
+//! on hosts of tenants, whose users are authenticated by the OpenID Connect realm only.
+
+use super::{OidcConfig, ReplicaCertificate, TrustedCertificate, WorkloadIdentity};
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::path::PathBuf;
+use std::task::{Context, Poll};
+
+use anyhow::bail;
+use axum::body::Body;
+use axum::http::header::HOST;
+use axum::http::Request;
+use axum::response::Response;
+use axum::routing::future::RouteFuture;
+use axum::Router;
+use tower::Service;
+use tracing::{debug, trace};
+
+/// Configuration of a tenant.
+#[derive(Debug)]
+pub struct TenantConfig {
+    /// Name of the tenant, which identifies it in logs and published events
+    pub name: String,
+    /// Host names requests to the tenant are addressed to
+    pub hosts: Vec<String>,
+    /// Path of the store root of the tenant
+    pub store: PathBuf,
+    /// OpenID Connect realm users of the tenant are authenticated by
+    pub oidc: OidcConfig,
+    /// Maximum number of bytes stored in all repositories of the tenant
+    pub quota: Option<u64>,
+}
+
+/// Returns the lowercase host name `req` is addressed to without the port, if any.
+pub(crate) fn request_host<B>(req: &Request<B>) -> Option<String> {
+    let host = req
+        .headers()
+        .get(HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| req.uri().host())?;
+    // NOTE: IPv6 literals are enclosed in brackets and contain colons.
+    let host = match host.rsplit_once(':') {
+        Some((name, port)) if !port.contains(']') => name,
+        _ => host,
+    };
+    Some(host.to_ascii_lowercase())
+}
+
+/// Service dispatching requests to routers of tenants keyed by the host names they claim.
+#[derive(Clone, Debug)]
+pub(crate) struct Tenants {
+    default: Router,
+    hosts: HashMap<String, (String, Router)>,
+}
+
+impl Tenants {
+    pub(crate) fn new(default: Router) -> Self {
+        Self {
+            default,
+            hosts: Default::default(),
+        }
+    }
+
+    /// Routes requests to `hosts` to `router` of tenant `name`.
+    pub(crate) fn insert(
+        &mut self,
+        name: &str,
+        hosts: impl IntoIterator<Item = String>,
+        router: Router,
+    ) -> anyhow::Result<()> {
+        for host in hosts {
+            let host = host.to_ascii_lowercase();
+            if let Some((other, _)) = self.hosts.get(&host) {
+                bail!("host `{host}` is claimed by tenants `{other}` and `{name}`");
+            }
+            _ = self.hosts.insert(host, (name.to_string(), router.clone()));
+        }
+        Ok(())
+    }
+
+    /// Returns the router of the tenant `req` is addressed to and strips the identities
+    /// established by client certificates from `req`, if it is addressed to a tenant.
+    fn route(&self, req: &mut Request<Body>) -> Router {
+        match request_host(req).and_then(|host| self.hosts.get(&host)) {
+            Some((name, router)) => {
+                trace!(target: "app::tenant", "route request to tenant `{name}`");
+                let ext = req.extensions_mut();
+                let cert = ext.remove::<TrustedCertificate>().is_some();
+                _ = ext.remove::<ReplicaCertificate>();
+                let workload = ext.remove::<WorkloadIdentity>().is_some();
+                if cert || workload {
+                    debug!(target: "app::tenant", "ignore client certificate on tenant `{name}`");
+                }
+                router.clone()
+            }
+            None => self.default.clone(),
+        }
+    }
+}
+
+impl Service<Request<Body>> for Tenants {
+    type Response = Response;
+    type Error = Infallible;
+    type Future = RouteFuture<Body, Infallible>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // NOTE: Routers are always ready.
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, mut req: Request<Body>) -> Self::Future {
+        self.route(&mut req).call(req)
+    }
+}
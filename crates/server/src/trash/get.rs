@@ -0,0 +1,54 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use crate::json;
+use crate::pagination::paginate;
+use crate::Clock;
+
+use drawbridge_type::UserContext;
+
+use async_std::sync::Arc;
+use axum::http::Uri;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Returns repositories and tags in the trash of the user, which are not expired, ordered by
+/// the time they were deleted.
+///
+/// The listing is paginated by the `cursor` and `limit` query parameters.
+pub async fn get(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref clock): Extension<Arc<dyn Clock>>,
+    claims: OidcClaims,
+    cx: UserContext,
+    uri: Uri,
+) -> impl IntoResponse {
+    trace!(target: "app::trash::get", "called for `{cx}`");
+
+    let user = claims
+        .assert_user(store, &cx, ScopeContext::Repository, ScopeLevel::Read)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let ids = user.trash_ids().await.map_err(|e| {
+        debug!(target: "app::trash::get", "failed to list trash of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let now = clock.now();
+    let mut entities = vec![];
+    for id in ids {
+        match user.get_trashed(&id).await {
+            Ok(trashed) if !trashed.is_expired(now) => entities.push(trashed),
+            Ok(_) => {}
+            Err(e) => {
+                debug!(target: "app::trash::get", "failed to get trashed entity `{id}` of `{cx}`: {:?}", e);
+            }
+        }
+    }
+    entities.sort_by_key(|trashed| trashed.deleted);
+    let (entities, link): (Vec<_>, _) = paginate(&uri, entities)?;
+    json::encode(&entities)
+        .map(|res| (link, res))
+        .map_err(IntoResponse::into_response)
+}
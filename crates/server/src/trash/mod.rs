@@ -0,0 +1,111 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Soft deletion of repositories and tags.
+//!
+//! Repositories and tags deleted are not removed right away. Instead, they are moved into the
+//! trash of their owner as a [TrashedEntity] listed by [get] under `_trash`, from which they
+//! may be restored using [post] under `_trash/ID` until the retention window of the
+//! [TrashPolicy] expires. Expired entities are purged permanently in background, by the
+//! leader only if the store is shared by a cluster.
+//!
+//! [TrashedEntity]: drawbridge_type::TrashedEntity
+
+mod get;
+mod post;
+
+pub use get::*;
+pub use post::*;
+
+use super::cluster::Cluster;
+use super::store::{Entity, User};
+use super::{Clock, Store};
+
+use drawbridge_type::{RepositoryName, TagName, TrashedEntity};
+
+use std::time::Duration;
+
+use async_std::sync::Arc;
+use async_std::task::{sleep, spawn};
+use axum::response::{IntoResponse, Response};
+use camino::Utf8Path;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// Interval, in which expired entities are purged.
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// ID of an entity in the trash, which is a UUID.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrashId(pub String);
+
+/// Policy of soft deletion of repositories and tags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrashPolicy {
+    /// Time, for which deleted entities may be restored, before they are purged. Entities are
+    /// purged right away, if zero.
+    pub retention: Duration,
+}
+
+impl Default for TrashPolicy {
+    fn default() -> Self {
+        Self {
+            retention: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+/// Moves `entity`, repository `repository` or its tag `tag`, of `user` into its trash on behalf
+/// of `deleter` and returns it, purging it right away if `policy` retains none.
+#[allow(clippy::result_large_err)]
+pub(crate) async fn trash(
+    user: &User<'_>,
+    entity: &Entity<'_, impl AsRef<Utf8Path>>,
+    policy: &TrashPolicy,
+    clock: &dyn Clock,
+    repository: RepositoryName,
+    tag: Option<TagName>,
+    deleter: &str,
+) -> Result<TrashedEntity, Response> {
+    let now = clock.now();
+    let trashed = TrashedEntity {
+        id: Uuid::new_v4().to_string(),
+        repository,
+        tag,
+        deleter: deleter.into(),
+        deleted: now.into(),
+        expires: (now + policy.retention).into(),
+    };
+    user.trash(entity, &trashed).await.map_err(|e| {
+        debug!(target: "app::trash::trash", "failed to trash `{}`: {:?}", entity.prefix(), e);
+        e.into_response()
+    })?;
+    if policy.retention.is_zero() {
+        if let Err(e) = user.purge(&trashed.id).await {
+            warn!(target: "app::trash::trash", "failed to purge trashed entity `{}`: {:?}", trashed.id, e);
+        }
+    }
+    Ok(trashed)
+}
+
+/// Purges entities in the trash of `store`, which are expired, every [PURGE_INTERVAL] in
+/// background, if the instance is the leader of `cluster`, if any.
+pub(crate) fn purge_periodically(
+    store: &Arc<Store>,
+    cluster: Option<Arc<Cluster>>,
+    clock: Arc<dyn Clock>,
+) {
+    let store = Arc::clone(store);
+    _ = spawn(async move {
+        loop {
+            if cluster.as_ref().is_none_or(|cluster| cluster.is_leader()) {
+                match store.purge_trash(clock.now()).await {
+                    Ok(0) => {}
+                    Ok(n) => debug!(target: "app::trash", "purged {n} expired entities"),
+                    Err(e) => warn!(target: "app::trash", "failed to purge trash: {:?}", e),
+                }
+            }
+            sleep(PURGE_INTERVAL).await;
+        }
+    });
+}
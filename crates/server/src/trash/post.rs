@@ -0,0 +1,134 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::cluster::Cluster;
+use super::super::events::{Event, EventBus};
+use super::super::{CreateError, GetError, OidcClaims, ScopeContext, ScopeLevel, Store};
+use super::TrashId;
+use crate::json;
+use crate::Clock;
+
+use drawbridge_type::{RepositoryContext, TagContext, UserContext};
+
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Restores the repository or tag [TrashId] from the trash of the user and returns it.
+///
+/// Entities may only be restored to their original name, if no entity of the same name was
+/// created since. Tags may only be restored into their original repository, expired entities
+/// are purged.
+pub async fn post(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref events): Extension<Arc<EventBus>>,
+    Extension(ref clock): Extension<Arc<dyn Clock>>,
+    Extension(TrashId(id)): Extension<TrashId>,
+    cluster: Option<Extension<Arc<Cluster>>>,
+    claims: OidcClaims,
+    cx: UserContext,
+) -> impl IntoResponse {
+    trace!(target: "app::trash::post", "called for `{cx}`");
+
+    let user = claims
+        .assert_user(store, &cx, ScopeContext::Tag, ScopeLevel::Write)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let trashed = user.get_trashed(&id).await.map_err(|e| {
+        debug!(target: "app::trash::post", "failed to get trashed entity `{id}` of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    if trashed.tag.is_none() {
+        claims
+            .assert_scope(ScopeContext::Repository, ScopeLevel::Write)
+            .map_err(IntoResponse::into_response)?;
+    }
+    if trashed.is_expired(clock.now()) {
+        if let Err(e) = user.purge(&id).await {
+            debug!(target: "app::trash::post", "failed to purge trashed entity `{id}` of `{cx}`: {:?}", e);
+        }
+        return Err((StatusCode::GONE, format!("Trashed entity `{id}` expired")).into_response());
+    }
+
+    let size = user.get_trashed_stored_size(&trashed).await.map_err(|e| {
+        debug!(target: "app::trash::post", "failed to get size of trashed entity `{id}` of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+
+    let repo = RepositoryContext {
+        owner: cx.clone(),
+        name: trashed.repository.clone(),
+    };
+    let res = match trashed.tag {
+        None => user.restore(&id, &user.repository(&repo.name)).await,
+        Some(ref name) => {
+            match user.repository(&repo.name).get_meta().await {
+                Ok(_) => {}
+                Err(GetError::NotFound) => {
+                    return Err((
+                        StatusCode::CONFLICT,
+                        format!("Repository `{repo}` of trashed tag `{name}` does not exist"),
+                    )
+                        .into_response())
+                }
+                Err(e) => {
+                    debug!(target: "app::trash::post", "failed to get repository `{repo}`: {:?}", e);
+                    return Err(e.into_response());
+                }
+            }
+            let tag = TagContext {
+                repository: repo.clone(),
+                name: name.clone(),
+            };
+            // NOTE: Other instances of the cluster may publish the tag concurrently.
+            let lock = match cluster {
+                Some(Extension(ref cluster)) => {
+                    Some(cluster.lock_tag(store, &tag).await.map_err(|e| {
+                        debug!(target: "app::trash::post", "failed to lock `{tag}`: {:?}", e);
+                        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                    })?)
+                }
+                None => None,
+            };
+            let res = user
+                .restore(&id, &user.repository(&repo.name).tag(name))
+                .await;
+            if let Some(lock) = lock {
+                lock.release().await;
+            }
+            res
+        }
+    };
+    match res {
+        Ok(()) => {
+            events.publish(match trashed.tag {
+                Some(ref name) => Event::TagRestored {
+                    tag: TagContext {
+                        repository: repo,
+                        name: name.clone(),
+                    },
+                    size,
+                },
+                None => Event::RepositoryRestored {
+                    repository: repo,
+                    size,
+                },
+            });
+            json::encode(&trashed).map_err(IntoResponse::into_response)
+        }
+        Err(CreateError::Occupied) => Err((
+            StatusCode::CONFLICT,
+            match trashed.tag {
+                Some(ref name) => format!("Tag `{name}` of `{repo}` already exists"),
+                None => format!("Repository `{repo}` already exists"),
+            },
+        )
+            .into_response()),
+        Err(e) => {
+            debug!(target: "app::trash::post", "failed to restore trashed entity `{id}` of `{cx}`: {:?}", e);
+            Err(e.into_response())
+        }
+    }
+}
@@ -1,26 +1,41 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
+use super::super::alerts::{Alerts, IntegrityAlert};
 use super::super::{Store, TrustedCertificate};
-use crate::auth::assert_repository_read;
+use crate::auth::{assert_repository_read, pull_tag};
+use crate::cache::CachePolicy;
+use crate::downloads::Downloads;
+use crate::hot::HotCache;
+use crate::{cbor, encoding, links};
 
-use drawbridge_type::TreeContext;
+use drawbridge_type::{MediaType, Meta, TreeContext, TreeDirectory, TreeEntry};
 
 use async_std::sync::Arc;
-use axum::body::Body;
+use axum::body::{Body, StreamBody};
+use axum::headers::LastModified;
 use axum::http::Request;
-use axum::response::IntoResponse;
-use axum::Extension;
+use axum::response::{IntoResponse, Response};
+use axum::{Extension, TypedHeader};
+use futures::try_join;
 use tracing::{debug, trace};
 
+#[allow(clippy::too_many_arguments)]
 pub async fn get(
     Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref cache): Extension<Arc<CachePolicy>>,
+    Extension(ref hot): Extension<Arc<HotCache>>,
+    Extension(ref downloads): Extension<Arc<Downloads>>,
+    Extension(ref alerts): Extension<Arc<Alerts>>,
     cert: Option<Extension<TrustedCertificate>>,
     cx: TreeContext,
     req: Request<Body>,
 ) -> impl IntoResponse {
     trace!(target: "app::trees::get", "called for `{cx}`");
 
+    let gzip = encoding::accepts_gzip(req.headers());
+    let cbor = cbor::accepts(req.headers(), TreeDirectory::<()>::TYPE);
+
     let repo = if cert.is_none() {
         assert_repository_read(store, &cx.tag.repository, req)
             .await
@@ -29,17 +44,62 @@ pub async fn get(
     } else {
         store.repository(&cx.tag.repository)
     };
-
-    // TODO: Stream body
-    // https://github.com/profianinc/drawbridge/issues/56
-    let mut body = vec![];
-    repo.tag(&cx.tag.name)
-        .node(&cx.path)
-        .get_to_writer(&mut body)
+    let (tag, config) = pull_tag(&repo, &cx.tag.name, cert.as_deref())
         .await
         .map_err(|e| {
             debug!(target: "app::trees::get", "failed for `{cx}`: {:?}", e);
             e.into_response()
-        })
-        .map(|meta| (meta, body))
+        })?;
+
+    let node = tag.node(&cx.path);
+    let (meta, modified) = try_join!(node.get_meta(), node.get_modified()).map_err(|e| {
+        debug!(target: "app::trees::get", "failed for `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    let headers = (
+        links::tree(&cx),
+        cache.content(config.public),
+        TypedHeader(LastModified::from(modified)),
+    );
+    let directory = meta.mime.is(MediaType::DIRECTORY.essence());
+    let compressible = encoding::is_compressible(&meta);
+    let vary = cbor::vary(directory, compressible);
+    if directory && cbor {
+        let (meta, body) = hot.get_body(&node, alerts).await.map_err(|e| {
+            debug!(target: "app::trees::get", "failed for `{cx}`: {:?}", e);
+            e.into_response()
+        })?;
+        let (meta, body) = cbor::transcode::<TreeDirectory<TreeEntry>>(meta, body)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        downloads.count_entry(&cx);
+        return Ok((meta, headers, vary, body).into_response());
+    }
+    if !compressible || !gzip {
+        let (meta, body) = hot.get_body(&node, alerts).await.map_err(|e| {
+            debug!(target: "app::trees::get", "failed for `{cx}`: {:?}", e);
+            e.into_response()
+        })?;
+        downloads.count_entry(&cx);
+        return Ok((meta, headers, vary, body).into_response());
+    }
+
+    let (meta, size, body) = node.get_gzip_stream().await.map_err(|e| {
+        debug!(target: "app::trees::get", "failed to compress `{cx}`: {:?}", e);
+        alerts.raise_on_mismatch(&e, || IntegrityAlert::read(&node, &meta.hash));
+        e.into_response()
+    })?;
+    downloads.count_entry(&cx);
+    let etag = encoding::gzip_etag(&meta).map(TypedHeader);
+    Ok::<_, Response>(
+        (
+            Meta { size, ..meta },
+            headers,
+            vary,
+            encoding::CONTENT_ENCODING_GZIP,
+            etag,
+            StreamBody::new(body),
+        )
+            .into_response(),
+    )
 }
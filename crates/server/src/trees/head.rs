@@ -3,39 +3,57 @@
 
 use super::super::{Store, TrustedCertificate};
 use crate::auth::assert_repository_read;
+use crate::cache::CachePolicy;
+use crate::{cbor, encoding, links};
 
-use drawbridge_type::TreeContext;
+use drawbridge_type::{MediaType, TreeContext};
 
 use async_std::sync::Arc;
 use axum::body::Body;
+use axum::headers::LastModified;
 use axum::http::Request;
 use axum::response::IntoResponse;
-use axum::Extension;
+use axum::{Extension, TypedHeader};
+use futures::try_join;
 use tracing::{debug, trace};
 
 pub async fn head(
     Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref cache): Extension<Arc<CachePolicy>>,
     cert: Option<Extension<TrustedCertificate>>,
     cx: TreeContext,
     req: Request<Body>,
 ) -> impl IntoResponse {
     trace!(target: "app::trees::head", "called for `{cx}`");
 
-    if cert.is_none() {
+    let repo = if cert.is_none() {
         assert_repository_read(store, &cx.tag.repository, req)
             .await
             .map_err(IntoResponse::into_response)
             .map(|(repo, _)| repo)?
     } else {
         store.repository(&cx.tag.repository)
-    }
-    .tag(&cx.tag.name)
-    .node(&cx.path)
-    .get_meta()
-    .await
-    .map_err(|e| {
-        debug!(target: "app::trees::head", "failed for `{cx}`: {:?}", e);
-        e.into_response()
-    })
-    .map(|meta| (meta, ()))
+    };
+    // NOTE: The vulnerability policy is not consulted, since no content is transferred and
+    // clients check presence of nodes while uploading trees, before any report is attached.
+    let node = repo.tag(&cx.tag.name).node(&cx.path);
+    try_join!(node.get_meta(), node.get_modified(), repo.get_json())
+        .map_err(|e| {
+            debug!(target: "app::trees::head", "failed for `{cx}`: {:?}", e);
+            e.into_response()
+        })
+        .map(|(meta, modified, config)| {
+            let vary = cbor::vary(
+                meta.mime.is(MediaType::DIRECTORY.essence()),
+                encoding::is_compressible(&meta),
+            );
+            (
+                meta,
+                links::tree(&cx),
+                cache.content(config.public),
+                TypedHeader(LastModified::from(modified)),
+                vary,
+                (),
+            )
+        })
 }
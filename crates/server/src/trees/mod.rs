@@ -3,8 +3,10 @@
 
 mod get;
 mod head;
+mod propfind;
 mod put;
 
 pub use get::*;
 pub use head::*;
+pub use propfind::*;
 pub use put::*;
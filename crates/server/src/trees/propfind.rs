@@ -0,0 +1,162 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{is_directory, Store, TrustedCertificate};
+use crate::auth::assert_repository_read;
+use crate::xml::escape;
+
+use drawbridge_type::{Meta, TreeContext, TreeDirectory, TreeEntry};
+
+use std::fmt::Write;
+
+use async_std::sync::Arc;
+use axum::body::Body;
+use axum::http::header::{ALLOW, CONTENT_TYPE};
+use axum::http::{Request, StatusCode};
+use axum::response::IntoResponse;
+use axum::Extension;
+use percent_encoding::{utf8_percent_encode, AsciiSet, PercentEncode, CONTROLS};
+use tracing::{debug, trace};
+
+/// Methods supported on tree endpoints, advertised in response to `OPTIONS`.
+const ALLOWED_METHODS: &str = "OPTIONS, HEAD, GET, PUT, PROPFIND";
+
+/// WebDAV compliance class advertised by the tree endpoints.
+///
+/// Only class 1 is supported, since the tree is read-only over WebDAV.
+const DAV_CLASS: &str = "1";
+
+/// Characters percent-encoded in path segments of `href`s, as per RFC 3986 Section 3.3.
+const SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b'\\')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// Percent-encodes `name` for use as a path segment of an `href`.
+fn encode_segment(name: &str) -> PercentEncode<'_> {
+    utf8_percent_encode(name, SEGMENT)
+}
+
+fn write_response(xml: &mut String, href: &str, meta: &Meta) {
+    let resource_type = if is_directory(meta) {
+        "<D:collection/>"
+    } else {
+        ""
+    };
+    // NOTE: Writing to a `String` cannot fail.
+    _ = write!(
+        xml,
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop>\
+<D:resourcetype>{resource_type}</D:resourcetype>\
+<D:getcontentlength>{}</D:getcontentlength>\
+<D:getcontenttype>{}</D:getcontenttype>\
+</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>",
+        escape(href),
+        meta.size,
+        escape(meta.mime.as_ref()),
+    );
+}
+
+/// Handles WebDAV `OPTIONS` requests, so that clients can discover WebDAV support.
+pub async fn options() -> impl IntoResponse {
+    ([(ALLOW.as_str(), ALLOWED_METHODS), ("dav", DAV_CLASS)], ())
+}
+
+/// Handles WebDAV `PROPFIND` requests by listing properties of a tree node and,
+/// if the node is a directory and `Depth: 1` is requested, of its entries.
+///
+/// All properties are always returned, regardless of the request body.
+pub async fn propfind(
+    Extension(ref store): Extension<Arc<Store>>,
+    cert: Option<Extension<TrustedCertificate>>,
+    cx: TreeContext,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::trees::propfind", "called for `{cx}`");
+
+    let depth = match req.headers().get("depth").map(|v| v.to_str()) {
+        Some(Ok("0")) => 0,
+        Some(Ok("1")) => 1,
+        // NOTE: Absent `Depth` header means `infinity` as per RFC 4918 Section 9.1
+        None | Some(Ok("infinity")) => {
+            return Err((StatusCode::FORBIDDEN, "Infinite depth is not supported").into_response())
+        }
+        _ => return Err((StatusCode::BAD_REQUEST, "Invalid `Depth` header").into_response()),
+    };
+    // NOTE: The request path is already percent-encoded, only entry names need encoding.
+    let href = req.uri().path().trim_end_matches('/').to_string();
+
+    let repo = if cert.is_none() {
+        assert_repository_read(store, &cx.tag.repository, req)
+            .await
+            .map_err(IntoResponse::into_response)
+            .map(|(repo, _)| repo)?
+    } else {
+        store.repository(&cx.tag.repository)
+    };
+    let node = repo.tag(&cx.tag.name).node(&cx.path);
+    let meta = node.get_meta().await.map_err(|e| {
+        debug!(target: "app::trees::propfind", "failed to get metadata for `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+
+    let mut xml =
+        String::from(r#"<?xml version="1.0" encoding="utf-8"?><D:multistatus xmlns:D="DAV:">"#);
+    if is_directory(&meta) {
+        write_response(&mut xml, &format!("{href}/"), &meta);
+        if depth > 0 {
+            let dir: TreeDirectory<TreeEntry> = node.get_content_json().await.map_err(|e| {
+                debug!(target: "app::trees::propfind", "failed to get directory for `{cx}`: {:?}", e);
+                e.into_response()
+            })?;
+            for (name, entry) in dir.iter() {
+                let name = encode_segment(name.as_ref());
+                let href = if is_directory(&entry.meta) {
+                    format!("{href}/{name}/")
+                } else {
+                    format!("{href}/{name}")
+                };
+                write_response(&mut xml, &href, &entry.meta);
+            }
+        }
+    } else {
+        write_response(&mut xml, &href, &meta);
+    }
+    xml.push_str("</D:multistatus>");
+
+    Ok((
+        StatusCode::MULTI_STATUS,
+        [(CONTENT_TYPE, "application/xml; charset=utf-8")],
+        xml,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_are_encoded() {
+        assert_eq!(encode_segment("test-file.txt").to_string(), "test-file.txt");
+        assert_eq!(encode_segment("a:b").to_string(), "a:b");
+        assert_eq!(
+            encode_segment("my file #1 (100%).txt").to_string(),
+            "my%20file%20%231%20(100%25).txt"
+        );
+        assert_eq!(encode_segment("a/b?c").to_string(), "a%2Fb%3Fc");
+        assert_eq!(encode_segment("ä").to_string(), "%C3%A4");
+    }
+}
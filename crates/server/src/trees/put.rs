@@ -1,21 +1,35 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use super::super::alerts::Alerts;
+use super::super::events::{Event, EventBus};
+use super::super::{Clock, CreateError, GetError, OidcClaims, ScopeContext, ScopeLevel, Store};
+use crate::algorithms;
+use crate::cbor::{self, BoundedCbor};
+use crate::integrity::{self, VerificationPolicy};
+use crate::json::BoundedJson;
+use crate::scan::{Scanners, Verdict};
 
-use drawbridge_type::{Meta, TreeContext, TreeDirectory};
+use drawbridge_type::{MediaType, Meta, ScanMode, Scanning, TreeContext, TreeDirectory, TreeEntry};
 
 use async_std::sync::Arc;
 use axum::body::Body;
 use axum::extract::{BodyStream, RequestParts};
-use axum::http::{Request, StatusCode};
+use axum::http::header::WARNING;
+use axum::http::{HeaderValue, Request, StatusCode};
 use axum::response::IntoResponse;
-use axum::{Extension, Json};
+use axum::Extension;
 use futures::{io, TryStreamExt};
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 
+#[allow(clippy::too_many_arguments)]
 pub async fn put(
     Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref events): Extension<Arc<EventBus>>,
+    Extension(ref verification): Extension<Arc<VerificationPolicy>>,
+    Extension(ref alerts): Extension<Arc<Alerts>>,
+    Extension(ref scanners): Extension<Arc<Scanners>>,
+    Extension(ref clock): Extension<Arc<dyn Clock>>,
     claims: OidcClaims,
     cx: TreeContext,
     meta: Meta,
@@ -42,29 +56,185 @@ pub async fn put(
         .map_err(IntoResponse::into_response)?;
 
     let mut req = RequestParts::new(req);
-    let tag = user.repository(&cx.tag.repository.name).tag(&cx.tag.name);
-    match meta.mime.to_string().as_str() {
+    let repo = user.repository(&cx.tag.repository.name);
+    // NOTE: Trees of tags, which do not exist yet, are uploaded to pending tags, which are
+    // published along with their tree once the tag is created.
+    let tag = match repo.tag(&cx.tag.name).get_meta().await {
+        Ok(_) => repo.tag(&cx.tag.name),
+        Err(GetError::NotFound) => {
+            let pending = repo.create_pending_tag(&cx.tag.name).await.map_err(|e| {
+                debug!(target: "app::trees::put", "failed to create pending tag `{}`: {:?}", cx.tag, e);
+                e.into_response()
+            })?;
+            if let Err(e) = pending.start_upload(claims.subject(), clock.now()).await {
+                debug!(target: "app::trees::put", "failed to record start of upload to `{}`: {:?}", cx.tag, e);
+            }
+            pending
+        }
+        Err(e) => {
+            debug!(target: "app::trees::put", "failed to get tag `{}`: {:?}", cx.tag, e);
+            return Err(e.into_response());
+        }
+    };
+    // NOTE: CBOR-encoded directories are stored as JSON, see `cbor`.
+    let dir = match meta.mime.essence() {
         TreeDirectory::<()>::TYPE => {
-            let dir = req
-                .extract()
-                .await
-                .map(|Json(v)| v)
-                .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+            let BoundedJson(dir) = req
+                .extract::<BoundedJson<TreeDirectory<TreeEntry>>>()
+                .await?;
+            Some(dir)
+        }
+        mime if cbor::json_type(mime).as_deref() == Some(TreeDirectory::<()>::TYPE) => {
+            let BoundedCbor(dir) = req
+                .extract::<BoundedCbor<TreeDirectory<TreeEntry>>>()
+                .await?;
+            Some(dir)
+        }
+        _ => None,
+    };
+    let meta = match dir {
+        Some(ref dir) if !meta.mime.is(MediaType::DIRECTORY.essence()) => {
+            cbor::stored_meta(dir, MediaType::DIRECTORY).map_err(IntoResponse::into_response)?
+        }
+        _ => meta,
+    };
+    let hash = meta.hash.clone();
+    let size = meta.size;
+    let config = repo.get_json().await.map_err(|e| {
+        debug!(target: "app::trees::put", "failed to get repository `{}`: {:?}", cx.tag.repository, e);
+        e.into_response()
+    })?;
+    let rules = algorithms::rules(store, &cx.tag.repository, &config)
+        .await
+        .map_err(|e| {
+            debug!(target: "app::trees::put", "failed to get algorithm rules of `{}`: {:?}", cx.tag.repository, e);
+            e.into_response()
+        })?;
+    algorithms::assert_accepted(&rules, &hash).map_err(IntoResponse::into_response)?;
+    // NOTE: Only contents of file nodes are scanned, directories are validated above.
+    let scanning = match dir {
+        Some(_) => None,
+        None => config
+            .scanning
+            .filter(|scanning| !scanning.scanners.is_empty()),
+    };
+    let res = match dir {
+        Some(dir) => {
+            if let Err(e) = dir
+                .values()
+                .try_for_each(|entry| entry.annotations.assert_unreserved())
+            {
+                return Err(
+                    (StatusCode::BAD_REQUEST, format!("Invalid annotations: {e}")).into_response(),
+                );
+            }
             tag.create_directory_node(&cx.path, meta, &dir).await
         }
-        _ => {
+        None => {
             let body = req
                 .extract::<BodyStream>()
                 .await
                 .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?
-                .map_err(|e| io::Error::new(io::ErrorKind::Other, e));
-            tag.create_file_node(&cx.path, meta, body.into_async_read())
+                // NOTE: The body stream only fails if the request body ends prematurely.
+                .map_err(|e| io::Error::new(io::ErrorKind::UnexpectedEof, e));
+            match **verification {
+                VerificationPolicy::Inline => {
+                    tag.create_file_node(&cx.path, meta, body.into_async_read())
+                        .await
+                }
+                VerificationPolicy::Deferred => {
+                    let hash = hash.clone();
+                    tag.create_file_node_deferred(&cx.path, meta, body.into_async_read())
+                        .await
+                        .map(|(node, deferred)| {
+                            integrity::verify_deferred(
+                                store,
+                                events,
+                                alerts,
+                                cx.clone(),
+                                hash,
+                                size,
+                                deferred,
+                            );
+                            node
+                        })
+                }
+            }
+        }
+    };
+    match res {
+        Ok(_) => {}
+        Err(CreateError::Occupied) => {
+            return tag
+                .node(&cx.path)
+                .resolve_occupied(&hash)
                 .await
+                .map(|()| StatusCode::OK.into_response())
+                .map_err(|e| {
+                    debug!(target: "app::trees::put", "failed for `{cx}`: {:?}", e);
+                    e.into_response()
+                })
+        }
+        Err(e) => {
+            debug!(target: "app::trees::put", "failed for `{cx}`: {:?}", e);
+            return Err(e.into_response());
         }
     }
-    .map_err(|e| {
-        debug!(target: "app::trees::put", "failed for `{cx}`: {:?}", e);
-        e.into_response()
-    })
-    .map(|_| StatusCode::CREATED)
+
+    let mut warning = None;
+    if let Some(Scanning {
+        scanners: ref names,
+        mode: ScanMode::Sync,
+    }) = scanning
+    {
+        let node = tag.node(&cx.path);
+        let res = match scanners.scan(&node, &cx, names).await {
+            Ok(Verdict::Clean) => Ok(()),
+            Ok(Verdict::Flagged(reason)) => {
+                warn!(target: "app::trees::put", "`{cx}` was flagged by {reason}");
+                // NOTE: Reasons, which are not valid header values, are only logged.
+                warning = HeaderValue::try_from(format!("199 - \"Content flagged by {reason}\""))
+                    .ok()
+                    .map(|value| [(WARNING, value)]);
+                Ok(())
+            }
+            Ok(Verdict::Rejected(reason)) => {
+                warn!(target: "app::trees::put", "`{cx}` was rejected by {reason}");
+                Err((
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    format!("Content rejected by {reason}"),
+                ))
+            }
+            Err(e) => {
+                debug!(target: "app::trees::put", "failed to scan `{cx}`: {:?}", e);
+                Err((
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    "Content scanning failed".into(),
+                ))
+            }
+        };
+        if let Err(res) = res {
+            if let Err(e) = tag.quarantine_node(&cx.path).await {
+                debug!(target: "app::trees::put", "failed to quarantine `{cx}`: {:?}", e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+            }
+            return Err(res.into_response());
+        }
+    }
+    if let Err(e) = tag.node(&cx.path).touch(clock.now()).await {
+        debug!(target: "app::trees::put", "failed to record timestamps of `{cx}`: {:?}", e);
+    }
+    events.publish(Event::TreeEntryUploaded {
+        node: cx.clone(),
+        digest: hash.clone(),
+        size,
+    });
+    if let Some(Scanning {
+        scanners: names,
+        mode: ScanMode::Queued,
+    }) = scanning
+    {
+        scanners.scan_queued(store, events, cx, hash, size, names);
+    }
+    Ok((warning, StatusCode::CREATED).into_response())
 }
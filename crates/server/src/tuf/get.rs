@@ -0,0 +1,53 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{CachePolicy, Store};
+use super::Tuf;
+
+use async_std::sync::Arc;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Name of a TUF metadata file, e.g. `timestamp.json` or `1.root.json`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MetadataName(pub String);
+
+/// Returns TUF metadata file [MetadataName].
+///
+/// Versioned root metadata is immutable, all other metadata is mutable.
+pub async fn get(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(cache): Extension<Arc<CachePolicy>>,
+    Extension(MetadataName(name)): Extension<MetadataName>,
+    tuf: Option<Extension<Arc<Tuf>>>,
+) -> impl IntoResponse {
+    trace!(target: "app::tuf::get", "called for `{name}`");
+
+    if tuf.is_none() {
+        return Err((StatusCode::NOT_FOUND, "TUF metadata is not served".into()));
+    }
+    match store.read_tuf(&name).await {
+        Ok(Some(buf)) => {
+            let cache = if name.ends_with(".root.json") {
+                cache.immutable(true)
+            } else {
+                cache.mutable(true)
+            };
+            Ok(([(CONTENT_TYPE, "application/json")], cache, buf))
+        }
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            format!("TUF metadata `{name}` not found"),
+        )),
+        Err(e) => {
+            debug!(target: "app::tuf::get", "failed for `{name}`: {:?}", e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to read TUF metadata `{name}`"),
+            ))
+        }
+    }
+}
@@ -0,0 +1,167 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Role, TufKey};
+
+use drawbridge_type::digest::{hex, Algorithm, ContentDigest};
+
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+use anyhow::Context;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Version of the TUF specification metadata conforms to.
+const SPEC_VERSION: &str = "1.0.31";
+
+/// Encodes `value` as canonical JSON, i.e. without whitespace and with object keys sorted,
+/// which is what signatures are computed over.
+///
+/// Note, that strings are escaped like by [serde_json], which only differs from OLPC
+/// canonical JSON for control characters, which never occur in metadata.
+pub(super) fn canonical(value: &impl Serialize) -> anyhow::Result<Vec<u8>> {
+    serde_json::to_value(value)
+        .and_then(|value| serde_json::to_vec(&value))
+        .context("failed to encode metadata")
+}
+
+/// Returns TUF hashes of `digest` keyed by the TUF names of their algorithms.
+///
+/// Values of algorithms without a TUF name are omitted.
+pub(super) fn hashes(digest: &ContentDigest) -> BTreeMap<String, String> {
+    digest
+        .iter()
+        .filter_map(|(algorithm, value)| {
+            let name = match algorithm {
+                Algorithm::Sha224 => "sha224",
+                Algorithm::Sha256 => "sha256",
+                Algorithm::Sha384 => "sha384",
+                Algorithm::Sha512 => "sha512",
+                _ => return None,
+            };
+            Some((name.into(), hex(value.as_ref())))
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct Signature {
+    pub(super) keyid: String,
+    pub(super) sig: String,
+}
+
+/// Metadata of a role along with its signatures.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(super) struct Signed<T> {
+    pub(super) signatures: Vec<Signature>,
+    pub(super) signed: T,
+}
+
+impl<T: Serialize> Signed<T> {
+    /// Signs `signed` with `key`.
+    pub(super) fn sign(signed: T, key: &TufKey) -> anyhow::Result<Self> {
+        let sig = key.sign(&canonical(&signed)?);
+        Ok(Self {
+            signatures: vec![Signature {
+                keyid: key.id().into(),
+                sig,
+            }],
+            signed,
+        })
+    }
+}
+
+/// Fields common to metadata of all roles.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct Header {
+    #[serde(rename = "_type")]
+    pub(super) kind: String,
+    pub(super) spec_version: String,
+    pub(super) version: u64,
+    /// Expiry in RFC 3339 format
+    pub(super) expires: String,
+}
+
+impl Header {
+    /// Returns the header of version `version` of metadata of `role` signed at `now`.
+    pub(super) fn new(role: Role, version: u64, now: SystemTime) -> Self {
+        Self {
+            kind: role.to_string(),
+            spec_version: SPEC_VERSION.into(),
+            version,
+            expires: DateTime::<Utc>::from(now + role.lifetime())
+                .to_rfc3339_opts(SecondsFormat::Secs, true),
+        }
+    }
+
+    /// Returns whether the metadata expires earlier than half of the lifetime of `role` after
+    /// `now` and should therefore be signed again.
+    ///
+    /// Metadata with an invalid expiry is considered expiring.
+    pub(super) fn is_expiring(&self, role: Role, now: SystemTime) -> bool {
+        let refresh = DateTime::<Utc>::from(now + role.lifetime() / 2);
+        DateTime::parse_from_rfc3339(&self.expires).map_or(true, |expires| expires < refresh)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct KeyValue {
+    pub(super) public: String,
+}
+
+/// Public key of a role.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct Key {
+    pub(super) keytype: String,
+    pub(super) scheme: String,
+    pub(super) keyval: KeyValue,
+}
+
+/// Keys trusted to sign metadata of a role.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct RoleKeys {
+    pub(super) keyids: Vec<String>,
+    pub(super) threshold: u32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(super) struct Root {
+    #[serde(flatten)]
+    pub(super) header: Header,
+    pub(super) consistent_snapshot: bool,
+    pub(super) keys: BTreeMap<String, Key>,
+    pub(super) roles: BTreeMap<String, RoleKeys>,
+}
+
+/// A target file, i.e. a tag entry.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct Target {
+    pub(super) length: u64,
+    pub(super) hashes: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(super) struct Targets {
+    #[serde(flatten)]
+    pub(super) header: Header,
+    pub(super) targets: BTreeMap<String, Target>,
+}
+
+/// Version of a metadata file listed by snapshot or timestamp metadata.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(super) struct MetaFile {
+    pub(super) version: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) length: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) hashes: Option<BTreeMap<String, String>>,
+}
+
+/// Snapshot or timestamp metadata.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(super) struct Versions {
+    #[serde(flatten)]
+    pub(super) header: Header,
+    pub(super) meta: BTreeMap<String, MetaFile>,
+}
@@ -0,0 +1,132 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+"use strict";
+
+// Browses repositories using the JSON API only. Locations are kept in the URL fragment, i.e.
+// `#/`, `#/search?q=...`, `#/{owner}/{repo}` and `#/{owner}/{repo}/{tag}/{path...}`.
+
+// NOTE: Relative to `/ui/`, so that the UI works under any base path of the server.
+const API = "../api/v0.1.0";
+const DIRECTORY = "application/vnd.drawbridge.directory.v1+json";
+
+const main = document.getElementById("main");
+const breadcrumbs = document.getElementById("breadcrumbs");
+
+function escape(s) {
+  return String(s).replace(/[&<>"']/g, (c) => `&#${c.charCodeAt(0)};`);
+}
+
+function link(href, text) {
+  return `<a href="${escape(href)}">${escape(text)}</a>`;
+}
+
+function encodePath(parts) {
+  return parts.map(encodeURIComponent).join("/");
+}
+
+async function get(path) {
+  const res = await fetch(API + path, { headers: { Accept: "application/json" } });
+  if (!res.ok) {
+    throw new Error(`${res.status} ${res.statusText}: ${await res.text()}`);
+  }
+  return { type: res.headers.get("Content-Type") || "", body: await res.json() };
+}
+
+function crumbs(parts) {
+  breadcrumbs.innerHTML = parts
+    .map((_, i) => link("#/" + encodePath(parts.slice(0, i + 1)), parts[i]))
+    .join(" / ");
+}
+
+async function search(q, offset) {
+  breadcrumbs.innerHTML = "";
+  const query = new URLSearchParams({ q, offset });
+  const { body } = await get(`/_search?${query}`);
+  const rows = body.hits.map(
+    (hit) => `<tr>
+      <td>${link(`#/${hit.repository}`, hit.repository)}</td>
+      <td>${escape(hit.description || "")}</td>
+      <td>${hit.tags.map((tag) => link(`#/${hit.repository}/${tag}`, tag)).join(" ")}</td>
+    </tr>`
+  );
+  const next = body.next
+    ? link(`#/search?${new URLSearchParams({ q, offset: body.next })}`, "Next")
+    : "";
+  main.innerHTML = `<h2>${body.total} repositories matching <code>${escape(q)}</code></h2>
+    <table>${rows.join("")}</table>${next}`;
+}
+
+async function repository(owner, name) {
+  crumbs([owner, name]);
+  const [{ body: config }, { body: tags }] = await Promise.all([
+    get(`/${owner}/${name}`),
+    get(`/${owner}/${name}/_tag`),
+  ]);
+  const rows = tags.map((tag) => `<li>${link(`#/${owner}/${name}/${tag}`, tag)}</li>`);
+  main.innerHTML = `<h2>${escape(owner)}/${escape(name)}</h2>
+    <p>${escape(config.description || "")}</p>
+    <h3>Tags</h3><ul>${rows.join("") || "<li>None</li>"}</ul>`;
+}
+
+async function tree(owner, name, tag, path) {
+  crumbs([owner, name, tag, ...path]);
+  const repo = `/${owner}/${name}`;
+  const url = `${repo}/_tag/${tag}/tree/${encodePath(path)}`;
+  const here = `#${repo}/${encodePath([tag, ...path])}`;
+  const [{ body: entry }, node] = await Promise.all([
+    path.length ? Promise.resolve({}) : get(`${repo}/_tag/${tag}`),
+    fetch(API + url, { method: "HEAD" }),
+  ]);
+  if (!node.ok) {
+    throw new Error(`${node.status} ${node.statusText}`);
+  }
+  let html = path.length ? "" : `<h3>Tag</h3><pre>${escape(JSON.stringify(entry, null, 2))}</pre>`;
+  if ((node.headers.get("Content-Type") || "").startsWith(DIRECTORY)) {
+    const { body: dir } = await get(url);
+    const rows = Object.entries(dir).map(
+      ([child, e]) => `<tr>
+        <td>${link(`${here}/${encodeURIComponent(child)}`, child)}</td>
+        <td>${escape(e.type)}</td>
+        <td>${escape(e.length)}</td>
+        <td><code>${escape(e.digest ? Object.keys(e.digest).join(", ") : "")}</code></td>
+      </tr>`
+    );
+    html += `<h3>Contents</h3>
+      <table><tr><th>Name</th><th>Type</th><th>Length</th><th>Digests</th></tr>${rows.join("")}</table>`;
+  } else {
+    html += `<h3>${escape(path[path.length - 1])}</h3>
+      <p>${escape(node.headers.get("Content-Type"))}, ${escape(node.headers.get("Content-Length"))} bytes</p>
+      <p><a href="${escape(API + url)}" download>Download</a></p>`;
+  }
+  main.innerHTML = html;
+}
+
+async function route() {
+  const hash = location.hash.replace(/^#\/?/, "");
+  const [path, query] = hash.split("?");
+  const parts = path.split("/").filter((p) => p).map(decodeURIComponent);
+  try {
+    if (parts.length === 1 && parts[0] === "search") {
+      const params = new URLSearchParams(query);
+      await search(params.get("q") || "", Number(params.get("offset")) || 0);
+    } else if (parts.length === 2) {
+      await repository(...parts);
+    } else if (parts.length >= 3) {
+      await tree(parts[0], parts[1], parts[2], parts.slice(3));
+    } else {
+      breadcrumbs.innerHTML = "";
+      main.innerHTML = "<p>Search public repositories or browse to <code>#/{owner}/{repository}</code>.</p>";
+    }
+  } catch (e) {
+    main.innerHTML = `<p class="error">${escape(e.message)}</p>`;
+  }
+}
+
+document.getElementById("search").addEventListener("submit", (e) => {
+  e.preventDefault();
+  const q = new FormData(e.target).get("q");
+  location.hash = `#/search?${new URLSearchParams({ q })}`;
+});
+window.addEventListener("hashchange", route);
+route();
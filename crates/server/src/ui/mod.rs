@@ -0,0 +1,35 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Single-page UI for browsing repositories, tags and trees served at `/ui/`.
+//!
+//! The assets are compiled into the binary and only use the JSON API, so the UI does not have
+//! access to anything, which is not accessible via the API.
+
+use axum::extract::Path;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Redirect};
+use tracing::trace;
+
+const INDEX: &str = include_str!("assets/index.html");
+const SCRIPT: &str = include_str!("assets/app.js");
+const STYLE: &str = include_str!("assets/style.css");
+
+/// Redirects to the UI index, so that relative asset references resolve.
+pub(crate) async fn redirect() -> impl IntoResponse {
+    Redirect::permanent("/ui/")
+}
+
+/// Returns the UI asset at `path`.
+pub(crate) async fn asset(Path(path): Path<String>) -> impl IntoResponse {
+    trace!(target: "app::ui::asset", "called for `{path}`");
+
+    let (mime, body) = match path.trim_start_matches('/') {
+        "" | "index.html" => ("text/html; charset=utf-8", INDEX),
+        "app.js" => ("text/javascript; charset=utf-8", SCRIPT),
+        "style.css" => ("text/css; charset=utf-8", STYLE),
+        _ => return Err((StatusCode::NOT_FOUND, "UI asset not found")),
+    };
+    Ok(([(CONTENT_TYPE, mime)], body))
+}
@@ -0,0 +1,64 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::cluster::Cluster;
+use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use super::UploadPolicy;
+use crate::json;
+
+use drawbridge_type::TagContext;
+
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Cancels the session uploading the tree of the tag, which is not published yet, removing
+/// the tree uploaded so far, and returns the cancelled session.
+pub async fn delete(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref policy): Extension<Arc<UploadPolicy>>,
+    cluster: Option<Extension<Arc<Cluster>>>,
+    claims: OidcClaims,
+    cx: TagContext,
+) -> impl IntoResponse {
+    trace!(target: "app::uploads::delete", "called for `{cx}`");
+
+    let user = claims
+        .assert_user(
+            store,
+            &cx.repository.owner,
+            ScopeContext::Tag,
+            ScopeLevel::Write,
+        )
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let repo = user.repository(&cx.repository.name);
+    // NOTE: Other instances of the cluster may publish the tag concurrently.
+    let lock = match cluster {
+        Some(Extension(ref cluster)) => Some(cluster.lock_tag(store, &cx).await.map_err(|e| {
+            debug!(target: "app::uploads::delete", "failed to lock `{cx}`: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })?),
+        None => None,
+    };
+    let res = match repo.upload_session(&cx.name, policy.expiry).await {
+        Ok(session) => repo
+            .remove_pending_tag(&cx.name)
+            .await
+            .map(|()| session)
+            .map_err(|e| {
+                debug!(target: "app::uploads::delete", "failed to remove pending tag `{cx}`: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }),
+        Err(e) => {
+            debug!(target: "app::uploads::delete", "failed to get upload session of `{cx}`: {:?}", e);
+            Err(e.into_response())
+        }
+    };
+    if let Some(lock) = lock {
+        lock.release().await;
+    }
+    json::encode(&res?).map_err(IntoResponse::into_response)
+}
@@ -0,0 +1,46 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use super::UploadPolicy;
+use crate::json;
+use crate::pagination::paginate;
+
+use drawbridge_type::RepositoryContext;
+
+use async_std::sync::Arc;
+use axum::http::Uri;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Returns sessions uploading trees to tags of the repository, which are not published yet,
+/// ordered by tag name.
+///
+/// The listing is paginated by the `cursor` and `limit` query parameters.
+pub async fn get(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref policy): Extension<Arc<UploadPolicy>>,
+    claims: OidcClaims,
+    cx: RepositoryContext,
+    uri: Uri,
+) -> impl IntoResponse {
+    trace!(target: "app::uploads::get", "called for `{cx}`");
+
+    let user = claims
+        .assert_user(store, &cx.owner, ScopeContext::Tag, ScopeLevel::Read)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let sessions = user
+        .repository(&cx.name)
+        .upload_sessions(policy.expiry)
+        .await
+        .map_err(|e| {
+            debug!(target: "app::uploads::get", "failed to list upload sessions of `{cx}`: {:?}", e);
+            e.into_response()
+        })?;
+    let (sessions, link): (Vec<_>, _) = paginate(&uri, sessions)?;
+    json::encode(&sessions)
+        .map(|res| (link, res))
+        .map_err(IntoResponse::into_response)
+}
@@ -0,0 +1,129 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Sessions uploading trees to tags, which are not published yet.
+//!
+//! A session starts with the first node uploaded to a tag, which does not exist yet, and
+//! ends once the tag is published. Sessions of a repository are listed as [UploadSession]s
+//! by [get] under `_uploads` and may be cancelled using [delete] under `_uploads/TAG`, which
+//! removes the tree uploaded so far. Sessions, to which no node was uploaded for the expiry
+//! of the [UploadPolicy], are abandoned and removed in background, by the leader only if the
+//! store is shared by a cluster.
+//!
+//! [UploadSession]: drawbridge_type::UploadSession
+
+mod delete;
+mod get;
+
+pub use delete::*;
+pub use get::*;
+
+use super::cluster::Cluster;
+use super::{Clock, Store};
+
+use drawbridge_type::{RepositoryContext, TagContext, UserContext};
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::Context;
+use async_std::sync::Arc;
+use async_std::task::{sleep, spawn};
+use tracing::{debug, warn};
+
+/// Interval, in which expired sessions are removed.
+const EXPIRE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Policy of sessions uploading trees to tags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UploadPolicy {
+    /// Time, after which sessions, to which no node was uploaded, expire.
+    pub expiry: Duration,
+}
+
+impl Default for UploadPolicy {
+    fn default() -> Self {
+        Self {
+            expiry: Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+/// Removes sessions of all repositories of `store`, which are expired by `policy` at `now`,
+/// and returns their number, locking each tag if `store` is shared with other instances of
+/// `cluster`.
+async fn expire(
+    store: &Store,
+    cluster: Option<&Cluster>,
+    policy: &UploadPolicy,
+    now: SystemTime,
+) -> anyhow::Result<usize> {
+    let users = store
+        .users()
+        .await
+        .map_err(anyhow::Error::from)
+        .context("failed to list users")?;
+    let mut expired = 0;
+    for name in users {
+        let cx = UserContext { name };
+        let user = store.user(&cx);
+        let repos = user
+            .repositories()
+            .await
+            .map_err(anyhow::Error::from)
+            .with_context(|| format!("failed to list repositories of `{cx}`"))?;
+        for name in repos {
+            let repo = user.repository(&name);
+            let sessions = repo
+                .upload_sessions(policy.expiry)
+                .await
+                .map_err(anyhow::Error::from)
+                .with_context(|| format!("failed to list upload sessions of `{cx}/{name}`"))?;
+            for session in sessions.into_iter().filter(|s| s.is_expired(now)) {
+                let cx = TagContext {
+                    repository: RepositoryContext {
+                        owner: cx.clone(),
+                        name: name.clone(),
+                    },
+                    name: session.tag,
+                };
+                // NOTE: Other instances of the cluster may publish the tag concurrently.
+                let lock = match cluster {
+                    Some(cluster) => Some(cluster.lock_tag(store, &cx).await?),
+                    None => None,
+                };
+                let res = repo.remove_pending_tag(&cx.name).await;
+                if let Some(lock) = lock {
+                    lock.release().await;
+                }
+                res.with_context(|| format!("failed to remove pending tag `{cx}`"))?;
+                expired += 1;
+            }
+        }
+    }
+    Ok(expired)
+}
+
+/// Removes sessions of `store`, which are expired by `policy`, every [EXPIRE_INTERVAL] in
+/// background, if the instance is the leader of `cluster`, if any.
+pub(crate) fn expire_periodically(
+    store: &Arc<Store>,
+    cluster: Option<Arc<Cluster>>,
+    policy: Arc<UploadPolicy>,
+    clock: Arc<dyn Clock>,
+) {
+    let store = Arc::clone(store);
+    _ = spawn(async move {
+        loop {
+            if cluster.as_ref().is_none_or(|cluster| cluster.is_leader()) {
+                match expire(&store, cluster.as_deref(), &policy, clock.now()).await {
+                    Ok(0) => {}
+                    Ok(n) => debug!(target: "app::uploads", "removed {n} expired upload sessions"),
+                    Err(e) => {
+                        warn!(target: "app::uploads", "failed to remove expired upload sessions: {:?}", e)
+                    }
+                }
+            }
+            sleep(EXPIRE_INTERVAL).await;
+        }
+    });
+}
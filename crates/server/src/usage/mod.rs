@@ -0,0 +1,445 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Accounting of bandwidth and storage used per repository.
+//!
+//! Bytes uploaded and downloaded are accounted by [handle] from the bytes of request and
+//! response bodies of successful requests to repository endpoints actually transferred,
+//! rather than from the lengths declared in headers. Bytes stored are accounted from lengths
+//! of tree nodes uploaded and deleted and of repositories and tags deleted and restored, as
+//! published on the [EventBus].
+//!
+//! Changes of counters are kept in memory and periodically merged into the counters rolled up
+//! into the store, so that at most [ROLLUP_INTERVAL] worth of usage is lost if the server
+//! crashes. Instances of a [Cluster] merge their changes under a lock and pick up changes of
+//! other instances on each roll-up, so that counters are accounted across the cluster.
+//!
+//! If a storage quota is set, uploads declaring a length, which would make the bytes stored
+//! in all repositories exceed it, are rejected with [Error::QuotaExceeded].
+
+mod repository;
+mod user;
+
+pub use repository::*;
+pub use user::*;
+
+use super::cluster::Cluster;
+use super::events::{Event, EventBus};
+use super::handle::split_api_path;
+use super::problem::Problem;
+use super::Store;
+
+use drawbridge_type::{Error, RepositoryContext, Usage, UserContext, UserName, UserUsage};
+
+use std::collections::{BTreeMap, HashMap};
+use std::mem::take;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use async_std::sync::Arc;
+use async_std::task::{sleep, spawn};
+use axum::body::{boxed, Body, BoxBody, Bytes, HttpBody};
+use axum::headers::{ContentLength, HeaderMapExt};
+use axum::http::{HeaderMap, Method, Request};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use futures::TryStreamExt;
+use hyper::body::SizeHint;
+use tracing::{trace, warn};
+
+/// Interval, in which counters are rolled up into the store.
+pub const ROLLUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Name of the cluster lock serializing roll-ups of counters.
+const ROLLUP_LOCK: &str = "usage";
+
+/// Change of usage of a repository, which was not rolled up yet.
+#[derive(Clone, Copy, Debug, Default)]
+struct Delta {
+    /// Usage added
+    added: Usage,
+    /// Number of bytes stored removed
+    removed: u64,
+}
+
+impl From<Usage> for Delta {
+    fn from(added: Usage) -> Self {
+        Self { added, removed: 0 }
+    }
+}
+
+impl Delta {
+    fn merge(&mut self, other: Self) {
+        self.added += other.added;
+        self.removed = self.removed.saturating_add(other.removed);
+    }
+
+    fn apply(&self, usage: &mut Usage) {
+        *usage += self.added;
+        usage.stored = usage.stored.saturating_sub(self.removed);
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    /// Usage of each repository including changes not rolled up yet
+    totals: HashMap<RepositoryContext, Usage>,
+    /// Changes of usage of each repository since they were last rolled up
+    pending: HashMap<RepositoryContext, Delta>,
+}
+
+/// Usage counters of all repositories.
+#[derive(Debug, Default)]
+pub struct Accounting {
+    counters: Mutex<Counters>,
+    /// Cluster, which the counters are shared with, if any
+    cluster: Option<Arc<Cluster>>,
+    /// Maximum number of bytes stored in all repositories
+    quota: Option<u64>,
+}
+
+/// Parses usage counters keyed by repository as rolled up into the store.
+fn parse(usage: BTreeMap<String, Usage>) -> HashMap<RepositoryContext, Usage> {
+    usage
+        .into_iter()
+        .filter_map(|(repo, usage)| match repo.parse() {
+            Ok(repo) => Some((repo, usage)),
+            Err(e) => {
+                warn!(target: "app::usage", "ignoring usage of invalid repository `{repo}`: {:?}", e);
+                None
+            }
+        })
+        .collect()
+}
+
+impl Accounting {
+    /// Loads counters rolled up into `store`, which are shared with `cluster`, if any, and
+    /// enforces storage `quota`, if any.
+    pub(crate) async fn load(
+        store: &Store,
+        cluster: Option<Arc<Cluster>>,
+        quota: Option<u64>,
+    ) -> anyhow::Result<Self> {
+        let totals = parse(store.read_usage().await?);
+        Ok(Self {
+            counters: Mutex::new(Counters {
+                totals,
+                pending: HashMap::new(),
+            }),
+            cluster,
+            quota,
+        })
+    }
+
+    fn update(&self, cx: &RepositoryContext, delta: Delta) {
+        if let Ok(mut counters) = self.counters.lock() {
+            delta.apply(counters.totals.entry(cx.clone()).or_default());
+            counters.pending.entry(cx.clone()).or_default().merge(delta);
+        }
+    }
+
+    /// Merges pending changes into the counters rolled up into `store` and replaces the
+    /// counters with the result.
+    ///
+    /// Pending changes are kept for the next roll-up on failure.
+    async fn merge(&self, store: &Store) -> anyhow::Result<()> {
+        let pending = take(
+            &mut self
+                .counters
+                .lock()
+                .map_err(|_| anyhow!("usage counters are poisoned"))?
+                .pending,
+        );
+        // NOTE: Other instances of a cluster may have changed the counters in the store.
+        if pending.is_empty() && self.cluster.is_none() {
+            return Ok(());
+        }
+        let merged = async {
+            let lock = match self.cluster {
+                Some(ref cluster) => Some(cluster.lock(store, ROLLUP_LOCK).await?),
+                None => None,
+            };
+            let merged = async {
+                let mut usage = store.read_usage().await?;
+                for (cx, delta) in &pending {
+                    delta.apply(usage.entry(cx.to_string()).or_default());
+                }
+                if !pending.is_empty() {
+                    store.write_usage(&usage).await?;
+                }
+                anyhow::Ok(usage)
+            }
+            .await;
+            if let Some(lock) = lock {
+                lock.release().await;
+            }
+            merged
+        }
+        .await;
+
+        let mut counters = self
+            .counters
+            .lock()
+            .map_err(|_| anyhow!("usage counters are poisoned"))?;
+        match merged {
+            Ok(usage) => {
+                let mut totals = parse(usage);
+                for (cx, delta) in &counters.pending {
+                    delta.apply(totals.entry(cx.clone()).or_default());
+                }
+                counters.totals = totals;
+                trace!(target: "app::usage", "rolled up usage of {} repositories", pending.len());
+                Ok(())
+            }
+            Err(e) => {
+                for (cx, delta) in pending {
+                    counters.pending.entry(cx).or_default().merge(delta);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns usage of repository `cx`.
+    pub fn repository(&self, cx: &RepositoryContext) -> Usage {
+        self.counters
+            .lock()
+            .ok()
+            .and_then(|counters| counters.totals.get(cx).copied())
+            .unwrap_or_default()
+    }
+
+    /// Returns the maximum number of bytes stored in all repositories, if any.
+    pub fn quota(&self) -> Option<u64> {
+        self.quota
+    }
+
+    /// Returns the number of bytes stored in all repositories.
+    pub fn stored(&self) -> u64 {
+        self.counters
+            .lock()
+            .map(|counters| {
+                counters
+                    .totals
+                    .values()
+                    .fold(0, |sum: u64, usage| sum.saturating_add(usage.stored))
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the sum of usage of all repositories of each user.
+    pub fn namespaces(&self) -> HashMap<UserName, Usage> {
+        self.counters
+            .lock()
+            .map(|counters| {
+                counters
+                    .totals
+                    .iter()
+                    .fold(HashMap::new(), |mut namespaces, (repo, usage)| {
+                        *namespaces.entry(repo.owner.name.clone()).or_default() += *usage;
+                        namespaces
+                    })
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns usage of all repositories of user `cx`.
+    pub fn user(&self, cx: &UserContext) -> UserUsage {
+        self.counters
+            .lock()
+            .map(|counters| {
+                counters
+                    .totals
+                    .iter()
+                    .filter(|(repo, _)| repo.owner == *cx)
+                    .map(|(repo, usage)| (repo.name.to_string(), *usage))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Accounts bytes stored by tree nodes uploaded and deleted and by repositories and tags
+    /// deleted and restored, as published on `events`, in background.
+    pub(crate) fn subscribe(self: &Arc<Self>, events: &EventBus) {
+        let accounting = Arc::clone(self);
+        let events = events.subscribe();
+        _ = spawn(async move {
+            while let Ok((_, event)) = events.recv().await {
+                match event {
+                    Event::RepositoryRestored { size, .. }
+                    | Event::TagRestored { size, .. }
+                    | Event::TreeEntryUploaded { size, .. } => accounting.update(
+                        event.repository(),
+                        Usage {
+                            stored: size,
+                            ..Default::default()
+                        }
+                        .into(),
+                    ),
+                    Event::RepositoryDeleted { size, .. }
+                    | Event::TagDeleted { size, .. }
+                    | Event::EntityDeleted { size, .. } => accounting.update(
+                        event.repository(),
+                        Delta {
+                            removed: size,
+                            ..Default::default()
+                        },
+                    ),
+                    Event::RepositoryCreated { .. }
+                    | Event::TagUpdated { .. }
+                    | Event::EntityRepaired { .. } => {}
+                }
+            }
+        });
+    }
+
+    /// Rolls up counters into `store` every [ROLLUP_INTERVAL] in background.
+    pub(crate) fn roll_up(self: &Arc<Self>, store: &Arc<Store>) {
+        let accounting = Arc::clone(self);
+        let store = Arc::clone(store);
+        _ = spawn(async move {
+            loop {
+                sleep(ROLLUP_INTERVAL).await;
+                if let Err(e) = accounting.merge(&store).await {
+                    warn!(target: "app::usage", "failed to roll up usage: {:?}", e);
+                }
+            }
+        });
+    }
+}
+
+/// Returns the repository targeted by a request to `path`, if any.
+fn target(path: &str) -> Option<RepositoryContext> {
+    let (_, path) = split_api_path(path)?;
+    let path = path.trim_start_matches('/');
+    let head = path.split_once("/_").map_or(path, |(head, _)| head);
+    head.split_once('/')?.try_into().ok()
+}
+
+/// Response body, which accounts the bytes sent as downloaded from the repository once
+/// dropped, so that responses interrupted by clients are accounted as far as they were sent.
+struct Metered {
+    body: BoxBody,
+    accounting: Arc<Accounting>,
+    cx: RepositoryContext,
+    sent: u64,
+}
+
+impl HttpBody for Metered {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let poll = Pin::new(&mut self.body).poll_data(cx);
+        if let Poll::Ready(Some(Ok(ref data))) = poll {
+            self.sent = self.sent.saturating_add(data.len() as u64);
+        }
+        poll
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Pin::new(&mut self.body).poll_trailers(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
+
+impl Drop for Metered {
+    fn drop(&mut self) {
+        let sent = self.sent;
+        if sent > 0 {
+            self.accounting.update(
+                &self.cx,
+                Usage {
+                    downloaded: sent,
+                    ..Default::default()
+                }
+                .into(),
+            );
+        }
+    }
+}
+
+/// Accounts bytes of request and response bodies actually transferred by successful requests
+/// to repository endpoints and rejects uploads declaring a length exceeding the storage quota.
+pub(crate) async fn handle(req: Request<Body>, next: Next<Body>) -> Response {
+    let accounting = req.extensions().get::<Arc<Accounting>>().cloned();
+    let (accounting, cx) = match (accounting, target(req.uri().path())) {
+        (Some(accounting), Some(cx)) => (accounting, cx),
+        _ => return next.run(req).await,
+    };
+    let method = req.method().clone();
+    if let Some(quota) = accounting.quota {
+        let declared = req
+            .headers()
+            .typed_get::<ContentLength>()
+            .map(|ContentLength(n)| n)
+            .unwrap_or_default();
+        let stored = accounting.stored();
+        if method == Method::PUT && stored.saturating_add(declared) > quota {
+            trace!(target: "app::usage", "reject upload of {declared} bytes to `{cx}` exceeding quota");
+            return Problem::from(Error::QuotaExceeded { quota, stored })
+                .detail(format!(
+                    "Storing {declared} more bytes would exceed the storage quota, {} bytes remain",
+                    quota.saturating_sub(stored)
+                ))
+                .into_response();
+        }
+    }
+
+    // NOTE: Empty bodies are passed through as is, since handlers forwarding requests
+    // distinguish them from streamed ones.
+    let received = Arc::new(AtomicU64::new(0));
+    let req = if req.body().is_end_stream() {
+        req
+    } else {
+        let received = Arc::clone(&received);
+        req.map(|body| {
+            Body::wrap_stream(body.inspect_ok(move |data| {
+                _ = received.fetch_add(data.len() as u64, Ordering::Relaxed);
+            }))
+        })
+    };
+    let res = next.run(req).await;
+    if !res.status().is_success() {
+        return res;
+    }
+    let uploaded = received.load(Ordering::Relaxed);
+    if uploaded > 0 {
+        accounting.update(
+            &cx,
+            Usage {
+                uploaded,
+                ..Default::default()
+            }
+            .into(),
+        );
+    }
+    if method == Method::HEAD {
+        return res;
+    }
+    res.map(|body| {
+        boxed(Metered {
+            body,
+            accounting,
+            cx,
+            sent: 0,
+        })
+    })
+}
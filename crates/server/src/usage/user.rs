@@ -0,0 +1,29 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use super::Accounting;
+use crate::json;
+
+use drawbridge_type::UserContext;
+
+use async_std::sync::Arc;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::trace;
+
+/// Returns bandwidth and storage used by all repositories of the user.
+pub async fn user(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref accounting): Extension<Arc<Accounting>>,
+    claims: OidcClaims,
+    cx: UserContext,
+) -> impl IntoResponse {
+    trace!(target: "app::usage::user", "called for `{cx}`");
+
+    _ = claims
+        .assert_user(store, &cx, ScopeContext::User, ScopeLevel::Read)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    json::encode(&accounting.user(&cx)).map_err(IntoResponse::into_response)
+}
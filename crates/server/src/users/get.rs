@@ -1,26 +1,36 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use super::super::{OidcClaims, ReplicaCertificate, ScopeContext, ScopeLevel, Store};
 
 use drawbridge_type::UserContext;
 
 use async_std::sync::Arc;
+use axum::body::Body;
+use axum::extract::RequestParts;
+use axum::http::Request;
 use axum::response::IntoResponse;
 use axum::Extension;
 use tracing::{debug, trace};
 
 pub async fn get(
     Extension(ref store): Extension<Arc<Store>>,
-    claims: OidcClaims,
+    replica: Option<Extension<ReplicaCertificate>>,
     ref cx: UserContext,
+    req: Request<Body>,
 ) -> impl IntoResponse {
     trace!(target: "app::users::get", "called for `{cx}`");
 
-    let user = claims
-        .assert_user(store, cx, ScopeContext::User, ScopeLevel::Read)
-        .await
-        .map_err(IntoResponse::into_response)?;
+    let user = if replica.is_none() {
+        RequestParts::new(req)
+            .extract::<OidcClaims>()
+            .await?
+            .assert_user(store, cx, ScopeContext::User, ScopeLevel::Read)
+            .await
+            .map_err(IntoResponse::into_response)?
+    } else {
+        store.user(cx)
+    };
 
     // TODO: Stream body
     // https://github.com/profianinc/drawbridge/issues/56
@@ -1,14 +1,15 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use super::super::{CreateError, OidcClaims, ScopeContext, ScopeLevel, Store};
+use crate::json::BoundedJson;
 
 use drawbridge_type::{Meta, UserContext, UserRecord};
 
 use async_std::sync::Arc;
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::{Extension, Json};
+use axum::Extension;
 use tracing::{debug, trace};
 
 pub async fn put(
@@ -16,7 +17,7 @@ pub async fn put(
     claims: OidcClaims,
     ref cx: UserContext,
     meta: Meta,
-    Json(ref record): Json<UserRecord>,
+    BoundedJson(ref record): BoundedJson<UserRecord>,
 ) -> impl IntoResponse {
     trace!(target: "app::users::put", "called for `{cx}`");
 
@@ -28,12 +29,18 @@ pub async fn put(
         return Err((StatusCode::UNAUTHORIZED, "OpenID Connect subject mismatch").into_response());
     }
 
-    store
-        .create_user(cx, meta, record)
-        .await
-        .map_err(|e| {
-            debug!(target: "app::users::put", "failed for `{cx}`: {:?}", e);
-            e.into_response()
-        })
-        .map(|_| StatusCode::CREATED)
+    let hash = meta.hash.clone();
+    match store.create_user(cx, meta, record).await {
+        Ok(_) => Ok(StatusCode::CREATED),
+        Err(CreateError::Occupied) => store
+            .user(cx)
+            .resolve_occupied(&hash)
+            .await
+            .map(|()| StatusCode::OK),
+        Err(e) => Err(e),
+    }
+    .map_err(|e| {
+        debug!(target: "app::users::put", "failed for `{cx}`: {:?}", e);
+        e.into_response()
+    })
 }
@@ -0,0 +1,42 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::Store;
+use crate::auth::assert_repository_read;
+use crate::json;
+use crate::problem::Problem;
+
+use drawbridge_type::{Error, TagContext};
+
+use async_std::sync::Arc;
+use axum::body::Body;
+use axum::http::Request;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::{debug, trace};
+
+/// Returns the latest vulnerability report attached to the tag in the
+/// [VulnerabilityReport](drawbridge_type::VulnerabilityReport) schema, SARIF logs are
+/// converted.
+pub async fn get(
+    Extension(ref store): Extension<Arc<Store>>,
+    cx: TagContext,
+    req: Request<Body>,
+) -> impl IntoResponse {
+    trace!(target: "app::vulnerabilities::get", "called for `{cx}`");
+
+    let (repo, _) = assert_repository_read(store, &cx.repository, req)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let report = repo
+        .tag(&cx.name)
+        .latest_vulnerability_report()
+        .await
+        .map_err(|e| {
+            debug!(target: "app::vulnerabilities::get", "failed for `{cx}`: {:?}", e);
+            e.into_response()
+        })?
+        .ok_or_else(|| Problem::from(Error::NotFound).into_response())?;
+    json::encode(&report).map_err(IntoResponse::into_response)
+}
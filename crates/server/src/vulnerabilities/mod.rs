@@ -0,0 +1,19 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Vulnerability reports attached to tags by scanners.
+//!
+//! Reports are either SARIF logs or documents in the [VulnerabilityReport] schema and are
+//! linked to the tag tree by the digests of their subjects. Only the latest report of a tag
+//! is served and consulted by the [VulnerabilityPolicy] of the repository, which may block
+//! pulls of the tag and its tree, see [Repository::pull_tag].
+//!
+//! [VulnerabilityPolicy]: drawbridge_type::VulnerabilityPolicy
+//! [VulnerabilityReport]: drawbridge_type::VulnerabilityReport
+//! [Repository::pull_tag]: crate::store::Repository::pull_tag
+
+mod get;
+mod put;
+
+pub use get::*;
+pub use put::*;
@@ -0,0 +1,88 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use crate::json::BoundedBody;
+use crate::Clock;
+
+use drawbridge_type::digest::hex;
+use drawbridge_type::{Meta, TagContext, VulnerabilityReport};
+
+use std::time::SystemTime;
+
+use async_std::sync::Arc;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Extension;
+use ring::digest::{digest, SHA256};
+use tracing::{debug, trace};
+
+/// Attaches a vulnerability report, either a SARIF log or a document in the
+/// [VulnerabilityReport] schema, to the tag.
+///
+/// At least one of the report subjects must match a node of the tag tree. The report
+/// becomes the latest one of the tag.
+pub async fn put(
+    Extension(store): Extension<Arc<Store>>,
+    Extension(ref clock): Extension<Arc<dyn Clock>>,
+    claims: OidcClaims,
+    cx: TagContext,
+    meta: Meta,
+    BoundedBody(body): BoundedBody,
+) -> impl IntoResponse {
+    trace!(target: "app::vulnerabilities::put", "called for `{cx}`");
+
+    if meta.hash.is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "At least one content digest value must be specified",
+        )
+            .into_response());
+    }
+    let mime = meta.mime.essence();
+    if mime != VulnerabilityReport::TYPE && mime != VulnerabilityReport::SARIF_TYPE {
+        return Err((StatusCode::BAD_REQUEST, "Invalid content type").into_response());
+    }
+
+    let user = claims
+        .assert_user(
+            &store,
+            &cx.repository.owner,
+            ScopeContext::Tag,
+            ScopeLevel::Write,
+        )
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let report = VulnerabilityReport::decode(mime, &body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("{e:#}")).into_response())?;
+
+    let tag = user.repository(&cx.repository.name).tag(&cx.name);
+    let nodes = tag.walk().await.map_err(|e| {
+        debug!(target: "app::vulnerabilities::put", "failed to walk tree of `{cx}`: {:?}", e);
+        e.into_response()
+    })?;
+    if !nodes.values().any(|meta| report.matches(&meta.hash)) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Report subject does not match any node of the tag tree",
+        )
+            .into_response());
+    }
+
+    // NOTE: Names start with the zero-padded time of attachment, so that the latest report
+    // is the last one by name.
+    let secs = clock
+        .now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let hex = hex(digest(&SHA256, &body));
+    tag.create_vulnerability_report(&format!("{secs:020}-{hex}"), meta, body.as_slice())
+        .await
+        .map_err(|e| {
+            debug!(target: "app::vulnerabilities::put", "failed for `{cx}`: {:?}", e);
+            e.into_response()
+        })
+        .map(|_| StatusCode::CREATED)
+}
@@ -0,0 +1,37 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{OidcClaims, ScopeContext, ScopeLevel, Store};
+use super::Webhooks;
+use crate::json;
+use crate::pagination::paginate;
+
+use drawbridge_type::RepositoryContext;
+
+use async_std::sync::Arc;
+use axum::http::Uri;
+use axum::response::IntoResponse;
+use axum::Extension;
+use tracing::trace;
+
+/// Returns recent webhook deliveries of the repository.
+///
+/// The listing is paginated by the `cursor` and `limit` query parameters.
+pub async fn deliveries(
+    Extension(ref store): Extension<Arc<Store>>,
+    Extension(ref webhooks): Extension<Arc<Webhooks>>,
+    claims: OidcClaims,
+    cx: RepositoryContext,
+    uri: Uri,
+) -> impl IntoResponse {
+    trace!(target: "app::webhooks::deliveries", "called for `{cx}`");
+
+    _ = claims
+        .assert_user(store, &cx.owner, ScopeContext::Repository, ScopeLevel::Read)
+        .await
+        .map_err(IntoResponse::into_response)?;
+    let (deliveries, link): (Vec<_>, _) = paginate(&uri, webhooks.deliveries(&cx))?;
+    json::encode(&deliveries)
+        .map(|res| (link, res))
+        .map_err(IntoResponse::into_response)
+}
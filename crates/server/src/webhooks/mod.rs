@@ -0,0 +1,374 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Outbound webhooks configured per repository in [RepositoryConfig].
+//!
+//! Payloads are `POST`ed as JSON with the HMAC-SHA256 signature of the body computed
+//! using the webhook secret in the `Drawbridge-Signature` header, formatted as
+//! `sha256=<hex>`. Failed deliveries are retried with exponential backoff and the most
+//! recent deliveries of each repository are kept in memory for debugging.
+//!
+//! Tags created, deleted and restored from the trash are notified of as
+//! [WebhookEvent::TagCreate], [WebhookEvent::TagDelete] and [WebhookEvent::TagUpdate].
+//!
+//! Webhooks are notified of [Event]s published on the [EventBus], which [Webhooks::subscribe]
+//! maps to [WebhookEvent]s.
+//!
+//! [RepositoryConfig]: drawbridge_type::RepositoryConfig
+
+mod deliveries;
+
+pub use deliveries::*;
+
+use super::events::{DeleteCause, Event, EventBus};
+use super::{Clock, Repository, Store};
+
+use drawbridge_type::digest::{hex, ContentDigest};
+use drawbridge_type::{
+    RepositoryContext, TagContext, Webhook, WebhookAttempt, WebhookDelivery, WebhookEvent,
+    WebhookPayload,
+};
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_std::sync::Arc;
+use async_std::task::{sleep, spawn, spawn_blocking};
+use camino::Utf8Path;
+use chrono::{DateTime, SecondsFormat, Utc};
+use openidconnect::url::Url;
+use ring::hmac;
+use tracing::{debug, trace};
+
+/// Maximum number of delivery attempts.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry, which is doubled after each subsequent attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Maximum number of deliveries kept per repository.
+const MAX_DELIVERIES: usize = 100;
+
+/// Signed payload of a delivery, which is sent once per attempt.
+struct Request {
+    url: Url,
+    id: String,
+    event: String,
+    signature: String,
+    body: Vec<u8>,
+}
+
+impl Request {
+    /// Sends the payload and returns the response status, if one was received, and the
+    /// error otherwise.
+    fn send(&self) -> (Option<u16>, Option<String>) {
+        let res = ureq::post(self.url.as_str())
+            .set("Content-Type", "application/json")
+            .set("Drawbridge-Delivery", &self.id)
+            .set("Drawbridge-Event", &self.event)
+            .set(
+                "Drawbridge-Signature",
+                &format!("sha256={}", self.signature),
+            )
+            .send_bytes(&self.body);
+        match res {
+            Ok(res) => (Some(res.status()), None),
+            Err(ureq::Error::Status(status, _)) => (Some(status), None),
+            Err(e) => (None, Some(e.to_string())),
+        }
+    }
+}
+
+/// Dispatcher of webhook payloads, which keeps a log of recent deliveries.
+#[derive(Debug)]
+pub struct Webhooks {
+    deliveries: Mutex<HashMap<RepositoryContext, VecDeque<WebhookDelivery>>>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Webhooks {
+    /// Constructs a new [Webhooks] dispatcher, which timestamps delivery attempts using `clock`.
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            deliveries: Default::default(),
+            clock,
+        }
+    }
+
+    /// Notifies webhooks of events published on `events` in background.
+    pub(crate) fn subscribe(self: &Arc<Self>, store: &Arc<Store>, events: &EventBus) {
+        let webhooks = Arc::clone(self);
+        let store = Arc::clone(store);
+        let events = events.subscribe();
+        _ = spawn(async move {
+            while let Ok((_, event)) = events.recv().await {
+                webhooks.handle(&store, event).await;
+            }
+        });
+    }
+
+    async fn handle(self: &Arc<Self>, store: &Store, event: Event) {
+        match event {
+            Event::RepositoryCreated { .. }
+            | Event::RepositoryDeleted { .. }
+            | Event::RepositoryRestored { .. } => {}
+            Event::TagUpdated { tag: cx, digest } => {
+                let repo = store.repository(&cx.repository);
+                self.notify(
+                    &repo,
+                    &cx.repository,
+                    WebhookPayload {
+                        event: WebhookEvent::TagCreate,
+                        repository: cx.repository.to_string(),
+                        tag: Some(cx.name.to_string()),
+                        path: None,
+                        digest: Some(digest.clone()),
+                    },
+                )
+                .await;
+                // NOTE: Tags published along with their tree are complete once created.
+                self.notify_complete(&repo, &cx, digest).await;
+            }
+            Event::TagDeleted {
+                tag: cx, digest, ..
+            } => {
+                let repo = store.repository(&cx.repository);
+                self.notify(
+                    &repo,
+                    &cx.repository,
+                    WebhookPayload {
+                        event: WebhookEvent::TagDelete,
+                        repository: cx.repository.to_string(),
+                        tag: Some(cx.name.to_string()),
+                        path: None,
+                        digest: Some(digest),
+                    },
+                )
+                .await;
+            }
+            Event::TagRestored { tag: cx, .. } => {
+                let repo = store.repository(&cx.repository);
+                let digest = match repo.tag(&cx.name).get_meta().await {
+                    Ok(meta) => Some(meta.hash),
+                    Err(e) => {
+                        debug!(target: "app::webhooks", "failed to get `{cx}` restored: {:?}", e);
+                        None
+                    }
+                };
+                self.notify(
+                    &repo,
+                    &cx.repository,
+                    WebhookPayload {
+                        event: WebhookEvent::TagUpdate,
+                        repository: cx.repository.to_string(),
+                        tag: Some(cx.name.to_string()),
+                        path: None,
+                        digest,
+                    },
+                )
+                .await;
+            }
+            Event::TreeEntryUploaded { node: cx, .. } => {
+                let repo = store.repository(&cx.tag.repository);
+                // NOTE: Nodes uploaded to pending tags are notified of once the tag is published.
+                if let Ok(meta) = repo.tag(&cx.tag.name).get_meta().await {
+                    self.notify_complete(&repo, &cx.tag, meta.hash).await;
+                }
+            }
+            Event::EntityDeleted {
+                node: cx,
+                digest,
+                cause: DeleteCause::IntegrityFailure,
+                ..
+            } => {
+                let repo = store.repository(&cx.tag.repository);
+                self.notify(
+                    &repo,
+                    &cx.tag.repository,
+                    WebhookPayload {
+                        event: WebhookEvent::IntegrityFailure,
+                        repository: cx.tag.repository.to_string(),
+                        tag: Some(cx.tag.name.to_string()),
+                        path: Some(cx.path.to_string()),
+                        digest: Some(digest),
+                    },
+                )
+                .await;
+            }
+            Event::EntityDeleted { .. } | Event::EntityRepaired { .. } => {}
+        }
+    }
+
+    /// Notifies webhooks of `repo` subscribed to [WebhookEvent::TreeComplete], if the tree of
+    /// tag `cx` with digest `digest` is complete.
+    ///
+    /// Completeness is only checked if there are webhooks to notify, since it requires
+    /// traversal of the whole tree.
+    async fn notify_complete(
+        self: &Arc<Self>,
+        repo: &Repository<'_, impl AsRef<Utf8Path>>,
+        cx: &TagContext,
+        digest: ContentDigest,
+    ) {
+        let hooks = self
+            .subscribed(repo, &cx.repository, WebhookEvent::TreeComplete)
+            .await;
+        if hooks.is_empty() {
+            return;
+        }
+        match repo.tag(&cx.name).is_complete().await {
+            Ok(true) => self.send(
+                &cx.repository,
+                hooks,
+                WebhookPayload {
+                    event: WebhookEvent::TreeComplete,
+                    repository: cx.repository.to_string(),
+                    tag: Some(cx.name.to_string()),
+                    path: None,
+                    digest: Some(digest),
+                },
+            ),
+            Ok(false) => {}
+            Err(e) => {
+                debug!(target: "app::webhooks", "failed to check completeness of `{cx}`: {:?}", e)
+            }
+        }
+    }
+
+    /// Returns recent deliveries for the repository, most recent first.
+    pub fn deliveries(&self, cx: &RepositoryContext) -> Vec<WebhookDelivery> {
+        self.deliveries
+            .lock()
+            .map(|deliveries| {
+                deliveries
+                    .get(cx)
+                    .map(|deliveries| deliveries.iter().rev().cloned().collect())
+                    .unwrap_or_default()
+            })
+            .unwrap_or_default()
+    }
+
+    fn update(&self, cx: &RepositoryContext, id: &str, f: impl FnOnce(&mut WebhookDelivery)) {
+        if let Ok(mut deliveries) = self.deliveries.lock() {
+            if let Some(delivery) = deliveries
+                .get_mut(cx)
+                .and_then(|deliveries| deliveries.iter_mut().find(|d| d.id == id))
+            {
+                f(delivery)
+            }
+        }
+    }
+
+    fn deliver(self: Arc<Self>, cx: RepositoryContext, webhook: Webhook, payload: WebhookPayload) {
+        let id = uuid::Uuid::new_v4().to_string();
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                debug!(target: "app::webhooks", "failed to encode payload: {:?}", e);
+                return;
+            }
+        };
+        let key = hmac::Key::new(hmac::HMAC_SHA256, webhook.secret.as_bytes());
+        let signature = hex(hmac::sign(&key, &body));
+        let event = serde_json::to_value(payload.event)
+            .ok()
+            .and_then(|v| v.as_str().map(String::from))
+            .unwrap_or_default();
+
+        if let Ok(mut deliveries) = self.deliveries.lock() {
+            let deliveries = deliveries.entry(cx.clone()).or_default();
+            if deliveries.len() >= MAX_DELIVERIES {
+                _ = deliveries.pop_front();
+            }
+            deliveries.push_back(WebhookDelivery {
+                id: id.clone(),
+                url: webhook.url.clone(),
+                payload,
+                attempts: vec![],
+                delivered: false,
+            });
+        }
+
+        let req = Arc::new(Request {
+            url: webhook.url,
+            id,
+            event,
+            signature,
+            body,
+        });
+        _ = spawn(async move {
+            let mut backoff = INITIAL_BACKOFF;
+            for attempt in 1..=MAX_ATTEMPTS {
+                trace!(target: "app::webhooks", "delivery `{}` attempt {attempt} to `{}`", req.id, req.url);
+                // NOTE: Requests are blocking, but retries wait without holding a thread.
+                let (status, error) = spawn_blocking({
+                    let req = Arc::clone(&req);
+                    move || req.send()
+                })
+                .await;
+                let delivered = matches!(status, Some(200..=299));
+                self.update(&cx, &req.id, |delivery| {
+                    delivery.attempts.push(WebhookAttempt {
+                        time: DateTime::<Utc>::from(self.clock.now())
+                            .to_rfc3339_opts(SecondsFormat::Secs, true),
+                        status,
+                        error,
+                    });
+                    delivery.delivered = delivered;
+                });
+                if delivered {
+                    return;
+                }
+                if attempt < MAX_ATTEMPTS {
+                    sleep(backoff).await;
+                    backoff *= 2;
+                }
+            }
+            debug!(target: "app::webhooks", "delivery `{}` to `{}` failed", req.id, req.url);
+        });
+    }
+
+    /// Notifies webhooks of `repo` subscribed to the event of `payload` in background.
+    async fn notify(
+        self: &Arc<Self>,
+        repo: &Repository<'_, impl AsRef<Utf8Path>>,
+        cx: &RepositoryContext,
+        payload: WebhookPayload,
+    ) {
+        let webhooks = self.subscribed(repo, cx, payload.event).await;
+        self.send(cx, webhooks, payload)
+    }
+
+    /// Sends `payload` to each of `webhooks` in background.
+    fn send(
+        self: &Arc<Self>,
+        cx: &RepositoryContext,
+        webhooks: Vec<Webhook>,
+        payload: WebhookPayload,
+    ) {
+        for webhook in webhooks {
+            Arc::clone(self).deliver(cx.clone(), webhook, payload.clone());
+        }
+    }
+
+    /// Returns webhooks of `repo` subscribed to `event`.
+    async fn subscribed(
+        &self,
+        repo: &Repository<'_, impl AsRef<Utf8Path>>,
+        cx: &RepositoryContext,
+        event: WebhookEvent,
+    ) -> Vec<Webhook> {
+        match repo.get_json().await {
+            Ok(conf) => conf
+                .webhooks
+                .into_iter()
+                .filter(|webhook| webhook.is_subscribed(event))
+                .collect(),
+            Err(e) => {
+                debug!(target: "app::webhooks", "failed to get repository `{cx}` config: {:?}", e);
+                vec![]
+            }
+        }
+    }
+}
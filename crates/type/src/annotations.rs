@@ -0,0 +1,302 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::ops::Deref;
+use std::str::FromStr;
+
+use anyhow::{bail, ensure};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Annotations of an entity, i.e. string values keyed by names like `org.example.source`
+///
+/// Keys consist of lowercase ASCII letters, digits, `.`, `-`, `_` and `/` and start with a
+/// letter or digit. Sizes of keys, values and the number of annotations are bounded. Keys with
+/// the [Annotations::RESERVED_PREFIX] are reserved for annotations set by Drawbridge itself.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[repr(transparent)]
+#[serde(transparent)]
+pub struct Annotations(BTreeMap<String, String>);
+
+impl Annotations {
+    /// Maximum number of annotations of an entity
+    pub const MAX_ENTRIES: usize = 64;
+
+    /// Maximum length of a key in bytes
+    pub const MAX_KEY_LENGTH: usize = 128;
+
+    /// Maximum length of a value in bytes
+    pub const MAX_VALUE_LENGTH: usize = 4096;
+
+    /// Prefix of keys reserved for annotations set by Drawbridge
+    pub const RESERVED_PREFIX: &'static str = "drawbridge.";
+
+    #[inline]
+    fn validate_key(key: &str) -> anyhow::Result<()> {
+        if key.is_empty() {
+            bail!("empty annotation key")
+        }
+        if key.len() > Self::MAX_KEY_LENGTH {
+            bail!(
+                "annotation key `{key}` exceeds {} bytes",
+                Self::MAX_KEY_LENGTH
+            )
+        }
+        if !key.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+            || key
+                .find(|c| !matches!(c, '0'..='9' | 'a'..='z' | '.' | '-' | '_' | '/'))
+                .is_some()
+        {
+            bail!("invalid characters in annotation key `{key}`")
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn validate_value(key: &str, value: &str) -> anyhow::Result<()> {
+        ensure!(
+            value.len() <= Self::MAX_VALUE_LENGTH,
+            "value of annotation `{key}` exceeds {} bytes",
+            Self::MAX_VALUE_LENGTH
+        );
+        Ok(())
+    }
+
+    /// Returns whether the annotations are empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns whether `key` is reserved for annotations set by Drawbridge.
+    pub fn is_reserved(key: &str) -> bool {
+        key.starts_with(Self::RESERVED_PREFIX)
+    }
+
+    /// Returns an error if any of the annotations uses a reserved key.
+    pub fn assert_unreserved(&self) -> anyhow::Result<()> {
+        match self.0.keys().find(|key| Self::is_reserved(key)) {
+            Some(key) => bail!("annotation key `{key}` is reserved"),
+            None => Ok(()),
+        }
+    }
+
+    /// Inserts annotation `key` with `value` and returns the previous value, if any.
+    pub fn insert(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> anyhow::Result<Option<String>> {
+        let key = key.into();
+        let value = value.into();
+        Self::validate_key(&key)?;
+        Self::validate_value(&key, &value)?;
+        ensure!(
+            self.0.len() < Self::MAX_ENTRIES || self.0.contains_key(&key),
+            "more than {} annotations",
+            Self::MAX_ENTRIES
+        );
+        Ok(self.0.insert(key, value))
+    }
+
+    /// Removes annotation `key` and returns its value, if any.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.0.remove(key)
+    }
+
+    /// Returns whether the annotations match all of `filters`.
+    pub fn matches<'a>(&self, filters: impl IntoIterator<Item = &'a AnnotationFilter>) -> bool {
+        filters.into_iter().all(
+            |AnnotationFilter { key, value }| match (self.0.get(key), value) {
+                (Some(_), None) => true,
+                (Some(actual), Some(expected)) => actual == expected,
+                (None, _) => false,
+            },
+        )
+    }
+}
+
+impl Deref for Annotations {
+    type Target = BTreeMap<String, String>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl TryFrom<BTreeMap<String, String>> for Annotations {
+    type Error = anyhow::Error;
+
+    fn try_from(annotations: BTreeMap<String, String>) -> Result<Self, Self::Error> {
+        ensure!(
+            annotations.len() <= Self::MAX_ENTRIES,
+            "more than {} annotations",
+            Self::MAX_ENTRIES
+        );
+        for (key, value) in &annotations {
+            Self::validate_key(key)?;
+            Self::validate_value(key, value)?;
+        }
+        Ok(Self(annotations))
+    }
+}
+
+impl<'de> Deserialize<'de> for Annotations {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let annotations = BTreeMap::<String, String>::deserialize(deserializer)?;
+        annotations.try_into().map_err(D::Error::custom)
+    }
+}
+
+/// A filter matching entities annotated with a key, optionally with a specific value
+///
+/// Filters are represented as `KEY` or `KEY=VALUE`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AnnotationFilter {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+impl FromStr for AnnotationFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = match s.split_once('=') {
+            Some((key, value)) => (key, Some(value.into())),
+            None => (s, None),
+        };
+        Annotations::validate_key(key)?;
+        Ok(Self {
+            key: key.into(),
+            value,
+        })
+    }
+}
+
+impl Display for AnnotationFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.value {
+            Some(ref value) => write!(f, "{}={value}", self.key),
+            None => f.write_str(&self.key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn validation() {
+        let mut annotations = Annotations::default();
+        for key in [
+            "",
+            "Org.example",
+            ".example",
+            "org example",
+            "org.exämple",
+            &"a".repeat(Annotations::MAX_KEY_LENGTH + 1),
+        ] {
+            assert!(
+                annotations.insert(key, "value").is_err(),
+                "key '{}' should be rejected",
+                key
+            );
+        }
+        assert!(annotations
+            .insert("key", "a".repeat(Annotations::MAX_VALUE_LENGTH + 1))
+            .is_err());
+        assert_eq!(
+            annotations
+                .insert("org.example/source-url_2", "value")
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            annotations
+                .insert("org.example/source-url_2", "other")
+                .unwrap(),
+            Some("value".into())
+        );
+        for i in 1..Annotations::MAX_ENTRIES {
+            _ = annotations.insert(format!("key{i}"), "value").unwrap();
+        }
+        assert!(annotations.insert("key", "value").is_err());
+        assert!(annotations.insert("key1", "other").is_ok());
+
+        assert!(annotations.assert_unreserved().is_ok());
+        assert_eq!(annotations.remove("key1"), Some("other".into()));
+        _ = annotations.insert("drawbridge.scanned", "true").unwrap();
+        assert!(annotations.assert_unreserved().is_err());
+    }
+
+    #[test]
+    fn serialization() {
+        let annotations: Annotations =
+            serde_json::from_value(json!({ "org.example.b": "2", "org.example.a": "1" })).unwrap();
+        assert_eq!(
+            serde_json::to_string(&annotations).unwrap(),
+            r#"{"org.example.a":"1","org.example.b":"2"}"#
+        );
+        assert!(serde_json::from_value::<Annotations>(json!({ "Key": "value" })).is_err());
+        assert!(serde_json::from_value::<Annotations>(json!({ "key": 1 })).is_err());
+    }
+
+    #[test]
+    fn filters() {
+        let annotations: Annotations =
+            serde_json::from_value(json!({ "org.example.a": "1", "org.example.b": "" })).unwrap();
+        for (filters, expected) in [
+            (vec![], true),
+            (vec!["org.example.a"], true),
+            (vec!["org.example.a=1"], true),
+            (vec!["org.example.a=2"], false),
+            (vec!["org.example.b="], true),
+            (vec!["org.example.a", "org.example.c"], false),
+        ] {
+            let filters = filters
+                .into_iter()
+                .map(|s| s.parse::<AnnotationFilter>().unwrap())
+                .collect::<Vec<_>>();
+            assert_eq!(annotations.matches(&filters), expected, "{filters:?}");
+        }
+        assert!("Key=value".parse::<AnnotationFilter>().is_err());
+        assert_eq!(
+            "org.example.a=1=2"
+                .parse::<AnnotationFilter>()
+                .unwrap()
+                .to_string(),
+            "org.example.a=1=2"
+        );
+    }
+
+    #[test]
+    fn tree_entry() {
+        let value = json!({
+            "digest": {"sha-256": "LCa0a2j/xo/5m0U8HTBBNBNCLXBkg7+g+YpeiGJm564="},
+            "length": 3,
+            "type": "text/plain",
+            "annotations": { "org.example.a": "1" },
+            "custom": true,
+        });
+        let entry: crate::TreeEntry = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(entry.annotations.get("org.example.a").unwrap(), "1");
+        assert!(!entry.custom.contains_key("annotations"));
+        assert_eq!(serde_json::to_value(&entry).unwrap(), value);
+
+        let entry = crate::TreeEntry {
+            annotations: Default::default(),
+            ..entry
+        };
+        assert!(serde_json::to_value(&entry)
+            .unwrap()
+            .get("annotations")
+            .is_none());
+    }
+}
@@ -80,6 +80,16 @@ impl<'de> Deserialize<'de> for Algorithm {
 }
 
 impl Algorithm {
+    /// Returns the size of digests produced by the algorithm in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            Self::Sha224 => 28,
+            Self::Sha256 => 32,
+            Self::Sha384 => 48,
+            Self::Sha512 => 64,
+        }
+    }
+
     pub(crate) fn hasher(self) -> Box<dyn DynDigest> {
         match self {
             Self::Sha224 => Box::new(Sha224::new()),
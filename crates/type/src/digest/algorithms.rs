@@ -1,7 +1,7 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::{Algorithm, ContentDigest, Reader, Writer};
+use super::{Algorithm, ContentDigest, Policy, Reader, Writer};
 
 use std::collections::BTreeSet;
 use std::ops::{Deref, DerefMut};
@@ -10,7 +10,7 @@ use futures::io::{self, copy, sink, AsyncRead};
 use serde::{Deserialize, Serialize};
 
 /// A set of hashing algorithms
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Algorithms(BTreeSet<Algorithm>);
 
 impl Default for Algorithms {
@@ -45,6 +45,31 @@ impl DerefMut for Algorithms {
 }
 
 impl Algorithms {
+    /// Returns the algorithms contained in `self` or `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        Self(&self.0 | &other.0)
+    }
+
+    /// Returns the algorithms contained in both `self` and `other`.
+    pub fn intersection(&self, other: &Self) -> Self {
+        Self(&self.0 & &other.0)
+    }
+
+    /// Returns the algorithms contained in `self`, but not in `other`.
+    pub fn difference(&self, other: &Self) -> Self {
+        Self(&self.0 - &other.0)
+    }
+
+    /// Returns the algorithms not considered weak by `policy`.
+    pub fn strip_weak(&self, policy: &Policy) -> Self {
+        Self(
+            self.iter()
+                .copied()
+                .filter(|algorithm| !policy.is_weak(*algorithm))
+                .collect(),
+        )
+    }
+
     /// Creates a reader instance
     pub fn reader<T>(&self, reader: T) -> Reader<T> {
         Reader::new(reader, self.iter().cloned())
@@ -74,6 +99,22 @@ impl Algorithms {
 mod tests {
     use super::*;
 
+    #[test]
+    fn algebra() {
+        let lhs = Algorithms::from(BTreeSet::from([Algorithm::Sha224, Algorithm::Sha256]));
+        let rhs = Algorithms::from(BTreeSet::from([Algorithm::Sha256, Algorithm::Sha512]));
+        assert_eq!(
+            *lhs.union(&rhs),
+            BTreeSet::from([Algorithm::Sha224, Algorithm::Sha256, Algorithm::Sha512])
+        );
+        assert_eq!(*lhs.intersection(&rhs), BTreeSet::from([Algorithm::Sha256]));
+        assert_eq!(*lhs.difference(&rhs), BTreeSet::from([Algorithm::Sha224]));
+        assert_eq!(
+            *Algorithms::default().strip_weak(&Policy::default()),
+            BTreeSet::from([Algorithm::Sha256, Algorithm::Sha384, Algorithm::Sha512])
+        );
+    }
+
     #[async_std::test]
     async fn digest() {
         let algorithms = Algorithms::default();
@@ -1,10 +1,10 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::{Algorithm, Error, Reader, Verifier, Writer};
+use super::{Algorithm, Algorithms, Error, Policy, Reader, Verifier, Writer};
 
 use std::collections::btree_map::IntoIter;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
 
@@ -38,6 +38,64 @@ where
     pub fn verifier<T>(self, reader: T) -> Verifier<T, H> {
         Verifier::new(self.reader(reader), self)
     }
+
+    /// Returns the algorithms the digest contains values of.
+    pub fn algorithms(&self) -> Algorithms {
+        Algorithms::from(self.keys().copied().collect::<BTreeSet<_>>())
+    }
+
+    /// Returns the values of `self` along with those of `other` for algorithms missing from
+    /// `self`, i.e. values of `self` take precedence.
+    pub fn union(&self, other: &Self) -> Self
+    where
+        H: Clone,
+    {
+        let mut union = other.0.clone();
+        union.extend(self.0.clone());
+        Self(union)
+    }
+
+    /// Returns the values of `self` for algorithms, which `other` contains the same value of.
+    pub fn intersection<U>(&self, other: &ContentDigest<U>) -> Self
+    where
+        H: Clone,
+        U: AsRef<[u8]> + From<Vec<u8>>,
+    {
+        self.filter(|algorithm, value| {
+            other
+                .get(algorithm)
+                .is_some_and(|other| other.as_ref() == value.as_ref())
+        })
+    }
+
+    /// Returns the values of `self` for algorithms missing from `other`.
+    pub fn difference<U>(&self, other: &ContentDigest<U>) -> Self
+    where
+        H: Clone,
+        U: AsRef<[u8]> + From<Vec<u8>>,
+    {
+        self.filter(|algorithm, _| !other.contains_key(algorithm))
+    }
+
+    /// Returns the values of `self` for algorithms not considered weak by `policy`.
+    pub fn strip_weak(&self, policy: &Policy) -> Self
+    where
+        H: Clone,
+    {
+        self.filter(|algorithm, _| !policy.is_weak(*algorithm))
+    }
+
+    fn filter(&self, mut f: impl FnMut(&Algorithm, &Bytes<H>) -> bool) -> Self
+    where
+        H: Clone,
+    {
+        Self(
+            self.iter()
+                .filter(|(algorithm, value)| f(algorithm, value))
+                .map(|(algorithm, value)| (*algorithm, value.clone()))
+                .collect(),
+        )
+    }
 }
 
 impl<H> From<BTreeMap<Algorithm, Bytes<H>>> for ContentDigest<H>
@@ -151,7 +209,7 @@ static CONTENT_DIGEST: HeaderName = HeaderName::from_static("content-digest");
 #[cfg(feature = "headers")]
 impl<H> Header for ContentDigest<H>
 where
-    H: Default + AsRef<[u8]> + From<Vec<u8>>,
+    H: Clone + Default + AsRef<[u8]> + From<Vec<u8>>,
 {
     fn name() -> &'static HeaderName {
         &CONTENT_DIGEST
@@ -171,9 +229,7 @@ where
                 .parse()
                 .map_err(|_| HeadErr::invalid())?;
 
-            for (algo, hash) in digests {
-                let _ = all.insert(algo, hash);
-            }
+            all = digests.union(&all);
         }
 
         if all.is_empty() {
@@ -198,4 +254,32 @@ mod tests {
         const STR: &str = "sha-224=:CAj2TmDViXn8tnbJbsk4Jw3qQkRa7vzTpOb42w==:,sha-256=:LCa0a2j/xo/5m0U8HTBBNBNCLXBkg7+g+YpeiGJm564=:,sha-384=:mMEf/f3VQGdrGhN8saIrKnA1DJpEFx1rEYDGvly7LuP3nVMsih3Z7y6OCOdSo7q7:,sha-512=:9/u6bgY2+JDlb7vzKD5STG+jIErimDgtYkdB0NxmODJuKCxBvl5CVNiCB3LFUYosWowMf37aGVlKfrU5RT4e1w==:";
         assert_eq!(STR.parse::<ContentDigest>().unwrap().to_string(), STR);
     }
+
+    #[test]
+    fn algebra() {
+        let lhs: ContentDigest = "sha-224=:CAj2TmDViXn8tnbJbsk4Jw3qQkRa7vzTpOb42w==:,sha-256=:LCa0a2j/xo/5m0U8HTBBNBNCLXBkg7+g+YpeiGJm564=:,sha-384=:mMEf/f3VQGdrGhN8saIrKnA1DJpEFx1rEYDGvly7LuP3nVMsih3Z7y6OCOdSo7q7:".parse().unwrap();
+        let rhs: ContentDigest = "sha-256=:LCa0a2j/xo/5m0U8HTBBNBNCLXBkg7+g+YpeiGJm564=:,sha-384=:OLBgp1GsljhM2TJ+sbHjaiH9txEUvgdDTAzHv2P24donTt6/529l+9Ua0vFImLlb:,sha-512=:9/u6bgY2+JDlb7vzKD5STG+jIErimDgtYkdB0NxmODJuKCxBvl5CVNiCB3LFUYosWowMf37aGVlKfrU5RT4e1w==:".parse().unwrap();
+
+        let union = lhs.union(&rhs);
+        assert_eq!(union.len(), 4);
+        assert_eq!(union[&Algorithm::Sha384], lhs[&Algorithm::Sha384]);
+        assert_eq!(union[&Algorithm::Sha512], rhs[&Algorithm::Sha512]);
+
+        assert_eq!(
+            lhs.intersection(&rhs).to_string(),
+            "sha-256=:LCa0a2j/xo/5m0U8HTBBNBNCLXBkg7+g+YpeiGJm564=:"
+        );
+        assert_eq!(
+            lhs.difference(&rhs).to_string(),
+            "sha-224=:CAj2TmDViXn8tnbJbsk4Jw3qQkRa7vzTpOb42w==:"
+        );
+        assert_eq!(
+            lhs.strip_weak(&Policy::default()).algorithms(),
+            [Algorithm::Sha256, Algorithm::Sha384]
+                .into_iter()
+                .collect::<BTreeSet<_>>()
+                .into()
+        );
+        assert!(lhs.strip_weak(&Policy { min_size: 64 }).is_empty());
+    }
 }
@@ -4,17 +4,34 @@
 mod algorithm;
 mod algorithms;
 mod digests;
+mod policy;
 mod reader;
+mod rules;
 mod verifier;
 mod writer;
 
 pub use algorithm::Algorithm;
 pub use algorithms::Algorithms;
 pub use digests::ContentDigest;
+pub use policy::Policy;
 pub use reader::Reader;
+pub use rules::AlgorithmRules;
 pub use verifier::Verifier;
 pub use writer::Writer;
 
+use std::fmt::Write as _;
+
+/// Returns the lowercase hexadecimal encoding of `bytes`, e.g. of a digest value.
+pub fn hex(bytes: impl AsRef<[u8]>) -> String {
+    let bytes = bytes.as_ref();
+    bytes
+        .iter()
+        .fold(String::with_capacity(bytes.len() * 2), |mut hex, b| {
+            _ = write!(hex, "{b:02x}");
+            hex
+        })
+}
+
 /// Parsing error
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Error {
@@ -42,3 +59,14 @@ impl From<base64::DecodeError> for Error {
         Self::Decode(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_encoding() {
+        assert_eq!(hex([]), "");
+        assert_eq!(hex([0x00, 0x0f, 0xa5, 0xff]), "000fa5ff");
+    }
+}
@@ -0,0 +1,27 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::Algorithm;
+
+/// A policy determining which hashing algorithms are strong enough to be relied on
+///
+/// Algorithms producing digests shorter than [Policy::min_size] are considered weak, e.g.
+/// SHA-224 by the default policy, which requires digests of at least 256 bits.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Policy {
+    /// Minimum size of digests in bytes
+    pub min_size: usize,
+}
+
+impl Default for Policy {
+    fn default() -> Self {
+        Self { min_size: 32 }
+    }
+}
+
+impl Policy {
+    /// Returns whether `algorithm` is considered weak.
+    pub fn is_weak(&self, algorithm: Algorithm) -> bool {
+        algorithm.size() < self.min_size
+    }
+}
@@ -0,0 +1,129 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Algorithm, ContentDigest};
+
+use std::collections::BTreeSet;
+
+use anyhow::ensure;
+use serde::{Deserialize, Serialize};
+
+/// Rules on the hashing algorithms content digests of uploads to a namespace consist of
+///
+/// For example, rules with `sha-512` [required](Self::required) and `sha-224`
+/// [forbidden](Self::forbidden) accept digests including a SHA-512 value and no SHA-224 value.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlgorithmRules {
+    /// Algorithms digests may consist of, any algorithm if empty
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub allowed: BTreeSet<Algorithm>,
+
+    /// Algorithms digests must include
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub required: BTreeSet<Algorithm>,
+
+    /// Algorithms digests must not include
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub forbidden: BTreeSet<Algorithm>,
+}
+
+impl AlgorithmRules {
+    /// Returns rules, which accept digests accepted by both `self` and `other`.
+    pub fn merge(&self, other: &Self) -> Self {
+        let allowed = match (self.allowed.is_empty(), other.allowed.is_empty()) {
+            (true, _) => other.allowed.clone(),
+            (_, true) => self.allowed.clone(),
+            _ => &self.allowed & &other.allowed,
+        };
+        Self {
+            allowed,
+            required: &self.required | &other.required,
+            forbidden: &self.forbidden | &other.forbidden,
+        }
+    }
+
+    /// Validates that the rules accept `digest`.
+    pub fn validate(&self, digest: &ContentDigest) -> anyhow::Result<()> {
+        for algorithm in digest.keys() {
+            ensure!(
+                !self.forbidden.contains(algorithm),
+                "algorithm `{algorithm}` is forbidden"
+            );
+            ensure!(
+                self.allowed.is_empty() || self.allowed.contains(algorithm),
+                "algorithm `{algorithm}` is not allowed"
+            );
+        }
+        for algorithm in &self.required {
+            ensure!(
+                digest.contains_key(algorithm),
+                "algorithm `{algorithm}` is required"
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn validate() {
+        let sha256: ContentDigest = "sha-256=:LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=:"
+            .parse()
+            .unwrap();
+        let sha224: ContentDigest = "sha-224=:6gmunMZ2jFD87pA+0FRVblv8g0eQfxJZiqJBkw==:"
+            .parse()
+            .unwrap();
+
+        let rules: AlgorithmRules = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(rules, AlgorithmRules::default());
+        assert!(rules.validate(&sha256).is_ok());
+        assert!(rules.validate(&sha224).is_ok());
+
+        let rules: AlgorithmRules = serde_json::from_value(json!({
+            "required": ["sha-256"],
+            "forbidden": ["sha-224"],
+        }))
+        .unwrap();
+        assert!(rules.validate(&sha256).is_ok());
+        assert!(rules.validate(&sha224).is_err());
+        assert!(rules.validate(&ContentDigest::default()).is_err());
+
+        let rules: AlgorithmRules = serde_json::from_value(json!({
+            "allowed": ["sha-384", "sha-512"],
+        }))
+        .unwrap();
+        assert!(rules.validate(&sha256).is_err());
+
+        assert!(serde_json::from_value::<AlgorithmRules>(json!({ "weak": [] })).is_err());
+    }
+
+    #[test]
+    fn merge() {
+        let owner = AlgorithmRules {
+            allowed: BTreeSet::from([Algorithm::Sha256, Algorithm::Sha512]),
+            required: BTreeSet::from([Algorithm::Sha256]),
+            forbidden: BTreeSet::from([Algorithm::Sha224]),
+        };
+        let repo = AlgorithmRules {
+            allowed: BTreeSet::from([Algorithm::Sha384, Algorithm::Sha512]),
+            required: BTreeSet::from([Algorithm::Sha512]),
+            ..Default::default()
+        };
+        assert_eq!(
+            owner.merge(&repo),
+            AlgorithmRules {
+                allowed: BTreeSet::from([Algorithm::Sha512]),
+                required: BTreeSet::from([Algorithm::Sha256, Algorithm::Sha512]),
+                forbidden: BTreeSet::from([Algorithm::Sha224]),
+            }
+        );
+        assert_eq!(owner.merge(&AlgorithmRules::default()), owner);
+        assert_eq!(AlgorithmRules::default().merge(&owner), owner);
+    }
+}
@@ -0,0 +1,93 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use drawbridge_byte::Bytes;
+use serde::{Deserialize, Serialize};
+
+/// An algorithm contents are encrypted with by clients
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum EncryptionAlgorithm {
+    /// AES-256 in Galois/Counter Mode with a 96-bit nonce, the 128-bit tag is appended to
+    /// the ciphertext
+    #[serde(rename = "A256GCM")]
+    Aes256Gcm,
+}
+
+impl EncryptionAlgorithm {
+    /// Returns the length of keys in bytes.
+    pub fn key_len(&self) -> usize {
+        match self {
+            Self::Aes256Gcm => 32,
+        }
+    }
+
+    /// Returns the length of nonces in bytes.
+    pub fn nonce_len(&self) -> usize {
+        match self {
+            Self::Aes256Gcm => 12,
+        }
+    }
+}
+
+/// Header of an entry, whose contents are encrypted by the client
+///
+/// Contents are encrypted before they are uploaded and decrypted after they are downloaded, so
+/// that the server never sees the plaintext. The length and digests of an encrypted entry are
+/// those of the ciphertext, which is what the server verifies.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Encryption {
+    /// Algorithm the contents are encrypted with
+    #[serde(rename = "alg")]
+    pub algorithm: EncryptionAlgorithm,
+
+    /// Identifier of the key the contents are encrypted with, which clients resolve the key by
+    #[serde(rename = "kid")]
+    pub key: String,
+
+    /// Nonce the contents are encrypted with
+    pub nonce: Bytes<Vec<u8>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn serialization() {
+        let encryption: Encryption = serde_json::from_value(json!({
+            "alg": "A256GCM",
+            "kid": "prod-2022",
+            "nonce": "AAECAwQFBgcICQoL",
+        }))
+        .unwrap();
+        assert_eq!(encryption.algorithm, EncryptionAlgorithm::Aes256Gcm);
+        assert_eq!(encryption.key, "prod-2022");
+        assert_eq!(
+            &encryption.nonce[..],
+            &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]
+        );
+        assert_eq!(
+            serde_json::to_value(&encryption).unwrap(),
+            json!({
+                "alg": "A256GCM",
+                "kid": "prod-2022",
+                "nonce": "AAECAwQFBgcICQoL",
+            })
+        );
+
+        assert!(serde_json::from_value::<Encryption>(json!({
+            "alg": "A128CBC",
+            "kid": "prod-2022",
+            "nonce": "AAECAwQFBgcICQoL",
+        }))
+        .is_err());
+        assert!(serde_json::from_value::<Encryption>(json!({
+            "alg": "A256GCM",
+            "nonce": "AAECAwQFBgcICQoL",
+        }))
+        .is_err());
+    }
+}
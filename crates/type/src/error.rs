@@ -0,0 +1,357 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::digest::ContentDigest;
+use super::tag::Severity;
+
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A stable machine-readable code identifying the kind of an error
+///
+/// Problem details returned by Drawbridge carry the code as their `code` member, which is a
+/// kebab-case string, e.g. `not-found`. Codes may be added in future versions, which are
+/// parsed as [ErrorCode::Unknown] by earlier ones.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum ErrorCode {
+    NotFound,
+    AlreadyExists,
+    DigestConflict,
+    LengthMismatch,
+    DigestMismatch,
+    Truncated,
+    Incomplete,
+    StorageFailure,
+    QuotaExceeded,
+    RateLimited,
+    Vulnerable,
+    /// A code unknown to this version
+    Unknown(String),
+}
+
+impl ErrorCode {
+    /// Returns the code as a string, e.g. `not-found`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::NotFound => "not-found",
+            Self::AlreadyExists => "already-exists",
+            Self::DigestConflict => "digest-conflict",
+            Self::LengthMismatch => "length-mismatch",
+            Self::DigestMismatch => "digest-mismatch",
+            Self::Truncated => "truncated",
+            Self::Incomplete => "incomplete",
+            Self::StorageFailure => "storage-failure",
+            Self::QuotaExceeded => "quota-exceeded",
+            Self::RateLimited => "rate-limited",
+            Self::Vulnerable => "vulnerable",
+            Self::Unknown(code) => code,
+        }
+    }
+}
+
+impl AsRef<str> for ErrorCode {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ErrorCode {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "not-found" => Self::NotFound,
+            "already-exists" => Self::AlreadyExists,
+            "digest-conflict" => Self::DigestConflict,
+            "length-mismatch" => Self::LengthMismatch,
+            "digest-mismatch" => Self::DigestMismatch,
+            "truncated" => Self::Truncated,
+            "incomplete" => Self::Incomplete,
+            "storage-failure" => Self::StorageFailure,
+            "quota-exceeded" => Self::QuotaExceeded,
+            "rate-limited" => Self::RateLimited,
+            "vulnerable" => Self::Vulnerable,
+            _ => Self::Unknown(s.into()),
+        })
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for ErrorCode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        Ok(code.parse().unwrap_or(Self::Unknown(code)))
+    }
+}
+
+/// An error returned by Drawbridge, identified by a stable machine-readable code.
+///
+/// Errors are serialized as the members of [RFC 9457] problem details specific to them,
+/// i.e. the `code` along with the fields of the variant.
+///
+/// [RFC 9457]: https://www.rfc-editor.org/rfc/rfc9457
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(tag = "code", rename_all = "kebab-case")]
+pub enum Error {
+    /// The entity does not exist
+    NotFound,
+    /// The entity already exists
+    AlreadyExists,
+    /// The entity already exists with a digest different from the requested one
+    DigestConflict {
+        #[serde(serialize_with = "serialize_digest")]
+        #[serde(deserialize_with = "deserialize_digest")]
+        existing: ContentDigest,
+        #[serde(serialize_with = "serialize_digest")]
+        #[serde(deserialize_with = "deserialize_digest")]
+        requested: ContentDigest,
+    },
+    /// The length of the content differs from the declared one
+    LengthMismatch { expected: u64, got: u64 },
+    /// The digest of the content differs from the declared one
+    DigestMismatch,
+    /// The content ended before its declared length was reached
+    Truncated,
+    /// Not all nodes of a tree were uploaded
+    Incomplete,
+    /// The storage backend failed
+    StorageFailure,
+    /// Storing the content would exceed the storage quota of the tenant
+    QuotaExceeded { quota: u64, stored: u64 },
+    /// The client sent more than `limit` requests in the current window, which resets in
+    /// `reset` seconds
+    RateLimited { limit: u64, used: u64, reset: u64 },
+    /// The vulnerability policy of the repository blocks pulls of the tag, because of
+    /// `findings` findings of at most `severity` or because the tag was not scanned
+    Vulnerable {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        severity: Option<Severity>,
+        findings: usize,
+    },
+}
+
+fn serialize_digest<S: Serializer>(digest: &ContentDigest, s: S) -> Result<S::Ok, S::Error> {
+    s.collect_str(digest)
+}
+
+fn deserialize_digest<'de, D: Deserializer<'de>>(d: D) -> Result<ContentDigest, D::Error> {
+    String::deserialize(d)?.parse().map_err(D::Error::custom)
+}
+
+impl Error {
+    /// Returns the stable machine-readable code of the error.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::NotFound => ErrorCode::NotFound,
+            Self::AlreadyExists => ErrorCode::AlreadyExists,
+            Self::DigestConflict { .. } => ErrorCode::DigestConflict,
+            Self::LengthMismatch { .. } => ErrorCode::LengthMismatch,
+            Self::DigestMismatch => ErrorCode::DigestMismatch,
+            Self::Truncated => ErrorCode::Truncated,
+            Self::Incomplete => ErrorCode::Incomplete,
+            Self::StorageFailure => ErrorCode::StorageFailure,
+            Self::QuotaExceeded { .. } => ErrorCode::QuotaExceeded,
+            Self::RateLimited { .. } => ErrorCode::RateLimited,
+            Self::Vulnerable { .. } => ErrorCode::Vulnerable,
+        }
+    }
+
+    /// Returns the HTTP status code the error is reported with.
+    pub fn status(&self) -> u16 {
+        match self {
+            Self::NotFound => 404,
+            Self::AlreadyExists | Self::DigestConflict { .. } | Self::Incomplete => 409,
+            Self::LengthMismatch { .. } | Self::DigestMismatch | Self::Truncated => 400,
+            Self::StorageFailure => 500,
+            Self::QuotaExceeded { .. } => 507,
+            Self::RateLimited { .. } => 429,
+            Self::Vulnerable { .. } => 403,
+        }
+    }
+
+    /// Returns a short, human-readable summary of the error.
+    pub fn title(&self) -> &'static str {
+        match self {
+            Self::NotFound => "Not found",
+            Self::AlreadyExists => "Already exists",
+            Self::DigestConflict { .. } => "Already exists with a different digest",
+            Self::LengthMismatch { .. } => "Content length mismatch",
+            Self::DigestMismatch => "Content digest mismatch",
+            Self::Truncated => "Content truncated",
+            Self::Incomplete => "Tree is incomplete",
+            Self::StorageFailure => "Storage backend failure",
+            Self::QuotaExceeded { .. } => "Storage quota exceeded",
+            Self::RateLimited { .. } => "Rate limit exceeded",
+            Self::Vulnerable { .. } => "Blocked by vulnerability policy",
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DigestConflict {
+                existing,
+                requested,
+            } => write!(
+                f,
+                "already exists with digest `{existing}`, requested `{requested}`"
+            ),
+            Self::LengthMismatch { expected, got } => {
+                write!(
+                    f,
+                    "content length mismatch, expected: {expected}, got {got}"
+                )
+            }
+            Self::QuotaExceeded { quota, stored } => {
+                write!(
+                    f,
+                    "storage quota of {quota} bytes exceeded, {stored} bytes stored"
+                )
+            }
+            Self::RateLimited { limit, reset, .. } => {
+                write!(
+                    f,
+                    "rate limit of {limit} requests exceeded, retry in {reset} seconds"
+                )
+            }
+            Self::Vulnerable {
+                severity: Some(severity),
+                findings,
+            } => write!(
+                f,
+                "blocked by vulnerability policy, {findings} findings of severity up to {severity}"
+            ),
+            Self::Vulnerable { severity: None, .. } => {
+                f.write_str("blocked by vulnerability policy, no vulnerability report attached")
+            }
+            _ => f.write_str(&self.title().to_lowercase()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn problem_members() {
+        let existing: ContentDigest = "sha-256=:LCa0a2j/xo/5m0U8HTBBNBNCLXBkg7+g+YpeiGJm564=:"
+            .parse()
+            .unwrap();
+        let requested: ContentDigest = "sha-256=:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=:"
+            .parse()
+            .unwrap();
+        for (err, members) in [
+            (Error::NotFound, json!({ "code": "not-found" })),
+            (
+                Error::LengthMismatch {
+                    expected: 42,
+                    got: 2,
+                },
+                json!({ "code": "length-mismatch", "expected": 42, "got": 2 }),
+            ),
+            (
+                Error::QuotaExceeded {
+                    quota: 1024,
+                    stored: 1000,
+                },
+                json!({ "code": "quota-exceeded", "quota": 1024, "stored": 1000 }),
+            ),
+            (
+                Error::RateLimited {
+                    limit: 100,
+                    used: 101,
+                    reset: 30,
+                },
+                json!({ "code": "rate-limited", "limit": 100, "used": 101, "reset": 30 }),
+            ),
+            (
+                Error::Vulnerable {
+                    severity: Some(Severity::Critical),
+                    findings: 2,
+                },
+                json!({ "code": "vulnerable", "severity": "critical", "findings": 2 }),
+            ),
+            (
+                Error::DigestConflict {
+                    existing: existing.clone(),
+                    requested: requested.clone(),
+                },
+                json!({
+                    "code": "digest-conflict",
+                    "existing": existing.to_string(),
+                    "requested": requested.to_string(),
+                }),
+            ),
+        ] {
+            assert_eq!(serde_json::to_value(&err).unwrap(), members);
+            assert_eq!(members["code"], err.code().as_str());
+            assert_eq!(serde_json::from_value::<Error>(members).unwrap(), err);
+        }
+
+        let problem = json!({
+            "title": "Content truncated",
+            "status": 400,
+            "detail": "The request body ended before all content was received",
+            "code": "truncated",
+        });
+        assert_eq!(
+            serde_json::from_value::<Error>(problem).unwrap(),
+            Error::Truncated
+        );
+    }
+
+    #[test]
+    fn codes() {
+        for code in [
+            ErrorCode::NotFound,
+            ErrorCode::AlreadyExists,
+            ErrorCode::DigestConflict,
+            ErrorCode::LengthMismatch,
+            ErrorCode::DigestMismatch,
+            ErrorCode::Truncated,
+            ErrorCode::Incomplete,
+            ErrorCode::StorageFailure,
+            ErrorCode::QuotaExceeded,
+            ErrorCode::RateLimited,
+            ErrorCode::Vulnerable,
+        ] {
+            assert_eq!(code.as_str().parse::<ErrorCode>().unwrap(), code);
+            assert!(!matches!(code, ErrorCode::Unknown(..)));
+        }
+        assert_eq!(
+            serde_json::to_value(ErrorCode::QuotaExceeded).unwrap(),
+            json!("quota-exceeded")
+        );
+        assert_eq!(
+            serde_json::from_value::<ErrorCode>(json!("unavailable")).unwrap(),
+            ErrorCode::Unknown("unavailable".into())
+        );
+        assert_eq!(
+            ErrorCode::Unknown("unavailable".into()).to_string(),
+            "unavailable"
+        );
+        assert_eq!(Error::Truncated.code(), ErrorCode::Truncated);
+    }
+}
@@ -0,0 +1,48 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// A GraphQL request as POSTed to a GraphQL endpoint
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct GraphQlRequest {
+    pub query: String,
+
+    #[serde(
+        default,
+        rename = "operationName",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub operation_name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub variables: Map<String, Value>,
+}
+
+/// The response to a [GraphQlRequest]
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct GraphQlResponse {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<GraphQlError>,
+}
+
+impl GraphQlResponse {
+    /// Returns a response without data carrying `error` only.
+    pub fn error(error: impl ToString) -> Self {
+        Self {
+            data: None,
+            errors: vec![GraphQlError {
+                message: error.to_string(),
+            }],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct GraphQlError {
+    pub message: String,
+}
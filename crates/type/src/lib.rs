@@ -35,15 +35,61 @@ pub mod tag;
 pub mod tree;
 pub mod user;
 
+mod annotations;
+mod encryption;
+mod error;
+mod graphql;
+mod license;
+mod manifest;
+mod media_type;
 mod meta;
+mod namespace;
+mod provenance;
+mod publication;
+mod reference;
+mod retention;
+mod search;
+mod signature;
+mod timestamp;
+mod trash;
+mod upload;
+mod usage;
+mod version;
 
+pub use annotations::*;
+pub use encryption::*;
+pub use error::{Error, ErrorCode};
+pub use graphql::*;
+pub use license::*;
+pub use manifest::*;
+pub use media_type::*;
 pub use meta::*;
+pub use namespace::*;
+pub use provenance::*;
+pub use publication::*;
+pub use reference::*;
 pub use repository::{
     Config as RepositoryConfig, Context as RepositoryContext, Name as RepositoryName,
+    PendingChange, ReviewPolicy, ScanMode, Scanning, SigningPolicy, Snapshot as RepositorySnapshot,
+    SnapshotEntity, SnapshotImport, SnapshotTag, VulnerabilityPolicy, Webhook, WebhookAttempt,
+    WebhookDelivery, WebhookEvent, WebhookPayload,
 };
-pub use tag::{Context as TagContext, Entry as TagEntry, Name as TagName};
+pub use retention::*;
+pub use search::*;
+pub use signature::*;
+pub use tag::{
+    Alias as TagAlias, Attestation as TagAttestation, Context as TagContext, Entry as TagEntry,
+    Finding as VulnerabilityFinding, Kind as TagKind, Name as TagName, SbomFormat, Severity,
+    Signature as TagSignature, Signatures as TagSignatures, Stats as TagStats,
+    Subject as TagAttestationSubject, Verification as TagVerification, VulnerabilityReport,
+};
+pub use timestamp::*;
+pub use trash::*;
 pub use tree::{
-    Content as TreeContent, Context as TreeContext, Directory as TreeDirectory, Entry as TreeEntry,
-    Name as TreeName, Path as TreePath, Tree,
+    Content as TreeContent, Context as TreeContext, Diff as TreeDiff, Directory as TreeDirectory,
+    Entry as TreeEntry, Name as TreeName, Path as TreePath, Tree,
 };
+pub use upload::*;
+pub use usage::*;
 pub use user::{Context as UserContext, Name as UserName, Record as UserRecord};
+pub use version::*;
@@ -0,0 +1,266 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{bail, ensure};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+/// A license of an artifact given as an
+/// [SPDX license expression](https://spdx.github.io/spdx-spec/v2.3/SPDX-license-expressions/),
+/// e.g. `MIT OR Apache-2.0` or `GPL-2.0-or-later WITH Classpath-exception-2.0`
+///
+/// Expressions are validated syntactically, license identifiers are not checked against the
+/// SPDX license list, so that newly added licenses and `LicenseRef-` references are accepted.
+/// `NONE` and `NOASSERTION` are only valid as the whole expression.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct License(String);
+
+impl License {
+    /// Maximum length of an expression in bytes
+    pub const MAX_LENGTH: usize = 1024;
+
+    /// Returns the license identifiers the expression refers to, excluding exceptions.
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        let tokens = tokens(&self.0);
+        let exceptions = tokens
+            .windows(2)
+            .filter(|pair| pair[0].eq_ignore_ascii_case("with"))
+            .map(|pair| pair[1])
+            .collect::<Vec<_>>();
+        tokens.into_iter().filter_map(move |token| {
+            if token == "(" || token == ")" || is_operator(token) || exceptions.contains(&token) {
+                None
+            } else {
+                Some(token.trim_end_matches('+'))
+            }
+        })
+    }
+
+    /// Returns whether the expression refers to license `id`, matched case-insensitively.
+    pub fn mentions(&self, id: &str) -> bool {
+        self.ids().any(|actual| actual.eq_ignore_ascii_case(id))
+    }
+}
+
+fn tokens(s: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let mut start = None;
+    for (i, c) in s.char_indices() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if let Some(start) = start.take() {
+                tokens.push(&s[start..i]);
+            }
+            if !c.is_whitespace() {
+                tokens.push(&s[i..i + 1]);
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(start) = start {
+        tokens.push(&s[start..]);
+    }
+    tokens
+}
+
+fn is_operator(token: &str) -> bool {
+    ["AND", "OR", "WITH"]
+        .iter()
+        .any(|op| token == *op || token == op.to_lowercase())
+}
+
+fn is_idstring(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-'))
+}
+
+/// Recursive descent parser of license expressions.
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        match self.peek() {
+            Some(token) if token == op || token == op.to_lowercase() => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn compound(&mut self) -> anyhow::Result<()> {
+        self.and()?;
+        while self.eat("OR") {
+            self.and()?;
+        }
+        Ok(())
+    }
+
+    fn and(&mut self) -> anyhow::Result<()> {
+        self.with()?;
+        while self.eat("AND") {
+            self.with()?;
+        }
+        Ok(())
+    }
+
+    fn with(&mut self) -> anyhow::Result<()> {
+        if self.eat("(") {
+            self.compound()?;
+            ensure!(self.eat(")"), "unbalanced parentheses");
+            return Ok(());
+        }
+        self.simple()?;
+        if self.eat("WITH") {
+            match self.next() {
+                Some(id) if is_idstring(id) && !is_operator(id) => {}
+                Some(id) => bail!("invalid license exception `{id}`"),
+                None => bail!("missing license exception"),
+            }
+        }
+        Ok(())
+    }
+
+    fn simple(&mut self) -> anyhow::Result<()> {
+        let token = match self.next() {
+            Some(token) => token,
+            None => bail!("missing license identifier"),
+        };
+        let valid = if let Some((doc, license)) = token.split_once(':') {
+            doc.strip_prefix("DocumentRef-").is_some_and(is_idstring)
+                && license.strip_prefix("LicenseRef-").is_some_and(is_idstring)
+        } else if let Some(id) = token.strip_prefix("LicenseRef-") {
+            is_idstring(id)
+        } else {
+            is_idstring(token.strip_suffix('+').unwrap_or(token))
+                && !is_operator(token)
+                && !matches!(token, "NONE" | "NOASSERTION")
+        };
+        ensure!(valid, "invalid license identifier `{token}`");
+        Ok(())
+    }
+}
+
+impl Display for License {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for License {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        ensure!(
+            s.len() <= Self::MAX_LENGTH,
+            "license expression exceeds {} bytes",
+            Self::MAX_LENGTH
+        );
+        if matches!(s, "NONE" | "NOASSERTION") {
+            return Ok(Self(s.into()));
+        }
+        let mut parser = Parser {
+            tokens: tokens(s),
+            pos: 0,
+        };
+        ensure!(!parser.tokens.is_empty(), "empty license expression");
+        parser.compound()?;
+        if let Some(token) = parser.peek() {
+            bail!("unexpected `{token}` in license expression")
+        }
+        Ok(Self(s.into()))
+    }
+}
+
+impl Serialize for License {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for License {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(|e| D::Error::custom(format!("invalid license expression: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_str() {
+        for s in [
+            "MIT",
+            "GPL-2.0+",
+            "MIT OR Apache-2.0",
+            "mit or apache-2.0",
+            "(MIT OR Apache-2.0) AND BSD-3-Clause",
+            "GPL-2.0-or-later WITH Classpath-exception-2.0",
+            "LicenseRef-Proprietary",
+            "DocumentRef-spdx-tool-1.2:LicenseRef-MIT-Style-2",
+            "((MIT))",
+            "NONE",
+            "NOASSERTION",
+        ] {
+            assert!(s.parse::<License>().is_ok(), "input '{}' should parse", s);
+        }
+        for s in [
+            "",
+            " ",
+            "MIT OR",
+            "AND MIT",
+            "MIT Apache-2.0",
+            "(MIT",
+            "MIT)",
+            "MIT WITH",
+            "MIT WITH AND",
+            "MIT Or Apache-2.0",
+            "M_T",
+            "LicenseRef-",
+            "DocumentRef-doc:MIT",
+            "MIT OR NONE",
+            &"A".repeat(License::MAX_LENGTH + 1),
+        ] {
+            assert!(
+                s.parse::<License>().is_err(),
+                "input '{}' should fail to parse",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn ids() {
+        let license: License = "(MIT OR GPL-2.0+) AND GPL-3.0 WITH GCC-exception-3.1"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            license.ids().collect::<Vec<_>>(),
+            ["MIT", "GPL-2.0", "GPL-3.0"]
+        );
+        assert!(license.mentions("mit"));
+        assert!(license.mentions("GPL-2.0"));
+        assert!(!license.mentions("GCC-exception-3.1"));
+        assert!(!license.mentions("Apache-2.0"));
+    }
+}
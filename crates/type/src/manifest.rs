@@ -0,0 +1,120 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Meta, Reference, Timestamp};
+
+use anyhow::{ensure, Context};
+use serde::{Deserialize, Serialize};
+
+/// A request for a [Manifest] of the artifacts a set of references refer to
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ManifestRequest {
+    /// [Reference]s to tags or paths within their trees, e.g. `user/repo:1.2.3/dir` or
+    /// `user/repo@sha-256:<hex>`
+    ///
+    /// References to directories, including tags without a path, i.e. their tree root, refer
+    /// to all files within them.
+    pub references: Vec<String>,
+
+    /// Number of seconds download URLs are pre-signed for, if they should be
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presign: Option<u64>,
+}
+
+impl ManifestRequest {
+    /// Maximum number of references of a request
+    pub const MAX_REFERENCES: usize = 256;
+
+    /// Returns the parsed references of the request.
+    pub fn references(&self) -> anyhow::Result<Vec<Reference>> {
+        ensure!(
+            self.references.len() <= Self::MAX_REFERENCES,
+            "request exceeds {} references",
+            Self::MAX_REFERENCES
+        );
+        self.references
+            .iter()
+            .map(|r| {
+                let reference: Reference = r
+                    .parse()
+                    .with_context(|| format!("invalid reference `{r}`"))?;
+                ensure!(
+                    reference.target.is_some(),
+                    "reference `{r}` must specify a tag or digest"
+                );
+                Ok(reference)
+            })
+            .collect()
+    }
+}
+
+/// Resolved digests, sizes and download URLs of the files references refer to
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Returns the total size of all files of the manifest.
+    pub fn size(&self) -> u64 {
+        self.entries.iter().map(|entry| entry.meta.size).sum()
+    }
+}
+
+/// A file a reference of a [ManifestRequest] refers to
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ManifestEntry {
+    /// The reference as requested
+    pub reference: String,
+
+    /// The [Reference] to the file by tag name and path
+    pub artifact: String,
+
+    /// The metadata of the file
+    #[serde(flatten)]
+    pub meta: Meta,
+
+    /// URL path the file can be downloaded from
+    pub url: String,
+
+    /// Time the URL expires, if it is pre-signed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<Timestamp>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn references() {
+        let req: ManifestRequest = serde_json::from_value(json!({
+            "references": ["user/repo:1.2.3", "user/repo:1.2.3/dir/file"],
+        }))
+        .unwrap();
+        assert_eq!(req.presign, None);
+        let references = req.references().unwrap();
+        assert_eq!(references.len(), 2);
+        assert_eq!(references[1].path, Some("dir/file".parse().unwrap()));
+
+        let req: ManifestRequest =
+            serde_json::from_value(json!({ "references": ["user/repo"], "presign": 60 })).unwrap();
+        assert_eq!(req.presign, Some(60));
+        assert!(req.references().is_err());
+
+        let req = ManifestRequest {
+            references: vec!["user/repo:1.0.0".into(); ManifestRequest::MAX_REFERENCES + 1],
+            presign: None,
+        };
+        assert!(req.references().is_err());
+
+        assert!(serde_json::from_value::<ManifestRequest>(json!({
+            "references": [],
+            "expires": 60,
+        }))
+        .is_err());
+    }
+}
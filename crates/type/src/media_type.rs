@@ -0,0 +1,257 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{TagAlias, TreeDirectory, TreeEntry};
+
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use drawbridge_jose::jws::Jws;
+use drawbridge_jose::MediaTyped;
+use mime::Mime;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+/// An error returned when parsing a [MediaType]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum MediaTypeError {
+    /// The media type is syntactically invalid
+    Syntax,
+    /// The type or subtype is not a restricted name as specified by RFC 6838, e.g. a wildcard
+    Name,
+}
+
+impl Display for MediaTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Syntax => f.write_str("invalid media type syntax"),
+            Self::Name => f.write_str("invalid media type or subtype name"),
+        }
+    }
+}
+
+impl std::error::Error for MediaTypeError {}
+
+/// A validated media type, optionally with parameters, e.g. `text/plain; charset=utf-8`
+///
+/// Type and subtype are lowercased and must be restricted names as specified by
+/// [RFC 6838](https://www.rfc-editor.org/rfc/rfc6838#section-4.2), which excludes wildcards.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MediaType(Cow<'static, str>);
+
+impl MediaType {
+    /// Maximum length of the type and the subtype in bytes
+    pub const MAX_NAME_LENGTH: usize = 127;
+
+    /// Arbitrary binary data, used for files of unknown type
+    pub const OCTET_STREAM: Self = Self(Cow::Borrowed("application/octet-stream"));
+
+    /// JSON
+    pub const JSON: Self = Self(Cow::Borrowed("application/json"));
+
+    /// Plain text
+    pub const TEXT: Self = Self(Cow::Borrowed("text/plain"));
+
+    /// A WebAssembly module
+    pub const WASM: Self = Self(Cow::Borrowed("application/wasm"));
+
+    /// A JSON-encoded Drawbridge directory
+    pub const DIRECTORY: Self = Self(Cow::Borrowed(TreeDirectory::<()>::TYPE));
+
+    /// A JSON-encoded, unsigned Drawbridge tag entry
+    pub const TAG: Self = Self(Cow::Borrowed(TreeEntry::<()>::TYPE));
+
+    /// A JWS-encoded, signed Drawbridge tag entry
+    pub const SIGNED_TAG: Self = Self(Cow::Borrowed(Jws::TYPE));
+
+    /// A JSON-encoded Drawbridge tag alias
+    pub const TAG_ALIAS: Self = Self(Cow::Borrowed(TagAlias::TYPE));
+
+    fn validate_name(name: &str) -> Result<(), MediaTypeError> {
+        if name.is_empty()
+            || name.len() > Self::MAX_NAME_LENGTH
+            || !name.starts_with(|c: char| c.is_ascii_alphanumeric())
+            || name.contains(|c: char| {
+                !c.is_ascii_alphanumeric()
+                    && !matches!(c, '!' | '#' | '$' | '&' | '-' | '^' | '_' | '.' | '+')
+            })
+        {
+            return Err(MediaTypeError::Name);
+        }
+        Ok(())
+    }
+
+    /// Returns the type and subtype without parameters, e.g. `text/plain`.
+    pub fn essence(&self) -> &str {
+        self.0.split(';').next().unwrap_or_default().trim()
+    }
+
+    /// Returns the type, e.g. `text`.
+    pub fn type_(&self) -> &str {
+        self.essence().split('/').next().unwrap_or_default()
+    }
+
+    /// Returns the subtype, e.g. `vnd.drawbridge.entry.v1+json`.
+    pub fn subtype(&self) -> &str {
+        self.essence()
+            .split_once('/')
+            .map(|(_, subtype)| subtype)
+            .unwrap_or_default()
+    }
+
+    /// Returns the structured syntax suffix of the subtype, if any, e.g. `json`.
+    pub fn suffix(&self) -> Option<&str> {
+        self.subtype().rsplit_once('+').map(|(_, suffix)| suffix)
+    }
+
+    /// Returns the value of parameter `name`, if specified.
+    pub fn parameter(&self, name: &str) -> Option<String> {
+        self.mime()
+            .get_param(name)
+            .map(|value| value.as_str().to_string())
+    }
+
+    /// Returns whether the media type is of `essence`, ignoring parameters.
+    pub fn is(&self, essence: &str) -> bool {
+        self.essence().eq_ignore_ascii_case(essence)
+    }
+
+    /// Returns the media type as a [Mime].
+    pub fn mime(&self) -> Mime {
+        // NOTE: Media types are valid by construction.
+        self.0.parse().unwrap_or(mime::APPLICATION_OCTET_STREAM)
+    }
+}
+
+impl AsRef<str> for MediaType {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for MediaType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<Mime> for MediaType {
+    type Error = MediaTypeError;
+
+    fn try_from(mime: Mime) -> Result<Self, Self::Error> {
+        Self::validate_name(mime.type_().as_str())?;
+        Self::validate_name(mime.subtype().as_str())?;
+        if let Some(suffix) = mime.suffix() {
+            Self::validate_name(suffix.as_str())?;
+        }
+        Ok(Self(Cow::Owned(mime.to_string())))
+    }
+}
+
+impl From<MediaType> for Mime {
+    fn from(media_type: MediaType) -> Self {
+        media_type.mime()
+    }
+}
+
+impl FromStr for MediaType {
+    type Err = MediaTypeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse::<Mime>()
+            .map_err(|_| MediaTypeError::Syntax)?
+            .try_into()
+    }
+}
+
+impl Serialize for MediaType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for MediaType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(|e| D::Error::custom(format!("invalid mime type: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_str() {
+        for s in [
+            "",
+            "text",
+            "text/",
+            "/plain",
+            "*/*",
+            "text/*",
+            "text/pl ain",
+            "text/pl%ain",
+            "-text/plain",
+            &format!("text/{}", "a".repeat(MediaType::MAX_NAME_LENGTH + 1)),
+        ] {
+            assert!(
+                s.parse::<MediaType>().is_err(),
+                "input '{}' should fail to parse",
+                s
+            );
+        }
+
+        let media_type: MediaType = "Application/Vnd.Example+JSON; charset=utf-8"
+            .parse()
+            .unwrap();
+        assert_eq!(media_type.essence(), "application/vnd.example+json");
+        assert_eq!(media_type.type_(), "application");
+        assert_eq!(media_type.subtype(), "vnd.example+json");
+        assert_eq!(media_type.suffix(), Some("json"));
+        assert_eq!(media_type.parameter("charset").as_deref(), Some("utf-8"));
+        assert_eq!(media_type.parameter("q"), None);
+        assert!(media_type.is("application/vnd.example+json"));
+        assert!(!media_type.is("application/json"));
+        assert_eq!(
+            media_type.to_string().parse::<MediaType>().unwrap(),
+            media_type
+        );
+    }
+
+    #[test]
+    fn constants() {
+        for media_type in [
+            MediaType::OCTET_STREAM,
+            MediaType::JSON,
+            MediaType::TEXT,
+            MediaType::WASM,
+            MediaType::DIRECTORY,
+            MediaType::TAG,
+            MediaType::SIGNED_TAG,
+            MediaType::TAG_ALIAS,
+        ] {
+            assert_eq!(
+                media_type.as_ref().parse::<MediaType>().unwrap(),
+                media_type
+            );
+            assert_eq!(media_type.mime().essence_str(), media_type.essence());
+        }
+        assert_eq!(MediaType::TAG.suffix(), Some("json"));
+        assert_eq!(MediaType::WASM.suffix(), None);
+    }
+
+    #[test]
+    fn serialization() {
+        assert_eq!(
+            serde_json::to_value(MediaType::DIRECTORY).unwrap(),
+            serde_json::json!("application/vnd.drawbridge.directory.v1+json")
+        );
+        assert_eq!(
+            serde_json::from_value::<MediaType>(serde_json::json!("text/plain")).unwrap(),
+            MediaType::TEXT
+        );
+        assert!(serde_json::from_value::<MediaType>(serde_json::json!("*/*")).is_err());
+    }
+}
@@ -2,16 +2,17 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 
 use crate::digest::ContentDigest;
+use crate::MediaType;
 
-use mime::Mime;
-use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use serde::{Deserialize, Serialize};
 
 #[cfg(feature = "axum")]
 use axum::{
-    extract::rejection::{TypedHeaderRejection, TypedHeaderRejectionReason},
+    extract::rejection::TypedHeaderRejectionReason,
     extract::{FromRequest, RequestParts, TypedHeader},
     headers::{ContentLength, ContentType},
-    response::{IntoResponseParts, Response, ResponseParts},
+    http::StatusCode,
+    response::{IntoResponse, IntoResponseParts, Response, ResponseParts},
 };
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -22,27 +23,14 @@ pub struct Meta {
     #[serde(rename = "length")]
     pub size: u64,
 
-    #[serde(deserialize_with = "deserialize")]
-    #[serde(serialize_with = "serialize")]
     #[serde(rename = "type")]
-    pub mime: Mime,
-}
-
-#[allow(single_use_lifetimes)]
-fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Mime, D::Error> {
-    String::deserialize(deserializer)?
-        .parse()
-        .map_err(|_| D::Error::custom("invalid mime type"))
-}
-
-fn serialize<S: Serializer>(mime: &Mime, serializer: S) -> Result<S::Ok, S::Error> {
-    mime.to_string().serialize(serializer)
+    pub mime: MediaType,
 }
 
 #[cfg(feature = "axum")]
 #[axum::async_trait]
 impl<B: Send> FromRequest<B> for Meta {
-    type Rejection = TypedHeaderRejection;
+    type Rejection = Response;
 
     async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
         let hash = match req.extract().await {
@@ -50,10 +38,25 @@ impl<B: Send> FromRequest<B> for Meta {
             Err(e) if matches!(e.reason(), TypedHeaderRejectionReason::Missing) => {
                 Default::default()
             }
-            Err(e) => return Err(e),
+            Err(e) => return Err(e.into_response()),
         };
-        let size = req.extract::<TypedHeader<ContentLength>>().await?.0 .0;
-        let mime = req.extract::<TypedHeader<ContentType>>().await?.0.into();
+        let size = req
+            .extract::<TypedHeader<ContentLength>>()
+            .await
+            .map_err(IntoResponse::into_response)?
+            .0
+             .0;
+        let TypedHeader(mime) = req
+            .extract::<TypedHeader<ContentType>>()
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let mime = MediaType::try_from(mime::Mime::from(mime)).map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Invalid content type: {e}"),
+            )
+                .into_response()
+        })?;
         Ok(Meta { hash, size, mime })
     }
 }
@@ -65,7 +68,7 @@ impl IntoResponseParts for Meta {
     fn into_response_parts(self, res: ResponseParts) -> Result<ResponseParts, Self::Error> {
         let hash = TypedHeader(self.hash);
         let size = TypedHeader(ContentLength(self.size));
-        let mime = TypedHeader(ContentType::from(self.mime));
+        let mime = TypedHeader(ContentType::from(self.mime.mime()));
         (hash, size, mime).into_response_parts(res)
     }
 }
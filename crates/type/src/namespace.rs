@@ -0,0 +1,278 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{bail, ensure};
+use serde::{Deserialize, Serialize};
+
+/// A class of characters, which may be allowed in names of a namespace
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CharClass {
+    /// ASCII digits, `0-9`
+    Digit,
+    /// Lowercase ASCII letters, `a-z`
+    Lower,
+    /// Uppercase ASCII letters, `A-Z`
+    Upper,
+    /// `-`
+    Hyphen,
+    /// `_`
+    Underscore,
+    /// `.`
+    Dot,
+}
+
+impl CharClass {
+    /// Returns whether `c` is of the class.
+    pub fn contains(self, c: char) -> bool {
+        match self {
+            Self::Digit => c.is_ascii_digit(),
+            Self::Lower => c.is_ascii_lowercase(),
+            Self::Upper => c.is_ascii_uppercase(),
+            Self::Hyphen => c == '-',
+            Self::Underscore => c == '_',
+            Self::Dot => c == '.',
+        }
+    }
+}
+
+impl Display for CharClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Digit => "digit",
+            Self::Lower => "lower",
+            Self::Upper => "upper",
+            Self::Hyphen => "hyphen",
+            Self::Underscore => "underscore",
+            Self::Dot => "dot",
+        })
+    }
+}
+
+impl FromStr for CharClass {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "digit" => Ok(Self::Digit),
+            "lower" => Ok(Self::Lower),
+            "upper" => Ok(Self::Upper),
+            "hyphen" => Ok(Self::Hyphen),
+            "underscore" => Ok(Self::Underscore),
+            "dot" => Ok(Self::Dot),
+            _ => bail!("unknown character class `{s}`"),
+        }
+    }
+}
+
+/// Rules names of a namespace, e.g. user or repository names, must satisfy
+///
+/// A namespace consists of up to [Self::max_depth] names separated by `/`, each of which must
+/// consist of [Self::min_length] to [Self::max_length] characters of the allowed classes and
+/// must not be reserved. Reserved names are matched case-insensitively and `.` and `..` are
+/// always rejected.
+///
+/// Rules are constructed using the builder methods, e.g.
+/// `NamespaceRules::new().allow(CharClass::Lower).max_length(32).reserve("admin")`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NamespaceRules {
+    classes: BTreeSet<CharClass>,
+    min_length: usize,
+    max_length: usize,
+    max_depth: usize,
+    reserved: BTreeSet<String>,
+}
+
+impl Default for NamespaceRules {
+    fn default() -> Self {
+        Self::repositories()
+    }
+}
+
+impl NamespaceRules {
+    /// Maximum length of a name in bytes, which rules cannot exceed
+    pub const MAX_LENGTH: usize = 255;
+
+    /// Returns rules allowing no characters, names of 1 to [Self::MAX_LENGTH] characters and
+    /// a depth of 1.
+    pub fn new() -> Self {
+        Self {
+            classes: BTreeSet::new(),
+            min_length: 1,
+            max_length: Self::MAX_LENGTH,
+            max_depth: 1,
+            reserved: BTreeSet::new(),
+        }
+    }
+
+    /// Returns the built-in rules of user names, which allow ASCII letters and digits.
+    pub fn users() -> Self {
+        Self::new()
+            .allow(CharClass::Digit)
+            .allow(CharClass::Lower)
+            .allow(CharClass::Upper)
+    }
+
+    /// Returns the built-in rules of repository names, which allow ASCII letters, digits and
+    /// `-`.
+    pub fn repositories() -> Self {
+        Self::users().allow(CharClass::Hyphen)
+    }
+
+    /// Allows characters of `class`.
+    pub fn allow(mut self, class: CharClass) -> Self {
+        _ = self.classes.insert(class);
+        self
+    }
+
+    /// Disallows characters of `class`.
+    pub fn deny(mut self, class: CharClass) -> Self {
+        _ = self.classes.remove(&class);
+        self
+    }
+
+    /// Sets the minimum length of names in characters.
+    pub fn min_length(self, min_length: usize) -> Self {
+        Self { min_length, ..self }
+    }
+
+    /// Sets the maximum length of names in characters, capped at [Self::MAX_LENGTH].
+    pub fn max_length(self, max_length: usize) -> Self {
+        Self {
+            max_length: max_length.min(Self::MAX_LENGTH),
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of names in a namespace.
+    pub fn max_depth(self, max_depth: usize) -> Self {
+        Self { max_depth, ..self }
+    }
+
+    /// Reserves `name`, so that it cannot be used.
+    pub fn reserve(mut self, name: impl Into<String>) -> Self {
+        _ = self.reserved.insert(name.into().to_lowercase());
+        self
+    }
+
+    /// Returns whether `c` is allowed in names.
+    pub fn allows(&self, c: char) -> bool {
+        self.classes.iter().any(|class| class.contains(c))
+    }
+
+    /// Returns whether `name` is reserved.
+    pub fn is_reserved(&self, name: &str) -> bool {
+        self.reserved.contains(&name.to_lowercase())
+    }
+
+    /// Returns an error unless names in `namespace` satisfy the rules.
+    pub fn validate(&self, namespace: &str) -> anyhow::Result<()> {
+        if namespace.is_empty() {
+            bail!("empty name")
+        }
+        let names = namespace.split('/').collect::<Vec<_>>();
+        ensure!(
+            names.len() <= self.max_depth,
+            "`{namespace}` exceeds the maximum depth of {}",
+            self.max_depth
+        );
+        for name in names {
+            self.validate_name(name)?;
+        }
+        Ok(())
+    }
+
+    fn validate_name(&self, name: &str) -> anyhow::Result<()> {
+        let len = name.chars().count();
+        if name.is_empty() {
+            bail!("empty name")
+        } else if name == "." || name == ".." {
+            bail!("name `{name}` refers to the current or parent directory")
+        } else if len < self.min_length {
+            bail!(
+                "name `{name}` is shorter than {} characters",
+                self.min_length
+            )
+        } else if len > self.max_length || name.len() > Self::MAX_LENGTH {
+            bail!("name `{name}` exceeds {} characters", self.max_length)
+        } else if !name.chars().all(|c| self.allows(c)) {
+            bail!("invalid characters in name `{name}`")
+        } else if self.is_reserved(name) {
+            bail!("name `{name}` is reserved")
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        let rules = NamespaceRules::new()
+            .allow(CharClass::Lower)
+            .allow(CharClass::Digit)
+            .allow(CharClass::Dot)
+            .min_length(2)
+            .max_length(8)
+            .max_depth(2)
+            .reserve("Admin");
+        for s in ["ab", "a.b", "group/name", "v1.2/x9"] {
+            assert!(rules.validate(s).is_ok(), "input '{}' should be valid", s);
+        }
+        for s in [
+            "",
+            "a",
+            "abcdefghi",
+            "Ab",
+            "a-b",
+            "admin",
+            "ADMIN",
+            "..",
+            "group/",
+            "/name",
+            "group//name",
+            "a/b/c",
+            "group/subgroup/name",
+        ] {
+            assert!(
+                rules.validate(s).is_err(),
+                "input '{}' should be invalid",
+                s
+            );
+        }
+
+        let rules = NamespaceRules::default().deny(CharClass::Upper);
+        assert!(rules.validate("n4me-").is_ok());
+        assert!(rules.validate("N4me").is_err());
+        assert!(NamespaceRules::users().validate("n4me-").is_err());
+        assert_eq!(
+            NamespaceRules::new().max_length(1024),
+            NamespaceRules::new()
+        );
+    }
+
+    #[test]
+    fn char_class() {
+        for class in [
+            CharClass::Digit,
+            CharClass::Lower,
+            CharClass::Upper,
+            CharClass::Hyphen,
+            CharClass::Underscore,
+            CharClass::Dot,
+        ] {
+            assert_eq!(class.to_string().parse::<CharClass>().unwrap(), class);
+            assert_eq!(
+                serde_json::to_value(class).unwrap(),
+                serde_json::json!(class.to_string())
+            );
+        }
+        assert!("alpha".parse::<CharClass>().is_err());
+    }
+}
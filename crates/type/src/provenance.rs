@@ -0,0 +1,58 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// Origin of an artifact, i.e. the sources and the build it was produced by
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Provenance {
+    /// URI of the sources, e.g. `https://github.com/profianinc/drawbridge`
+    pub source: Url,
+
+    /// Revision of the sources, e.g. a commit hash or a tag
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<String>,
+
+    /// Identifier of the builder, e.g. the URI of a CI workflow
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub builder: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn serialization() {
+        let provenance: Provenance = serde_json::from_value(json!({
+            "source": "https://github.com/profianinc/drawbridge",
+            "revision": "0123abc",
+        }))
+        .unwrap();
+        assert_eq!(
+            provenance.source.as_str(),
+            "https://github.com/profianinc/drawbridge"
+        );
+        assert_eq!(provenance.revision.as_deref(), Some("0123abc"));
+        assert_eq!(provenance.builder, None);
+        assert_eq!(
+            serde_json::to_value(&provenance).unwrap(),
+            json!({
+                "source": "https://github.com/profianinc/drawbridge",
+                "revision": "0123abc",
+            })
+        );
+
+        assert!(serde_json::from_value::<Provenance>(json!({ "source": "not a uri" })).is_err());
+        assert!(serde_json::from_value::<Provenance>(json!({ "revision": "0123abc" })).is_err());
+        assert!(serde_json::from_value::<Provenance>(json!({
+            "source": "https://example.com",
+            "unknown": true,
+        }))
+        .is_err());
+    }
+}
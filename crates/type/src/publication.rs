@@ -0,0 +1,80 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::Timestamp;
+
+use anyhow::ensure;
+use serde::{Deserialize, Serialize};
+
+/// Claims of a signed tag entry binding it to a single publication, so that a captured publish
+/// request cannot be replayed, e.g. to re-point another tag to the signed tree
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Publication {
+    /// Tag the entry is published as in `owner/repository:tag` form
+    pub tag: String,
+
+    /// Random value unique to the publication, e.g. a UUID
+    pub nonce: String,
+
+    /// Time the entry was signed at
+    pub issued: Timestamp,
+}
+
+impl Publication {
+    /// Minimum length of a nonce in bytes
+    pub const MIN_NONCE_LENGTH: usize = 16;
+
+    /// Maximum length of a nonce in bytes
+    pub const MAX_NONCE_LENGTH: usize = 128;
+
+    /// Returns an error if the nonce is too short or too long.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            (Self::MIN_NONCE_LENGTH..=Self::MAX_NONCE_LENGTH).contains(&self.nonce.len()),
+            "nonce must be between {} and {} bytes long",
+            Self::MIN_NONCE_LENGTH,
+            Self::MAX_NONCE_LENGTH
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn serialization() {
+        let publication: Publication = serde_json::from_value(json!({
+            "tag": "user/repo:1.0.0",
+            "nonce": "4bd2b6b1-8a2f-4c1c-9d43-2f9b5fdc1e07",
+            "issued": "2022-09-01T12:00:00Z",
+        }))
+        .unwrap();
+        assert_eq!(publication.tag, "user/repo:1.0.0");
+        assert!(publication.validate().is_ok());
+        assert_eq!(
+            serde_json::to_value(&publication).unwrap(),
+            json!({
+                "tag": "user/repo:1.0.0",
+                "nonce": "4bd2b6b1-8a2f-4c1c-9d43-2f9b5fdc1e07",
+                "issued": "2022-09-01T12:00:00Z",
+            })
+        );
+
+        assert!(serde_json::from_value::<Publication>(json!({
+            "tag": "user/repo:1.0.0",
+            "nonce": "4bd2b6b1-8a2f-4c1c-9d43-2f9b5fdc1e07",
+        }))
+        .is_err());
+        assert!(Publication {
+            nonce: "short".into(),
+            ..publication
+        }
+        .validate()
+        .is_err());
+    }
+}
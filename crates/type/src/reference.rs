@@ -0,0 +1,268 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::digest::{Algorithm, ContentDigest};
+use super::{RepositoryContext, TagContext, TagName, TreeContext, TreePath};
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, ensure, Context as _};
+
+/// The version of a repository an artifact [Reference] refers to
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ReferenceTarget {
+    /// A tag identified by its name, e.g. `:1.2.3`
+    Tag(TagName),
+    /// A tag identified by the digest of its entry, e.g. `@sha-256:<hex>`
+    Digest(Algorithm, Vec<u8>),
+}
+
+impl ReferenceTarget {
+    /// Returns whether `digest` of a tag entry is matched by the target.
+    pub fn matches(&self, digest: &ContentDigest) -> bool {
+        match self {
+            Self::Tag(..) => false,
+            Self::Digest(algorithm, value) => digest
+                .get(algorithm)
+                .is_some_and(|actual| actual.as_ref() == value.as_slice()),
+        }
+    }
+}
+
+impl Display for ReferenceTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Tag(name) => write!(f, ":{name}"),
+            Self::Digest(algorithm, value) => {
+                write!(f, "@{algorithm}:")?;
+                value.iter().try_for_each(|b| write!(f, "{b:02x}"))
+            }
+        }
+    }
+}
+
+/// Coordinates of an artifact, i.e. a repository, optionally a tag and a path within its tree
+///
+/// References are of the form `[HOST/]OWNER/REPOSITORY[:TAG|@ALGORITHM:HEX][/PATH]`, e.g.
+/// `drawbridge.example.com/user/repo:1.2.3/dir/file` or `user/repo@sha-256:<hex>`. The first
+/// segment is a host, if it contains `.` or `:` or is `localhost`, which user names never do.
+/// A path may only be specified along with a tag or digest.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Reference {
+    /// Host, optionally with a port, of the Drawbridge instance
+    pub host: Option<String>,
+    pub repository: RepositoryContext,
+    pub target: Option<ReferenceTarget>,
+    /// Path within the tree of the tag, `/` referring to the tree root
+    pub path: Option<TreePath>,
+}
+
+impl Reference {
+    /// Returns the context of the tag referred to by name, if any.
+    pub fn tag(&self) -> Option<TagContext> {
+        match self.target {
+            Some(ReferenceTarget::Tag(ref name)) => Some(TagContext {
+                repository: self.repository.clone(),
+                name: name.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the context of the tree node referred to by tag name and path, if any.
+    pub fn tree(&self) -> Option<TreeContext> {
+        Some(TreeContext {
+            tag: self.tag()?,
+            path: self.path.clone()?,
+        })
+    }
+
+    /// Returns an error unless the reference refers to a repository only, e.g. to accept
+    /// references only where a repository is expected.
+    pub fn assert_repository(&self) -> anyhow::Result<&RepositoryContext> {
+        ensure!(
+            self.target.is_none() && self.path.is_none(),
+            "reference `{self}` must not specify a tag, digest or path"
+        );
+        Ok(&self.repository)
+    }
+}
+
+fn is_host(segment: &str) -> bool {
+    segment == "localhost" || segment.contains(['.', ':'])
+}
+
+fn parse_digest(s: &str) -> anyhow::Result<ReferenceTarget> {
+    let (algorithm, hex) = s
+        .split_once(':')
+        .ok_or_else(|| anyhow!("digest `{s}` must be of the form `ALGORITHM:HEX`"))?;
+    let algorithm: Algorithm = algorithm
+        .parse()
+        .map_err(|_| anyhow!("unknown digest algorithm `{algorithm}`"))?;
+    let size = algorithm.size();
+    ensure!(
+        hex.len() == 2 * size,
+        "{algorithm} digest must consist of {} hexadecimal digits",
+        2 * size
+    );
+    ensure!(
+        hex.bytes().all(|c| matches!(c, b'0'..=b'9' | b'a'..=b'f')),
+        "{algorithm} digest must consist of lowercase hexadecimal digits"
+    );
+    let value = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect::<Result<_, _>>()?;
+    Ok(ReferenceTarget::Digest(algorithm, value))
+}
+
+impl FromStr for Reference {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (host, rest) = match s.split_once('/') {
+            Some((host, rest)) if is_host(host) => (Some(host.to_string()), rest),
+            _ => (None, s),
+        };
+        let (owner, repository, path) = match rest.splitn(3, '/').collect::<Vec<_>>()[..] {
+            [owner, repository] => (owner, repository, None),
+            [owner, repository, path] => (owner, repository, Some(path)),
+            _ => bail!(
+                "reference `{s}` must be of the form `[HOST/]OWNER/REPOSITORY[:TAG|@DIGEST][/PATH]`"
+            ),
+        };
+        let (repository, target) = if let Some((repository, digest)) = repository.split_once('@') {
+            (
+                repository,
+                Some(parse_digest(digest).context("failed to parse tag digest")?),
+            )
+        } else if let Some((repository, tag)) = repository.split_once(':') {
+            (
+                repository,
+                Some(ReferenceTarget::Tag(
+                    tag.parse()
+                        .context("failed to parse tag semantic version")?,
+                )),
+            )
+        } else {
+            (repository, None)
+        };
+        let repository = (owner, repository)
+            .try_into()
+            .context("failed to parse repository context")?;
+        let path = match path {
+            Some(_) if target.is_none() => {
+                bail!("path in reference `{s}` requires a tag or digest")
+            }
+            Some(path) => Some(
+                format!("/{path}")
+                    .parse()
+                    .context("failed to parse tree path")?,
+            ),
+            None => None,
+        };
+        Ok(Self {
+            host,
+            repository,
+            target,
+            path,
+        })
+    }
+}
+
+impl Display for Reference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(ref host) = self.host {
+            write!(f, "{host}/")?;
+        }
+        write!(f, "{}", self.repository)?;
+        if let Some(ref target) = self.target {
+            write!(f, "{target}")?;
+        }
+        if let Some(ref path) = self.path {
+            write!(f, "/{path}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA256: &str = "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae";
+
+    #[test]
+    fn from_str() {
+        let reference: Reference = "user/repo".parse().unwrap();
+        assert_eq!(reference.host, None);
+        assert_eq!(reference.repository, "user/repo".parse().unwrap());
+        assert_eq!(reference.target, None);
+        assert_eq!(reference.path, None);
+        assert_eq!(reference.tag(), None);
+        assert!(reference.assert_repository().is_ok());
+
+        let reference: Reference = "drawbridge.example.com:8443/user/repo:1.2.3-rc.1/dir/file"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            reference.host.as_deref(),
+            Some("drawbridge.example.com:8443")
+        );
+        assert_eq!(
+            reference.tag(),
+            Some("user/repo:1.2.3-rc.1".parse().unwrap())
+        );
+        assert_eq!(
+            reference.tree().unwrap().path,
+            "dir/file".parse::<TreePath>().unwrap()
+        );
+        assert!(reference.assert_repository().is_err());
+
+        let reference: Reference = "localhost/user/repo:1.0.0/".parse().unwrap();
+        assert_eq!(reference.host.as_deref(), Some("localhost"));
+        assert_eq!(reference.path, Some(TreePath::ROOT));
+
+        let reference: Reference = format!("user/repo@sha-256:{SHA256}").parse().unwrap();
+        let digest: ContentDigest = "sha-256=:LCa0a2j/xo/5m0U8HTBBNBNCLXBkg7+g+YpeiGJm564=:"
+            .parse()
+            .unwrap();
+        assert!(reference.target.as_ref().unwrap().matches(&digest));
+        assert_eq!(reference.tag(), None);
+
+        for s in [
+            "",
+            "user",
+            "example.com/user",
+            "user/repo/dir",
+            "user/re.po",
+            "us-er/repo",
+            "user/repo:v1",
+            "user/repo:1.0.0/../etc",
+            "user/repo:1.0.0//file",
+            "user/repo@sha-256",
+            "user/repo@md5:00",
+            "user/repo@sha-256:00",
+            &format!("user/repo@sha-256:{}", SHA256.to_uppercase()),
+        ] {
+            assert!(
+                s.parse::<Reference>().is_err(),
+                "input '{}' should fail to parse",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn display() {
+        for s in [
+            "user/repo",
+            "user/repo:1.2.3",
+            "example.com/user/repo:1.2.3/dir/file",
+            &format!("localhost:8080/user/repo@sha-256:{SHA256}/file"),
+        ] {
+            assert_eq!(s.parse::<Reference>().unwrap().to_string(), s);
+        }
+    }
+}
@@ -1,6 +1,10 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
+use super::super::digest::AlgorithmRules;
+use super::super::{License, Provenance};
+use super::{ReviewPolicy, Scanning, SigningPolicy, VulnerabilityPolicy, Webhook};
+
 use serde::{Deserialize, Serialize};
 
 /// A repository config
@@ -9,4 +13,40 @@ use serde::{Deserialize, Serialize};
 #[serde(deny_unknown_fields)]
 pub struct Config {
     pub public: bool,
+
+    /// Description of the repository, e.g. as shown in search results
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<Webhook>,
+
+    /// License of the artifacts hosted in the repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<License>,
+
+    /// Origin of the artifacts hosted in the repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+
+    /// Content scanning of uploaded tree nodes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scanning: Option<Scanning>,
+
+    /// Signatures published tags must carry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing: Option<SigningPolicy>,
+
+    /// Vulnerability findings blocking pulls of tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vulnerabilities: Option<VulnerabilityPolicy>,
+
+    /// Hashing algorithms content digests of uploads must or must not consist of, in
+    /// addition to the rules of the owner
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithms: Option<AlgorithmRules>,
+
+    /// Two-person review of publishes to protected tags
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review: Option<ReviewPolicy>,
 }
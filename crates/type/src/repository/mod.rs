@@ -4,7 +4,19 @@
 mod config;
 mod context;
 mod name;
+mod review;
+mod scanning;
+mod signing;
+mod snapshot;
+mod vulnerability;
+mod webhook;
 
 pub use config::*;
 pub use context::*;
 pub use name::*;
+pub use review::*;
+pub use scanning::*;
+pub use signing::*;
+pub use snapshot::*;
+pub use vulnerability::*;
+pub use webhook::*;
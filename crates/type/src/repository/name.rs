@@ -1,11 +1,13 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
+use super::super::NamespaceRules;
+
 use std::fmt::Display;
 use std::ops::Deref;
 use std::str::FromStr;
 
-use anyhow::bail;
+use anyhow::anyhow;
 use serde::de::Error;
 use serde::{Deserialize, Deserializer, Serialize};
 
@@ -18,17 +20,9 @@ pub struct Name(String);
 impl Name {
     #[inline]
     fn validate(s: impl AsRef<str>) -> anyhow::Result<()> {
-        let s = s.as_ref();
-        if s.is_empty() {
-            bail!("empty repository name")
-        } else if s
-            .find(|c| !matches!(c, '0'..='9' | 'a'..='z' | 'A'..='Z' | '-'))
-            .is_some()
-        {
-            bail!("invalid characters in repository name")
-        } else {
-            Ok(())
-        }
+        NamespaceRules::repositories()
+            .validate(s.as_ref())
+            .map_err(|e| anyhow!("invalid repository name: {e}"))
     }
 }
 
@@ -0,0 +1,162 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::digest::ContentDigest;
+use super::super::{TagKind, TagName, Timestamp, VersionReq};
+
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+fn default_expiry() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+/// Two-person review of publishes to protected tags of a repository
+///
+/// Publishes to protected tags, which do not exist yet, create a [PendingChange] instead of
+/// the tag, which only becomes visible once one of the [Self::reviewers] other than the
+/// publisher approves the change.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReviewPolicy {
+    /// Requirements names of protected tags match one of, all tags are protected if empty
+    ///
+    /// Note, that requirements only match pre-release tags, if they mention a pre-release of
+    /// the same version, e.g. `>=1.0.0-rc.1`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<VersionReq>,
+
+    /// OpenID Connect subjects of the identities, which may approve pending changes
+    pub reviewers: Vec<String>,
+
+    /// Number of seconds, after which pending changes expire, 7 days by default
+    #[serde(default = "default_expiry")]
+    pub expiry: u64,
+}
+
+impl ReviewPolicy {
+    /// Returns whether tag `name` is protected by the policy.
+    pub fn protects(&self, name: &TagName) -> bool {
+        self.tags.is_empty() || self.tags.iter().any(|req| req.matches(name))
+    }
+
+    /// Returns whether the identity with OpenID Connect subject `subject` may approve `change`.
+    ///
+    /// Reviewers never approve their own changes.
+    pub fn may_approve(&self, change: &PendingChange, subject: &str) -> bool {
+        change.requester != subject && self.reviewers.iter().any(|r| r == subject)
+    }
+
+    /// Returns a change of tag `tag` to an entry of kind `kind` with digest `digest` requested
+    /// by `requester` at `now`, which expires as configured by the policy.
+    pub fn change(
+        &self,
+        id: impl Into<String>,
+        tag: TagName,
+        kind: TagKind,
+        digest: ContentDigest,
+        requester: impl Into<String>,
+        now: SystemTime,
+    ) -> PendingChange {
+        PendingChange {
+            id: id.into(),
+            tag,
+            kind,
+            digest,
+            requester: requester.into(),
+            created: now.into(),
+            expires: (now + Duration::from_secs(self.expiry)).into(),
+        }
+    }
+}
+
+/// A publish to a protected tag awaiting approval
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct PendingChange {
+    pub id: String,
+
+    /// Name of the tag published
+    pub tag: TagName,
+
+    /// Kind of the tag entry published
+    pub kind: TagKind,
+
+    /// Digest of the tag entry published
+    pub digest: ContentDigest,
+
+    /// OpenID Connect subject of the publisher
+    pub requester: String,
+
+    pub created: Timestamp,
+
+    /// Time, after which the change may no longer be approved
+    pub expires: Timestamp,
+}
+
+impl PendingChange {
+    /// Returns whether the change is expired at `now`.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        now > self.expires.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn protects() {
+        let policy: ReviewPolicy =
+            serde_json::from_value(json!({ "reviewers": ["alice"] })).unwrap();
+        assert_eq!(policy.expiry, default_expiry());
+        assert!(policy.protects(&"0.1.0".parse().unwrap()));
+
+        let policy: ReviewPolicy = serde_json::from_value(json!({
+            "tags": [">=1.0.0"],
+            "reviewers": ["alice"],
+        }))
+        .unwrap();
+        assert!(policy.protects(&"1.2.3".parse().unwrap()));
+        assert!(!policy.protects(&"0.1.0".parse().unwrap()));
+        assert!(!policy.protects(&"1.2.3-rc.1".parse().unwrap()));
+
+        assert!(serde_json::from_value::<ReviewPolicy>(json!({})).is_err());
+        assert!(serde_json::from_value::<ReviewPolicy>(json!({
+            "reviewers": [],
+            "approvals": 2,
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn approve() {
+        let policy: ReviewPolicy = serde_json::from_value(json!({
+            "reviewers": ["alice", "bob"],
+            "expiry": 60,
+        }))
+        .unwrap();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let change = policy.change(
+            "1",
+            "1.0.0".parse().unwrap(),
+            TagKind::Unsigned,
+            ContentDigest::default(),
+            "alice",
+            now,
+        );
+        assert!(!policy.may_approve(&change, "alice"));
+        assert!(policy.may_approve(&change, "bob"));
+        assert!(!policy.may_approve(&change, "eve"));
+
+        assert!(!change.is_expired(now));
+        assert!(!change.is_expired(now + Duration::from_secs(60)));
+        assert!(change.is_expired(now + Duration::from_secs(61)));
+        assert_eq!(
+            serde_json::to_value(&change).unwrap()["expires"],
+            json!("1970-01-12T13:47:40Z")
+        );
+    }
+}
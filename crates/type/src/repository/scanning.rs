@@ -0,0 +1,67 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+
+/// Mode of content scanning of uploaded tree nodes
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScanMode {
+    /// Nodes are scanned before the upload is accepted and rejected uploads are discarded
+    #[default]
+    Sync,
+    /// Nodes are scanned in background after the upload is accepted and tags containing
+    /// rejected nodes are quarantined
+    Queued,
+}
+
+/// Content scanning of tree nodes uploaded to a repository
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scanning {
+    /// Names of the scanners configured on the server, which all uploaded file nodes are
+    /// scanned by
+    pub scanners: Vec<String>,
+
+    #[serde(default)]
+    pub mode: ScanMode,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn serialization() {
+        let scanning: Scanning = serde_json::from_value(json!({
+            "scanners": ["clamav"],
+        }))
+        .unwrap();
+        assert_eq!(
+            scanning,
+            Scanning {
+                scanners: vec!["clamav".into()],
+                mode: ScanMode::Sync,
+            }
+        );
+
+        let scanning = Scanning {
+            scanners: vec!["clamav".into(), "policy".into()],
+            mode: ScanMode::Queued,
+        };
+        let json = json!({
+            "scanners": ["clamav", "policy"],
+            "mode": "queued",
+        });
+        assert_eq!(serde_json::to_value(&scanning).unwrap(), json);
+        assert_eq!(serde_json::from_value::<Scanning>(json).unwrap(), scanning);
+
+        assert!(serde_json::from_value::<Scanning>(json!({
+            "scanners": [],
+            "mode": "async",
+        }))
+        .is_err());
+    }
+}
@@ -0,0 +1,50 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use serde::{Deserialize, Serialize};
+
+/// Signatures tags published to a repository must carry
+///
+/// Tags must be signed tags with a signature verified by one of the [Self::keys], aliases of
+/// such tags are accepted as well.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SigningPolicy {
+    /// Names of the server keys, which may verify signatures of tags, all server keys if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<String>,
+}
+
+impl SigningPolicy {
+    /// Returns whether a signature verified by server key `name` satisfies the policy.
+    pub fn accepts(&self, name: &str) -> bool {
+        self.keys.is_empty() || self.keys.iter().any(|key| key == name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn accepts() {
+        let policy: SigningPolicy = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(policy, SigningPolicy::default());
+        assert!(policy.accepts("release"));
+
+        let policy: SigningPolicy = serde_json::from_value(json!({
+            "keys": ["release"],
+        }))
+        .unwrap();
+        assert!(policy.accepts("release"));
+        assert!(!policy.accepts("nightly"));
+        assert_eq!(
+            serde_json::to_value(&policy).unwrap(),
+            json!({ "keys": ["release"] })
+        );
+
+        assert!(serde_json::from_value::<SigningPolicy>(json!({ "required": true })).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{Meta, TagName};
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// An entity contained in a repository snapshot archive
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SnapshotEntity {
+    /// Path of the entity contents within the archive
+    pub path: String,
+
+    #[serde(flatten)]
+    pub meta: Meta,
+}
+
+/// A tag contained in a repository snapshot archive
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SnapshotTag {
+    /// The tag entry
+    pub entry: SnapshotEntity,
+
+    /// Nodes of the tag tree keyed by path, e.g. `/` for the root
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tree: BTreeMap<String, SnapshotEntity>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sbom: Option<SnapshotEntity>,
+
+    /// Signatures attached to the tag keyed by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub signatures: BTreeMap<String, SnapshotEntity>,
+
+    /// Attestations attached to the tag keyed by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attestations: BTreeMap<String, SnapshotEntity>,
+}
+
+/// Manifest of a repository snapshot archive, which lists digests of all entities contained
+/// in it
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Snapshot {
+    /// Version of the archive format
+    pub version: u32,
+
+    /// Repository the snapshot was exported from, e.g. `user/repo`
+    pub repository: String,
+
+    /// The repository config
+    pub config: SnapshotEntity,
+
+    /// Tags of the repository keyed by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tags: BTreeMap<String, SnapshotTag>,
+}
+
+impl Snapshot {
+    /// Path of the manifest within the archive
+    pub const PATH: &'static str = "manifest.json";
+
+    /// Current version of the archive format
+    pub const VERSION: u32 = 1;
+
+    /// Returns all entities listed in the manifest.
+    pub fn entities(&self) -> impl Iterator<Item = &SnapshotEntity> {
+        Some(&self.config)
+            .into_iter()
+            .chain(self.tags.values().flat_map(|tag| {
+                Some(&tag.entry)
+                    .into_iter()
+                    .chain(tag.tree.values())
+                    .chain(tag.sbom.as_ref())
+                    .chain(tag.signatures.values())
+                    .chain(tag.attestations.values())
+            }))
+    }
+}
+
+/// Result of an import of a repository snapshot archive
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SnapshotImport {
+    /// Whether the repository was created
+    pub created: bool,
+
+    /// Tags created by the import
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub imported: Vec<TagName>,
+
+    /// Tags skipped, since they already existed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<TagName>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn serialization() {
+        let entity = |path: &str| SnapshotEntity {
+            path: path.into(),
+            meta: Meta {
+                hash: "sha-256=:LCa0a2j/xo/5m0U8HTBBNBNCLXBkg7+g+YpeiGJm564=:"
+                    .parse()
+                    .unwrap(),
+                size: 3,
+                mime: "application/json".parse().unwrap(),
+            },
+        };
+        let snapshot = Snapshot {
+            version: Snapshot::VERSION,
+            repository: "user/repo".into(),
+            config: entity("blobs/a"),
+            tags: BTreeMap::from([(
+                "0.1.0".into(),
+                SnapshotTag {
+                    entry: entity("blobs/b"),
+                    tree: BTreeMap::from([("/".into(), entity("blobs/c"))]),
+                    sbom: None,
+                    signatures: BTreeMap::new(),
+                    attestations: BTreeMap::from([("d".into(), entity("blobs/d"))]),
+                },
+            )]),
+        };
+        assert_eq!(
+            snapshot
+                .entities()
+                .map(|entity| entity.path.as_str())
+                .collect::<Vec<_>>(),
+            ["blobs/a", "blobs/b", "blobs/c", "blobs/d"]
+        );
+
+        let value = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(
+            value["tags"]["0.1.0"]["tree"]["/"],
+            json!({
+                "path": "blobs/c",
+                "digest": {"sha-256": "LCa0a2j/xo/5m0U8HTBBNBNCLXBkg7+g+YpeiGJm564="},
+                "length": 3,
+                "type": "application/json",
+            })
+        );
+        assert!(value["tags"]["0.1.0"].get("signatures").is_none());
+        assert_eq!(serde_json::from_value::<Snapshot>(value).unwrap(), snapshot);
+
+        assert_eq!(
+            serde_json::to_value(SnapshotImport {
+                created: true,
+                ..Default::default()
+            })
+            .unwrap(),
+            json!({ "created": true })
+        );
+    }
+}
@@ -0,0 +1,109 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{Error, Severity, VulnerabilityReport};
+
+use serde::{Deserialize, Serialize};
+
+fn default_block() -> Severity {
+    Severity::Critical
+}
+
+/// Vulnerability findings, which block pulls of tags of a repository
+///
+/// Pulls are blocked if the latest vulnerability report attached to the tag contains a
+/// finding with a severity of at least [Self::block]. Tags without reports are only blocked
+/// if [Self::require_report] is set.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct VulnerabilityPolicy {
+    /// Least severity of findings blocking pulls, `critical` by default
+    #[serde(default = "default_block")]
+    pub block: Severity,
+
+    /// Whether tags without a vulnerability report are blocked
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub require_report: bool,
+}
+
+impl Default for VulnerabilityPolicy {
+    fn default() -> Self {
+        Self {
+            block: default_block(),
+            require_report: false,
+        }
+    }
+}
+
+impl VulnerabilityPolicy {
+    /// Returns [Error::Vulnerable] if pulls of a tag with the latest vulnerability report
+    /// `report` are blocked by the policy.
+    pub fn check(&self, report: Option<&VulnerabilityReport>) -> Result<(), Error> {
+        match report {
+            None if self.require_report => Err(Error::Vulnerable {
+                severity: None,
+                findings: 0,
+            }),
+            None => Ok(()),
+            Some(report) => {
+                let blocking: Vec<_> = report.findings_at_least(self.block).collect();
+                match blocking.iter().map(|f| f.severity).max() {
+                    Some(severity) => Err(Error::Vulnerable {
+                        severity: Some(severity),
+                        findings: blocking.len(),
+                    }),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn check() {
+        let report: VulnerabilityReport = serde_json::from_value(json!({
+            "subject": [],
+            "scanner": "grype",
+            "findings": [
+                { "id": "CVE-2022-0001", "severity": "high" },
+                { "id": "CVE-2022-0002", "severity": "medium" },
+            ],
+        }))
+        .unwrap();
+
+        let policy: VulnerabilityPolicy = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(policy, VulnerabilityPolicy::default());
+        assert_eq!(policy.check(None), Ok(()));
+        assert_eq!(policy.check(Some(&report)), Ok(()));
+
+        let policy: VulnerabilityPolicy = serde_json::from_value(json!({
+            "block": "medium",
+            "require_report": true,
+        }))
+        .unwrap();
+        assert_eq!(
+            policy.check(None),
+            Err(Error::Vulnerable {
+                severity: None,
+                findings: 0
+            })
+        );
+        assert_eq!(
+            policy.check(Some(&report)),
+            Err(Error::Vulnerable {
+                severity: Some(Severity::High),
+                findings: 2
+            })
+        );
+
+        assert!(
+            serde_json::from_value::<VulnerabilityPolicy>(json!({ "block": "severe" })).is_err()
+        );
+    }
+}
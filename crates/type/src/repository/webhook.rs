@@ -0,0 +1,143 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::digest::ContentDigest;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// A kind of event, which webhooks are notified of
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WebhookEvent {
+    /// A tag was created
+    TagCreate,
+    /// A tag deleted before was restored from the trash
+    TagUpdate,
+    /// A tag was deleted, e.g. moved into the trash or rejected by a scan
+    TagDelete,
+    /// All nodes of a tag tree were uploaded
+    TreeComplete,
+    /// An uploaded tree node failed deferred digest verification and was quarantined
+    IntegrityFailure,
+}
+
+/// A webhook, which is notified of repository events
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    /// URL, which event payloads are `POST`ed to
+    pub url: Url,
+
+    /// Secret used to compute HMAC-SHA256 signatures of payloads
+    pub secret: String,
+
+    /// Events the webhook is subscribed to, all events if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<WebhookEvent>,
+}
+
+impl Webhook {
+    /// Returns `true` if the webhook is subscribed to `event`.
+    pub fn is_subscribed(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+/// A webhook event payload
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct WebhookPayload {
+    pub event: WebhookEvent,
+
+    /// Repository the event occurred in, e.g. `user/repo`
+    pub repository: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+
+    /// Path of the tree node the event refers to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+
+    /// Digest of the entity the event refers to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<ContentDigest>,
+}
+
+/// An attempt to deliver a webhook payload
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct WebhookAttempt {
+    /// RFC 3339 timestamp of the attempt
+    pub time: String,
+
+    /// HTTP status code of the response, if one was received
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A record of a webhook payload delivery
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct WebhookDelivery {
+    /// Unique delivery identifier, sent in `Drawbridge-Delivery` header
+    pub id: String,
+
+    pub url: Url,
+
+    pub payload: WebhookPayload,
+
+    pub attempts: Vec<WebhookAttempt>,
+
+    /// Whether the payload was successfully delivered
+    pub delivered: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_subscribed() {
+        let webhook = |events| Webhook {
+            url: "https://example.com/hook".parse().unwrap(),
+            secret: "secret".into(),
+            events,
+        };
+
+        assert!(webhook(vec![]).is_subscribed(WebhookEvent::TagCreate));
+        assert!(webhook(vec![]).is_subscribed(WebhookEvent::TreeComplete));
+        assert!(webhook(vec![WebhookEvent::TagCreate]).is_subscribed(WebhookEvent::TagCreate));
+        assert!(!webhook(vec![WebhookEvent::TagCreate]).is_subscribed(WebhookEvent::TreeComplete));
+        assert!(webhook(vec![WebhookEvent::IntegrityFailure])
+            .is_subscribed(WebhookEvent::IntegrityFailure));
+        assert!(!webhook(vec![WebhookEvent::TagCreate]).is_subscribed(WebhookEvent::TagDelete));
+    }
+
+    #[test]
+    fn serde() {
+        let webhook: Webhook = serde_json::from_str(
+            r#"{"url":"https://example.com/hook","secret":"secret","events":["tag-create"]}"#,
+        )
+        .unwrap();
+        assert_eq!(webhook.events, vec![WebhookEvent::TagCreate]);
+        let webhook: Webhook = serde_json::from_str(
+            r#"{"url":"https://example.com/hook","secret":"secret","events":["integrity-failure"]}"#,
+        )
+        .unwrap();
+        assert_eq!(webhook.events, vec![WebhookEvent::IntegrityFailure]);
+        let webhook: Webhook = serde_json::from_str(
+            r#"{"url":"https://example.com/hook","secret":"secret","events":["tag-update","tag-delete"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            webhook.events,
+            vec![WebhookEvent::TagUpdate, WebhookEvent::TagDelete]
+        );
+        assert!(serde_json::from_str::<Webhook>(
+            r#"{"url":"https://example.com/hook","secret":"secret","events":["tag-rename"]}"#,
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,176 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::Timestamp;
+
+use std::ops::AddAssign;
+use std::time::{Duration, SystemTime};
+
+use anyhow::ensure;
+use serde::{Deserialize, Serialize};
+
+fn default_interval() -> u64 {
+    24 * 60 * 60
+}
+
+/// Storage retention policy of an owner, which ages out data no longer referenced by any tag
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetentionPolicy {
+    /// Number of seconds between runs of the policy, a day by default
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+
+    /// Number of seconds since the last upload, after which complete trees of tags, which
+    /// were never published, are removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub untagged: Option<u64>,
+
+    /// Number of seconds since the last upload, after which incomplete trees of tags, i.e.
+    /// abandoned upload sessions, are removed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uploads: Option<u64>,
+
+    /// Number of seconds since deletion, after which repositories and tags in the trash are
+    /// purged, if they do not expire earlier
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trash: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// Minimum number of seconds between runs
+    pub const MIN_INTERVAL: u64 = 60;
+
+    /// Validates the policy.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        ensure!(
+            self.interval >= Self::MIN_INTERVAL,
+            "interval must be at least {} seconds",
+            Self::MIN_INTERVAL
+        );
+        Ok(())
+    }
+
+    /// Returns whether a run is due at `now`, if the policy last ran at `last`, if ever.
+    pub fn is_due(&self, last: Option<Timestamp>, now: SystemTime) -> bool {
+        last.is_none_or(|last| last.age(now) >= Duration::from_secs(self.interval))
+    }
+
+    /// Returns whether data last modified `age` ago is aged out by the rule `max_age`, if any.
+    pub fn ages_out(max_age: Option<u64>, age: Duration) -> bool {
+        max_age.is_some_and(|max_age| age > Duration::from_secs(max_age))
+    }
+}
+
+/// Number of entities and bytes of storage reclaimed
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Reclaimed {
+    pub entities: u64,
+    pub bytes: u64,
+}
+
+impl AddAssign for Reclaimed {
+    fn add_assign(&mut self, rhs: Self) {
+        self.entities += rhs.entities;
+        self.bytes += rhs.bytes;
+    }
+}
+
+/// Storage reclaimed by a [RetentionPolicy] by rule
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RetentionStats {
+    pub untagged: Reclaimed,
+    pub uploads: Reclaimed,
+    pub trash: Reclaimed,
+}
+
+impl RetentionStats {
+    /// Returns the storage reclaimed by all rules.
+    pub fn total(&self) -> Reclaimed {
+        let mut total = self.untagged;
+        total += self.uploads;
+        total += self.trash;
+        total
+    }
+}
+
+/// A run of a [RetentionPolicy]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RetentionRun {
+    pub time: Timestamp,
+
+    /// Storage actually reclaimed
+    pub reclaimed: RetentionStats,
+}
+
+/// Report of storage reclaimed by a [RetentionPolicy]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RetentionReport {
+    /// Storage, which would be reclaimed, if the policy ran now
+    pub projected: RetentionStats,
+
+    /// The last run of the policy, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last: Option<RetentionRun>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::UNIX_EPOCH;
+
+    use serde_json::json;
+
+    #[test]
+    fn policy() {
+        let policy: RetentionPolicy = serde_json::from_value(json!({ "uploads": 3600 })).unwrap();
+        assert_eq!(policy.interval, default_interval());
+        assert_eq!(policy.untagged, None);
+        policy.validate().unwrap();
+
+        let now = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        assert!(policy.is_due(None, now));
+        assert!(!policy.is_due(Some((now - Duration::from_secs(60)).into()), now));
+        assert!(policy.is_due(
+            Some((now - Duration::from_secs(default_interval())).into()),
+            now
+        ));
+
+        assert!(!RetentionPolicy::ages_out(None, Duration::MAX));
+        assert!(!RetentionPolicy::ages_out(
+            policy.uploads,
+            Duration::from_secs(3600)
+        ));
+        assert!(RetentionPolicy::ages_out(
+            policy.uploads,
+            Duration::from_secs(3601)
+        ));
+
+        let policy: RetentionPolicy = serde_json::from_value(json!({ "interval": 1 })).unwrap();
+        assert!(policy.validate().is_err());
+        assert!(serde_json::from_value::<RetentionPolicy>(json!({ "tags": 60 })).is_err());
+    }
+
+    #[test]
+    fn total() {
+        let stats = RetentionStats {
+            untagged: Reclaimed {
+                entities: 1,
+                bytes: 10,
+            },
+            uploads: Reclaimed {
+                entities: 2,
+                bytes: 20,
+            },
+            trash: Reclaimed::default(),
+        };
+        assert_eq!(
+            stats.total(),
+            Reclaimed {
+                entities: 3,
+                bytes: 30,
+            }
+        );
+    }
+}
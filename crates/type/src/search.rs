@@ -0,0 +1,210 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{Annotations, RepositoryContext, TagName};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::str::FromStr;
+
+use anyhow::{bail, ensure};
+use serde::{Deserialize, Serialize};
+
+/// Score of a term equal to the repository name
+const NAME_EXACT: u32 = 10;
+
+/// Score of a term contained in the repository name
+const NAME: u32 = 5;
+
+/// Score of a term contained in the name of the repository owner
+const OWNER: u32 = 3;
+
+/// Score of a term contained in the repository description
+const DESCRIPTION: u32 = 2;
+
+/// Score of a term contained in a tag name
+const TAG: u32 = 2;
+
+/// Score of a term contained in an annotation key or value of a tag
+const ANNOTATION: u32 = 1;
+
+/// A repository as indexed for search
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchDocument {
+    pub repository: RepositoryContext,
+
+    pub description: Option<String>,
+
+    /// Published tags along with the annotations of their tree roots
+    pub tags: BTreeMap<TagName, Annotations>,
+}
+
+/// A search query, i.e. case-insensitive terms separated by whitespace, all of which must be
+/// found in a repository for it to match
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SearchQuery(Vec<String>);
+
+impl SearchQuery {
+    /// Maximum number of terms of a query
+    pub const MAX_TERMS: usize = 16;
+
+    /// Maximum length of a term in bytes
+    pub const MAX_TERM_LENGTH: usize = 128;
+
+    /// Returns the hit of `doc`, if it matches the query.
+    ///
+    /// Every term contributes the sum of the scores of the parts of the repository it is found
+    /// in, where only the tag with the highest score counts, so that repositories with many
+    /// tags do not dominate the results.
+    pub fn score(&self, doc: &SearchDocument) -> Option<SearchHit> {
+        let name = doc.repository.name.to_string().to_lowercase();
+        let owner = doc.repository.owner.to_string().to_lowercase();
+        let description = doc.description.as_deref().unwrap_or("").to_lowercase();
+        let mut score = 0;
+        let mut tags = BTreeSet::new();
+        for term in &self.0 {
+            let mut term_score = 0;
+            if name == *term {
+                term_score += NAME_EXACT;
+            } else if name.contains(term) {
+                term_score += NAME;
+            }
+            if owner.contains(term) {
+                term_score += OWNER;
+            }
+            if description.contains(term) {
+                term_score += DESCRIPTION;
+            }
+            let mut best_tag = 0;
+            for (tag, annotations) in &doc.tags {
+                let mut tag_score = 0;
+                if tag.to_string().to_lowercase().contains(term) {
+                    tag_score += TAG;
+                }
+                if annotations
+                    .iter()
+                    .any(|(k, v)| k.contains(term) || v.to_lowercase().contains(term))
+                {
+                    tag_score += ANNOTATION;
+                }
+                if tag_score > 0 {
+                    _ = tags.insert(tag.clone());
+                    best_tag = best_tag.max(tag_score);
+                }
+            }
+            term_score += best_tag;
+            if term_score == 0 {
+                return None;
+            }
+            score += term_score;
+        }
+        Some(SearchHit {
+            repository: doc.repository.to_string(),
+            description: doc.description.clone(),
+            tags: tags.into_iter().collect(),
+            score,
+        })
+    }
+}
+
+impl FromStr for SearchQuery {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let terms: Vec<_> = s.split_whitespace().map(str::to_lowercase).collect();
+        if terms.is_empty() {
+            bail!("empty search query")
+        }
+        ensure!(
+            terms.len() <= Self::MAX_TERMS,
+            "search query exceeds {} terms",
+            Self::MAX_TERMS
+        );
+        if let Some(term) = terms.iter().find(|t| t.len() > Self::MAX_TERM_LENGTH) {
+            bail!(
+                "search term `{term}` exceeds {} bytes",
+                Self::MAX_TERM_LENGTH
+            )
+        }
+        Ok(Self(terms))
+    }
+}
+
+/// A repository matching a [SearchQuery]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SearchHit {
+    /// The repository in `owner/name` form
+    pub repository: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Tags matching any of the terms
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<TagName>,
+
+    /// Relevance of the repository, higher is more relevant
+    pub score: u32,
+}
+
+/// A page of [SearchHit]s ordered by descending relevance
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SearchResults {
+    pub hits: Vec<SearchHit>,
+
+    /// Total number of hits
+    pub total: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_str() {
+        assert_eq!(
+            "Web  server\t".parse::<SearchQuery>().unwrap(),
+            SearchQuery(vec!["web".into(), "server".into()])
+        );
+        assert!("".parse::<SearchQuery>().is_err());
+        assert!(" ".parse::<SearchQuery>().is_err());
+        assert!(["a"; 17].join(" ").parse::<SearchQuery>().is_err());
+        assert!("a".repeat(129).parse::<SearchQuery>().is_err());
+    }
+
+    #[test]
+    fn score() {
+        let doc = SearchDocument {
+            repository: "acme/nginx".parse().unwrap(),
+            description: Some("A web server".into()),
+            tags: BTreeMap::from([
+                (
+                    "1.0.0".parse().unwrap(),
+                    Annotations::try_from(BTreeMap::from([(
+                        "org.example.os".into(),
+                        "Linux".into(),
+                    )]))
+                    .unwrap(),
+                ),
+                ("2.0.0".parse().unwrap(), Annotations::default()),
+            ]),
+        };
+        let score = |q: &str| q.parse::<SearchQuery>().unwrap().score(&doc);
+
+        let hit = score("nginx").unwrap();
+        assert_eq!(hit.repository, "acme/nginx");
+        assert_eq!(hit.score, NAME_EXACT);
+        assert!(hit.tags.is_empty());
+        assert_eq!(score("ngin").unwrap().score, NAME);
+        assert_eq!(score("ACME Web").unwrap().score, OWNER + DESCRIPTION);
+
+        let hit = score("linux").unwrap();
+        assert_eq!(hit.score, ANNOTATION);
+        assert_eq!(hit.tags, vec!["1.0.0".parse().unwrap()]);
+        let hit = score("0.0").unwrap();
+        assert_eq!(hit.score, TAG);
+        assert_eq!(hit.tags.len(), 2);
+
+        assert!(score("apache").is_none());
+        assert!(score("nginx apache").is_none());
+    }
+}
@@ -0,0 +1,167 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! The subset of [CBOR](https://www.rfc-editor.org/rfc/rfc8949) used by COSE structures.
+
+use anyhow::{bail, ensure, Context};
+
+/// Maximum nesting depth of decoded values
+const MAX_DEPTH: usize = 8;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(super) enum Value {
+    Integer(i64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Value>),
+    Map(Vec<(Value, Value)>),
+    Tag(u64, Box<Value>),
+    Null,
+}
+
+fn head(buf: &mut Vec<u8>, major: u8, n: u64) {
+    let major = major << 5;
+    match n {
+        0..=23 => buf.push(major | n as u8),
+        24..=0xff => buf.extend([major | 24, n as u8]),
+        0x100..=0xffff => {
+            buf.push(major | 25);
+            buf.extend((n as u16).to_be_bytes())
+        }
+        0x1_0000..=0xffff_ffff => {
+            buf.push(major | 26);
+            buf.extend((n as u32).to_be_bytes())
+        }
+        _ => {
+            buf.push(major | 27);
+            buf.extend(n.to_be_bytes())
+        }
+    }
+}
+
+fn encode_into(buf: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Integer(n) if *n >= 0 => head(buf, 0, *n as u64),
+        Value::Integer(n) => head(buf, 1, !*n as u64),
+        Value::Bytes(b) => {
+            head(buf, 2, b.len() as u64);
+            buf.extend(b)
+        }
+        Value::Text(s) => {
+            head(buf, 3, s.len() as u64);
+            buf.extend(s.as_bytes())
+        }
+        Value::Array(items) => {
+            head(buf, 4, items.len() as u64);
+            items.iter().for_each(|item| encode_into(buf, item))
+        }
+        Value::Map(entries) => {
+            head(buf, 5, entries.len() as u64);
+            entries.iter().for_each(|(k, v)| {
+                encode_into(buf, k);
+                encode_into(buf, v)
+            })
+        }
+        Value::Tag(tag, value) => {
+            head(buf, 6, *tag);
+            encode_into(buf, value)
+        }
+        Value::Null => buf.push(0xf6),
+    }
+}
+
+/// Encodes `value` using definite lengths and the shortest form of integers.
+pub(super) fn encode(value: &Value) -> Vec<u8> {
+    let mut buf = vec![];
+    encode_into(&mut buf, value);
+    buf
+}
+
+struct Decoder<'a>(&'a [u8]);
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, n: usize) -> anyhow::Result<&'a [u8]> {
+        ensure!(n <= self.0.len(), "unexpected end of CBOR input");
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn argument(&mut self, info: u8) -> anyhow::Result<u64> {
+        let n = match info {
+            0..=23 => info as u64,
+            24 => self.take(1)?[0] as u64,
+            25 => u16::from_be_bytes(self.take(2)?.try_into()?) as u64,
+            26 => u32::from_be_bytes(self.take(4)?.try_into()?) as u64,
+            27 => u64::from_be_bytes(self.take(8)?.try_into()?),
+            _ => bail!("unsupported CBOR argument encoding"),
+        };
+        Ok(n)
+    }
+
+    fn length(&mut self, info: u8) -> anyhow::Result<usize> {
+        let n = self.argument(info)?;
+        // NOTE: Every item occupies at least a byte, which bounds preallocations.
+        ensure!(n <= self.0.len() as u64, "CBOR length exceeds input");
+        Ok(n as usize)
+    }
+
+    fn value(&mut self, depth: usize) -> anyhow::Result<Value> {
+        ensure!(depth < MAX_DEPTH, "CBOR input nested too deeply");
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        let value = match major {
+            0 => Value::Integer(
+                self.argument(info)?
+                    .try_into()
+                    .context("CBOR integer out of range")?,
+            ),
+            1 => Value::Integer(
+                !i64::try_from(self.argument(info)?).context("CBOR integer out of range")?,
+            ),
+            2 => {
+                let n = self.length(info)?;
+                Value::Bytes(self.take(n)?.to_vec())
+            }
+            3 => {
+                let n = self.length(info)?;
+                Value::Text(
+                    std::str::from_utf8(self.take(n)?)
+                        .context("invalid UTF-8 in CBOR text")?
+                        .into(),
+                )
+            }
+            4 => {
+                let n = self.length(info)?;
+                Value::Array(
+                    (0..n)
+                        .map(|_| self.value(depth + 1))
+                        .collect::<anyhow::Result<_>>()?,
+                )
+            }
+            5 => {
+                let n = self.length(info)?;
+                Value::Map(
+                    (0..n)
+                        .map(|_| Ok((self.value(depth + 1)?, self.value(depth + 1)?)))
+                        .collect::<anyhow::Result<_>>()?,
+                )
+            }
+            6 => {
+                let tag = self.argument(info)?;
+                Value::Tag(tag, Box::new(self.value(depth + 1)?))
+            }
+            7 if info == 22 => Value::Null,
+            _ => bail!("unsupported CBOR item"),
+        };
+        Ok(value)
+    }
+}
+
+/// Decodes a single value spanning all of `buf`.
+pub(super) fn decode(buf: &[u8]) -> anyhow::Result<Value> {
+    let mut decoder = Decoder(buf);
+    let value = decoder.value(0)?;
+    ensure!(decoder.0.is_empty(), "trailing bytes after CBOR value");
+    Ok(value)
+}
@@ -0,0 +1,635 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod cbor;
+
+use cbor::Value;
+
+use super::digest::{Algorithms, ContentDigest};
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{bail, ensure, Context};
+use drawbridge_byte::Bytes;
+use drawbridge_jose::b64::Json;
+use drawbridge_jose::jws::{self, Flattened, General, Jws, Parameters};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// COSE header label of the algorithm
+const COSE_ALG: i64 = 1;
+
+/// COSE header label of the key ID
+const COSE_KID: i64 = 4;
+
+/// COSE header label of the X.509 certificate chain
+const COSE_X5CHAIN: i64 = 33;
+
+/// CBOR tag of a `COSE_Sign` structure
+const COSE_SIGN_TAG: u64 = 98;
+
+/// An algorithm a [Signature] is produced with
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum SignatureAlgorithm {
+    /// ECDSA using P-256 and SHA-256
+    Es256,
+    /// ECDSA using P-384 and SHA-384
+    Es384,
+    /// EdDSA using Ed25519
+    EdDsa,
+}
+
+impl SignatureAlgorithm {
+    /// Returns the identifier of the algorithm in COSE.
+    pub fn cose(&self) -> i64 {
+        match self {
+            Self::Es256 => -7,
+            Self::Es384 => -35,
+            Self::EdDsa => -8,
+        }
+    }
+
+    /// Returns the algorithm identified by `id` in COSE, if supported.
+    pub fn from_cose(id: i64) -> Option<Self> {
+        [Self::Es256, Self::Es384, Self::EdDsa]
+            .into_iter()
+            .find(|alg| alg.cose() == id)
+    }
+}
+
+impl AsRef<str> for SignatureAlgorithm {
+    fn as_ref(&self) -> &str {
+        match self {
+            Self::Es256 => "ES256",
+            Self::Es384 => "ES384",
+            Self::EdDsa => "EdDSA",
+        }
+    }
+}
+
+impl Display for SignatureAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_ref())
+    }
+}
+
+impl FromStr for SignatureAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ES256" => Ok(Self::Es256),
+            "ES384" => Ok(Self::Es384),
+            "EdDSA" => Ok(Self::EdDsa),
+            _ => bail!("unsupported signature algorithm `{s}`"),
+        }
+    }
+}
+
+impl Serialize for SignatureAlgorithm {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_ref())
+    }
+}
+
+impl<'de> Deserialize<'de> for SignatureAlgorithm {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
+/// The envelope format a [Signature] is produced for, which determines the signed input
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureFormat {
+    /// [JWS](https://www.rfc-editor.org/rfc/rfc7515) using the general JSON serialization
+    Jws,
+    /// [COSE_Sign](https://www.rfc-editor.org/rfc/rfc9052) structure
+    Cose,
+}
+
+/// A signature of a detached payload, e.g. an encoded tag entry
+///
+/// The signature covers the signing input of its [SignatureFormat], which binds the algorithm,
+/// key ID and certificate chain as protected header parameters. The raw signature is encoded as
+/// specified for the algorithm by JOSE and COSE, i.e. ECDSA signatures are the concatenation of
+/// `r` and `s`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Signature {
+    #[serde(rename = "alg")]
+    pub algorithm: SignatureAlgorithm,
+
+    #[serde(rename = "kid", default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+
+    /// Digest of the signed payload
+    #[serde(rename = "digest")]
+    pub payload_digest: ContentDigest,
+
+    pub format: SignatureFormat,
+
+    pub signature: Bytes<Vec<u8>>,
+
+    /// ASN.1 DER-encoded X.509 certificate chain of the key, leaf first, if any
+    #[serde(rename = "x5c", default, skip_serializing_if = "Vec::is_empty")]
+    pub certificates: Vec<Bytes<Vec<u8>>>,
+}
+
+/// Computes the digest of a payload.
+fn payload_digest(payload: &[u8]) -> anyhow::Result<ContentDigest> {
+    let (_, digest) = Algorithms::default()
+        .read_sync(payload)
+        .context("failed to compute payload digest")?;
+    Ok(digest)
+}
+
+impl Signature {
+    fn jws_parameters(&self) -> Parameters {
+        Parameters {
+            alg: Some(self.algorithm.to_string()),
+            kid: self.key_id.clone(),
+            x5c: (!self.certificates.is_empty()).then(|| self.certificates.clone()),
+            ..Default::default()
+        }
+    }
+
+    fn cose_protected(&self) -> Vec<u8> {
+        let mut header = vec![(
+            Value::Integer(COSE_ALG),
+            Value::Integer(self.algorithm.cose()),
+        )];
+        if let Some(ref kid) = self.key_id {
+            header.push((
+                Value::Integer(COSE_KID),
+                Value::Bytes(kid.as_bytes().into()),
+            ));
+        }
+        match self.certificates.as_slice() {
+            [] => {}
+            [cert] => header.push((Value::Integer(COSE_X5CHAIN), Value::Bytes(cert.to_vec()))),
+            certs => header.push((
+                Value::Integer(COSE_X5CHAIN),
+                Value::Array(
+                    certs
+                        .iter()
+                        .map(|cert| Value::Bytes(cert.to_vec()))
+                        .collect(),
+                ),
+            )),
+        }
+        cbor::encode(&Value::Map(header))
+    }
+
+    /// Returns the input signed by the signature over `payload`.
+    ///
+    /// The raw signature is not part of the input, so signers may leave it empty until the
+    /// input is signed. Fails if `payload` does not match the payload digest.
+    pub fn signing_input(&self, payload: &[u8]) -> anyhow::Result<Vec<u8>> {
+        ensure!(
+            payload_digest(payload)? == self.payload_digest,
+            "payload does not match signature digest"
+        );
+        let input = match self.format {
+            SignatureFormat::Jws => {
+                let protected = serde_json::to_vec(&self.jws_parameters())?;
+                format!(
+                    "{}.{}",
+                    base64::encode_config(protected, base64::URL_SAFE_NO_PAD),
+                    base64::encode_config(payload, base64::URL_SAFE_NO_PAD)
+                )
+                .into_bytes()
+            }
+            SignatureFormat::Cose => cbor::encode(&Value::Array(vec![
+                Value::Text("Signature".into()),
+                Value::Bytes(vec![]),
+                Value::Bytes(self.cose_protected()),
+                Value::Bytes(vec![]),
+                Value::Bytes(payload.into()),
+            ])),
+        };
+        Ok(input)
+    }
+}
+
+/// Signatures of the same payload, which can be serialized as JWS or COSE
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SignatureBundle {
+    pub signatures: Vec<Signature>,
+}
+
+impl SignatureBundle {
+    /// Returns the digest of the signed payload, if there are any signatures.
+    pub fn payload_digest(&self) -> Option<&ContentDigest> {
+        self.signatures.first().map(|sig| &sig.payload_digest)
+    }
+
+    /// Returns the signatures produced by the key `kid`.
+    pub fn by_key<'a>(&'a self, kid: &'a str) -> impl Iterator<Item = &'a Signature> {
+        self.signatures
+            .iter()
+            .filter(move |sig| sig.key_id.as_deref() == Some(kid))
+    }
+
+    /// Ensures that there are signatures, all of `format` and of `payload`, if specified.
+    fn validate(&self, format: SignatureFormat, payload: Option<&[u8]>) -> anyhow::Result<()> {
+        let digest = self.payload_digest().context("no signatures")?;
+        ensure!(
+            self.signatures.iter().all(|sig| sig.format == format),
+            "signatures must all be produced for {format:?}"
+        );
+        ensure!(
+            self.signatures
+                .iter()
+                .all(|sig| &sig.payload_digest == digest),
+            "signatures must all be of the same payload"
+        );
+        if let Some(payload) = payload {
+            ensure!(
+                &payload_digest(payload)? == digest,
+                "payload does not match signature digest"
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the payload attached to an encoding, if any, or the detached `payload`.
+    fn payload<'a>(
+        attached: Option<&'a [u8]>,
+        detached: Option<&'a [u8]>,
+    ) -> anyhow::Result<&'a [u8]> {
+        match (attached, detached) {
+            (Some(attached), Some(detached)) if attached != detached => {
+                bail!("attached payload differs from detached payload")
+            }
+            (Some(payload), _) | (None, Some(payload)) => Ok(payload),
+            (None, None) => bail!("payload is neither attached nor specified"),
+        }
+    }
+
+    /// Serializes the signatures as JWS, attaching `payload`, if specified.
+    pub fn to_jws(&self, payload: Option<&[u8]>) -> anyhow::Result<Jws> {
+        self.validate(SignatureFormat::Jws, payload)?;
+        Ok(Jws::General(General {
+            payload: payload.map(|payload| payload.to_vec().into()),
+            signatures: self
+                .signatures
+                .iter()
+                .map(|sig| jws::Signature {
+                    protected: Some(Json(sig.jws_parameters())),
+                    header: None,
+                    signature: sig.signature.to_vec().into(),
+                })
+                .collect(),
+        }))
+    }
+
+    /// Parses the signatures of a JWS of either the attached or the detached `payload`.
+    ///
+    /// All parameters must be protected and only the algorithm, key ID and certificate chain
+    /// parameters are supported.
+    pub fn from_jws(jws: &Jws, payload: Option<&[u8]>) -> anyhow::Result<Self> {
+        let (attached, signatures) = match jws {
+            Jws::General(General {
+                payload,
+                signatures,
+            }) => (payload, signatures.iter().collect::<Vec<_>>()),
+            Jws::Flattened(Flattened { payload, signature }) => (payload, vec![signature]),
+        };
+        let digest = payload_digest(Self::payload(
+            attached.as_deref().map(Vec::as_slice),
+            payload,
+        )?)?;
+        let signatures = signatures
+            .into_iter()
+            .map(|sig| {
+                ensure!(
+                    sig.header.is_none(),
+                    "unprotected JWS header is not supported"
+                );
+                let protected = sig
+                    .protected
+                    .as_ref()
+                    .context("JWS protected header missing")?;
+                let signature = Signature {
+                    algorithm: protected
+                        .alg
+                        .as_deref()
+                        .context("JWS algorithm missing")?
+                        .parse()?,
+                    key_id: protected.kid.clone(),
+                    payload_digest: digest.clone(),
+                    format: SignatureFormat::Jws,
+                    signature: sig.signature.to_vec().into(),
+                    certificates: protected.x5c.clone().unwrap_or_default(),
+                };
+                ensure!(
+                    signature.jws_parameters() == **protected,
+                    "unsupported JWS header parameters"
+                );
+                Ok(signature)
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let bundle = Self { signatures };
+        bundle.validate(SignatureFormat::Jws, None)?;
+        Ok(bundle)
+    }
+
+    /// Serializes the signatures as a tagged `COSE_Sign` structure, attaching `payload`, if
+    /// specified.
+    pub fn to_cose(&self, payload: Option<&[u8]>) -> anyhow::Result<Vec<u8>> {
+        self.validate(SignatureFormat::Cose, payload)?;
+        Ok(cbor::encode(&Value::Tag(
+            COSE_SIGN_TAG,
+            Box::new(Value::Array(vec![
+                Value::Bytes(vec![]),
+                Value::Map(vec![]),
+                payload.map_or(Value::Null, |payload| Value::Bytes(payload.into())),
+                Value::Array(
+                    self.signatures
+                        .iter()
+                        .map(|sig| {
+                            Value::Array(vec![
+                                Value::Bytes(sig.cose_protected()),
+                                Value::Map(vec![]),
+                                Value::Bytes(sig.signature.to_vec()),
+                            ])
+                        })
+                        .collect(),
+                ),
+            ])),
+        )))
+    }
+
+    /// Parses the signatures of a, optionally tagged, `COSE_Sign` structure of either the
+    /// attached or the detached `payload`.
+    ///
+    /// All header parameters must be protected and only the algorithm, key ID and certificate
+    /// chain parameters are supported.
+    pub fn from_cose(buf: &[u8], payload: Option<&[u8]>) -> anyhow::Result<Self> {
+        let value = match cbor::decode(buf)? {
+            Value::Tag(COSE_SIGN_TAG, value) => *value,
+            Value::Tag(tag, _) => bail!("unexpected CBOR tag {tag}, expected COSE_Sign"),
+            value => value,
+        };
+        let (attached, signatures) = match value {
+            Value::Array(items) => match items.as_slice() {
+                [Value::Bytes(protected), Value::Map(unprotected), attached, Value::Array(signatures)]
+                    if protected.is_empty() && unprotected.is_empty() =>
+                {
+                    let attached = match attached {
+                        Value::Bytes(payload) => Some(payload.clone()),
+                        Value::Null => None,
+                        _ => bail!("invalid COSE_Sign payload"),
+                    };
+                    (attached, signatures.clone())
+                }
+                _ => bail!("invalid or unsupported COSE_Sign structure"),
+            },
+            _ => bail!("invalid COSE_Sign structure"),
+        };
+        let digest = payload_digest(Self::payload(attached.as_deref(), payload)?)?;
+        let signatures = signatures
+            .into_iter()
+            .map(|sig| {
+                let (protected, signature) = match sig {
+                    Value::Array(items) => match items.as_slice() {
+                        [Value::Bytes(protected), Value::Map(unprotected), Value::Bytes(signature)]
+                            if unprotected.is_empty() =>
+                        {
+                            (protected.clone(), signature.clone())
+                        }
+                        _ => bail!("invalid or unsupported COSE_Signature structure"),
+                    },
+                    _ => bail!("invalid COSE_Signature structure"),
+                };
+                let header = match cbor::decode(&protected)? {
+                    Value::Map(header) => header,
+                    _ => bail!("invalid COSE protected header"),
+                };
+                let mut signature = Signature {
+                    algorithm: SignatureAlgorithm::Es256,
+                    key_id: None,
+                    payload_digest: digest.clone(),
+                    format: SignatureFormat::Cose,
+                    signature: signature.into(),
+                    certificates: vec![],
+                };
+                let mut algorithm = None;
+                for (label, value) in header {
+                    match (label, value) {
+                        (Value::Integer(COSE_ALG), Value::Integer(id)) => {
+                            algorithm =
+                                Some(SignatureAlgorithm::from_cose(id).with_context(|| {
+                                    format!("unsupported COSE signature algorithm {id}")
+                                })?)
+                        }
+                        (Value::Integer(COSE_KID), Value::Bytes(kid)) => {
+                            signature.key_id =
+                                Some(String::from_utf8(kid).context("non-UTF-8 COSE key ID")?)
+                        }
+                        (Value::Integer(COSE_X5CHAIN), Value::Bytes(cert)) => {
+                            signature.certificates = vec![cert.into()]
+                        }
+                        (Value::Integer(COSE_X5CHAIN), Value::Array(certs)) => {
+                            signature.certificates = certs
+                                .into_iter()
+                                .map(|cert| match cert {
+                                    Value::Bytes(cert) => Ok(cert.into()),
+                                    _ => bail!("invalid COSE certificate chain"),
+                                })
+                                .collect::<anyhow::Result<_>>()?
+                        }
+                        _ => bail!("unsupported COSE header parameter"),
+                    }
+                }
+                signature.algorithm = algorithm.context("COSE algorithm missing")?;
+                ensure!(
+                    signature.cose_protected() == protected,
+                    "non-canonical COSE protected header"
+                );
+                Ok(signature)
+            })
+            .collect::<anyhow::Result<_>>()?;
+        let bundle = Self { signatures };
+        bundle.validate(SignatureFormat::Cose, None)?;
+        Ok(bundle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    const PAYLOAD: &[u8] = br#"{"digest":{"sha-256":"LCa0a2j/xo/5m0U8HTBBNBNCLXBkg7+g+YpeiGJm564="},"length":3,"type":"text/plain"}"#;
+
+    fn signature(format: SignatureFormat, key_id: &str, certificates: usize) -> Signature {
+        Signature {
+            algorithm: SignatureAlgorithm::Es256,
+            key_id: Some(key_id.into()),
+            payload_digest: payload_digest(PAYLOAD).unwrap(),
+            format,
+            signature: vec![0x2a; 64].into(),
+            certificates: (0..certificates).map(|i| vec![i as u8; 8].into()).collect(),
+        }
+    }
+
+    #[test]
+    fn algorithm() {
+        for alg in [
+            SignatureAlgorithm::Es256,
+            SignatureAlgorithm::Es384,
+            SignatureAlgorithm::EdDsa,
+        ] {
+            assert_eq!(alg.to_string().parse::<SignatureAlgorithm>().unwrap(), alg);
+            assert_eq!(SignatureAlgorithm::from_cose(alg.cose()), Some(alg));
+        }
+        assert!("RS256".parse::<SignatureAlgorithm>().is_err());
+        assert_eq!(SignatureAlgorithm::from_cose(-257), None);
+    }
+
+    #[test]
+    fn serialization() {
+        let sig = signature(SignatureFormat::Jws, "release", 1);
+        let value = serde_json::to_value(&sig).unwrap();
+        assert_eq!(value["alg"], json!("ES256"));
+        assert_eq!(value["kid"], json!("release"));
+        assert_eq!(value["format"], json!("jws"));
+        assert_eq!(value["x5c"], json!(["AAAAAAAAAAA="]));
+        assert_eq!(serde_json::from_value::<Signature>(value).unwrap(), sig);
+
+        let sig = Signature {
+            key_id: None,
+            certificates: vec![],
+            ..sig
+        };
+        let value = serde_json::to_value(&sig).unwrap();
+        assert!(value.get("kid").is_none());
+        assert!(value.get("x5c").is_none());
+    }
+
+    #[test]
+    fn jws() {
+        let bundle = SignatureBundle {
+            signatures: vec![
+                signature(SignatureFormat::Jws, "a", 0),
+                signature(SignatureFormat::Jws, "b", 2),
+            ],
+        };
+        assert_eq!(bundle.by_key("b").count(), 1);
+
+        let jws = bundle.to_jws(None).unwrap();
+        assert!(SignatureBundle::from_jws(&jws, None).is_err());
+        // NOTE: Detached payloads are only bound to signatures by verifying them.
+        assert_ne!(
+            SignatureBundle::from_jws(&jws, Some(b"other")).unwrap(),
+            bundle
+        );
+        assert_eq!(
+            SignatureBundle::from_jws(&jws, Some(PAYLOAD)).unwrap(),
+            bundle
+        );
+
+        let jws = bundle.to_jws(Some(PAYLOAD)).unwrap();
+        let jws: Jws = serde_json::from_slice(&serde_json::to_vec(&jws).unwrap()).unwrap();
+        assert_eq!(SignatureBundle::from_jws(&jws, None).unwrap(), bundle);
+
+        // NOTE: The signing input of a JWS is reconstructed from its protected header.
+        let Jws::General(ref general) = jws else {
+            panic!("expected general JWS")
+        };
+        let input = bundle.signatures[0].signing_input(PAYLOAD).unwrap();
+        let (protected, payload) = std::str::from_utf8(&input)
+            .unwrap()
+            .split_once('.')
+            .unwrap();
+        assert_eq!(
+            protected,
+            serde_json::to_value(&general.signatures[0].protected)
+                .unwrap()
+                .as_str()
+                .unwrap()
+        );
+        assert_eq!(
+            payload,
+            base64::encode_config(PAYLOAD, base64::URL_SAFE_NO_PAD)
+        );
+        assert!(bundle.signatures[0].signing_input(b"other").is_err());
+
+        assert!(bundle.to_jws(Some(b"other")).is_err());
+        assert!(SignatureBundle::default().to_jws(None).is_err());
+        assert!(SignatureBundle {
+            signatures: vec![signature(SignatureFormat::Cose, "a", 0)]
+        }
+        .to_jws(None)
+        .is_err());
+
+        let jws: Jws = serde_json::from_value(json!({
+            "payload": "e30",
+            "protected": "eyJhbGciOiJSUzI1NiJ9",
+            "signature": "AAAA",
+        }))
+        .unwrap();
+        assert!(SignatureBundle::from_jws(&jws, None).is_err());
+    }
+
+    #[test]
+    fn cose() {
+        let bundle = SignatureBundle {
+            signatures: vec![
+                signature(SignatureFormat::Cose, "a", 0),
+                signature(SignatureFormat::Cose, "b", 1),
+                signature(SignatureFormat::Cose, "c", 2),
+            ],
+        };
+        let buf = bundle.to_cose(None).unwrap();
+        assert_eq!(&buf[..2], &[0xd8, 0x62]);
+        assert!(SignatureBundle::from_cose(&buf, None).is_err());
+        assert_eq!(
+            SignatureBundle::from_cose(&buf, Some(PAYLOAD)).unwrap(),
+            bundle
+        );
+
+        let buf = bundle.to_cose(Some(PAYLOAD)).unwrap();
+        assert_eq!(SignatureBundle::from_cose(&buf, None).unwrap(), bundle);
+        assert!(SignatureBundle::from_cose(&buf, Some(b"other")).is_err());
+        assert!(SignatureBundle::from_cose(&buf[..buf.len() - 1], None).is_err());
+        assert!(SignatureBundle::from_cose(&[buf.as_slice(), &[0]].concat(), None).is_err());
+
+        // NOTE: Sig_structure of the first signature, see RFC 9052, section 4.4.
+        let input = bundle.signatures[0].signing_input(PAYLOAD).unwrap();
+        let expected = [
+            &[0x85, 0x69][..],
+            b"Signature",
+            &[
+                0x40,
+                0x46,
+                0xa2,
+                0x01,
+                0x26,
+                0x04,
+                0x41,
+                b'a',
+                0x40,
+                0x58,
+                PAYLOAD.len() as u8,
+            ],
+            PAYLOAD,
+        ]
+        .concat();
+        assert_eq!(input, expected);
+
+        assert!(SignatureBundle {
+            signatures: vec![signature(SignatureFormat::Jws, "a", 0)]
+        }
+        .to_cose(None)
+        .is_err());
+    }
+}
@@ -0,0 +1,91 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::digest::{hex, ContentDigest};
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+/// An in-toto attestation statement, e.g. SLSA provenance
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Attestation {
+    #[serde(rename = "_type")]
+    pub kind: String,
+
+    pub subject: Vec<Subject>,
+
+    #[serde(rename = "predicateType")]
+    pub predicate_type: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub predicate: Option<serde_json::Value>,
+}
+
+impl Attestation {
+    pub const TYPE: &'static str = "application/vnd.in-toto+json";
+
+    /// Returns `true` if any of the attestation subjects matches `hash`.
+    pub fn matches(&self, hash: &ContentDigest) -> bool {
+        self.subject.iter().any(|subject| subject.matches(hash))
+    }
+}
+
+/// A subject of an in-toto attestation statement
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Subject {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+
+    /// Hex-encoded digests indexed by in-toto algorithm name, e.g. `sha256`
+    pub digest: BTreeMap<String, String>,
+}
+
+impl Subject {
+    /// Returns `true` if any of the subject digests is equal to the respective value in `hash`.
+    pub fn matches(&self, hash: &ContentDigest) -> bool {
+        hash.iter().any(|(algo, value)| {
+            let hex = hex(value);
+            self.digest
+                .get(&algo.as_ref().replace('-', ""))
+                .is_some_and(|v| v.eq_ignore_ascii_case(&hex))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches() {
+        let hash: ContentDigest = "sha-256=:LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=:"
+            .parse()
+            .unwrap();
+        let subject = |alg: &str, hex: &str| Subject {
+            name: "".into(),
+            digest: BTreeMap::from([(alg.into(), hex.into())]),
+        };
+
+        assert!(subject(
+            "sha256",
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        )
+        .matches(&hash));
+        assert!(subject(
+            "sha256",
+            "2CF24DBA5FB0A30E26E83B2AC5B9E29E1B161E5C1FA7425E73043362938B9824"
+        )
+        .matches(&hash));
+        assert!(!subject(
+            "sha512",
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        )
+        .matches(&hash));
+        assert!(!subject(
+            "sha256",
+            "0000000000000000000000000000000000000000000000000000000000000000"
+        )
+        .matches(&hash));
+    }
+}
@@ -1,15 +1,190 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::super::TreeEntry;
+use super::super::{MediaType, TreeEntry};
+use super::Name;
 
-use drawbridge_jose::jws::Jws;
+use std::fmt::Display;
+use std::str::FromStr;
 
+use anyhow::{anyhow, bail, Context as _};
+use drawbridge_jose::jws::Jws;
+use drawbridge_jose::MediaTyped;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
+/// Kind of a tag [Entry], which is identified by the media type of the entry
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// A tree entry referencing the root of the tree of the tag
+    Unsigned,
+    /// A JWS carrying signatures of the tree entry referencing the root of the tree of the tag
+    Signed,
+    /// A reference to another tag of the same repository
+    Alias,
+}
+
+impl Kind {
+    /// Returns the kind of entries of media type `mime`, if it is one of a tag entry.
+    pub fn of(mime: &str) -> Option<Self> {
+        match mime {
+            TreeEntry::<()>::TYPE => Some(Self::Unsigned),
+            Jws::TYPE => Some(Self::Signed),
+            Alias::TYPE => Some(Self::Alias),
+            _ => None,
+        }
+    }
+
+    /// Returns the media type of entries of the kind.
+    pub fn media_type(self) -> MediaType {
+        match self {
+            Self::Unsigned => MediaType::TAG,
+            Self::Signed => MediaType::SIGNED_TAG,
+            Self::Alias => MediaType::TAG_ALIAS,
+        }
+    }
+}
+
+impl Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Unsigned => "unsigned",
+            Self::Signed => "signed",
+            Self::Alias => "alias",
+        })
+    }
+}
+
+impl FromStr for Kind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unsigned" => Ok(Self::Unsigned),
+            "signed" => Ok(Self::Signed),
+            "alias" => Ok(Self::Alias),
+            _ => bail!("unknown tag kind `{s}`"),
+        }
+    }
+}
+
+/// A tag referring to another tag of the same repository, e.g. `latest` to `1.2.3`
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Alias {
+    /// Name of the tag referred to
+    pub target: Name,
+}
+
+impl Alias {
+    pub const TYPE: &'static str = "application/vnd.drawbridge.alias.v1+json";
+}
+
+/// A tag entry
+///
+/// Entries are encoded as their contents only, without a discriminator, since the [Kind] is
+/// identified by the media type the entry is stored and exchanged with, see [Self::decode].
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 #[serde(untagged)]
 pub enum Entry<E = TreeEntry> {
     Signed(Jws),
     Unsigned(E),
+    Alias(Alias),
+}
+
+impl<E> Entry<E> {
+    /// Returns the kind of the entry.
+    pub fn kind(&self) -> Kind {
+        match self {
+            Self::Signed(..) => Kind::Signed,
+            Self::Unsigned(..) => Kind::Unsigned,
+            Self::Alias(..) => Kind::Alias,
+        }
+    }
+
+    /// Returns the media type of the entry.
+    pub fn media_type(&self) -> MediaType {
+        self.kind().media_type()
+    }
+}
+
+impl<E: DeserializeOwned> Entry<E> {
+    /// Decodes the JSON-encoded entry `buf` of media type `mime`.
+    pub fn decode(mime: &str, buf: &[u8]) -> anyhow::Result<Self> {
+        let kind = Kind::of(mime).ok_or_else(|| anyhow!("unsupported tag entry type `{mime}`"))?;
+        match kind {
+            Kind::Unsigned => serde_json::from_slice(buf).map(Self::Unsigned),
+            Kind::Signed => serde_json::from_slice(buf).map(Self::Signed),
+            Kind::Alias => serde_json::from_slice(buf).map(Self::Alias),
+        }
+        .with_context(|| format!("failed to decode {kind} tag entry"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::TreeDirectory;
+
+    use serde_json::json;
+
+    #[test]
+    fn kind() {
+        let entry = json!({
+            "digest": {"sha-256": "LCa0a2j/xo/5m0U8HTBBNBNCLXBkg7+g+YpeiGJm564="},
+            "length": 3,
+            "type": "text/plain",
+        });
+        let buf = serde_json::to_vec(&entry).unwrap();
+        let tag = Entry::<TreeEntry>::decode(TreeEntry::<()>::TYPE, &buf).unwrap();
+        assert_eq!(tag.kind(), Kind::Unsigned);
+        assert_eq!(tag.media_type(), MediaType::TAG);
+        assert!(Entry::<TreeEntry>::decode(Alias::TYPE, &buf).is_err());
+        assert!(Entry::<TreeEntry>::decode("application/json", &buf).is_err());
+
+        let alias = json!({ "target": "1.2.3" });
+        let buf = serde_json::to_vec(&alias).unwrap();
+        let tag = Entry::<TreeEntry>::decode(Alias::TYPE, &buf).unwrap();
+        assert_eq!(
+            tag,
+            Entry::Alias(Alias {
+                target: "1.2.3".parse().unwrap()
+            })
+        );
+        assert_eq!(tag.media_type(), MediaType::TAG_ALIAS);
+        assert_eq!(serde_json::to_value(&tag).unwrap(), alias);
+        assert_eq!(serde_json::from_value::<Entry>(alias).unwrap(), tag);
+        assert!(Entry::<TreeEntry>::decode(TreeEntry::<()>::TYPE, &buf).is_err());
+
+        for kind in [Kind::Unsigned, Kind::Signed, Kind::Alias] {
+            assert_eq!(Kind::of(kind.media_type().essence()), Some(kind));
+            assert_eq!(kind.to_string().parse::<Kind>().unwrap(), kind);
+            assert_eq!(serde_json::to_value(kind).unwrap(), json!(kind.to_string()));
+        }
+    }
+
+    #[test]
+    fn msgpack() {
+        let entry: TreeEntry = serde_json::from_value(json!({
+            "digest": {"sha-256": "LCa0a2j/xo/5m0U8HTBBNBNCLXBkg7+g+YpeiGJm564="},
+            "length": 3,
+            "type": "text/plain",
+            "annotations": { "org.example.a": "1" },
+            "custom": [true, null, -1, 1.5, "value"],
+        }))
+        .unwrap();
+
+        let tag = Entry::Unsigned(entry.clone());
+        let buf = rmp_serde::to_vec_named(&tag).unwrap();
+        assert_eq!(rmp_serde::from_slice::<Entry>(&buf).unwrap(), tag);
+
+        let dir: TreeDirectory<TreeEntry> =
+            [("file".parse().unwrap(), entry)].into_iter().collect();
+        let buf = rmp_serde::to_vec_named(&dir).unwrap();
+        assert_eq!(
+            rmp_serde::from_slice::<TreeDirectory<TreeEntry>>(&buf).unwrap(),
+            dir
+        );
+    }
 }
@@ -1,10 +1,20 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
+mod attestation;
 mod context;
 mod entry;
 mod name;
+mod sbom;
+mod signature;
+mod stats;
+mod vulnerability;
 
+pub use attestation::*;
 pub use context::*;
 pub use entry::*;
 pub use name::*;
+pub use sbom::*;
+pub use signature::*;
+pub use stats::*;
+pub use vulnerability::*;
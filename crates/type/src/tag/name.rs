@@ -1,16 +1,19 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
+use crate::Version;
+
 use std::fmt::Display;
 use std::ops::{Deref, DerefMut};
 use std::str::FromStr;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+/// A tag name, which is a [Version], so that tags are ordered by precedence.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
 #[repr(transparent)]
 #[serde(transparent)]
-pub struct Name(semver::Version);
+pub struct Name(Version);
 
 impl FromStr for Name {
     type Err = semver::Error;
@@ -20,8 +23,14 @@ impl FromStr for Name {
     }
 }
 
+impl From<Version> for Name {
+    fn from(version: Version) -> Self {
+        Self(version)
+    }
+}
+
 impl Deref for Name {
-    type Target = semver::Version;
+    type Target = Version;
 
     fn deref(&self) -> &Self::Target {
         &self.0
@@ -78,10 +87,22 @@ mod tests {
         ] {
             assert_eq!(
                 s.parse::<Name>().unwrap(),
-                Name(expected),
+                Name(expected.into()),
                 "input '{}' should succeed to parse",
                 s
             );
         }
     }
+
+    #[test]
+    fn ordering() {
+        let mut names = ["1.10.0", "1.2.0", "1.2.0-rc.1", "0.9.9"]
+            .map(|s| s.parse::<Name>().unwrap())
+            .to_vec();
+        names.sort();
+        assert_eq!(
+            names.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["0.9.9", "1.2.0-rc.1", "1.2.0", "1.10.0"]
+        );
+    }
 }
@@ -0,0 +1,117 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use anyhow::{bail, ensure, Context};
+use serde::Deserialize;
+
+/// A software bill of materials (SBOM) document format
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SbomFormat {
+    /// SPDX JSON document
+    Spdx,
+    /// CycloneDX JSON document
+    CycloneDx,
+}
+
+impl SbomFormat {
+    pub const SPDX_TYPE: &'static str = "application/spdx+json";
+    pub const CYCLONEDX_TYPE: &'static str = "application/vnd.cyclonedx+json";
+
+    /// Returns the format of SBOM documents with media type essence `mime`, if supported.
+    pub fn from_media_type(mime: &str) -> Option<Self> {
+        match mime {
+            Self::SPDX_TYPE => Some(Self::Spdx),
+            Self::CYCLONEDX_TYPE => Some(Self::CycloneDx),
+            _ => None,
+        }
+    }
+
+    /// Returns the media type of SBOM documents in this format.
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Self::Spdx => Self::SPDX_TYPE,
+            Self::CycloneDx => Self::CYCLONEDX_TYPE,
+        }
+    }
+
+    /// Validates that `buf` contains a JSON document in this format.
+    pub fn validate(&self, buf: &[u8]) -> anyhow::Result<()> {
+        match self {
+            Self::Spdx => {
+                #[derive(Deserialize)]
+                #[serde(rename_all = "camelCase")]
+                struct Document {
+                    spdx_version: String,
+                    #[serde(rename = "SPDXID")]
+                    spdx_id: String,
+                }
+
+                let doc: Document = serde_json::from_slice(buf).context("invalid SPDX document")?;
+                ensure!(
+                    doc.spdx_version.starts_with("SPDX-"),
+                    "invalid SPDX version `{}`",
+                    doc.spdx_version
+                );
+                ensure!(
+                    doc.spdx_id == "SPDXRef-DOCUMENT",
+                    "invalid SPDX document identifier `{}`",
+                    doc.spdx_id
+                );
+            }
+            Self::CycloneDx => {
+                #[derive(Deserialize)]
+                #[serde(rename_all = "camelCase")]
+                struct Document {
+                    bom_format: String,
+                    spec_version: String,
+                }
+
+                let doc: Document =
+                    serde_json::from_slice(buf).context("invalid CycloneDX document")?;
+                if doc.bom_format != "CycloneDX" {
+                    bail!("invalid CycloneDX BOM format `{}`", doc.bom_format)
+                }
+                ensure!(
+                    !doc.spec_version.is_empty(),
+                    "CycloneDX specification version must not be empty"
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        let spdx = br#"{"spdxVersion":"SPDX-2.3","SPDXID":"SPDXRef-DOCUMENT","name":"test"}"#;
+        let cyclonedx = br#"{"bomFormat":"CycloneDX","specVersion":"1.4","version":1}"#;
+
+        assert!(SbomFormat::Spdx.validate(spdx).is_ok());
+        assert!(SbomFormat::CycloneDx.validate(cyclonedx).is_ok());
+
+        assert!(SbomFormat::Spdx.validate(cyclonedx).is_err());
+        assert!(SbomFormat::CycloneDx.validate(spdx).is_err());
+        assert!(SbomFormat::Spdx
+            .validate(br#"{"spdxVersion":"2.3","SPDXID":"SPDXRef-DOCUMENT"}"#)
+            .is_err());
+        assert!(SbomFormat::CycloneDx
+            .validate(br#"{"bomFormat":"SPDX","specVersion":"1.4"}"#)
+            .is_err());
+        assert!(SbomFormat::Spdx.validate(b"not json").is_err());
+    }
+
+    #[test]
+    fn media_type() {
+        for format in [SbomFormat::Spdx, SbomFormat::CycloneDx] {
+            assert_eq!(
+                SbomFormat::from_media_type(format.media_type()),
+                Some(format)
+            );
+        }
+        assert_eq!(SbomFormat::from_media_type("application/json"), None);
+    }
+}
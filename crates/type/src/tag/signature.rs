@@ -0,0 +1,45 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use drawbridge_byte::Bytes;
+
+use serde::{Deserialize, Serialize};
+
+/// A detached signature of the tag entry contents, as produced by `cosign sign-blob`
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Signature {
+    /// ASN.1 DER-encoded ECDSA P-256 SHA-256 signature
+    pub signature: Bytes<Vec<u8>>,
+
+    /// Names of the server keys, which the signature is verified against
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<String>,
+}
+
+/// Signatures attached to a tag
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Signatures {
+    pub signatures: Vec<Signature>,
+
+    /// Whether at least one of the signatures is verified against a server key
+    pub verified: bool,
+}
+
+/// Verification status of the signatures of a tag
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Verification {
+    /// Whether the tag is a signed tag
+    pub signed: bool,
+
+    /// Names of the server keys, which verify a signature of the tag or a detached signature
+    /// attached to it
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<String>,
+
+    /// Whether the signatures satisfy the signing policy of the repository, or are verified
+    /// by any server key if there is none
+    pub verified: bool,
+}
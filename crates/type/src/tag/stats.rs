@@ -0,0 +1,57 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+
+use serde::{Deserialize, Serialize};
+
+/// Download statistics of a tag.
+///
+/// Counts are approximate, since they are updated in batches.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Stats {
+    /// Number of successful pulls of the tag
+    pub downloads: u64,
+    /// Number of successful downloads of each tree entry, keyed by path
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub entries: BTreeMap<String, u64>,
+}
+
+impl AddAssign<&Stats> for Stats {
+    fn add_assign(&mut self, rhs: &Stats) {
+        self.downloads = self.downloads.saturating_add(rhs.downloads);
+        for (path, n) in &rhs.entries {
+            let count = self.entries.entry(path.clone()).or_default();
+            *count = count.saturating_add(*n);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_assign() {
+        let mut stats = Stats {
+            downloads: 1,
+            entries: BTreeMap::from([("/".into(), 1), ("/a".into(), 2)]),
+        };
+        stats += &Stats {
+            downloads: 2,
+            entries: BTreeMap::from([("/a".into(), 3), ("/b".into(), 4)]),
+        };
+        assert_eq!(
+            stats,
+            Stats {
+                downloads: 3,
+                entries: BTreeMap::from([("/".into(), 1), ("/a".into(), 5), ("/b".into(), 4)]),
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(Stats::default()).unwrap(),
+            serde_json::json!({ "downloads": 0 })
+        );
+    }
+}
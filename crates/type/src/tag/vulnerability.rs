@@ -0,0 +1,337 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::digest::ContentDigest;
+use super::Subject;
+
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+
+/// Severity of a vulnerability finding, ordered from the least to the most severe
+#[derive(
+    Copy, Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Unknown,
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl Severity {
+    /// Returns the severity corresponding to a CVSS score, e.g. a SARIF `security-severity`.
+    pub fn from_score(score: f64) -> Self {
+        match score {
+            s if s >= 9.0 => Self::Critical,
+            s if s >= 7.0 => Self::High,
+            s if s >= 4.0 => Self::Medium,
+            s if s > 0.0 => Self::Low,
+            _ => Self::Unknown,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Unknown => "unknown",
+            Self::Low => "low",
+            Self::Medium => "medium",
+            Self::High => "high",
+            Self::Critical => "critical",
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A vulnerability found by a scanner
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Finding {
+    /// Identifier of the vulnerability, e.g. `CVE-2021-44228`
+    pub id: String,
+
+    pub severity: Severity,
+
+    /// Affected package, e.g. a package URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// A vulnerability report produced by a scanner for one or more nodes of a tag tree
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct VulnerabilityReport {
+    /// Scanned nodes identified by their digests
+    pub subject: Vec<Subject>,
+
+    /// Name of the scanner, which produced the report
+    pub scanner: String,
+
+    #[serde(default)]
+    pub findings: Vec<Finding>,
+}
+
+impl VulnerabilityReport {
+    pub const TYPE: &'static str = "application/vnd.drawbridge.vulnerability-report.v1+json";
+    pub const SARIF_TYPE: &'static str = "application/sarif+json";
+
+    /// Decodes a report of media type essence `mime`, which is either [Self::TYPE] or
+    /// [Self::SARIF_TYPE].
+    ///
+    /// SARIF logs are converted, with subjects taken from the hashes of the artifacts and
+    /// findings from the results of all runs.
+    pub fn decode(mime: &str, buf: &[u8]) -> anyhow::Result<Self> {
+        match mime {
+            Self::TYPE => serde_json::from_slice(buf).context("invalid vulnerability report"),
+            Self::SARIF_TYPE => serde_json::from_slice::<sarif::Log>(buf)
+                .context("invalid SARIF log")?
+                .try_into(),
+            _ => bail!("unsupported vulnerability report type `{mime}`"),
+        }
+    }
+
+    /// Returns `true` if any of the report subjects matches `hash`.
+    pub fn matches(&self, hash: &ContentDigest) -> bool {
+        self.subject.iter().any(|subject| subject.matches(hash))
+    }
+
+    /// Returns the findings with a severity of at least `severity`.
+    pub fn findings_at_least(&self, severity: Severity) -> impl Iterator<Item = &Finding> {
+        self.findings.iter().filter(move |f| f.severity >= severity)
+    }
+}
+
+/// The subset of SARIF 2.1.0 vulnerability reports are converted from
+mod sarif {
+    use super::*;
+
+    #[derive(Deserialize)]
+    pub(super) struct Log {
+        #[serde(default)]
+        pub(super) runs: Vec<Run>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Run {
+        pub(super) tool: Tool,
+        #[serde(default)]
+        pub(super) artifacts: Vec<Artifact>,
+        #[serde(default)]
+        pub(super) results: Vec<Result>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Tool {
+        pub(super) driver: Driver,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Driver {
+        pub(super) name: String,
+        #[serde(default)]
+        pub(super) rules: Vec<Rule>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Rule {
+        pub(super) id: String,
+        #[serde(default)]
+        pub(super) properties: Properties,
+    }
+
+    #[derive(Default, Deserialize)]
+    pub(super) struct Properties {
+        #[serde(rename = "security-severity")]
+        pub(super) security_severity: Option<String>,
+    }
+
+    impl Properties {
+        pub(super) fn severity(&self) -> Option<Severity> {
+            self.security_severity
+                .as_ref()
+                .and_then(|score| score.parse().ok())
+                .map(Severity::from_score)
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Artifact {
+        #[serde(default)]
+        pub(super) location: Option<Location>,
+        #[serde(default)]
+        pub(super) hashes: BTreeMap<String, String>,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Location {
+        pub(super) uri: String,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    pub(super) struct Result {
+        pub(super) rule_id: String,
+        #[serde(default)]
+        pub(super) level: Option<String>,
+        #[serde(default)]
+        pub(super) message: Option<Message>,
+        #[serde(default)]
+        pub(super) properties: Properties,
+    }
+
+    #[derive(Deserialize)]
+    pub(super) struct Message {
+        pub(super) text: Option<String>,
+    }
+
+    impl TryFrom<Log> for VulnerabilityReport {
+        type Error = anyhow::Error;
+
+        fn try_from(log: Log) -> anyhow::Result<Self> {
+            let mut scanners = vec![];
+            let mut subject = vec![];
+            let mut findings = vec![];
+            for run in log.runs {
+                let Driver { name, rules } = run.tool.driver;
+                if !scanners.contains(&name) {
+                    scanners.push(name);
+                }
+                subject.extend(run.artifacts.into_iter().map(|artifact| {
+                    Subject {
+                        name: artifact.location.map(|l| l.uri).unwrap_or_default(),
+                        digest: artifact
+                            .hashes
+                            .into_iter()
+                            .map(|(algo, hex)| (algo.replace('-', ""), hex))
+                            .collect(),
+                    }
+                }));
+                findings.extend(run.results.into_iter().map(|result| {
+                    let severity = result
+                        .properties
+                        .severity()
+                        .or_else(|| {
+                            rules
+                                .iter()
+                                .find(|rule| rule.id == result.rule_id)
+                                .and_then(|rule| rule.properties.severity())
+                        })
+                        .unwrap_or(match result.level.as_deref() {
+                            Some("error") => Severity::High,
+                            Some("warning") | None => Severity::Medium,
+                            Some("note") => Severity::Low,
+                            Some(_) => Severity::Unknown,
+                        });
+                    Finding {
+                        id: result.rule_id,
+                        severity,
+                        package: None,
+                        title: result.message.and_then(|m| m.text),
+                    }
+                }));
+            }
+            if subject.iter().all(|subject| subject.digest.is_empty()) {
+                bail!("SARIF log does not contain any artifact hashes")
+            }
+            Ok(Self {
+                subject,
+                scanner: scanners.join(", "),
+                findings,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn decode() {
+        let hash: ContentDigest = "sha-256=:LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=:"
+            .parse()
+            .unwrap();
+        let hex = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+        let report = json!({
+            "subject": [{ "digest": { "sha256": hex } }],
+            "scanner": "grype",
+            "findings": [
+                { "id": "CVE-2021-44228", "severity": "critical", "package": "pkg:maven/log4j" },
+                { "id": "CVE-2022-0001", "severity": "low" },
+            ],
+        });
+        let report =
+            VulnerabilityReport::decode(VulnerabilityReport::TYPE, report.to_string().as_bytes())
+                .unwrap();
+        assert!(report.matches(&hash));
+        assert_eq!(report.findings_at_least(Severity::Critical).count(), 1);
+        assert_eq!(report.findings_at_least(Severity::Low).count(), 2);
+
+        let sarif = json!({
+            "version": "2.1.0",
+            "runs": [{
+                "tool": { "driver": {
+                    "name": "trivy",
+                    "rules": [
+                        { "id": "CVE-2021-44228", "properties": { "security-severity": "10.0" } },
+                    ],
+                } },
+                "artifacts": [{
+                    "location": { "uri": "lib/app.jar" },
+                    "hashes": { "sha-256": hex },
+                }],
+                "results": [
+                    { "ruleId": "CVE-2021-44228", "level": "error", "message": { "text": "Log4Shell" } },
+                    { "ruleId": "CVE-2022-0001", "level": "note" },
+                    {
+                        "ruleId": "CVE-2022-0002",
+                        "properties": { "security-severity": "7.5" },
+                    },
+                ],
+            }],
+        });
+        let report = VulnerabilityReport::decode(
+            VulnerabilityReport::SARIF_TYPE,
+            sarif.to_string().as_bytes(),
+        )
+        .unwrap();
+        assert!(report.matches(&hash));
+        assert_eq!(report.scanner, "trivy");
+        assert_eq!(report.subject[0].name, "lib/app.jar");
+        assert_eq!(
+            report
+                .findings
+                .iter()
+                .map(|f| (f.id.as_str(), f.severity))
+                .collect::<Vec<_>>(),
+            vec![
+                ("CVE-2021-44228", Severity::Critical),
+                ("CVE-2022-0001", Severity::Low),
+                ("CVE-2022-0002", Severity::High),
+            ]
+        );
+        assert_eq!(report.findings[0].title.as_deref(), Some("Log4Shell"));
+
+        let sarif = json!({ "runs": [{ "tool": { "driver": { "name": "trivy" } } }] });
+        assert!(VulnerabilityReport::decode(
+            VulnerabilityReport::SARIF_TYPE,
+            sarif.to_string().as_bytes()
+        )
+        .is_err());
+        assert!(VulnerabilityReport::decode("application/json", b"{}").is_err());
+    }
+}
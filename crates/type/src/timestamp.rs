@@ -0,0 +1,145 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use std::fmt::Display;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context as _;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+/// A point in time with a precision of seconds, encoded as specified by
+/// [RFC 3339](https://www.rfc-editor.org/rfc/rfc3339), e.g. `2022-09-01T12:00:00Z`
+///
+/// Seconds precision matches the one of HTTP dates, so that timestamps compare consistently
+/// with `If-Modified-Since` and similar headers.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Timestamp(SystemTime);
+
+impl Timestamp {
+    /// Returns the time elapsed between the timestamp and `now`, zero if `now` precedes it.
+    pub fn age(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.0).unwrap_or_default()
+    }
+}
+
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        let secs = match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => UNIX_EPOCH + Duration::from_secs(d.as_secs()),
+            Err(e) => UNIX_EPOCH - Duration::from_secs(e.duration().as_secs()),
+        };
+        Self(secs)
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.0
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&DateTime::<Utc>::from(self.0).to_rfc3339_opts(SecondsFormat::Secs, true))
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DateTime::parse_from_rfc3339(s)
+            .map(|time| SystemTime::from(time).into())
+            .with_context(|| format!("invalid RFC 3339 timestamp `{s}`"))
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(|e| D::Error::custom(format!("{e:#}")))
+    }
+}
+
+/// Creation and last modification time of an entity, e.g. a repository, tag or tree node
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Timestamps {
+    pub created: Timestamp,
+    pub modified: Timestamp,
+}
+
+impl Timestamps {
+    /// Returns timestamps of an entity created at `now`.
+    pub fn new(now: SystemTime) -> Self {
+        let now = now.into();
+        Self {
+            created: now,
+            modified: now,
+        }
+    }
+
+    /// Returns the timestamps with the modification time set to `now`.
+    pub fn touch(self, now: SystemTime) -> Self {
+        Self {
+            modified: now.into(),
+            ..self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn timestamp() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_662_033_600_500);
+        let timestamp = Timestamp::from(time);
+        assert_eq!(timestamp.to_string(), "2022-09-01T12:00:00Z");
+        assert_eq!(
+            SystemTime::from(timestamp),
+            UNIX_EPOCH + Duration::from_secs(1_662_033_600)
+        );
+        assert_eq!(timestamp.age(time), Duration::from_millis(500));
+        assert_eq!(timestamp.age(UNIX_EPOCH), Duration::ZERO);
+
+        assert_eq!(
+            "2022-09-01T14:00:00+02:00".parse::<Timestamp>().unwrap(),
+            timestamp
+        );
+        for s in ["", "2022-09-01", "2022-09-01 12:00:00", "12:00:00Z"] {
+            assert!(
+                s.parse::<Timestamp>().is_err(),
+                "input '{}' should fail to parse",
+                s
+            );
+        }
+    }
+
+    #[test]
+    fn serialization() {
+        let created = UNIX_EPOCH + Duration::from_secs(1_662_033_600);
+        let timestamps = Timestamps::new(created).touch(created + Duration::from_secs(60));
+        let json = json!({
+            "created": "2022-09-01T12:00:00Z",
+            "modified": "2022-09-01T12:01:00Z",
+        });
+        assert_eq!(serde_json::to_value(timestamps).unwrap(), json);
+        assert_eq!(
+            serde_json::from_value::<Timestamps>(json).unwrap(),
+            timestamps
+        );
+        assert!(timestamps.created < timestamps.modified);
+    }
+}
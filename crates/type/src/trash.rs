@@ -0,0 +1,70 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{RepositoryName, TagName, Timestamp};
+
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+/// A deleted repository or tag kept in the trash of its owner, from which it may be restored
+/// until it expires and is purged
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TrashedEntity {
+    pub id: String,
+
+    /// Name of the repository deleted or the one of the tag deleted
+    pub repository: RepositoryName,
+
+    /// Name of the tag deleted, if a tag was deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<TagName>,
+
+    /// OpenID Connect subject of the deleter
+    pub deleter: String,
+
+    pub deleted: Timestamp,
+
+    /// Time, after which the entity may no longer be restored and is purged
+    pub expires: Timestamp,
+}
+
+impl TrashedEntity {
+    /// Returns whether the entity is expired at `now`.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        now > self.expires.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use serde_json::json;
+
+    #[test]
+    fn expired() {
+        let deleted = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let entity = TrashedEntity {
+            id: "1".into(),
+            repository: "repo".parse().unwrap(),
+            tag: None,
+            deleter: "alice".into(),
+            deleted: deleted.into(),
+            expires: (deleted + Duration::from_secs(60)).into(),
+        };
+        assert!(!entity.is_expired(deleted));
+        assert!(!entity.is_expired(deleted + Duration::from_secs(60)));
+        assert!(entity.is_expired(deleted + Duration::from_secs(61)));
+
+        let value = serde_json::to_value(&entity).unwrap();
+        assert_eq!(value.get("tag"), None);
+        assert_eq!(value["expires"], json!("1970-01-12T13:47:40Z"));
+        assert_eq!(
+            serde_json::from_value::<TrashedEntity>(value).unwrap(),
+            entity
+        );
+    }
+}
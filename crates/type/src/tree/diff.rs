@@ -0,0 +1,121 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::super::{MediaType, Meta};
+use super::Path;
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Differences between files of a local tree and the ones of a remote tree compared by
+/// content digest
+///
+/// Pushing the local tree uploads files [Self::to_upload] and drops files [Self::to_delete]
+/// from the remote tree. Pulling the remote tree downloads [Diff::modified] and
+/// [Diff::removed] files and deletes [Diff::added] files locally instead.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Diff {
+    /// Files only present in the local tree
+    pub added: BTreeSet<Path>,
+
+    /// Files present in both trees, whose contents differ
+    pub modified: BTreeSet<Path>,
+
+    /// Files only present in the remote tree
+    pub removed: BTreeSet<Path>,
+
+    /// Files present in both trees with identical contents
+    pub unchanged: BTreeSet<Path>,
+}
+
+impl Diff {
+    /// Compares file metadata of the `local` tree to the ones of the `remote` tree, both
+    /// keyed by path. Directories are ignored, since they only differ if files do.
+    pub fn new(local: &BTreeMap<Path, Meta>, remote: &BTreeMap<Path, Meta>) -> Self {
+        let is_file = |meta: &&Meta| !meta.mime.is(MediaType::DIRECTORY.essence());
+        let mut diff = Self::default();
+        for (path, meta) in local.iter().filter(|(_, meta)| is_file(meta)) {
+            let set = match remote.get(path).filter(is_file) {
+                None => &mut diff.added,
+                Some(other) if other.hash == meta.hash && other.size == meta.size => {
+                    &mut diff.unchanged
+                }
+                Some(_) => &mut diff.modified,
+            };
+            _ = set.insert(path.clone());
+        }
+        diff.removed = remote
+            .iter()
+            .filter(|(_, meta)| is_file(meta))
+            .filter(|(path, _)| local.get(path).filter(is_file).is_none())
+            .map(|(path, _)| path.clone())
+            .collect();
+        diff
+    }
+
+    /// Returns whether the trees contain identical files.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.modified.is_empty() && self.removed.is_empty()
+    }
+
+    /// Returns local files missing from the remote tree or differing from its files in order.
+    pub fn to_upload(&self) -> impl Iterator<Item = &Path> {
+        self.added.union(&self.modified)
+    }
+
+    /// Returns remote files missing from the local tree in order.
+    pub fn to_delete(&self) -> impl Iterator<Item = &Path> {
+        self.removed.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::digest::Algorithms;
+    use super::*;
+
+    fn meta(data: &str, mime: MediaType) -> Meta {
+        Algorithms::default()
+            .read_sync(data.as_bytes())
+            .map(|(size, hash)| Meta { hash, size, mime })
+            .unwrap()
+    }
+
+    #[test]
+    fn diff() {
+        let path = |path: &str| path.parse::<Path>().unwrap();
+        let local = BTreeMap::from([
+            (Path::ROOT, meta("root", MediaType::DIRECTORY)),
+            (path("a"), meta("a", MediaType::OCTET_STREAM)),
+            (path("b"), meta("b", MediaType::OCTET_STREAM)),
+            (path("dir"), meta("dir", MediaType::DIRECTORY)),
+            (path("dir/c"), meta("c", MediaType::OCTET_STREAM)),
+        ]);
+        let remote = BTreeMap::from([
+            (Path::ROOT, meta("other root", MediaType::DIRECTORY)),
+            (path("b"), meta("changed b", MediaType::OCTET_STREAM)),
+            (path("dir"), meta("dir", MediaType::DIRECTORY)),
+            (path("dir/c"), meta("c", MediaType::OCTET_STREAM)),
+            (path("dir/d"), meta("d", MediaType::OCTET_STREAM)),
+        ]);
+        let diff = Diff::new(&local, &remote);
+        assert_eq!(
+            diff,
+            Diff {
+                added: BTreeSet::from([path("a")]),
+                modified: BTreeSet::from([path("b")]),
+                removed: BTreeSet::from([path("dir/d")]),
+                unchanged: BTreeSet::from([path("dir/c")]),
+            }
+        );
+        assert!(!diff.is_empty());
+        assert_eq!(
+            diff.to_upload().collect::<Vec<_>>(),
+            vec![&path("a"), &path("b")]
+        );
+        assert_eq!(diff.to_delete().collect::<Vec<_>>(), vec![&path("dir/d")]);
+
+        let diff = Diff::new(&local, &local);
+        assert!(diff.is_empty());
+        assert_eq!(diff.unchanged.len(), 3);
+    }
+}
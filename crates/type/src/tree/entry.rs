@@ -1,7 +1,7 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
-use super::super::Meta;
+use super::super::{Annotations, Encryption, License, Meta, Provenance, Publication};
 
 use std::collections::HashMap;
 
@@ -18,6 +18,26 @@ pub struct Entry<C: ?Sized = ()> {
     #[serde(flatten)]
     pub meta: Meta,
 
+    /// Annotations of the entry
+    #[serde(default, skip_serializing_if = "Annotations::is_empty")]
+    pub annotations: Annotations,
+
+    /// License of the entry, e.g. of the tree of a tag referencing it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<License>,
+
+    /// Origin of the entry, e.g. of the tree of a tag referencing it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+
+    /// Publication the entry is bound to, e.g. of a signed tag entry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publication: Option<Publication>,
+
+    /// Header of contents encrypted by the client, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encryption: Option<Encryption>,
+
     /// Custom fields
     #[serde(flatten)]
     pub custom: HashMap<String, Value>,
@@ -2,19 +2,21 @@
 // SPDX-License-Identifier: AGPL-3.0-only
 
 mod context;
+mod diff;
 mod directory;
 mod entry;
 mod name;
 mod path;
 
 pub use context::*;
+pub use diff::*;
 pub use directory::*;
 pub use entry::*;
 pub use name::*;
 pub use path::*;
 
 use super::digest::Algorithms;
-use super::Meta;
+use super::{MediaType, Meta};
 
 use std::collections::BTreeMap;
 use std::ffi::OsStr;
@@ -22,7 +24,6 @@ use std::io::Seek;
 use std::ops::Bound::{Excluded, Unbounded};
 use std::ops::Deref;
 
-use mime::APPLICATION_OCTET_STREAM;
 use walkdir::WalkDir;
 
 #[derive(Debug, Clone)]
@@ -59,6 +60,96 @@ impl<F> Tree<F> {
         // in this module and therefore always has a root.
         self.get(&Path::ROOT).unwrap()
     }
+
+    /// Returns the tree with file entries replaced by the ones returned by `f`, which is passed
+    /// the path and the entry of each file, e.g. to encrypt their contents.
+    ///
+    /// Directories are encoded again, so that they refer to the entries returned by `f`.
+    pub fn map_files<G>(
+        self,
+        mut f: impl FnMut(&Path, Entry<F>) -> std::io::Result<Entry<G>>,
+    ) -> std::io::Result<Tree<G>> {
+        let mut tree: BTreeMap<Path, Entry<Content<G>>> = BTreeMap::new();
+        // NOTE: Descendants are mapped before the directories containing them.
+        for (path, entry) in self.0.into_iter().rev() {
+            let Entry {
+                meta,
+                annotations,
+                license,
+                provenance,
+                publication,
+                encryption,
+                custom,
+                content,
+            } = entry;
+            let entry = match content {
+                Content::File(file) => {
+                    let Entry {
+                        meta,
+                        annotations,
+                        license,
+                        provenance,
+                        publication,
+                        encryption,
+                        custom,
+                        content,
+                    } = f(
+                        &path,
+                        Entry {
+                            meta,
+                            annotations,
+                            license,
+                            provenance,
+                            publication,
+                            encryption,
+                            custom,
+                            content: file,
+                        },
+                    )?;
+                    Entry {
+                        meta,
+                        annotations,
+                        license,
+                        provenance,
+                        publication,
+                        encryption,
+                        custom,
+                        content: Content::File(content),
+                    }
+                }
+                Content::Directory(_) => {
+                    let dir: Directory<_> = tree
+                        .range((Excluded(&path), Unbounded))
+                        .take_while(|(p, _)| p.starts_with(&path))
+                        .filter_map(|(p, e)| match p.split_last() {
+                            Some((base, dir)) if dir == path.as_slice() => Some((base.clone(), e)),
+                            _ => None,
+                        })
+                        .collect();
+                    let buf = serde_json::to_vec(&dir).map_err(|e| {
+                        std::io::Error::other(format!("failed to encode directory to JSON: {e}"))
+                    })?;
+                    let (size, hash) = Algorithms::default().read_sync(&buf[..])?;
+                    Entry {
+                        meta: Meta {
+                            hash,
+                            size,
+                            mime: meta.mime,
+                        },
+                        annotations,
+                        license,
+                        provenance,
+                        publication,
+                        encryption,
+                        custom,
+                        content: Content::Directory(buf),
+                    }
+                }
+            };
+            _ = tree.insert(path, entry);
+        }
+        Ok(Tree(tree))
+    }
 }
 
 impl Tree<std::fs::File> {
@@ -102,11 +193,16 @@ impl Tree<std::fs::File> {
                                 hash,
                                 size,
                                 mime: match e.path().extension().and_then(OsStr::to_str) {
-                                    Some("wasm") => "application/wasm".parse().unwrap(),
+                                    Some("wasm") => MediaType::WASM,
                                     Some("toml") => "application/toml".parse().unwrap(),
-                                    _ => APPLICATION_OCTET_STREAM,
+                                    _ => MediaType::OCTET_STREAM,
                                 },
                             },
+                            annotations: Default::default(),
+                            license: None,
+                            provenance: None,
+                            publication: None,
+                            encryption: None,
                             custom: Default::default(),
                             content: Content::File(file),
                         }
@@ -134,8 +230,13 @@ impl Tree<std::fs::File> {
                             meta: Meta {
                                 hash,
                                 size,
-                                mime: Directory::<()>::TYPE.parse().unwrap(),
+                                mime: MediaType::DIRECTORY,
                             },
+                            annotations: Default::default(),
+                            license: None,
+                            provenance: None,
+                            publication: None,
+                            encryption: None,
                             custom: Default::default(),
                             content: Content::Directory(buf),
                         }
@@ -178,7 +279,7 @@ mod tests {
             .map(|(size, hash)| Meta {
                 hash,
                 size,
-                mime: APPLICATION_OCTET_STREAM,
+                mime: MediaType::OCTET_STREAM,
             })
             .unwrap();
 
@@ -187,7 +288,7 @@ mod tests {
             .map(|(size, hash)| Meta {
                 hash,
                 size,
-                mime: APPLICATION_OCTET_STREAM,
+                mime: MediaType::OCTET_STREAM,
             })
             .unwrap();
 
@@ -198,6 +299,11 @@ mod tests {
                     "test-file-bar".parse().unwrap(),
                     Entry {
                         meta: bar_meta.clone(),
+                        annotations: Default::default(),
+                        license: None,
+                        provenance: None,
+                        publication: None,
+                        encryption: None,
                         custom: Default::default(),
                         content: (),
                     },
@@ -212,7 +318,7 @@ mod tests {
             .map(|(size, hash)| Meta {
                 hash,
                 size,
-                mime: Directory::<()>::TYPE.parse().unwrap(),
+                mime: MediaType::DIRECTORY,
             })
             .unwrap();
 
@@ -223,6 +329,11 @@ mod tests {
                     "test-dir".parse().unwrap(),
                     Entry {
                         meta: test_dir_meta.clone(),
+                        annotations: Default::default(),
+                        license: None,
+                        provenance: None,
+                        publication: None,
+                        encryption: None,
                         custom: Default::default(),
                         content: (),
                     },
@@ -237,7 +348,7 @@ mod tests {
             .map(|(size, hash)| Meta {
                 hash,
                 size,
-                mime: Directory::<()>::TYPE.parse().unwrap(),
+                mime: MediaType::DIRECTORY,
             })
             .unwrap();
 
@@ -289,4 +400,81 @@ mod tests {
 
         assert!(tree.next().is_none());
     }
+
+    #[test]
+    fn map_files() {
+        let root = tempdir().expect("failed to create temporary root directory");
+        create_dir(root.path().join("test-dir")).unwrap();
+        write(root.path().join("test-dir").join("test-file-bar"), "bar").unwrap();
+
+        let tree = Tree::from_path_sync(root.path())
+            .expect("failed to construct a tree")
+            .map_files(|_, entry| {
+                let Entry { content, .. } = entry;
+                let mut buf = vec![];
+                _ = { content }.read_to_end(&mut buf)?;
+                buf.make_ascii_uppercase();
+                let (size, hash) = Algorithms::default().read_sync(&buf[..])?;
+                Ok(Entry {
+                    meta: Meta {
+                        hash,
+                        size,
+                        mime: MediaType::OCTET_STREAM,
+                    },
+                    annotations: Default::default(),
+                    license: None,
+                    provenance: None,
+                    publication: None,
+                    encryption: None,
+                    custom: Default::default(),
+                    content: buf,
+                })
+            })
+            .expect("failed to map files");
+
+        let bar_meta = Algorithms::default()
+            .read_sync("BAR".as_bytes())
+            .map(|(size, hash)| Meta {
+                hash,
+                size,
+                mime: MediaType::OCTET_STREAM,
+            })
+            .unwrap();
+        let bar = tree
+            .get(&"test-dir/test-file-bar".parse().unwrap())
+            .unwrap();
+        assert_eq!(bar.meta, bar_meta);
+        assert!(matches!(bar.content, Content::File(ref buf) if buf == b"BAR"));
+
+        let dir = tree.get(&"test-dir".parse().unwrap()).unwrap();
+        let json = match dir.content {
+            Content::Directory(ref json) => json,
+            _ => panic!("invalid content type"),
+        };
+        let listing: Directory = serde_json::from_slice(json).unwrap();
+        assert_eq!(
+            listing.get(&"test-file-bar".parse().unwrap()).unwrap().meta,
+            bar_meta
+        );
+        assert_eq!(
+            dir.meta,
+            Algorithms::default()
+                .read_sync(&json[..])
+                .map(|(size, hash)| Meta {
+                    hash,
+                    size,
+                    mime: MediaType::DIRECTORY,
+                })
+                .unwrap()
+        );
+
+        let listing: Directory = match tree.root().content {
+            Content::Directory(ref json) => serde_json::from_slice(json).unwrap(),
+            _ => panic!("invalid content type"),
+        };
+        assert_eq!(
+            listing.get(&"test-dir".parse().unwrap()).unwrap().meta,
+            dir.meta
+        );
+    }
 }
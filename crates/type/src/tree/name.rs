@@ -18,11 +18,20 @@ use serde::{Deserialize, Deserializer, Serialize};
 pub struct Name(String);
 
 impl Name {
+    /// Maximum length of an entry name in bytes
+    pub const MAX_LENGTH: usize = 255;
+
     #[inline]
     fn validate(s: impl AsRef<str>) -> anyhow::Result<()> {
         let s = s.as_ref();
         if s.is_empty() {
             bail!("empty entry name")
+        } else if s == "." || s == ".." {
+            bail!("entry name `{s}` refers to the current or parent directory")
+        } else if s.len() > Self::MAX_LENGTH {
+            bail!("entry name exceeds {} bytes", Self::MAX_LENGTH)
+        } else if s.contains('\0') {
+            bail!("NUL byte in entry name")
         } else if s
             .find(|c| !matches!(c, '0'..='9' | 'a'..='z' | 'A'..='Z' | '-' | '_' | '.' | ':'))
             .is_some()
@@ -113,6 +122,13 @@ mod tests {
         assert!("/".parse::<Name>().is_err());
         assert!("/test".parse::<Name>().is_err());
         assert!("test/".parse::<Name>().is_err());
+        assert!(".".parse::<Name>().is_err());
+        assert!("..".parse::<Name>().is_err());
+        assert!("foo\0".parse::<Name>().is_err());
+        assert!("a".repeat(Name::MAX_LENGTH + 1).parse::<Name>().is_err());
+
+        assert_eq!("..foo".parse::<Name>().unwrap(), Name("..foo".into()));
+        assert!("a".repeat(Name::MAX_LENGTH).parse::<Name>().is_ok());
 
         assert_eq!("foo".parse::<Name>().unwrap(), Name("foo".into()));
         assert_eq!("some.txt".parse::<Name>().unwrap(), Name("some.txt".into()));
@@ -8,16 +8,54 @@ use std::ops::Deref;
 use std::path::PathBuf;
 use std::str::FromStr;
 
-use serde::{Deserialize, Serialize};
-
-#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+use anyhow::bail;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A path of a node relative to the root of a tree, e.g. `foo/bar`
+///
+/// Paths are ordered segment-wise, so that each directory immediately precedes its descendants,
+/// e.g. `foo` < `foo/bar` < `foo-bar`. Paths are serialized as strings using `/` as separator.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
 #[repr(transparent)]
-#[serde(transparent)]
 pub struct Path(Vec<Name>);
 
 impl Path {
     pub const ROOT: Self = Self(vec![]);
 
+    /// Returns whether the path refers to the tree root.
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns an iterator over the segments of the path.
+    pub fn segments(&self) -> std::slice::Iter<'_, Name> {
+        self.0.iter()
+    }
+
+    /// Returns the last segment of the path, i.e. the name of the node, unless the path refers
+    /// to the tree root.
+    pub fn name(&self) -> Option<&Name> {
+        self.0.last()
+    }
+
+    /// Returns the path of the parent directory, unless the path refers to the tree root.
+    pub fn parent(&self) -> Option<Self> {
+        self.0.split_last().map(|(_, parent)| Self(parent.to_vec()))
+    }
+
+    /// Returns the path of the child `name` of the directory at this path.
+    pub fn join(&self, name: Name) -> Self {
+        let mut path = self.clone();
+        path.push(name);
+        path
+    }
+
+    /// Appends segment `name` to the path.
+    pub fn push(&mut self, name: Name) {
+        self.0.push(name)
+    }
+
     pub fn intersperse(&self, sep: &str) -> String {
         let mut it = self.0.iter();
         match it.next() {
@@ -84,8 +122,19 @@ impl FromIterator<Name> for Path {
 impl FromStr for Path {
     type Err = anyhow::Error;
 
+    /// Parses a path relative to the root of a tree.
+    ///
+    /// Both `/` and `\` are accepted as separators. A single leading separator refers to the
+    /// tree root and a single trailing one is ignored. Paths with empty segments, segments
+    /// referring to the current or parent directory or otherwise invalid [Name]s are rejected,
+    /// so that parsed paths never escape the tree.
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        s.trim_start_matches('/')
+        let s = s.replace('\\', "/");
+        if s.starts_with("//") {
+            bail!("absolute path `{s}`")
+        }
+        s.strip_prefix('/')
+            .unwrap_or(&s)
             .split_terminator('/')
             .map(FromStr::from_str)
             .collect::<Result<Vec<_>, Self::Err>>()
@@ -93,6 +142,20 @@ impl FromStr for Path {
     }
 }
 
+impl Serialize for Path {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Path {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(D::Error::custom)
+    }
+}
+
 impl IntoIterator for Path {
     type Item = Name;
     type IntoIter = std::vec::IntoIter<Self::Item>;
@@ -121,5 +184,68 @@ mod tests {
             "/foo/bar".parse::<Path>().unwrap(),
             Path(vec!["foo".parse().unwrap(), "bar".parse().unwrap()])
         );
+        assert_eq!(
+            "foo\\bar".parse::<Path>().unwrap(),
+            Path(vec!["foo".parse().unwrap(), "bar".parse().unwrap()])
+        );
+
+        assert!("//etc/passwd".parse::<Path>().is_err());
+        assert!("\\\\host\\share".parse::<Path>().is_err());
+        assert!("foo//bar".parse::<Path>().is_err());
+        assert!("foo/bar//".parse::<Path>().is_err());
+        assert!("../foo".parse::<Path>().is_err());
+        assert!("foo/../../bar".parse::<Path>().is_err());
+        assert!("foo/./bar".parse::<Path>().is_err());
+        assert!("foo\\..\\bar".parse::<Path>().is_err());
+        assert!("foo\0/bar".parse::<Path>().is_err());
+    }
+
+    #[test]
+    fn navigation() {
+        let foo: Name = "foo".parse().unwrap();
+        let bar: Name = "bar".parse().unwrap();
+
+        assert!(Path::ROOT.is_root());
+        assert_eq!(Path::ROOT.name(), None);
+        assert_eq!(Path::ROOT.parent(), None);
+
+        let path = Path::ROOT.join(foo.clone()).join(bar.clone());
+        assert_eq!(path, "foo/bar".parse().unwrap());
+        assert!(!path.is_root());
+        assert_eq!(path.segments().collect::<Vec<_>>(), [&foo, &bar]);
+        assert_eq!(path.name(), Some(&bar));
+        assert_eq!(path.parent(), Some(foo.clone().into()));
+        assert_eq!(path.parent().and_then(|p| p.parent()), Some(Path::ROOT));
+        assert_eq!(path.to_string(), "foo/bar");
+        assert_eq!(path.to_string().parse::<Path>().unwrap(), path);
+    }
+
+    #[test]
+    fn ordering() {
+        let mut paths = ["foo-bar", "foo/bar/baz", "", "foo", "a", "foo/bar", "foo/a"]
+            .map(|s| s.parse::<Path>().unwrap());
+        paths.sort();
+        assert_eq!(
+            paths.map(|p| p.to_string()),
+            ["", "a", "foo", "foo/a", "foo/bar", "foo/bar/baz", "foo-bar"]
+        );
+    }
+
+    #[test]
+    fn serialization() {
+        let path: Path = "foo/bar".parse().unwrap();
+        assert_eq!(
+            serde_json::to_value(&path).unwrap(),
+            serde_json::json!("foo/bar")
+        );
+        assert_eq!(
+            serde_json::from_value::<Path>(serde_json::json!("/foo/bar/")).unwrap(),
+            path
+        );
+        assert_eq!(
+            serde_json::to_value(Path::ROOT).unwrap(),
+            serde_json::json!("")
+        );
+        assert!(serde_json::from_value::<Path>(serde_json::json!("foo/../bar")).is_err());
     }
 }
@@ -0,0 +1,76 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use super::{TagName, Timestamp};
+
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+/// An upload of a tree to a tag, which is not published yet, in progress
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct UploadSession {
+    /// Name of the tag the tree is uploaded to
+    pub tag: TagName,
+
+    /// OpenID Connect subject of the uploader, who started the session, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+
+    pub started: Timestamp,
+
+    /// Time a node was last uploaded at
+    pub updated: Timestamp,
+
+    /// Bytes received so far, which are freed if the session is cancelled or expires
+    pub received: u64,
+
+    /// Whether the tree is complete, so that the tag may be published
+    pub complete: bool,
+
+    /// Time, after which the session is abandoned and its tree removed, unless more nodes are
+    /// uploaded
+    pub expires: Timestamp,
+}
+
+impl UploadSession {
+    /// Returns whether the session is expired at `now`.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        now > self.expires.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use serde_json::json;
+
+    #[test]
+    fn expired() {
+        let started = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let session = UploadSession {
+            tag: "1.0.0".parse().unwrap(),
+            owner: None,
+            started: started.into(),
+            updated: (started + Duration::from_secs(30)).into(),
+            received: 42,
+            complete: false,
+            expires: (started + Duration::from_secs(90)).into(),
+        };
+        assert!(!session.is_expired(started));
+        assert!(!session.is_expired(started + Duration::from_secs(90)));
+        assert!(session.is_expired(started + Duration::from_secs(91)));
+
+        let value = serde_json::to_value(&session).unwrap();
+        assert_eq!(value.get("owner"), None);
+        assert_eq!(value["received"], json!(42));
+        assert_eq!(value["expires"], json!("1970-01-12T13:48:10Z"));
+        assert_eq!(
+            serde_json::from_value::<UploadSession>(value).unwrap(),
+            session
+        );
+    }
+}
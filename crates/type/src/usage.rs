@@ -0,0 +1,154 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+
+use serde::{Deserialize, Serialize};
+
+/// Bandwidth and storage used by a repository, or all repositories of a user.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Usage {
+    /// Number of bytes of request bodies accepted
+    pub uploaded: u64,
+    /// Number of bytes of response bodies served
+    pub downloaded: u64,
+    /// Number of bytes of contents stored
+    pub stored: u64,
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, rhs: Self) {
+        self.uploaded = self.uploaded.saturating_add(rhs.uploaded);
+        self.downloaded = self.downloaded.saturating_add(rhs.downloaded);
+        self.stored = self.stored.saturating_add(rhs.stored);
+    }
+}
+
+/// Usage of all repositories of a user.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct UserUsage {
+    /// Sum of usage of all repositories
+    pub total: Usage,
+    /// Usage of each repository, keyed by repository name
+    pub repositories: BTreeMap<String, Usage>,
+}
+
+impl FromIterator<(String, Usage)> for UserUsage {
+    fn from_iter<T: IntoIterator<Item = (String, Usage)>>(iter: T) -> Self {
+        let repositories: BTreeMap<_, _> = iter.into_iter().collect();
+        let mut total = Usage::default();
+        for usage in repositories.values() {
+            total += *usage;
+        }
+        Self {
+            total,
+            repositories,
+        }
+    }
+}
+
+/// Storage quota of a tenant.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct StorageQuota {
+    /// Maximum number of bytes stored in all repositories
+    pub limit: u64,
+    /// Number of bytes stored in all repositories
+    pub used: u64,
+}
+
+impl StorageQuota {
+    /// Returns the number of bytes, which may still be stored.
+    pub fn remaining(&self) -> u64 {
+        self.limit.saturating_sub(self.used)
+    }
+}
+
+/// Request rate quota of a client.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct RateQuota {
+    /// Maximum number of requests in a window
+    pub limit: u64,
+    /// Number of seconds of a window
+    pub window: u64,
+    /// Number of requests, which may still be sent in the current window
+    pub remaining: u64,
+    /// Number of seconds until the current window resets
+    pub reset: u64,
+}
+
+/// Quotas applying to a client, which are unlimited if unset.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Quota {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage: Option<StorageQuota>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate: Option<RateQuota>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    #[test]
+    fn user_usage() {
+        let usage: UserUsage = [
+            (
+                "foo".into(),
+                Usage {
+                    uploaded: 1,
+                    downloaded: 2,
+                    stored: u64::MAX,
+                },
+            ),
+            (
+                "bar".into(),
+                Usage {
+                    uploaded: 3,
+                    downloaded: 4,
+                    stored: 5,
+                },
+            ),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(
+            usage.total,
+            Usage {
+                uploaded: 4,
+                downloaded: 6,
+                stored: u64::MAX,
+            }
+        );
+        assert_eq!(
+            usage.repositories.keys().collect::<Vec<_>>(),
+            ["bar", "foo"]
+        );
+    }
+
+    #[test]
+    fn quota() {
+        let storage = StorageQuota { limit: 10, used: 4 };
+        assert_eq!(storage.remaining(), 6);
+        assert_eq!(
+            StorageQuota {
+                limit: 10,
+                used: 11
+            }
+            .remaining(),
+            0
+        );
+
+        assert_eq!(serde_json::to_value(Quota::default()).unwrap(), json!({}));
+        let quota = Quota {
+            storage: Some(storage),
+            rate: None,
+        };
+        assert_eq!(
+            serde_json::to_value(quota).unwrap(),
+            json!({ "storage": { "limit": 10, "used": 4 } })
+        );
+    }
+}
@@ -1,6 +1,8 @@
 // SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
 // SPDX-License-Identifier: AGPL-3.0-only
 
+use super::super::digest::AlgorithmRules;
+
 use serde::{Deserialize, Serialize};
 
 /// A user record
@@ -9,4 +11,9 @@ use serde::{Deserialize, Serialize};
 pub struct Record {
     /// OpenID Connect identity subject uniquely identifying the user
     pub subject: String,
+
+    /// Hashing algorithms content digests of uploads to all repositories of the user must or
+    /// must not consist of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub algorithms: Option<AlgorithmRules>,
 }
@@ -0,0 +1,172 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+use std::cmp::Ordering;
+use std::fmt::Display;
+use std::ops::{Deref, DerefMut};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+pub use semver::VersionReq;
+
+/// A semantic version as specified by [SemVer 2.0](https://semver.org/spec/v2.0.0.html).
+///
+/// Versions are ordered by precedence, where versions only differing in build metadata,
+/// which have equal precedence, are ordered by their build metadata to make the order total.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[repr(transparent)]
+#[serde(transparent)]
+pub struct Version(semver::Version);
+
+impl Version {
+    /// Compares precedence of the versions, which ignores build metadata.
+    pub fn cmp_precedence(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch, &self.pre).cmp(&(
+            other.major,
+            other.minor,
+            other.patch,
+            &other.pre,
+        ))
+    }
+
+    /// Returns whether the version is a pre-release.
+    pub fn is_prerelease(&self) -> bool {
+        !self.pre.is_empty()
+    }
+
+    /// Returns the major and minor version, which identify the minor release series the version
+    /// belongs to, e.g. to retain the last releases of each series.
+    pub fn series(&self) -> (u64, u64) {
+        (self.major, self.minor)
+    }
+
+    /// Returns the version of highest precedence in `versions` matching `req`.
+    ///
+    /// Pre-releases only match requirements, which explicitly refer to pre-releases of the same
+    /// version, e.g. `>=1.2.3-rc.1`.
+    pub fn resolve<'a>(
+        req: &VersionReq,
+        versions: impl IntoIterator<Item = &'a Self>,
+    ) -> Option<&'a Self> {
+        versions
+            .into_iter()
+            .filter(|version| req.matches(version))
+            .max()
+    }
+}
+
+impl From<semver::Version> for Version {
+    fn from(version: semver::Version) -> Self {
+        Self(version)
+    }
+}
+
+impl FromStr for Version {
+    type Err = semver::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse().map(Self)
+    }
+}
+
+impl Deref for Version {
+    type Target = semver::Version;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Version {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_str() {
+        for s in [
+            "", "1", "1.2", "v1.2.3", "01.2.3", "1.2.3-", "1.2.3-01", "1.2.3+",
+        ] {
+            assert!(
+                s.parse::<Version>().is_err(),
+                "input '{}' should fail to parse",
+                s
+            );
+        }
+        let version: Version = "1.2.3-rc.1+build.5".parse().unwrap();
+        assert_eq!((version.major, version.minor, version.patch), (1, 2, 3));
+        assert_eq!(version.pre.as_str(), "rc.1");
+        assert_eq!(version.build.as_str(), "build.5");
+        assert!(version.is_prerelease());
+        assert_eq!(version.series(), (1, 2));
+        assert_eq!(version.to_string(), "1.2.3-rc.1+build.5");
+    }
+
+    #[test]
+    fn precedence() {
+        // NOTE: Example given by section 11 of the specification.
+        let versions = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+            "1.1.0",
+            "2.0.0",
+            "10.0.0",
+        ]
+        .map(|s| s.parse::<Version>().unwrap());
+        for pair in versions.windows(2) {
+            assert!(pair[0] < pair[1], "{} should precede {}", pair[0], pair[1]);
+            assert_eq!(pair[0].cmp_precedence(&pair[1]), Ordering::Less);
+        }
+
+        let a: Version = "1.0.0+a".parse().unwrap();
+        let b: Version = "1.0.0+b".parse().unwrap();
+        assert_eq!(a.cmp_precedence(&b), Ordering::Equal);
+        assert_ne!(a, b);
+        assert!(a < b);
+    }
+
+    #[test]
+    fn serialization() {
+        let version: Version = "1.2.3-rc.1".parse().unwrap();
+        assert_eq!(
+            serde_json::to_value(&version).unwrap(),
+            serde_json::json!("1.2.3-rc.1")
+        );
+        assert_eq!(
+            serde_json::from_value::<Version>(serde_json::json!("1.2.3-rc.1")).unwrap(),
+            version
+        );
+        assert!(serde_json::from_value::<Version>(serde_json::json!("v1.2.3")).is_err());
+    }
+
+    #[test]
+    fn resolve() {
+        let versions = ["0.1.0", "1.0.0", "1.2.0", "1.3.0-rc.1", "2.0.0"]
+            .map(|s| s.parse::<Version>().unwrap());
+        let resolve =
+            |req: &str| Version::resolve(&req.parse().unwrap(), &versions).map(ToString::to_string);
+        assert_eq!(resolve("*").as_deref(), Some("2.0.0"));
+        assert_eq!(resolve("^1").as_deref(), Some("1.2.0"));
+        assert_eq!(resolve(">=1.3.0-rc.1, <2").as_deref(), Some("1.3.0-rc.1"));
+        assert_eq!(resolve("~0.1").as_deref(), Some("0.1.0"));
+        assert_eq!(resolve("^3"), None);
+    }
+}
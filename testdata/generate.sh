@@ -28,6 +28,7 @@ printf "\nGenerating Client key\n"
 openssl ecparam -genkey -name prime256v1 | openssl pkcs8 -topk8 -nocrypt -out client.key
 printf "\nClient "
 openssl pkey -noout -text -in client.key
+openssl pkey -pubout -in client.key -out client.pub
 
 printf "\nGenerating Client Certificate Signing Request\n"
 openssl req -new -config client.conf -key client.key -out client.csr
@@ -38,3 +39,33 @@ printf "\nGenerating Client Certificate\n"
 openssl x509 -req -days 9999 -CAcreateserial -CA ca.crt -CAkey ca.key -in client.csr -out client.crt -extfile client.conf -extensions client_crt
 printf "\nClient "
 openssl x509 -noout -text -in client.crt
+
+printf "\nGenerating Steward CA key\n"
+openssl genpkey -algorithm RSA -pkeyopt rsa_keygen_bits:2048 -out steward-ca.key
+printf "\nSteward CA "
+openssl pkey -noout -text -in steward-ca.key
+
+printf "\nGenerating Steward CA certificate\n"
+openssl req -new -x509 -days 9999 -config steward.conf -key steward-ca.key -out steward-ca.crt
+printf "\nSteward CA "
+openssl x509 -noout -text -in steward-ca.crt
+
+printf "\nGenerating Workload key\n"
+openssl ecparam -genkey -name prime256v1 | openssl pkcs8 -topk8 -nocrypt -out workload.key
+printf "\nWorkload "
+openssl pkey -noout -text -in workload.key
+
+printf "\nGenerating Workload Certificate Signing Request\n"
+openssl req -new -subj "/CN=workload.steward.localhost" -key workload.key -out workload.csr
+
+printf "\nGenerating Workload Certificate\n"
+openssl x509 -req -days 9999 -set_serial 1 -sha256 -CA steward-ca.crt -CAkey steward-ca.key -in workload.csr -out workload.crt -extfile steward.conf -extensions workload_crt
+printf "\nWorkload "
+openssl x509 -noout -text -in workload.crt
+
+# NOTE: Steward does not issue RSA-PSS signed certificates, which the TLS handshake accepts,
+# but the Steward integration rejects.
+printf "\nGenerating RSA-PSS signed Workload Certificate\n"
+openssl x509 -req -days 9999 -set_serial 2 -sha256 -sigopt rsa_padding_mode:pss -sigopt rsa_pss_saltlen:digest -CA steward-ca.crt -CAkey steward-ca.key -in workload.csr -out workload-pss.crt -extfile steward.conf -extensions workload_crt
+printf "\nRSA-PSS signed Workload "
+openssl x509 -noout -text -in workload-pss.crt
@@ -0,0 +1,93 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{RepositoryConfig, UserRecord};
+use drawbridge_server::admission::{Admission, AdmissionRules};
+
+use async_std::task::spawn_blocking;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+#[async_std::test]
+async fn denied_deletion() {
+    let oidc = Oidc::start();
+    let rules: AdmissionRules = serde_json::from_str(
+        r#"[{
+            "namespace": "testuser/prod",
+            "actions": ["delete"],
+            "require": { "deny": true },
+            "description": "production artifacts are retained"
+        }]"#,
+    )
+    .unwrap();
+    let srv = Server::start(&oidc, None, |app| {
+        app.admission(Admission::default().policy(rules))
+    })
+    .await;
+
+    let cl = srv.client();
+    let agent = srv.agent(None);
+    let url = srv.url();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token.clone()).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        let user = owner.user(&user_name);
+        assert!(user
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("test-file.txt"), "text").unwrap();
+        for name in ["prod", "dev"] {
+            let repo = user.repository(&name.parse().unwrap());
+            assert!(repo
+                .create(&RepositoryConfig::default())
+                .expect("failed to create repository"));
+            _ = repo
+                .tag(&"0.1.0".parse().unwrap())
+                .create_from_path_unsigned(pkg.path())
+                .expect("failed to create a tag and upload the tree");
+        }
+
+        let delete = |path: &str| match agent
+            .delete(&format!("{url}/api/v0.1.0/testuser/{path}"))
+            .set("authorization", &format!("Bearer {token}"))
+            .call()
+        {
+            Ok(res) => (res.status(), String::new()),
+            Err(ureq::Error::Status(status, res)) => (status, res.into_string().unwrap()),
+            Err(e) => panic!("failed to delete `{path}`: {e}"),
+        };
+
+        // Policies denying deletion are evaluated before anything is moved into the trash
+        for path in ["prod/_tag/0.1.0", "prod"] {
+            let (status, reason) = delete(path);
+            assert_eq!(status, 403, "deletion of `{path}` was admitted");
+            assert!(
+                reason.contains("production artifacts are retained: delete is not allowed"),
+                "{reason}"
+            );
+        }
+        assert!(user.trash().expect("failed to list trash").is_empty());
+        _ = user
+            .repository(&"prod".parse().unwrap())
+            .tag(&"0.1.0".parse().unwrap())
+            .get()
+            .expect("tag denied deletion was deleted");
+
+        // Other namespaces are not affected by the policy
+        assert_eq!(delete("dev/_tag/0.1.0").0, 200);
+        assert_eq!(delete("dev").0, 200);
+    })
+    .await;
+
+    srv.stop().await;
+}
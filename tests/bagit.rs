@@ -0,0 +1,202 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{corrupt_contents, Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{RepositoryConfig, UserRecord};
+
+use std::collections::BTreeMap;
+use std::io::Read;
+
+use async_std::task::spawn_blocking;
+use ring::digest::{digest, SHA256};
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+/// Returns paths and contents of the entries of ustar archive `buf` in order.
+fn entries(mut buf: &[u8]) -> Vec<(String, Vec<u8>)> {
+    let mut entries = vec![];
+    loop {
+        let (header, rest) = buf.split_at(512);
+        if header.iter().all(|b| *b == 0) {
+            return entries;
+        }
+        let field = |range: std::ops::Range<usize>| {
+            let field = &header[range];
+            let n = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+            std::str::from_utf8(&field[..n]).unwrap().to_string()
+        };
+        let size = usize::from_str_radix(&field(124..136), 8).unwrap();
+        entries.push((field(0..100), rest[..size].to_vec()));
+        buf = &rest[size.div_ceil(512) * 512..];
+    }
+}
+
+/// Returns the hex-encoded SHA-256 digest of `data`.
+fn sha256(data: &[u8]) -> String {
+    digest(&SHA256, data)
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[async_std::test]
+async fn bag_export() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let cl = srv.client();
+    let agent = srv.agent(None);
+    let url = srv.url();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let repo = owner
+            .user(&user_name)
+            .repository(&"public".parse().unwrap());
+        assert!(repo
+            .create(&RepositoryConfig {
+                public: true,
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+
+        let big: Vec<_> = (0..200 * 1024).map(|i| (i % 251) as u8).collect();
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("a.txt"), "text").unwrap();
+        std::fs::create_dir(pkg.path().join("dir")).unwrap();
+        std::fs::write(pkg.path().join("dir").join("big.bin"), &big).unwrap();
+        _ = repo
+            .tag(&"0.1.0".parse().unwrap())
+            .create_from_path_unsigned(pkg.path())
+            .expect("failed to create a tag and upload the tree");
+
+        let res = agent
+            .get(&format!(
+                "{url}/api/v0.1.0/testuser/public/_tag/0.1.0/bagit"
+            ))
+            .call()
+            .expect("failed to export tag");
+        assert_eq!(res.content_type(), "application/x-tar");
+        let mut buf = vec![];
+        _ = res.into_reader().read_to_end(&mut buf).unwrap();
+
+        // Payload files precede the tag files, which list them
+        let entries = entries(&buf);
+        let paths: Vec<_> = entries.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "public-0.1.0/data/a.txt",
+                "public-0.1.0/data/dir/big.bin",
+                "public-0.1.0/bagit.txt",
+                "public-0.1.0/bag-info.txt",
+                "public-0.1.0/manifest-sha256.txt",
+                "public-0.1.0/tagmanifest-sha256.txt",
+            ]
+        );
+        let files: BTreeMap<_, _> = entries
+            .iter()
+            .map(|(path, content)| (path.strip_prefix("public-0.1.0/").unwrap(), content))
+            .collect();
+        assert_eq!(files["data/a.txt"].as_slice(), b"text");
+        assert_eq!(files["data/dir/big.bin"], &big);
+
+        let bag_info = String::from_utf8(files["bag-info.txt"].to_vec()).unwrap();
+        assert!(
+            bag_info.contains(&format!("Payload-Oxum: {}.2\n", 4 + big.len())),
+            "{bag_info}"
+        );
+        for (manifest, expected) in [
+            (
+                "manifest-sha256.txt",
+                &["data/a.txt", "data/dir/big.bin"][..],
+            ),
+            (
+                "tagmanifest-sha256.txt",
+                &["bagit.txt", "bag-info.txt", "manifest-sha256.txt"][..],
+            ),
+        ] {
+            let listed: Vec<_> = std::str::from_utf8(files[manifest])
+                .unwrap()
+                .lines()
+                .map(|line| {
+                    let (hash, path) = line.split_once("  ").unwrap();
+                    assert_eq!(hash, sha256(files[path]), "digest of `{path}` mismatch");
+                    path
+                })
+                .collect();
+            assert_eq!(listed, expected);
+        }
+    })
+    .await;
+
+    srv.stop().await;
+}
+
+#[async_std::test]
+async fn corrupted_contents() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let cl = srv.client();
+    let agent = srv.agent(None);
+    let url = srv.url();
+    let store = srv.store.path().to_owned();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let repo = owner
+            .user(&user_name)
+            .repository(&"public".parse().unwrap());
+        assert!(repo
+            .create(&RepositoryConfig {
+                public: true,
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("a.txt"), "text").unwrap();
+        _ = repo
+            .tag(&"0.1.0".parse().unwrap())
+            .create_from_path_unsigned(pkg.path())
+            .expect("failed to create a tag and upload the tree");
+        assert_eq!(corrupt_contents(&store, b"text", b"test"), 1);
+
+        // Payload files not matching their digests abort the export instead of being listed
+        // in the manifest with the digest of their corrupted contents
+        let mut buf = vec![];
+        let res = agent
+            .get(&format!(
+                "{url}/api/v0.1.0/testuser/public/_tag/0.1.0/bagit"
+            ))
+            .call()
+            .and_then(|res| Ok(res.into_reader().read_to_end(&mut buf)?));
+        assert!(res.is_err(), "corrupted contents exported");
+        assert!(!buf.windows(4).any(|w| w == b"test"));
+    })
+    .await;
+
+    srv.stop().await;
+}
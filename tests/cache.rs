@@ -0,0 +1,93 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{RepositoryConfig, UserRecord};
+
+use async_std::task::spawn_blocking;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+#[async_std::test]
+async fn name_addressed_responses_are_revalidated() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let cl = srv.client();
+    let agent = srv.agent(None);
+    let url = srv.url();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let repo = owner
+            .user(&user_name)
+            .repository(&"public".parse().unwrap());
+        assert!(repo
+            .create(&RepositoryConfig {
+                public: true,
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+        let tag = repo.tag(&"0.1.0".parse().unwrap());
+        let publish = |contents: &str| {
+            let pkg = tempdir().expect("failed to create temporary package directory");
+            std::fs::write(pkg.path().join("test-file.txt"), contents).unwrap();
+            _ = tag
+                .create_from_path_unsigned(pkg.path())
+                .expect("failed to create a tag and upload the tree");
+        };
+
+        let paths = [
+            "/api/v0.1.0/testuser/public/_tag/0.1.0",
+            "/api/v0.1.0/testuser/public/_tag/0.1.0/tree/test-file.txt",
+        ];
+        let get = |path: &str| {
+            let res = agent
+                .get(&format!("{url}{path}"))
+                .call()
+                .unwrap_or_else(|e| panic!("failed to get `{path}`: {e}"));
+            assert_eq!(res.header("cache-control"), Some("public, no-cache"));
+            res.header("etag").expect("entity tag missing").to_string()
+        };
+        let revalidate = |path: &str, etag: &str| match agent
+            .get(&format!("{url}{path}"))
+            .set("If-None-Match", etag)
+            .call()
+        {
+            Ok(res) => res.status(),
+            Err(ureq::Error::Status(status, _)) => status,
+            Err(e) => panic!("failed to revalidate `{path}`: {e}"),
+        };
+
+        // Tags and tree nodes are addressed by name, hence they are revalidated by their
+        // entity tags, even if no vulnerability policy is set
+        publish("text");
+        let etags = paths.map(get);
+        for (path, etag) in paths.iter().zip(&etags) {
+            assert_eq!(revalidate(path, etag), 304);
+        }
+
+        // Names are bound to different contents once a tag is published again
+        _ = tag.delete().expect("failed to delete tag");
+        publish("other");
+        for (path, etag) in paths.iter().zip(&etags) {
+            assert_eq!(revalidate(path, etag), 200);
+            assert_ne!(get(path), *etag);
+        }
+    })
+    .await;
+
+    srv.stop().await;
+}
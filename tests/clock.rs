@@ -0,0 +1,112 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{RepositoryConfig, UserRecord};
+use drawbridge_server::Clock;
+
+use std::io::Read;
+use std::time::{Duration, SystemTime};
+
+use async_std::task::spawn_blocking;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+/// A [Clock] always returning the same time.
+#[derive(Debug)]
+struct FixedClock(SystemTime);
+
+impl Clock for FixedClock {
+    fn now(&self) -> SystemTime {
+        self.0
+    }
+}
+
+#[async_std::test]
+async fn expired_tokens() {
+    let oidc = Oidc::start();
+    // NOTE: Tokens issued by the test provider are valid for an hour.
+    let srv = Server::start(&oidc, None, |app| {
+        app.clock(FixedClock(
+            SystemTime::now() + Duration::from_secs(2 * 3600),
+        ))
+    })
+    .await;
+
+    let cl = srv.client();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+        let err = owner
+            .user(&"testuser".parse().unwrap())
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect_err("expired token was accepted");
+        assert!(format!("{err:#}").contains("401"), "{err:#}");
+    })
+    .await;
+
+    srv.stop().await;
+}
+
+#[async_std::test]
+async fn bagging_date() {
+    // 2001-02-03T04:05:06Z
+    let now = SystemTime::UNIX_EPOCH + Duration::from_secs(981_173_106);
+
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app.clock(FixedClock(now))).await;
+
+    let cl = srv.client();
+    let agent = srv.agent(None);
+    let url = srv.url();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let repo = owner
+            .user(&user_name)
+            .repository(&"public".parse().unwrap());
+        assert!(repo
+            .create(&RepositoryConfig {
+                public: true,
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("test-file.txt"), "text").unwrap();
+        _ = repo
+            .tag(&"0.1.0".parse().unwrap())
+            .create_from_path_unsigned(pkg.path())
+            .expect("failed to create a tag and upload the tree");
+
+        let mut bag = vec![];
+        _ = agent
+            .get(&format!(
+                "{url}/api/v0.1.0/testuser/public/_tag/0.1.0/bagit"
+            ))
+            .call()
+            .expect("failed to export tag")
+            .into_reader()
+            .read_to_end(&mut bag)
+            .unwrap();
+        let bag = String::from_utf8_lossy(&bag);
+        assert!(bag.contains("Bagging-Date: 2001-02-03\n"), "{bag}");
+    })
+    .await;
+
+    srv.stop().await;
+}
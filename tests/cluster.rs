@@ -0,0 +1,278 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{RepositoryConfig, TagEntry, Tree, UserRecord};
+use drawbridge_server::cluster::ClusterConfig;
+use drawbridge_server::store::Store;
+use drawbridge_server::Clock;
+
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use async_std::task::{sleep, spawn, spawn_blocking};
+use cap_async_std::ambient_authority;
+use cap_async_std::fs_utf8::Dir;
+use futures::future::join_all;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+/// Minimum TTL of leases accepted by clusters.
+const LEASE_TTL: Duration = Duration::from_secs(3);
+
+/// A [Clock], which may be advanced, e.g. to expire leases.
+#[derive(Clone, Debug, Default)]
+struct ManualClock(Arc<AtomicU64>);
+
+impl ManualClock {
+    fn advance(&self, by: Duration) {
+        _ = self.0.fetch_add(by.as_secs(), Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now() + Duration::from_secs(self.0.load(Ordering::SeqCst))
+    }
+}
+
+/// Returns the configuration of cluster instance `instance`.
+fn cluster(instance: &str) -> ClusterConfig {
+    ClusterConfig {
+        instance: instance.into(),
+        lease_ttl: LEASE_TTL,
+        scrub_interval: None,
+    }
+}
+
+/// Opens the store at `path` like an instance of a cluster sharing it.
+async fn open(path: &Path) -> Store {
+    let root = Dir::open_ambient_dir(path.to_str().unwrap(), ambient_authority())
+        .await
+        .expect("failed to open store directory");
+    Store::new_shared(root).await.expect("failed to open store")
+}
+
+/// Returns the current holder of lease `name` in the store at `path`, if any.
+async fn holder(path: &Path, name: &str) -> Option<String> {
+    open(path)
+        .await
+        .lease_holder(name, SystemTime::now())
+        .await
+        .expect("failed to read lease")
+}
+
+#[async_std::test]
+async fn leases() {
+    let dir = tempdir().expect("failed to create temporary store directory");
+    let mut instances = vec![];
+    for _ in 0..8 {
+        instances.push(Arc::new(open(dir.path()).await));
+    }
+    let name = |i: usize| format!("instance-{i}");
+
+    // Makes all instances except `except` compete for lease `lease` at `at` and returns the
+    // ones, which acquired it.
+    let compete = |lease: &str, at: SystemTime, except: Option<usize>| {
+        join_all(
+            instances
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| Some(i) != except)
+                .map(|(i, store)| {
+                    let store = Arc::clone(store);
+                    let lease = lease.to_string();
+                    spawn(async move {
+                        store
+                            .acquire_lease(&lease, &name(i), at, LEASE_TTL)
+                            .await
+                            .expect("failed to acquire lease")
+                            .then_some(i)
+                    })
+                }),
+        )
+    };
+
+    let now = SystemTime::now();
+    let secs = Duration::from_secs;
+    for round in 0..10 {
+        let lease = format!("test-{round}");
+
+        // Exactly one of the instances acquires the lease
+        let acquired: Vec<_> = compete(&lease, now, None)
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+        assert_eq!(acquired.len(), 1, "{acquired:?}");
+        let holder = acquired[0];
+        let store = &instances[holder];
+
+        // The holder renews the lease, which others cannot take over before it expires
+        assert!(store
+            .acquire_lease(&lease, &name(holder), now + secs(1), LEASE_TTL)
+            .await
+            .unwrap());
+        let acquired: Vec<_> = compete(&lease, now + secs(2), Some(holder))
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+        assert!(
+            acquired.is_empty(),
+            "lease was taken over before it expired by {acquired:?}"
+        );
+
+        // Once the lease of the crashed holder expires, exactly one instance takes it over
+        let expired = now + secs(1) + LEASE_TTL + secs(1);
+        let acquired: Vec<_> = compete(&lease, expired, Some(holder))
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+        assert_eq!(acquired.len(), 1, "{acquired:?}");
+        let successor = acquired[0];
+        assert!(
+            !store
+                .acquire_lease(&lease, &name(holder), expired, LEASE_TTL)
+                .await
+                .unwrap(),
+            "lease was renewed after it was taken over"
+        );
+
+        // Leases are only released by their holder
+        store.release_lease(&lease, &name(holder)).await.unwrap();
+        assert!(!store
+            .acquire_lease(&lease, &name(holder), expired, LEASE_TTL)
+            .await
+            .unwrap());
+        instances[successor]
+            .release_lease(&lease, &name(successor))
+            .await
+            .unwrap();
+        assert!(store
+            .acquire_lease(&lease, &name(holder), expired, LEASE_TTL)
+            .await
+            .unwrap());
+    }
+}
+
+#[async_std::test]
+async fn leader_takeover() {
+    let store = tempdir().expect("failed to create temporary store directory");
+    // NOTE: The lease left behind by the crashed leader is long-lived, so that it does not
+    // expire before the clock of the successor is advanced.
+    assert!(open(store.path())
+        .await
+        .acquire_lease(
+            "leader",
+            "crashed",
+            SystemTime::now(),
+            Duration::from_secs(60)
+        )
+        .await
+        .unwrap());
+
+    let oidc = Oidc::start();
+    let clock = ManualClock::default();
+    let srv = Server::start_in(store, &oidc, None, |app| {
+        app.cluster(cluster("successor")).clock(clock.clone())
+    })
+    .await;
+
+    // Leadership is only taken over once the lease of the crashed leader expires
+    sleep(LEASE_TTL).await;
+    assert_eq!(
+        holder(srv.store.path(), "leader").await.as_deref(),
+        Some("crashed")
+    );
+    clock.advance(Duration::from_secs(61));
+    for _ in 0..50 {
+        if holder(srv.store.path(), "leader").await.as_deref() == Some("successor") {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(
+        holder(srv.store.path(), "leader").await.as_deref(),
+        Some("successor")
+    );
+
+    srv.stop().await;
+}
+
+#[async_std::test]
+async fn concurrent_publication() {
+    let oidc = Oidc::start();
+    let a = Server::start(&oidc, None, |app| app.cluster(cluster("a"))).await;
+    let b = a
+        .start_sharing(&oidc, None, |app| app.cluster(cluster("b")))
+        .await;
+
+    let clients: Vec<_> = (0..8)
+        .map(|i| if i % 2 == 0 { a.client() } else { b.client() })
+        .collect();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let user_name = "testuser".parse().unwrap();
+        let repo_name = "repo".parse().unwrap();
+        let tag_name = "0.1.0".parse().unwrap();
+        let owner = clients[0].clone().token(token.clone()).build().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        assert!(owner
+            .user(&user_name)
+            .repository(&repo_name)
+            .create(&RepositoryConfig::default())
+            .expect("failed to create repository"));
+
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("test-file.txt"), "text").unwrap();
+        let tree = Tree::from_path_sync(pkg.path()).unwrap();
+        let entry = TagEntry::Unsigned(tree.root());
+
+        // Publications of the same tag through both instances are serialized, so that
+        // exactly one of them creates the tag and all others find it published
+        let created: Vec<_> = thread::scope(|s| {
+            let threads: Vec<_> = clients
+                .iter()
+                .map(|cl| {
+                    let (token, entry) = (token.clone(), &entry);
+                    let (user_name, repo_name, tag_name) = (&user_name, &repo_name, &tag_name);
+                    s.spawn(move || {
+                        cl.clone()
+                            .token(token)
+                            .build()
+                            .unwrap()
+                            .user(user_name)
+                            .repository(repo_name)
+                            .tag(tag_name)
+                            .create(entry)
+                            .expect("failed to publish tag")
+                    })
+                })
+                .collect();
+            threads
+                .into_iter()
+                .map(|t| t.join().expect("publishing thread panicked"))
+                .collect()
+        });
+        assert_eq!(created.iter().filter(|&&c| c).count(), 1, "{created:?}");
+    })
+    .await;
+
+    b.stop().await;
+    a.stop().await;
+}
@@ -0,0 +1,390 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Helpers shared by integration tests, which run a server against a mock OpenID Connect
+//! provider and access it using clients authenticated by tokens or client certificates.
+
+#![allow(dead_code)]
+
+use std::io::BufReader;
+use std::net::{SocketAddr, TcpStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use drawbridge_client::{Client, ClientBuilder};
+use drawbridge_server::{App, Builder, OidcConfig, Steward, TlsConfig};
+
+use async_std::net::{Ipv4Addr, TcpListener};
+use async_std::task::{block_on, spawn, JoinHandle};
+use futures::channel::oneshot::{channel, Sender};
+use futures::StreamExt;
+use http_types::convert::{json, Serialize};
+use http_types::{Body, Response, StatusCode};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use openidconnect::core::{
+    CoreJwsSigningAlgorithm, CoreProviderMetadata, CoreResponseType, CoreSubjectIdentifierType,
+};
+use openidconnect::{
+    AuthUrl, EmptyAdditionalProviderMetadata, IssuerUrl, JsonWebKeySetUrl, ResponseTypes,
+};
+use rsa::{pkcs1::EncodeRsaPrivateKey, PublicKeyParts};
+use rustls::{Certificate, ClientConnection, PrivateKey, RootCertStore, StreamOwned};
+use rustls_pemfile::Item::*;
+use tempfile::{tempdir, TempDir};
+
+/// Scopes granting full access to the resources of the subject.
+pub const SCOPES: &str =
+    "openid manage:drawbridge_users manage:drawbridge_repositories manage:drawbridge_tags";
+
+/// Audience of tokens issued by [Oidc].
+pub const AUDIENCE: &str = "testserver";
+
+#[derive(Clone, Debug, Serialize)]
+struct TokenClaims {
+    #[serde(rename = "iss")]
+    issuer: String,
+    #[serde(rename = "aud")]
+    audience: Vec<String>,
+    #[serde(rename = "sub")]
+    subject: String,
+    #[serde(rename = "iat")]
+    issued_at: u64,
+    #[serde(rename = "exp")]
+    expires_at: u64,
+    scope: String,
+}
+
+/// Mock OpenID Connect provider serving its discovery document and key set.
+///
+/// The provider runs on a dedicated thread, so that it is served while the server blocks on
+/// the discovery of its metadata.
+pub struct Oidc {
+    issuer: String,
+    key: EncodingKey,
+    stop: Option<Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Oidc {
+    pub fn start() -> Self {
+        let lis = block_on(TcpListener::bind((Ipv4Addr::LOCALHOST, 0)))
+            .expect("failed to bind to address");
+        let addr = lis.local_addr().unwrap();
+
+        let raw = rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 2048)
+            .expect("failed to generate a key");
+        let der = raw.to_pkcs1_der().expect("failed to encode key");
+        let key = EncodingKey::from_rsa_der(der.as_bytes());
+        let public = raw.to_public_key();
+        let keys = drawbridge_jose::jwk::JwkSet {
+            keys: vec![drawbridge_jose::jwk::Jwk {
+                key: drawbridge_jose::jwk::Key::Rsa {
+                    prv: None,
+                    n: public.n().to_bytes_be().into(),
+                    e: public.e().to_bytes_be().into(),
+                },
+                prm: drawbridge_jose::jwk::Parameters {
+                    kid: Some("signkey".into()),
+                    ..Default::default()
+                },
+            }],
+        };
+
+        let (tx, rx) = channel::<()>();
+        let thread = thread::spawn(move || {
+            block_on(
+                lis.incoming()
+                    .take_until(rx)
+                    .for_each_concurrent(None, |stream| async {
+                        let keys = &keys;
+                        _ = async_h1::accept(
+                            stream.expect("failed to initialize stream"),
+                            |req| async move {
+                                fn json_response(
+                                    body: &impl Serialize,
+                                ) -> Result<Response, http_types::Error>
+                                {
+                                    let mut res = Response::new(StatusCode::Ok);
+                                    res.insert_header("Content-Type", "application/json");
+                                    res.set_body(Body::from_json(&json!(body))?);
+                                    Ok(res)
+                                }
+
+                                let url = format!("http://{addr}/");
+                                match req.url().path() {
+                                    "/.well-known/openid-configuration" => {
+                                        json_response(&CoreProviderMetadata::new(
+                                            IssuerUrl::new(url.clone()).unwrap(),
+                                            AuthUrl::new(format!("{url}authorize")).unwrap(),
+                                            JsonWebKeySetUrl::new(format!("{url}jwks")).unwrap(),
+                                            vec![ResponseTypes::new(vec![CoreResponseType::Code])],
+                                            vec![CoreSubjectIdentifierType::Pairwise],
+                                            vec![CoreJwsSigningAlgorithm::RsaSsaPssSha256],
+                                            EmptyAdditionalProviderMetadata {},
+                                        ))
+                                    }
+                                    "/jwks" => json_response(keys),
+                                    p => panic!("Unsupported path requested: `{p}`"),
+                                }
+                            },
+                        )
+                        .await;
+                    }),
+            )
+        });
+        Self {
+            issuer: format!("http://{addr}/"),
+            key,
+            stop: Some(tx),
+            thread: Some(thread),
+        }
+    }
+
+    /// Returns the configuration of a server verifying tokens issued by the provider.
+    pub fn config(&self) -> OidcConfig {
+        OidcConfig {
+            audience: AUDIENCE.into(),
+            issuer: self.issuer.parse().unwrap(),
+        }
+    }
+
+    /// Issues a token valid for an hour to `subject` granting `scope`.
+    pub fn token(&self, subject: &str, scope: &str) -> String {
+        let now = SystemTime::now();
+        let secs = |t: SystemTime| t.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+        let mut header = Header::new(jsonwebtoken::Algorithm::RS256);
+        header.kid = Some("signkey".into());
+        encode(
+            &header,
+            &TokenClaims {
+                issuer: self.issuer.clone(),
+                audience: vec![AUDIENCE.into()],
+                subject: subject.into(),
+                issued_at: secs(now),
+                expires_at: secs(now + Duration::from_secs(3600)),
+                scope: scope.into(),
+            },
+            &self.key,
+        )
+        .expect("failed to sign token")
+    }
+}
+
+impl Drop for Oidc {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            _ = stop.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("OpenID Connect provider panicked");
+        }
+    }
+}
+
+pub fn certificates(pem: &[u8]) -> Vec<Certificate> {
+    rustls_pemfile::certs(&mut BufReader::new(pem))
+        .unwrap()
+        .into_iter()
+        .map(Certificate)
+        .collect()
+}
+
+pub fn private_key(pem: &[u8]) -> PrivateKey {
+    match rustls_pemfile::read_one(&mut BufReader::new(pem)).unwrap() {
+        Some(RSAKey(buf) | PKCS8Key(buf) | ECKey(buf)) => PrivateKey(buf),
+        item => panic!("unsupported key type `{:?}`", item),
+    }
+}
+
+/// Returns the server TLS configuration trusting client certificates issued by the test CA
+/// and `steward`, if any.
+pub fn tls(steward: Option<&Steward>) -> TlsConfig {
+    TlsConfig::read_with_steward(
+        include_bytes!("../../testdata/server.crt").as_slice(),
+        include_bytes!("../../testdata/server.key").as_slice(),
+        include_bytes!("../../testdata/ca.crt").as_slice(),
+        steward,
+    )
+    .unwrap()
+}
+
+/// Returns the client certificate chain and key issued by the test CA.
+pub fn client_credentials() -> (Vec<Certificate>, PrivateKey) {
+    (
+        certificates(include_bytes!("../../testdata/client.crt")),
+        private_key(include_bytes!("../../testdata/client.key")),
+    )
+}
+
+/// Overwrites contents of all entities in `store`, which equal `from`, with `to` behind the
+/// back of the server and returns their number.
+pub fn corrupt_contents(store: &Path, from: &[u8], to: &[u8]) -> usize {
+    let mut corrupted = 0;
+    for entry in std::fs::read_dir(store).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            corrupted += corrupt_contents(&path, from, to);
+        } else if path.ends_with("content") && std::fs::read(&path).unwrap() == from {
+            std::fs::write(&path, to).unwrap();
+            corrupted += 1;
+        }
+    }
+    corrupted
+}
+
+/// Returns the roots trusting the test CA.
+fn roots() -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    certificates(include_bytes!("../../testdata/ca.crt"))
+        .iter()
+        .try_for_each(|cert| roots.add(cert))
+        .unwrap();
+    roots
+}
+
+/// Builds the application in `store` using `configure`.
+async fn build(
+    store: &Path,
+    oidc: &Oidc,
+    steward: Option<&Steward>,
+    configure: impl FnOnce(Builder<std::path::PathBuf>) -> Builder<std::path::PathBuf>,
+) -> App {
+    let builder = configure(App::builder(store.to_owned(), tls(steward), oidc.config()));
+    builder.build().await.expect("failed to build application")
+}
+
+/// Server listening on a local port, which is stopped on drop.
+pub struct Server {
+    pub addr: SocketAddr,
+    /// Store of the server, which may be shared with other servers started by
+    /// [Self::start_sharing]
+    pub store: Arc<TempDir>,
+    stop: Option<Sender<()>>,
+    task: Option<JoinHandle<()>>,
+}
+
+impl Server {
+    /// Builds the application in a temporary store using `configure` and serves it.
+    pub async fn start(
+        oidc: &Oidc,
+        steward: Option<&Steward>,
+        configure: impl FnOnce(Builder<std::path::PathBuf>) -> Builder<std::path::PathBuf>,
+    ) -> Self {
+        let store = tempdir().expect("failed to create temporary store directory");
+        Self::start_in(store, oidc, steward, configure).await
+    }
+
+    /// Builds the application in `store` using `configure` and serves it.
+    pub async fn start_in(
+        store: TempDir,
+        oidc: &Oidc,
+        steward: Option<&Steward>,
+        configure: impl FnOnce(Builder<std::path::PathBuf>) -> Builder<std::path::PathBuf>,
+    ) -> Self {
+        let app = build(store.path(), oidc, steward, configure).await;
+        Self::serve(app, store).await
+    }
+
+    /// Builds another application in the store of the server using `configure` and serves it,
+    /// e.g. as another instance of a cluster.
+    ///
+    /// The returned server must be stopped before the server owning the store.
+    pub async fn start_sharing(
+        &self,
+        oidc: &Oidc,
+        steward: Option<&Steward>,
+        configure: impl FnOnce(Builder<std::path::PathBuf>) -> Builder<std::path::PathBuf>,
+    ) -> Self {
+        let app = build(self.store.path(), oidc, steward, configure).await;
+        Self::serve_shared(app, self.store.clone()).await
+    }
+
+    /// Serves `app` backed by `store`.
+    pub async fn serve(app: App, store: TempDir) -> Self {
+        Self::serve_shared(app, Arc::new(store)).await
+    }
+
+    async fn serve_shared(app: App, store: Arc<TempDir>) -> Self {
+        let lis = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+            .await
+            .expect("failed to bind to address");
+        let addr = lis.local_addr().unwrap();
+        let (tx, rx) = channel::<()>();
+        let task = spawn(async move {
+            lis.incoming()
+                .take_until(rx)
+                .for_each_concurrent(None, |stream| async {
+                    // NOTE: Clients may close connections before request bodies are read,
+                    // which is not a failure of the server.
+                    if let Err(e) = app
+                        .handle(stream.expect("failed to initialize stream"))
+                        .await
+                    {
+                        eprintln!("failed to handle stream: {e:?}");
+                    }
+                })
+                .await
+        });
+        Self {
+            addr,
+            store,
+            stop: Some(tx),
+            task: Some(task),
+        }
+    }
+
+    /// Returns a builder of clients of the server trusting the test CA.
+    pub fn client(&self) -> ClientBuilder {
+        Client::builder(
+            format!("https://localhost:{}", self.addr.port())
+                .parse()
+                .unwrap(),
+        )
+        .roots(roots())
+    }
+
+    /// Returns an agent for plain HTTP requests to the server, e.g. at [Self::url], trusting the
+    /// test CA and presenting `credentials`, if specified.
+    pub fn agent(&self, credentials: Option<(Vec<Certificate>, PrivateKey)>) -> ureq::Agent {
+        let tls = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots());
+        let tls = match credentials {
+            Some((cert, key)) => tls.with_single_cert(cert, key).unwrap(),
+            None => tls.with_no_client_auth(),
+        };
+        ureq::AgentBuilder::new().tls_config(Arc::new(tls)).build()
+    }
+
+    /// Opens a TLS connection to the server, e.g. to send raw requests with bodies not matching
+    /// their headers, which [Self::agent] refuses to send.
+    pub fn connect(&self) -> StreamOwned<ClientConnection, TcpStream> {
+        let tls = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots())
+            .with_no_client_auth();
+        let conn = ClientConnection::new(Arc::new(tls), "localhost".try_into().unwrap())
+            .expect("failed to initialize TLS connection");
+        let sock = TcpStream::connect(self.addr).expect("failed to connect to server");
+        StreamOwned::new(conn, sock)
+    }
+
+    /// Returns the base URL of the server.
+    pub fn url(&self) -> String {
+        format!("https://localhost:{}", self.addr.port())
+    }
+
+    /// Stops the server and returns its store, e.g. to serve it again, if no other server
+    /// shares it.
+    pub async fn stop(mut self) -> Option<TempDir> {
+        if let Some(stop) = self.stop.take() {
+            _ = stop.send(());
+        }
+        if let Some(task) = self.task.take() {
+            task.await;
+        }
+        Arc::try_unwrap(self.store).ok()
+    }
+}
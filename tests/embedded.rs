@@ -0,0 +1,64 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::SCOPES;
+
+use drawbridge_client::types::UserRecord;
+use drawbridge_server::{Builder, OidcClaims};
+
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::{Extension, Router};
+use tempfile::tempdir;
+use tower::ServiceExt;
+
+const SUBJECT: &str = "test|subject";
+
+/// Requests `router` to create a user for [SUBJECT] with `authorization` header, if specified,
+/// and returns the response status.
+async fn create_user(router: Router, authorization: Option<&str>) -> StatusCode {
+    let body = serde_json::to_vec(&UserRecord {
+        subject: SUBJECT.into(),
+        algorithms: None,
+    })
+    .unwrap();
+    let mut req = Request::put("/api/v0.1.0/testuser")
+        .header("content-type", "application/json")
+        .header("content-length", body.len());
+    if let Some(authorization) = authorization {
+        req = req.header("authorization", authorization);
+    }
+    router
+        .oneshot(req.body(Body::from(body)).unwrap())
+        .await
+        .expect("failed to handle request")
+        .status()
+}
+
+#[async_std::test]
+async fn inserted_claims() {
+    let store = tempdir().expect("failed to create temporary store directory");
+    let router = Builder::embedded(store.path().to_owned())
+        .build_router()
+        .await
+        .expect("failed to build router");
+
+    // Without a layer of the embedding application, clients are not authenticated
+    assert_eq!(
+        create_user(router.clone(), None).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        create_user(router.clone(), Some("Bearer forged")).await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    // Claims inserted by the embedding application authenticate clients
+    let claims = OidcClaims::new(SUBJECT, SCOPES.split(' ').map(String::from));
+    assert_eq!(
+        create_user(router.layer(Extension(claims)), None).await,
+        StatusCode::CREATED
+    );
+}
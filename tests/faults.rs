@@ -0,0 +1,85 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+//! Faults injected into the store underneath a running server, which must surface as errors
+//! instead of corrupted contents and must not outlive the fault.
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{Error, RepositoryConfig, UserRecord};
+
+use std::path::{Path, PathBuf};
+
+use async_std::task::spawn_blocking;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+/// Returns the path of the contents of tree node `test-file.txt` in the store at `path`.
+fn content_path(path: &Path) -> PathBuf {
+    path.join("users/testuser/repos/repo/tags/0.1.0/tree/entries/test-file.txt/content")
+}
+
+#[async_std::test]
+async fn storage_faults() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let cl = srv.client();
+    let store = srv.store.path().to_owned();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let repo = owner.user(&user_name).repository(&"repo".parse().unwrap());
+        assert!(repo
+            .create(&RepositoryConfig::default())
+            .expect("failed to create repository"));
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("test-file.txt"), "text").unwrap();
+        let tag = repo.tag(&"0.1.0".parse().unwrap());
+        _ = tag
+            .create_from_path_unsigned(pkg.path())
+            .expect("failed to create a tag and upload the tree");
+        let node = tag.path(&"test-file.txt".parse().unwrap());
+        let path = content_path(&store);
+        assert_eq!(std::fs::read(&path).unwrap(), b"text");
+
+        let storage_failure = |fault: &str| {
+            let e = node
+                .get_bytes(4)
+                .expect_err(&format!("{fault} contents were served"));
+            assert_eq!(
+                e.downcast_ref::<Error>(),
+                Some(&Error::StorageFailure),
+                "{e:#}"
+            );
+        };
+
+        // Contents, which cannot be read, fail requests as storage failures
+        std::fs::remove_file(&path).unwrap();
+        std::fs::create_dir(&path).unwrap();
+        storage_failure("unreadable");
+
+        // Poisoned reads do not pass verification and fail requests the same way
+        std::fs::remove_dir(&path).unwrap();
+        std::fs::write(&path, "test").unwrap();
+        storage_failure("poisoned");
+
+        // Requests succeed again once the fault is gone
+        std::fs::write(&path, "text").unwrap();
+        assert_eq!(node.get_bytes(4).expect("failed to get file").1, b"text");
+    })
+    .await;
+
+    srv.stop().await;
+}
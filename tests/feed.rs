@@ -0,0 +1,69 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{RepositoryConfig, UserRecord};
+
+use async_std::task::spawn_blocking;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+#[async_std::test]
+async fn hostile_host() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let cl = srv.client();
+    let agent = srv.agent(None);
+    let url = format!("{}/api/v0.1.0/testuser/public/_tag.atom", srv.url());
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let repo = owner
+            .user(&user_name)
+            .repository(&"public".parse().unwrap());
+        assert!(repo
+            .create(&RepositoryConfig {
+                public: true,
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("test-file.txt"), "text").unwrap();
+        _ = repo
+            .tag(&"0.1.0".parse().unwrap())
+            .create_from_path_unsigned(pkg.path())
+            .expect("failed to create a tag and upload the tree");
+
+        let feed = agent
+            .get(&url)
+            .set("Host", r#"evil.example"/><link href="https://attacker.example"#)
+            .call()
+            .expect("failed to get feed")
+            .into_string()
+            .expect("failed to read feed");
+        assert!(!feed.contains(r#"href="https://attacker.example""#), "{feed}");
+        assert!(
+            feed.contains(
+                r#"<link rel="self" href="https://evil.example&quot;/&gt;&lt;link href=&quot;https://attacker.example/api/v"#
+            ),
+            "{feed}"
+        );
+        assert_eq!(feed.matches("<link ").count(), 2, "{feed}");
+    })
+    .await;
+
+    srv.stop().await;
+}
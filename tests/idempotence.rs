@@ -0,0 +1,201 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::digest::{AlgorithmRules, Algorithms, ContentDigest};
+use drawbridge_client::types::{
+    Error, RepositoryConfig, TagEntry, Tree, TreeContent, TreePath, UserRecord,
+};
+use drawbridge_client::Result;
+
+use std::fmt::Debug;
+use std::thread;
+
+use async_std::task::spawn_blocking;
+use serde::Serialize;
+use tempfile::{tempdir, TempDir};
+
+const SUBJECT: &str = "test|subject";
+
+/// Returns the error, which the server rejected the request with.
+fn error(res: Result<impl Debug>) -> Error {
+    let e = res.expect_err("request was accepted");
+    e.downcast_ref::<Error>()
+        .unwrap_or_else(|| panic!("unexpected error: {e:#}"))
+        .clone()
+}
+
+/// Returns the digest of `val` encoded as JSON, like clients compute it on upload.
+fn digest(val: &impl Serialize) -> ContentDigest {
+    let buf = serde_json::to_vec(val).unwrap();
+    Algorithms::default().read_sync(buf.as_slice()).unwrap().1
+}
+
+/// Returns a package directory containing `test-file.txt` with `contents`.
+fn package(contents: &str) -> TempDir {
+    let pkg = tempdir().expect("failed to create temporary package directory");
+    std::fs::write(pkg.path().join("test-file.txt"), contents).unwrap();
+    pkg
+}
+
+#[async_std::test]
+async fn idempotent_puts() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let cl = srv.client();
+    let token = oidc.token(SUBJECT, SCOPES);
+    let store = srv.store.clone();
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+
+        // Repeated PUTs of identical entities succeed without creating them again, while PUTs
+        // of different ones are rejected with both digests
+        let user = owner.user(&"testuser".parse().unwrap());
+        let record = UserRecord {
+            subject: SUBJECT.into(),
+            algorithms: None,
+        };
+        assert!(user.create(&record).expect("failed to create user"));
+        assert!(!user
+            .create(&record)
+            .expect("failed to repeat user creation"));
+        let other = UserRecord {
+            algorithms: Some(AlgorithmRules::default()),
+            ..record.clone()
+        };
+        assert_eq!(
+            error(user.create(&other)),
+            Error::DigestConflict {
+                existing: digest(&record),
+                requested: digest(&other),
+            }
+        );
+        assert_eq!(user.get().unwrap(), record);
+
+        let repo = user.repository(&"repo".parse().unwrap());
+        let config = RepositoryConfig::default();
+        assert!(repo.create(&config).expect("failed to create repository"));
+        assert!(!repo
+            .create(&config)
+            .expect("failed to repeat repository creation"));
+        let other = RepositoryConfig {
+            public: true,
+            ..config.clone()
+        };
+        assert_eq!(
+            error(repo.create(&other)),
+            Error::DigestConflict {
+                existing: digest(&config),
+                requested: digest(&other),
+            }
+        );
+
+        let pkg = package("text");
+        let tree = Tree::from_path_sync(pkg.path()).unwrap();
+        let other_pkg = package("other");
+        let other_tree = Tree::from_path_sync(other_pkg.path()).unwrap();
+        let tag = repo.tag(&"0.1.0".parse().unwrap());
+        let entry = TagEntry::Unsigned(tree.root());
+        assert!(tag.create(&entry).expect("failed to create tag"));
+        assert!(!tag.create(&entry).expect("failed to repeat tag creation"));
+        let other = TagEntry::Unsigned(other_tree.root());
+        assert_eq!(
+            error(tag.create(&other)),
+            Error::DigestConflict {
+                existing: digest(&entry),
+                requested: digest(&other),
+            }
+        );
+
+        let root = tree.root();
+        let buf = match root.content {
+            TreeContent::Directory(ref buf) => buf.as_slice(),
+            _ => panic!("tree root is not a directory"),
+        };
+        let node = tag.path(&TreePath::ROOT);
+        assert!(node
+            .create_from(&root.meta, buf)
+            .expect("failed to upload root directory"));
+        assert!(!node
+            .create_from(&root.meta, buf)
+            .expect("failed to repeat upload of root directory"));
+
+        // Repeated uploads of file nodes leave their stored contents untouched
+        let path: TreePath = "test-file.txt".parse().unwrap();
+        let node = tag.path(&path);
+        let meta = &tree[&path].meta;
+        assert!(node
+            .create_from(meta, "text".as_bytes())
+            .expect("failed to upload file"));
+        let content = store
+            .path()
+            .join("users/testuser/repos/repo/tags/0.1.0/tree/entries/test-file.txt/content");
+        let modified = std::fs::metadata(&content).unwrap().modified().unwrap();
+        assert!(!node
+            .create_from(meta, "text".as_bytes())
+            .expect("failed to repeat upload of file"));
+        assert_eq!(
+            std::fs::metadata(&content).unwrap().modified().unwrap(),
+            modified
+        );
+        let other = &other_tree[&path].meta;
+        assert_eq!(
+            error(node.create_from(other, "other".as_bytes())),
+            Error::DigestConflict {
+                existing: meta.hash.clone(),
+                requested: other.hash.clone(),
+            }
+        );
+        assert_eq!(node.get_bytes(4).unwrap().1, b"text");
+
+        // Of concurrent conflicting publications, exactly one creates the tag, publications of
+        // the same entry find it existing and all others conflict with it
+        let tag = repo.tag(&"0.2.0".parse().unwrap());
+        let entries = [entry, TagEntry::Unsigned(other_tree.root())];
+        let published: Vec<_> = thread::scope(|s| {
+            let threads: Vec<_> = (0..8)
+                .map(|i| {
+                    let (tag, entries) = (&tag, &entries);
+                    s.spawn(move || (i % 2, tag.create(&entries[i % 2])))
+                })
+                .collect();
+            threads
+                .into_iter()
+                .map(|t| t.join().expect("publishing thread panicked"))
+                .collect()
+        });
+        let created: Vec<_> = published
+            .iter()
+            .filter_map(|(i, res)| matches!(res, Ok(true)).then_some(*i))
+            .collect();
+        assert_eq!(created.len(), 1, "{published:?}");
+        let winner = created[0];
+        for (i, res) in published {
+            match res {
+                Ok(_) => assert_eq!(i, winner),
+                // NOTE: Publications may also find the tag still being created.
+                Err(e) => match e.downcast_ref::<Error>() {
+                    Some(Error::AlreadyExists) => {}
+                    Some(Error::DigestConflict { existing, .. }) if i != winner => {
+                        assert_eq!(*existing, digest(&entries[winner]))
+                    }
+                    _ => panic!("unexpected error: {e:#}"),
+                },
+            }
+        }
+        assert_eq!(
+            error(tag.create(&entries[1 - winner])),
+            Error::DigestConflict {
+                existing: digest(&entries[winner]),
+                requested: digest(&entries[1 - winner]),
+            }
+        );
+    })
+    .await;
+
+    srv.stop().await;
+}
@@ -0,0 +1,195 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{corrupt_contents, Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{RepositoryConfig, UserRecord};
+
+use std::collections::BTreeSet;
+use std::io::Read;
+
+use async_std::task::spawn_blocking;
+use ring::digest::{digest, SHA256};
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+/// Decodes the unsigned varint at the start of `buf` and advances `buf` past it.
+fn read_varint(buf: &mut &[u8]) -> usize {
+    let (mut n, mut shift) = (0, 0);
+    loop {
+        let (b, rest) = buf.split_first().expect("truncated varint");
+        *buf = rest;
+        n |= ((b & 0x7f) as usize) << shift;
+        if b & 0x80 == 0 {
+            return n;
+        }
+        shift += 7;
+    }
+}
+
+/// Formats binary CID `cid` using the lowercase base32 multibase encoding.
+fn format_cid(cid: &[u8]) -> String {
+    const ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+    let bits: Vec<_> = cid
+        .iter()
+        .flat_map(|b| (0..8).rev().map(move |i| b >> i & 1))
+        .collect();
+    let digits = bits
+        .chunks(5)
+        .map(|d| {
+            d.iter()
+                .chain([0; 5].iter())
+                .take(5)
+                .fold(0, |n, b| n << 1 | b)
+        })
+        .map(|n| ALPHABET[n as usize] as char);
+    format!("b{}", digits.collect::<String>())
+}
+
+#[async_std::test]
+async fn car_export() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let cl = srv.client();
+    let agent = srv.agent(None);
+    let url = srv.url();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let repo = owner
+            .user(&user_name)
+            .repository(&"public".parse().unwrap());
+        assert!(repo
+            .create(&RepositoryConfig {
+                public: true,
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+
+        // Large files are chunked into several leaves, which are shared by identical files
+        let big: Vec<_> = (0..600 * 1024).map(|i| (i % 251) as u8).collect();
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("a.txt"), "text").unwrap();
+        std::fs::write(pkg.path().join("big.bin"), &big).unwrap();
+        std::fs::write(pkg.path().join("copy.bin"), &big).unwrap();
+        std::fs::create_dir(pkg.path().join("dir")).unwrap();
+        std::fs::write(pkg.path().join("dir").join("empty.txt"), "").unwrap();
+        _ = repo
+            .tag(&"0.1.0".parse().unwrap())
+            .create_from_path_unsigned(pkg.path())
+            .expect("failed to create a tag and upload the tree");
+
+        let res = agent
+            .get(&format!("{url}/api/v0.1.0/testuser/public/_tag/0.1.0/ipfs"))
+            .call()
+            .expect("failed to export tag");
+        assert_eq!(res.content_type(), "application/vnd.ipld.car");
+        let root = res
+            .header("ipfs-root")
+            .expect("root CID missing")
+            .to_string();
+        let mut car = vec![];
+        _ = res.into_reader().read_to_end(&mut car).unwrap();
+
+        let mut buf = car.as_slice();
+        let len = read_varint(&mut buf);
+        let (header, mut buf) = buf.split_at(len);
+        let mut cids = BTreeSet::new();
+        let mut raw = 0;
+        while !buf.is_empty() {
+            let len = read_varint(&mut buf);
+            let (block, rest) = buf.split_at(len);
+            buf = rest;
+
+            // NOTE: CIDs are CIDv1 with 32-byte SHA-256 multihashes.
+            let (cid, data) = block.split_at(36);
+            assert_eq!(cid[0], 0x01);
+            assert_eq!(&cid[2..4], &[0x12, 0x20]);
+            assert_eq!(
+                &cid[4..],
+                digest(&SHA256, data).as_ref(),
+                "invalid block digest"
+            );
+            assert!(cids.insert(format_cid(cid)), "duplicate block");
+            if cid[1] == 0x55 {
+                raw += data.len();
+            }
+        }
+        assert!(cids.contains(&root), "root block missing");
+        assert!(
+            header
+                .windows(36)
+                .any(|w| w[0] == 0x01 && format_cid(w) == root),
+            "root missing in header"
+        );
+        assert_eq!(raw, "text".len() + big.len());
+    })
+    .await;
+
+    srv.stop().await;
+}
+
+#[async_std::test]
+async fn corrupted_contents() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let cl = srv.client();
+    let agent = srv.agent(None);
+    let url = srv.url();
+    let store = srv.store.path().to_owned();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let repo = owner
+            .user(&user_name)
+            .repository(&"public".parse().unwrap());
+        assert!(repo
+            .create(&RepositoryConfig {
+                public: true,
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("a.txt"), "text").unwrap();
+        _ = repo
+            .tag(&"0.1.0".parse().unwrap())
+            .create_from_path_unsigned(pkg.path())
+            .expect("failed to create a tag and upload the tree");
+        assert_eq!(corrupt_contents(&store, b"text", b"test"), 1);
+
+        // Files not matching their digests are not hashed into the DAG
+        match agent
+            .get(&format!("{url}/api/v0.1.0/testuser/public/_tag/0.1.0/ipfs"))
+            .call()
+        {
+            Err(ureq::Error::Status(500, _)) => {}
+            res => panic!("unexpected export result: {res:?}"),
+        }
+    })
+    .await;
+
+    srv.stop().await;
+}
@@ -0,0 +1,133 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::jose::jwk::{JwkSet, Key};
+use drawbridge_client::types::{RepositoryConfig, UserRecord};
+use drawbridge_server::keys::{Kms, LocalKms};
+
+use std::thread::sleep;
+use std::time::Duration;
+
+use async_std::task::spawn_blocking;
+use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+#[test]
+fn wrapped_keys_are_bound_to_context() {
+    let kms = LocalKms::new(&[7; 32]).expect("failed to construct KMS");
+    let wrapped = kms
+        .wrap(b"users/testuser/repos/public", b"key")
+        .expect("failed to wrap key");
+    assert_eq!(
+        kms.unwrap(b"users/testuser/repos/public", &wrapped)
+            .expect("failed to unwrap key"),
+        b"key"
+    );
+    assert!(kms.unwrap(b"users/testuser/repos/other", &wrapped).is_err());
+    assert!(LocalKms::new(&[8; 32])
+        .unwrap()
+        .unwrap(b"users/testuser/repos/public", &wrapped)
+        .is_err());
+}
+
+#[async_std::test]
+async fn import_and_countersign() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| {
+        app.kms(LocalKms::new(&[7; 32]).expect("failed to construct KMS"))
+    })
+    .await;
+
+    let cl = srv.client();
+    let agent = srv.agent(None);
+    let url = srv.url();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token.clone()).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let repo = owner
+            .user(&user_name)
+            .repository(&"public".parse().unwrap());
+        assert!(repo
+            .create(&RepositoryConfig {
+                public: true,
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+
+        // Imported PEM-encoded keys are served as JWKs
+        let pem = include_str!("../testdata/client.key");
+        let keys_url = format!("{url}/api/v0.1.0/testuser/public/_keys");
+        let res = agent
+            .put(&keys_url)
+            .set("authorization", &format!("Bearer {token}"))
+            .send_string(pem)
+            .expect("failed to import key");
+        assert_eq!(res.status(), 201);
+        let pkcs8 = rustls_pemfile::pkcs8_private_keys(&mut pem.as_bytes())
+            .unwrap()
+            .remove(0);
+        let point = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8)
+            .unwrap()
+            .public_key()
+            .as_ref()
+            .to_vec();
+        let set: JwkSet = agent
+            .get(&keys_url)
+            .call()
+            .expect("failed to get keys")
+            .into_json()
+            .unwrap();
+        assert_eq!(set.keys.len(), 1);
+        let kid = set.keys[0].prm.kid.clone().expect("key ID missing");
+        match &set.keys[0].key {
+            Key::EllipticCurve { x, y, .. } => {
+                assert_eq!([&point[1..33], &point[33..]], [&x[..], &y[..]])
+            }
+            key => panic!("unexpected key {key:?}"),
+        }
+
+        match agent
+            .put(&keys_url)
+            .set("authorization", &format!("Bearer {token}"))
+            .send_string("-----BEGIN CERTIFICATE-----\nAAAA\n-----END CERTIFICATE-----\n")
+        {
+            Err(ureq::Error::Status(400, _)) => {}
+            res => panic!("expected a non-key PEM to be rejected, got {res:?}"),
+        }
+
+        // Published tags are countersigned with the imported key in background
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("test-file.txt"), "text").unwrap();
+        let tag = repo.tag(&"0.1.0".parse().unwrap());
+        _ = tag
+            .create_from_path_unsigned(pkg.path())
+            .expect("failed to create a tag and upload the tree");
+        for _ in 0..50 {
+            let signatures = tag.signatures().expect("failed to get signatures");
+            if signatures.verified {
+                assert_eq!(signatures.signatures.len(), 1);
+                assert_eq!(signatures.signatures[0].keys, [kid]);
+                return;
+            }
+            sleep(Duration::from_millis(100));
+        }
+        panic!("tag was not countersigned");
+    })
+    .await;
+
+    srv.stop().await;
+}
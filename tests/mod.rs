@@ -4,16 +4,18 @@
 use std::collections::HashMap;
 use std::time::{Duration, SystemTime};
 
-use drawbridge_client::mime::APPLICATION_OCTET_STREAM;
 use drawbridge_client::types::{RepositoryConfig, TreePath, UserRecord};
 use drawbridge_client::Client;
+use drawbridge_server::backup::{backup_store, restore_store, BackupTarget};
+use drawbridge_server::snapshots::export_store;
+use drawbridge_server::store::check_store;
 use drawbridge_server::{App, OidcConfig, TlsConfig};
 
 use async_std::fs::{create_dir, write};
 use async_std::net::{Ipv4Addr, TcpListener};
 use async_std::task::{spawn, spawn_blocking};
 use drawbridge_type::digest::Algorithms;
-use drawbridge_type::Meta;
+use drawbridge_type::{MediaType, Meta, RepositoryContext};
 use futures::channel::oneshot::channel;
 use futures::{join, try_join, StreamExt};
 use http_types::convert::{json, Serialize};
@@ -200,6 +202,7 @@ async fn app() {
     let srv_port = srv_lis.local_addr().unwrap().port();
 
     let store = tempdir().expect("failed to create temporary store directory");
+    let srv_store = store.path().to_owned();
 
     let (srv_tx, srv_rx) = channel::<()>();
     let srv = spawn(async move {
@@ -210,7 +213,7 @@ async fn app() {
         )
         .unwrap();
         let app = App::new(
-            store.path(),
+            &srv_store,
             tls,
             OidcConfig {
                 audience: oidc_audience.to_string(),
@@ -275,6 +278,7 @@ async fn app() {
         let user_name = "testuser".parse().unwrap();
         let user_record = UserRecord {
             subject: SUBJECT.into(),
+            algorithms: None,
         };
 
         let anon_user = anon_cl.user(&user_name);
@@ -298,6 +302,7 @@ async fn app() {
         assert!(oidc_user
             .create(&UserRecord {
                 subject: format!("{}other", user_record.subject),
+                algorithms: None,
             })
             .is_err());
         assert!(oidc_user
@@ -313,10 +318,16 @@ async fn app() {
         assert_eq!(oidc_user.get().expect("failed to get user"), user_record);
 
         let prv_repo_name = "test-repo-private".parse().unwrap();
-        let prv_repo_conf = RepositoryConfig { public: false };
+        let prv_repo_conf = RepositoryConfig {
+            public: false,
+            ..Default::default()
+        };
 
         let pub_repo_name = "test-repo-public".parse().unwrap();
-        let pub_repo_conf = RepositoryConfig { public: true };
+        let pub_repo_conf = RepositoryConfig {
+            public: true,
+            ..Default::default()
+        };
 
         let anon_prv_repo = anon_user.repository(&prv_repo_name);
         let cert_prv_repo = cert_user.repository(&prv_repo_name);
@@ -481,7 +492,7 @@ async fn app() {
             .map(|(size, hash)| Meta {
                 hash,
                 size,
-                mime: APPLICATION_OCTET_STREAM,
+                mime: MediaType::OCTET_STREAM,
             })
             .unwrap();
         let file_expected = (file_meta, "text".into());
@@ -525,4 +536,50 @@ async fn app() {
     // Stop server
     assert_eq!(srv_tx.send(()), Ok(()));
     assert!(matches!(join!(oidc, srv), ((), ())));
+
+    // Back up the store and restore it into an empty one
+    let backups = tempdir().expect("failed to create temporary backup directory");
+    let target = BackupTarget::Local(backups.path().into());
+    let backup = backup_store(store.path(), &target)
+        .await
+        .expect("failed to back up store")
+        .expect("no backup taken");
+    assert!(backup.full);
+    assert_eq!(
+        backup.repositories,
+        ["testuser/test-repo-private", "testuser/test-repo-public"]
+    );
+    assert!(backup_store(store.path(), &target)
+        .await
+        .expect("failed to back up store")
+        .is_none());
+
+    let restored = tempdir().expect("failed to create temporary store directory");
+    let report = restore_store(restored.path(), &target)
+        .await
+        .expect("failed to restore store");
+    assert_eq!(report.backups, 1);
+    assert_eq!(report.repositories, 2);
+    assert_eq!(report.tags, 2);
+    assert!(check_store(restored.path())
+        .await
+        .expect("failed to check restored store")
+        .is_consistent());
+    for repo in ["testuser/test-repo-private", "testuser/test-repo-public"] {
+        let cx: RepositoryContext = repo.parse().unwrap();
+        assert_eq!(
+            export_store(restored.path(), &cx)
+                .await
+                .expect("failed to export restored repository"),
+            export_store(store.path(), &cx)
+                .await
+                .expect("failed to export repository"),
+        );
+    }
+
+    // Restoring again leaves the store unchanged
+    let report = restore_store(restored.path(), &target)
+        .await
+        .expect("failed to restore store");
+    assert_eq!(report.tags, 0);
 }
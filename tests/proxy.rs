@@ -0,0 +1,199 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::digest::{hex, Algorithm, Algorithms};
+use drawbridge_server::url::Url;
+use drawbridge_server::ProxyRegistries;
+
+use std::collections::BTreeMap;
+use std::thread;
+
+use async_std::net::{Ipv4Addr, TcpListener};
+use async_std::task::{block_on, spawn_blocking};
+use futures::channel::oneshot::{channel, Sender};
+use futures::StreamExt;
+use http_types::{Response, StatusCode};
+use serde_json::json;
+
+const SUBJECT: &str = "test|subject";
+
+const MANIFEST_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+
+/// Returns the `sha256:<hex>` digest of `buf`.
+fn sha256(buf: &[u8]) -> String {
+    let (_, hash) = Algorithms::default().read_sync(buf).unwrap();
+    format!("sha256:{}", hex(&hash[&Algorithm::Sha256]))
+}
+
+/// Mock upstream OCI registry serving a single repository, `library/test`.
+struct Registry {
+    url: Url,
+    stop: Option<Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Registry {
+    /// Serves `manifests` by tag and `blobs` by digest.
+    fn start(manifests: BTreeMap<String, Vec<u8>>, blobs: BTreeMap<String, Vec<u8>>) -> Self {
+        let lis = block_on(TcpListener::bind((Ipv4Addr::LOCALHOST, 0)))
+            .expect("failed to bind to address");
+        let addr = lis.local_addr().unwrap();
+
+        let (tx, rx) = channel::<()>();
+        let thread = thread::spawn(move || {
+            block_on(
+                lis.incoming()
+                    .take_until(rx)
+                    .for_each_concurrent(None, |stream| async {
+                        let (manifests, blobs) = (&manifests, &blobs);
+                        _ = async_h1::accept(
+                            stream.expect("failed to initialize stream"),
+                            |req| async move {
+                                let path = req.url().path();
+                                let (mime, body) = if let Some(tag) =
+                                    path.strip_prefix("/v2/library/test/manifests/")
+                                {
+                                    (MANIFEST_TYPE, manifests.get(tag))
+                                } else if let Some(digest) =
+                                    path.strip_prefix("/v2/library/test/blobs/")
+                                {
+                                    ("application/octet-stream", blobs.get(digest))
+                                } else {
+                                    panic!("Unsupported path requested: `{path}`")
+                                };
+                                let mut res = match body {
+                                    Some(body) => {
+                                        let mut res = Response::new(StatusCode::Ok);
+                                        res.set_body(body.as_slice());
+                                        res
+                                    }
+                                    None => Response::new(StatusCode::NotFound),
+                                };
+                                res.insert_header("Content-Type", mime);
+                                Ok(res)
+                            },
+                        )
+                        .await;
+                    }),
+            )
+        });
+        Self {
+            url: format!("http://{addr}").parse().unwrap(),
+            stop: Some(tx),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for Registry {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            _ = stop.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("registry panicked");
+        }
+    }
+}
+
+/// Returns the manifest of an image consisting of `config` and `layers`, the descriptors of
+/// which claim the paired sizes instead of the actual ones, if specified.
+fn manifest(config: &[u8], layers: &[(&[u8], Option<u64>)]) -> Vec<u8> {
+    let desc = |mime: &str, buf: &[u8], size: Option<u64>| {
+        json!({
+            "mediaType": mime,
+            "digest": sha256(buf),
+            "size": size.unwrap_or(buf.len() as u64),
+        })
+    };
+    serde_json::to_vec(&json!({
+        "schemaVersion": 2,
+        "mediaType": MANIFEST_TYPE,
+        "config": desc("application/vnd.oci.image.config.v1+json", config, None),
+        "layers": layers
+            .iter()
+            .map(|(buf, size)| desc("application/vnd.oci.image.layer.v1.tar", buf, *size))
+            .collect::<Vec<_>>(),
+    }))
+    .unwrap()
+}
+
+#[async_std::test]
+async fn pull_through() {
+    let config = b"{}".as_slice();
+    let layer = b"layer".as_slice();
+    let oversized = b"oversized".as_slice();
+    let registry = Registry::start(
+        [
+            ("valid".into(), manifest(config, &[(layer, None)])),
+            (
+                "overflowing".into(),
+                manifest(config, &[(layer, None), (oversized, Some(4))]),
+            ),
+            (
+                "huge".into(),
+                manifest(config, &[(oversized, Some(1 << 40))]),
+            ),
+        ]
+        .into(),
+        [config, layer, oversized]
+            .into_iter()
+            .map(|buf| (sha256(buf), buf.to_vec()))
+            .collect(),
+    );
+    let mut registries = ProxyRegistries::default();
+    registries.insert("test", registry.url.clone()).unwrap();
+
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app.proxy_registries(registries)).await;
+
+    let agent = srv.agent(None);
+    let url = format!("{}/api/v0.1.0/_proxy/test/library/test/_tag", srv.url());
+    let token = oidc.token(SUBJECT, SCOPES);
+    let staging = srv.store.path().join("staging");
+    spawn_blocking(move || {
+        let get = |path: &str| {
+            agent
+                .get(&format!("{url}/{path}"))
+                .set("Authorization", &format!("Bearer {token}"))
+                .call()
+        };
+
+        let res = get("valid/tree/layers/0").expect("failed to pull image");
+        assert_eq!(
+            res.header("docker-content-digest"),
+            Some(sha256(&manifest(config, &[(layer, None)])).as_str())
+        );
+        assert_eq!(res.into_string().unwrap(), "layer");
+        assert_eq!(
+            get("valid/tree/config.json")
+                .unwrap()
+                .into_string()
+                .unwrap(),
+            "{}"
+        );
+
+        // Blobs exceeding their descriptors or the maximum size are rejected
+        for tag in ["overflowing", "huge"] {
+            match get(tag) {
+                Err(ureq::Error::Status(502, _)) => {}
+                res => panic!("expected pull of `{tag}` to fail, got {res:?}"),
+            }
+        }
+
+        // Nothing is left in the staging area
+        assert_eq!(
+            std::fs::read_dir(staging)
+                .expect("failed to read staging area")
+                .count(),
+            0
+        );
+    })
+    .await;
+
+    srv.stop().await;
+}
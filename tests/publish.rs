@@ -0,0 +1,185 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{
+    Error, RepositoryConfig, TagEntry, Tree, TreeContent, TreePath, UserRecord,
+};
+use drawbridge_client::{scope, ClientBuilder, Result, Scope, Tag};
+
+use std::fmt::Debug;
+use std::fs::File;
+use std::path::Path;
+use std::thread;
+
+use async_std::task::spawn_blocking;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+/// Returns the error, which the server rejected the request with.
+fn error(res: Result<impl Debug>) -> Error {
+    let e = res.expect_err("request was accepted");
+    e.downcast_ref::<Error>()
+        .unwrap_or_else(|| panic!("unexpected error: {e:#}"))
+        .clone()
+}
+
+/// Uploads nodes of `tree` to `tag`, except `skip`.
+fn upload(tag: &Tag<'_, impl Scope>, tree: &Tree<File>, skip: Option<&TreePath>) {
+    for (path, entry) in tree.iter() {
+        if Some(path) == skip {
+            continue;
+        }
+        let node = tag.path(path);
+        let created = match entry.content {
+            TreeContent::File(ref file) => node.create_from(&entry.meta, file),
+            TreeContent::Directory(ref buf) => node.create_from(&entry.meta, buf.as_slice()),
+        };
+        assert!(created.expect("failed to upload node"));
+    }
+}
+
+/// Calls `f` with tag `0.1.0` of the test repository accessed by a client built by `cl`
+/// authenticated by `token`.
+fn tag<R>(cl: ClientBuilder, token: String, f: impl FnOnce(&Tag<'_, scope::Root>) -> R) -> R {
+    let owner = cl.token(token).build().unwrap();
+    let repo = owner
+        .user(&"testuser".parse().unwrap())
+        .repository(&"repo".parse().unwrap());
+    f(&repo.tag(&"0.1.0".parse().unwrap()))
+}
+
+#[async_std::test]
+async fn atomic_publication() {
+    let pkg = tempdir().expect("failed to create temporary package directory");
+    std::fs::write(pkg.path().join("a.txt"), "a").unwrap();
+    std::fs::write(pkg.path().join("b.txt"), "b").unwrap();
+    let other = tempdir().expect("failed to create temporary package directory");
+    std::fs::write(other.path().join("a.txt"), "other").unwrap();
+    let skipped: TreePath = "b.txt".parse().unwrap();
+
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+    let pending = srv
+        .store
+        .path()
+        .join("users/testuser/repos/repo/pending/0.1.0");
+
+    let cl = srv.client();
+    let token = oidc.token(SUBJECT, SCOPES);
+    let (pkg_path, other_path, skip) = (
+        pkg.path().to_owned(),
+        other.path().to_owned(),
+        skipped.clone(),
+    );
+    spawn_blocking(move || {
+        let owner = cl.clone().token(token.clone()).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        assert!(owner
+            .user(&user_name)
+            .repository(&"repo".parse().unwrap())
+            .create(&RepositoryConfig::default())
+            .expect("failed to create repository"));
+
+        tag(cl, token, |tag| {
+            // Trees of tags, which do not exist yet, are not visible until published
+            let tree = Tree::from_path_sync(&pkg_path).unwrap();
+            upload(tag, &tree, Some(&skip));
+            assert_eq!(error(tag.get()), Error::NotFound);
+            assert_eq!(
+                error(tag.path(&"a.txt".parse().unwrap()).get_bytes(1)),
+                Error::NotFound
+            );
+
+            // Tags are only published along with their complete tree
+            assert_eq!(
+                error(tag.create(&TagEntry::Unsigned(tree.root()))),
+                Error::Incomplete
+            );
+            let other = Tree::from_path_sync(&other_path).unwrap();
+            assert_eq!(
+                error(tag.create(&TagEntry::Unsigned(other.root()))),
+                Error::DigestMismatch
+            );
+            assert_eq!(error(tag.get()), Error::NotFound);
+        });
+    })
+    .await;
+
+    // Pending trees survive restarts, e.g. after a crash, and are published once complete
+    let store = srv.stop().await.expect("store is still shared");
+    let srv = Server::start_in(store, &oidc, None, |app| app).await;
+    let cl = srv.client();
+    let token = oidc.token(SUBJECT, SCOPES);
+    let pkg_path = pkg.path().to_owned();
+    spawn_blocking(move || {
+        let tree = Tree::from_path_sync(&pkg_path).unwrap();
+        tag(cl.clone(), token.clone(), |tag| {
+            for (path, entry) in tree.iter() {
+                if *path == skipped {
+                    if let TreeContent::File(ref file) = entry.content {
+                        assert!(tag
+                            .path(path)
+                            .create_from(&entry.meta, file)
+                            .expect("failed to upload node"));
+                    }
+                }
+            }
+        });
+
+        // Concurrent publications of the pending tag either publish it, find it published
+        // or find it being published, but never fail otherwise
+        let entry = TagEntry::Unsigned(tree.root());
+        let published: Vec<_> = thread::scope(|s| {
+            let threads: Vec<_> = (0..4)
+                .map(|_| {
+                    let (cl, token, entry) = (cl.clone(), token.clone(), &entry);
+                    s.spawn(move || tag(cl, token, |tag| tag.create(entry)))
+                })
+                .collect();
+            threads
+                .into_iter()
+                .map(|t| t.join().expect("publishing thread panicked"))
+                .collect()
+        });
+        assert_eq!(
+            published
+                .iter()
+                .filter(|res| matches!(res, Ok(true)))
+                .count(),
+            1,
+            "{published:?}"
+        );
+        for res in published {
+            if let Err(e) = res {
+                assert_eq!(e.downcast_ref(), Some(&Error::AlreadyExists), "{e:#}");
+            }
+        }
+
+        tag(cl, token, |tag| {
+            assert!(tag.get().is_ok());
+            for (name, contents) in [("a.txt", "a"), ("b.txt", "b")] {
+                let (_, buf) = tag
+                    .path(&name.parse().unwrap())
+                    .get_bytes(1)
+                    .expect("failed to get published file");
+                assert_eq!(buf, contents.as_bytes());
+            }
+        });
+    })
+    .await;
+    assert!(!Path::new(&pending).exists());
+
+    srv.stop().await;
+}
@@ -0,0 +1,160 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::digest::Algorithms;
+use drawbridge_client::types::{
+    Publication, RepositoryConfig, Signature, SignatureAlgorithm, SignatureBundle, SignatureFormat,
+    SigningPolicy, Tree, UserRecord,
+};
+use drawbridge_client::{Result, Scope, Tag};
+use drawbridge_server::replay::ReplayProtection;
+use drawbridge_server::SignatureKeys;
+
+use std::time::SystemTime;
+
+use async_std::task::spawn_blocking;
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING};
+use serde_json::Value;
+use tempfile::{tempdir, TempDir};
+
+const SUBJECT: &str = "test|subject";
+
+/// Asserts that publication of `entry` as `tag` is rejected as replayed.
+fn assert_replayed(tag: &Tag<'_, impl Scope>, entry: &Value) {
+    let err = tag
+        .create_signed(entry, sign)
+        .expect_err("replayed publication was accepted");
+    assert!(format!("{err:#}").contains("was used before"), "{err:#}");
+}
+
+/// Signs `payload` with the key of the test client, which the server trusts as `release`.
+fn sign(payload: &[u8]) -> Result<SignatureBundle> {
+    let pkcs8 = rustls_pemfile::pkcs8_private_keys(
+        &mut include_bytes!("../testdata/client.key").as_slice(),
+    )?
+    .remove(0);
+    let pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &pkcs8).unwrap();
+    let (_, payload_digest) = Algorithms::default().read_sync(payload)?;
+    let mut sig = Signature {
+        algorithm: SignatureAlgorithm::Es256,
+        key_id: None,
+        payload_digest,
+        format: SignatureFormat::Jws,
+        signature: vec![].into(),
+        certificates: vec![],
+    };
+    let input = sig.signing_input(payload)?;
+    sig.signature = pair
+        .sign(&SystemRandom::new(), &input)
+        .unwrap()
+        .as_ref()
+        .to_vec()
+        .into();
+    Ok(SignatureBundle {
+        signatures: vec![sig],
+    })
+}
+
+/// Starts a server in `store` with replay protection, which trusts the key of the test client.
+async fn start(oidc: &Oidc, store: TempDir) -> Server {
+    let mut keys = SignatureKeys::default();
+    keys.read(
+        "release",
+        include_bytes!("../testdata/client.pub").as_slice(),
+    )
+    .unwrap();
+    Server::start_in(store, oidc, None, |app| {
+        app.signature_keys(keys)
+            .replay_protection(ReplayProtection::default())
+    })
+    .await
+}
+
+#[async_std::test]
+async fn nonces_survive_restarts() {
+    let oidc = Oidc::start();
+    let srv = start(
+        &oidc,
+        tempdir().expect("failed to create temporary store directory"),
+    )
+    .await;
+
+    // Returns the root entry of a tree bound to a publication as `0.1.0` using `nonce`.
+    let pkg = tempdir().expect("failed to create temporary package directory");
+    std::fs::write(pkg.path().join("test-file.txt"), "text").unwrap();
+    let tree = Tree::from_path_sync(pkg.path()).unwrap();
+    let root = serde_json::to_value(tree.root()).unwrap();
+    let entry = move |nonce: &str| {
+        let mut entry = root.clone();
+        entry["publication"] = serde_json::to_value(Publication {
+            tag: "testuser/signed:0.1.0".into(),
+            nonce: nonce.into(),
+            issued: SystemTime::now().into(),
+        })
+        .unwrap();
+        entry
+    };
+    let replayed = entry("0123456789abcdef-replayed");
+    let fresh = entry("0123456789abcdef-fresh");
+
+    let cl = srv.client();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking({
+        let token = token.clone();
+        let replayed = replayed.clone();
+        move || {
+            let owner = cl.token(token).build().unwrap();
+            let user_name = "testuser".parse().unwrap();
+            assert!(owner
+                .user(&user_name)
+                .create(&UserRecord {
+                    subject: SUBJECT.into(),
+                    algorithms: None,
+                })
+                .expect("failed to create user"));
+            let repo = owner
+                .user(&user_name)
+                .repository(&"signed".parse().unwrap());
+            assert!(repo
+                .create(&RepositoryConfig {
+                    public: true,
+                    signing: Some(SigningPolicy::default()),
+                    ..Default::default()
+                })
+                .expect("failed to create repository"));
+            let tag = repo.tag(&"0.1.0".parse().unwrap());
+            assert!(tag
+                .create_signed(&replayed, sign)
+                .expect("failed to publish signed tag"));
+
+            // A deleted tag cannot be re-created by replaying its publication
+            _ = tag.delete().expect("failed to delete tag");
+            assert_replayed(&tag, &replayed);
+        }
+    })
+    .await;
+
+    // Nonces are kept in the store, so they are still known after a restart
+    let store = srv.stop().await.expect("store is still shared");
+    let srv = start(&oidc, store).await;
+    let cl = srv.client();
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+        let tag = owner
+            .user(&"testuser".parse().unwrap())
+            .repository(&"signed".parse().unwrap())
+            .tag(&"0.1.0".parse().unwrap());
+        assert_replayed(&tag, &replayed);
+        assert!(tag
+            .create_signed(&fresh, sign)
+            .expect("failed to publish signed tag with a fresh nonce"));
+    })
+    .await;
+
+    srv.stop().await;
+}
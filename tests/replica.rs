@@ -0,0 +1,73 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{client_credentials, Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{RepositoryConfig, UserRecord};
+
+use async_std::task::spawn_blocking;
+
+const SUBJECT: &str = "test|subject";
+
+/// Starts a server with the replica identity `identity`, creates a user with a private
+/// repository and returns the statuses of reading the user, the tags of the repository and the
+/// change feed with the certificate of the test client.
+async fn replica_reads(oidc: &Oidc, identity: &'static str) -> [u16; 3] {
+    let srv = Server::start(oidc, None, |app| app.replica_identity(identity)).await;
+
+    let cl = srv.client();
+    let token = oidc.token(SUBJECT, SCOPES);
+    let cert = srv.agent(Some(client_credentials()));
+    let url = srv.url();
+    let statuses = spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        assert!(owner
+            .user(&user_name)
+            .repository(&"private".parse().unwrap())
+            .create(&RepositoryConfig {
+                public: false,
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+
+        let status = |path: &str| match cert.get(&format!("{url}/{path}")).call() {
+            Ok(res) => res.status(),
+            Err(ureq::Error::Status(status, _)) => status,
+            Err(e) => panic!("failed to send request: {e}"),
+        };
+        [
+            status("api/v0.1.0/testuser"),
+            status("api/v0.1.0/testuser/private/_tag"),
+            status("api/v0.1.0/_admin/events"),
+        ]
+    })
+    .await;
+
+    srv.stop().await;
+    statuses
+}
+
+#[async_std::test]
+async fn replica_identities() {
+    let oidc = Oidc::start();
+
+    // Certificates valid for a replica identity may read all users and repositories and the
+    // change feed
+    assert_eq!(replica_reads(&oidc, "localhost").await, [200; 3]);
+
+    // Other certificates issued by the trusted CA may not
+    let [user, tags, feed] = replica_reads(&oidc, "replica.example.com").await;
+    assert_eq!(user, 401);
+    assert_eq!(tags, 401);
+    assert_eq!(feed, 403);
+}
@@ -0,0 +1,138 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{RepositoryConfig, UserRecord};
+
+use async_std::task::spawn_blocking;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+/// Returns the contents of all `<name>` elements in `xml`.
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{name}>"), format!("</{name}>"));
+    xml.split(&open)
+        .skip(1)
+        .filter_map(|s| s.split_once(&close).map(|(s, _)| s))
+        .collect()
+}
+
+#[async_std::test]
+async fn list_pages() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let cl = srv.client();
+    let agent = srv.agent(None);
+    let url = format!("{}/s3/testuser.public", srv.url());
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let repo = owner
+            .user(&user_name)
+            .repository(&"public".parse().unwrap());
+        assert!(repo
+            .create(&RepositoryConfig {
+                public: true,
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("a.txt"), "a").unwrap();
+        std::fs::create_dir(pkg.path().join("dir")).unwrap();
+        std::fs::write(pkg.path().join("dir").join("b.txt"), "b").unwrap();
+        let tags = ["0.1.0", "0.1.0-rc.1", "0.2.0"];
+        for tag in tags {
+            _ = repo
+                .tag(&tag.parse().unwrap())
+                .create_from_path_unsigned(pkg.path())
+                .expect("failed to create a tag and upload the tree");
+        }
+
+        // Follows continuation tokens and returns keys and common prefixes of all pages.
+        let list = |query: &str, max_keys: usize| {
+            let (mut keys, mut prefixes, mut pages) = (Vec::<String>::new(), vec![], 0);
+            let mut token: Option<String> = None;
+            loop {
+                let mut req = agent
+                    .get(&url)
+                    .query("list-type", "2")
+                    .query("max-keys", &max_keys.to_string());
+                for (k, v) in query.split('&').filter_map(|kv| kv.split_once('=')) {
+                    req = req.query(k, v);
+                }
+                if let Some(ref token) = token {
+                    req = req.query("continuation-token", token);
+                }
+                let xml = req
+                    .call()
+                    .expect("failed to list objects")
+                    .into_string()
+                    .unwrap();
+                pages += 1;
+                let page: Vec<_> = elements(&xml, "Key")
+                    .into_iter()
+                    .map(String::from)
+                    .collect();
+                let page_prefixes: Vec<_> = elements(&xml, "CommonPrefixes")
+                    .into_iter()
+                    .flat_map(|s| elements(s, "Prefix"))
+                    .map(String::from)
+                    .collect();
+                assert!(page.len() + page_prefixes.len() <= max_keys, "{xml}");
+                keys.extend(page);
+                prefixes.extend(page_prefixes);
+                match elements(&xml, "NextContinuationToken").first() {
+                    Some(next) => {
+                        assert_eq!(elements(&xml, "IsTruncated"), ["true"]);
+                        token = Some(next.to_string());
+                    }
+                    None => {
+                        assert_eq!(elements(&xml, "IsTruncated"), ["false"]);
+                        return (keys, prefixes, pages);
+                    }
+                }
+            }
+        };
+
+        let mut all: Vec<_> = tags
+            .iter()
+            .flat_map(|tag| [format!("{tag}/a.txt"), format!("{tag}/dir/b.txt")])
+            .collect();
+        all.sort();
+        assert_eq!(list("", 1000), (all.clone(), vec![], 1));
+        assert_eq!(list("", 4), (all.clone(), vec![], 2));
+        assert_eq!(list("", 1), (all.clone(), vec![], 6));
+
+        assert_eq!(
+            list("prefix=0.1.0/", 1),
+            (
+                vec!["0.1.0/a.txt".into(), "0.1.0/dir/b.txt".into()],
+                vec![],
+                2
+            )
+        );
+        let mut tag_prefixes: Vec<_> = tags.iter().map(|tag| format!("{tag}/")).collect();
+        tag_prefixes.sort();
+        assert_eq!(list("delimiter=/", 2), (vec![], tag_prefixes, 2));
+        assert_eq!(
+            list("prefix=0.2.0/&delimiter=/", 1),
+            (vec!["0.2.0/a.txt".into()], vec!["0.2.0/dir/".into()], 2)
+        );
+    })
+    .await;
+
+    srv.stop().await;
+}
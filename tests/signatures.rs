@@ -0,0 +1,151 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{RepositoryConfig, TagEntry, Tree, UserRecord};
+use drawbridge_server::SignatureKeys;
+
+use std::thread;
+
+use async_std::task::spawn_blocking;
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+use tempfile::{tempdir, TempDir};
+
+const SUBJECT: &str = "test|subject";
+
+/// Returns the key of the test client, which the server trusts as `release`.
+fn release_key() -> EcdsaKeyPair {
+    let pkcs8 = rustls_pemfile::pkcs8_private_keys(
+        &mut include_bytes!("../testdata/client.key").as_slice(),
+    )
+    .unwrap()
+    .remove(0);
+    EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &pkcs8).unwrap()
+}
+
+/// Returns a Base64-encoded signature of `payload` made by `key` like `cosign sign-blob`.
+fn sign(key: &EcdsaKeyPair, payload: &[u8]) -> String {
+    base64::encode(key.sign(&SystemRandom::new(), payload).unwrap())
+}
+
+/// Starts a server in `store`, which trusts the key of the test client.
+async fn start(oidc: &Oidc, store: TempDir) -> Server {
+    let mut keys = SignatureKeys::default();
+    keys.read(
+        "release",
+        include_bytes!("../testdata/client.pub").as_slice(),
+    )
+    .unwrap();
+    Server::start_in(store, oidc, None, |app| app.signature_keys(keys)).await
+}
+
+#[async_std::test]
+async fn cosign_signatures() {
+    let oidc = Oidc::start();
+    let srv = start(
+        &oidc,
+        tempdir().expect("failed to create temporary store directory"),
+    )
+    .await;
+
+    let pkg = tempdir().expect("failed to create temporary package directory");
+    std::fs::write(pkg.path().join("test-file.txt"), "text").unwrap();
+
+    let cl = srv.client();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking({
+        let token = token.clone();
+        move || {
+            let tree = Tree::from_path_sync(pkg.path()).unwrap();
+            // NOTE: Signatures are made over the tag entry as sent by the client.
+            let entry = TagEntry::Unsigned(tree.root());
+            let payload = serde_json::to_vec(&entry).unwrap();
+
+            let owner = cl.token(token).build().unwrap();
+            let user_name = "testuser".parse().unwrap();
+            assert!(owner
+                .user(&user_name)
+                .create(&UserRecord {
+                    subject: SUBJECT.into(),
+                    algorithms: None,
+                })
+                .expect("failed to create user"));
+            let repo = owner.user(&user_name).repository(&"repo".parse().unwrap());
+            assert!(repo
+                .create(&RepositoryConfig::default())
+                .expect("failed to create repository"));
+            let tag = repo.tag(&"0.1.0".parse().unwrap());
+            assert!(tag.create(&entry).expect("failed to create tag"));
+
+            let signatures = tag.signatures().expect("failed to get signatures");
+            assert!(signatures.signatures.is_empty());
+            assert!(!signatures.verified);
+
+            // Signatures, which are not verified by a trusted key, are rejected
+            let rng = SystemRandom::new();
+            let pkcs8 =
+                EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+            let untrusted =
+                EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref()).unwrap();
+            for (signature, reason) in [
+                (sign(&untrusted, &payload), "not verified"),
+                (sign(&release_key(), b"other"), "not verified"),
+                ("not a signature".into(), "Invalid signature encoding"),
+            ] {
+                let e = tag
+                    .sign(&signature)
+                    .expect_err("invalid signature was accepted");
+                let msg = format!("{e:#}");
+                assert!(msg.contains("400") && msg.contains(reason), "{msg}");
+            }
+            assert!(tag.signatures().unwrap().signatures.is_empty());
+
+            // Signatures attached concurrently are all kept
+            let signatures: Vec<_> = (0..4).map(|_| sign(&release_key(), &payload)).collect();
+            thread::scope(|s| {
+                let threads: Vec<_> = signatures
+                    .iter()
+                    .map(|signature| {
+                        let tag = &tag;
+                        s.spawn(move || tag.sign(signature))
+                    })
+                    .collect();
+                for t in threads {
+                    assert!(t
+                        .join()
+                        .expect("signing thread panicked")
+                        .expect("failed to attach signature"));
+                }
+            });
+        }
+    })
+    .await;
+
+    // Signatures are kept in the store, so they are still listed after a restart
+    let store = srv.stop().await.expect("store is still shared");
+    let srv = start(&oidc, store).await;
+    let cl = srv.client();
+    spawn_blocking(move || {
+        let signatures = cl
+            .token(token)
+            .build()
+            .unwrap()
+            .user(&"testuser".parse().unwrap())
+            .repository(&"repo".parse().unwrap())
+            .tag(&"0.1.0".parse().unwrap())
+            .signatures()
+            .expect("failed to get signatures");
+        assert!(signatures.verified);
+        assert_eq!(signatures.signatures.len(), 4);
+        for signature in signatures.signatures {
+            assert_eq!(signature.keys, ["release"]);
+        }
+    })
+    .await;
+
+    srv.stop().await;
+}
@@ -0,0 +1,156 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{
+    RepositoryConfig, TagEntry, Tree, TreeContent, TreePath, UserRecord,
+};
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+use async_std::task::spawn_blocking;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+/// Size of the uploaded file, which is large enough for uploads to overlap.
+const FILE_SIZE: usize = 4 << 20;
+
+/// Returns the number of entries in the staging area of the store at `path`.
+fn staged(path: &Path) -> usize {
+    std::fs::read_dir(path.join("staging"))
+        .expect("failed to read staging area")
+        .count()
+}
+
+#[async_std::test]
+async fn concurrent_uploads() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let contents: Vec<u8> = (0..FILE_SIZE).map(|i| (i % 251) as u8).collect();
+    let pkg = tempdir().expect("failed to create temporary package directory");
+    std::fs::write(pkg.path().join("test-file.txt"), &contents).unwrap();
+
+    let cl = srv.client();
+    let token = oidc.token(SUBJECT, SCOPES);
+    let store = srv.store.clone();
+    let expected = contents.clone();
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let repo = owner.user(&user_name).repository(&"repo".parse().unwrap());
+        assert!(repo
+            .create(&RepositoryConfig::default())
+            .expect("failed to create repository"));
+        let tag = repo.tag(&"0.1.0".parse().unwrap());
+        let tree = Tree::from_path_sync(pkg.path()).unwrap();
+        assert!(tag
+            .create(&TagEntry::Unsigned(tree.root()))
+            .expect("failed to create tag"));
+        match tree.root().content {
+            TreeContent::Directory(ref buf) => assert!(tag
+                .path(&TreePath::ROOT)
+                .create_from(&tree.root().meta, buf.as_slice())
+                .expect("failed to upload root directory")),
+            _ => panic!("tree root is not a directory"),
+        }
+
+        let path: TreePath = "test-file.txt".parse().unwrap();
+        let meta = &tree[&path].meta;
+        let node = tag.path(&path);
+        let mut corrupted = contents.clone();
+        corrupted[FILE_SIZE / 2] ^= 0xff;
+
+        // Uploads of valid and corrupted contents race with readers of the node, which must
+        // never observe contents other than the valid ones
+        let uploading = AtomicBool::new(true);
+        let created = thread::scope(|s| {
+            let reader = s.spawn(|| {
+                while uploading.load(Ordering::Acquire) {
+                    if let Ok((_, buf)) = node.get_bytes(FILE_SIZE as _) {
+                        assert!(buf == contents, "partial or corrupted contents observed");
+                    }
+                }
+            });
+            let uploads: Vec<_> = (0..8)
+                .map(|i| {
+                    let (node, contents, corrupted) = (&node, &contents, &corrupted);
+                    s.spawn(move || {
+                        let valid = i % 2 == 0;
+                        let buf = if valid { contents } else { corrupted };
+                        (valid, node.create_from(meta, buf.as_slice()))
+                    })
+                })
+                .collect();
+            let created: Vec<_> = uploads
+                .into_iter()
+                .map(|t| t.join().expect("uploading thread panicked"))
+                .collect();
+            uploading.store(false, Ordering::Release);
+            reader.join().expect("reading thread panicked");
+            created
+        });
+        // NOTE: Uploads may fail while another upload of the node is in progress, but
+        // corrupted contents are never stored.
+        for (valid, res) in &created {
+            assert!(*valid || !matches!(res, Ok(true)), "{created:?}");
+        }
+        assert!(
+            created
+                .iter()
+                .filter(|(_, res)| matches!(res, Ok(true)))
+                .count()
+                <= 1,
+            "{created:?}"
+        );
+        // NOTE: Like clients do, uploads of existing nodes are skipped, since the server
+        // responds to them before reading the body.
+        if node.head().unwrap().is_none() {
+            assert!(node
+                .create_from(meta, contents.as_slice())
+                .expect("failed to upload file"));
+        }
+        assert_eq!(node.get_bytes(FILE_SIZE as _).unwrap().1, contents);
+        assert_eq!(staged(store.path()), 0);
+    })
+    .await;
+
+    // Entities left in the staging area by a crashed server are removed on startup
+    let store = srv.stop().await.expect("store is still shared");
+    let partial = store.path().join("staging").join("crashed");
+    std::fs::create_dir(&partial).unwrap();
+    std::fs::write(partial.join("meta.json"), "{}").unwrap();
+    std::fs::write(partial.join("content"), &expected[..FILE_SIZE / 2]).unwrap();
+    let srv = Server::start_in(store, &oidc, None, |app| app).await;
+    assert_eq!(staged(srv.store.path()), 0);
+
+    let cl = srv.client();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+        let (_, buf) = owner
+            .user(&"testuser".parse().unwrap())
+            .repository(&"repo".parse().unwrap())
+            .tag(&"0.1.0".parse().unwrap())
+            .path(&"test-file.txt".parse().unwrap())
+            .get_bytes(FILE_SIZE as _)
+            .expect("failed to get file");
+        assert!(buf == expected);
+    })
+    .await;
+
+    srv.stop().await;
+}
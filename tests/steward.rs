@@ -0,0 +1,117 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{certificates, client_credentials, private_key, Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{RepositoryConfig, UserRecord};
+use drawbridge_server::Steward;
+
+use async_std::task::spawn_blocking;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+#[async_std::test]
+async fn client_certificates() {
+    let oidc = Oidc::start();
+    let mut steward = Steward::read(include_bytes!("../testdata/steward-ca.crt").as_slice())
+        .expect("failed to read Steward CA certificates");
+    steward
+        .grant(
+            "workload.steward.localhost",
+            "testuser/private".parse().unwrap(),
+        )
+        .unwrap();
+    let srv = Server::start(&oidc, Some(&steward), |app| {
+        app.replica_identity("localhost").steward(steward.clone())
+    })
+    .await;
+
+    let cl = srv.client();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.clone().token(token).build().unwrap();
+        let ca = {
+            let (cert, key) = client_credentials();
+            cl.clone().credentials(cert, key).build().unwrap()
+        };
+        let workload = cl
+            .clone()
+            .credentials(
+                certificates(include_bytes!("../testdata/workload.crt")),
+                private_key(include_bytes!("../testdata/workload.key")),
+            )
+            .build()
+            .unwrap();
+        // NOTE: The certificate is accepted in the TLS handshake, but its signature algorithm
+        // is not accepted by the Steward integration.
+        let rejected = cl
+            .clone()
+            .credentials(
+                certificates(include_bytes!("../testdata/workload-pss.crt")),
+                private_key(include_bytes!("../testdata/workload.key")),
+            )
+            .build()
+            .unwrap();
+
+        let user_name = "testuser".parse().unwrap();
+        let user_record = UserRecord {
+            subject: SUBJECT.into(),
+            algorithms: None,
+        };
+        assert!(owner
+            .user(&user_name)
+            .create(&user_record)
+            .expect("failed to create user"));
+
+        let repo_name = "private".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .repository(&repo_name)
+            .create(&RepositoryConfig {
+                public: false,
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("test-file.txt"), "text").unwrap();
+        let tag_name = "0.1.0".parse().unwrap();
+        _ = owner
+            .user(&user_name)
+            .repository(&repo_name)
+            .tag(&tag_name)
+            .create_from_path_unsigned(pkg.path())
+            .expect("failed to create a tag and upload the tree");
+
+        let file_name = "test-file.txt".parse().unwrap();
+        let read = |cl: &drawbridge_client::Client| {
+            cl.user(&user_name)
+                .repository(&repo_name)
+                .tag(&tag_name)
+                .path(&file_name)
+                .get_string(4)
+                .map(|(_, content)| content)
+        };
+
+        // Replica certificates issued by the trusted CA are trusted
+        assert_eq!(read(&ca).expect("failed to get file"), "text");
+        assert_eq!(
+            ca.user(&user_name).get().expect("failed to get user"),
+            user_record
+        );
+
+        // Workloads may read the repositories granted to their identity only
+        assert_eq!(read(&workload).expect("failed to get file"), "text");
+        assert!(workload.user(&user_name).get().is_err());
+
+        // Certificates rejected by the Steward integration are not trusted either
+        assert!(read(&rejected).is_err());
+        assert!(rejected.user(&user_name).get().is_err());
+    })
+    .await;
+
+    srv.stop().await;
+}
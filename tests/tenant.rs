@@ -0,0 +1,80 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{client_credentials, Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{RepositoryConfig, UserRecord};
+use drawbridge_server::TenantConfig;
+
+use async_std::task::spawn_blocking;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+#[async_std::test]
+async fn client_certificates_ignored_on_tenant_hosts() {
+    let oidc = Oidc::start();
+    let tenant_oidc = oidc.config();
+    // NOTE: The tenant is served from the default store, so that the same repository is
+    // addressed through the hosts of both.
+    let store = tempdir().expect("failed to create temporary store directory");
+    let tenant_store = store.path().to_owned();
+    let srv = Server::start_in(store, &oidc, None, |app| {
+        app.replica_identity("localhost").tenant(TenantConfig {
+            name: "acme".into(),
+            hosts: vec!["acme.localhost".into()],
+            store: tenant_store,
+            oidc: tenant_oidc,
+            quota: None,
+        })
+    })
+    .await;
+
+    let cl = srv.client();
+    let token = oidc.token(SUBJECT, SCOPES);
+    let anonymous = srv.agent(None);
+    let cert = srv.agent(Some(client_credentials()));
+    let url = format!("{}/api/v0.1.0/testuser/private/_tag", srv.url());
+    spawn_blocking(move || {
+        let owner = cl.token(token.clone()).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        assert!(owner
+            .user(&user_name)
+            .repository(&"private".parse().unwrap())
+            .create(&RepositoryConfig {
+                public: false,
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+
+        let status = |req: ureq::Request| match req.call() {
+            Ok(res) => res.status(),
+            Err(ureq::Error::Status(status, _)) => status,
+            Err(e) => panic!("failed to send request: {e}"),
+        };
+
+        // Replica certificates issued by the trusted CA grant access on hosts of the deployment
+        assert_eq!(status(cert.get(&url)), 200);
+
+        // Tenants only trust their OpenID Connect realm
+        let tenant = |agent: &ureq::Agent| agent.get(&url).set("Host", "acme.localhost");
+        assert_eq!(status(tenant(&cert)), 401);
+        assert_eq!(status(tenant(&anonymous)), 401);
+        assert_eq!(
+            status(tenant(&anonymous).set("Authorization", &format!("Bearer {token}"))),
+            200
+        );
+    })
+    .await;
+
+    srv.stop().await;
+}
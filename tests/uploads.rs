@@ -0,0 +1,204 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{
+    Meta, RepositoryConfig, TagEntry, Tree, TreeContent, TreePath, UserRecord,
+};
+
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::path::{Path, PathBuf};
+
+use async_std::task::spawn_blocking;
+use rustls::{ClientConnection, StreamOwned};
+use serde_json::Value;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+/// Size of the uploaded file.
+const FILE_SIZE: usize = 16 << 10;
+
+/// Path of the uploaded file node.
+const PATH: &str = "/api/v0.1.0/testuser/repo/_tag/0.1.0/tree/test-file.txt";
+
+/// Sends a `PUT` request of the uploaded file node with `meta` on `conn`, followed by
+/// `headers` and `body`, and returns the status code and the problem details of the response.
+///
+/// If `shutdown` is set, the connection is closed for writing once `body` is sent, so that the
+/// body ends prematurely.
+fn put(
+    mut conn: StreamOwned<ClientConnection, TcpStream>,
+    token: &str,
+    meta: &Meta,
+    headers: &str,
+    body: &[u8],
+    shutdown: bool,
+) -> (u16, Value) {
+    write!(
+        conn,
+        "PUT {PATH} HTTP/1.1\r\n\
+         Host: localhost\r\n\
+         Authorization: Bearer {token}\r\n\
+         Content-Digest: {}\r\n\
+         Content-Type: {}\r\n\
+         Connection: close\r\n\
+         {headers}\r\n",
+        meta.hash, meta.mime,
+    )
+    .unwrap();
+    conn.write_all(body).unwrap();
+    conn.flush().unwrap();
+    if shutdown {
+        conn.conn.send_close_notify();
+        conn.flush().unwrap();
+        conn.sock.shutdown(Shutdown::Write).unwrap();
+    }
+
+    // NOTE: The server may close the connection without notifying the client.
+    let mut res = vec![];
+    match conn.read_to_end(&mut res) {
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {}
+        Err(e) => panic!("failed to read response: {e}"),
+    }
+    let res = String::from_utf8(res).expect("response is not UTF-8");
+    let (head, body) = res
+        .split_once("\r\n\r\n")
+        .unwrap_or_else(|| panic!("invalid response `{res}`"));
+    let status = head
+        .split(' ')
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .unwrap_or_else(|| panic!("invalid status line in `{head}`"));
+    (status, serde_json::from_str(body).unwrap_or_default())
+}
+
+/// Returns the directory of the uploaded file node in the store at `path`.
+fn node_dir(path: &Path) -> PathBuf {
+    path.join("users/testuser/repos/repo/tags/0.1.0/tree/entries/test-file.txt")
+}
+
+/// Asserts that nothing was left behind by a rejected upload to the store at `path`.
+fn assert_clean(path: &Path) {
+    assert!(!node_dir(path).exists(), "node directory was left behind");
+    let staged: Vec<_> = std::fs::read_dir(path.join("staging"))
+        .expect("failed to read staging area")
+        .collect();
+    assert!(staged.is_empty(), "{staged:?} left in staging area");
+}
+
+#[async_std::test]
+async fn rejected_uploads() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let contents: Vec<u8> = (0..FILE_SIZE).map(|i| (i % 251) as u8).collect();
+    let pkg = tempdir().expect("failed to create temporary package directory");
+    std::fs::write(pkg.path().join("test-file.txt"), &contents).unwrap();
+    let tree = Tree::from_path_sync(pkg.path()).unwrap();
+    let meta = tree[&"test-file.txt".parse().unwrap()].meta.clone();
+
+    let cl = srv.client();
+    let token = oidc.token(SUBJECT, SCOPES);
+    let owner = cl.token(token.clone()).build().unwrap();
+    spawn_blocking({
+        let owner = owner.clone();
+        move || {
+            let user_name = "testuser".parse().unwrap();
+            assert!(owner
+                .user(&user_name)
+                .create(&UserRecord {
+                    subject: SUBJECT.into(),
+                    algorithms: None,
+                })
+                .expect("failed to create user"));
+            let repo = owner.user(&user_name).repository(&"repo".parse().unwrap());
+            assert!(repo
+                .create(&RepositoryConfig::default())
+                .expect("failed to create repository"));
+            let tag = repo.tag(&"0.1.0".parse().unwrap());
+            assert!(tag
+                .create(&TagEntry::Unsigned(tree.root()))
+                .expect("failed to create tag"));
+            match tree.root().content {
+                TreeContent::Directory(ref buf) => assert!(tag
+                    .path(&TreePath::ROOT)
+                    .create_from(&tree.root().meta, buf.as_slice())
+                    .expect("failed to upload root directory")),
+                _ => panic!("tree root is not a directory"),
+            }
+        }
+    })
+    .await;
+    let half = &contents[..FILE_SIZE / 2];
+
+    // Bodies shorter than their `Content-Length` are rejected as length mismatches
+    // NOTE: The body is decoded as chunked, while the `Content-Length` header is retained.
+    let mut chunked = format!("{:x}\r\n", half.len()).into_bytes();
+    chunked.extend_from_slice(half);
+    chunked.extend_from_slice(b"\r\n0\r\n\r\n");
+    let (status, problem) = put(
+        srv.connect(),
+        &token,
+        &meta,
+        &format!("Content-Length: {FILE_SIZE}\r\nTransfer-Encoding: chunked\r\n"),
+        &chunked,
+        false,
+    );
+    assert_eq!(status, 400, "{problem}");
+    assert_eq!(problem["code"], "length-mismatch", "{problem}");
+    assert_eq!(problem["expected"], FILE_SIZE, "{problem}");
+    assert_eq!(problem["got"], FILE_SIZE / 2, "{problem}");
+    assert_clean(srv.store.path());
+
+    // Bodies not matching their `Content-Digest` are rejected as digest mismatches
+    let mut corrupted = contents.clone();
+    corrupted[FILE_SIZE / 2] ^= 0xff;
+    let (status, problem) = put(
+        srv.connect(),
+        &token,
+        &meta,
+        &format!("Content-Length: {FILE_SIZE}\r\n"),
+        &corrupted,
+        false,
+    );
+    assert_eq!(status, 400, "{problem}");
+    assert_eq!(problem["code"], "digest-mismatch", "{problem}");
+    assert_clean(srv.store.path());
+
+    // Bodies ending before their `Content-Length` is reached are rejected as truncated
+    let (status, problem) = put(
+        srv.connect(),
+        &token,
+        &meta,
+        &format!("Content-Length: {FILE_SIZE}\r\n"),
+        half,
+        true,
+    );
+    assert_eq!(status, 400, "{problem}");
+    assert_eq!(problem["code"], "truncated", "{problem}");
+    assert_clean(srv.store.path());
+
+    // Rejected uploads may be retried
+    let store = srv.store.clone();
+    spawn_blocking(move || {
+        let node = owner
+            .user(&"testuser".parse().unwrap())
+            .repository(&"repo".parse().unwrap())
+            .tag(&"0.1.0".parse().unwrap())
+            .path(&"test-file.txt".parse().unwrap());
+        assert!(node
+            .create_from(&meta, contents.as_slice())
+            .expect("failed to upload file"));
+        assert_eq!(node.get_bytes(FILE_SIZE as _).unwrap().1, contents);
+        assert!(node_dir(store.path()).is_dir());
+    })
+    .await;
+
+    srv.stop().await;
+}
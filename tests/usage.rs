@@ -0,0 +1,218 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{RepositoryConfig, Tree, Usage, UserRecord, UserUsage};
+
+use std::io::Read;
+use std::thread::sleep;
+use std::time::Duration;
+
+use async_std::task::spawn_blocking;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+/// Size of a file, which exceeds what fits in socket buffers.
+const LARGE_SIZE: usize = 16 * 1024 * 1024;
+
+#[async_std::test]
+async fn transferred_bytes() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let cl = srv.client();
+    let agent = srv.agent(None);
+    let user_url = format!("{}/api/v0.1.0/testuser", srv.url());
+    let url = format!("{user_url}/public");
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token.clone()).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let repo = owner
+            .user(&user_name)
+            .repository(&"public".parse().unwrap());
+        assert!(repo
+            .create(&RepositoryConfig {
+                public: true,
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("test-file.txt"), "text").unwrap();
+        std::fs::write(pkg.path().join("large-file"), vec![0; LARGE_SIZE]).unwrap();
+        _ = repo
+            .tag(&"0.1.0".parse().unwrap())
+            .create_from_path_unsigned(pkg.path())
+            .expect("failed to create a tag and upload the tree");
+
+        // NOTE: Usage of the user is queried, since requests to the repository are accounted.
+        let usage = || -> Usage {
+            let usage: UserUsage = agent
+                .get(&format!("{user_url}/_usage"))
+                .set("Authorization", &format!("Bearer {token}"))
+                .call()
+                .expect("failed to get usage")
+                .into_json()
+                .expect("failed to decode usage");
+            usage.repositories["public"]
+        };
+        // NOTE: Downloads are accounted once the server is done sending the response body,
+        // which may be after the client received it.
+        let before = usage();
+        assert!(before.uploaded > 0);
+        let wait_for = |downloaded: u64| {
+            let expected = before.downloaded + downloaded;
+            for _ in 0..50 {
+                if usage().downloaded == expected {
+                    return;
+                }
+                sleep(Duration::from_millis(20));
+            }
+            assert_eq!(usage().downloaded, expected);
+        };
+
+        let file = agent
+            .get(&format!("{url}/_tag/0.1.0/tree/test-file.txt"))
+            .call()
+            .expect("failed to get file")
+            .into_string()
+            .unwrap();
+        assert_eq!(file, "text");
+        wait_for(4);
+
+        let entry = agent
+            .get(&format!("{url}/_tag/0.1.0"))
+            .call()
+            .expect("failed to get tag")
+            .into_string()
+            .unwrap();
+        wait_for(4 + entry.len() as u64);
+
+        // Nothing is sent in response to `HEAD` requests
+        _ = agent
+            .head(&format!("{url}/_tag/0.1.0/tree/test-file.txt"))
+            .call()
+            .expect("failed to query file");
+        sleep(Duration::from_millis(100));
+        assert_eq!(
+            usage().downloaded,
+            before.downloaded + 4 + entry.len() as u64
+        );
+        assert_eq!(usage().uploaded, before.uploaded);
+
+        // Downloads interrupted by the client are accounted as far as they were sent
+        let mut buf = [0; 1024];
+        agent
+            .get(&format!("{url}/_tag/0.1.0/tree/large-file"))
+            .call()
+            .expect("failed to get file")
+            .into_reader()
+            .read_exact(&mut buf)
+            .expect("failed to read file");
+        let mut downloaded = 0;
+        for _ in 0..50 {
+            sleep(Duration::from_millis(20));
+            downloaded = usage().downloaded - before.downloaded - 4 - entry.len() as u64;
+            if downloaded > 0 {
+                break;
+            }
+        }
+        assert!(downloaded >= buf.len() as u64, "{downloaded}");
+        assert!(downloaded < LARGE_SIZE as u64, "{downloaded}");
+    })
+    .await;
+
+    srv.stop().await;
+}
+
+#[async_std::test]
+async fn deleted_and_restored_bytes() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let cl = srv.client();
+    let agent = srv.agent(None);
+    let user_url = format!("{}/api/v0.1.0/testuser", srv.url());
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token.clone()).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        let user = owner.user(&user_name);
+        assert!(user
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let repo = user.repository(&"public".parse().unwrap());
+        assert!(repo
+            .create(&RepositoryConfig {
+                public: true,
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("test-file.txt"), "text").unwrap();
+        let size: u64 = Tree::from_path_sync(pkg.path())
+            .unwrap()
+            .values()
+            .map(|entry| entry.meta.size)
+            .sum();
+        let tag = repo.tag(&"0.1.0".parse().unwrap());
+        _ = tag
+            .create_from_path_unsigned(pkg.path())
+            .expect("failed to create a tag and upload the tree");
+
+        // NOTE: Bytes stored are accounted from events in background.
+        let wait_for = |stored: u64, found: usize| {
+            let usage = || -> Usage {
+                let usage: UserUsage = agent
+                    .get(&format!("{user_url}/_usage"))
+                    .set("Authorization", &format!("Bearer {token}"))
+                    .call()
+                    .expect("failed to get usage")
+                    .into_json()
+                    .expect("failed to decode usage");
+                usage.repositories["public"]
+            };
+            let hits = || owner.search("0.1.0", 10).expect("failed to search").total;
+            for _ in 0..50 {
+                if usage().stored == stored && hits() == found {
+                    return;
+                }
+                sleep(Duration::from_millis(20));
+            }
+            assert_eq!(usage().stored, stored);
+            assert_eq!(hits(), found);
+        };
+        wait_for(size, 1);
+
+        // Bytes of tags and repositories moved into the trash are no longer accounted as
+        // stored and they are no longer found, until they are restored
+        let trashed = tag.delete().expect("failed to delete tag");
+        wait_for(0, 0);
+        _ = user.restore(&trashed.id).expect("failed to restore tag");
+        wait_for(size, 1);
+
+        let trashed = repo.delete().expect("failed to delete repository");
+        wait_for(0, 0);
+        _ = user
+            .restore(&trashed.id)
+            .expect("failed to restore repository");
+        wait_for(size, 1);
+    })
+    .await;
+
+    srv.stop().await;
+}
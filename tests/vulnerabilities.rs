@@ -0,0 +1,109 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{
+    RepositoryConfig, TagAttestationSubject, UserRecord, VulnerabilityPolicy, VulnerabilityReport,
+};
+
+use async_std::task::spawn_blocking;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+/// Hex-encoded SHA-256 digest of `text`, the contents of the file in the tag tree.
+const TEXT_SHA256: &str = "982d9e3eb996f559e633f4d194def3761d909f5a3b647d1a851fead67c32c9d1";
+
+#[async_std::test]
+async fn policy_blocks_pulls() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let cl = srv.client();
+    let agent = srv.agent(None);
+    let url = srv.url();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+
+        let repo_name = "public".parse().unwrap();
+        let repo = owner.user(&user_name).repository(&repo_name);
+        assert!(repo
+            .create(&RepositoryConfig {
+                public: true,
+                vulnerabilities: Some(VulnerabilityPolicy {
+                    require_report: true,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("test-file.txt"), "text").unwrap();
+        let tag_name = "0.1.0".parse().unwrap();
+        let tag = repo.tag(&tag_name);
+        _ = tag
+            .create_from_path_unsigned(pkg.path())
+            .expect("failed to create a tag and upload the tree");
+
+        let paths = [
+            "/api/v0.1.0/testuser/public/_tag/0.1.0",
+            "/api/v0.1.0/testuser/public/_tag/0.1.0/tree/test-file.txt",
+            "/api/v0.1.0/testuser/public/_tag/0.1.0/bagit",
+            "/api/v0.1.0/testuser/public/_tag/0.1.0/ipfs",
+            "/s3/testuser.public/0.1.0/test-file.txt",
+        ];
+        let get = |path: &str| agent.get(&format!("{url}{path}")).call();
+
+        // Tags without a report are blocked by the policy, whatever the protocol
+        for path in paths {
+            match get(path) {
+                Err(ureq::Error::Status(403, _)) => {}
+                res => panic!("expected `{path}` to be blocked, got {res:?}"),
+            }
+        }
+
+        assert!(tag
+            .attach_vulnerability_report(&VulnerabilityReport {
+                subject: vec![TagAttestationSubject {
+                    name: "test-file.txt".into(),
+                    digest: [("sha256".into(), TEXT_SHA256.into())].into(),
+                }],
+                scanner: "test".into(),
+                findings: vec![],
+            })
+            .expect("failed to attach report"));
+
+        for path in paths {
+            _ = get(path).unwrap_or_else(|e| panic!("failed to get `{path}`: {e}"));
+        }
+        // Responses must be revalidated, since a later report may block the tag
+        for path in &paths[..2] {
+            let res = get(path).unwrap();
+            assert_eq!(res.header("cache-control"), Some("public, no-cache"));
+        }
+        assert_eq!(
+            tag.path(&"test-file.txt".parse().unwrap())
+                .get_string(4)
+                .expect("failed to get file")
+                .1,
+            "text"
+        );
+    })
+    .await;
+
+    srv.stop().await;
+}
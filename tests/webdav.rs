@@ -0,0 +1,107 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{RepositoryConfig, UserRecord};
+
+use async_std::task::spawn_blocking;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+/// Returns the contents of all `<name>` elements in `xml`.
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let (open, close) = (format!("<{name}>"), format!("</{name}>"));
+    xml.split(&open)
+        .skip(1)
+        .filter_map(|s| s.split_once(&close).map(|(s, _)| s))
+        .collect()
+}
+
+#[async_std::test]
+async fn propfind() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let cl = srv.client();
+    let agent = srv.agent(None);
+    let url = srv.url();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        assert!(owner
+            .user(&user_name)
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let repo = owner
+            .user(&user_name)
+            .repository(&"public".parse().unwrap());
+        assert!(repo
+            .create(&RepositoryConfig {
+                public: true,
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("a:b.txt"), "text").unwrap();
+        std::fs::create_dir(pkg.path().join("dir")).unwrap();
+        std::fs::write(pkg.path().join("dir").join("c.txt"), "c").unwrap();
+        _ = repo
+            .tag(&"0.1.0".parse().unwrap())
+            .create_from_path_unsigned(pkg.path())
+            .expect("failed to create a tag and upload the tree");
+
+        let tree = "/api/v0.1.0/testuser/public/_tag/0.1.0/tree";
+        let propfind = |path: &str, depth: &str| {
+            let res = agent
+                .request("PROPFIND", &format!("{url}{path}"))
+                .set("depth", depth)
+                .call()
+                .expect("failed to list properties");
+            assert_eq!(res.status(), 207);
+            res.into_string().unwrap()
+        };
+
+        let xml = propfind(tree, "1");
+        assert_eq!(
+            elements(&xml, "D:href"),
+            [
+                format!("{tree}/"),
+                format!("{tree}/a:b.txt"),
+                format!("{tree}/dir/")
+            ]
+        );
+        assert_eq!(elements(&xml, "D:getcontentlength")[1], "4");
+
+        let xml = propfind(&format!("{tree}/dir/"), "1");
+        assert_eq!(
+            elements(&xml, "D:href"),
+            [format!("{tree}/dir/"), format!("{tree}/dir/c.txt")]
+        );
+
+        // Listed references can be followed
+        for href in elements(&xml, "D:href") {
+            _ = agent
+                .get(&format!("{url}{href}"))
+                .call()
+                .unwrap_or_else(|e| panic!("failed to get `{href}`: {e}"));
+        }
+
+        let xml = propfind(&format!("{tree}/a:b.txt"), "0");
+        assert_eq!(elements(&xml, "D:href"), [format!("{tree}/a:b.txt")]);
+        match agent.request("PROPFIND", &format!("{url}{tree}")).call() {
+            Err(ureq::Error::Status(403, _)) => {}
+            res => panic!("expected infinite depth to be rejected, got {res:?}"),
+        }
+    })
+    .await;
+
+    srv.stop().await;
+}
@@ -0,0 +1,143 @@
+// SPDX-FileCopyrightText: 2022 Profian Inc. <opensource@profian.com>
+// SPDX-License-Identifier: AGPL-3.0-only
+
+mod common;
+
+use common::{Oidc, Server, SCOPES};
+
+use drawbridge_client::types::{
+    RepositoryConfig, UserRecord, Webhook, WebhookDelivery, WebhookEvent,
+};
+
+use std::collections::BTreeSet;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread::{sleep, spawn};
+use std::time::Duration;
+
+use async_std::task::spawn_blocking;
+use tempfile::tempdir;
+
+const SUBJECT: &str = "test|subject";
+
+/// Starts a webhook receiver, which fails the first attempt of each delivery, and returns
+/// its URL.
+fn receiver() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    _ = spawn(move || {
+        let mut seen = BTreeSet::new();
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut rdr = BufReader::new(&stream);
+            let (mut id, mut len) = (String::new(), 0);
+            _ = rdr.read_line(&mut String::new()).unwrap();
+            loop {
+                let mut line = String::new();
+                _ = rdr.read_line(&mut line).unwrap();
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break;
+                }
+                let (name, value) = line.split_once(": ").unwrap();
+                match name.to_ascii_lowercase().as_str() {
+                    "drawbridge-delivery" => id = value.into(),
+                    "content-length" => len = value.parse().unwrap(),
+                    _ => {}
+                }
+            }
+            rdr.read_exact(&mut vec![0; len]).unwrap();
+            let status = if seen.insert(id) { 500 } else { 200 };
+            write!(
+                stream,
+                "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+            )
+            .unwrap();
+        }
+    });
+    format!("http://{addr}/hook")
+}
+
+#[async_std::test]
+async fn tag_events() {
+    let oidc = Oidc::start();
+    let srv = Server::start(&oidc, None, |app| app).await;
+
+    let cl = srv.client();
+    let agent = srv.agent(None);
+    let url = srv.url();
+    let token = oidc.token(SUBJECT, SCOPES);
+    spawn_blocking(move || {
+        let owner = cl.token(token.clone()).build().unwrap();
+        let user_name = "testuser".parse().unwrap();
+        let user = owner.user(&user_name);
+        assert!(user
+            .create(&UserRecord {
+                subject: SUBJECT.into(),
+                algorithms: None,
+            })
+            .expect("failed to create user"));
+        let repo = user.repository(&"public".parse().unwrap());
+        assert!(repo
+            .create(&RepositoryConfig {
+                public: true,
+                webhooks: vec![Webhook {
+                    url: receiver().parse().unwrap(),
+                    secret: "secret".into(),
+                    events: vec![
+                        WebhookEvent::TagCreate,
+                        WebhookEvent::TagUpdate,
+                        WebhookEvent::TagDelete,
+                    ],
+                }],
+                ..Default::default()
+            })
+            .expect("failed to create repository"));
+
+        let pkg = tempdir().expect("failed to create temporary package directory");
+        std::fs::write(pkg.path().join("test-file.txt"), "text").unwrap();
+        let tag = repo.tag(&"0.1.0".parse().unwrap());
+        _ = tag
+            .create_from_path_unsigned(pkg.path())
+            .expect("failed to create a tag and upload the tree");
+        let trashed = tag.delete().expect("failed to delete tag");
+        _ = user.restore(&trashed.id).expect("failed to restore tag");
+
+        // Tags created, deleted and restored are notified of and failed deliveries are
+        // retried after a backoff
+        let deliveries_url = format!("{url}/api/v0.1.0/testuser/public/_webhook/deliveries");
+        for _ in 0..50 {
+            let deliveries: Vec<WebhookDelivery> = agent
+                .get(&deliveries_url)
+                .set("authorization", &format!("Bearer {token}"))
+                .call()
+                .expect("failed to get deliveries")
+                .into_json()
+                .unwrap();
+            if deliveries.len() < 3 || deliveries.iter().any(|d| !d.delivered) {
+                sleep(Duration::from_millis(200));
+                continue;
+            }
+            let events: Vec<_> = deliveries.iter().map(|d| d.payload.event).collect();
+            assert_eq!(
+                events,
+                [
+                    WebhookEvent::TagUpdate,
+                    WebhookEvent::TagDelete,
+                    WebhookEvent::TagCreate
+                ]
+            );
+            for delivery in deliveries {
+                assert_eq!(delivery.payload.tag.as_deref(), Some("0.1.0"));
+                assert!(delivery.payload.digest.is_some());
+                let statuses: Vec<_> = delivery.attempts.iter().map(|a| a.status).collect();
+                assert_eq!(statuses, [Some(500), Some(200)]);
+            }
+            return;
+        }
+        panic!("webhooks were not delivered");
+    })
+    .await;
+
+    srv.stop().await;
+}